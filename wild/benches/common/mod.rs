@@ -0,0 +1,45 @@
+//! Code shared by our benchmarks.
+
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+use std::time::Instant;
+
+const DEFAULT_ITERATIONS: usize = 10;
+
+/// Times linking with `link_args`, which shouldn't include `-o`, using a single thread and using
+/// the default number of threads, then prints the results. The number of links timed for each
+/// thread count can be set via `WILD_BENCH_ITERATIONS`.
+pub fn compare_thread_counts(link_args: &[String]) {
+    let iterations = std::env::var("WILD_BENCH_ITERATIONS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_ITERATIONS);
+    let output_path = std::env::temp_dir().join(format!("wild-bench-{}", std::process::id()));
+    for threads in [Some(1), None] {
+        let mut times = (0..iterations)
+            .map(|_| time_link(link_args, threads, &output_path))
+            .collect::<Vec<_>>();
+        times.sort();
+        let label = threads.map_or_else(|| "default".to_owned(), |t| t.to_string());
+        println!(
+            "threads={label:8} min={:?} median={:?}",
+            times[0],
+            times[times.len() / 2]
+        );
+    }
+    let _ = std::fs::remove_file(&output_path);
+}
+
+fn time_link(link_args: &[String], threads: Option<usize>, output_path: &Path) -> Duration {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_wild"));
+    if let Some(threads) = threads {
+        command.arg(format!("--threads={threads}"));
+    }
+    command.arg("-o").arg(output_path).args(link_args);
+    let start = Instant::now();
+    let status = command.status().expect("Failed to run wild");
+    let elapsed = start.elapsed();
+    assert!(status.success(), "Link failed");
+    elapsed
+}
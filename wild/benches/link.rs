@@ -0,0 +1,22 @@
+//! Times how long it takes to link something with a single thread and with the default number of
+//! threads. Pass the arguments for the link, excluding `-o`, after `--`. e.g.
+//!
+//! cargo bench --bench link -- -static foo.o bar.o
+//!
+//! Cargo runs benchmarks from the package directory, so relative paths are relative to `wild/`.
+//! The number of links timed for each thread count can be set via `WILD_BENCH_ITERATIONS`.
+
+mod common;
+
+fn main() {
+    // Cargo passes `--bench` to benchmarks, which isn't a linker argument.
+    let link_args: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| arg != "--bench")
+        .collect();
+    if link_args.is_empty() {
+        eprintln!("No linker arguments supplied. Usage: cargo bench --bench link -- <ARGS>");
+        return;
+    }
+    common::compare_thread_counts(&link_args);
+}
@@ -0,0 +1,58 @@
+//! Times linking objects that have lots of string-merge sections containing mostly duplicated
+//! strings, so that most of the link time is spent merging strings.
+
+mod common;
+
+use std::fmt::Write as _;
+use std::process::Command;
+
+const NUM_OBJECTS: usize = 64;
+const SECTIONS_PER_OBJECT: usize = 16;
+const STRINGS_PER_SECTION: usize = 1000;
+
+/// The number of distinct strings. Each section contains a different selection of these.
+const NUM_DISTINCT_STRINGS: usize = 5000;
+
+fn main() {
+    let dir = std::env::temp_dir().join(format!("wild-bench-merge-strings-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("Failed to create temporary directory");
+    let mut link_args = vec!["-static".to_owned()];
+    for object_index in 0..NUM_OBJECTS {
+        let mut source = String::new();
+        if object_index == 0 {
+            source.push_str(".text\n.globl _start\n_start:\n    ret\n");
+        }
+        for section_index in 0..SECTIONS_PER_OBJECT {
+            let _ = writeln!(
+                source,
+                ".section .rodata.str1.1.s{section_index}, \"aMS\", @progbits, 1"
+            );
+            let start = (object_index * SECTIONS_PER_OBJECT + section_index) * 7;
+            for string_index in 0..STRINGS_PER_SECTION {
+                let id = (start + string_index) % NUM_DISTINCT_STRINGS;
+                let _ = writeln!(
+                    source,
+                    ".asciz \"a string that appears in many places {id}\""
+                );
+            }
+        }
+        let source_path = dir.join(format!("strings{object_index}.s"));
+        let object_path = dir.join(format!("strings{object_index}.o"));
+        std::fs::write(&source_path, source).expect("Failed to write source");
+        let status = Command::new("cc")
+            .arg("-c")
+            .arg(&source_path)
+            .arg("-o")
+            .arg(&object_path)
+            .status()
+            .expect("Failed to run cc");
+        assert!(
+            status.success(),
+            "Failed to assemble {}",
+            source_path.display()
+        );
+        link_args.push(object_path.to_string_lossy().into_owned());
+    }
+    common::compare_thread_counts(&link_args);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -5,26 +5,198 @@
 //! that are tested by examining the resulting binaries. Directives have the format '//#Directive:
 //! Args'.
 //!
+//! InputType: A comma-separated list of how the files other than the first are passed to the
+//! linker, each of which is a separate variant: `Object`, `Archive`, `ThinArchive`, `Shared`, which
+//! links each into a shared object of its own, or `Relocatable`, which partially links each with
+//! `-r` and checks that the result is a relocatable object.
+//!
 //! ExpectComment: Checks that the the next comment in the .comment section is equal to the supplied
 //! argument. If no ExpectComment directives are given then .comment isn't checked. The argument may
 //! end with '*' which matches anything. The last ExpectComment directive may start with '?' to
 //! indicate that the comment if present should match the rest of the argument, but that it's OK for
 //! it to be absent.
 //!
+//! NoSym: Checks that the output's symbol table doesn't contain the named symbol.
+//!
+//! ExpectLocalSym: Checks that each of the named symbols, separated by spaces, is local in the
+//! output's symbol table. Also checks that all local symbols precede the global ones.
+//!
+//! ExpectGlobalSym: Checks that each of the named symbols isn't local in the output's symbol table.
+//!
+//! ExpectUniqueSym: Checks that each of the named symbols has a single definition in the output's
+//! symbol table, and in its dynamic symbol table if it has one, and that it's bound as
+//! `STB_GNU_UNIQUE`.
+//!
+//! ExpectSymVisibility: Takes a visibility, one of `default`, `internal`, `hidden` or `protected`,
+//! followed by symbol names, e.g. `hidden foo bar`, and checks that each of the named symbols has
+//! that visibility in the output's symbol table.
+//!
+//! ContainsCount: Takes a count and a string and checks that the output file contains the string
+//! exactly that many times.
+//!
+//! ExpectDynSym: Checks that the output defines the named symbol in its dynamic symbol table.
+//!
+//! NoDynSym: Checks that the output doesn't have the named symbol in its dynamic symbol table.
+//!
+//! ExpectSysvHash: Checks that looking up the named symbol via the SysV `.hash` section finds its
+//! definition.
+//!
+//! ExpectGnuHash: Checks that the output's `.gnu.hash` section is well formed, that looking up each
+//! of the named symbols, separated by spaces, via it finds them and that looking up a symbol that
+//! doesn't exist doesn't. No symbols may be named, e.g. when there's nothing to export.
+//!
+//! ExpectSection: Checks that the output has a section with the given name. The name may be
+//! followed by attributes, each of the form `key=value`:
+//! - `compressed`: `true` or `false`, whether the section is marked as compressed.
+//! - `size`: The section's size.
+//! - `address`: The section's address.
+//! - `uncompressed-size`: The size of the section's contents once decompressed. If the section is
+//!   compressed, then it must also be smaller than this.
+//!
+//! NoSection: Checks that the output doesn't have a section with the given name.
+//!
+//! ExpectEhFrame: Checks that each FDE in the output's `.eh_frame` points back at a CIE in it.
+//! Takes attributes, each of the form `key=value`:
+//! - `cies`: The number of CIEs, e.g. to check that identical CIEs were deduplicated.
+//! - `fdes`: The number of FDEs.
+//!
+//! ExpectEhFrameHdr: Checks that the output's `.eh_frame_hdr` has the given number of entries, one
+//! for each FDE in `.eh_frame`, that they use 32 bit offsets relative to `.eh_frame_hdr` and that
+//! they're sorted by function address and point at the FDEs for those functions.
+//!
+//! ExpectError: Checks that the link fails and that what the linker printed contains the argument,
+//! which is interpreted as for OutputContains, but without any addresses. Nothing else is checked
+//! and the output isn't run.
+//!
+//! OutputOnError: Set to `true` together with ExpectError to check that the linker still wrote the
+//! output. The output is then checked like any other, but isn't run.
+//!
+//! OutputContains: Checks that what the linker printed to stdout or stderr contains the argument. A
+//! '*' in the argument matches any characters other than a newline and `\n` matches a newline, so
+//! the argument only spans lines where it says so. `{section-addr:NAME}`
+//! and `{symbol-addr:NAME}` are replaced with the address of the named section or symbol in the
+//! output, formatted as in a link map, e.g. `0x0000000000401000`. `{section-size:NAME}` and
+//! `{file-size}` are replaced with the size in decimal of the named section or of the output file.
+//!
+//! OutputDoesNotContain: Checks that what the linker printed doesn't contain the argument, which is
+//! interpreted as for OutputContains.
+//!
+//! ExpectSegment: Checks that the output has a program header of the given type, e.g. `GNU_RELRO`.
+//! The type may be followed by attributes, each of the form `key=value`:
+//! - `flags`: The segment's flags, e.g. `R` or `RW`.
+//! - `sections`: A comma-separated list of sections. The segment must start where the first of them
+//!   starts and its file contents must end where the last of them with file contents ends. Its
+//!   memory must end where the last of them ends, rounded up to `end-align` if given.
+//! - `end-align`: See `sections`.
+//! - `excludes`: A comma-separated list of sections that mustn't start within the segment's memory.
+//! - `memsz`: The segment's size in memory.
+//! - `align`: The segment's alignment. Its address must also be a multiple of it.
+//!
+//! NoSegment: Checks that the output doesn't have a program header of the given type.
+//!
+//! ExpectDynamic: Checks that the output's dynamic section has an entry with the given tag, e.g.
+//! `FLAGS`. The tag may be followed by a value. For `FLAGS` and `FLAGS_1`, this is a comma-separated
+//! list of flags, e.g. `SYMBOLIC,ORIGIN`, all of which must be set. For tags whose value is a string,
+//! e.g. `NEEDED`, an entry must match the value, where a '*' matches any characters. For tags whose
+//! value is an address, e.g. `INIT`, the value is the name of the section that it must point to.
+//! For tags whose value is a size, e.g. `RELRSZ`, the value is the name of the section whose size
+//! it must be. Otherwise, it's a number that an entry must be equal to.
+//!
+//! ExpectVersionNeed: Takes a file name and a version, e.g. `*libc.so* GLIBC_2.34`, and checks that
+//! the output's `.gnu.version_r` requires the version from the file. The file name may contain '*',
+//! which matches any characters.
+//!
+//! ExpectVersionDef: Takes a version followed by the names of its parents, if any, e.g. `VERS_2.0
+//! VERS_1.0`, and checks that the output's `.gnu.version_d` defines the version with exactly those
+//! parents.
+//!
+//! ExpectSymVersion: Takes a dynamic symbol name and a version, e.g. `foo VERS_1.0`, and checks
+//! that `.gnu.version` gives the symbol that version, which must be either defined in
+//! `.gnu.version_d` or required in `.gnu.version_r`.
+//!
+//! ExpectSpareDynamicTags: Checks that the output's dynamic section ends with its terminating
+//! DT_NULL entry followed by the given number of spare DT_NULL entries, as requested with
+//! `--spare-dynamic-tags`.
+//!
+//! NoDynamic: Checks that the dynamic section doesn't have an entry with the given tag. If a value
+//! is given, then it only checks that no entry matches the value, or for `FLAGS` and `FLAGS_1`,
+//! that none of the flags are set.
+//!
+//! ExpectDynamicReloc: Checks that the output has a dynamic relocation of the given type, e.g.
+//! `R_X86_64_COPY`, against the given symbol. Either may be '*', which matches any type or any
+//! symbol. If the symbol is omitted, then the relocation must not refer to a symbol.
+//!
+//! NoDynamicReloc: Checks that the output doesn't have a dynamic relocation matching the arguments,
+//! which are interpreted as for ExpectDynamicReloc.
+//!
+//! ExpectIrelativeResolvers: Checks that `.rela.plt` contains only IRELATIVE relocations, that
+//! their addends are the addresses of the named resolvers, in any order, and that
+//! `__rela_iplt_start` and `__rela_iplt_end` bracket them, as is needed in static executables.
+//!
+//! ExpectDynamicRelocIn: Checks that the output has a dynamic relocation of any type that applies
+//! to a place within the named section, e.g. `.text` for a text relocation.
+//!
+//! ExpectRelocation: Takes a section name and a symbol name, e.g. `.text get_value`, and checks
+//! that the output's relocations for that section, as written by `--emit-relocs`, include one
+//! against the symbol. All PC-relative relocations against symbols in the section are checked
+//! against what was written to the output.
+//!
+//! SameAddress: Checks that the named symbols, separated by spaces, all have the same address in
+//! the output's symbol table, e.g. because identical code folding merged them.
+//!
+//! DifferentAddress: Checks that no two of the named symbols have the same address.
+//!
+//! ExpectEntry: Checks that the output's entry point is the address of the named symbol.
+//!
+//! SymbolOrder: Checks that each of the named symbols has a lower address than the one after it.
+//!
+//! ExpectPointer: Takes a symbol name and either another symbol name or `0`, e.g. `ptr value`, and
+//! checks that the output file holds the address of the second symbol, or 0, in the 8 bytes at the
+//! first symbol's address.
+//!
+//! ExpectGdbIndex: Checks that the output's `.gdb_index` lists each unit in `.debug_info` and that
+//! looking up the named symbol or type in it the way that GDB does finds it. If the name is also a
+//! symbol in the output, then the unit that it's indexed against must cover the symbol's address.
+//!
+//! NoGdbIndex: Checks that looking up the named symbol in the output's `.gdb_index` doesn't find it.
+//!
+//! ExpectX86Features: Checks that the x86 feature property in the output's PT_GNU_PROPERTY segment
+//! has exactly the named features, which may be `IBT` and `SHSTK`. If no features are named, checks
+//! that there's no such property.
+//!
+//! ExpectAbiTag: Checks that the output's PT_NOTE segment has a single GNU ABI tag note, which
+//! requires the given kernel version, e.g. `4.1.0`.
+//!
+//! SharedLinker: Set to `ld` or `wild` in a file other than the first to link it into its shared
+//! object with that linker, whichever linker we're testing. This can be used to check how the other
+//! linker uses a shared object that we wrote, or when the file needs something that we don't
+//! support yet. Only applies when InputType is `Shared`.
+//!
+//! SharedLinkArgs: Extra arguments to pass to the linker when linking a file other than the first
+//! into its shared object, e.g. a version script. Only applies when InputType is `Shared`.
+//!
+//! RunEnabled: Set to `false` to link the output without running it, e.g. for shared objects.
+//!
+//! SkipLinker: Don't link the test with the named linker (e.g. "ld"). Useful when a test exercises
+//! a flag that the other linker doesn't support.
+//!
 //! TODO: Document the rest of the directives.
 
 use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::Context;
+use object::read::elf::FileHeader as _;
 use object::LittleEndian;
 use object::Object;
 use object::ObjectSection;
 use object::ObjectSymbol;
 use std::collections::HashMap;
+use std::ffi::OsStr;
 use std::fmt::Display;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
+use std::process::Output;
 use wait_timeout::ChildExt;
 
 type Result<T = (), E = anyhow::Error> = core::result::Result<T, E>;
@@ -46,6 +218,7 @@ struct ProgramInputs {
 struct Program<'a> {
     link_output: LinkOutput,
     assertions: &'a Assertions,
+    run_enabled: bool,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -61,6 +234,12 @@ struct ThirdPartyLinker {
     path: &'static str,
 }
 
+const GNU_LD: ThirdPartyLinker = ThirdPartyLinker {
+    name: "ld",
+    gcc_name: "bfd",
+    path: "/usr/bin/ld",
+};
+
 impl Linker {
     fn path(&self) -> &Path {
         match self {
@@ -69,17 +248,41 @@ impl Linker {
         }
     }
 
-    fn link_shared(&self, obj_path: &Path, so_path: &Path) -> Result<LinkerInput> {
+    fn link_shared(
+        &self,
+        obj_path: &Path,
+        so_path: &Path,
+        extra_args: &[String],
+    ) -> Result<LinkerInput> {
+        let mut args = vec!["-shared".to_owned()];
+        args.extend_from_slice(extra_args);
+        self.link_single_input(obj_path, so_path, "shared", args)
+    }
+
+    fn link_relocatable(&self, obj_path: &Path, out_path: &Path) -> Result<LinkerInput> {
+        self.link_single_input(obj_path, out_path, "relocatable", vec!["-r".to_owned()])
+    }
+
+    fn link_single_input(
+        &self,
+        obj_path: &Path,
+        out_path: &Path,
+        name: &str,
+        args: Vec<String>,
+    ) -> Result<LinkerInput> {
         let mut command = LinkCommand::new(
             *self,
             &[LinkerInput::new(obj_path.to_owned())],
-            so_path,
-            &ArgumentSet::default_for_linking(),
+            out_path,
+            &ArgumentSet {
+                name: name.to_owned(),
+                args,
+            },
         );
-        if self.is_wild() || !is_newer(so_path, obj_path) {
+        if self.is_wild() || !is_newer(out_path, obj_path) {
             command.run()?;
         }
-        Ok(LinkerInput::with_command(so_path.to_owned(), command))
+        Ok(LinkerInput::with_command(out_path.to_owned(), command))
     }
 
     fn is_wild(&self) -> bool {
@@ -95,6 +298,9 @@ struct LinkOutput {
     binary: PathBuf,
     command: LinkCommand,
     linker_used: Linker,
+
+    /// What the linker printed to stdout and stderr. Empty if we skipped linking.
+    messages: String,
 }
 
 struct LinkCommand {
@@ -125,32 +331,433 @@ struct TestParameters {
     assertions: Assertions,
     linker_args: Vec<ArgumentSet>,
     compiler_args: Vec<ArgumentSet>,
+    skip_linkers: Vec<String>,
+    run_enabled: bool,
 }
 
 #[derive(Default)]
 struct Assertions {
     expected_symtab_entries: Vec<ExpectedSymtabEntry>,
+    unexpected_symtab_entries: Vec<String>,
+    expected_local_symbols: Vec<String>,
+    expected_unique_symbols: Vec<String>,
+    expected_symbol_visibilities: Vec<(String, u8)>,
+    expected_global_symbols: Vec<String>,
     expected_comments: Vec<String>,
     does_not_contain: Vec<String>,
     contains_strings: Vec<String>,
+    contains_counts: Vec<(usize, String)>,
+    expected_dynsym_entries: Vec<String>,
+    unexpected_dynsym_entries: Vec<String>,
+    expected_sysv_hash_entries: Vec<String>,
+    expected_sections: Vec<ExpectedSection>,
+    unexpected_sections: Vec<String>,
+    output_contains: Vec<String>,
+    output_does_not_contain: Vec<String>,
+    expected_errors: Vec<String>,
+    output_on_error: bool,
+    expected_segments: Vec<ExpectedSegment>,
+    unexpected_segments: Vec<SegmentType>,
+    expected_dynamic_entries: Vec<DynamicEntryPattern>,
+    unexpected_dynamic_entries: Vec<DynamicEntryPattern>,
+    expected_dynamic_relocs: Vec<DynamicRelocPattern>,
+    unexpected_dynamic_relocs: Vec<DynamicRelocPattern>,
+    dynamic_reloc_sections: Vec<String>,
+    expected_irelative_resolvers: Option<Vec<String>>,
+    expected_relocations: Vec<(String, String)>,
+    same_addresses: Vec<Vec<String>>,
+    different_addresses: Vec<Vec<String>>,
+    symbol_orders: Vec<Vec<String>>,
+    expected_pointers: Vec<(String, String)>,
+    expected_gnu_hash_entries: Option<Vec<String>>,
+    expected_entry: Option<String>,
+    expected_gdb_index_names: Vec<String>,
+    unexpected_gdb_index_names: Vec<String>,
+    expected_x86_features: Option<u32>,
+    expected_abi_tag: Option<[u32; 3]>,
+    expected_spare_dynamic_tags: Option<usize>,
+    expected_eh_frame: Option<ExpectedEhFrame>,
+    expected_eh_frame_hdr_entries: Option<usize>,
+    expected_version_needs: Vec<(String, String)>,
+    expected_version_defs: Vec<Vec<String>>,
+    expected_symbol_versions: Vec<(String, String)>,
 }
 
 struct ExpectedSymtabEntry {
     name: String,
     section_name: String,
+    size: Option<u64>,
 }
 
 impl ExpectedSymtabEntry {
     fn parse(s: &str) -> Result<Self> {
         let mut parts = s.split(' ').map(str::to_owned);
-        let (Some(name), Some(section), None) = (parts.next(), parts.next(), parts.next()) else {
-            bail!("ExpectSym requires {{symbol name}}, {{symbol section}}");
+        let (Some(name), Some(section), size, None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            bail!("ExpectSym requires {{symbol name}}, {{symbol section}} [{{symbol size}}]");
         };
         Ok(Self {
             name,
             section_name: section,
+            size: size
+                .map(|size| size.parse())
+                .transpose()
+                .context("Invalid symbol size")?,
+        })
+    }
+}
+
+struct ExpectedSegment {
+    segment_type: SegmentType,
+    flags: Option<u32>,
+    sections: Vec<String>,
+    end_align: u64,
+    excludes: Vec<String>,
+    memsz: Option<u64>,
+    align: Option<u64>,
+}
+
+impl ExpectedSegment {
+    fn parse(s: &str) -> Result<Self> {
+        let mut parts = s.split_whitespace();
+        let segment_type = SegmentType::parse(parts.next().context("Missing segment type")?)?;
+        let mut segment = ExpectedSegment {
+            segment_type,
+            flags: None,
+            sections: Vec::new(),
+            end_align: 1,
+            excludes: Vec::new(),
+            memsz: None,
+            align: None,
+        };
+        for attribute in parts {
+            let (key, value) = attribute
+                .split_once('=')
+                .with_context(|| format!("Expected key=value, got `{attribute}`"))?;
+            let list = || value.split(',').map(str::to_owned).collect();
+            match key {
+                "flags" => segment.flags = Some(parse_segment_flags(value)?),
+                "sections" => segment.sections = list(),
+                "end-align" => segment.end_align = parse_number(value)?,
+                "excludes" => segment.excludes = list(),
+                "memsz" => segment.memsz = Some(parse_number(value)?),
+                "align" => segment.align = Some(parse_number(value)?),
+                other => bail!("Unknown segment attribute `{other}`"),
+            }
+        }
+        Ok(segment)
+    }
+}
+
+struct ExpectedSection {
+    name: String,
+    compressed: Option<bool>,
+    size: Option<u64>,
+    address: Option<u64>,
+    uncompressed_size: Option<u64>,
+}
+
+impl ExpectedSection {
+    fn parse(s: &str) -> Result<Self> {
+        let mut parts = s.split_whitespace();
+        let mut section = ExpectedSection {
+            name: parts.next().context("Missing section name")?.to_owned(),
+            compressed: None,
+            size: None,
+            address: None,
+            uncompressed_size: None,
+        };
+        for attribute in parts {
+            match attribute.split_once('=') {
+                Some(("compressed", value)) => {
+                    section.compressed = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("Invalid compressed value `{value}`"))?,
+                    )
+                }
+                Some(("size", value)) => section.size = Some(parse_number(value)?),
+                Some(("address", value)) => section.address = Some(parse_number(value)?),
+                Some(("uncompressed-size", value)) => {
+                    section.uncompressed_size = Some(parse_number(value)?)
+                }
+                _ => bail!("Unknown section attribute `{attribute}`"),
+            }
+        }
+        Ok(section)
+    }
+}
+
+#[derive(Default)]
+struct ExpectedEhFrame {
+    cies: Option<usize>,
+    fdes: Option<usize>,
+}
+
+impl ExpectedEhFrame {
+    fn parse(s: &str) -> Result<Self> {
+        let mut eh_frame = ExpectedEhFrame::default();
+        for attribute in s.split_whitespace() {
+            match attribute.split_once('=') {
+                Some(("cies", value)) => eh_frame.cies = Some(parse_number(value)? as usize),
+                Some(("fdes", value)) => eh_frame.fdes = Some(parse_number(value)? as usize),
+                _ => bail!("Unknown .eh_frame attribute `{attribute}`"),
+            }
+        }
+        Ok(eh_frame)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct SegmentType {
+    name: &'static str,
+    p_type: u32,
+}
+
+impl SegmentType {
+    fn parse(name: &str) -> Result<Self> {
+        const TYPES: &[(&str, u32)] = &[
+            ("LOAD", object::elf::PT_LOAD),
+            ("DYNAMIC", object::elf::PT_DYNAMIC),
+            ("INTERP", object::elf::PT_INTERP),
+            ("NOTE", object::elf::PT_NOTE),
+            ("PHDR", object::elf::PT_PHDR),
+            ("TLS", object::elf::PT_TLS),
+            ("GNU_EH_FRAME", object::elf::PT_GNU_EH_FRAME),
+            ("GNU_STACK", object::elf::PT_GNU_STACK),
+            ("GNU_RELRO", object::elf::PT_GNU_RELRO),
+            ("GNU_PROPERTY", object::elf::PT_GNU_PROPERTY),
+        ];
+        TYPES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|&(name, p_type)| SegmentType { name, p_type })
+            .with_context(|| format!("Unknown segment type `{name}`"))
+    }
+}
+
+fn parse_segment_flags(flags: &str) -> Result<u32> {
+    flags.chars().try_fold(0, |acc, flag| {
+        Ok(acc
+            | match flag {
+                'R' => object::elf::PF_R,
+                'W' => object::elf::PF_W,
+                'X' => object::elf::PF_X,
+                other => bail!("Unknown segment flag `{other}`"),
+            })
+    })
+}
+
+fn parse_number(s: &str) -> Result<u64> {
+    let parsed = match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.with_context(|| format!("Invalid number `{s}`"))
+}
+
+#[derive(Clone, Copy)]
+enum DynamicValueKind {
+    Flags(&'static [(&'static str, u32)]),
+    String,
+    Address,
+    Size,
+    Number,
+}
+
+const DT_FLAGS_NAMES: &[(&str, u32)] = &[
+    ("ORIGIN", object::elf::DF_ORIGIN),
+    ("SYMBOLIC", object::elf::DF_SYMBOLIC),
+    ("TEXTREL", object::elf::DF_TEXTREL),
+    ("BIND_NOW", object::elf::DF_BIND_NOW),
+    ("STATIC_TLS", object::elf::DF_STATIC_TLS),
+];
+
+const DT_FLAGS_1_NAMES: &[(&str, u32)] = &[
+    ("NOW", object::elf::DF_1_NOW),
+    ("GLOBAL", object::elf::DF_1_GLOBAL),
+    ("NODELETE", object::elf::DF_1_NODELETE),
+    ("INITFIRST", object::elf::DF_1_INITFIRST),
+    ("NOOPEN", object::elf::DF_1_NOOPEN),
+    ("ORIGIN", object::elf::DF_1_ORIGIN),
+    ("INTERPOSE", object::elf::DF_1_INTERPOSE),
+    ("NODEFLIB", object::elf::DF_1_NODEFLIB),
+    ("NODUMP", object::elf::DF_1_NODUMP),
+    ("PIE", object::elf::DF_1_PIE),
+];
+
+/// A tag in the dynamic section, together with how to interpret the value in a directive.
+#[derive(Clone, Copy)]
+struct DynamicTag {
+    name: &'static str,
+    d_tag: u32,
+    kind: DynamicValueKind,
+}
+
+impl DynamicTag {
+    fn parse(name: &str) -> Result<Self> {
+        use object::elf;
+        use DynamicValueKind::*;
+
+        const TAGS: &[(&str, u32, DynamicValueKind)] = &[
+            ("NULL", elf::DT_NULL, Number),
+            ("NEEDED", elf::DT_NEEDED, String),
+            ("SONAME", elf::DT_SONAME, String),
+            ("RPATH", elf::DT_RPATH, String),
+            ("RUNPATH", elf::DT_RUNPATH, String),
+            ("INIT", elf::DT_INIT, Address),
+            ("FINI", elf::DT_FINI, Address),
+            ("SYMBOLIC", elf::DT_SYMBOLIC, Number),
+            ("TEXTREL", elf::DT_TEXTREL, Number),
+            ("FLAGS", elf::DT_FLAGS, Flags(DT_FLAGS_NAMES)),
+            ("FLAGS_1", elf::DT_FLAGS_1, Flags(DT_FLAGS_1_NAMES)),
+            ("VERSYM", elf::DT_VERSYM, Address),
+            ("VERDEF", elf::DT_VERDEF, Address),
+            ("VERDEFNUM", elf::DT_VERDEFNUM, Number),
+            ("VERNEED", elf::DT_VERNEED, Address),
+            ("VERNEEDNUM", elf::DT_VERNEEDNUM, Number),
+            // The version of `object` that we use doesn't define the RELR tags.
+            ("RELRSZ", 35, Size),
+            ("RELR", 36, Address),
+        ];
+        TAGS.iter()
+            .find(|(n, _, _)| *n == name)
+            .map(|&(name, d_tag, kind)| DynamicTag { name, d_tag, kind })
+            .with_context(|| format!("Unknown dynamic tag `{name}`"))
+    }
+}
+
+#[derive(Clone)]
+struct DynamicEntryPattern {
+    tag: DynamicTag,
+    value: Option<String>,
+}
+
+impl DynamicEntryPattern {
+    fn parse(s: &str) -> Result<Self> {
+        let (tag, value) = match s.split_once(' ') {
+            Some((tag, value)) => (tag, Some(value.trim().to_owned())),
+            None => (s, None),
+        };
+        Ok(Self {
+            tag: DynamicTag::parse(tag)?,
+            value,
+        })
+    }
+
+    /// Returns whether the entry with value `d_val` matches this pattern. `Flags` patterns match if
+    /// any of the flags are set, so callers that need all of them check each flag separately.
+    fn matches(&self, d_val: u64, obj: &object::File) -> Result<bool> {
+        let Some(value) = self.value.as_deref() else {
+            return Ok(true);
+        };
+        Ok(match self.tag.kind {
+            DynamicValueKind::Flags(names) => d_val & self.flags(names)? != 0,
+            DynamicValueKind::String => glob_matches(&dynamic_string(obj, d_val)?, value),
+            DynamicValueKind::Address => {
+                let section = obj
+                    .section_by_name(value)
+                    .with_context(|| format!("Missing section `{value}`"))?;
+                d_val == section.address()
+            }
+            DynamicValueKind::Size => {
+                let section = obj
+                    .section_by_name(value)
+                    .with_context(|| format!("Missing section `{value}`"))?;
+                d_val == section.size()
+            }
+            DynamicValueKind::Number => d_val == parse_number(value)?,
+        })
+    }
+
+    fn flags(&self, names: &[(&str, u32)]) -> Result<u64> {
+        self.value
+            .iter()
+            .flat_map(|value| value.split(','))
+            .try_fold(0, |acc, flag| {
+                let (_, bit) = names
+                    .iter()
+                    .find(|(name, _)| *name == flag)
+                    .with_context(|| format!("Unknown {} flag `{flag}`", self.tag.name))?;
+                Ok(acc | u64::from(*bit))
+            })
+    }
+}
+
+impl Display for DynamicEntryPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self.tag.name, f)?;
+        if let Some(value) = self.value.as_deref() {
+            write!(f, " {value}")?;
+        }
+        Ok(())
+    }
+}
+
+struct DynamicRelocPattern {
+    r_type: Option<u32>,
+    symbol: Option<String>,
+}
+
+impl DynamicRelocPattern {
+    fn parse(s: &str) -> Result<Self> {
+        let mut parts = s.split_whitespace();
+        let r_type = match parts.next().context("Missing relocation type")? {
+            "*" => None,
+            name => Some(parse_relocation_type(name)?),
+        };
+        Ok(Self {
+            r_type,
+            symbol: parts.next().map(str::to_owned),
         })
     }
+
+    fn matches(&self, r_type: u32, symbol: Option<&str>) -> bool {
+        self.r_type.is_none_or(|t| t == r_type)
+            && match (self.symbol.as_deref(), symbol) {
+                (None, None) => true,
+                (Some("*"), Some(_)) => true,
+                (Some(expected), Some(actual)) => expected == actual,
+                _ => false,
+            }
+    }
+}
+
+impl Display for DynamicRelocPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.r_type {
+            Some(r_type) => write!(f, "type {r_type}")?,
+            None => write!(f, "any type")?,
+        }
+        match self.symbol.as_deref() {
+            Some("*") => write!(f, " against any symbol"),
+            Some(symbol) => write!(f, " against `{symbol}`"),
+            None => write!(f, " without a symbol"),
+        }
+    }
+}
+
+fn parse_relocation_type(name: &str) -> Result<u32> {
+    use object::elf;
+
+    const TYPES: &[(&str, u32)] = &[
+        ("R_X86_64_64", elf::R_X86_64_64),
+        ("R_X86_64_PC32", elf::R_X86_64_PC32),
+        ("R_X86_64_COPY", elf::R_X86_64_COPY),
+        ("R_X86_64_GLOB_DAT", elf::R_X86_64_GLOB_DAT),
+        ("R_X86_64_JUMP_SLOT", elf::R_X86_64_JUMP_SLOT),
+        ("R_X86_64_RELATIVE", elf::R_X86_64_RELATIVE),
+        ("R_X86_64_DTPMOD64", elf::R_X86_64_DTPMOD64),
+        ("R_X86_64_DTPOFF64", elf::R_X86_64_DTPOFF64),
+        ("R_X86_64_TPOFF64", elf::R_X86_64_TPOFF64),
+        ("R_X86_64_IRELATIVE", elf::R_X86_64_IRELATIVE),
+    ];
+    TYPES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, r_type)| *r_type)
+        .with_context(|| format!("Unknown relocation type `{name}`"))
 }
 
 #[derive(Clone, Debug)]
@@ -166,11 +773,39 @@ struct Variant {
     linker_args: ArgumentSet,
 }
 
+impl Variant {
+    /// The variant used by tests that build and link their inputs themselves rather than being
+    /// driven by directives.
+    fn default_for_standalone_test() -> Self {
+        Variant {
+            input_type: InputType::Object,
+            compilation: CompilationVariant {
+                variant_num: 0,
+                compiler_args: ArgumentSet::default_for_compiling(),
+            },
+            linker_args: ArgumentSet::default_for_linking(),
+        }
+    }
+
+    /// Like `default_for_standalone_test`, but compiles with `args`. `name` distinguishes the
+    /// resulting object files from those built with other arguments.
+    fn standalone_with_compiler_args(name: &str, args: &[&str]) -> Self {
+        let mut variant = Self::default_for_standalone_test();
+        variant.compilation.compiler_args = ArgumentSet {
+            name: name.to_owned(),
+            args: args.iter().map(|arg| (*arg).to_owned()).collect(),
+        };
+        variant
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 enum InputType {
     Object,
     Archive,
+    ThinArchive,
     SharedObject,
+    Relocatable,
 }
 
 impl InputType {
@@ -178,7 +813,9 @@ impl InputType {
         Ok(match arg {
             "Object" => Self::Object,
             "Archive" => Self::Archive,
+            "ThinArchive" => Self::ThinArchive,
             "Shared" => Self::SharedObject,
+            "Relocatable" => Self::Relocatable,
             other => bail!("Unknown LinkKind `{other}`"),
         })
     }
@@ -231,9 +868,51 @@ impl TestParameters {
         let mut linker_args = Vec::new();
         let mut compiler_args = Vec::new();
         let mut expected_symtab_entries = Vec::new();
+        let mut unexpected_symtab_entries = Vec::new();
+        let mut expected_local_symbols = Vec::new();
+        let mut expected_unique_symbols = Vec::new();
+        let mut expected_symbol_visibilities = Vec::new();
+        let mut expected_global_symbols = Vec::new();
         let mut expected_comments = Vec::new();
         let mut does_not_contain = Vec::new();
         let mut contains_strings = Vec::new();
+        let mut contains_counts = Vec::new();
+        let mut expected_dynsym_entries = Vec::new();
+        let mut unexpected_dynsym_entries = Vec::new();
+        let mut expected_sysv_hash_entries = Vec::new();
+        let mut expected_sections = Vec::new();
+        let mut unexpected_sections = Vec::new();
+        let mut output_contains = Vec::new();
+        let mut output_does_not_contain = Vec::new();
+        let mut expected_errors = Vec::new();
+        let mut output_on_error = false;
+        let mut expected_segments = Vec::new();
+        let mut unexpected_segments = Vec::new();
+        let mut expected_dynamic_entries = Vec::new();
+        let mut unexpected_dynamic_entries = Vec::new();
+        let mut expected_dynamic_relocs = Vec::new();
+        let mut unexpected_dynamic_relocs = Vec::new();
+        let mut dynamic_reloc_sections = Vec::new();
+        let mut expected_irelative_resolvers = None;
+        let mut expected_relocations = Vec::new();
+        let mut same_addresses = Vec::new();
+        let mut different_addresses = Vec::new();
+        let mut symbol_orders = Vec::new();
+        let mut expected_pointers = Vec::new();
+        let mut expected_gnu_hash_entries = None;
+        let mut expected_entry = None;
+        let mut expected_gdb_index_names = Vec::new();
+        let mut unexpected_gdb_index_names = Vec::new();
+        let mut expected_x86_features = None;
+        let mut expected_abi_tag = None;
+        let mut expected_spare_dynamic_tags = None;
+        let mut expected_version_needs = Vec::new();
+        let mut expected_version_defs = Vec::new();
+        let mut expected_symbol_versions = Vec::new();
+        let mut expected_eh_frame = None;
+        let mut expected_eh_frame_hdr_entries = None;
+        let mut run_enabled = true;
+        let mut skip_linkers = Vec::new();
         for line in source.lines() {
             if let Some(rest) = line.trim().strip_prefix("//#") {
                 let (directive, arg) = rest.split_once(':').context("Missing arg")?;
@@ -253,9 +932,104 @@ impl TestParameters {
                     "ExpectSym" => {
                         expected_symtab_entries.push(ExpectedSymtabEntry::parse(arg.trim())?)
                     }
+                    "NoSym" => unexpected_symtab_entries.push(arg.to_owned()),
+                    "ExpectLocalSym" => expected_local_symbols.extend(parse_symbol_names(arg)),
+                    "ExpectUniqueSym" => expected_unique_symbols.extend(parse_symbol_names(arg)),
+                    "ExpectSymVisibility" => {
+                        let mut names = parse_symbol_names(arg).into_iter();
+                        let visibility = parse_visibility(&names.next().unwrap_or_default())?;
+                        expected_symbol_visibilities.extend(names.map(|name| (name, visibility)));
+                    }
+                    "ExpectGlobalSym" => expected_global_symbols.extend(parse_symbol_names(arg)),
                     "ExpectComment" => expected_comments.push(arg.trim().to_owned()),
                     "DoesNotContain" => does_not_contain.push(arg.trim().to_owned()),
                     "Contains" => contains_strings.push(arg.trim().to_owned()),
+                    "ContainsCount" => {
+                        let (count, needle) = arg
+                            .split_once(' ')
+                            .context("ContainsCount requires {count} {string}")?;
+                        contains_counts.push((parse_number(count)? as usize, needle.to_owned()));
+                    }
+                    "ExpectDynSym" => expected_dynsym_entries.push(arg.trim().to_owned()),
+                    "NoDynSym" => unexpected_dynsym_entries.push(arg.trim().to_owned()),
+                    "ExpectSysvHash" => expected_sysv_hash_entries.push(arg.to_owned()),
+                    "ExpectGnuHash" => expected_gnu_hash_entries = Some(parse_symbol_names(arg)),
+                    "ExpectSection" => expected_sections.push(ExpectedSection::parse(arg)?),
+                    "NoSection" => unexpected_sections.push(arg.to_owned()),
+                    "ExpectEhFrame" => expected_eh_frame = Some(ExpectedEhFrame::parse(arg)?),
+                    "ExpectEhFrameHdr" => {
+                        expected_eh_frame_hdr_entries = Some(parse_number(arg)? as usize)
+                    }
+                    "OutputContains" => output_contains.push(arg.replace("\\n", "\n")),
+                    "OutputDoesNotContain" => {
+                        output_does_not_contain.push(arg.replace("\\n", "\n"))
+                    }
+                    "ExpectError" => expected_errors.push(arg.replace("\\n", "\n")),
+                    "OutputOnError" => {
+                        output_on_error = arg
+                            .parse()
+                            .with_context(|| format!("Invalid OutputOnError `{arg}`"))?
+                    }
+                    "ExpectSegment" => expected_segments.push(ExpectedSegment::parse(arg)?),
+                    "NoSegment" => unexpected_segments.push(SegmentType::parse(arg)?),
+                    "ExpectDynamic" => {
+                        expected_dynamic_entries.push(DynamicEntryPattern::parse(arg)?)
+                    }
+                    "ExpectVersionNeed" => {
+                        let (file, version) = arg
+                            .split_once(' ')
+                            .with_context(|| format!("Expected file and version, got `{arg}`"))?;
+                        expected_version_needs.push((file.to_owned(), version.trim().to_owned()));
+                    }
+                    "ExpectVersionDef" => expected_version_defs.push(parse_symbol_names(arg)),
+                    "ExpectSymVersion" => {
+                        let (name, version) = arg
+                            .split_once(' ')
+                            .with_context(|| format!("Expected symbol and version, got `{arg}`"))?;
+                        expected_symbol_versions.push((name.to_owned(), version.trim().to_owned()));
+                    }
+                    "ExpectSpareDynamicTags" => {
+                        expected_spare_dynamic_tags = Some(parse_number(arg)? as usize)
+                    }
+                    "NoDynamic" => {
+                        unexpected_dynamic_entries.push(DynamicEntryPattern::parse(arg)?)
+                    }
+                    "ExpectDynamicReloc" => {
+                        expected_dynamic_relocs.push(DynamicRelocPattern::parse(arg)?)
+                    }
+                    "NoDynamicReloc" => {
+                        unexpected_dynamic_relocs.push(DynamicRelocPattern::parse(arg)?)
+                    }
+                    "ExpectIrelativeResolvers" => {
+                        expected_irelative_resolvers = Some(parse_symbol_names(arg))
+                    }
+                    "ExpectDynamicRelocIn" => dynamic_reloc_sections.push(arg.trim().to_owned()),
+                    "ExpectRelocation" => {
+                        let (section, symbol) = arg
+                            .split_once(' ')
+                            .context("ExpectRelocation requires {section name} {symbol name}")?;
+                        expected_relocations.push((section.to_owned(), symbol.trim().to_owned()));
+                    }
+                    "SameAddress" => same_addresses.push(parse_symbol_names(arg)),
+                    "DifferentAddress" => different_addresses.push(parse_symbol_names(arg)),
+                    "SymbolOrder" => symbol_orders.push(parse_symbol_names(arg)),
+                    "ExpectPointer" => {
+                        let (symbol, target) = arg
+                            .split_once(' ')
+                            .context("ExpectPointer requires {symbol name} {target}")?;
+                        expected_pointers.push((symbol.to_owned(), target.trim().to_owned()));
+                    }
+                    "ExpectEntry" => expected_entry = Some(arg.to_owned()),
+                    "ExpectGdbIndex" => expected_gdb_index_names.push(arg.trim().to_owned()),
+                    "NoGdbIndex" => unexpected_gdb_index_names.push(arg.trim().to_owned()),
+                    "ExpectX86Features" => expected_x86_features = Some(parse_x86_features(arg)?),
+                    "ExpectAbiTag" => expected_abi_tag = Some(parse_abi_tag(arg)?),
+                    "RunEnabled" => {
+                        run_enabled = arg
+                            .parse()
+                            .with_context(|| format!("Invalid RunEnabled `{arg}`"))?
+                    }
+                    "SkipLinker" => skip_linkers.push(arg.trim().to_owned()),
                     other => bail!("{}: Unknown directive '{other}'", src_filename.display()),
                 }
             }
@@ -280,12 +1054,54 @@ impl TestParameters {
             variant_nums: variants,
             assertions: Assertions {
                 expected_symtab_entries,
+                unexpected_symtab_entries,
+                expected_local_symbols,
+                expected_unique_symbols,
+                expected_symbol_visibilities,
+                expected_global_symbols,
                 expected_comments,
                 does_not_contain,
                 contains_strings,
+                contains_counts,
+                expected_dynsym_entries,
+                unexpected_dynsym_entries,
+                expected_sysv_hash_entries,
+                expected_sections,
+                unexpected_sections,
+                output_contains,
+                output_does_not_contain,
+                expected_errors,
+                output_on_error,
+                expected_segments,
+                unexpected_segments,
+                expected_dynamic_entries,
+                unexpected_dynamic_entries,
+                expected_dynamic_relocs,
+                unexpected_dynamic_relocs,
+                dynamic_reloc_sections,
+                expected_irelative_resolvers,
+                expected_relocations,
+                same_addresses,
+                different_addresses,
+                symbol_orders,
+                expected_pointers,
+                expected_gnu_hash_entries,
+                expected_entry,
+                expected_gdb_index_names,
+                unexpected_gdb_index_names,
+                expected_x86_features,
+                expected_abi_tag,
+                expected_spare_dynamic_tags,
+                expected_eh_frame,
+                expected_eh_frame_hdr_entries,
+                expected_version_needs,
+                expected_version_defs,
+                expected_symbol_versions,
             },
             linker_args,
             compiler_args,
+            skip_linkers,
+            run_enabled,
         })
     }
 }
@@ -293,6 +1109,8 @@ impl TestParameters {
 #[derive(Default)]
 struct FileOverrides {
     compiler_args: Option<Vec<String>>,
+    shared_linker: Option<Linker>,
+    shared_link_args: Vec<String>,
 }
 
 impl FileOverrides {
@@ -304,10 +1122,15 @@ impl FileOverrides {
             .with_context(|| format!("Failed to read {}", src_filename.display()))?;
 
         let mut compiler_args = None;
+        let mut shared_linker = None;
+        let mut shared_link_args = Vec::new();
         for line in source.lines() {
             if let Some(rest) = line.trim().strip_prefix("//#") {
                 let (directive, arg) = rest.split_once(':').context("Missing arg")?;
                 let arg = arg.trim();
+                // Only OverrideCompArgs, SharedLinker and SharedLinkArgs apply to secondary files.
+                // Other directives belong to the program that the file is the primary source of and
+                // are checked when it is parsed.
                 match directive {
                     "OverrideCompArgs" => {
                         compiler_args = Some(
@@ -315,13 +1138,26 @@ impl FileOverrides {
                                 .filter(|a| !a.is_empty())
                                 .map(str::to_owned)
                                 .collect(),
-                        )
+                        );
                     }
-                    other => bail!("{}: Unknown directive '{other}'", src_filename.display()),
+                    "SharedLinker" => {
+                        shared_linker = Some(match arg {
+                            "ld" => Linker::ThirdParty(GNU_LD),
+                            "wild" => Linker::Wild,
+                            other => bail!("Unsupported SharedLinker `{other}`"),
+                        })
+                    }
+                    "SharedLinkArgs" => shared_link_args
+                        .extend(arg.split(' ').filter(|a| !a.is_empty()).map(str::to_owned)),
+                    _ => {}
                 }
             }
         }
-        Ok(Self { compiler_args })
+        Ok(Self {
+            compiler_args,
+            shared_linker,
+            shared_link_args,
+        })
     }
 }
 
@@ -344,13 +1180,19 @@ impl ProgramInputs {
         &self,
         linker: Linker,
         variant: &Variant,
-        assertions: &'a Assertions,
+        parameters: &'a TestParameters,
     ) -> Result<Program<'a>> {
+        let assertions = &parameters.assertions;
         let inputs = self
             .source_files
             .iter()
             .enumerate()
             .map(|(i, source)| {
+                // Entries starting with '-' are passed to the linker as arguments, in order with the
+                // inputs, e.g. `--start-group`.
+                if source.starts_with('-') {
+                    return Ok(LinkerInput::new(PathBuf::from(source)));
+                }
                 let mut variant_for_file = variant.clone();
                 let placement = if i == 0 {
                     // For the first input file, we always compile as an object, never an archive.
@@ -362,19 +1204,32 @@ impl ProgramInputs {
                 build_linker_input(source, &variant_for_file, placement, linker)
             })
             .collect::<Result<Vec<LinkerInput>>>()?;
-        let link_output = linker.link(self.name, &inputs, variant)?;
+        let link_output = linker.link(self.name, &inputs, variant, assertions)?;
         Ok(Program {
             link_output,
             assertions,
+            run_enabled: parameters.run_enabled,
         })
     }
 }
 
 impl<'a> Program<'a> {
     fn run(&self) -> Result {
+        if self.assertions.expects_link_failure() {
+            self.assertions.verify_expected_errors(&self.link_output)?;
+            if self.assertions.output_on_error {
+                self.assertions
+                    .check_path(&self.link_output.binary, self.link_output.linker_used)
+                    .context("Output binary assertions failed")?;
+            }
+            return Ok(());
+        }
         self.assertions
             .check(&self.link_output)
             .context("Output binary assertions failed")?;
+        if !self.run_enabled {
+            return Ok(());
+        }
         let mut child = Command::new(&self.link_output.binary).spawn()?;
         let status = match child.wait_timeout(std::time::Duration::from_millis(500))? {
             Some(s) => s,
@@ -424,6 +1279,11 @@ impl LinkerInput {
             command: Some(command),
         }
     }
+
+    /// Returns whether this is an argument to pass to the linker rather than a file.
+    fn is_argument(&self) -> bool {
+        self.path.to_str().is_some_and(|path| path.starts_with('-'))
+    }
 }
 
 /// Creates a linker input from a source file. This will be either an object file or an archive.
@@ -443,20 +1303,39 @@ fn build_linker_input(
         InputType::Archive => {
             let archive_path = obj_path.with_extension("a");
             if !is_newer(&archive_path, &obj_path) {
-                make_archive(&archive_path, &obj_path)?;
+                make_archive(&archive_path, &[obj_path.as_path()])?;
+            }
+            Ok(LinkerInput::new(archive_path))
+        }
+        InputType::ThinArchive => {
+            let archive_path = obj_path.with_extension("thin.a");
+            if !is_newer(&archive_path, &obj_path) {
+                make_thin_archive(&archive_path, &obj_path)?;
             }
             Ok(LinkerInput::new(archive_path))
         }
         InputType::Object => Ok(LinkerInput::new(obj_path)),
         InputType::SharedObject => {
+            let overrides = FileOverrides::from_source(&src_path, placement)?;
+            let linker = overrides.shared_linker.unwrap_or(linker);
             let so_path = obj_path.with_extension(format!("{linker}.so"));
-            let out = linker.link_shared(&obj_path, &so_path)?;
+            let out = linker.link_shared(&obj_path, &so_path, &overrides.shared_link_args)?;
             let assertions = Assertions::default();
             assertions
                 .check_path(&out.path, linker)
                 .with_context(|| format!("Assertions failed for `{}`", out.path.display()))?;
             Ok(out)
         }
+        InputType::Relocatable => {
+            let out_path = obj_path.with_extension(format!("{linker}.r.o"));
+            let out = linker.link_relocatable(&obj_path, &out_path)?;
+            let bytes = std::fs::read(&out.path)?;
+            let kind = object::File::parse(bytes.as_slice())?.kind();
+            if kind != object::ObjectKind::Relocatable {
+                bail!("`{}` has kind {kind:?}", out.path.display());
+            }
+            Ok(out)
+        }
     }
 }
 
@@ -552,16 +1431,34 @@ fn is_newer(output_path: &Path, src_path: &Path) -> bool {
 impl Linker {
     /// Links the supplied object files with this configuration and returns the path to the
     /// resulting binary.
-    fn link(self, basename: &str, inputs: &[LinkerInput], variant: &Variant) -> Result<LinkOutput> {
+    fn link(
+        self,
+        basename: &str,
+        inputs: &[LinkerInput],
+        variant: &Variant,
+        assertions: &Assertions,
+    ) -> Result<LinkOutput> {
         let output_path = self.output_path(basename, variant);
         let mut command = LinkCommand::new(self, inputs, &output_path, &variant.linker_args);
-        if !command.can_skip {
-            command.run()?;
+        let mut messages = String::new();
+        if assertions.expects_link_failure() {
+            // Make sure that we don't check an output left over from a previous run.
+            let _ = std::fs::remove_file(&output_path);
+            let (succeeded, output) = command.run_allowing_failure()?;
+            if succeeded {
+                bail!("Linker succeeded when it should have failed. Relink with:\n{command}");
+            }
+            messages = output;
+        } else if !command.can_skip || assertions.checks_linker_messages() {
+            // If we're going to check what the linker prints, then we need to run it even if its
+            // output is up-to-date.
+            messages = command.run()?;
         }
         Ok(LinkOutput {
             binary: output_path,
             command,
             linker_used: self,
+            messages,
         })
     }
 
@@ -574,10 +1471,10 @@ impl Linker {
     }
 }
 
-fn make_archive(archive_path: &Path, path: &Path) -> Result {
+fn make_archive(archive_path: &Path, paths: &[&Path]) -> Result {
     let _ = std::fs::remove_file(archive_path);
     let mut cmd = Command::new("ar");
-    cmd.arg("cr").arg(archive_path).arg(path);
+    cmd.arg("cr").arg(archive_path).args(paths);
     let status = cmd.status()?;
     if !status.success() {
         bail!("Failed to create archive");
@@ -585,6 +1482,30 @@ fn make_archive(archive_path: &Path, path: &Path) -> Result {
     Ok(())
 }
 
+/// Creates a thin archive that refers to `member`, which must be in the same directory as the
+/// archive. The member's path is stored relative to the archive, so linking against the archive only
+/// works if the linker resolves it relative to the archive rather than its working directory.
+fn make_thin_archive(archive_path: &Path, member: &Path) -> Result {
+    let _ = std::fs::remove_file(archive_path);
+    let dir = archive_path
+        .parent()
+        .context("Archive path has no parent")?;
+    let status = Command::new("ar")
+        .current_dir(dir)
+        .arg("crT")
+        .arg(
+            archive_path
+                .file_name()
+                .context("Archive path has no filename")?,
+        )
+        .arg(member.strip_prefix(dir)?)
+        .status()?;
+    if !status.success() {
+        bail!("Failed to create thin archive");
+    }
+    Ok(())
+}
+
 impl LinkCommand {
     fn new(
         linker: Linker,
@@ -598,6 +1519,7 @@ impl LinkCommand {
         let can_skip = linker != Linker::Wild
             && inputs
                 .iter()
+                .filter(|input| !input.is_argument())
                 .all(|input| is_newer(output_path, &input.path));
         let mut command;
         let mut invocation_mode = LinkerInvocationMode::Direct;
@@ -647,7 +1569,11 @@ impl LinkCommand {
                 command.args(&linker_args.args[1..]);
             } else {
                 command = Command::new(linker_path);
-                command.arg("--gc-sections").arg("-static");
+                // GNU ld only garbage collects a relocatable output if told which symbols to keep.
+                if !linker_args.args.iter().any(|arg| arg == "-r") {
+                    command.arg("--gc-sections");
+                }
+                command.arg("-static");
                 command.args(&linker_args.args);
             }
             command.env(wild_lib::args::VALIDATE_ENV, "1");
@@ -668,15 +1594,27 @@ impl LinkCommand {
         }
     }
 
-    fn run(&mut self) -> Result {
-        let status = self
+    /// Runs the linker, returning what it printed to stdout and stderr.
+    fn run(&mut self) -> Result<String> {
+        let (succeeded, messages) = self.run_allowing_failure()?;
+        if !succeeded {
+            bail!("Linker failed:\n{messages}\nRelink with:\n{self}");
+        }
+        Ok(messages)
+    }
+
+    /// Runs the linker, returning whether it succeeded and what it printed to stdout and stderr.
+    fn run_allowing_failure(&mut self) -> Result<(bool, String)> {
+        let output = self
             .command
-            .status()
+            .output()
             .with_context(|| format!("Failed to run command: {:?}", self.command))?;
-        if !status.success() {
-            bail!("Linker failed. Relink with:\n{self}");
-        }
-        Ok(())
+        let messages = format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        Ok((output.status.success(), messages))
     }
 }
 
@@ -690,7 +1628,26 @@ fn get_script(inputs: &[LinkerInput]) -> Option<(PathBuf, &[LinkerInput])> {
 
 impl Assertions {
     fn check(&self, link_output: &LinkOutput) -> Result {
-        self.check_path(&link_output.binary, link_output.linker_used)
+        self.check_path(&link_output.binary, link_output.linker_used)?;
+        self.verify_linker_messages(link_output)
+    }
+
+    fn expects_link_failure(&self) -> bool {
+        !self.expected_errors.is_empty()
+    }
+
+    fn verify_expected_errors(&self, link_output: &LinkOutput) -> Result {
+        let messages = &link_output.messages;
+        for pattern in &self.expected_errors {
+            if !contains_glob(messages, pattern) {
+                bail!("Linker errors don't contain `{pattern}`:\n{messages}");
+            }
+        }
+        Ok(())
+    }
+
+    fn checks_linker_messages(&self) -> bool {
+        !self.output_contains.is_empty() || !self.output_does_not_contain.is_empty()
     }
 
     fn check_path(&self, path: &PathBuf, linker_used: Linker) -> Result {
@@ -700,6 +1657,22 @@ impl Assertions {
         self.verify_symbol_assertions(&obj)?;
         self.verify_comment_section(&obj, linker_used)?;
         self.verify_strings(&bytes)?;
+        self.verify_dynamic_symbols(&obj)?;
+        self.verify_gnu_hash(&obj)?;
+        self.verify_sections(&obj)?;
+        self.verify_eh_frame(&obj)?;
+        self.verify_eh_frame_hdr(&obj)?;
+        self.verify_segments(&bytes, &obj)?;
+        self.verify_dynamic_entries(&obj)?;
+        self.verify_spare_dynamic_tags(&obj)?;
+        self.verify_version_needs(&obj)?;
+        self.verify_version_defs(&obj)?;
+        self.verify_dynamic_relocs(&obj)?;
+        self.verify_relocations(&obj)?;
+        self.verify_irelative_relocs(&obj)?;
+        self.verify_symbol_addresses(&obj)?;
+        self.verify_gdb_index(&obj)?;
+        self.verify_notes(&bytes)?;
         // TODO: Check files other than .so files. Right now, I'm having trouble with symbol base in
         // non-shared objects generated by GNU ld.
         if path.extension().is_some_and(|e| e == "so") {
@@ -729,6 +1702,12 @@ impl Assertions {
                             );
                         }
                     }
+                    if let Some(exp_size) = exp.size {
+                        let size = sym.size();
+                        if size != exp_size {
+                            bail!("Expected symbol `{name}` to have size {exp_size}, but it was {size}");
+                        }
+                    }
                 }
             }
         }
@@ -736,10 +1715,92 @@ impl Assertions {
         if !missing.is_empty() {
             bail!("Missing expected symbol(s): {}", missing.join(", "));
         };
+        for name in &self.unexpected_symtab_entries {
+            if obj.symbols().any(|sym| sym.name() == Ok(name)) {
+                bail!("Symbol `{name}` is present when it shouldn't be");
+            }
+        }
+        if !self.expected_local_symbols.is_empty() {
+            let mut seen_global = false;
+            for sym in obj.symbols() {
+                if sym.is_global() {
+                    seen_global = true;
+                } else if seen_global {
+                    bail!("Local symbol `{}` follows a global symbol", sym.name()?);
+                }
+            }
+        }
+        let expected_bindings = self
+            .expected_local_symbols
+            .iter()
+            .map(|name| (name, true))
+            .chain(
+                self.expected_global_symbols
+                    .iter()
+                    .map(|name| (name, false)),
+            );
+        for (name, expect_local) in expected_bindings {
+            let sym = obj
+                .symbols()
+                .find(|sym| sym.name() == Ok(name))
+                .with_context(|| format!("Missing symbol `{name}`"))?;
+            if sym.is_local() != expect_local {
+                let binding = if expect_local { "local" } else { "global" };
+                bail!("Expected symbol `{name}` to be {binding}");
+            }
+        }
+        for name in &self.expected_unique_symbols {
+            let bindings = |symbols: object::read::SymbolIterator| {
+                symbols
+                    .filter(|sym| sym.name() == Ok(name) && !sym.is_undefined())
+                    .map(|sym| match sym.flags() {
+                        object::SymbolFlags::Elf { st_info, .. } => st_info >> 4,
+                        _ => 0,
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let symtab_bindings = bindings(obj.symbols());
+            if symtab_bindings != [object::elf::STB_GNU_UNIQUE] {
+                bail!(
+                    "Expected a single unique definition of `{name}`, got bindings \
+                     {symtab_bindings:?}"
+                );
+            }
+            if obj.dynamic_symbol_table().is_some() {
+                let dynsym_bindings = bindings(obj.dynamic_symbols());
+                if dynsym_bindings != [object::elf::STB_GNU_UNIQUE] {
+                    bail!(
+                        "Expected a single unique dynamic symbol `{name}`, got bindings \
+                         {dynsym_bindings:?}"
+                    );
+                }
+            }
+        }
+        for (name, expected) in &self.expected_symbol_visibilities {
+            let sym = obj
+                .symbols()
+                .find(|sym| sym.name() == Ok(name))
+                .with_context(|| format!("Missing symbol `{name}`"))?;
+            let object::SymbolFlags::Elf { st_other, .. } = sym.flags() else {
+                bail!("Unexpected symbol flags");
+            };
+            let visibility = st_other & 3;
+            if visibility != *expected {
+                bail!("Expected symbol `{name}` to have visibility {expected}, but it was {visibility}");
+            }
+        }
         Ok(())
     }
 
     fn verify_comment_section(&self, obj: &object::File, linker_used: Linker) -> Result {
+        // Without .comment, we can't tell which linker was used.
+        if self
+            .unexpected_sections
+            .iter()
+            .any(|name| name == ".comment")
+        {
+            return Ok(());
+        }
         if self.expected_comments.is_empty() {
             match linker_used {
                 Linker::Wild => {
@@ -796,6 +1857,834 @@ impl Assertions {
                 bail!("Binary doesn't contain `{needle}` when it should");
             }
         }
+        for (expected, needle) in &self.contains_counts {
+            let count = bytes
+                .windows(needle.len())
+                .filter(|w| *w == needle.as_bytes())
+                .count();
+            if count != *expected {
+                bail!("Binary contains `{needle}` {count} times, expected {expected}");
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_segments(&self, bytes: &[u8], obj: &object::File) -> Result {
+        use object::read::elf::ProgramHeader as _;
+
+        if self.expected_segments.is_empty() && self.unexpected_segments.is_empty() {
+            return Ok(());
+        }
+        let e = LittleEndian;
+        let header = object::elf::FileHeader64::<LittleEndian>::parse(bytes)?;
+        let segments = header.program_headers(e, bytes)?;
+        let find_segment = |segment_type: SegmentType| {
+            segments
+                .iter()
+                .find(|seg| seg.p_type(e) == segment_type.p_type)
+        };
+        for expected in &self.expected_segments {
+            let name = expected.segment_type.name;
+            let segment = find_segment(expected.segment_type)
+                .with_context(|| format!("Missing {name} segment"))?;
+            let (vaddr, filesz, memsz) =
+                (segment.p_vaddr(e), segment.p_filesz(e), segment.p_memsz(e));
+            if let Some(flags) = expected.flags {
+                if segment.p_flags(e) != flags {
+                    bail!(
+                        "{name} has flags {:#x}, expected {flags:#x}",
+                        segment.p_flags(e)
+                    );
+                }
+            }
+            if !expected.sections.is_empty() {
+                let mut start = u64::MAX;
+                let mut file_end = 0;
+                let mut end = 0;
+                for section_name in &expected.sections {
+                    let section = obj
+                        .section_by_name(section_name)
+                        .with_context(|| format!("Missing section `{section_name}`"))?;
+                    let section_end = section.address() + section.size();
+                    start = start.min(section.address());
+                    end = end.max(section_end);
+                    if !matches!(
+                        section.kind(),
+                        object::SectionKind::UninitializedData
+                            | object::SectionKind::UninitializedTls
+                    ) {
+                        file_end = file_end.max(section_end);
+                    }
+                }
+                let expected_range = (start, file_end, end.next_multiple_of(expected.end_align));
+                let actual_range = (vaddr, vaddr + filesz, vaddr + memsz);
+                if actual_range != expected_range {
+                    bail!(
+                        "{name} (start, file end, memory end) is {actual_range:x?}, expected \
+                         {expected_range:x?}"
+                    );
+                }
+            }
+            for section_name in &expected.excludes {
+                if let Some(section) = obj.section_by_name(section_name) {
+                    if (vaddr..vaddr + memsz).contains(&section.address()) {
+                        bail!("Section `{section_name}` is inside {name}");
+                    }
+                }
+            }
+            if let Some(expected_memsz) = expected.memsz {
+                if memsz != expected_memsz {
+                    bail!("{name} has memsz {memsz:#x}, expected {expected_memsz:#x}");
+                }
+            }
+            if let Some(expected_align) = expected.align {
+                let align = segment.p_align(e);
+                if align != expected_align {
+                    bail!("{name} has alignment {align:#x}, expected {expected_align:#x}");
+                }
+                if vaddr % align != 0 {
+                    bail!("{name} starts at unaligned address {vaddr:#x}");
+                }
+            }
+        }
+        for &segment_type in &self.unexpected_segments {
+            if find_segment(segment_type).is_some() {
+                bail!(
+                    "{} segment is present when it shouldn't be",
+                    segment_type.name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_dynamic_entries(&self, obj: &object::File) -> Result {
+        if self.expected_dynamic_entries.is_empty() && self.unexpected_dynamic_entries.is_empty() {
+            return Ok(());
+        }
+        let entries = dynamic_entries(obj)?;
+        let values = |tag: DynamicTag| {
+            entries
+                .iter()
+                .filter(move |(d_tag, _)| *d_tag == u64::from(tag.d_tag))
+                .map(|(_, d_val)| *d_val)
+        };
+        let any_matches = |pattern: &DynamicEntryPattern| -> Result<bool> {
+            for d_val in values(pattern.tag) {
+                if pattern.matches(d_val, obj)? {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        };
+        for expected in &self.expected_dynamic_entries {
+            let found = match expected.tag.kind {
+                DynamicValueKind::Flags(names) => {
+                    let wanted = expected.flags(names)?;
+                    let set = values(expected.tag).fold(0, |acc, d_val| acc | d_val);
+                    values(expected.tag).next().is_some() && set & wanted == wanted
+                }
+                _ => any_matches(expected)?,
+            };
+            if !found {
+                bail!("Missing dynamic entry `{expected}`");
+            }
+        }
+        for unexpected in &self.unexpected_dynamic_entries {
+            if any_matches(unexpected)? {
+                bail!("Dynamic entry `{unexpected}` is present when it shouldn't be");
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_eh_frame(&self, obj: &object::File) -> Result {
+        let Some(expected) = &self.expected_eh_frame else {
+            return Ok(());
+        };
+        let data = obj
+            .section_by_name(".eh_frame")
+            .context("Missing .eh_frame")?
+            .data()?;
+        let entries = eh_frame_entries(data)?;
+        let cies = entries
+            .iter()
+            .filter(|(_, cie)| cie.is_none())
+            .map(|(offset, _)| *offset)
+            .collect::<Vec<_>>();
+        let fde_cies = entries
+            .iter()
+            .filter_map(|(_, cie)| *cie)
+            .collect::<Vec<_>>();
+        if let Some(cie) = fde_cies.iter().find(|cie| !cies.contains(cie)) {
+            bail!("FDE points at 0x{cie:x} in .eh_frame, which isn't a CIE");
+        }
+        if expected.cies.is_some_and(|n| n != cies.len()) {
+            bail!(
+                "Expected {} CIEs in .eh_frame, found {}",
+                expected.cies.unwrap_or_default(),
+                cies.len()
+            );
+        }
+        if expected.fdes.is_some_and(|n| n != fde_cies.len()) {
+            bail!(
+                "Expected {} FDEs in .eh_frame, found {}",
+                expected.fdes.unwrap_or_default(),
+                fde_cies.len()
+            );
+        }
+        Ok(())
+    }
+
+    fn verify_eh_frame_hdr(&self, obj: &object::File) -> Result {
+        let Some(expected_entries) = self.expected_eh_frame_hdr_entries else {
+            return Ok(());
+        };
+        let hdr = obj
+            .section_by_name(".eh_frame_hdr")
+            .context("Missing .eh_frame_hdr")?;
+        let eh_frame = obj
+            .section_by_name(".eh_frame")
+            .context("Missing .eh_frame")?;
+        let hdr_data = hdr.data()?;
+        let eh_frame_data = eh_frame.data()?;
+        // DW_EH_PE_datarel | DW_EH_PE_sdata4
+        if hdr_data.get(3) != Some(&0x3b) {
+            bail!(
+                "Unexpected .eh_frame_hdr table encoding {:x?}",
+                hdr_data.get(3)
+            );
+        }
+        let read_i32 = |data: &[u8], offset: usize| {
+            data.get(offset..offset + 4)
+                .map(|bytes| i64::from(i32::from_le_bytes(bytes.try_into().unwrap())))
+                .context("Read past the end of the section")
+        };
+        let entry_count = read_i32(hdr_data, 8)? as usize;
+        let num_fdes = eh_frame_entries(eh_frame_data)?
+            .iter()
+            .filter(|(_, cie)| cie.is_some())
+            .count();
+        if entry_count != expected_entries || entry_count != num_fdes {
+            bail!(
+                "Expected {expected_entries} .eh_frame_hdr entries, found {entry_count} for \
+                 {num_fdes} FDEs"
+            );
+        }
+        let mut previous_function = 0;
+        for entry in hdr_data[12..].chunks_exact(8).take(entry_count) {
+            let function = hdr.address().wrapping_add_signed(read_i32(entry, 0)?);
+            let fde = hdr.address().wrapping_add_signed(read_i32(entry, 4)?);
+            if function < previous_function {
+                bail!(".eh_frame_hdr isn't sorted by function address");
+            }
+            previous_function = function;
+            let fde_offset = fde
+                .checked_sub(eh_frame.address())
+                .filter(|offset| *offset + 16 <= eh_frame.size())
+                .with_context(|| format!("FDE address 0x{fde:x} isn't in .eh_frame"))?
+                as usize;
+            let pc_begin = (fde + 8).wrapping_add_signed(read_i32(eh_frame_data, fde_offset + 8)?);
+            if read_i32(eh_frame_data, fde_offset + 4)? == 0 || pc_begin != function {
+                bail!("Entry for 0x{function:x} points at 0x{fde:x}, which isn't its FDE");
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_spare_dynamic_tags(&self, obj: &object::File) -> Result {
+        let Some(spare) = self.expected_spare_dynamic_tags else {
+            return Ok(());
+        };
+        let entries = dynamic_entries(obj)?;
+        let trailing_nulls = entries
+            .iter()
+            .rev()
+            .take_while(|(d_tag, _)| *d_tag == u64::from(object::elf::DT_NULL))
+            .count();
+        if trailing_nulls == entries.len() {
+            bail!(".dynamic contains only DT_NULL entries");
+        }
+        if trailing_nulls != spare + 1 {
+            bail!(
+                "Expected {} trailing DT_NULL entries, found {trailing_nulls}",
+                spare + 1
+            );
+        }
+        Ok(())
+    }
+
+    fn verify_version_needs(&self, obj: &object::File) -> Result {
+        if self.expected_version_needs.is_empty() {
+            return Ok(());
+        }
+        let needs = version_needs(obj)?;
+        for (file, version) in &self.expected_version_needs {
+            if !needs
+                .iter()
+                .any(|need| glob_matches(&need.file, file) && need.version == *version)
+            {
+                bail!("Expected a requirement for {version} from {file}, got {needs:?}");
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_version_defs(&self, obj: &object::File) -> Result {
+        if !self.expected_version_defs.is_empty() {
+            let defs = version_defs(obj)?;
+            for expected in &self.expected_version_defs {
+                if !defs.iter().any(|(_, names)| names == expected) {
+                    bail!(
+                        "Expected a definition of {}, got {defs:?}",
+                        expected.join(" ")
+                    );
+                }
+            }
+        }
+        if self.expected_symbol_versions.is_empty() {
+            return Ok(());
+        }
+        // The name of each version index, whether we define the version or require it.
+        let mut version_names = Vec::new();
+        if obj.section_by_name(".gnu.version_d").is_some() {
+            version_names.extend(
+                version_defs(obj)?
+                    .into_iter()
+                    .filter_map(|(index, names)| Some((index, names.into_iter().next()?))),
+            );
+        }
+        if obj.section_by_name(".gnu.version_r").is_some() {
+            version_names.extend(
+                version_needs(obj)?
+                    .into_iter()
+                    .map(|need| (need.index, need.version)),
+            );
+        }
+        let versym = obj
+            .section_by_name(".gnu.version")
+            .context("Missing .gnu.version")?
+            .data()?;
+        for (name, version) in &self.expected_symbol_versions {
+            let sym = obj
+                .dynamic_symbols()
+                .find(|sym| sym.name() == Ok(name))
+                .with_context(|| format!("Missing dynamic symbol `{name}`"))?;
+            let offset = sym.index().0 * 2;
+            let index = versym
+                .get(offset..offset + 2)
+                .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) & !object::elf::VERSYM_HIDDEN)
+                .context(".gnu.version is too short")?;
+            let actual = version_names
+                .iter()
+                .find(|(i, _)| *i == index)
+                .map(|(_, name)| name);
+            if actual != Some(version) {
+                bail!("Expected `{name}` to have version {version}, got {actual:?}");
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_irelative_relocs(&self, obj: &object::File) -> Result {
+        let Some(resolver_names) = &self.expected_irelative_resolvers else {
+            return Ok(());
+        };
+        let rela_plt = obj
+            .section_by_name(".rela.plt")
+            .context("Missing .rela.plt")?;
+        if symbol_address(obj, "__rela_iplt_start")? != rela_plt.address()
+            || symbol_address(obj, "__rela_iplt_end")? != rela_plt.address() + rela_plt.size()
+        {
+            bail!("__rela_iplt_start/__rela_iplt_end don't bracket .rela.plt");
+        }
+        let mut addends = Vec::new();
+        for entry in rela_plt.data()?.chunks_exact(24) {
+            let word = |i: usize| u64::from_le_bytes(entry[i..i + 8].try_into().unwrap());
+            if word(8) as u32 != object::elf::R_X86_64_IRELATIVE {
+                bail!("Unexpected relocation type {} in .rela.plt", word(8) as u32);
+            }
+            addends.push(word(16));
+        }
+        addends.sort();
+        let mut resolvers = resolver_names
+            .iter()
+            .map(|name| symbol_address(obj, name))
+            .collect::<Result<Vec<_>>>()?;
+        resolvers.sort();
+        if addends != resolvers {
+            bail!("IRELATIVE addends {addends:x?} don't match resolvers {resolvers:x?}");
+        }
+        Ok(())
+    }
+
+    fn verify_dynamic_relocs(&self, obj: &object::File) -> Result {
+        use object::ObjectSymbolTable as _;
+
+        if self.expected_dynamic_relocs.is_empty()
+            && self.unexpected_dynamic_relocs.is_empty()
+            && self.dynamic_reloc_sections.is_empty()
+        {
+            return Ok(());
+        }
+        let mut relocs = Vec::new();
+        let mut offsets = Vec::new();
+        for (offset, relocation) in obj.dynamic_relocations().into_iter().flatten() {
+            offsets.push(offset);
+            let object::RelocationFlags::Elf { r_type } = relocation.flags() else {
+                bail!("Unexpected relocation flags");
+            };
+            let symbol = match relocation.target() {
+                object::RelocationTarget::Symbol(index) => Some(
+                    obj.dynamic_symbol_table()
+                        .context("Missing dynamic symbol table")?
+                        .symbol_by_index(index)?
+                        .name()?
+                        .to_owned(),
+                ),
+                _ => None,
+            };
+            relocs.push((r_type, symbol));
+        }
+        let any_matches = |pattern: &DynamicRelocPattern| {
+            relocs
+                .iter()
+                .any(|(r_type, symbol)| pattern.matches(*r_type, symbol.as_deref()))
+        };
+        for expected in &self.expected_dynamic_relocs {
+            if !any_matches(expected) {
+                bail!("Missing dynamic relocation of {expected}");
+            }
+        }
+        for unexpected in &self.unexpected_dynamic_relocs {
+            if any_matches(unexpected) {
+                bail!("Unexpected dynamic relocation of {unexpected}");
+            }
+        }
+        for section_name in &self.dynamic_reloc_sections {
+            let section = obj
+                .section_by_name(section_name)
+                .with_context(|| format!("Missing section `{section_name}`"))?;
+            let range = section.address()..section.address() + section.size();
+            if !offsets.iter().any(|offset| range.contains(offset)) {
+                bail!("No dynamic relocation applies to `{section_name}`");
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_linker_messages(&self, link_output: &LinkOutput) -> Result {
+        if !self.checks_linker_messages() {
+            return Ok(());
+        }
+        let bytes = std::fs::read(&link_output.binary)?;
+        let obj = object::File::parse(bytes.as_slice())?;
+        let messages = &link_output.messages;
+        for pattern in &self.output_contains {
+            let pattern = expand_placeholders(pattern, &bytes, &obj)?;
+            if !contains_glob(messages, &pattern) {
+                bail!("Linker output doesn't contain `{pattern}`:\n{messages}");
+            }
+        }
+        for pattern in &self.output_does_not_contain {
+            let pattern = expand_placeholders(pattern, &bytes, &obj)?;
+            if contains_glob(messages, &pattern) {
+                bail!("Linker output contains `{pattern}` when it shouldn't:\n{messages}");
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_dynamic_symbols(&self, obj: &object::File) -> Result {
+        let defined: Vec<&str> = obj
+            .dynamic_symbols()
+            .filter(|sym| sym.is_definition())
+            .filter_map(|sym| sym.name().ok())
+            .collect();
+        for name in &self.expected_dynsym_entries {
+            if !defined.contains(&name.as_str()) {
+                bail!("Missing expected dynamic symbol `{name}`");
+            }
+        }
+        for name in &self.unexpected_dynsym_entries {
+            if obj.dynamic_symbols().any(|sym| sym.name() == Ok(name)) {
+                bail!("Dynamic symbol `{name}` is present when it shouldn't be");
+            }
+        }
+        for name in &self.expected_sysv_hash_entries {
+            let symbol = sysv_hash_lookup(obj, name)?
+                .with_context(|| format!("Failed to find `{name}` via .hash"))?;
+            if symbol.is_undefined() || symbol.address() == 0 {
+                bail!("`{name}` found via .hash isn't defined");
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_sections(&self, obj: &object::File) -> Result {
+        for expected in &self.expected_sections {
+            let name = &expected.name;
+            let section = obj
+                .section_by_name(name)
+                .with_context(|| format!("Missing section `{name}`"))?;
+            if let Some(compressed) = expected.compressed {
+                let object::SectionFlags::Elf { sh_flags } = section.flags() else {
+                    bail!("Unexpected section flags");
+                };
+                if (sh_flags & u64::from(object::elf::SHF_COMPRESSED) != 0) != compressed {
+                    bail!("Expected section `{name}` to have compressed={compressed}");
+                }
+            }
+            if let Some(size) = expected.size {
+                if section.size() != size {
+                    bail!(
+                        "Section `{name}` has size {:#x}, expected {size:#x}",
+                        section.size()
+                    );
+                }
+            }
+            if let Some(address) = expected.address {
+                if section.address() != address {
+                    bail!(
+                        "Section `{name}` is at {:#x}, expected {address:#x}",
+                        section.address()
+                    );
+                }
+            }
+            if let Some(uncompressed_size) = expected.uncompressed_size {
+                let data = section
+                    .uncompressed_data()
+                    .with_context(|| format!("Failed to decompress section `{name}`"))?;
+                if data.len() as u64 != uncompressed_size {
+                    bail!(
+                        "Section `{name}` decompressed to {:#x} bytes, expected \
+                         {uncompressed_size:#x}",
+                        data.len()
+                    );
+                }
+                if section.compressed_file_range()?.format != object::CompressionFormat::None
+                    && section.size() >= uncompressed_size
+                {
+                    bail!(
+                        "Section `{name}` is {:#x} bytes compressed, which is no smaller than \
+                         uncompressed",
+                        section.size()
+                    );
+                }
+            }
+        }
+        for name in &self.unexpected_sections {
+            if obj.section_by_name(name).is_some() {
+                bail!("Section `{name}` is present when it shouldn't be");
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_relocations(&self, obj: &object::File) -> Result {
+        let mut section_names = self
+            .expected_relocations
+            .iter()
+            .map(|(section, _)| section.as_str())
+            .collect::<Vec<_>>();
+        section_names.dedup();
+        for section_name in section_names {
+            let found = checked_relocation_symbols(obj, section_name)?;
+            for (_, name) in self
+                .expected_relocations
+                .iter()
+                .filter(|(section, _)| section == section_name)
+            {
+                if !found.contains(name) {
+                    bail!(
+                        "Missing relocation in `{section_name}` against `{name}`. Found \
+                         relocations against {found:?}"
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_symbol_addresses(&self, obj: &object::File) -> Result {
+        for names in &self.same_addresses {
+            let addresses = names
+                .iter()
+                .map(|name| symbol_address(obj, name))
+                .collect::<Result<Vec<_>>>()?;
+            if addresses.iter().any(|a| *a != addresses[0]) {
+                bail!("Expected {names:?} to have the same address, got {addresses:x?}");
+            }
+        }
+        for names in &self.different_addresses {
+            let mut seen = HashMap::new();
+            for name in names {
+                let address = symbol_address(obj, name)?;
+                if let Some(other) = seen.insert(address, name) {
+                    bail!("`{other}` and `{name}` both have address {address:#x}");
+                }
+            }
+        }
+        for names in &self.symbol_orders {
+            let addresses = names
+                .iter()
+                .map(|name| symbol_address(obj, name))
+                .collect::<Result<Vec<_>>>()?;
+            if addresses.windows(2).any(|pair| pair[0] >= pair[1]) {
+                bail!("Expected {names:?} to be in order, got addresses {addresses:x?}");
+            }
+        }
+        for (symbol, target) in &self.expected_pointers {
+            let expected = if target == "0" {
+                0
+            } else {
+                symbol_address(obj, target)?
+            };
+            let address = symbol_address(obj, symbol)?;
+            let section = obj
+                .sections()
+                .find(|sec| (sec.address()..sec.address() + sec.size()).contains(&address))
+                .with_context(|| format!("`{symbol}` isn't in a section"))?;
+            let offset = (address - section.address()) as usize;
+            let value = section
+                .data()?
+                .get(offset..offset + 8)
+                .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+                .with_context(|| format!("`{symbol}` doesn't have file contents"))?;
+            if value != expected {
+                bail!("`{symbol}` holds {value:#x}, expected `{target}` ({expected:#x})");
+            }
+        }
+        if let Some(name) = &self.expected_entry {
+            let address = symbol_address(obj, name)?;
+            if obj.entry() != address {
+                bail!(
+                    "Entry point is {:#x}, expected `{name}` at {address:#x}",
+                    obj.entry()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_gdb_index(&self, obj: &object::File) -> Result {
+        if self.expected_gdb_index_names.is_empty() && self.unexpected_gdb_index_names.is_empty() {
+            return Ok(());
+        }
+        let section_data = |name: &str| -> Result<&[u8]> {
+            Ok(obj
+                .section_by_name(name)
+                .with_context(|| format!("Missing {name}"))?
+                .data()?)
+        };
+        let index = section_data(".gdb_index")?;
+        let debug_info = section_data(".debug_info")?;
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(index[offset..offset + 4].try_into().unwrap());
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(index[offset..offset + 8].try_into().unwrap());
+
+        let [version, cu_list, types_cu_list, address_area, symbol_table, constant_pool] =
+            [0, 4, 8, 12, 16, 20].map(|offset| u32_at(offset) as usize);
+        if version != 8 || cu_list != 24 {
+            bail!("Unexpected .gdb_index header: version {version}, CU list at {cu_list}");
+        }
+        if types_cu_list != address_area
+            || address_area > symbol_table
+            || symbol_table > constant_pool
+            || constant_pool > index.len()
+        {
+            bail!("Inconsistent .gdb_index header");
+        }
+
+        // Each unit in the output .debug_info should be listed, in order.
+        let mut units = Vec::new();
+        let mut offset = 0;
+        while offset < debug_info.len() {
+            let length =
+                u32::from_le_bytes(debug_info[offset..offset + 4].try_into().unwrap()) as usize + 4;
+            units.push((offset as u64, length as u64));
+            offset += length;
+        }
+        let listed_units = (cu_list..types_cu_list)
+            .step_by(16)
+            .map(|entry| (u64_at(entry), u64_at(entry + 8)))
+            .collect::<Vec<_>>();
+        if listed_units != units {
+            bail!("Expected CUs {units:x?}, got {listed_units:x?}");
+        }
+
+        // Look up names the same way that GDB does.
+        let num_slots = (constant_pool - symbol_table) / 8;
+        let lookup = |name: &str| -> Option<Vec<u32>> {
+            let hash = name.bytes().fold(0u32, |hash, c| {
+                hash.wrapping_mul(67)
+                    .wrapping_add(u32::from(c.to_ascii_lowercase()))
+                    .wrapping_sub(113)
+            });
+            let mask = num_slots as u32 - 1;
+            let step = (hash.wrapping_mul(17) & mask) | 1;
+            let mut slot = hash & mask;
+            loop {
+                let entry = symbol_table + slot as usize * 8;
+                let (name_offset, vector_offset) = (u32_at(entry), u32_at(entry + 4));
+                if (name_offset, vector_offset) == (0, 0) {
+                    return None;
+                }
+                let name_start = constant_pool + name_offset as usize;
+                let name_end = name_start + name.len();
+                if index.get(name_start..name_end) == Some(name.as_bytes()) && index[name_end] == 0
+                {
+                    let vector = constant_pool + vector_offset as usize;
+                    return Some(
+                        (0..u32_at(vector))
+                            .map(|i| u32_at(vector + 4 + i as usize * 4))
+                            .collect(),
+                    );
+                }
+                slot = (slot + step) & mask;
+            }
+        };
+        for name in &self.expected_gdb_index_names {
+            let cus = lookup(name)
+                .with_context(|| format!("{name} missing from .gdb_index"))?
+                .iter()
+                .map(|entry| entry & 0xff_ffff)
+                .collect::<Vec<_>>();
+            let Ok(address) = symbol_address(obj, name) else {
+                continue;
+            };
+            // The symbol should be covered by an address range of the CU that defines it.
+            let unit = (address_area..symbol_table)
+                .step_by(20)
+                .find(|entry| (u64_at(*entry)..u64_at(entry + 8)).contains(&address))
+                .map(|entry| u32_at(entry + 16))
+                .with_context(|| format!("No address range covers {name}"))?;
+            if cus != [unit] {
+                bail!("{name} is indexed against CUs {cus:?}, expected {unit}");
+            }
+        }
+        for name in &self.unexpected_gdb_index_names {
+            if lookup(name).is_some() {
+                bail!("Found unexpected symbol {name} in .gdb_index");
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_notes(&self, bytes: &[u8]) -> Result {
+        let e = LittleEndian;
+        if let Some(expected_features) = self.expected_x86_features {
+            let mut features = 0;
+            for note in elf_notes(bytes, object::elf::PT_GNU_PROPERTY)? {
+                let Some(mut properties) = note.gnu_properties(e) else {
+                    continue;
+                };
+                while let Some(property) = properties.next()? {
+                    if property.pr_type() == object::elf::GNU_PROPERTY_X86_FEATURE_1_AND {
+                        features = property.data_u32(e)?;
+                    }
+                }
+            }
+            if features != expected_features {
+                bail!("Expected x86 features {expected_features:#x}, got {features:#x}");
+            }
+        }
+        if let Some(expected_tag) = self.expected_abi_tag {
+            let mut tags = Vec::new();
+            for note in elf_notes(bytes, object::elf::PT_NOTE)? {
+                if note.name() == b"GNU" && note.n_type(e) == object::elf::NT_GNU_ABI_TAG {
+                    let (desc, _) =
+                        object::slice_from_bytes::<object::U32<LittleEndian>>(note.desc(), 4)
+                            .map_err(|()| anyhow!("Invalid ABI tag note"))?;
+                    tags.push([desc[1].get(e), desc[2].get(e), desc[3].get(e)]);
+                }
+            }
+            if tags != [expected_tag] {
+                bail!("Expected a single ABI tag requiring {expected_tag:?}, got {tags:?}");
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_gnu_hash(&self, obj: &object::File) -> Result {
+        use object::ObjectSymbolTable as _;
+
+        let Some(expected) = &self.expected_gnu_hash_entries else {
+            return Ok(());
+        };
+        let data = obj
+            .section_by_name(".gnu.hash")
+            .context("Missing .gnu.hash")?
+            .data()?;
+        let num_dynsyms = obj
+            .dynamic_symbol_table()
+            .context("Missing dynamic symbol table")?
+            .symbols()
+            .count();
+        let word = |index: usize| -> Result<u32> {
+            let bytes = data
+                .get(index * 4..index * 4 + 4)
+                .context(".gnu.hash is truncated")?;
+            Ok(u32::from_le_bytes(bytes.try_into()?))
+        };
+        let (bucket_count, symbol_base, bloom_count, bloom_shift) = (
+            word(0)? as usize,
+            word(1)? as usize,
+            word(2)? as usize,
+            word(3)?,
+        );
+        if bucket_count == 0 || !bloom_count.is_power_of_two() {
+            bail!("Invalid .gnu.hash header: {bucket_count} buckets, {bloom_count} bloom words");
+        }
+        if symbol_base > num_dynsyms {
+            bail!("symoffset {symbol_base} is beyond the {num_dynsyms} dynamic symbols");
+        }
+        let buckets_start = 4 + bloom_count * 2;
+        let chains_start = buckets_start + bucket_count;
+        if data.len() != (chains_start + num_dynsyms - symbol_base) * 4 {
+            bail!("Unexpected .gnu.hash size {}", data.len());
+        }
+        for bucket in 0..bucket_count {
+            let index = word(buckets_start + bucket)? as usize;
+            if index != 0 && !(symbol_base..num_dynsyms).contains(&index) {
+                bail!("Bucket {bucket} refers to out-of-range symbol {index}");
+            }
+        }
+        let dynsym = obj.dynamic_symbol_table().unwrap();
+        let lookup = |name: &str| -> Result<bool> {
+            let hash = object::elf::gnu_hash(name.as_bytes());
+            let bloom_index = 4 + 2 * ((hash / 64) as usize & (bloom_count - 1));
+            let bloom = u64::from(word(bloom_index)?) | u64::from(word(bloom_index + 1)?) << 32;
+            let mask = 1 << (hash % 64) | 1 << ((hash >> bloom_shift) % 64);
+            if bloom & mask != mask {
+                return Ok(false);
+            }
+            let mut index = word(buckets_start + hash as usize % bucket_count)? as usize;
+            if index == 0 {
+                return Ok(false);
+            }
+            loop {
+                let chain = word(chains_start + index - symbol_base)?;
+                if chain | 1 == hash | 1
+                    && dynsym.symbol_by_index(object::SymbolIndex(index))?.name()? == name
+                {
+                    return Ok(true);
+                }
+                if chain & 1 != 0 {
+                    return Ok(false);
+                }
+                index += 1;
+            }
+        };
+        for name in expected {
+            if !lookup(name)? {
+                bail!("Failed to find `{name}` via .gnu.hash");
+            }
+        }
+        if lookup("not_a_symbol")? {
+            bail!("Found a symbol that doesn't exist via .gnu.hash");
+        }
         Ok(())
     }
 
@@ -906,6 +2795,177 @@ fn lookup_symbol(
     }
 }
 
+/// Returns whether all of `text` matches `pattern`, where a '*' in `pattern` matches any characters.
+fn glob_matches(text: &str, pattern: &str) -> bool {
+    let Some((first, rest)) = pattern.split_once('*') else {
+        return text == pattern;
+    };
+    let Some(mut text) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = rest.split('*').collect::<Vec<_>>();
+    let last = parts.pop().unwrap_or_default();
+    for part in parts {
+        let Some(offset) = text.find(part) else {
+            return false;
+        };
+        text = &text[offset + part.len()..];
+    }
+    text.ends_with(last)
+}
+
+/// Returns whether `text` contains `pattern`, where a '*' in `pattern` matches any characters other
+/// than a newline.
+fn contains_glob(text: &str, pattern: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let rest = parts.collect::<Vec<_>>();
+    text.match_indices(first)
+        .any(|(offset, _)| starts_with_glob_parts(&text[offset + first.len()..], &rest))
+}
+
+/// Returns whether `text` starts with each of `parts` in turn, with any characters other than a
+/// newline between them.
+fn starts_with_glob_parts(text: &str, parts: &[&str]) -> bool {
+    let Some((part, rest)) = parts.split_first() else {
+        return true;
+    };
+    let line_end = text.find('\n').unwrap_or(text.len());
+    text.match_indices(part)
+        .take_while(|(offset, _)| *offset <= line_end)
+        .any(|(offset, _)| starts_with_glob_parts(&text[offset + part.len()..], rest))
+}
+
+/// Replaces each placeholder in `pattern` with the corresponding value from `obj`, whose bytes are
+/// `bytes`. See OutputContains for the placeholders that are supported.
+fn expand_placeholders(pattern: &str, bytes: &[u8], obj: &object::File) -> Result<String> {
+    let mut expanded = String::new();
+    let mut rest = pattern;
+    while let Some(start) = [
+        "{section-addr:",
+        "{symbol-addr:",
+        "{section-size:",
+        "{file-size}",
+    ]
+    .iter()
+    .filter_map(|prefix| rest.find(prefix))
+    .min()
+    {
+        expanded.push_str(&rest[..start]);
+        let (placeholder, after) = rest[start + 1..]
+            .split_once('}')
+            .with_context(|| format!("Unterminated placeholder in `{pattern}`"))?;
+        let section = |name| {
+            obj.section_by_name(name)
+                .with_context(|| format!("Missing section `{name}`"))
+        };
+        let value = match placeholder.split_once(':') {
+            Some(("section-addr", name)) => format!("0x{:016x}", section(name)?.address()),
+            Some(("symbol-addr", name)) => format!("0x{:016x}", symbol_address(obj, name)?),
+            Some(("section-size", name)) => section(name)?.size().to_string(),
+            _ => bytes.len().to_string(),
+        };
+        expanded.push_str(&value);
+        rest = after;
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Returns the names of the symbols that the relocations for `section_name` in `obj` refer to,
+/// checking that each PC-relative relocation against a symbol gives the value in the output.
+fn checked_relocation_symbols(obj: &object::File, section_name: &str) -> Result<Vec<String>> {
+    let section = obj
+        .section_by_name(section_name)
+        .with_context(|| format!("Missing `{section_name}`"))?;
+    let data = section.data()?;
+    let rela_name = format!(".rela{section_name}");
+    let rela = obj
+        .section_by_name(&rela_name)
+        .with_context(|| format!("Missing `{rela_name}`"))?;
+    let mut found = Vec::new();
+    for entry in rela.data()?.chunks_exact(24) {
+        let r_offset = u64::from_le_bytes(entry[..8].try_into().unwrap());
+        let r_info = u64::from_le_bytes(entry[8..16].try_into().unwrap());
+        let r_addend = i64::from_le_bytes(entry[16..].try_into().unwrap());
+        let r_type = r_info as u32;
+        let symbol_index = (r_info >> 32) as usize;
+        if !(section.address()..section.address() + section.size()).contains(&r_offset) {
+            bail!("Relocation offset {r_offset:#x} is outside `{section_name}`");
+        }
+        if symbol_index == 0 {
+            continue;
+        }
+        let symbol = obj.symbol_by_index(object::SymbolIndex(symbol_index))?;
+        let name = symbol.name()?;
+        if matches!(
+            r_type,
+            object::elf::R_X86_64_PC32 | object::elf::R_X86_64_PLT32
+        ) {
+            let offset = (r_offset - section.address()) as usize;
+            let actual = i32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+            let expected = symbol
+                .address()
+                .wrapping_add(r_addend as u64)
+                .wrapping_sub(r_offset) as i32;
+            if actual != expected {
+                bail!(
+                    "Relocation against `{name}` at {r_offset:#x} should give {expected:#x}, \
+                     but output contains {actual:#x}"
+                );
+            }
+        }
+        found.push(name.to_owned());
+    }
+    Ok(found)
+}
+
+/// Returns the address of the symbol called `name` in the symbol table of `obj`.
+fn symbol_address(obj: &object::File, name: &str) -> Result<u64> {
+    Ok(obj
+        .symbols()
+        .find(|sym| sym.name() == Ok(name))
+        .with_context(|| format!("Missing symbol `{name}`"))?
+        .address())
+}
+
+fn parse_x86_features(arg: &str) -> Result<u32> {
+    arg.split_whitespace().try_fold(0, |features, name| {
+        Ok(features
+            | match name {
+                "IBT" => object::elf::GNU_PROPERTY_X86_FEATURE_1_IBT,
+                "SHSTK" => object::elf::GNU_PROPERTY_X86_FEATURE_1_SHSTK,
+                other => bail!("Unknown x86 feature `{other}`"),
+            })
+    })
+}
+
+fn parse_abi_tag(arg: &str) -> Result<[u32; 3]> {
+    let parts = arg
+        .trim()
+        .split('.')
+        .map(str::parse)
+        .collect::<Result<Vec<u32>, _>>()
+        .with_context(|| format!("Invalid ABI tag `{arg}`"))?;
+    parts
+        .try_into()
+        .map_err(|_| anyhow!("ABI tag `{arg}` should have the form `major.minor.patch`"))
+}
+
+fn parse_symbol_names(arg: &str) -> Vec<String> {
+    arg.split_whitespace().map(str::to_owned).collect()
+}
+
+fn parse_visibility(name: &str) -> Result<u8> {
+    Ok(match name {
+        "default" => object::elf::STV_DEFAULT,
+        "internal" => object::elf::STV_INTERNAL,
+        "hidden" => object::elf::STV_HIDDEN,
+        "protected" => object::elf::STV_PROTECTED,
+        other => bail!("Unknown symbol visibility `{other}`"),
+    })
+}
+
 /// Returns whether the supplied object indicates that it was linked with wild.
 fn was_linked_with_wild(obj: &object::File<'_>) -> bool {
     let Ok(actual_comments) = read_comments(obj) else {
@@ -928,6 +2988,146 @@ fn read_comments<'data>(obj: &object::File<'data>) -> Result<Vec<std::borrow::Co
         .collect())
 }
 
+/// Returns the offset of each entry in `.eh_frame` together with, for FDEs, the offset of the CIE
+/// that it points at.
+fn eh_frame_entries(data: &[u8]) -> Result<Vec<(usize, Option<usize>)>> {
+    let read_u32 = |offset: usize| {
+        data.get(offset..offset + 4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+            .context(".eh_frame entry is truncated")
+    };
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while offset + 8 <= data.len() {
+        let length = read_u32(offset)?;
+        if length == 0 {
+            break;
+        }
+        let cie = match read_u32(offset + 4)? {
+            0 => None,
+            cie_pointer => Some(
+                (offset + 4)
+                    .checked_sub(cie_pointer)
+                    .context("FDE points before the start of .eh_frame")?,
+            ),
+        };
+        entries.push((offset, cie));
+        offset += 4 + length;
+    }
+    Ok(entries)
+}
+
+fn dynamic_entries(obj: &object::File) -> Result<Vec<(u64, u64)>> {
+    let dynamic = obj
+        .section_by_name(".dynamic")
+        .context("Missing .dynamic")?
+        .data()?;
+    Ok(dynamic
+        .chunks_exact(16)
+        .map(|entry| {
+            let tag = u64::from_le_bytes(entry[..8].try_into().unwrap());
+            let value = u64::from_le_bytes(entry[8..].try_into().unwrap());
+            (tag, value)
+        })
+        .collect())
+}
+
+/// Returns the file and version name of each version requirement in `.gnu.version_r`.
+#[derive(Debug)]
+struct VersionNeed {
+    file: String,
+    version: String,
+    /// The index that `.gnu.version` uses to refer to the version.
+    index: u16,
+}
+
+fn version_needs(obj: &object::File) -> Result<Vec<VersionNeed>> {
+    let data = obj
+        .section_by_name(".gnu.version_r")
+        .context("Missing .gnu.version_r")?
+        .data()?;
+    let read = |offset: usize, len: usize| -> Result<usize> {
+        let bytes = data
+            .get(offset..offset + len)
+            .context(".gnu.version_r is too short")?;
+        Ok(bytes
+            .iter()
+            .rev()
+            .fold(0, |acc, &byte| acc << 8 | usize::from(byte)))
+    };
+    let read_u32 = |offset: usize| read(offset, 4);
+    let mut needs = Vec::new();
+    let mut verneed = 0;
+    loop {
+        // Elf64_Verneed: vn_version, vn_cnt, vn_file, vn_aux, vn_next.
+        let file = dynamic_string(obj, read_u32(verneed + 4)? as u64)?;
+        let mut vernaux = verneed + read_u32(verneed + 8)?;
+        for _ in 0..read(verneed + 2, 2)? {
+            // Elf64_Vernaux: vna_hash, vna_flags, vna_other, vna_name, vna_next.
+            needs.push(VersionNeed {
+                file: file.clone(),
+                version: dynamic_string(obj, read_u32(vernaux + 8)? as u64)?,
+                index: read(vernaux + 6, 2)? as u16,
+            });
+            vernaux += read_u32(vernaux + 12)?;
+        }
+        match read_u32(verneed + 12)? {
+            0 => break,
+            next => verneed += next,
+        }
+    }
+    Ok(needs)
+}
+
+/// Returns the index of each version that `.gnu.version_d` defines, together with its name and
+/// those of its parents.
+fn version_defs(obj: &object::File) -> Result<Vec<(u16, Vec<String>)>> {
+    let data = obj
+        .section_by_name(".gnu.version_d")
+        .context("Missing .gnu.version_d")?
+        .data()?;
+    let read = |offset: usize, len: usize| -> Result<usize> {
+        let bytes = data
+            .get(offset..offset + len)
+            .context(".gnu.version_d is too short")?;
+        Ok(bytes
+            .iter()
+            .rev()
+            .fold(0, |acc, &byte| acc << 8 | usize::from(byte)))
+    };
+    let read_u32 = |offset: usize| read(offset, 4);
+    let mut defs = Vec::new();
+    let mut verdef = 0;
+    loop {
+        // Elf64_Verdef: vd_version, vd_flags, vd_ndx, vd_cnt, vd_hash, vd_aux, vd_next.
+        let mut names = Vec::new();
+        let mut verdaux = verdef + read_u32(verdef + 12)?;
+        for _ in 0..read(verdef + 6, 2)? {
+            // Elf64_Verdaux: vda_name, vda_next.
+            names.push(dynamic_string(obj, read_u32(verdaux)? as u64)?);
+            verdaux += read_u32(verdaux + 4)?;
+        }
+        defs.push((read(verdef + 4, 2)? as u16, names));
+        match read_u32(verdef + 16)? {
+            0 => break,
+            next => verdef += next,
+        }
+    }
+    Ok(defs)
+}
+
+fn dynamic_string(obj: &object::File, offset: u64) -> Result<String> {
+    let dynstr = obj
+        .section_by_name(".dynstr")
+        .context("Missing .dynstr")?
+        .data()?;
+    let string = dynstr
+        .get(offset as usize..)
+        .and_then(|rest| rest.split(|b| *b == 0).next())
+        .with_context(|| format!("Invalid .dynstr offset {offset:#x}"))?;
+    Ok(String::from_utf8_lossy(string).into_owned())
+}
+
 impl Display for LinkCommand {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for sub in &self.input_commands {
@@ -1000,7 +3200,9 @@ impl Display for InputType {
         match self {
             InputType::Object => write!(f, "object"),
             InputType::Archive => write!(f, "archive"),
+            InputType::ThinArchive => write!(f, "thin-archive"),
             InputType::SharedObject => write!(f, "shared"),
+            InputType::Relocatable => write!(f, "relocatable"),
         }
     }
 }
@@ -1087,7 +3289,67 @@ fn integration_test() -> Result {
         ProgramInputs::new("init_test", &["init_test.c", "init.c", "exit.c"])?,
         ProgramInputs::new("ifunc", &["ifunc.c", "ifunc1.c", "ifunc_init.c", "exit.c"])?,
         ProgramInputs::new("internal-syms", &["internal-syms.c", "exit.c"])?,
+        ProgramInputs::new("defsym", &["defsym.c", "defsym1.c", "exit.c"])?,
+        ProgramInputs::new("defsym_expr", &["defsym_expr.c", "exit.c"])?,
+        ProgramInputs::new("check_sections", &["check_sections.c", "exit.c"])?,
+        ProgramInputs::new(
+            "check_sections_overlap",
+            &["check_sections_overlap.c", "exit.c"],
+        )?,
+        ProgramInputs::new(
+            "check_sections_disabled",
+            &["check_sections_disabled.c", "exit.c"],
+        )?,
+        ProgramInputs::new("start_stop", &["start_stop.c", "exit.c"])?,
+        ProgramInputs::new("start_stop_hidden", &["start_stop_hidden.c", "exit.c"])?,
+        ProgramInputs::new("start_stop_default", &["start_stop_default.c", "exit.c"])?,
+        ProgramInputs::new("merge_rules", &["merge_rules.c", "exit.c"])?,
+        ProgramInputs::new(
+            "init_fini",
+            &[
+                "init_fini_crti.s",
+                "init_fini.c",
+                "exit.c",
+                "init_fini_crtn.s",
+            ],
+        )?,
+        ProgramInputs::new(
+            "init_fini_shared",
+            &[
+                "init_fini_shared.c",
+                "init_fini_crti.s",
+                "init_fini.c",
+                "exit.c",
+                "init_fini_crtn.s",
+            ],
+        )?,
+        ProgramInputs::new("init_fini_empty", &["init_fini_empty.c"])?,
+        ProgramInputs::new("sort_common", &["sort_common.c", "exit.c"])?,
+        ProgramInputs::new(
+            "sort_common_ascending",
+            &["sort_common_ascending.c", "exit.c"],
+        )?,
+        ProgramInputs::new("gc_keep_exported", &["gc_keep_exported.c", "exit.c"])?,
+        ProgramInputs::new(
+            "gc_keep_exported_keep",
+            &["gc_keep_exported_keep.c", "exit.c"],
+        )?,
+        ProgramInputs::new(
+            "gc_keep_exported_shared",
+            &["gc_keep_exported_shared.c", "exit.c"],
+        )?,
+        ProgramInputs::new(
+            "defsym_expr_undefined",
+            &["defsym_expr_undefined.c", "exit.c"],
+        )?,
         ProgramInputs::new("tls", &["tls.c", "tls1.c", "init_tls.c", "exit.c"])?,
+        ProgramInputs::new("tls_layout", &["tls_layout.c", "init_tls.c", "exit.c"])?,
+        ProgramInputs::new("compress_section", &["compress_section.c", "exit.c"])?,
+        ProgramInputs::new(
+            "compress_section_text",
+            &["compress_section_text.c", "exit.c"],
+        )?,
+        ProgramInputs::new("pack_relative_relocs", &["pack_relative_relocs.c"])?,
         ProgramInputs::new(
             "old_init",
             &["old_init.c", "old_init0.s", "old_init1.s", "exit.c"],
@@ -1135,6 +3397,184 @@ fn integration_test() -> Result {
             &["comments.c", "comments0.c", "comments1.c", "exit.c"],
         )?,
         ProgramInputs::new("eh_frame", &["eh_frame.c", "eh_frame_end.c", "exit.c"])?,
+        ProgramInputs::new("eh_frame_cie_dedup", &["eh_frame_cie_dedup.c", "exit.c"])?,
+        ProgramInputs::new("eh_frame_hdr_far", &["eh_frame_hdr_far.c", "exit.c"])?,
+        ProgramInputs::new(
+            "apply_dynamic_relocs",
+            &["apply_dynamic_relocs.c", "exit.c"],
+        )?,
+        ProgramInputs::new(
+            "apply_dynamic_relocs_off",
+            &["apply_dynamic_relocs_off.c", "exit.c"],
+        )?,
+        ProgramInputs::new("gnu_hash_single", &["gnu_hash_single.c"])?,
+        ProgramInputs::new("gnu_hash_no_exports", &["gnu_hash_no_exports.c", "exit.c"])?,
+        ProgramInputs::new("static_tls", &["static_tls.c"])?,
+        ProgramInputs::new("static_tls_none", &["static_tls_none.c"])?,
+        ProgramInputs::new("trace_symbol", &["trace_symbol.c", "exit.c"])?,
+        ProgramInputs::new(
+            "relocatable",
+            &["relocatable.c", "relocatable1.c", "exit.c"],
+        )?,
+        ProgramInputs::new(
+            "gnu-property",
+            &["gnu-property.c", "gnu-property1.c", "exit.c"],
+        )?,
+        ProgramInputs::new("gnu-property-cet", &["gnu-property-cet.c", "exit.c"])?,
+        ProgramInputs::new("dynamic-list", &["dynamic-list.c", "exit.c"])?,
+        ProgramInputs::new(
+            "stack-protector",
+            &[
+                "stack-protector.c",
+                "stack-protector1.c",
+                "init_tls.c",
+                "exit.c",
+            ],
+        )?,
+        ProgramInputs::new("link_map", &["link_map.c", "exit.c"])?,
+        ProgramInputs::new("error_handling_script", &["error_handling_script.c"])?,
+        ProgramInputs::new("cref", &["cref.c", "exit.c"])?,
+        ProgramInputs::new("relro", &["relro.c", "init.c", "exit.c"])?,
+        ProgramInputs::new(
+            "groups",
+            &[
+                "groups.c",
+                "exit.c",
+                "--start-group",
+                "groups-a1.c",
+                "groups-a2.c",
+                "groups-b1.c",
+                "--end-group",
+            ],
+        )?,
+        ProgramInputs::new("norelro", &["norelro.c", "init.c", "exit.c"])?,
+        ProgramInputs::new("thin_archive", &["thin_archive.c", "exit.c"])?,
+        ProgramInputs::new("symbolic", &["symbolic.c"])?,
+        ProgramInputs::new("icf", &["icf.c", "icf1.c", "exit.c"])?,
+        ProgramInputs::new("icf_no_fold", &["icf_no_fold.c", "icf1.c", "exit.c"])?,
+        ProgramInputs::new("icf_print", &["icf_print.c", "icf1.c", "exit.c"])?,
+        ProgramInputs::new("icf_addrsig", &["icf_addrsig.s"])?,
+        ProgramInputs::new("icf_addrsig_r", &["icf_addrsig_r.s"])?,
+        ProgramInputs::new("keep_section", &["keep_section.c", "exit.c"])?,
+        ProgramInputs::new(
+            "keep_section_default",
+            &["keep_section_default.c", "exit.c"],
+        )?,
+        ProgramInputs::new("unique", &["unique.s", "unique1.s"])?,
+        ProgramInputs::new(
+            "unique_shared",
+            &["unique_shared.c", "unique.s", "unique1.s"],
+        )?,
+        ProgramInputs::new("emit_relocs", &["emit_relocs.c", "exit.c"])?,
+        ProgramInputs::new("retain_symbols_file", &["retain_symbols_file.c", "exit.c"])?,
+        ProgramInputs::new("stack_size", &["stack_size.c", "exit.c"])?,
+        ProgramInputs::new("hash_style_sysv", &["hash_style_sysv.c"])?,
+        ProgramInputs::new("hash_style_both", &["hash_style_both.c"])?,
+        ProgramInputs::new("gc_sections", &["gc_sections.c", "exit.c"])?,
+        ProgramInputs::new(
+            "undefined_symbol",
+            &["undefined_symbol.c", "exit.c", "undefined_member.c"],
+        )?,
+        ProgramInputs::new(
+            "undefined_symbol_unused",
+            &["undefined_symbol_unused.c", "exit.c", "undefined_member.c"],
+        )?,
+        ProgramInputs::new(
+            "undefined_symbol_error",
+            &["undefined_symbol_error.c", "exit.c", "undefined_member.c"],
+        )?,
+        ProgramInputs::new(
+            "whole_archive",
+            &[
+                "whole_archive.c",
+                "exit.c",
+                "--whole-archive",
+                "whole_archive_member.c",
+            ],
+        )?,
+        ProgramInputs::new(
+            "whole_archive_popped",
+            &[
+                "whole_archive_unused.c",
+                "exit.c",
+                "--push-state",
+                "--whole-archive",
+                "--pop-state",
+                "whole_archive_member.c",
+            ],
+        )?,
+        ProgramInputs::new(
+            "no_whole_archive",
+            &[
+                "whole_archive_unused.c",
+                "exit.c",
+                "--whole-archive",
+                "--no-whole-archive",
+                "whole_archive_member.c",
+            ],
+        )?,
+        ProgramInputs::new(
+            "as_needed",
+            &["as_needed.c", "symbolic.c", "as_needed_lib.c"],
+        )?,
+        ProgramInputs::new(
+            "no_as_needed",
+            &["no_as_needed.c", "symbolic.c", "as_needed_lib.c"],
+        )?,
+        ProgramInputs::new("rpath_none", &["rpath_none.c"])?,
+        ProgramInputs::new("rpath_origin", &["rpath_origin.c"])?,
+        ProgramInputs::new("rpath_old_dtags", &["rpath_old_dtags.c"])?,
+        ProgramInputs::new("z_origin", &["z_origin.c"])?,
+        ProgramInputs::new("compressed_debug", &["compressed_debug.c", "exit.c"])?,
+        ProgramInputs::new(
+            "merge_constants",
+            &["merge_constants.c", "merge_constants1.c", "exit.c"],
+        )?,
+        ProgramInputs::new("no_merge", &["no_merge.c", "merge_constants1.c", "exit.c"])?,
+        ProgramInputs::new(
+            "merge_many_strings",
+            &["merge_many_strings.c", "merge_many_strings1.s", "exit.c"],
+        )?,
+        ProgramInputs::new("sort_section", &["sort_section.c", "exit.c"])?,
+        ProgramInputs::new("sort_section_name", &["sort_section_name.c", "exit.c"])?,
+        ProgramInputs::new(
+            "sort_section_alignment",
+            &["sort_section_alignment.c", "exit.c"],
+        )?,
+        ProgramInputs::new("print_memory_usage", &["print_memory_usage.c", "exit.c"])?,
+        ProgramInputs::new("linker_script", &["linker_script.c", "exit.c"])?,
+        ProgramInputs::new(
+            "warn_common",
+            &["warn_common.c", "warn_common1.c", "exit.c"],
+        )?,
+        ProgramInputs::new(
+            "warn_common_fatal",
+            &["warn_common_fatal.c", "warn_common1.c", "exit.c"],
+        )?,
+        ProgramInputs::new(
+            "warn_common_quiet",
+            &["warn_common_quiet.c", "warn_common1.c", "exit.c"],
+        )?,
+        ProgramInputs::new("noinhibit_exec", &["noinhibit_exec.c"])?,
+        ProgramInputs::new("check_reproducible", &["check_reproducible.c", "exit.c"])?,
+        ProgramInputs::new("xindex", &["xindex.s"])?,
+        ProgramInputs::new("orphan", &["orphan.c", "exit.c"])?,
+        ProgramInputs::new("orphan_place", &["orphan_place.c", "exit.c"])?,
+        ProgramInputs::new("orphan_error", &["orphan_error.c", "exit.c"])?,
+        ProgramInputs::new("cet", &["cet.c", "cet1.c", "exit.c"])?,
+        ProgramInputs::new("cet_partial", &["cet_partial.c", "cet1_nocet.c", "exit.c"])?,
+        ProgramInputs::new("gdb_index", &["gdb_index.c", "gdb_index1.c", "exit.c"])?,
+        ProgramInputs::new("textrel", &["textrel.c"])?,
+        ProgramInputs::new("textrel_error", &["textrel_error.c"])?,
+        ProgramInputs::new("textrel_warn", &["textrel_warn.c"])?,
+        ProgramInputs::new("textrel_none", &["textrel_none.c"])?,
+        ProgramInputs::new("localize", &["localize.c", "exit.c"])?,
+        ProgramInputs::new("localize_hidden", &["localize_hidden.c", "exit.c"])?,
+        ProgramInputs::new("localize_none", &["localize_none.c", "exit.c"])?,
+        ProgramInputs::new("z_flags_1", &["z_flags_1.c"])?,
+        ProgramInputs::new("z_flags_1_default", &["z_flags_1_default.c"])?,
+        ProgramInputs::new("spare_dynamic_tags", &["spare_dynamic_tags.c"])?,
+        ProgramInputs::new("spare_dynamic_tags_none", &["spare_dynamic_tags_none.c"])?,
         ProgramInputs::new(
             "pie",
             &[
@@ -1146,6 +3586,16 @@ fn integration_test() -> Result {
                 "exit.c",
             ],
         )?,
+        ProgramInputs::new(
+            "copy_relocation",
+            &["copy_relocation.c", "copy_relocation_lib.c", "exit.c"],
+        )?,
+        ProgramInputs::new("symbol_versions", &["symbol_versions.c"])?,
+        ProgramInputs::new(
+            "symbol_versions_main",
+            &["symbol_versions_main.c", "symbol_versions_lib.c"],
+        )?,
+        ProgramInputs::new("lto", &["lto.c", "lto1.c", "exit.c"])?,
         ProgramInputs::new("libc-integration", &["libc-integration.c"])?,
         ProgramInputs::new("rust-integration", &["rust-integration.rs"])?,
         ProgramInputs::new(
@@ -1154,22 +3604,24 @@ fn integration_test() -> Result {
         )?,
     ];
 
-    let linkers = [
-        Linker::ThirdParty(ThirdPartyLinker {
-            name: "ld",
-            gcc_name: "bfd",
-            path: "/usr/bin/ld",
-        }),
-        Linker::Wild,
-    ];
+    let linkers = [Linker::ThirdParty(GNU_LD), Linker::Wild];
 
     setup_wild_ld_symlink()?;
 
+    // We keep going after a program fails, so that one failure doesn't hide others.
+    let mut failures = Vec::new();
     for program_inputs in &programs {
         let filename = program_inputs.source_files.first().unwrap();
         let instructions = TestParameters::from_source(&src_path(filename))
             .with_context(|| format!("Failed to parse test parameters from `{filename}`"))?;
         for linker in linkers {
+            if instructions
+                .skip_linkers
+                .iter()
+                .any(|l| *l == linker.to_string())
+            {
+                continue;
+            }
             for &link_kind in &instructions.input_type {
                 for link_args in &instructions.linker_args {
                     for compiler_args in &instructions.compiler_args {
@@ -1182,12 +3634,19 @@ fn integration_test() -> Result {
                                     compiler_args: compiler_args.clone(),
                                 },
                             };
-                            let program = program_inputs.build(linker, &variant, &instructions.assertions).with_context(|| {
-                                format!("Failed to build program `{program_inputs}` with linker `{linker}` variant #{variant}")
-                            })?;
-                            program
-                                .run()
-                                .with_context(|| format!("Failed to run program. {program}"))?;
+                            let result = program_inputs
+                                .build(linker, &variant, &instructions)
+                                .with_context(|| {
+                                    format!("Failed to build program `{program_inputs}` with linker `{linker}` variant #{variant}")
+                                })
+                                .and_then(|program| {
+                                    program
+                                        .run()
+                                        .with_context(|| format!("Failed to run program. {program}"))
+                                });
+                            if let Err(error) = result {
+                                failures.push(error);
+                            }
                         }
                     }
                 }
@@ -1195,9 +3654,438 @@ fn integration_test() -> Result {
         }
     }
 
+    if !failures.is_empty() {
+        let num_failures = failures.len();
+        let details = failures
+            .iter()
+            .map(|error| format!("{error:?}"))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        bail!("{num_failures} program variant(s) failed:\n\n{details}");
+    }
+
+    Ok(())
+}
+
+/// Links `inputs` with wild into `name` in the build directory, passing `args` first. Fails,
+/// reporting wild's stderr, if the link does.
+fn link_standalone(name: &str, args: &[&str], inputs: &[impl AsRef<OsStr>]) -> Result<Output> {
+    std::fs::create_dir_all(build_dir())?;
+    let output = Command::new(wild_path())
+        .args(args)
+        .arg("-o")
+        .arg(build_dir().join(name))
+        .args(inputs)
+        .env(wild_lib::args::VALIDATE_ENV, "1")
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "Linking `{name}` with {args:?} failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(output)
+}
+
+/// Runs `name` from the build directory and checks that it exits with 42, like the programs in
+/// `integration_test`.
+fn run_standalone(name: &str) -> Result {
+    let status = Command::new(build_dir().join(name)).status()?;
+    if status.code() != Some(42) {
+        bail!("`{name}` exited with unexpected status {status}");
+    }
+    Ok(())
+}
+
+/// Links a program with several thousand inputs while the open file limit is set much lower than
+/// the number of inputs. This checks that we don't hold a file descriptor open for each input.
+#[test]
+fn many_inputs_test() -> Result {
+    const NUM_FILLER_INPUTS: usize = 4000;
+    const OPEN_FILE_LIMIT: usize = 256;
+
+    std::fs::create_dir_all(build_dir())?;
+    let variant = Variant::default_for_standalone_test();
+    let mut inputs = vec![
+        build_obj("trivial.c", &variant, FilePlacement::Primary)?,
+        build_obj("exit.c", &variant, FilePlacement::Secondary)?,
+    ];
+
+    // Inputs are deduplicated by path, so we need a separate copy of the filler for each input.
+    let filler = build_obj("empty.c", &variant, FilePlacement::Secondary)?;
+    let filler_dir = build_dir().join("many-inputs");
+    std::fs::create_dir_all(&filler_dir)?;
+    for i in 0..NUM_FILLER_INPUTS {
+        let path = filler_dir.join(format!("empty-{i}.o"));
+        if !is_newer(&path, &filler) {
+            std::fs::copy(&filler, &path)?;
+        }
+        inputs.push(path);
+    }
+
+    let output_path = build_dir().join("many-inputs.wild");
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("ulimit -n {OPEN_FILE_LIMIT} && exec \"$0\" \"$@\""))
+        .arg(wild_path())
+        .arg("--gc-sections")
+        .arg("-static")
+        .arg("-o")
+        .arg(&output_path)
+        .args(&inputs)
+        .env(wild_lib::args::VALIDATE_ENV, "1")
+        .status()?;
+    if !status.success() {
+        bail!("Linking {} inputs failed", inputs.len());
+    }
+
+    let status = Command::new(&output_path).status()?;
+    if status.code() != Some(42) {
+        bail!("Binary exited with unexpected status {status}");
+    }
+    Ok(())
+}
+
+/// Links a program with `--map-format=json` and checks that the map parses, that it lists the
+/// input sections that contributed to each output section and that the sizes of those
+/// contributions add up.
+#[test]
+fn json_map_test() -> Result {
+    let variant = Variant::default_for_standalone_test();
+    let inputs = [
+        build_obj("trivial.c", &variant, FilePlacement::Primary)?,
+        build_obj("exit.c", &variant, FilePlacement::Secondary)?,
+        build_obj("json_map.s", &variant, FilePlacement::Secondary)?,
+        build_obj("json_map1.s", &variant, FilePlacement::Secondary)?,
+    ];
+    let map_path = build_dir().join("json-map.json");
+    link_standalone(
+        "json-map.wild",
+        &[
+            "-static",
+            &format!("-Map={}", map_path.display()),
+            "--map-format=json",
+        ],
+        &inputs,
+    )?;
+    run_standalone("json-map.wild")?;
+
+    let map: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&map_path)?)?;
+    let sections = map["output_sections"]
+        .as_array()
+        .context("Missing output_sections")?;
+    let section = |name: &str| {
+        sections
+            .iter()
+            .find(|section| section["name"] == name)
+            .with_context(|| format!("Map doesn't contain {name}"))
+    };
+    let total_input_size = |section: &serde_json::Value| {
+        section["inputs"].as_array().map(|inputs| {
+            inputs
+                .iter()
+                .filter_map(|i| i["size"].as_u64())
+                .sum::<u64>()
+        })
+    };
+    for section in sections {
+        let size = section["size"].as_u64().context("Missing size")?;
+        if total_input_size(section).is_none_or(|total| total > size) {
+            bail!("Inputs of {} are larger than the section", section["name"]);
+        }
+    }
+    let data = section("json_map_data")?;
+    if data["inputs"].as_array().map(Vec::len) != Some(2)
+        || total_input_size(data) != data["size"].as_u64()
+    {
+        bail!("Input sizes don't add up to the size of json_map_data: {data}");
+    }
+
+    let bytes = std::fs::read(build_dir().join("json-map.wild"))?;
+    let obj = object::File::parse(bytes.as_slice())?;
+    let text = obj.section_by_name(".text").context("Missing .text")?;
+    if section(".text")?["address"].as_u64() != Some(text.address()) {
+        bail!("Address of .text doesn't match the output");
+    }
+    let start = obj
+        .symbols()
+        .find(|sym| sym.name() == Ok("_start"))
+        .context("Missing _start")?;
+    if !map["symbols"].as_array().is_some_and(|symbols| {
+        symbols.iter().any(|symbol| {
+            symbol["name"] == "_start"
+                && symbol["name_is_lossy"] == false
+                && symbol["value"].as_u64() == Some(start.address())
+        })
+    }) {
+        bail!("Map doesn't contain _start");
+    }
+    Ok(())
+}
+
+/// Builds an archive in the build directory containing just `member`.
+fn build_archive(archive_name: &str, member: &Path) -> Result<PathBuf> {
+    let archive_path = build_dir().join(archive_name);
+    let _ = std::fs::remove_file(&archive_path);
+    let status = Command::new("ar")
+        .arg("cr")
+        .arg(&archive_path)
+        .arg(member)
+        .status()?;
+    if !status.success() {
+        bail!("Failed to create archive");
+    }
+    Ok(archive_path)
+}
+
+/// Checks that the output is the same regardless of how many threads we use. In particular, we
+/// apply relocations for the sections of each input file in parallel, including when writing
+/// dynamic relocations for them, and deduplicate the strings in each file's string-merge sections in
+/// parallel.
+#[test]
+fn threads_determinism_test() -> Result {
+    let static_variant = Variant::standalone_with_compiler_args(
+        "function-data-sections",
+        &["-O2", "-ffunction-sections", "-fdata-sections"],
+    );
+    let pic_variant = Variant::standalone_with_compiler_args(
+        "function-data-sections-pic",
+        &["-O2", "-ffunction-sections", "-fdata-sections", "-fPIC"],
+    );
+    // Exporting the data from the executables stops it from being garbage collected.
+    for (kind, variant, args) in [
+        ("static", &static_variant, &["-static"][..]),
+        ("pie", &pic_variant, &["-pie", "--export-dynamic"]),
+        ("shared", &pic_variant, &["-shared"]),
+        (
+            "relr",
+            &pic_variant,
+            &["-pie", "--export-dynamic", "-z", "pack-relative-relocs"],
+        ),
+    ] {
+        let inputs = [
+            build_obj("icf.c", variant, FilePlacement::Primary)?,
+            build_obj("icf1.c", variant, FilePlacement::Secondary)?,
+            build_obj("threads_determinism.c", variant, FilePlacement::Secondary)?,
+            build_obj("merge_many_strings1.s", variant, FilePlacement::Secondary)?,
+            build_obj("exit.c", variant, FilePlacement::Secondary)?,
+        ];
+        let mut outputs = Vec::new();
+        for threads in [1, 8] {
+            let name = format!("threads-{kind}-{threads}.wild");
+            let threads_arg = format!("--threads={threads}");
+            let args = [args, &[threads_arg.as_str()]].concat();
+            link_standalone(&name, &args, &inputs)?;
+            outputs.push(std::fs::read(build_dir().join(name))?);
+        }
+        if outputs[0] != outputs[1] {
+            bail!("{kind} output with --threads=1 differs from output with --threads=8");
+        }
+    }
+    Ok(())
+}
+
+/// Links the same inputs with `--object-cache` with a cold cache, a warm cache and a corrupt cache
+/// and checks that we get the same output each time.
+#[test]
+fn object_cache_test() -> Result {
+    let variant = Variant::default_for_standalone_test();
+    let trivial = build_obj("trivial.c", &variant, FilePlacement::Primary)?;
+    let exit = build_obj("exit.c", &variant, FilePlacement::Secondary)?;
+    let archive = build_archive("libexit-for-object-cache.a", &exit)?;
+    let cache_dir = build_dir().join("object-cache");
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    let index_path = cache_dir.join("index");
+
+    let link = |name: &str| -> Result<Vec<u8>> {
+        let name = format!("object-cache-{name}.wild");
+        link_standalone(
+            &name,
+            &[
+                "-static",
+                &format!("--object-cache={}", cache_dir.display()),
+            ],
+            &[&trivial, &archive],
+        )?;
+        run_standalone(&name)?;
+        Ok(std::fs::read(build_dir().join(name))?)
+    };
+
+    let cold = link("cold")?;
+    let index = std::fs::read(&index_path).context("Cold link didn't write cache index")?;
+    let index_modified = std::fs::metadata(&index_path)?.modified()?;
+    if link("warm")? != cold {
+        bail!("Output with warm object cache differs from output with cold cache");
+    }
+    // Everything should have been found in the cache, so we shouldn't have rewritten the index.
+    if std::fs::metadata(&index_path)?.modified()? != index_modified
+        || std::fs::read(&index_path)? != index
+    {
+        bail!("Warm link rewrote the object cache index");
+    }
+    std::fs::write(&index_path, b"not an index")?;
+    if link("corrupt")? != cold {
+        bail!("Output with corrupt object cache differs from output with cold cache");
+    }
+    Ok(())
+}
+
+/// Links with `--incremental`, then changes one input and checks that relinking updates the
+/// previous output and produces the same bytes as a full link would. The update shouldn't write to
+/// the previous output file, since something might be running it.
+#[test]
+fn incremental_test() -> Result {
+    use std::io::Read as _;
+
+    std::fs::create_dir_all(build_dir())?;
+    let mut variant = Variant::default_for_standalone_test();
+    let exit = build_obj("exit.c", &variant, FilePlacement::Secondary)?;
+    let mut objects = Vec::new();
+    for variant_num in [1, 2] {
+        variant.compilation.variant_num = variant_num;
+        objects.push(build_obj(
+            "incremental.c",
+            &variant,
+            FilePlacement::Primary,
+        )?);
+    }
+    // The state from the previous link is keyed by input path, so each variant has to be linked
+    // from the same path.
+    let input_path = build_dir().join("incremental-input.o");
+    let output_path = build_dir().join("incremental.wild");
+    let _ = std::fs::remove_file(&output_path);
+
+    // Links the given object and returns the names of the phases that wild reported.
+    let link = |object: &Path, expected_exit_code: i32| -> Result<String> {
+        std::fs::copy(object, &input_path)?;
+        let output = Command::new(wild_path())
+            .arg("-static")
+            .arg("--incremental")
+            .arg("--time")
+            .arg("-o")
+            .arg(&output_path)
+            .arg(&input_path)
+            .arg(&exit)
+            .output()?;
+        if !output.status.success() {
+            bail!(
+                "Incremental link failed:\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        let status = Command::new(&output_path).status()?;
+        if status.code() != Some(expected_exit_code) {
+            bail!("Incrementally linked binary exited with unexpected status {status}");
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+
+    link(&objects[0], 41)?;
+    let mut previous_file = std::fs::File::open(&output_path)?;
+    let previous = std::fs::read(&output_path)?;
+    if !link(&objects[1], 42)?.contains("Update output file") {
+        bail!("Relink after changing an input didn't update the output in place");
+    }
+    let mut previous_after_update = Vec::new();
+    previous_file.read_to_end(&mut previous_after_update)?;
+    if previous_after_update != previous {
+        bail!("Updating the output wrote to the previous output file");
+    }
+    let updated = std::fs::read(&output_path)?;
+    // Changing the output behind our back should make us write it from scratch.
+    std::fs::write(&output_path, b"not an executable")?;
+    if link(&objects[1], 42)?.contains("Update output file") {
+        bail!("Relink after output was modified tried to update it in place");
+    }
+    if std::fs::read(&output_path)? != updated {
+        bail!("Output updated in place differs from output of full link");
+    }
     Ok(())
 }
 
+/// Looks up `name` in the SysV `.hash` section of `obj`, returning the dynamic symbol found, if any.
+fn sysv_hash_lookup<'data, 'file>(
+    obj: &'file object::File<'data>,
+    name: &str,
+) -> Result<Option<object::Symbol<'data, 'file>>> {
+    use object::ObjectSymbolTable as _;
+
+    let hash = obj
+        .section_by_name(".hash")
+        .context("Missing .hash")?
+        .data()?;
+    let words: Vec<u32> = hash
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .collect();
+    let (bucket_count, chain_count) = (words[0] as usize, words[1] as usize);
+    let buckets = &words[2..2 + bucket_count];
+    let chains = &words[2 + bucket_count..];
+    let dynsym = obj
+        .dynamic_symbol_table()
+        .context("Missing dynamic symbol table")?;
+    if chains.len() != chain_count || chain_count != dynsym.symbols().count() {
+        bail!("SysV hash has {chain_count} chain entries, which doesn't match .dynsym");
+    }
+
+    let mut index = buckets[object::elf::hash(name.as_bytes()) as usize % bucket_count];
+    while index != 0 {
+        let symbol = dynsym.symbol_by_index(object::SymbolIndex(index as usize))?;
+        if symbol.name()? == name {
+            return Ok(Some(symbol));
+        }
+        index = chains[index as usize];
+    }
+    Ok(None)
+}
+
+/// Links against a linker script that stands in for a shared object, as libc.so does, and checks
+/// that the files that it lists are found relative to the script's directory.
+#[test]
+fn linker_script_inputs_test() -> Result {
+    let variant = Variant::default_for_standalone_test();
+    let trivial = build_obj("trivial.c", &variant, FilePlacement::Primary)?;
+    let exit = build_obj("exit.c", &variant, FilePlacement::Secondary)?;
+    let script_dir = build_dir().join("linker-script-inputs");
+    std::fs::create_dir_all(&script_dir)?;
+    std::fs::copy(&trivial, script_dir.join("main.o"))?;
+    make_archive(&script_dir.join("libexit.a"), &[exit.as_path()])?;
+    let script_path = script_dir.join("libredirect.so");
+    std::fs::write(
+        &script_path,
+        "/* GNU ld script */\nINPUT(main.o)\nGROUP ( libexit.a )\n",
+    )?;
+
+    link_standalone("linker-script-inputs.wild", &["-static"], &[&script_path])?;
+    run_standalone("linker-script-inputs.wild")
+}
+
+/// Returns the notes in all segments of type `p_type`.
+fn elf_notes(
+    bytes: &[u8],
+    p_type: u32,
+) -> Result<Vec<object::read::elf::Note<'_, object::elf::FileHeader64<LittleEndian>>>> {
+    use object::read::elf::FileHeader as _;
+    use object::read::elf::ProgramHeader as _;
+
+    let e = LittleEndian;
+    let header = object::elf::FileHeader64::<LittleEndian>::parse(bytes)?;
+    let mut notes = Vec::new();
+    for segment in header.program_headers(e, bytes)? {
+        if segment.p_type(e) != p_type {
+            continue;
+        }
+        let data = segment
+            .data(e, bytes)
+            .map_err(|()| anyhow!("Invalid note segment"))?;
+        let mut segment_notes = object::read::elf::NoteIterator::new(e, segment.p_align(e), data)?;
+        while let Some(note) = segment_notes.next()? {
+            notes.push(note);
+        }
+    }
+    Ok(notes)
+}
+
 fn setup_wild_ld_symlink() -> Result {
     let wild = wild_path();
     let wild_ld_path = wild.with_file_name("ld");
@@ -1212,3 +4100,650 @@ fn setup_wild_ld_symlink() -> Result {
     }
     Ok(())
 }
+
+/// Checks that `--repro` writes a tar containing each input, including those found via the library
+/// search path, plus a response file that can be used to repeat the link from the extracted tar.
+#[test]
+fn repro_test() -> Result {
+    let variant = Variant::default_for_standalone_test();
+    let primary = build_obj("orphan.c", &variant, FilePlacement::Primary)?;
+    let exit = build_obj("exit.c", &variant, FilePlacement::Secondary)?;
+    build_archive("libexit-for-repro.a", &exit)?;
+    let tar_path = build_dir().join("repro-test.tar");
+    link_standalone(
+        "repro.wild",
+        &[
+            "-static",
+            &format!("-L{}", build_dir().display()),
+            &format!("--repro={}", tar_path.display()),
+        ],
+        &[primary.as_os_str(), OsStr::new("-lexit-for-repro")],
+    )?;
+
+    let tar = std::fs::read(&tar_path)?;
+    let mut entries = Vec::new();
+    let mut long_name = None;
+    let mut offset = 0;
+    while offset + 512 <= tar.len() && tar[offset] != 0 {
+        let header = &tar[offset..offset + 512];
+        let field = |range: std::ops::Range<usize>| {
+            let bytes = &header[range];
+            let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+            std::str::from_utf8(&bytes[..end]).map(str::to_owned)
+        };
+        let size = usize::from_str_radix(field(124..136)?.trim(), 8)?;
+        let data = &tar[offset + 512..offset + 512 + size];
+        offset += 512 + size.next_multiple_of(512);
+        if header[156] == b'x' {
+            let record = std::str::from_utf8(data)?;
+            long_name = record
+                .split_once(" path=")
+                .map(|(_, path)| path.trim_end_matches('\n').to_owned());
+            continue;
+        }
+        let name = match long_name.take() {
+            Some(name) => name,
+            None if header[345] == 0 => field(0..100)?,
+            None => format!("{}/{}", field(345..500)?, field(0..100)?),
+        };
+        entries.push((name, data.to_owned()));
+    }
+
+    let names = entries.iter().map(|(name, _)| name).collect::<Vec<_>>();
+    let (_, response) = entries
+        .iter()
+        .find(|(name, _)| name == "repro-test/response.txt")
+        .with_context(|| format!("Missing response file in {names:?}"))?;
+    // One entry per input plus the response file.
+    if entries.len() != 3 {
+        bail!("Expected 3 entries, got {names:?}");
+    }
+    for input in [primary, build_dir().join("libexit-for-repro.a")] {
+        let input = std::fs::canonicalize(&input)?;
+        let expected = format!("repro-test{}", input.display());
+        if !names.iter().any(|name| **name == expected) {
+            bail!("Missing `{expected}` in {names:?}");
+        }
+    }
+
+    // Extract the tar and repeat the link using the response file.
+    let extract_dir = build_dir().join("repro-test-extracted");
+    let _ = std::fs::remove_dir_all(&extract_dir);
+    std::fs::create_dir_all(&extract_dir)?;
+    let status = Command::new("tar")
+        .arg("xf")
+        .arg(&tar_path)
+        .current_dir(&extract_dir)
+        .status()?;
+    if !status.success() {
+        bail!("Failed to extract {}", tar_path.display());
+    }
+    let root = extract_dir.join("repro-test");
+    let status = Command::new(wild_path())
+        .args(std::str::from_utf8(response)?.lines())
+        .current_dir(&root)
+        .status()?;
+    if !status.success() {
+        bail!("Link from response file failed");
+    }
+    let status = Command::new(root.join("repro.wild")).status()?;
+    if status.code() != Some(42) {
+        bail!("Binary linked from response file exited with unexpected status {status}");
+    }
+    Ok(())
+}
+
+/// Links against a shared object that references a symbol defined in a library we don't link, then
+/// checks that `--unresolved-symbols=report-all` and `--no-allow-shlib-undefined` reject that, while
+/// the default of `ignore-in-shared-libs`, `--allow-shlib-undefined` and `--warn-unresolved-symbols`
+/// allow it.
+#[test]
+fn unresolved_symbols_test() -> Result {
+    let variant = Variant::standalone_with_compiler_args("pic", &["-fPIC"]);
+    let dep = build_obj("unresolved_dep.c", &variant, FilePlacement::Secondary)?;
+    link_standalone("libunresolved-dep.so", &["-shared"], &[dep])?;
+    let lib = build_obj("unresolved_lib.c", &variant, FilePlacement::Secondary)?;
+    link_standalone(
+        "libunresolved-lib.so",
+        &["-shared"],
+        &[lib, build_dir().join("libunresolved-dep.so")],
+    )?;
+
+    // Link against `lib`, but not against `dep`, which defines what `lib` references.
+    let inputs = [
+        build_obj("unresolved_main.c", &variant, FilePlacement::Primary)?,
+        build_dir().join("libunresolved-lib.so"),
+    ];
+    let name = "unresolved-symbols.so";
+    for args in [
+        "--unresolved-symbols=report-all",
+        "--no-allow-shlib-undefined",
+    ] {
+        let Err(error) = link_standalone(name, &["-shared", args], &inputs) else {
+            bail!("Expected {args} to fail");
+        };
+        if !error.to_string().contains("unresolved_dep_value") {
+            bail!("Expected {args} to report `unresolved_dep_value`: {error}");
+        }
+    }
+    link_standalone(name, &["-shared"], &inputs)?;
+    link_standalone(
+        name,
+        &[
+            "-shared",
+            "--no-allow-shlib-undefined",
+            "--allow-shlib-undefined",
+        ],
+        &inputs,
+    )?;
+    link_standalone(
+        name,
+        &["-shared", "--unresolved-symbols", "ignore-in-shared-libs"],
+        &inputs,
+    )?;
+    let output = link_standalone(
+        name,
+        &[
+            "-shared",
+            "--unresolved-symbols=report-all",
+            "--warn-unresolved-symbols",
+        ],
+        &inputs,
+    )?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.contains("warning") || !stderr.contains("unresolved_dep_value") {
+        bail!("Expected a warning for `unresolved_dep_value`:\n{stderr}");
+    }
+    Ok(())
+}
+
+/// Checks that `--reproduce-on-error` saves our inputs and a script to rerun the link when the link
+/// fails, but leaves nothing behind when it succeeds.
+#[test]
+fn reproduce_on_error_test() -> Result {
+    std::fs::create_dir_all(build_dir())?;
+    let variant = Variant::default_for_standalone_test();
+    let trivial = build_obj("trivial.c", &variant, FilePlacement::Primary)?;
+    let exit = build_obj("exit.c", &variant, FilePlacement::Secondary)?;
+    let save_dir = build_dir().join("reproduce-on-error");
+    if save_dir.exists() {
+        std::fs::remove_dir_all(&save_dir)?;
+    }
+    let reproduce_arg = format!("--reproduce-on-error={}", save_dir.display());
+    let args = ["-static", reproduce_arg.as_str()];
+
+    link_standalone("reproduce-on-error.wild", &args, &[&trivial, &exit])?;
+    if save_dir.exists() {
+        bail!("Successful link shouldn't create `{}`", save_dir.display());
+    }
+
+    // Without `exit.o`, `exit_syscall` is undefined.
+    let Err(error) = link_standalone("reproduce-on-error.wild", &args, &[&trivial]) else {
+        bail!("Link without exit.o should have failed");
+    };
+    let error = error.to_string();
+    if !error.contains(&save_dir.display().to_string()) || !error.contains("exit_syscall") {
+        bail!("Error should mention the original error and the save directory: {error}");
+    }
+    let run_with = std::fs::read_to_string(save_dir.join("run-with"))?;
+    let saved_input = save_dir.join(trivial.file_name().context("Input has no file name")?);
+    if !saved_input.exists() {
+        bail!("Missing `{}`", saved_input.display());
+    }
+    if !run_with.contains("--reproduce-on-error=") {
+        bail!("run-with is missing our arguments: {run_with}");
+    }
+    Ok(())
+}
+
+/// Links a trivial program in-process via the library API, twice, then checks that a bad link
+/// returns an error rather than exiting.
+#[test]
+fn library_api_test() -> Result {
+    std::fs::create_dir_all(build_dir())?;
+    let variant = Variant::default_for_standalone_test();
+    let inputs = [
+        build_obj("trivial.c", &variant, FilePlacement::Primary)?,
+        build_obj("exit.c", &variant, FilePlacement::Secondary)?,
+    ];
+    let args = [
+        "-static".into(),
+        "-o".into(),
+        build_dir().join("library-api.wild").into_os_string(),
+    ]
+    .into_iter()
+    .chain(inputs.iter().map(|input| input.clone().into_os_string()));
+    wild_lib::link(args)?;
+    run_standalone("library-api.wild")?;
+
+    let mut options = wild_lib::LinkOptions::new()
+        .static_linking()
+        .output(build_dir().join("library-api-options.wild"));
+    for input in &inputs {
+        options = options.input(input);
+    }
+    options.link()?;
+    run_standalone("library-api-options.wild")?;
+
+    let result = wild_lib::LinkOptions::new()
+        .static_linking()
+        .input(build_dir().join("does-not-exist.o"))
+        .output(build_dir().join("library-api-missing.wild"))
+        .link();
+    if result.is_ok() {
+        bail!("Link with a missing input should have failed");
+    }
+    Ok(())
+}
+
+/// Links with `--check-reproducible` and a version script that names a different version each time
+/// it's read. The version script is a FIFO that we write to once for each link, so the two links
+/// see different inputs and should be reported as not reproducible.
+#[test]
+fn check_reproducible_nondeterministic_test() -> Result {
+    std::fs::create_dir_all(build_dir())?;
+    let variant = Variant::standalone_with_compiler_args("pic", &["-fPIC"]);
+    let obj_path = build_obj("symbol_versions.c", &variant, FilePlacement::Primary)?;
+    let fifo_path = build_dir().join("check-reproducible.map");
+    let _ = std::fs::remove_file(&fifo_path);
+    let status = Command::new("mkfifo").arg(&fifo_path).status()?;
+    if !status.success() {
+        bail!("Failed to create FIFO");
+    }
+    // Opening the FIFO for writing blocks until the linker opens it for reading. After each write,
+    // we give the linker time to close the FIFO, since otherwise our next write could go to the
+    // same read. We don't wait for this thread, since it'd never finish if the linker didn't read
+    // the FIFO twice.
+    std::thread::spawn({
+        let fifo_path = fifo_path.clone();
+        move || -> Result {
+            for version in ["VERS_1", "VERS_2"] {
+                std::fs::write(&fifo_path, format!("{version} {{ global: *; }};"))?;
+                std::thread::sleep(std::time::Duration::from_millis(200));
+            }
+            Ok(())
+        }
+    });
+    let output = Command::new(wild_path())
+        .arg("-shared")
+        .arg("--check-reproducible")
+        .arg(format!("--version-script={}", fifo_path.display()))
+        .arg("-o")
+        .arg(build_dir().join("libcheck-reproducible.so"))
+        .arg(&obj_path)
+        .output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if output.status.success() || !stderr.contains("Output isn't reproducible") {
+        bail!("Expected the links to be reported as not reproducible:\n{stderr}");
+    }
+    Ok(())
+}
+
+/// Runs two in-process links with different thread counts, which each need their own thread pool.
+#[test]
+fn per_link_thread_pool_test() -> Result {
+    std::fs::create_dir_all(build_dir())?;
+    let variant = Variant::default_for_standalone_test();
+    let inputs = [
+        build_obj("trivial.c", &variant, FilePlacement::Primary)?,
+        build_obj("exit.c", &variant, FilePlacement::Secondary)?,
+    ];
+    for threads in [1, 3] {
+        let name = format!("thread-pool-{threads}.wild");
+        let mut options = wild_lib::LinkOptions::new()
+            .static_linking()
+            .threads(threads)
+            .output(build_dir().join(&name));
+        for input in &inputs {
+            options = options.input(input);
+        }
+        options
+            .link()
+            .with_context(|| format!("Link with {threads} threads failed"))?;
+        run_standalone(&name)?;
+    }
+    Ok(())
+}
+
+/// Checks that a symbol that's strongly defined by two objects is reported as a duplicate, while
+/// definitions in COMDAT groups, absolute symbols and weak definitions overridden by a strong one
+/// aren't. With `--allow-multiple-definition`, the first definition is used instead.
+#[test]
+fn duplicate_definition_test() -> Result {
+    use std::ffi::OsString;
+    use wild_lib::error::LinkError;
+
+    std::fs::create_dir_all(build_dir())?;
+    let variant = Variant::default_for_standalone_test();
+    let inputs = [
+        build_obj("duplicate_definition.s", &variant, FilePlacement::Primary)?,
+        build_obj(
+            "duplicate_definition1.s",
+            &variant,
+            FilePlacement::Secondary,
+        )?,
+        build_obj(
+            "duplicate_definition2.s",
+            &variant,
+            FilePlacement::Secondary,
+        )?,
+    ];
+    let link_and_run = |name: &str, extra_args: &[&str], inputs: &[PathBuf]| -> Result {
+        let mut args = vec![
+            OsString::from("-static"),
+            "-o".into(),
+            build_dir().join(name).into(),
+        ];
+        args.extend(extra_args.iter().map(OsString::from));
+        args.extend(inputs.iter().map(OsString::from));
+        wild_lib::link(args)?;
+        run_standalone(name)
+    };
+
+    link_and_run("duplicate-definition.wild", &[], &inputs[..2])?;
+
+    let Err(error) = link_and_run("duplicate-definition-error.wild", &[], &inputs) else {
+        bail!("Link with a duplicate definition unexpectedly succeeded");
+    };
+    match error.downcast_ref::<LinkError>() {
+        Some(LinkError::DuplicateDefinition {
+            name,
+            first_file,
+            second_file,
+        }) if name == "strong_value"
+            && Path::new(first_file) == inputs[0]
+            && Path::new(second_file) == inputs[2] => {}
+        _ => bail!("Unexpected error for duplicate definition: {error:#}"),
+    }
+
+    link_and_run(
+        "duplicate-definition-allowed.wild",
+        &["--allow-multiple-definition"],
+        &inputs,
+    )
+}
+
+/// Checks that unsupported relocations and malformed inputs are reported via the corresponding
+/// `LinkError` variants when linking through the library API.
+#[test]
+fn link_error_test() -> Result {
+    use wild_lib::error::LinkError;
+
+    std::fs::create_dir_all(build_dir())?;
+    let variant = Variant::default_for_standalone_test();
+    let link = |name: &str, inputs: &[PathBuf]| -> Result<LinkError> {
+        let mut options = wild_lib::LinkOptions::new()
+            .static_linking()
+            .output(build_dir().join(name));
+        for input in inputs {
+            options = options.input(input);
+        }
+        let error = match options.link() {
+            Ok(()) => bail!("Link of {name} should have failed"),
+            Err(error) => error,
+        };
+        error
+            .downcast_ref::<LinkError>()
+            .cloned()
+            .with_context(|| format!("Link of {name} failed without a LinkError: {error:#}"))
+    };
+
+    let unsupported = build_obj("unsupported_relocation.s", &variant, FilePlacement::Primary)?;
+    let error = link("unsupported-relocation.wild", &[unsupported])?;
+    if !matches!(
+        error,
+        LinkError::UnsupportedRelocation { r_type } if r_type == object::elf::R_X86_64_GOTPC64
+    ) {
+        bail!("Unexpected error for unsupported relocation: {error:?}");
+    }
+
+    let undefined = build_obj(
+        "undefined_section_start.s",
+        &variant,
+        FilePlacement::Primary,
+    )?;
+    let error = link("undefined-section-start.wild", &[undefined])?;
+    if !matches!(
+        &error,
+        LinkError::UndefinedSymbol { name, note: Some(_) } if name == "__start_missing_section"
+    ) {
+        bail!("Unexpected error for undefined section start: {error:?}");
+    }
+
+    let malformed = build_dir().join("malformed.o");
+    std::fs::write(&malformed, b"\x7fELF not really")?;
+    let error = link("malformed.wild", &[malformed])?;
+    if !matches!(error, LinkError::MalformedInput { .. }) {
+        bail!("Unexpected error for malformed input: {error:?}");
+    }
+    Ok(())
+}
+
+/// Links AArch64 objects assembled with llvm-mc into a static executable and checks that the
+/// relocated instructions refer to the right places. We don't run the output, since we can't assume
+/// that we're on AArch64. Also checks that we warn about Cortex-A53 erratum workarounds, which we
+/// don't implement, and that we reject objects for a different architecture.
+#[test]
+fn aarch64_test() -> Result {
+    let mut inputs = ["aarch64_start.s", "aarch64_defs.s"]
+        .into_iter()
+        .map(|filename| assemble_with_llvm_mc(filename, "aarch64", &[]))
+        .collect::<Result<Vec<_>>>()?;
+    // Compiler drivers pass this when targeting affected cores. We don't work around the erratum,
+    // so should say so.
+    let output = link_standalone(
+        "aarch64.wild",
+        &["-static", "--fix-cortex-a53-843419"],
+        &inputs,
+    )?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.contains("Ignoring `--fix-cortex-a53-843419`") {
+        bail!("Expected a warning about --fix-cortex-a53-843419, got:\n{stderr}");
+    }
+
+    let bytes = std::fs::read(build_dir().join("aarch64.wild"))?;
+    let obj = object::File::parse(bytes.as_slice())?;
+    if obj.architecture() != object::Architecture::Aarch64 {
+        bail!("Output has architecture {:?}", obj.architecture());
+    }
+    let symbol_address = |name: &str| -> Result<u64> {
+        Ok(obj
+            .symbols()
+            .find(|sym| sym.name() == Ok(name))
+            .with_context(|| format!("Missing symbol `{name}`"))?
+            .address())
+    };
+    let read_bytes = |address: u64, len: u64| -> Result<&[u8]> {
+        obj.sections()
+            .find_map(|section| section.data_range(address, len).ok().flatten())
+            .with_context(|| format!("No data at 0x{address:x}"))
+    };
+    let start = symbol_address("_start")?;
+    let data = symbol_address("data")?;
+    let instruction = |index: u64| -> Result<u32> {
+        Ok(u32::from_le_bytes(
+            read_bytes(start + index * 4, 4)?.try_into()?,
+        ))
+    };
+    let sign_extend = |value: u32, bits: u32| ((value << (32 - bits)) as i32 >> (32 - bits)) as i64;
+    // Returns the address that an ADRP at `index` refers to.
+    let adrp_page = |index: u64| -> Result<u64> {
+        let insn = instruction(index)?;
+        let imm = (insn >> 29) & 0x3 | ((insn >> 5) & 0x7ffff) << 2;
+        let place = start + index * 4;
+        Ok((place & !0xfff).wrapping_add_signed(sign_extend(imm, 21) << 12))
+    };
+    let lo12 = |index: u64| -> Result<u64> { Ok(u64::from((instruction(index)? >> 10) & 0xfff)) };
+
+    let bl_target = start.wrapping_add_signed(sign_extend(instruction(0)? & 0x3ff_ffff, 26) * 4);
+    if bl_target != symbol_address("foo")? {
+        bail!("bl goes to 0x{bl_target:x}");
+    }
+    if adrp_page(1)? + lo12(2)? != data {
+        bail!("adrp/add pair doesn't point at `data`");
+    }
+    if adrp_page(1)? + lo12(3)? * 8 != data + 8 {
+        bail!("ldr doesn't point at `data+8`");
+    }
+    let got_entry = adrp_page(4)? + lo12(5)? * 8;
+    if u64::from_le_bytes(read_bytes(got_entry, 8)?.try_into()?) != data {
+        bail!("GOT entry at 0x{got_entry:x} doesn't hold the address of `data`");
+    }
+    // The TLS block starts after a 16 byte thread control block that the thread pointer points to.
+    if lo12(7)? != 16 {
+        bail!("Unexpected TP offset {} for `tvar`", lo12(7)?);
+    }
+    // The TLS descriptor sequence should have been relaxed to local-exec.
+    let expected = [0xd2a0_0000, 0xf280_0000 | 20 << 5, 0xd503_201f, 0xd503_201f];
+    for (i, expected) in expected.into_iter().enumerate() {
+        let actual = instruction(8 + i as u64)?;
+        if actual != expected {
+            bail!("TLS descriptor instruction {i} is 0x{actual:08x}, expected 0x{expected:08x}");
+        }
+    }
+
+    let variant = Variant::default_for_standalone_test();
+    inputs.push(build_obj("exit.c", &variant, FilePlacement::Secondary)?);
+    let Err(error) = link_standalone("aarch64-mixed.wild", &["-static"], &inputs) else {
+        bail!("Linking x86-64 and AArch64 objects together should have failed");
+    };
+    if !error
+        .to_string()
+        .contains("is for x86-64, but we're linking for AArch64")
+    {
+        bail!("Unexpected error linking x86-64 and AArch64 objects together: {error}");
+    }
+    Ok(())
+}
+
+/// Links RISC-V objects assembled with llvm-mc into a static executable and checks that the
+/// relocated instructions refer to the right places, including the PC-relative low parts that refer
+/// to the AUIPC that holds the high part. Like `aarch64_test`, we don't run the output.
+#[test]
+fn riscv64_test() -> Result {
+    let mut inputs = ["riscv64_start.s", "riscv64_defs.s"]
+        .into_iter()
+        .map(|filename| assemble_with_llvm_mc(filename, "riscv64", &["-mattr=+c,+relax"]))
+        .collect::<Result<Vec<_>>>()?;
+    link_and_check_riscv64(&inputs, "riscv64.wild")?;
+
+    // Partially linking first shouldn't change the result, since we keep the relocations.
+    link_standalone("riscv64-r.o", &["-r"], &inputs)?;
+    link_and_check_riscv64(&[build_dir().join("riscv64-r.o")], "riscv64-r.wild")?;
+
+    let variant = Variant::default_for_standalone_test();
+    inputs.push(build_obj("exit.c", &variant, FilePlacement::Secondary)?);
+    let Err(error) = link_standalone("riscv64-mixed.wild", &["-static"], &inputs) else {
+        bail!("Linking x86-64 and RISC-V objects together should have failed");
+    };
+    if !error
+        .to_string()
+        .contains("is for x86-64, but we're linking for RISC-V")
+    {
+        bail!("Unexpected error linking x86-64 and RISC-V objects together: {error}");
+    }
+    Ok(())
+}
+
+/// Assembles `filename` from our test sources for `arch`, e.g. `aarch64`, which needn't be the
+/// architecture that we're running on, with llvm-mc.
+fn assemble_with_llvm_mc(filename: &str, arch: &str, args: &[&str]) -> Result<PathBuf> {
+    std::fs::create_dir_all(build_dir())?;
+    let output_path = build_dir().join(Path::new(filename).with_extension(format!("{arch}.o")));
+    let status = Command::new("llvm-mc")
+        .arg(format!("-triple={arch}-linux-gnu"))
+        .args(args)
+        .arg("-filetype=obj")
+        .arg("-o")
+        .arg(&output_path)
+        .arg(src_path(filename))
+        .status()?;
+    if !status.success() {
+        bail!("Assembling {filename} failed");
+    }
+    Ok(output_path)
+}
+
+/// Statically links the RISC-V test objects in `inputs` and checks the instructions and data that
+/// our relocations produced.
+fn link_and_check_riscv64(inputs: &[PathBuf], output_name: &str) -> Result {
+    link_standalone(output_name, &["-static"], inputs)?;
+    let bytes = std::fs::read(build_dir().join(output_name))?;
+    let obj = object::read::elf::ElfFile64::<LittleEndian>::parse(bytes.as_slice())?;
+    if obj.architecture() != object::Architecture::Riscv64 {
+        bail!("Output has architecture {:?}", obj.architecture());
+    }
+    let e_flags = obj.raw_header().e_flags(LittleEndian);
+    if e_flags != object::elf::EF_RISCV_RVC {
+        bail!("Unexpected e_flags 0x{e_flags:x}");
+    }
+    let symbol_address = |name: &str| -> Result<u64> {
+        Ok(obj
+            .symbols()
+            .find(|sym| sym.name() == Ok(name))
+            .with_context(|| format!("Missing symbol `{name}`"))?
+            .address())
+    };
+    let read_bytes = |address: u64, len: u64| -> Result<&[u8]> {
+        obj.sections()
+            .find_map(|section| section.data_range(address, len).ok().flatten())
+            .with_context(|| format!("No data at 0x{address:x}"))
+    };
+    let read_u64 = |address: u64| -> Result<u64> {
+        Ok(u64::from_le_bytes(read_bytes(address, 8)?.try_into()?))
+    };
+    let start = symbol_address("_start")?;
+    let data = symbol_address("data")?;
+    let instruction = |index: u64| -> Result<u32> {
+        Ok(u32::from_le_bytes(
+            read_bytes(start + index * 4, 4)?.try_into()?,
+        ))
+    };
+    // Returns the upper 20 bits of the U-type instruction at `index`.
+    let hi20 =
+        |index: u64| -> Result<i64> { Ok(i64::from(instruction(index)? as i32 >> 12 << 12)) };
+    // Returns the sign-extended 12 bit immediate of the I-type instruction at `index`.
+    let lo12_i = |index: u64| -> Result<i64> { Ok(i64::from(instruction(index)? as i32 >> 20)) };
+    let lo12_s = |index: u64| -> Result<i64> {
+        let insn = instruction(index)?;
+        Ok(i64::from(
+            (insn & 0xfe00_0000) as i32 >> 20 | ((insn >> 7) & 0x1f) as i32,
+        ))
+    };
+    // Returns the address computed by an AUIPC at `index` and the I-type instruction after it.
+    let pcrel_i = |index: u64| -> Result<u64> {
+        Ok((start + index * 4).wrapping_add_signed(hi20(index)? + lo12_i(index + 1)?))
+    };
+
+    if pcrel_i(0)? != symbol_address("__global_pointer$")? {
+        bail!("gp doesn't point at `__global_pointer$`");
+    }
+    if pcrel_i(2)? != symbol_address("foo")? {
+        bail!("call doesn't go to `foo`");
+    }
+    if pcrel_i(4)? != data {
+        bail!("lla doesn't point at `data`");
+    }
+    if (start + 6 * 4).wrapping_add_signed(hi20(6)? + lo12_s(7)?) != data + 8 {
+        bail!("sd doesn't store to `data+8`");
+    }
+    if read_u64(pcrel_i(8)?)? != data {
+        bail!("GOT entry doesn't hold the address of `data`");
+    }
+    // The thread pointer points at the start of the TLS block.
+    if hi20(10)? != 0 || lo12_i(12)? != 0 {
+        bail!("Unexpected TP offset for `tvar`");
+    }
+    if read_u64(pcrel_i(13)?)? != 4 {
+        bail!("GOT entry doesn't hold the TP offset of `tvar2`");
+    }
+    let size = symbol_address("size")?;
+    if pcrel_i(15)? != size {
+        bail!("lw doesn't load from `size`");
+    }
+    // `size` is computed from a pair of ADD32 and SUB32 relocations.
+    if read_bytes(size, 4)? != 2u32.to_le_bytes() {
+        bail!("`size` should be the size of `foo`");
+    }
+    Ok(())
+}
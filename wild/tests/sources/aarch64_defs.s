@@ -0,0 +1,21 @@
+    .text
+    .globl foo
+    .type foo, %function
+foo:
+    ret
+    .size foo, .-foo
+
+    .data
+    .globl data
+    .p2align 4
+data:
+    .quad foo
+    .quad 7
+
+    .section .tdata,"awT",@progbits
+    .globl tvar, tvar2
+    .p2align 2
+tvar:
+    .word 5
+tvar2:
+    .word 6
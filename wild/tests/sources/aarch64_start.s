@@ -0,0 +1,25 @@
+// Exercises the relocations that compilers commonly emit for AArch64. `aarch64_test` assembles
+// this with llvm-mc and checks the instructions in the output, since we can't assume that we can
+// run AArch64 binaries.
+
+    .text
+    .globl _start
+    .type _start, %function
+_start:
+    bl foo
+    adrp x0, data
+    add x0, x0, :lo12:data
+    ldr x1, [x0, :lo12:data+8]
+    adrp x2, :got:data
+    ldr x2, [x2, :got_lo12:data]
+    mrs x3, tpidr_el0
+    add x3, x3, :tprel_lo12_nc:tvar
+    adrp x0, :tlsdesc:tvar2
+    ldr x1, [x0, :tlsdesc_lo12:tvar2]
+    add x0, x0, :tlsdesc_lo12:tvar2
+    .tlsdesccall tvar2
+    blr x1
+    mov x0, #42
+    mov x8, #93
+    svc #0
+    .size _start, .-_start
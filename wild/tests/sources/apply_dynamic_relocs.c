@@ -0,0 +1,22 @@
+//#CompArgs:pic:-fPIC
+//#LinkArgs:apply-dynamic-relocs:-shared --apply-dynamic-relocs
+//#RunEnabled:false
+//#ExpectPointer:ptr value
+//#ExpectDynamicReloc:R_X86_64_RELATIVE
+//#NoDynamicReloc:R_X86_64_JUMP_SLOT *
+//#SkipLinker:ld
+
+// With --apply-dynamic-relocs, we should write the link-time value of `ptr` as well as emitting a
+// relative dynamic relocation for it. We always bind eagerly, so there shouldn't be any lazy
+// `.rela.plt` entries. GNU ld doesn't support --apply-dynamic-relocs on x86-64.
+
+#include "exit.h"
+
+static int value = 42;
+
+// In a position-independent output, this needs a relative dynamic relocation.
+int* ptr = &value;
+
+void _start(void) {
+    exit_syscall(*ptr);
+}
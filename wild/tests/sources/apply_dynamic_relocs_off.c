@@ -0,0 +1,12 @@
+//#CompArgs:pic:-fPIC
+//#LinkArgs:shared:-shared
+//#RunEnabled:false
+//#ExpectPointer:ptr 0
+//#ExpectDynamicReloc:R_X86_64_RELATIVE
+//#NoDynamicReloc:R_X86_64_JUMP_SLOT *
+//#SkipLinker:ld
+
+// Without --apply-dynamic-relocs, we leave `ptr` for the loader to fill in. GNU ld writes the
+// link-time value regardless.
+
+#include "apply_dynamic_relocs.c"
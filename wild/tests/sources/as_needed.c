@@ -0,0 +1,23 @@
+//#CompArgs:pic:-fPIC
+//#InputType:Shared
+//#LinkArgs:as-needed:-shared -Bdynamic --as-needed
+//#RunEnabled:false
+//#SkipLinker:ld
+//#ExpectDynamic:NEEDED */symbolic.0-pic.*.so
+//#NoDynamic:NEEDED */as_needed_lib.0-pic.*.so
+
+// Linked against shared objects built from symbolic.c and as_needed_lib.c. Only `unused_caller`
+// references the latter and nothing references it, so with --as-needed, we shouldn't emit DT_NEEDED
+// for as_needed_lib. The shared object that we do use should always get a DT_NEEDED entry. GNU ld
+// decides which shared objects are needed before garbage collection, so it keeps both.
+
+int get_value(void);
+int as_needed_lib_value(void);
+
+__attribute__((used, section(".text.unused_caller"))) static int unused_caller(void) {
+    return as_needed_lib_value();
+}
+
+int call_get_value(void) {
+    return get_value();
+}
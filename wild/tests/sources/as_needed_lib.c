@@ -0,0 +1,5 @@
+// Only referenced from a section that gets garbage collected.
+
+int as_needed_lib_value(void) {
+    return 3;
+}
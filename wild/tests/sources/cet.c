@@ -0,0 +1,24 @@
+//#CompArgs:cet:-fcf-protection=full
+//#ExpectX86Features:IBT SHSTK
+//#ExpectAbiTag:4.1.0
+//#SkipLinker:ld
+
+// All our inputs are built with `-fcf-protection`, so the output should advertise IBT and SHSTK.
+// This file and cet1.c each have an ABI tag requiring a different kernel version, so the output
+// should have a single tag requiring the newer of the two. GNU ld keeps both tags.
+
+#include "exit.h"
+
+__asm__(
+    ".section .note.ABI-tag, \"a\", @note\n"
+    ".balign 4\n"
+    ".long 4, 16, 1\n"
+    ".asciz \"GNU\"\n"
+    ".long 0, 3, 2, 0\n"
+    ".previous\n");
+
+int cet1_value(void);
+
+void _start(void) {
+    exit_syscall(cet1_value());
+}
@@ -0,0 +1,11 @@
+__asm__(
+    ".section .note.ABI-tag, \"a\", @note\n"
+    ".balign 4\n"
+    ".long 4, 16, 1\n"
+    ".asciz \"GNU\"\n"
+    ".long 0, 4, 1, 0\n"
+    ".previous\n");
+
+int cet1_value(void) {
+    return 42;
+}
@@ -0,0 +1,3 @@
+//#OverrideCompArgs:-fcf-protection=none
+
+#include "cet1.c"
@@ -0,0 +1,8 @@
+//#CompArgs:cet:-fcf-protection=full
+//#ExpectX86Features:
+//#ExpectAbiTag:4.1.0
+//#SkipLinker:ld
+
+// cet1_nocet.c isn't built with `-fcf-protection`, so the output shouldn't advertise any features.
+
+#include "cet.c"
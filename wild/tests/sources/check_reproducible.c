@@ -0,0 +1,12 @@
+//#LinkArgs:check-reproducible:--threads=4 --check-reproducible
+//#SkipLinker:ld
+
+// `--check-reproducible` links twice and fails if the outputs differ. Our links are supposed to be
+// deterministic, even when using multiple threads. It should still leave us with an output that we
+// can run.
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
@@ -0,0 +1,13 @@
+//#LinkArgs:start:--section-start=.text=0x600000
+//#ExpectSection:.text address=0x600000
+
+// Places `.text` at a requested address. check_sections_overlap.c and check_sections_disabled.c
+// place it on top of our read-only sections instead.
+
+#include "exit.h"
+
+int data_value = 42;
+
+void _start(void) {
+    exit_syscall(data_value);
+}
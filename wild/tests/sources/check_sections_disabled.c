@@ -0,0 +1,8 @@
+//#LinkArgs:overlap:--section-start=.text=0x400000 --no-check-sections
+//#ExpectSection:.text address=0x400000
+//#RunEnabled:false
+
+// Like check_sections_overlap.c, but `--no-check-sections` permits the overlap. The output isn't
+// expected to work.
+
+#include "check_sections.c"
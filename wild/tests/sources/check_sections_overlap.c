@@ -0,0 +1,8 @@
+//#LinkArgs:overlap:--section-start=.text=0x400000
+//#ExpectError:Sections overlap in memory*.text
+//#SkipLinker:ld
+
+// Our read-only sections start at 0x400000, so placing `.text` there should be rejected by the
+// default `--check-sections`. GNU ld moves its read-only sections out of the way instead.
+
+#include "check_sections.c"
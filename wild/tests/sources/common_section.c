@@ -6,6 +6,7 @@ int a;
 extern int data[];
 extern int q[];
 extern int z[];
+extern int aligned[];
 
 void _start(void) {
     a = 30;
@@ -23,10 +24,17 @@ void _start(void) {
         exit_syscall(101);
     }
     data[100] = 10;
+    // `aligned` is declared with a large alignment in one file, but the larger declaration in the
+    // other file has a smaller alignment. We should use the size from one and the alignment from the
+    // other.
+    if (((long)aligned & 255) != 0) {
+        exit_syscall(102);
+    }
     exit_syscall(42);
 }
 
 //#ExpectSym: a .bss
-//#ExpectSym: data .bss
+//#ExpectSym: data .bss 4000
 //#ExpectSym: q .bss
 //#ExpectSym: z .bss
+//#ExpectSym: aligned .bss 64
@@ -4,3 +4,5 @@ int data[10];
 // Not referenced.
 extern int data2[];
 int data2[10];
+
+int aligned[2] __attribute__ ((aligned (256)));
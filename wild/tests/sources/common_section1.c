@@ -2,3 +2,5 @@ int q[10];
 extern int data[];
 int data[1000];
 int z[10];
+
+int aligned[16];
@@ -0,0 +1,22 @@
+//#LinkArgs:zlib:--keep-section=.asset --compress-sections=.as*=zlib
+//#LinkArgs:zlib-9:--keep-section=.asset --compress-sections=.as*=zlib:9
+//#LinkArgs:zstd:--keep-section=.asset --compress-sections=.as*=zstd
+//#LinkArgs:zstd-19:--keep-section=.asset --compress-sections=.as*=zstd:19
+//#ExpectSection:.asset compressed=true uncompressed-size=0x1100
+//#SkipLinker:ld
+
+// `.asset` isn't loaded at runtime, so can be compressed. Its contents are repetitive, so compress
+// well. GNU ld doesn't support `--compress-sections`.
+
+#include "exit.h"
+
+__asm__(
+    ".section .asset, \"\", @progbits\n"
+    ".rept 256\n"
+    ".ascii \"Some asset data. \"\n"
+    ".endr\n"
+    ".text\n");
+
+void _start(void) {
+    exit_syscall(42);
+}
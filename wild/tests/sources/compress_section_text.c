@@ -0,0 +1,11 @@
+//#LinkArgs:text:--compress-sections=.text=zlib
+//#ExpectError:can't compress `.text`
+//#SkipLinker:ld
+
+// Code is loaded at runtime, so asking to compress it should be an error.
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
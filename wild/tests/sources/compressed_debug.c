@@ -0,0 +1,28 @@
+//#CompArgs:zlib:-g -Wa,--compress-debug-sections=zlib
+//#CompArgs:zstd:-g -Wa,--compress-debug-sections=zstd
+//#ExpectSection:.debug_str compressed=false
+//#Contains:compressed_debug_field_number_eight
+
+// Our debug sections are compressed by the assembler. The output should contain them uncompressed.
+// We currently garbage collect `.debug_info` and friends, so this mostly exercises the string
+// sections, which we always keep. Field names only end up in .debug_str and there are enough of
+// them for the assembler to consider compressing it worthwhile.
+
+#include "exit.h"
+
+struct compressed_debug_fields {
+    int compressed_debug_field_number_one;
+    int compressed_debug_field_number_two;
+    int compressed_debug_field_number_three;
+    int compressed_debug_field_number_four;
+    int compressed_debug_field_number_five;
+    int compressed_debug_field_number_six;
+    int compressed_debug_field_number_seven;
+    int compressed_debug_field_number_eight;
+};
+
+struct compressed_debug_fields fields = {42};
+
+void _start(void) {
+    exit_syscall(fields.compressed_debug_field_number_one);
+}
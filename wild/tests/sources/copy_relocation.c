@@ -0,0 +1,49 @@
+// Linked as a non-PIE executable against a shared object built from copy_relocation_lib.c. Since
+// we're compiled without -fPIC, we access the shared object's data directly, so it needs to be
+// copied into our .bss. We should emit a single copy relocation per object, even though the shared
+// object has an alias for one of them, and the shared object should then use the copy via both
+// names. GNU ld doesn't export the alias, so the shared object reads the original through it.
+
+//#CompArgs:no-pic:-fno-pic
+//#InputType:Shared
+//#LinkArgs:copy:-no-pie -Bdynamic -dynamic-linker /lib64/ld-linux-x86-64.so.2
+//#ExpectDynamicReloc:R_X86_64_COPY copied_value
+//#ExpectDynamicReloc:R_X86_64_COPY copied_array
+//#NoDynamicReloc:R_X86_64_COPY copied_alias
+//#ExpectDynSym:copied_alias
+//#SkipLinker:ld
+
+#include "exit.h"
+
+extern int copied_value;
+extern char copied_array[24];
+
+int get_copied_value(void);
+int get_copied_alias(void);
+int array_sum(void);
+
+void _start(void) {
+    if (copied_value != 10) {
+        exit_syscall(1);
+    }
+    int sum = 0;
+    for (int i = 0; i < 24; i++) {
+        sum += copied_array[i];
+    }
+    if (sum != 276) {
+        exit_syscall(2);
+    }
+    copied_value = 15;
+    copied_array[0] = 100;
+    // The shared object should see our writes, including through an alias.
+    if (get_copied_value() != 15) {
+        exit_syscall(3);
+    }
+    if (get_copied_alias() != 15) {
+        exit_syscall(4);
+    }
+    if (array_sum() != 376) {
+        exit_syscall(5);
+    }
+    exit_syscall(42);
+}
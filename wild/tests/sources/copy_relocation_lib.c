@@ -0,0 +1,29 @@
+// Linked into a shared object for copy_relocation.c. Defines data objects that the executable
+// accesses directly, one of them with an alias. We link it with GNU ld, since we bind references to
+// a shared object's own symbols locally, which would stop it from using the copies.
+
+//#OverrideCompArgs:-fPIC
+//#SharedLinker:ld
+
+int copied_value = 10;
+extern int copied_alias __attribute__((weak, alias("copied_value")));
+
+char copied_array[24] __attribute__((aligned(16))) = {
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
+};
+
+int get_copied_value(void) {
+    return copied_value;
+}
+
+int get_copied_alias(void) {
+    return copied_alias;
+}
+
+int array_sum(void) {
+    int sum = 0;
+    for (int i = 0; i < 24; i++) {
+        sum += copied_array[i];
+    }
+    return sum;
+}
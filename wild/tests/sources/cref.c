@@ -0,0 +1,8 @@
+//#LinkArgs:cref:--cref
+//#OutputContains:\nexit_syscall *exit.0-default.o\n *cref.0-default.o\n
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
@@ -0,0 +1,29 @@
+// Tests symbols defined on the command line with --defsym and --defsym-weak. A weak defsym should
+// be used only when no object defines the symbol.
+
+//#LinkArgs:defsym:--defsym=bar=0x30 --defsym-weak=foo=0x20
+//#Variant: 0
+//#Variant: 1
+//#SkipLinker:ld
+
+#include "exit.h"
+
+extern char foo[];
+extern char bar[];
+
+void _start(void) {
+    if ((long)bar != 0x30) {
+        exit_syscall(10);
+    }
+#if VARIANT == 1
+    // foo is defined strongly by our other file, which should take precedence.
+    if (foo[0] != 7) {
+        exit_syscall(11);
+    }
+#else
+    if ((long)foo != 0x20) {
+        exit_syscall(12);
+    }
+#endif
+    exit_syscall(42);
+}
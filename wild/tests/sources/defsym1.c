@@ -0,0 +1,3 @@
+#if VARIANT == 1
+char foo[] = {7};
+#endif
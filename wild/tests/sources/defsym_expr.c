@@ -0,0 +1,21 @@
+//#LinkArgs:defsym:--defsym=after_base=base+0x10 --defsym=diff=(later-base) --defsym=later=base+(2+2)
+
+// The symbols declared here are defined on the command line in terms of `base`, including by other
+// `--defsym`s that come later on the command line.
+
+#include "exit.h"
+
+char base[32];
+
+extern char after_base[];
+extern char diff[];
+
+void _start(void) {
+    if (after_base != base + 0x10) {
+        exit_syscall(10);
+    }
+    if ((long)diff != 4) {
+        exit_syscall(11);
+    }
+    exit_syscall(42);
+}
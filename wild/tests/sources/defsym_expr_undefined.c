@@ -0,0 +1,6 @@
+//#LinkArgs:defsym:--defsym=after_base=not_defined+1 --defsym=diff=4
+//#ExpectError:not_defined
+
+// A `--defsym` expression that refers to an undefined symbol should be reported.
+
+#include "defsym_expr.c"
@@ -0,0 +1,32 @@
+// Used by `duplicate_definition_test`. This file and duplicate_definition1.s both define
+// `comdat_value` in a COMDAT group, the absolute symbol `abs_value` and `override_value`, which is
+// weak here. None of these should be reported as duplicates. duplicate_definition2.s strongly
+// defines `strong_value`, which should be.
+
+.section .data.comdat_value,"awG",@progbits,comdat_value,comdat
+.globl comdat_value
+comdat_value:
+    .long 20
+
+.data
+.weak override_value
+override_value:
+    .long 99
+
+.globl strong_value
+strong_value:
+    .long 12
+
+.globl abs_value
+.set abs_value, 0
+
+.text
+.globl _start
+.type _start, @function
+_start:
+    mov comdat_value(%rip), %edi
+    add override_value(%rip), %edi
+    add strong_value(%rip), %edi
+    add $abs_value, %edi
+    mov $60, %eax
+    syscall
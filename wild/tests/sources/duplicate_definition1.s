@@ -0,0 +1,15 @@
+// Used by `duplicate_definition_test`. See duplicate_definition.s. Our strong definition of
+// `override_value` should be used in place of the weak one.
+
+.section .data.comdat_value,"awG",@progbits,comdat_value,comdat
+.globl comdat_value
+comdat_value:
+    .long 20
+
+.data
+.globl override_value
+override_value:
+    .long 10
+
+.globl abs_value
+.set abs_value, 0
@@ -0,0 +1,7 @@
+// Used by `duplicate_definition_test`. Strongly defines `strong_value`, which
+// duplicate_definition.s also strongly defines.
+
+.data
+.globl strong_value
+strong_value:
+    .long 0
@@ -0,0 +1,23 @@
+//#LinkArgs:dynamic-list:-pie --no-dynamic-linker --dynamic-list=./tests/sources/dynamic-list.txt
+//#ExpectDynSym:exported_fn
+//#ExpectDynSym:exported_var
+//#NoDynSym:not_exported_fn
+
+#include "exit.h"
+
+int exported_var = 10;
+
+int exported_fn(void) {
+    return 20;
+}
+
+int not_exported_fn(void) {
+    return 12;
+}
+
+void _start(void) {
+    if (exported_fn() + not_exported_fn() + exported_var != 42) {
+        exit_syscall(100);
+    }
+    exit_syscall(42);
+}
@@ -0,0 +1,12 @@
+//#LinkArgs:eh-frame-hdr:--eh-frame-hdr
+//#ExpectEhFrame:cies=1 fdes=2
+//#ExpectEhFrameHdr:2
+
+// The FDEs for `_start` and for `exit_syscall` in exit.c use identical CIEs, so only one CIE should
+// be written.
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
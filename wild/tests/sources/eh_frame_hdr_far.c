@@ -0,0 +1,12 @@
+//#LinkArgs:far:-T ./tests/sources/eh_frame_hdr_far.ld --eh-frame-hdr
+//#ExpectError:more than 2GiB away from .eh_frame_hdr
+//#SkipLinker:ld
+
+// eh_frame_hdr_far.ld puts .text too far from .eh_frame_hdr for the 32 bit offsets in its lookup
+// table, which we should report rather than writing truncated offsets.
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
@@ -0,0 +1,19 @@
+//#LinkArgs:emit-relocs:--emit-relocs
+//#ExpectRelocation:.text get_value
+//#ExpectRelocation:.text value
+//#ExpectRelocation:.text exit_syscall
+
+// Checks that the relocations for the call to `get_value` and the reference to `value` are kept in
+// the output and that they refer to output addresses and symbols.
+
+#include "exit.h"
+
+int value = 42;
+
+int __attribute__((noinline)) get_value(void) {
+    return value;
+}
+
+void _start(void) {
+    exit_syscall(get_value());
+}
@@ -0,0 +1 @@
+// An object with no symbols. Used as filler when testing links with very large numbers of inputs.
@@ -0,0 +1,12 @@
+// We deliberately don't provide exit_syscall, so that the link fails and runs our script.
+
+//#LinkArgs:script:--error-handling-script=./tests/sources/error_handling_script.sh
+//#ExpectError:Suggestion for undefined-symbol exit_syscall: link with exit.o
+// GNU ld only supports --error-handling-script when it's configured with it.
+//#SkipLinker:ld
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
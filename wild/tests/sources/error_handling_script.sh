@@ -0,0 +1,2 @@
+#!/bin/sh
+echo "Suggestion for $1 $2: link with exit.o"
@@ -0,0 +1,19 @@
+//#NoSym:exported_fn
+//#NoSym:hidden_fn
+
+// Nothing references `exported_fn` or `hidden_fn`, and each is in its own section, so GC discards
+// them from an executable. gc_keep_exported_keep.c and gc_keep_exported_shared.c keep `exported_fn`.
+
+#include "exit.h"
+
+__attribute__((section(".text.exported_fn"))) int exported_fn(void) {
+    return 7;
+}
+
+__attribute__((section(".text.hidden_fn"), visibility("hidden"))) int hidden_fn(void) {
+    return 8;
+}
+
+void _start(void) {
+    exit_syscall(42);
+}
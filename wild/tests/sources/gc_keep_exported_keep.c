@@ -0,0 +1,7 @@
+//#LinkArgs:keep:--gc-keep-exported
+//#ExpectGlobalSym:exported_fn
+//#NoSym:hidden_fn
+
+// With `--gc-keep-exported`, GC keeps `exported_fn`, but still discards the hidden function.
+
+#include "gc_keep_exported.c"
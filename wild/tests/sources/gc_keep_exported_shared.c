@@ -0,0 +1,9 @@
+//#CompArgs:pic:-fPIC
+//#LinkArgs:shared:-shared
+//#RunEnabled:false
+//#ExpectDynSym:exported_fn
+//#NoSym:hidden_fn
+
+// A shared object exports `exported_fn`, so GC keeps it, but still discards the hidden function.
+
+#include "gc_keep_exported.c"
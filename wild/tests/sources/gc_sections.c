@@ -0,0 +1,22 @@
+//#LinkArgs:print-gc-sections:--print-gc-sections
+//#OutputContains:removing unused section '.text.unused' in file '*gc_sections.0-default.o'
+//#OutputDoesNotContain:'.text.retained'
+//#OutputDoesNotContain:'.text'
+
+// Nothing references `unused`, so its section should be reported as removed. `retained` isn't
+// referenced either, but is marked as retained, so it should be kept, as should the section
+// containing the entry point.
+
+#include "exit.h"
+
+__attribute__((section(".text.unused"))) int unused(void) {
+    return 1;
+}
+
+__attribute__((retain, section(".text.retained"))) int retained(void) {
+    return 2;
+}
+
+void _start(void) {
+    exit_syscall(42);
+}
@@ -0,0 +1,17 @@
+//#CompArgs:gdb-index:-g -ggnu-pubnames
+//#LinkArgs:gdb-index:--gdb-index
+//#ExpectGdbIndex:gdb_index_value
+//#ExpectGdbIndex:GdbIndexType
+//#NoGdbIndex:not_a_symbol
+//#SkipLinker:ld
+
+// Checks the index built from the pubnames and pubtypes in our inputs. GNU ld doesn't support
+// `--gdb-index`.
+
+#include "exit.h"
+
+int gdb_index_value(void);
+
+void _start(void) {
+    exit_syscall(gdb_index_value());
+}
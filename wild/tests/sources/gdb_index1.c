@@ -0,0 +1,8 @@
+struct GdbIndexType {
+    int value;
+};
+
+int gdb_index_value(void) {
+    struct GdbIndexType t = {42};
+    return t.value;
+}
@@ -0,0 +1,8 @@
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
+
+//#CompArgs:cet:-fcf-protection
+//#Contains:.note.gnu.property
@@ -0,0 +1,17 @@
+#include "exit.h"
+
+int foo(void);
+
+void _start(void) {
+    if (foo() != 10) {
+        exit_syscall(100);
+    }
+    exit_syscall(42);
+}
+
+// When compiled with -fcf-protection, this file has a .note.gnu.property section marking it as
+// compatible with IBT and SHSTK. The other file isn't compiled with that flag, so doesn't have the
+// note. The merged result has no features, so no note should be emitted.
+//#CompArgs:default:
+//#CompArgs:cet:-fcf-protection
+//#DoesNotContain:.note.gnu.property
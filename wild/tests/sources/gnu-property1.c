@@ -0,0 +1,5 @@
+//#OverrideCompArgs:
+
+int foo(void) {
+    return 10;
+}
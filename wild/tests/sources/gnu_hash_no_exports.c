@@ -0,0 +1,11 @@
+//#CompArgs:pic:-fPIC
+//#LinkArgs:pie:-pie --no-dynamic-linker --hash-style=gnu
+//#ExpectGnuHash:
+
+// A PIE that exports no symbols should still have a `.gnu.hash` that a dynamic loader can use.
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
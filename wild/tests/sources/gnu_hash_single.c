@@ -0,0 +1,9 @@
+//#CompArgs:pic:-fPIC
+//#LinkArgs:shared:-shared --hash-style=gnu
+//#RunEnabled:false
+//#ExpectGnuHash:get_answer
+
+// The only symbol that a shared object linked from this file exports.
+int get_answer(void) {
+    return 42;
+}
@@ -0,0 +1,5 @@
+int group_b1(void);
+
+int group_a1(void) {
+    return group_b1();
+}
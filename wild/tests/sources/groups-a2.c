@@ -0,0 +1,5 @@
+// Only needed by groups-b1.c, which comes from a later archive, so without a group, GNU ld won't
+// load this.
+int group_a2(void) {
+    return 42;
+}
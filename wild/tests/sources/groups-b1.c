@@ -0,0 +1,5 @@
+int group_a2(void);
+
+int group_b1(void) {
+    return group_a2();
+}
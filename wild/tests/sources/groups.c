@@ -0,0 +1,12 @@
+// Links against archives that depend on each other. groups-a2.c is only needed by groups-b1.c,
+// which comes from a later archive, so GNU ld only finds it because of the group.
+
+//#InputType:Archive
+
+#include "exit.h"
+
+int group_a1(void);
+
+void _start(void) {
+    exit_syscall(group_a1());
+}
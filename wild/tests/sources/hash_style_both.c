@@ -0,0 +1,7 @@
+//#CompArgs:pic:-fPIC
+//#LinkArgs:both:-shared --hash-style=both
+//#RunEnabled:false
+//#ExpectSysvHash:get_value
+//#ExpectSection:.gnu.hash
+
+#include "hash_style_sysv.c"
@@ -0,0 +1,14 @@
+//#CompArgs:pic:-fPIC
+//#LinkArgs:sysv:-shared --hash-style=sysv
+//#RunEnabled:false
+//#ExpectSysvHash:get_value
+//#NoSection:.gnu.hash
+
+// Looking up our symbols via the SysV `.hash` section should find them. `.gnu.hash` should only be
+// present when requested.
+
+int value = 42;
+
+int get_value(void) {
+    return value;
+}
@@ -0,0 +1,33 @@
+//#CompArgs:function-sections:-O2 -ffunction-sections
+//#LinkArgs:icf:--icf=all
+//#SkipLinker:ld
+//#SameAddress:add_a add_b
+//#SameAddress:call_a call_b
+//#DifferentAddress:call_a different
+
+// `add_a` and `add_b` are identical, but are in different objects. `call_a` and `call_b` only become
+// identical once `add_a` and `add_b` have been folded. GNU ld doesn't support --icf.
+
+#include "exit.h"
+
+int __attribute__((noipa)) add_a(int x) {
+    return x + 10;
+}
+
+int add_b(int x);
+
+int __attribute__((noinline)) call_a(int x) {
+    return add_a(x) + 1;
+}
+
+int __attribute__((noinline)) call_b(int x) {
+    return add_b(x) + 1;
+}
+
+int __attribute__((noinline)) different(int x) {
+    return add_a(x) + 2;
+}
+
+void _start(void) {
+    exit_syscall(call_a(10) + call_b(10) + different(0) - 12);
+}
@@ -0,0 +1,3 @@
+int add_b(int x) {
+    return x + 10;
+}
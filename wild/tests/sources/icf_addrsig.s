@@ -0,0 +1,54 @@
+//#LinkArgs:icf-safe:--icf=safe
+//#SameAddress:add_b add_c
+//#DifferentAddress:add_a add_b
+//#NoSection:.llvm_addrsig
+//#SkipLinker:ld
+
+// `add_a`, `add_b` and `add_c` are identical, but `add_a` is listed in `.llvm_addrsig`, so
+// `--icf=safe` must leave it alone, while still folding the other two. The table itself shouldn't
+// be copied to the output, since its symbol indexes would then be wrong. GNU ld doesn't support
+// --icf.
+
+.section .text.add_a,"ax",@progbits
+.globl add_a
+.type add_a, @function
+add_a:
+    lea 10(%rdi), %eax
+    ret
+
+.section .text.add_b,"ax",@progbits
+.globl add_b
+.type add_b, @function
+add_b:
+    lea 10(%rdi), %eax
+    ret
+
+.section .text.add_c,"ax",@progbits
+.globl add_c
+.type add_c, @function
+add_c:
+    lea 10(%rdi), %eax
+    ret
+
+.section .text._start,"ax",@progbits
+.globl _start
+.type _start, @function
+_start:
+    mov $10, %edi
+    call add_a
+    mov %eax, %ebx
+    mov $10, %edi
+    call add_b
+    add %eax, %ebx
+    mov $-8, %edi
+    call add_c
+    add %eax, %ebx
+    mov %ebx, %edi
+    mov $60, %eax
+    syscall
+
+// The assembler doesn't know how to write an address-significance table, so we write one by hand.
+// Entries are symbol indexes. `add_a` is the first symbol that the assembler writes after the null
+// symbol. If that changes, then `add_a` will be folded.
+.section .llvm_addrsig,"e",@0x6fff4c03
+    .uleb128 1
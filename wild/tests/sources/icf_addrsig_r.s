@@ -0,0 +1,17 @@
+//#LinkArgs:relocatable:-r
+//#RunEnabled:false
+//#NoSection:.llvm_addrsig
+//#SkipLinker:ld
+
+// A relocatable link shouldn't copy `.llvm_addrsig` either, since we renumber symbols. GNU ld
+// copies it as is.
+
+.section .text.add_a,"ax",@progbits
+.globl add_a
+.type add_a, @function
+add_a:
+    lea 10(%rdi), %eax
+    ret
+
+.section .llvm_addrsig,"e",@0x6fff4c03
+    .uleb128 1
@@ -0,0 +1,10 @@
+//#CompArgs:function-sections:-O2 -ffunction-sections
+//#LinkArgs:icf-safe:--icf=safe
+//#LinkArgs:icf-none:--icf=none
+//#SkipLinker:ld
+//#DifferentAddress:add_a add_b
+
+// The same functions as icf.c. `--icf=safe` can't tell whether the addresses of `add_a` and `add_b`
+// are significant, so it shouldn't fold them, and `--icf=none` shouldn't fold anything.
+
+#include "icf.c"
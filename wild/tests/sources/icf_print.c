@@ -0,0 +1,10 @@
+//#CompArgs:function-sections:-O2 -ffunction-sections
+//#LinkArgs:icf:--icf=all --print-icf-sections
+//#SkipLinker:ld
+//#OutputContains:selected section '.text.add_a' in file '*/icf_print.*.o'\n  removing identical section '.text.add_b' in file '*/icf1.*.o'\n
+//#OutputDoesNotContain:removing identical section '.text.add_a'
+
+// With --print-icf-sections, the folding of `add_b` into the identical `add_a` should be reported
+// as a single group.
+
+#include "icf.c"
@@ -1,3 +1,5 @@
+//#ExpectIrelativeResolvers:resolve_compute_value10 resolve_compute_value32
+
 #include "exit.h"
 #include "init.h"
 #include "ifunc_init.h"
@@ -0,0 +1,12 @@
+#include "exit.h"
+
+// Built in two variants that differ in the exit code, so that relinking after switching between
+// them can update the previous output in place. The first variant also has more code, so the second
+// leaves part of the space that the first used as padding, which must be zeroed.
+void _start(void) {
+#if VARIANT == 1
+    for (volatile int i = 0; i < 10; i++) {
+    }
+#endif
+    exit_syscall(40 + VARIANT);
+}
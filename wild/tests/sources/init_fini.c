@@ -0,0 +1,35 @@
+// Our `.init` and `.fini` fragments go between those of `init_fini_crti.s` and `init_fini_crtn.s`.
+// They're more aligned than either, so they'd end up in the wrong place if we ordered fragments by
+// alignment rather than by input order.
+
+#include "exit.h"
+
+static int value = 0;
+
+void add_init(void) {
+    value += 10;
+}
+
+void add_fini(void) {
+    value += 32;
+}
+
+asm(".section .init,\"ax\",@progbits\n"
+    ".p2align 4\n"
+    "call add_init@PLT\n"
+    ".section .fini,\"ax\",@progbits\n"
+    ".p2align 4\n"
+    "call add_fini@PLT\n"
+    ".text\n");
+
+void _init(void);
+void _fini(void);
+
+void _start(void) {
+    _init();
+    if (value != 10) {
+        exit_syscall(value + 100);
+    }
+    _fini();
+    exit_syscall(value);
+}
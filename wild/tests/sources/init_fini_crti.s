@@ -0,0 +1,16 @@
+// Like glibc's crti.o, this opens the `_init` and `_fini` functions, which other objects add to via
+// their own `.init` and `.fini` sections and `init_fini_crtn.s` closes.
+
+.section .init,"ax",@progbits
+.p2align 2
+.globl _init
+.type _init, @function
+_init:
+    subq $8, %rsp
+
+.section .fini,"ax",@progbits
+.p2align 2
+.globl _fini
+.type _fini, @function
+_fini:
+    subq $8, %rsp
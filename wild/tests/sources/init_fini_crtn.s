@@ -0,0 +1,9 @@
+// Like glibc's crtn.o, this closes the functions opened by `init_fini_crti.s`.
+
+.section .init,"ax",@progbits
+    addq $8, %rsp
+    ret
+
+.section .fini,"ax",@progbits
+    addq $8, %rsp
+    ret
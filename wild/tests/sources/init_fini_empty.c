@@ -0,0 +1,10 @@
+//#LinkArgs:shared:-shared
+//#RunEnabled:false
+//#NoDynamic:INIT
+//#NoDynamic:FINI
+
+// Without any `.init` or `.fini`, `DT_INIT` and `DT_FINI` should be omitted.
+
+int init_fini_empty_value(void) {
+    return 42;
+}
@@ -0,0 +1,8 @@
+//#CompArgs:pic:-fPIC
+//#LinkArgs:shared:-shared
+//#RunEnabled:false
+//#ExpectDynamic:INIT .init
+//#ExpectDynamic:FINI .fini
+
+// Links init_fini.c between init_fini_crti.s and init_fini_crtn.s into a shared object, where
+// `DT_INIT` and `DT_FINI` should point at the start of `.init` and `.fini`.
@@ -62,46 +62,46 @@ u8*** get_tcb(void) {
 
 int init_tls(uint64_t base_address) {
     // A buffer to hold our TLS storage.
-    static u8 tls_area[1024] __attribute__ ((aligned (8)));
+    static u8 tls_area[1024] __attribute__ ((aligned (64)));
 
     const u32 SHT_TLS = 7;
 
-    u8 *t_out = tls_area;
     int num_headers = __ehdr_start.program_header_num;
     struct ProgramHeader *headers = (struct ProgramHeader *)((void *)(&__ehdr_start) + __ehdr_start.program_header_offset);
-    for (int i; i < num_headers; i++) {
+    for (int i = 0; i < num_headers; i++) {
         struct ProgramHeader *h = &headers[i];
         if (h->segment_type == SHT_TLS) {
+            u64 align = h->alignment ? h->alignment : 1;
+            // The TLS block ends at the TCB and its size is rounded up to the segment alignment.
+            // The TCB itself needs to be at least 64-byte aligned, as it is with glibc.
+            u64 block_size = (h->mem_size + align - 1) & ~(align - 1);
+            u64 tcb_align = align > 64 ? align : 64;
+            u64 tcb_address = ((u64)tls_area + block_size + tcb_align - 1) & ~(tcb_align - 1);
+            u8 *block = (u8*)(tcb_address - block_size);
             u8 *t_in = (u8*)h->virtual_addr + base_address;
-            for (int j = 0; j < h->mem_size; j++) {
+            for (u64 j = 0; j < block_size; j++) {
                 if (j < h->file_size) {
-                    *t_out = *t_in;
-                    t_in++;
+                    block[j] = t_in[j];
                 } else {
                     // We're past file_size, initialise with zeros.
-                    *t_out = 0;
+                    block[j] = 0;
                 }
-                t_out++;
-            }
-            // Keep going until we're 8-byte aligned, otherwise the TCB might not have the correct
-            // alignment.
-            while ((((u64)t_out) & 0x7) != 0) {
-                t_out++;
             }
 
             // Put a pointer to the TCB at the start of the TCB.
-            u64 *tcb_u64 = (u64*)t_out;
+            u64 *tcb_u64 = (u64*)tcb_address;
             tcb_u64[0] = (u64)tcb_u64;
 
-            // Next entry in the TCB
+            // Next entry in the TCB. Module 1, the executable, has its TLS block just before the
+            // TCB.
             u64 *modules = &tcb_u64[2];
-            modules[1] = (u64)tcb_u64;
+            modules[1] = (u64)block;
 
             tcb_u64[1] = (u64)modules;
 
-            // Point the GS register to the TCB.
-            set_fs_register(t_out);
-            tcb = (u8***)t_out;
+            // Point the FS register to the TCB.
+            set_fs_register(tcb_u64);
+            tcb = (u8***)tcb_u64;
             return 0;
         }
     }
@@ -10,6 +10,26 @@ struct Rela {
 extern const struct Rela *__rela_iplt_start __attribute__ ((weak));
 extern const struct Rela *__rela_iplt_end __attribute__ ((weak));
 
+typedef void (*init_fn_t)(void);
+
+extern init_fn_t __init_array_start[];
+extern init_fn_t __init_array_end[];
+
+extern char __bss_start[];
+extern char _edata[];
+extern char _end[];
+extern char end[];
+
+// The linker would define this if we didn't, but our definition should take precedence.
+int edata = 7;
+
+int bss_var;
+
+static void init_fn(void) {}
+
+// We never call this, we just check that it's bracketed by __init_array_start/end.
+init_fn_t init_array_entry __attribute__ ((section (".init_array"), used)) = init_fn;
+
 void _start(void) {
     int value = 42;
     // We shouldn't have any .rela.plt entries, so this loop should terminate without dereferencing
@@ -17,5 +37,17 @@ void _start(void) {
     for (const struct Rela *e = __rela_iplt_start; e < __rela_iplt_end; ++e) {
         value += 1 + e->a;
     }
+    if (&init_array_entry < __init_array_start || &init_array_entry >= __init_array_end) {
+        exit_syscall(101);
+    }
+    if (__bss_start != _edata || _end != end) {
+        exit_syscall(102);
+    }
+    if ((char*)&bss_var < __bss_start || (char*)&bss_var >= _end) {
+        exit_syscall(103);
+    }
+    if (edata != 7) {
+        exit_syscall(104);
+    }
     exit_syscall(value);
 }
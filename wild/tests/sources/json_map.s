@@ -0,0 +1,8 @@
+// Used by `json_map_test`. The section is retained so that it survives garbage collection. The data
+// has byte alignment, so contributions to the section from this file and json_map1.s are packed
+// without any padding between them.
+
+.section json_map_data,"awR",@progbits
+.globl json_map_first
+json_map_first:
+    .byte 1, 2, 3, 4, 5
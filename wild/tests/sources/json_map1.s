@@ -0,0 +1,6 @@
+// Used by `json_map_test`. See json_map.s.
+
+.section json_map_data,"awR",@progbits
+.globl json_map_second
+json_map_second:
+    .byte 6, 7, 8
@@ -0,0 +1,16 @@
+//#LinkArgs:keep-remove:--keep-section=.my_* --remove-section .comment
+//#ExpectSection:.my_meta
+//#NoSection:.comment
+//#SkipLinker:ld
+
+// Nothing refers to `meta`, so its section would normally be garbage collected, but --keep-section
+// keeps it. --remove-section should drop .comment, including our own contribution to it. GNU ld
+// supports neither flag.
+
+#include "exit.h"
+
+static const char meta[] __attribute__((section(".my_meta"), used)) = "meta";
+
+void _start(void) {
+    exit_syscall(42);
+}
@@ -0,0 +1,6 @@
+//#NoSection:.my_meta
+//#ExpectSection:.comment
+
+// Without --keep-section, the unreferenced .my_meta should be garbage collected.
+
+#include "keep_section.c"
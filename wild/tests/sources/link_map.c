@@ -0,0 +1,10 @@
+//#LinkArgs:map:-M
+//#OutputContains:.text           {section-addr:.text}
+//#OutputContains:.text*0x*link_map
+//#OutputContains:{symbol-addr:_start}                _start
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
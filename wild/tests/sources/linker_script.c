@@ -0,0 +1,12 @@
+//#LinkArgs:script:-T ./tests/sources/linker_script.ld
+//#ExpectSection:.text address=0x600000
+//#ExpectEntry:custom_start
+
+// Linked with linker_script.ld, which places `.text` at a fixed address and makes `custom_start` the
+// entry point.
+
+#include "exit.h"
+
+void custom_start(void) {
+    exit_syscall(42);
+}
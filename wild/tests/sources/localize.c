@@ -0,0 +1,23 @@
+//#LinkArgs:localize-symbol:--localize-hidden --localize-symbol=localize_*
+//#ExpectLocalSym:hidden_value localize_me
+//#ExpectGlobalSym:keep_global
+//#SkipLinker:ld
+
+// `--localize-symbol` makes matching symbols local, in addition to the hidden symbols made local
+// by `--localize-hidden`. GNU ld doesn't support these flags.
+
+#include "exit.h"
+
+__attribute__((visibility("hidden"))) int hidden_value = 40;
+
+int localize_me(void) {
+    return 2;
+}
+
+int keep_global(void) {
+    return hidden_value;
+}
+
+void _start(void) {
+    exit_syscall(keep_global() + localize_me());
+}
@@ -0,0 +1,6 @@
+//#LinkArgs:localize-hidden:--localize-hidden
+//#ExpectLocalSym:hidden_value
+//#ExpectGlobalSym:localize_me keep_global
+//#SkipLinker:ld
+
+#include "localize.c"
@@ -0,0 +1,6 @@
+//#ExpectGlobalSym:hidden_value localize_me keep_global
+//#SkipLinker:ld
+
+// Without `--localize-hidden`, we keep hidden symbols global. GNU ld makes them local.
+
+#include "localize.c"
@@ -0,0 +1,19 @@
+// Checks that we can do link-time optimisation using GCC's linker plugin. The objects that GCC
+// produces with -flto contain only GCC's internal representation, so this fails to link unless the
+// plugin compiles them for us.
+
+//#CompArgs:lto:-flto -O2 -fno-stack-protector
+//#LinkArgs:lto:--cc=gcc -flto -O2 -nostdlib -static
+//#InputType: Object, Archive
+
+#include "exit.h"
+
+int lto_value(void);
+
+void _start(void) {
+    exit_syscall(lto_value());
+}
+
+//#ExpectSym: _start .text
+// Nothing references this, so LTO should discard it.
+//#DoesNotContain:lto-unused-marker
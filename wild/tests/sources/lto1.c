@@ -0,0 +1,7 @@
+int lto_value(void) {
+    return 42;
+}
+
+const char *lto_unused(void) {
+    return "lto-unused-marker";
+}
@@ -0,0 +1,18 @@
+//#CompArgs:O2:-O2
+//#ContainsCount:1 MERGE_ME
+
+// Both this file and merge_constants1.c multiply by the same constant, which the compiler puts in a
+// `.rodata.cst8` section in each object. The output should only contain a single copy of it. The
+// constant's bytes spell out `MERGE_ME` so that we can count the copies.
+
+#include "exit.h"
+
+double scale_b(double x);
+
+double __attribute__((noipa)) scale_a(double x) {
+    return x * 0x1.d5f454752454dp+85;
+}
+
+void _start(void) {
+    exit_syscall(scale_a(3.0) == scale_b(3.0) ? 42 : 1);
+}
@@ -0,0 +1,3 @@
+double __attribute__((noipa)) scale_b(double x) {
+    return x * 0x1.d5f454752454dp+85;
+}
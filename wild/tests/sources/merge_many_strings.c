@@ -0,0 +1,60 @@
+//#ContainsCount:1 merge-me-hello
+//#ContainsCount:1 merge-me-world
+
+// Checks that the identical strings from each of the sections in merge_many_strings1.s were merged
+// and that the unique strings were kept.
+
+#include "exit.h"
+
+#define NUM_SECTIONS 8
+
+extern const char hello0[], hello1[], hello2[], hello3[], hello4[], hello5[], hello6[], hello7[];
+extern const char tail0[], tail1[], tail2[], tail3[], tail4[], tail5[], tail6[], tail7[];
+extern const char world0[], world1[], world2[], world3[], world4[], world5[], world6[], world7[];
+extern const char unique0[], unique1[], unique2[], unique3[], unique4[], unique5[], unique6[],
+    unique7[];
+
+static const char* const hellos[NUM_SECTIONS] = {
+    hello0, hello1, hello2, hello3, hello4, hello5, hello6, hello7};
+static const char* const tails[NUM_SECTIONS] = {
+    tail0, tail1, tail2, tail3, tail4, tail5, tail6, tail7};
+static const char* const worlds[NUM_SECTIONS] = {
+    world0, world1, world2, world3, world4, world5, world6, world7};
+static const char* const uniques[NUM_SECTIONS] = {
+    unique0, unique1, unique2, unique3, unique4, unique5, unique6, unique7};
+
+static int str_eq(const char* a, const char* b) {
+    while (*a && *a == *b) {
+        a++;
+        b++;
+    }
+    return *a == *b;
+}
+
+static int starts_with(const char* s, const char* prefix) {
+    while (*prefix && *s == *prefix) {
+        s++;
+        prefix++;
+    }
+    return *prefix == 0;
+}
+
+void _start(void) {
+    char expected_unique[] = "unique-0";
+    for (int i = 0; i < NUM_SECTIONS; i++) {
+        if (hellos[i] != hellos[0] || worlds[i] != worlds[0] || tails[i] != hellos[0] + 9) {
+            exit_syscall(10 + i);
+        }
+        // We check the shared strings in pieces so that our own copies of them don't get counted
+        // by the test.
+        if (!starts_with(hellos[i], "merge-me-") || !str_eq(tails[i], "hello")
+            || !starts_with(worlds[i], "merge-me-") || !str_eq(worlds[i] + 9, "world")) {
+            exit_syscall(20 + i);
+        }
+        expected_unique[7] = '0' + i;
+        if (!str_eq(uniques[i], expected_unique)) {
+            exit_syscall(30 + i);
+        }
+    }
+    exit_syscall(42);
+}
@@ -0,0 +1,115 @@
+// Used by merge_many_strings.c. Each section contains the same two strings, in varying
+// orders, plus a string that's unique to that section. `tailN` points part way into the first of
+// the shared strings.
+
+.section .rodata.str1.1.s0, "aMS", @progbits, 1
+.align 1
+
+.globl hello0
+hello0: .ascii "merge-me-"
+.globl tail0
+tail0: .ascii "hello\0"
+
+.globl world0
+world0: .ascii "merge-me-world\0"
+
+.globl unique0
+unique0: .ascii "unique-0\0"
+
+.section .rodata.str1.1.s1, "aMS", @progbits, 1
+.align 1
+
+.globl unique1
+unique1: .ascii "unique-1\0"
+
+.globl world1
+world1: .ascii "merge-me-world\0"
+
+.globl hello1
+hello1: .ascii "merge-me-"
+.globl tail1
+tail1: .ascii "hello\0"
+
+.section .rodata.str1.1.s2, "aMS", @progbits, 1
+.align 1
+
+.globl hello2
+hello2: .ascii "merge-me-"
+.globl tail2
+tail2: .ascii "hello\0"
+
+.globl world2
+world2: .ascii "merge-me-world\0"
+
+.globl unique2
+unique2: .ascii "unique-2\0"
+
+.section .rodata.str1.1.s3, "aMS", @progbits, 1
+.align 1
+
+.globl unique3
+unique3: .ascii "unique-3\0"
+
+.globl world3
+world3: .ascii "merge-me-world\0"
+
+.globl hello3
+hello3: .ascii "merge-me-"
+.globl tail3
+tail3: .ascii "hello\0"
+
+.section .rodata.str1.1.s4, "aMS", @progbits, 1
+.align 1
+
+.globl hello4
+hello4: .ascii "merge-me-"
+.globl tail4
+tail4: .ascii "hello\0"
+
+.globl world4
+world4: .ascii "merge-me-world\0"
+
+.globl unique4
+unique4: .ascii "unique-4\0"
+
+.section .rodata.str1.1.s5, "aMS", @progbits, 1
+.align 1
+
+.globl unique5
+unique5: .ascii "unique-5\0"
+
+.globl world5
+world5: .ascii "merge-me-world\0"
+
+.globl hello5
+hello5: .ascii "merge-me-"
+.globl tail5
+tail5: .ascii "hello\0"
+
+.section .rodata.str1.1.s6, "aMS", @progbits, 1
+.align 1
+
+.globl hello6
+hello6: .ascii "merge-me-"
+.globl tail6
+tail6: .ascii "hello\0"
+
+.globl world6
+world6: .ascii "merge-me-world\0"
+
+.globl unique6
+unique6: .ascii "unique-6\0"
+
+.section .rodata.str1.1.s7, "aMS", @progbits, 1
+.align 1
+
+.globl unique7
+unique7: .ascii "unique-7\0"
+
+.globl world7
+world7: .ascii "merge-me-world\0"
+
+.globl hello7
+hello7: .ascii "merge-me-"
+.globl tail7
+tail7: .ascii "hello\0"
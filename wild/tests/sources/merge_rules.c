@@ -0,0 +1,21 @@
+//#LinkArgs:no-merge:--no-merge=kept_*
+//#ExpectSection:merged_strings size=10
+//#ExpectSection:kept_strings size=20
+//#ContainsCount:3 duplicate
+//#SkipLinker:ld
+
+#include "exit.h"
+
+// Two string-merge sections, each containing the same string twice. Unless merging is disabled for
+// a section, the duplicates get merged. GNU ld doesn't support `--no-merge`.
+asm(".section merged_strings,\"aMSR\",@progbits,1\n"
+    ".asciz \"duplicate\"\n"
+    ".asciz \"duplicate\"\n"
+    ".section kept_strings,\"aMSR\",@progbits,1\n"
+    ".asciz \"duplicate\"\n"
+    ".asciz \"duplicate\"\n"
+    ".text\n");
+
+void _start(void) {
+    exit_syscall(42);
+}
@@ -0,0 +1,11 @@
+//#CompArgs:pic:-fPIC
+//#InputType:Shared
+//#LinkArgs:no-as-needed:-shared -Bdynamic --no-as-needed
+//#RunEnabled:false
+//#ExpectDynamic:NEEDED */symbolic.0-pic.*.so
+//#ExpectDynamic:NEEDED */as_needed_lib.0-pic.*.so
+
+// Without --as-needed, both shared objects should get DT_NEEDED entries, even though
+// as_needed_lib.c is only referenced from a section that gets garbage collected.
+
+#include "as_needed.c"
@@ -0,0 +1,9 @@
+//#CompArgs:O2:-O2
+//#LinkArgs:no-merge:--no-merge
+//#SkipLinker:ld
+//#ContainsCount:2 MERGE_ME
+
+// With --no-merge, each object's copy of the constant should be kept. GNU ld doesn't support
+// --no-merge.
+
+#include "merge_constants.c"
@@ -0,0 +1,14 @@
+//#LinkArgs:noinhibit-exec:--noinhibit-exec
+//#ExpectError:Undefined strong reference to `exit_syscall`
+//#OutputOnError:true
+//#ExpectEntry:_start
+//#SkipLinker:ld
+
+// We don't link against exit.c, so `exit_syscall` is undefined. With `--noinhibit-exec`, the link
+// should still fail, but only after writing the output. GNU ld reports success.
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
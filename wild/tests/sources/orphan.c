@@ -0,0 +1,13 @@
+//#LinkArgs:orphan-warn:--orphan-handling=warn
+//#OutputContains:warning: *orphan section `.orphan_data
+
+// `.orphan_data` isn't placed by any of our built-in rules, so it's an orphan. With
+// `--orphan-handling=warn`, it's still placed, but we warn about it.
+
+#include "exit.h"
+
+__attribute__((section(".orphan_data"))) int orphan_value = 42;
+
+void _start(void) {
+    exit_syscall(orphan_value);
+}
@@ -0,0 +1,4 @@
+//#LinkArgs:orphan-error:--orphan-handling=error
+//#ExpectError:orphan section `.orphan_data
+
+#include "orphan.c"
@@ -0,0 +1,7 @@
+//#LinkArgs:orphan-place:--orphan-handling=place
+//#ExpectSection:.orphan_data
+//#OutputDoesNotContain:orphan
+
+// `--orphan-handling=place` places orphans without reporting them.
+
+#include "orphan.c"
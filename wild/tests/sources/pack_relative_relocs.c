@@ -0,0 +1,27 @@
+//#CompArgs:pie:-fPIE
+//#LinkArgs:pack:--cc=gcc -pie -Wl,-z,pack-relative-relocs
+//#ExpectDynamic:RELR .relr.dyn
+//#ExpectDynamic:RELRSZ .relr.dyn
+//#NoDynamicReloc:R_X86_64_RELATIVE
+//#ExpectVersionNeed:*libc.so* GLIBC_ABI_DT_RELR
+
+// Links against glibc with `-z pack-relative-relocs`. The relative relocations should all be
+// packed into .relr.dyn. The output should require `GLIBC_ABI_DT_RELR` from libc, so that versions
+// of glibc without DT_RELR support refuse to load it.
+
+// Each of these pointers needs a relative relocation in a PIE. Several are adjacent, so they can
+// share a bitmap in .relr.dyn, while the one far along `far` needs a new address entry.
+
+static int a = 10;
+static int b = 20;
+static int c = 12;
+
+int* ptrs[] = {&a, &b, &c, &a};
+int* far[200] = {[150] = &b};
+
+int main(void) {
+    if (far[150] != &b || ptrs[3] != &a) {
+        return 1;
+    }
+    return *ptrs[0] + *ptrs[1] + *ptrs[2];
+}
@@ -0,0 +1,14 @@
+//#LinkArgs:print-memory-usage:--print-memory-usage
+//#OutputContains:\n  File size *{file-size}\n
+//#OutputContains:\n  .text *{section-size:.text}\n
+//#OutputContains:\n  Dynamic relocations *0\n
+//#SkipLinker:ld
+
+// The sizes reported by --print-memory-usage should match what we write to the output. GNU ld
+// reports memory regions from its linker script instead.
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
@@ -0,0 +1,14 @@
+//#InputType:Relocatable
+//#ExpectSym: get_value .text
+//#ExpectSym: value .data
+
+// relocatable1.c and exit.c are each partially linked with `-r` before being linked with this file.
+// relocatable1.c references `exit_syscall`, which the output of `-r` needs to leave undefined for
+// exit.c to define.
+
+int get_value(void);
+void relocatable_exit(int code);
+
+void _start(void) {
+    relocatable_exit(get_value());
+}
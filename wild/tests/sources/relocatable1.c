@@ -0,0 +1,11 @@
+#include "exit.h"
+
+static int value = 40;
+
+int get_value(void) {
+    return value + 2;
+}
+
+void relocatable_exit(int code) {
+    exit_syscall(code);
+}
@@ -0,0 +1,24 @@
+// Has data in .init_array and .got, which should be covered by GNU_RELRO. We request partial RELRO,
+// however since we always bind eagerly, the whole GOT should still be covered and there should be
+// no writable `.got.plt` left outside the segment.
+
+// With -fPIC, `value` is accessed through the GOT. Turning off relaxable relocations stops us from
+// replacing the GOT access with a direct one. GNU ld still replaces it, so doesn't have a .got.
+//#CompArgs:pic:-fPIC -Wa,-mrelax-relocations=no
+//#SkipLinker:ld
+//#LinkArgs:relro:-z relro -z lazy
+//#ExpectSegment:GNU_RELRO flags=R sections=.init_array,.got end-align=0x1000 excludes=.got.plt
+
+#include "exit.h"
+#include "init.h"
+
+int value = 0;
+
+void __attribute__ ((constructor)) premain() {
+    value = 42;
+}
+
+void _start(void) {
+    call_init_functions();
+    exit_syscall(value);
+}
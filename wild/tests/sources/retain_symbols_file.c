@@ -0,0 +1,11 @@
+//#LinkArgs:retain:--retain-symbols-file=./tests/sources/retain_symbols_file.txt
+//#ExpectSym:exit_syscall .text
+//#NoSym:_start
+
+// Only the symbol listed in retain_symbols_file.txt should end up in the symbol table.
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
@@ -0,0 +1,39 @@
+# Exercises the relocations that compilers commonly emit for RISC-V. `riscv64_test` assembles this
+# with llvm-mc and checks the instructions in the output, since we can't assume that we can run
+# RISC-V binaries.
+
+    .text
+    .globl _start
+    .type _start, @function
+_start:
+    .option push
+    .option norelax
+    lla gp, __global_pointer$
+    .option pop
+    call foo
+    lla a0, data
+.Lpcrel_hi_store:
+    auipc a1, %pcrel_hi(data+8)
+    sd a0, %pcrel_lo(.Lpcrel_hi_store)(a1)
+    .option push
+    .option pic
+    la a2, data
+    .option pop
+    lui a3, %tprel_hi(tvar)
+    add a3, a3, tp, %tprel_add(tvar)
+    addi a3, a3, %tprel_lo(tvar)
+    la.tls.ie a4, tvar2
+    lw a5, size
+    beq a0, a1, foo
+    c.beqz a0, foo
+    c.j foo
+    jal foo
+    li a0, 42
+    li a7, 93
+    ecall
+    .size _start, .-_start
+
+    .data
+    .globl size
+size:
+    .word foo_end - foo
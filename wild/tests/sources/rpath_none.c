@@ -0,0 +1,11 @@
+//#CompArgs:pic:-fPIC
+//#LinkArgs:shared:-shared
+//#RunEnabled:false
+//#NoDynamic:RPATH
+//#NoDynamic:RUNPATH
+//#NoDynamic:FLAGS ORIGIN
+//#NoDynamic:FLAGS_1 ORIGIN
+
+// Without -rpath or -z origin, we shouldn't emit an rpath or set the ORIGIN flags.
+
+#include "symbolic.c"
@@ -0,0 +1,11 @@
+//#CompArgs:pic:-fPIC
+//#LinkArgs:shared:-shared --disable-new-dtags -rpath=/opt/lib
+//#RunEnabled:false
+//#ExpectDynamic:RPATH /opt/lib
+//#NoDynamic:RUNPATH
+//#NoDynamic:FLAGS ORIGIN
+//#NoDynamic:FLAGS_1 ORIGIN
+
+// With --disable-new-dtags, the rpath should be emitted as DT_RPATH.
+
+#include "symbolic.c"
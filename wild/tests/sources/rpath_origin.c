@@ -0,0 +1,13 @@
+//#CompArgs:pic:-fPIC
+//#LinkArgs:shared:-shared -rpath $ORIGIN/../lib
+//#RunEnabled:false
+//#SkipLinker:ld
+//#ExpectDynamic:RUNPATH $ORIGIN/../lib
+//#NoDynamic:RPATH
+//#ExpectDynamic:FLAGS ORIGIN
+//#ExpectDynamic:FLAGS_1 ORIGIN
+
+// An rpath that refers to `$ORIGIN` should be emitted as DT_RUNPATH and should set the ORIGIN flags.
+// GNU ld only sets them with -z origin.
+
+#include "symbolic.c"
@@ -0,0 +1,23 @@
+//#CompArgs:common:-fcommon
+//#LinkArgs:default:
+//#LinkArgs:descending:--sort-common
+//#ExpectSection:.bss size=25
+//#SymbolOrder:c16 c8 c1
+//#SkipLinker:ld
+
+// With `-fcommon`, these tentative definitions become common symbols with alignments of 1, 8 and
+// 16. By default and with `--sort-common`, they should be placed in descending order of alignment,
+// which needs no padding. GNU ld pads the end of `.bss` to its alignment anyway.
+
+#include "exit.h"
+
+char c1;
+long c8;
+long double c16;
+
+void _start(void) {
+    c1 = 1;
+    c8 = 8;
+    c16 = 33;
+    exit_syscall(c1 + c8 + (int)c16);
+}
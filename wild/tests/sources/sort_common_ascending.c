@@ -0,0 +1,8 @@
+//#CompArgs:common:-fcommon
+//#LinkArgs:ascending:--sort-common=ascending
+//#ExpectSection:.bss size=32
+//#SymbolOrder:c1 c8 c16
+
+// Like sort_common.c, but reverses the order, at the cost of padding in a larger `.bss`.
+
+#include "sort_common.c"
@@ -0,0 +1,18 @@
+//#CompArgs:data-sections:-O2 -fdata-sections
+//#SymbolOrder:zebra apple
+
+// Built with `-fdata-sections` so that each variable is in its own section. GCC emits the sections
+// in the reverse of the order in which the variables are defined, so `.data.zebra` comes before
+// `.data.apple` in the object. Without --sort-section, the sections should stay in that order.
+
+#include "exit.h"
+
+int apple = 1;
+char pad1 = 2;
+long long big[2] __attribute__((aligned(32))) = {3, 4};
+char pad2 = 5;
+int zebra = 6;
+
+void _start(void) {
+    exit_syscall(apple + pad1 + big[0] + big[1] + pad2 + zebra == 21 ? 42 : 1);
+}
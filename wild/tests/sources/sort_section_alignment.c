@@ -0,0 +1,13 @@
+//#CompArgs:data-sections:-O2 -fdata-sections
+//#LinkArgs:alignment:--sort-section=alignment
+//#SymbolOrder:big zebra pad2
+//#SymbolOrder:big apple pad1
+//#ExpectSection:.data size=42
+//#SkipLinker:ld
+
+// --sort-section=alignment should place sections in descending order of alignment, so that the only
+// padding in `.data` is from rounding each section up to a multiple of its alignment. Without
+// sorting, GNU ld needs an extra 14 bytes of padding for this program. When sorting, GNU ld doesn't
+// pad `big` up to its alignment, so its `.data` is smaller than ours.
+
+#include "sort_section.c"
@@ -0,0 +1,8 @@
+//#CompArgs:data-sections:-O2 -fdata-sections
+//#LinkArgs:name:--sort-section=name
+//#SymbolOrder:apple zebra
+
+// --sort-section=name should place `.data.apple` before `.data.zebra`, even though it comes later in
+// its object.
+
+#include "sort_section.c"
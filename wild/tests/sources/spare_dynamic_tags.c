@@ -0,0 +1,8 @@
+//#LinkArgs:spare:-shared --spare-dynamic-tags=3
+//#RunEnabled:false
+//#ExpectSpareDynamicTags:3
+//#ExpectSegment:DYNAMIC sections=.dynamic
+
+int spare_dynamic_tags_value(void) {
+    return 42;
+}
@@ -0,0 +1,6 @@
+//#LinkArgs:spare-none:-shared --spare-dynamic-tags=0
+//#RunEnabled:false
+//#ExpectSpareDynamicTags:0
+//#ExpectSegment:DYNAMIC sections=.dynamic
+
+#include "spare_dynamic_tags.c"
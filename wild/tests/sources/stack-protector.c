@@ -0,0 +1,24 @@
+//#CompArgs:global:-fstack-protector-all -mstack-protector-guard=global
+//#CompArgs:tls:-fstack-protector-explicit -mstack-protector-guard=tls
+
+#include "exit.h"
+#include "init_tls.h"
+
+__thread long tls_value = 42;
+
+// In the tls variant, this is the only function that's protected, since the guard is read relative
+// to the thread pointer and nothing else can run before TLS is initialised.
+__attribute__ ((stack_protect)) int fill(char value) {
+    volatile char buffer[64];
+    for (int i = 0; i < sizeof(buffer); i++) {
+        buffer[i] = value;
+    }
+    return buffer[10];
+}
+
+void _start(void) {
+    if (init_tls(0) != 0) {
+        exit_syscall(101);
+    }
+    exit_syscall(fill(tls_value));
+}
@@ -0,0 +1,9 @@
+// Provides the symbols that stack-protected code references. These would normally come from libc.
+
+#include "exit.h"
+
+unsigned long __stack_chk_guard = 0x5a17e5c0ffee;
+
+void __stack_chk_fail(void) {
+    exit_syscall(99);
+}
@@ -0,0 +1,10 @@
+//#LinkArgs:stack-size:-z stack-size=0x800000
+//#ExpectSegment:GNU_STACK flags=RW memsz=0x800000
+
+// The GNU_STACK segment should request the stack size that we asked for and a non-executable stack.
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
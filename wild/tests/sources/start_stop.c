@@ -0,0 +1,21 @@
+//#ExpectSymVisibility:protected __start_foo __stop_foo
+
+// `__start_foo` and `__stop_foo` should bracket the `foo` section and, without
+// `-z start-stop-visibility=`, be protected. start_stop_hidden.c and start_stop_default.c request
+// other visibilities.
+
+#include "exit.h"
+
+static int foo1 __attribute__ ((used, retain, section ("foo"))) = 10;
+static int foo2 __attribute__ ((used, retain, section ("foo"))) = 32;
+
+extern int __start_foo[];
+extern int __stop_foo[];
+
+void _start(void) {
+    int value = 0;
+    for (int *foo = __start_foo; foo < __stop_foo; foo++) {
+        value += *foo;
+    }
+    exit_syscall(value);
+}
@@ -0,0 +1,6 @@
+//#LinkArgs:default:-z start-stop-visibility=default
+//#ExpectSymVisibility:default __start_foo __stop_foo
+
+// Like start_stop.c, but requests default visibility.
+
+#include "start_stop.c"
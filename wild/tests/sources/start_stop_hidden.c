@@ -0,0 +1,6 @@
+//#LinkArgs:hidden:-z start-stop-visibility=hidden
+//#ExpectSymVisibility:hidden __start_foo __stop_foo
+
+// Like start_stop.c, but requests hidden visibility.
+
+#include "start_stop.c"
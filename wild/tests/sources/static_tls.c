@@ -0,0 +1,14 @@
+//#CompArgs:pic-local-exec:-fPIC -ftls-model=local-exec
+//#LinkArgs:shared:-shared
+//#RunEnabled:false
+//#ExpectDynamic:FLAGS STATIC_TLS
+//#SkipLinker:ld
+
+// Accesses to `counter` use an offset from the thread pointer, which requires static TLS. GNU ld
+// refuses to use local-exec TLS in a shared object.
+
+static __thread int counter;
+
+int next_value(void) {
+    return ++counter;
+}
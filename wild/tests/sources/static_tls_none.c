@@ -0,0 +1,10 @@
+//#CompArgs:pic-local-exec:-fPIC -ftls-model=local-exec
+//#LinkArgs:shared:-shared
+//#RunEnabled:false
+//#NoDynamic:FLAGS STATIC_TLS
+
+// Without any TLS accesses, a shared object doesn't need static TLS.
+
+int get_answer(void) {
+    return 42;
+}
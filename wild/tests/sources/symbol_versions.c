@@ -0,0 +1,26 @@
+// Linked into a shared object with a version script that defines two versions, one of which
+// inherits from the other. Each exported symbol should get the version that the script gives it.
+
+//#CompArgs:pic:-fPIC
+//#LinkArgs:versions:-shared --version-script=./tests/sources/symbol_versions.map
+//#RunEnabled:false
+//#ExpectDynamic:VERDEF .gnu.version_d
+//#ExpectDynamic:VERDEFNUM 3
+//#ExpectDynamic:VERSYM .gnu.version
+//#ExpectVersionDef:VERS_1.0
+//#ExpectVersionDef:VERS_2.0 VERS_1.0
+//#ExpectSymVersion:foo VERS_1.0
+//#ExpectSymVersion:bar VERS_2.0
+//#NoDynSym:not_exported
+
+int foo(void) {
+    return 10;
+}
+
+int bar(void) {
+    return 20;
+}
+
+int not_exported(void) {
+    return 30;
+}
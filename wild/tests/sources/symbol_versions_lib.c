@@ -0,0 +1,9 @@
+// Linked into a shared object for symbol_versions_main.c with the version script from
+// symbol_versions.c. We always link it with wild, so that we can check that GNU ld understands our
+// version definitions.
+
+//#OverrideCompArgs:-fPIC
+//#SharedLinker:wild
+//#SharedLinkArgs:--version-script=./tests/sources/symbol_versions.map
+
+#include "symbol_versions.c"
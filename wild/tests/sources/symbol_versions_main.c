@@ -0,0 +1,19 @@
+// Linked against a shared object that wild built from symbol_versions_lib.c, so with GNU ld, this
+// checks that it understands our version definitions. It should require the versions of the
+// symbols that it uses from the shared object, as well as those that it uses from libc.
+
+//#InputType:Shared
+//#LinkArgs:cc:--cc=gcc
+//#ExpectVersionNeed:*symbol_versions_lib* VERS_1.0
+//#ExpectVersionNeed:*symbol_versions_lib* VERS_2.0
+//#ExpectVersionNeed:*libc.so* GLIBC_2.34
+//#ExpectSymVersion:foo VERS_1.0
+//#ExpectSymVersion:bar VERS_2.0
+//#ExpectSymVersion:__libc_start_main GLIBC_2.34
+
+int foo(void);
+int bar(void);
+
+int main(void) {
+    return foo() + bar() + 12;
+}
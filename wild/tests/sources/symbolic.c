@@ -0,0 +1,23 @@
+//#CompArgs:pic:-fPIC
+//#LinkArgs:symbolic:-shared -Bsymbolic
+//#RunEnabled:false
+//#ExpectDynamic:FLAGS SYMBOLIC
+//#NoDynamicReloc:* *
+
+// Linked as a shared object with -Bsymbolic. References to `value` and `get_value` from within the
+// shared object should be bound at link time, so there should be no dynamic relocations against
+// them.
+
+int value = 42;
+
+int get_value(void) {
+    return value;
+}
+
+int call_get_value(void) {
+    return get_value();
+}
+
+int* value_address(void) {
+    return &value;
+}
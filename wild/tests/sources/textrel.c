@@ -0,0 +1,20 @@
+//#LinkArgs:notext:-shared -z notext
+//#RunEnabled:false
+//#ExpectDynamic:TEXTREL
+//#ExpectDynamic:FLAGS TEXTREL
+//#ExpectDynamicRelocIn:.text
+//#OutputDoesNotContain:text relocation
+
+// The absolute relocation in `.text` can't be resolved until we know where the shared object is
+// loaded, so needs a text relocation. `-z notext` permits it, in which case the output should be
+// flagged as having text relocations. We only warn about them with `--warn-textrel`.
+
+int textrel_value = 42;
+
+__asm__(
+    ".text\n"
+    ".globl get_textrel_address\n"
+    ".type get_textrel_address, @function\n"
+    "get_textrel_address:\n"
+    "    movabs $textrel_value, %rax\n"
+    "    ret\n");
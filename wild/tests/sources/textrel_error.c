@@ -0,0 +1,8 @@
+//#LinkArgs:default:-shared
+//#LinkArgs:text:-shared -z text
+//#ExpectError:requires a text relocation
+//#SkipLinker:ld
+
+// Text relocations are an error unless `-z notext` is given. GNU ld only warns by default.
+
+#include "textrel.c"
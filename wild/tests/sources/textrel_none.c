@@ -0,0 +1,11 @@
+//#LinkArgs:notext:-shared -z notext
+//#RunEnabled:false
+//#NoDynamic:TEXTREL
+//#NoDynamic:FLAGS TEXTREL
+
+// Nothing here needs a text relocation, so even with `-z notext`, the output shouldn't be flagged
+// as having them.
+
+int no_textrel_value(void) {
+    return 42;
+}
@@ -0,0 +1,8 @@
+//#LinkArgs:warn-textrel:-shared -z notext --warn-textrel
+//#RunEnabled:false
+//#OutputContains:warning: *symbol `textrel_value` *section `.text` *creates a text relocation
+//#SkipLinker:ld
+
+// GNU ld words its warning differently.
+
+#include "textrel.c"
@@ -0,0 +1,9 @@
+// Links exit.c from a thin archive, which refers to its member by a path relative to the archive.
+
+//#InputType:ThinArchive
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
@@ -0,0 +1,19 @@
+// Pointers in data sections of their own. In position-independent outputs, these need dynamic
+// relocations, which we write from several sections at once when linking with multiple threads.
+
+int add_a(int x);
+int add_b(int x);
+
+static int a = 1;
+static int b = 2;
+static int c = 3;
+
+int* ptrs1[] = {&a, &b, &c};
+int* ptrs2[] = {&c, &b};
+int* ptrs3[64] = {[40] = &a};
+int (*fns1[])(int) = {add_a, add_b};
+int (*fns2[])(int) = {add_b};
+
+extern const char hello0[], world3[], tail5[], unique7[];
+
+const char* strings[] = {hello0, world3, tail5, unique7};
@@ -0,0 +1,36 @@
+//#CompArgs:global-dynamic:-fPIC -ftls-model=global-dynamic
+//#CompArgs:local-dynamic:-fPIC -ftls-model=local-dynamic
+//#CompArgs:initial-exec:-fPIC -ftls-model=initial-exec
+//#CompArgs:local-exec:-fPIC -ftls-model=local-exec
+//#ExpectSegment:TLS sections=.tdata,.tbss align=32
+
+#include "exit.h"
+#include "init_tls.h"
+
+// Uses each TLS model, which we relax to local-exec. Without -fPIC, GCC would use local-exec or
+// initial-exec instead of the dynamic models. The TLS segment's file size should cover only .tdata,
+// while its memory size and alignment cover .tbss as well.
+
+// A mix of initialised variables, which go in .tdata, and zero-initialised variables, which go in
+// .tbss. The most-aligned variable is in .tbss, so the alignment of the TLS segment comes from
+// there.
+__thread char tdata_char = 1;
+__thread int tdata_array[4] = {1, 2, 3, 4};
+__thread long long tbss_aligned __attribute__((aligned(32)));
+__thread int tbss_int;
+
+void _start(void) {
+    int ret = init_tls(0);
+    if (ret != 0) {
+        exit_syscall(ret);
+    }
+    if (((long)&tbss_aligned & 31) != 0) {
+        exit_syscall(10);
+    }
+    if (tbss_aligned != 0 || tbss_int != 0) {
+        exit_syscall(11);
+    }
+    tbss_int = 5;
+    // Accessing a constant index of the array produces a TLS relocation with a non-zero addend.
+    exit_syscall(tdata_char + tdata_array[2] + tbss_int + 33);
+}
@@ -0,0 +1,14 @@
+//#InputType:Archive
+//#LinkArgs:trace:-y exit_syscall
+//#OutputContains:/trace_symbol.*.o: reference to exit_syscall\n*/exit.*.a @ exit.*.o: definition of exit_syscall\n
+//#SkipLinker:ld
+
+// `exit_syscall` is defined in an archive member. With `-y exit_syscall`, we should report both the
+// reference that caused the member to be loaded and the definition. GNU ld names archive members
+// differently.
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
@@ -0,0 +1,6 @@
+// Nothing references `force_loaded`, so this member should only be loaded when `force_loaded` is
+// named via `-u`.
+
+int force_loaded(void) {
+    return 7;
+}
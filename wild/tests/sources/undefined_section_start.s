@@ -0,0 +1,6 @@
+// Used by `link_error_test`. References the start of a custom section that no input defines.
+
+.section .text,"ax",@progbits
+.globl _start
+_start:
+    .quad __start_missing_section
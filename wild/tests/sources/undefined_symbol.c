@@ -0,0 +1,13 @@
+//#InputType:Archive
+//#LinkArgs:u:-u force_loaded
+//#LinkArgs:long:--undefined=force_loaded
+//#ExpectSym:force_loaded .text
+
+// Nothing references `force_loaded`, but naming it with `-u` should cause the archive member that
+// defines it to be loaded.
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
@@ -0,0 +1,8 @@
+//#LinkArgs:no-undefined:--no-undefined -u does_not_exist
+//#ExpectError:Symbol `does_not_exist` was specified via -u
+//#SkipLinker:ld
+
+// With `--no-undefined`, naming a symbol that isn't defined anywhere with `-u` is an error. GNU ld
+// only reports undefined references from input files.
+
+#include "undefined_symbol.c"
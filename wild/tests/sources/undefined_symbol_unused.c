@@ -0,0 +1,9 @@
+//#InputType:Archive
+//#LinkArgs:none:
+//#LinkArgs:missing:-u does_not_exist
+//#NoSym:force_loaded
+
+// Without `-u force_loaded`, the archive member that defines it shouldn't be loaded. Naming a symbol
+// that isn't defined anywhere with `-u` is allowed.
+
+#include "undefined_symbol.c"
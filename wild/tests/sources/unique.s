@@ -0,0 +1,35 @@
+//#ExpectUniqueSym:unique_value
+//#SkipLinker:ld
+
+// This file and unique1.s both define `unique_value` with `STB_GNU_UNIQUE` binding outside of any
+// group. The definitions should be merged, with the first one winning, so that both files see the
+// same address. GNU ld reports them as duplicates.
+
+.data
+.globl unique_value
+.type unique_value, @gnu_unique_object
+.size unique_value, 4
+unique_value:
+    .long 40
+
+.text
+.globl get_a
+.type get_a, @function
+get_a:
+    mov unique_value@GOTPCREL(%rip), %rax
+    ret
+
+.globl _start
+.type _start, @function
+_start:
+    call get_a
+    mov %rax, %rbx
+    call get_b
+    mov $1, %edi
+    cmp %rax, %rbx
+    jne 1f
+    mov (%rax), %edi
+    add $2, %edi
+1:
+    mov $60, %eax
+    syscall
@@ -0,0 +1,15 @@
+// See unique.s.
+
+.data
+.globl unique_value
+.type unique_value, @gnu_unique_object
+.size unique_value, 4
+unique_value:
+    .long 7
+
+.text
+.globl get_b
+.type get_b, @function
+get_b:
+    mov unique_value@GOTPCREL(%rip), %rax
+    ret
@@ -0,0 +1,12 @@
+//#LinkArgs:shared:-shared
+//#RunEnabled:false
+//#ExpectUniqueSym:unique_value
+//#ExpectDynamic:FLAGS_1 NODELETE
+//#SkipLinker:ld
+
+// Links unique.s and unique1.s into a shared object. Since it exports a unique symbol, it should be
+// marked as not to be unloaded. GNU ld reports the unique definitions as duplicates.
+
+int unique_shared_value(void) {
+    return 42;
+}
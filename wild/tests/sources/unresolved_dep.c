@@ -0,0 +1 @@
+int unresolved_dep_value(void) { return 42; }
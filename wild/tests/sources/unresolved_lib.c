@@ -0,0 +1,3 @@
+int unresolved_dep_value(void);
+
+int unresolved_lib_value(void) { return unresolved_dep_value(); }
@@ -0,0 +1,3 @@
+int unresolved_lib_value(void);
+
+int unresolved_main_value(void) { return unresolved_lib_value(); }
@@ -0,0 +1,7 @@
+// Used by `link_error_test`. R_X86_64_GOTPC64 isn't a relocation type that we support.
+
+.section .text,"ax",@progbits
+.globl _start
+_start:
+    .reloc ., R_X86_64_GOTPC64, _start
+    .quad 0
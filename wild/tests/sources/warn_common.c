@@ -0,0 +1,19 @@
+//#CompArgs:common:-fcommon
+//#LinkArgs:warn-common:--warn-common
+//#OutputContains:multiple common of `same_size`
+//#OutputContains:common of `smaller` in *overridden by larger common
+//#OutputContains:common of `overridden` in *overridden by definition
+//#SkipLinker:ld
+
+// Each of these common symbols collides with a symbol in warn_common1.c, so `--warn-common` should
+// report them. GNU ld words its warnings differently.
+
+#include "exit.h"
+
+int same_size;
+int smaller[2];
+int overridden;
+
+void _start(void) {
+    exit_syscall(42 + same_size + smaller[0] + overridden - 5);
+}
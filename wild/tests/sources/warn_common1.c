@@ -0,0 +1,5 @@
+// Each of these collides with a common symbol in warn_common.c.
+
+int same_size;
+int smaller[10];
+int overridden = 5;
@@ -0,0 +1,8 @@
+//#CompArgs:common:-fcommon
+//#LinkArgs:fatal-warnings:--warn-common --fatal-warnings
+//#ExpectError:multiple common of `same_size
+
+// `--fatal-warnings` turns the warnings from `--warn-common` into an error, but they should still
+// be reported.
+
+#include "warn_common.c"
@@ -0,0 +1,6 @@
+//#CompArgs:common:-fcommon
+//#OutputDoesNotContain:warning
+
+// Without `--warn-common`, colliding common symbols aren't reported.
+
+#include "warn_common.c"
@@ -0,0 +1,11 @@
+//#InputType:Archive
+//#ExpectSym:whole_archive_member .text
+
+// Nothing references `whole_archive_member`, but it should be loaded since its archive is linked
+// with --whole-archive.
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
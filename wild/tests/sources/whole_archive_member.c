@@ -0,0 +1,6 @@
+// Nothing references `whole_archive_member`, so this member should only be loaded with
+// --whole-archive. The function is marked as retained so that it's kept once the member is loaded.
+
+__attribute__((retain)) int whole_archive_member(void) {
+    return 8;
+}
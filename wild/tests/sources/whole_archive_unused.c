@@ -0,0 +1,8 @@
+//#InputType:Archive
+//#NoSym:whole_archive_member
+
+// Used by programs where --whole-archive isn't in effect for the archive containing
+// `whole_archive_member`, either because of --no-whole-archive or because it was popped by
+// --pop-state. The member shouldn't be loaded.
+
+#include "whole_archive.c"
@@ -0,0 +1,36 @@
+//#ExpectSection:sec_65299
+//#ExpectSym:_start .text
+//#ExpectSym:xindex_value last_section
+//#ExpectSym:sym_0 sec_0
+//#ExpectSym:sym_65299 sec_65299
+
+// Defines more sections than fit in a 16 bit section index, so the assembler has to use SHN_XINDEX
+// for symbols in the later sections. Each section has a C identifier as its name, so each becomes a
+// separate output section and we need to use extended section indexes in our output too. If we
+// didn't store the section count in the first section header, the later sections wouldn't be found.
+
+.altmacro
+.macro section_with_symbol n
+    .section sec_\n,"aR"
+    .globl sym_\n
+sym_\n:
+    .byte 1
+.endm
+
+.set i, 0
+.rept 65300
+    section_with_symbol %i
+    .set i, i + 1
+.endr
+
+    .section last_section,"aR"
+    .globl xindex_value
+xindex_value:
+    .long 42
+
+    .text
+    .globl _start
+_start:
+    mov xindex_value(%rip), %edi
+    mov $60, %eax
+    syscall
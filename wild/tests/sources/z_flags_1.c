@@ -0,0 +1,11 @@
+//#LinkArgs:z-flags:-shared -z nodelete -z initfirst
+//#RunEnabled:false
+//#ExpectDynamic:FLAGS_1 NODELETE,INITFIRST,NOW
+//#SkipLinker:ld
+
+// Requesting extra DT_FLAGS_1 bits shouldn't lose the NOW flag that we always set. GNU ld only sets
+// NOW with `-z now`.
+
+int z_flags_1_value(void) {
+    return 42;
+}
@@ -0,0 +1,5 @@
+//#LinkArgs:shared:-shared
+//#RunEnabled:false
+//#NoDynamic:FLAGS_1 NODELETE,INITFIRST
+
+#include "z_flags_1.c"
@@ -0,0 +1,11 @@
+//#CompArgs:pic:-fPIC
+//#LinkArgs:shared:-shared -z origin
+//#RunEnabled:false
+//#NoDynamic:RPATH
+//#NoDynamic:RUNPATH
+//#ExpectDynamic:FLAGS ORIGIN
+//#ExpectDynamic:FLAGS_1 ORIGIN
+
+// -z origin should set the ORIGIN flags even without an rpath.
+
+#include "symbolic.c"
@@ -0,0 +1,385 @@
+//! AArch64-specific parts of linking. Relocations on AArch64 mostly store their values in bit
+//! fields of 4-byte instructions, often split across a pair of instructions, one of which holds the
+//! page (4 KiB) of the target relative to the page of the instruction and the other the low 12
+//! bits of the target. See "ELF for the Arm 64-bit Architecture (AArch64)" for details.
+
+use crate::args::OutputKind;
+use crate::elf::RelocationKind;
+use crate::elf::RelocationKindInfo;
+use crate::elf::RelocationSize;
+use crate::error::LinkError;
+use crate::error::Result;
+use crate::relaxation::Relaxation;
+use crate::resolution::ValueKind;
+use anyhow::bail;
+use object::elf::*;
+
+/// A PLT entry loads the address of the function from its GOT entry into x17 and jumps there. The
+/// GOT address gets filled in by `write_plt_entry`.
+pub(crate) const PLT_ENTRY_TEMPLATE: &[u8] = &[
+    0x10, 0x00, 0x00, 0x90, // adrp x16, {GOT page}
+    0x11, 0x02, 0x40, 0xf9, // ldr x17, [x16, {GOT page offset}]
+    0x10, 0x02, 0x00, 0x91, // add x16, x16, {GOT page offset}
+    0x20, 0x02, 0x1f, 0xd6, // br x17
+];
+
+const NOP: u32 = 0xd503_201f;
+/// `movz x0, #0, lsl #16`
+const MOVZ_X0_LSL_16: u32 = 0xd2a0_0000;
+/// `movk x0, #0`
+const MOVK_X0: u32 = 0xf280_0000;
+/// `adrp x0, 0`
+const ADRP_X0: u32 = 0x9000_0000;
+/// `ldr x0, [x0]`
+const LDR_X0: u32 = 0xf940_0000;
+
+/// The different ways in which a relocation can store its value in an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Instruction {
+    /// ADRP. Stores bits 12..33 of the offset from the page containing the instruction to the page
+    /// containing the target.
+    Adrp,
+    /// ADR. Stores a 21 bit offset from the instruction to the target.
+    Adr,
+    /// ADD or a load / store with an unsigned immediate. Stores the low 12 bits of the target,
+    /// shifted right by `shift`, which is the log2 of the size of the data being accessed.
+    Lo12 { shift: u32 },
+    /// ADD with its immediate shifted left by 12. Stores bits 12..23 of the value.
+    Hi12,
+    /// B or BL. Stores a 26 bit offset, in words, from the instruction to the target.
+    Branch26,
+    /// B.cond, CBZ, CBNZ or LDR (literal). Stores a 19 bit offset, in words.
+    Branch19,
+    /// TBZ or TBNZ. Stores a 14 bit offset, in words.
+    Branch14,
+    /// MOVZ or MOVK. Stores bits `shift..shift + 16` of the value.
+    Movw { shift: u32 },
+}
+
+pub(crate) fn relocation_kind_info(r_type: u32) -> Result<RelocationKindInfo> {
+    use Instruction as I;
+    use RelocationKind as K;
+    // Relocations that store the low 12 bits of an absolute address use `RelocationKind::Relative`.
+    // The low bits of an address don't change when the output is loaded at a different address,
+    // since that address will be page aligned, so there's no need for a dynamic relocation.
+    let (kind, size) = match r_type {
+        R_AARCH64_NONE => (K::None, RelocationSize::ByteSize(0)),
+        R_AARCH64_ABS64 => (K::Absolute, RelocationSize::ByteSize(8)),
+        R_AARCH64_ABS32 => (K::Absolute, RelocationSize::ByteSize(4)),
+        R_AARCH64_ABS16 => (K::Absolute, RelocationSize::ByteSize(2)),
+        R_AARCH64_PREL64 => (K::Relative, RelocationSize::ByteSize(8)),
+        R_AARCH64_PREL32 => (K::Relative, RelocationSize::ByteSize(4)),
+        R_AARCH64_PREL16 => (K::Relative, RelocationSize::ByteSize(2)),
+        R_AARCH64_TLS_DTPREL => (K::DtpOff, RelocationSize::ByteSize(8)),
+        _ => {
+            let (kind, instruction) = match r_type {
+                R_AARCH64_MOVW_UABS_G0 | R_AARCH64_MOVW_UABS_G0_NC => {
+                    (K::Absolute, I::Movw { shift: 0 })
+                }
+                R_AARCH64_MOVW_UABS_G1 | R_AARCH64_MOVW_UABS_G1_NC => {
+                    (K::Absolute, I::Movw { shift: 16 })
+                }
+                R_AARCH64_MOVW_UABS_G2 | R_AARCH64_MOVW_UABS_G2_NC => {
+                    (K::Absolute, I::Movw { shift: 32 })
+                }
+                R_AARCH64_MOVW_UABS_G3 => (K::Absolute, I::Movw { shift: 48 }),
+                R_AARCH64_LD_PREL_LO19 => (K::Relative, I::Branch19),
+                R_AARCH64_ADR_PREL_LO21 => (K::Relative, I::Adr),
+                R_AARCH64_ADR_PREL_PG_HI21 | R_AARCH64_ADR_PREL_PG_HI21_NC => {
+                    (K::Relative, I::Adrp)
+                }
+                R_AARCH64_ADD_ABS_LO12_NC | R_AARCH64_LDST8_ABS_LO12_NC => {
+                    (K::Relative, I::Lo12 { shift: 0 })
+                }
+                R_AARCH64_LDST16_ABS_LO12_NC => (K::Relative, I::Lo12 { shift: 1 }),
+                R_AARCH64_LDST32_ABS_LO12_NC => (K::Relative, I::Lo12 { shift: 2 }),
+                R_AARCH64_LDST64_ABS_LO12_NC => (K::Relative, I::Lo12 { shift: 3 }),
+                R_AARCH64_LDST128_ABS_LO12_NC => (K::Relative, I::Lo12 { shift: 4 }),
+                R_AARCH64_TSTBR14 => (K::Relative, I::Branch14),
+                R_AARCH64_CONDBR19 => (K::Relative, I::Branch19),
+                R_AARCH64_JUMP26 | R_AARCH64_CALL26 => (K::PltRelative, I::Branch26),
+                R_AARCH64_ADR_GOT_PAGE => (K::GotRelative, I::Adrp),
+                R_AARCH64_LD64_GOT_LO12_NC => (K::GotRelative, I::Lo12 { shift: 3 }),
+                R_AARCH64_TLSGD_ADR_PAGE21 => (K::TlsGd, I::Adrp),
+                R_AARCH64_TLSGD_ADD_LO12_NC => (K::TlsGd, I::Lo12 { shift: 0 }),
+                R_AARCH64_TLSLD_ADR_PAGE21 => (K::TlsLd, I::Adrp),
+                R_AARCH64_TLSLD_ADD_LO12_NC => (K::TlsLd, I::Lo12 { shift: 0 }),
+                R_AARCH64_TLSLD_ADD_DTPREL_HI12 => (K::DtpOff, I::Hi12),
+                R_AARCH64_TLSLD_ADD_DTPREL_LO12 | R_AARCH64_TLSLD_ADD_DTPREL_LO12_NC => {
+                    (K::DtpOff, I::Lo12 { shift: 0 })
+                }
+                R_AARCH64_TLSIE_ADR_GOTTPREL_PAGE21 => (K::GotTpOff, I::Adrp),
+                R_AARCH64_TLSIE_LD64_GOTTPREL_LO12_NC => (K::GotTpOff, I::Lo12 { shift: 3 }),
+                R_AARCH64_TLSLE_MOVW_TPREL_G2 => (K::TpOff, I::Movw { shift: 32 }),
+                R_AARCH64_TLSLE_MOVW_TPREL_G1 | R_AARCH64_TLSLE_MOVW_TPREL_G1_NC => {
+                    (K::TpOff, I::Movw { shift: 16 })
+                }
+                R_AARCH64_TLSLE_MOVW_TPREL_G0 | R_AARCH64_TLSLE_MOVW_TPREL_G0_NC => {
+                    (K::TpOff, I::Movw { shift: 0 })
+                }
+                R_AARCH64_TLSLE_ADD_TPREL_HI12 => (K::TpOff, I::Hi12),
+                R_AARCH64_TLSLE_ADD_TPREL_LO12 | R_AARCH64_TLSLE_ADD_TPREL_LO12_NC => {
+                    (K::TpOff, I::Lo12 { shift: 0 })
+                }
+                R_AARCH64_TLSLE_LDST8_TPREL_LO12 | R_AARCH64_TLSLE_LDST8_TPREL_LO12_NC => {
+                    (K::TpOff, I::Lo12 { shift: 0 })
+                }
+                R_AARCH64_TLSLE_LDST16_TPREL_LO12 | R_AARCH64_TLSLE_LDST16_TPREL_LO12_NC => {
+                    (K::TpOff, I::Lo12 { shift: 1 })
+                }
+                R_AARCH64_TLSLE_LDST32_TPREL_LO12 | R_AARCH64_TLSLE_LDST32_TPREL_LO12_NC => {
+                    (K::TpOff, I::Lo12 { shift: 2 })
+                }
+                R_AARCH64_TLSLE_LDST64_TPREL_LO12 | R_AARCH64_TLSLE_LDST64_TPREL_LO12_NC => {
+                    (K::TpOff, I::Lo12 { shift: 3 })
+                }
+                _ => return Err(LinkError::UnsupportedRelocation { r_type }.into()),
+            };
+            (kind, RelocationSize::AArch64(instruction))
+        }
+    };
+    Ok(RelocationKindInfo { kind, size })
+}
+
+/// Returns a relaxation for the relocation `r_type`, if there's one that we can apply.
+pub(crate) fn relaxation(
+    r_type: u32,
+    value_kind: ValueKind,
+    output_kind: OutputKind,
+) -> Option<(Relaxation, RelocationKindInfo)> {
+    use Instruction as I;
+    use RelocationKind as K;
+    let is_executable = output_kind != OutputKind::SharedObject;
+    let is_local = value_kind != ValueKind::Dynamic;
+    let (relaxation, kind, size) = match r_type {
+        // Calls to functions defined in an executable can go directly to the function rather than
+        // via the PLT.
+        R_AARCH64_JUMP26 | R_AARCH64_CALL26 if is_executable && is_local => (
+            Relaxation::NoOp,
+            K::Relative,
+            RelocationSize::AArch64(I::Branch26),
+        ),
+        // An executable doesn't need TLS descriptors, since variables are at fixed offsets from the
+        // thread pointer. If the variable is in the executable, we know that offset at link time,
+        // otherwise we load it from the GOT. In both cases, the result ends up in x0, which is
+        // where the TLS descriptor call would have left it.
+        R_AARCH64_TLSDESC_ADR_PAGE21 if is_executable && is_local => (
+            Relaxation::ReplaceInstruction(MOVZ_X0_LSL_16),
+            K::TpOff,
+            RelocationSize::AArch64(I::Movw { shift: 16 }),
+        ),
+        R_AARCH64_TLSDESC_LD64_LO12 if is_executable && is_local => (
+            Relaxation::ReplaceInstruction(MOVK_X0),
+            K::TpOff,
+            RelocationSize::AArch64(I::Movw { shift: 0 }),
+        ),
+        R_AARCH64_TLSDESC_ADR_PAGE21 if is_executable => (
+            Relaxation::ReplaceInstruction(ADRP_X0),
+            K::GotTpOff,
+            RelocationSize::AArch64(I::Adrp),
+        ),
+        R_AARCH64_TLSDESC_LD64_LO12 if is_executable => (
+            Relaxation::ReplaceInstruction(LDR_X0),
+            K::GotTpOff,
+            RelocationSize::AArch64(I::Lo12 { shift: 3 }),
+        ),
+        R_AARCH64_TLSDESC_ADD_LO12 | R_AARCH64_TLSDESC_CALL if is_executable => (
+            Relaxation::ReplaceInstruction(NOP),
+            K::None,
+            RelocationSize::ByteSize(0),
+        ),
+        _ => return None,
+    };
+    Some((relaxation, RelocationKindInfo { kind, size }))
+}
+
+/// Stores `value` into `instruction`. `value` is the value computed for the relocation, which for a
+/// PC-relative relocation is an offset from `place`, the address of the instruction.
+pub(crate) fn write_instruction(
+    out: &mut [u8],
+    instruction: Instruction,
+    value: u64,
+    place: u64,
+    is_pc_relative: bool,
+) -> Result {
+    let Some(bytes) = out.first_chunk_mut::<4>() else {
+        bail!("Relocation outside of bounds of section");
+    };
+    let (mask, bits) = instruction.encode(value, place, is_pc_relative)?;
+    let existing = u32::from_le_bytes(*bytes);
+    *bytes = (existing & !mask | bits).to_le_bytes();
+    Ok(())
+}
+
+impl Instruction {
+    /// Returns a mask of the bits of the instruction that hold the value and what those bits
+    /// should be set to.
+    fn encode(self, value: u64, place: u64, is_pc_relative: bool) -> Result<(u32, u32)> {
+        let target = if is_pc_relative {
+            value.wrapping_add(place)
+        } else {
+            value
+        };
+        Ok(match self {
+            Instruction::Adrp => {
+                let offset = value.wrapping_add(place & 0xfff) as i64 >> 12;
+                check_range(self, offset, 21)?;
+                encode_adr(offset)
+            }
+            Instruction::Adr => {
+                check_range(self, value as i64, 21)?;
+                encode_adr(value as i64)
+            }
+            Instruction::Lo12 { shift } => (0xfff << 10, ((target as u32 & 0xfff) >> shift) << 10),
+            Instruction::Hi12 => {
+                if value >= 1 << 24 {
+                    bail!("Value 0x{value:x} is out of range for {self:?}");
+                }
+                (0xfff << 10, ((value >> 12) as u32 & 0xfff) << 10)
+            }
+            Instruction::Branch26 => encode_branch(self, value, 26, 0)?,
+            Instruction::Branch19 => encode_branch(self, value, 19, 5)?,
+            Instruction::Branch14 => encode_branch(self, value, 14, 5)?,
+            Instruction::Movw { shift } => (0xffff << 5, ((value >> shift) as u32 & 0xffff) << 5),
+        })
+    }
+}
+
+/// Encodes a 21 bit immediate for ADR or ADRP. The low 2 bits go in bits 29..31 and the rest in
+/// bits 5..24.
+fn encode_adr(imm: i64) -> (u32, u32) {
+    let imm = imm as u32;
+    (
+        0x3 << 29 | 0x7ffff << 5,
+        (imm & 0x3) << 29 | ((imm >> 2) & 0x7ffff) << 5,
+    )
+}
+
+fn encode_branch(instruction: Instruction, offset: u64, bits: u32, lsb: u32) -> Result<(u32, u32)> {
+    let offset = offset as i64;
+    if offset & 0x3 != 0 {
+        bail!("Branch offset 0x{offset:x} for {instruction:?} isn't a multiple of 4");
+    }
+    let words = offset >> 2;
+    check_range(instruction, words, bits)?;
+    let mask = ((1u32 << bits) - 1) << lsb;
+    Ok((mask, ((words as u32) << lsb) & mask))
+}
+
+/// Checks that `value` fits in a signed immediate of `bits` bits.
+fn check_range(instruction: Instruction, value: i64, bits: u32) -> Result {
+    let limit = 1i64 << (bits - 1);
+    if !(-limit..limit).contains(&value) {
+        bail!("Relocation value 0x{value:x} is out of range for {instruction:?}");
+    }
+    Ok(())
+}
+
+/// Fills in the GOT address in a PLT entry that was copied from `PLT_ENTRY_TEMPLATE`.
+pub(crate) fn write_plt_entry(plt_entry: &mut [u8], got_address: u64, plt_address: u64) -> Result {
+    let got_offset = got_address.wrapping_sub(plt_address);
+    write_instruction(plt_entry, Instruction::Adrp, got_offset, plt_address, true)?;
+    for (offset, instruction) in [
+        (4, Instruction::Lo12 { shift: 3 }),
+        (8, Instruction::Lo12 { shift: 0 }),
+    ] {
+        write_instruction(
+            &mut plt_entry[offset..],
+            instruction,
+            got_address,
+            plt_address + offset as u64,
+            false,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn encode(
+        instruction: u32,
+        kind: Instruction,
+        value: u64,
+        place: u64,
+        pc_relative: bool,
+    ) -> u32 {
+        let mut bytes = instruction.to_le_bytes();
+        write_instruction(&mut bytes, kind, value, place, pc_relative).unwrap();
+        u32::from_le_bytes(bytes)
+    }
+
+    #[test]
+    fn test_encode_instructions() {
+        // adrp x0, <page 0x3 pages after the instruction's page>
+        assert_eq!(
+            encode(0x9000_0000, Instruction::Adrp, 0x2f00, 0x400_180, true),
+            0xf000_0000
+        );
+        // A backwards page offset.
+        assert_eq!(
+            encode(
+                0x9000_0000,
+                Instruction::Adrp,
+                (-0x1000i64) as u64,
+                0x400_000,
+                true
+            ),
+            0xf0ff_ffe0
+        );
+        // add x0, x0, #0x123
+        assert_eq!(
+            encode(
+                0x9100_0000,
+                Instruction::Lo12 { shift: 0 },
+                0x401_123,
+                0,
+                false
+            ),
+            0x9104_8c00
+        );
+        // ldr x1, [x0, #0x18]
+        assert_eq!(
+            encode(
+                0xf940_0001,
+                Instruction::Lo12 { shift: 3 },
+                0x18,
+                0x400_000,
+                true
+            ),
+            0xf940_0001 | (0x18 >> 3) << 10
+        );
+        // bl .-8
+        assert_eq!(
+            encode(0x9400_0000, Instruction::Branch26, (-8i64) as u64, 0, true),
+            0x97ff_fffe
+        );
+        // movk x0, #0x1234, lsl #16
+        assert_eq!(
+            encode(
+                0xf2a0_0000,
+                Instruction::Movw { shift: 16 },
+                0x1234_5678,
+                0,
+                false
+            ),
+            0xf2a2_4680
+        );
+        let mut bytes = 0x9400_0000u32.to_le_bytes();
+        assert!(write_instruction(&mut bytes, Instruction::Branch26, 1 << 28, 0, true).is_err());
+    }
+
+    #[test]
+    fn test_plt_entry() {
+        let mut entry = PLT_ENTRY_TEMPLATE.to_owned();
+        write_plt_entry(&mut entry, 0x412_018, 0x401_010).unwrap();
+        let words: Vec<u32> = entry
+            .chunks(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        // adrp x16, 0x412000; ldr x17, [x16, #0x18]; add x16, x16, #0x18; br x17
+        assert_eq!(words, [0xb000_0090, 0xf940_0e11, 0x9100_6210, 0xd61f_0220]);
+    }
+}
@@ -0,0 +1,137 @@
+//! Handling of `.note.ABI-tag` sections. These say which OS and minimum kernel version the code
+//! needs. Usually only `crt1.o` has one, but if several inputs do, we emit a single note that
+//! requires the newest kernel version that any of them requires.
+
+use crate::elf::Section;
+use crate::error::Result;
+use anyhow::bail;
+use object::elf::FileHeader64;
+use object::elf::NT_GNU_ABI_TAG;
+use object::read::elf::NoteIterator;
+use object::LittleEndian;
+use object::ObjectSection as _;
+
+const NOTE_NAME: &[u8] = b"GNU\0";
+const NOTE_HEADER_SIZE: usize = 12;
+const DESC_SIZE: usize = 16;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct AbiTag {
+    /// The OS (`ELF_NOTE_OS_*`) and minimum kernel version, if any input had a tag.
+    pub(crate) required: Option<RequiredAbi>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RequiredAbi {
+    pub(crate) os: u32,
+    pub(crate) version: [u32; 3],
+}
+
+impl AbiTag {
+    /// Reads the tag from an input `.note.ABI-tag` section.
+    pub(crate) fn from_section(section: &Section) -> Result<Self> {
+        let e = LittleEndian;
+        let mut tag = AbiTag::default();
+        let mut notes =
+            NoteIterator::<FileHeader64<LittleEndian>>::new(e, section.align(), section.data()?)?;
+        while let Some(note) = notes.next()? {
+            if note.name() != b"GNU" || note.n_type(e) != NT_GNU_ABI_TAG {
+                continue;
+            }
+            let desc = note.desc();
+            if desc.len() < DESC_SIZE {
+                bail!("ABI tag is too short");
+            }
+            let word = |i: usize| u32::from_le_bytes(desc[i * 4..i * 4 + 4].try_into().unwrap());
+            tag.required = Some(RequiredAbi {
+                os: word(0),
+                version: [word(1), word(2), word(3)],
+            });
+        }
+        Ok(tag)
+    }
+
+    /// Merges the tags of all our inputs. Inputs without a tag don't constrain the result. It's an
+    /// error for inputs to require different operating systems.
+    pub(crate) fn merge<'a>(inputs: impl Iterator<Item = &'a AbiTag>) -> Result<AbiTag> {
+        let mut required: Option<RequiredAbi> = None;
+        for input in inputs.filter_map(|input| input.required.as_ref()) {
+            match &mut required {
+                None => required = Some(*input),
+                Some(existing) => {
+                    if existing.os != input.os {
+                        bail!(
+                            "Inputs have ABI tags for different operating systems ({} and {})",
+                            existing.os,
+                            input.os
+                        );
+                    }
+                    existing.version = existing.version.max(input.version);
+                }
+            }
+        }
+        Ok(AbiTag { required })
+    }
+
+    /// Returns the number of bytes needed to write our note. Returns 0 if we have no tag, in which
+    /// case we don't emit a note at all.
+    pub(crate) fn note_size(&self) -> u64 {
+        if self.required.is_none() {
+            return 0;
+        }
+        (NOTE_HEADER_SIZE + NOTE_NAME.len() + DESC_SIZE) as u64
+    }
+
+    pub(crate) fn write(&self, out: &mut [u8]) -> Result {
+        let Some(required) = self.required else {
+            return Ok(());
+        };
+        if (out.len() as u64) < self.note_size() {
+            bail!("Insufficient space allocated for .note.ABI-tag");
+        }
+        let words = [
+            NOTE_NAME.len() as u32,
+            DESC_SIZE as u32,
+            NT_GNU_ABI_TAG,
+            u32::from_le_bytes(NOTE_NAME.try_into().unwrap()),
+            required.os,
+            required.version[0],
+            required.version[1],
+            required.version[2],
+        ];
+        for (chunk, word) in out.chunks_exact_mut(4).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_merge_no_tags() {
+    let inputs = [AbiTag::default(), AbiTag::default()];
+    let merged = AbiTag::merge(inputs.iter()).unwrap();
+    assert_eq!(merged.required, None);
+    assert_eq!(merged.note_size(), 0);
+}
+
+#[test]
+fn test_merge_newest_version() {
+    let tag = |os, version| AbiTag {
+        required: Some(RequiredAbi { os, version }),
+    };
+    let inputs = [tag(0, [3, 2, 0]), AbiTag::default(), tag(0, [4, 1, 0])];
+    let merged = AbiTag::merge(inputs.iter()).unwrap();
+    assert_eq!(merged, tag(0, [4, 1, 0]));
+
+    let mut out = vec![0; merged.note_size() as usize];
+    merged.write(&mut out).unwrap();
+    assert_eq!(
+        out,
+        [
+            4, 0, 0, 0, 16, 0, 0, 0, 1, 0, 0, 0, b'G', b'N', b'U', 0, 0, 0, 0, 0, 4, 0, 0, 0, 1, 0,
+            0, 0, 0, 0, 0, 0,
+        ]
+    );
+
+    assert!(AbiTag::merge([tag(0, [3, 2, 0]), tag(1, [3, 2, 0])].iter()).is_err());
+}
@@ -7,7 +7,7 @@ use std::ops::Index;
 use std::ops::IndexMut;
 
 /// An alignment. Always a power of two.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, PartialOrd, Ord, Hash)]
 pub(crate) struct Alignment {
     pub(crate) exponent: u16,
 }
@@ -26,22 +26,55 @@ pub(crate) const SYMTAB_ENTRY: Alignment = Alignment { exponent: 3 };
 /// Alignment for entries in the global offset table.
 pub(crate) const GOT_ENTRY: Alignment = Alignment { exponent: 3 };
 
+/// Alignment for the procedure linkage table. AArch64 instructions need to be at least 4-byte
+/// aligned and aligning each 16 byte entry is good for performance on all architectures.
+pub(crate) const PLT_ENTRY: Alignment = Alignment { exponent: 4 };
+
 /// The minimum alignment of a rela entry.
 pub(crate) const RELA_ENTRY: Alignment = Alignment { exponent: 3 };
 
 /// Alignment of the .gnu.hash section.
 pub(crate) const GNU_HASH: Alignment = Alignment { exponent: 3 };
 
+/// Alignment of the SysV .hash section.
+pub(crate) const HASH: Alignment = Alignment { exponent: 2 };
+
+/// Alignment of .note.ABI-tag. Notes in 64 bit objects are usually 8 byte aligned, but this one
+/// has traditionally only been aligned to 4.
+pub(crate) const NOTE_ABI_TAG: Alignment = Alignment { exponent: 2 };
+
+/// Alignment of .gdb_index, which is made up of 32 and 64 bit fields, but only requires 4 byte
+/// alignment.
+pub(crate) const GDB_INDEX: Alignment = Alignment { exponent: 2 };
+
+/// Alignment of the entries in .symtab_shndx.
+pub(crate) const SYMTAB_SHNDX_ENTRY: Alignment = Alignment { exponent: 2 };
+
+/// Alignment of the entries in .relr.dyn.
+pub(crate) const RELR_ENTRY: Alignment = Alignment { exponent: 3 };
+
+/// Alignment of the entries in .gnu.version.
+pub(crate) const VERSYM_ENTRY: Alignment = Alignment { exponent: 1 };
+
+/// Alignment of .gnu.version_d, which is made up of 16 and 32 bit fields.
+pub(crate) const VERDEF: Alignment = Alignment { exponent: 2 };
+
+/// Alignment of .gnu.version_r, which is made up of 16 and 32 bit fields.
+pub(crate) const VERNEED: Alignment = Alignment { exponent: 2 };
+
+/// Alignment of the data of a compressed output section, which starts with an `Elf64_Chdr`.
+pub(crate) const COMPRESSION_HEADER: Alignment = Alignment { exponent: 3 };
+
 /// The minimum alignment of a phdr entry.
 pub(crate) const PROGRAM_HEADER_ENTRY: Alignment = Alignment { exponent: 3 };
 
-/// The minimum alignment of loadable program segments.
+/// The default page size. Loadable program segments are aligned to the page size.
 pub(crate) const PAGE: Alignment = Alignment { exponent: 12 };
 
 pub(crate) const USIZE: Alignment = Alignment { exponent: 3 };
 
 /// A map from alignments to some value.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub(crate) struct AlignmentMap<T> {
     // TODO: Consider only storing frequently used alignments in an array and storing less
     // frequently used alignments in an on-demand sorted Vec or smallvec.
@@ -62,6 +95,17 @@ impl Alignment {
         })
     }
 
+    /// Creates an alignment for a page size. Unlike `new`, this permits alignments larger than
+    /// `MAX`, since page sizes are only ever used to align segments, never as section alignments.
+    pub(crate) fn page_size(raw: u64) -> Result<Self> {
+        if !raw.is_power_of_two() {
+            bail!("Page size 0x{raw:x} isn't a power of two");
+        }
+        Ok(Alignment {
+            exponent: raw.trailing_zeros() as u16,
+        })
+    }
+
     pub(crate) fn value(self) -> u64 {
         1 << self.exponent
     }
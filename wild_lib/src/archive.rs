@@ -30,6 +30,8 @@ pub(crate) struct Identifier<'data> {
 
 pub(crate) struct ArchiveContent<'data> {
     ident: &'data str,
+    /// The bytes of the entry. For thin archives, this is empty, since the content of each entry
+    /// is in a separate file.
     pub(crate) entry_data: &'data [u8],
 }
 
@@ -41,6 +43,7 @@ pub(crate) struct SymbolTable<'data> {
 
 pub(crate) struct ArchiveIterator<'data> {
     data: &'data [u8],
+    is_thin: bool,
 }
 
 #[derive(Zeroable, Pod, Clone, Copy)]
@@ -63,12 +66,21 @@ const HEADER_SIZE: usize = core::mem::size_of::<EntryHeader>();
 
 impl<'data> ArchiveIterator<'data> {
     /// Create an iterator from the bytes of the whole archive. The supplied bytes should start with
-    /// an archive entry.
+    /// an archive entry. Both regular and thin archives are supported.
     pub(crate) fn from_archive_bytes(data: &'data [u8]) -> Result<Self> {
-        let Some(data) = data.strip_prefix(b"!<arch>\n") else {
+        if let Some(data) = data.strip_prefix(b"!<arch>\n") {
+            return Ok(Self {
+                data,
+                is_thin: false,
+            });
+        }
+        let Some(data) = data.strip_prefix(b"!<thin>\n") else {
             bail!("Missing header");
         };
-        Ok(Self { data })
+        Ok(Self {
+            data,
+            is_thin: true,
+        })
     }
 
     fn next_result(&mut self) -> Result<Option<ArchiveEntry<'data>>> {
@@ -83,14 +95,22 @@ impl<'data> ArchiveIterator<'data> {
         let bytes: &[u8] = &header.size;
         let size: usize = parse_decimal_int(bytes);
         self.data = rest;
+        let ident = std::str::from_utf8(&header.ident).context("archive ident is invalid UTF-8")?;
+        let ident = ident.trim();
+        if self.is_thin && ident != "/" && ident != "//" {
+            // The size in the header is the size of the external file that holds the entry's
+            // content. The archive itself contains no data for the entry.
+            return Ok(Some(ArchiveEntry::Regular(ArchiveContent {
+                ident,
+                entry_data: &[],
+            })));
+        }
         if self.data.len() < size {
             bail!(
                 "Entry size is {size}, but only {} bytes left",
                 self.data.len()
             );
         }
-        let ident = std::str::from_utf8(&header.ident).context("archive ident is invalid UTF-8")?;
-        let ident = ident.trim();
         let entry_data = &self.data[..size];
         let entry = match ident {
             "/" => ArchiveEntry::Symbols(SymbolTable { data: entry_data }),
@@ -145,7 +165,12 @@ impl<'data> ArchiveContent<'data> {
 
 impl<'data> Identifier<'data> {
     pub(crate) fn as_slice(&self) -> &'data [u8] {
-        let end = memchr::memchr(b'/', self.data).unwrap_or(self.data.len());
+        // Names in the extended filenames table are terminated by "/\n". We look for that first,
+        // since thin archives can have paths containing slashes there. Names stored in the entry
+        // header are just terminated by a slash.
+        let end = memchr::memmem::find(self.data, b"/\n")
+            .or_else(|| memchr::memchr(b'/', self.data))
+            .unwrap_or(self.data.len());
         &self.data[..end]
     }
 }
@@ -4,6 +4,7 @@ use crate::args::Modifiers;
 use crate::error::Result;
 use crate::file_kind::FileKind;
 use crate::input_data::InputData;
+use crate::input_data::InputFile;
 use crate::input_data::InputRef;
 use rayon::iter::IntoParallelRefIterator;
 use rayon::iter::ParallelIterator;
@@ -21,50 +22,56 @@ pub fn split_archives<'data>(input_data: &'data InputData) -> Result<Vec<InputBy
     let split_output = input_data
         .files
         .par_iter()
-        .map(|f| {
-            match f.kind {
-                FileKind::Archive => {
-                    let mut extended_filenames = None;
-                    let mut outputs = Vec::new();
-                    for entry in ArchiveIterator::from_archive_bytes(f.data())? {
-                        let entry = entry?;
-                        match entry {
-                            ArchiveEntry::Symbols(_) => {
-                                // We used to read the symbol table from the archive, but when you're linking
-                                // lots of archives and discarding very few, it turns out it's faster to just
-                                // ignore the symbol table and eagerly read the objects.
-                            }
-                            ArchiveEntry::Filenames(t) => extended_filenames = Some(t),
-                            ArchiveEntry::Regular(archive_entry) => {
-                                outputs.push(InputBytes {
-                                    kind: f.kind,
-                                    input: InputRef {
-                                        file: f,
-                                        entry_filename: Some(
-                                            archive_entry.identifier(extended_filenames),
-                                        ),
-                                    },
-                                    data: archive_entry.entry_data,
-                                    modifiers: f.modifiers,
-                                });
-                            }
-                        }
+        .map(split_file)
+        .collect::<Result<Vec<Vec<InputBytes>>>>()?;
+    Ok(split_output.into_iter().flatten().collect())
+}
+
+/// Returns the objects contained in `f`, which is just `f` itself unless it's an archive.
+pub(crate) fn split_file(f: &InputFile) -> Result<Vec<InputBytes<'_>>> {
+    match f.kind {
+        FileKind::Archive | FileKind::ThinArchive => {
+            let mut extended_filenames = None;
+            let mut outputs = Vec::new();
+            for entry in ArchiveIterator::from_archive_bytes(f.data())? {
+                let entry = entry?;
+                match entry {
+                    ArchiveEntry::Symbols(_) => {
+                        // We used to read the symbol table from the archive, but when you're
+                        // linking lots of archives and discarding very few, it turns out it's
+                        // faster to just ignore the symbol table and eagerly read the objects.
+                    }
+                    ArchiveEntry::Filenames(t) => extended_filenames = Some(t),
+                    ArchiveEntry::Regular(archive_entry) => {
+                        let data = if f.kind == FileKind::ThinArchive {
+                            f.thin_archive_member(outputs.len())?
+                        } else {
+                            archive_entry.entry_data
+                        };
+                        outputs.push(InputBytes {
+                            kind: f.kind,
+                            input: InputRef {
+                                file: f,
+                                entry_filename: Some(archive_entry.identifier(extended_filenames)),
+                            },
+                            data,
+                            modifiers: f.modifiers,
+                        });
                     }
-                    Ok(outputs)
                 }
-                _ => Ok(vec![InputBytes {
-                    input: InputRef {
-                        file: f,
-                        entry_filename: None,
-                    },
-                    kind: f.kind,
-                    data: f.data(),
-                    modifiers: f.modifiers,
-                }]),
             }
-        })
-        .collect::<Result<Vec<Vec<InputBytes>>>>()?;
-    Ok(split_output.into_iter().flatten().collect())
+            Ok(outputs)
+        }
+        _ => Ok(vec![InputBytes {
+            input: InputRef {
+                file: f,
+                entry_filename: None,
+            },
+            kind: f.kind,
+            data: f.data(),
+            modifiers: f.modifiers,
+        }]),
+    }
 }
 
 impl<'data> Display for InputBytes<'data> {
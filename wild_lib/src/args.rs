@@ -2,10 +2,18 @@
 //! order is important for some arguments and it's not clear how easy it would be to get that
 //! correct with something like clap.
 
+use crate::alignment::Alignment;
+use crate::defsym::DefsymExpr;
+use crate::elf;
 use crate::error::Result;
+use crate::linker_script::LinkerScript;
+use crate::save_dir::DeferredSaveDir;
 use crate::save_dir::SaveDir;
 use anyhow::anyhow;
 use anyhow::bail;
+use anyhow::Context;
+use std::collections::HashSet;
+use std::io::Write as _;
 use std::num::NonZeroUsize;
 use std::path::Path;
 use std::path::PathBuf;
@@ -13,6 +21,8 @@ use std::sync::atomic::AtomicI64;
 use std::sync::Arc;
 
 pub(crate) struct Args {
+    /// Directories to search for libraries. Every `-L` applies to every `-l`, regardless of their
+    /// relative order, as with GNU ld.
     pub(crate) lib_search_path: Vec<Box<Path>>,
     pub(crate) inputs: Vec<Input>,
     pub(crate) output: Arc<Path>,
@@ -24,11 +34,631 @@ pub(crate) struct Args {
     pub(crate) prepopulate_maps: bool,
     pub(crate) sym_info: Option<String>,
     pub(crate) merge_strings: bool,
+    /// Whether to merge identical fixed-size entries in sections that have SHF_MERGE, but not
+    /// SHF_STRINGS. e.g. `.rodata.cst8`.
+    pub(crate) merge_constants: bool,
+    /// Rules that override `merge_strings` and `merge_constants` for output sections whose names
+    /// match a glob pattern. Set by `--merge=` and `--no-merge=`.
+    pub(crate) merge_rules: Vec<MergeRule>,
     pub(crate) debug_fuel: Option<AtomicI64>,
     pub(crate) time_phases: bool,
     pub(crate) validate_output: bool,
+    /// Whether to link twice and check that both links produced identical output. Set by
+    /// `--check-reproducible`.
+    pub(crate) check_reproducible: bool,
+    /// Whether to also write the link-time value to places that get a dynamic relocation, rather
+    /// than leaving them for the loader to fill in. Set by `--apply-dynamic-relocs`.
+    pub(crate) apply_dynamic_relocs: bool,
     pub(crate) pie: bool,
     pub(crate) version_script_path: Option<PathBuf>,
+    pub(crate) defsyms: Vec<Defsym>,
+    /// Whether to emit a GNU_RELRO segment. Cleared by `-z norelro`.
+    pub(crate) relro: bool,
+    /// The page size that loadable segments are aligned to. Set by `-z max-page-size=`.
+    pub(crate) max_page_size: Alignment,
+    /// The page size that we expect the system to actually use. We pad the file to this size
+    /// where we need things to be on separate pages at runtime, e.g. the end of RELRO, while
+    /// segment addresses stay congruent to their file offsets modulo `max_page_size`. Set by
+    /// `-z common-page-size=` and never larger than `max_page_size`.
+    pub(crate) common_page_size: Alignment,
+    /// The stack size to request via the GNU_STACK segment. Set by `-z stack-size=`. Zero leaves
+    /// the choice to the system.
+    pub(crate) stack_size: u64,
+    /// The number of extra DT_NULL entries to reserve at the end of .dynamic so that tools like
+    /// patchelf can add entries later without moving the section. Set by `--spare-dynamic-tags`.
+    pub(crate) spare_dynamic_tags: usize,
+    /// Whether the stack should be executable. Set by `-z execstack`.
+    pub(crate) execstack: bool,
+    /// Whether executable segments should be kept on separate pages from other segments. Set by
+    /// `-z separate-code`.
+    pub(crate) separate_code: bool,
+    /// Whether read-only sections get their own non-executable segment. With `--no-rosegment`,
+    /// they're instead placed in the executable segment, which saves a `PT_LOAD`.
+    pub(crate) rosegment: bool,
+    pub(crate) b_symbolic: BSymbolic,
+    /// Whether all non-hidden symbols should be exported as dynamic symbols, even when we're
+    /// producing an executable. Set by `-E` / `--export-dynamic`.
+    pub(crate) export_all_dynamic_symbols: bool,
+    pub(crate) dynamic_list_path: Option<PathBuf>,
+    /// Where to write a link map, if one was requested.
+    pub(crate) map_file: Option<MapOutput>,
+    /// The format of the link map. Set by `--map-format`.
+    pub(crate) map_format: MapFormat,
+    /// Whether to output a cross reference table. Set by `--cref`.
+    pub(crate) cref: bool,
+    /// A script to run when we're about to report an undefined symbol or a missing library. Its
+    /// output is included in the error message.
+    pub(crate) error_handling_script: Option<PathBuf>,
+    /// Which executable sections, if any, we should fold into identical copies. Set by `--icf`.
+    pub(crate) icf: Icf,
+    /// How to order the sections that each input object contributes to an output section. Set by
+    /// `--sort-section`.
+    pub(crate) sort_section: SortSection,
+    /// The order, by alignment, in which we place common symbols in `.bss`. Set by
+    /// `--sort-common`.
+    pub(crate) sort_common: SortCommon,
+    /// What to do with orphan sections, i.e. allocated sections that neither the linker script nor
+    /// our built-in rules place. Set by `--orphan-handling`.
+    pub(crate) orphan_handling: OrphanHandling,
+    /// Which unresolved symbol references to report. Set by `--unresolved-symbols`. Whether
+    /// references in shared libs are reported is also set by `--no-allow-shlib-undefined` and
+    /// cleared by `--allow-shlib-undefined`.
+    pub(crate) unresolved_symbols: UnresolvedSymbols,
+    /// Whether unresolved symbols are reported as warnings rather than errors. Set by
+    /// `--warn-unresolved-symbols` and cleared by `--error-unresolved-symbols`.
+    pub(crate) warn_unresolved_symbols: bool,
+    /// Whether to keep the relocations from our input files in the output, adjusted to refer to
+    /// output addresses and symbols. Set by `--emit-relocs` / `-q`.
+    pub(crate) emit_relocs: bool,
+    /// If set, the only symbols that we put in the output symbol table. Set by
+    /// `--retain-symbols-file`.
+    pub(crate) retain_symbols: Option<HashSet<String>>,
+    /// Glob patterns for global symbols that should be made local in the output. Set by
+    /// `--localize-symbol`.
+    pub(crate) localize_symbols: Vec<String>,
+    /// Whether to make all symbols with hidden or internal visibility local in the output. Set by
+    /// `--localize-hidden`.
+    pub(crate) localize_hidden: bool,
+    /// Glob patterns for input sections that we keep even if nothing references them. Set by
+    /// `--keep-section`.
+    pub(crate) keep_sections: Vec<String>,
+    /// Glob patterns for input sections that we discard. Set by `--remove-section`.
+    pub(crate) remove_sections: Vec<String>,
+    /// Rules for which output sections to compress and how. Set by `--compress-sections`.
+    pub(crate) compress_sections: Vec<CompressSections>,
+    /// A linker script that controls how we lay out the output. Set by `-T` / `--script`.
+    pub(crate) linker_script: Option<LinkerScript>,
+    /// Addresses at which to place output sections, keyed by section name. These take precedence
+    /// over addresses from the linker script. Set by `--section-start`.
+    pub(crate) section_starts: Vec<(String, u64)>,
+    /// Whether to check that no two allocated output sections overlap in memory. Cleared by
+    /// `--no-check-sections`.
+    pub(crate) check_sections: bool,
+    /// Which kinds of hash table we write for dynamic symbol lookup. Set by `--hash-style`.
+    pub(crate) hash_style: HashStyle,
+    /// The output format. Set by `-m`. If not set, we pick the format that matches our input
+    /// objects.
+    pub(crate) emulation: Option<Emulation>,
+    /// The byte order of the output. Set by `-EL`. We reject `-EB`.
+    pub(crate) endianness: Endianness,
+    /// The directory that `=` and `$SYSROOT` prefixes of library search directories refer to. Set
+    /// by `--sysroot`.
+    pub(crate) sysroot: Option<PathBuf>,
+    /// If set, we just print the requested information rather than linking, in which case `-o`
+    /// isn't required. Set by `--print-output-format` and `--print-sysroot`.
+    pub(crate) query: Option<Query>,
+    /// Whether to report each input section that we discard because it isn't referenced. Set by
+    /// `--print-gc-sections`.
+    pub(crate) print_gc_sections: bool,
+    /// Whether to keep sections that define global symbols with default or protected visibility,
+    /// even if nothing references them and we're not exporting them. Shared objects export all such
+    /// symbols, so already keep them. Set by `--gc-keep-exported`.
+    pub(crate) gc_keep_exported: bool,
+    /// Whether to report each group of sections folded by `--icf`. Set by `--print-icf-sections`.
+    pub(crate) print_icf_sections: bool,
+    /// Whether to print the size of the output file and of its major sections once we've finished
+    /// layout. Set by `--print-memory-usage`.
+    pub(crate) print_memory_usage: bool,
+    /// Symbols that we treat as undefined references, so that archive members that define them get
+    /// loaded. Set by `-u` / `--undefined`.
+    pub(crate) undefined: Vec<String>,
+    /// Symbols for which we report each loaded file that references or defines them. Set by `-y` /
+    /// `--trace-symbol`.
+    pub(crate) trace_symbols: Vec<String>,
+    /// Whether symbols that remain undefined should be an error. Set by `--no-undefined` or
+    /// `-z defs`. Currently only affects symbols named via `-u`.
+    pub(crate) no_undefined: bool,
+    /// Colon-separated directories for the dynamic loader to search. Set by `-rpath`. Any
+    /// `$ORIGIN` is left for the loader to expand.
+    pub(crate) rpath: Option<String>,
+    /// Whether to emit the rpath as DT_RUNPATH rather than DT_RPATH. Set by `--enable-new-dtags`.
+    pub(crate) new_dtags: bool,
+    /// Whether `-z origin` was given.
+    pub(crate) z_origin: bool,
+    /// Extra bits for DT_FLAGS_1 requested by `-z` keywords such as `nodelete` and `initfirst`.
+    pub(crate) z_flags_1: u64,
+    /// The visibility of the `__start_X` and `__stop_X` symbols that we define for custom sections.
+    /// Set by `-z start-stop-visibility=`.
+    pub(crate) start_stop_visibility: StartStopVisibility,
+    /// A directory in which we cache the symbol tables of our input objects between links. Set by
+    /// `--object-cache`.
+    pub(crate) object_cache: Option<PathBuf>,
+    /// Whether to warn when a common symbol is merged with another common symbol or overridden by a
+    /// definition. Set by `--warn-common`.
+    pub(crate) warn_common: bool,
+    /// Whether warnings should cause the link to fail. Set by `--fatal-warnings`.
+    pub(crate) fatal_warnings: bool,
+    /// Unrecognised arguments that we ignored and should warn about because of
+    /// `--unknown-flags=warn`.
+    pub(crate) ignored_unknown_flags: Vec<String>,
+    /// Cortex-A53 erratum workarounds that were requested, e.g. `--fix-cortex-a53-843419`. We don't
+    /// patch the affected instruction sequences, so we warn about these when linking for AArch64.
+    pub(crate) cortex_a53_fixes: Vec<String>,
+    /// Whether to still write the output file when there were errors such as undefined symbols.
+    /// Set by `--noinhibit-exec`.
+    pub(crate) noinhibit_exec: bool,
+    /// Whether a symbol that's strongly defined by more than one input is permitted, in which case
+    /// the first definition is used. Set by `--allow-multiple-definition` or `-z muldefs`.
+    pub(crate) allow_multiple_definition: bool,
+    /// Whether to write a `.gdb_index` section built from the inputs' debug info. Set by
+    /// `--gdb-index`.
+    pub(crate) gdb_index: bool,
+    /// Where to write a tar file containing our inputs and arguments. Set by `--repro`.
+    pub(crate) repro: Option<PathBuf>,
+    /// Where to save our inputs and arguments if the link fails. Set by `--reproduce-on-error`.
+    pub(crate) reproduce_on_error: Option<DeferredSaveDir>,
+    /// Whether dynamic relocations are permitted in read-only sections. Set by `-z notext` and
+    /// cleared by `-z text`.
+    pub(crate) allow_text_relocations: bool,
+    /// Whether to warn about each dynamic relocation that we emit into a read-only section, when
+    /// those are permitted. Set by `--warn-textrel`.
+    pub(crate) warn_textrel: bool,
+    /// Whether to pack relative dynamic relocations into `.relr.dyn` rather than emitting them as
+    /// RELA entries. Set by `-z pack-relative-relocs` or `--pack-dyn-relocs=relr`.
+    pub(crate) pack_relative_relocs: bool,
+    /// A linker plugin, such as GCC's or LLVM's LTO plugin, that may claim input files and supply
+    /// objects to link in their place. Set by `-plugin`.
+    pub(crate) plugin: Option<PathBuf>,
+    /// Options to pass to the linker plugin. Set by `-plugin-opt`.
+    pub(crate) plugin_opts: Vec<String>,
+    /// Whether to save the layout of the output after linking, so that the next link can update
+    /// the output in place if only a few inputs changed. Set by `--incremental`.
+    pub(crate) incremental: bool,
+}
+
+/// The output format that we produce. Selected by `-m`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Emulation {
+    #[default]
+    X86_64,
+    AArch64,
+    RiscV64,
+}
+
+impl Emulation {
+    fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "elf_x86_64" => Emulation::X86_64,
+            "aarch64linux" | "aarch64elf" => Emulation::AArch64,
+            "elf64lriscv" => Emulation::RiscV64,
+            other => bail!("Unsupported emulation `{other}`"),
+        })
+    }
+
+    /// Returns the emulation that produces output for `e_machine`, if we support it.
+    pub(crate) fn from_elf_machine(e_machine: u16) -> Option<Self> {
+        match e_machine {
+            object::elf::EM_X86_64 => Some(Emulation::X86_64),
+            object::elf::EM_AARCH64 => Some(Emulation::AArch64),
+            object::elf::EM_RISCV => Some(Emulation::RiscV64),
+            _ => None,
+        }
+    }
+
+    /// Returns the value for `e_machine` in the file header.
+    pub(crate) fn elf_machine(self) -> u16 {
+        match self {
+            Emulation::X86_64 => object::elf::EM_X86_64,
+            Emulation::AArch64 => object::elf::EM_AARCH64,
+            Emulation::RiscV64 => object::elf::EM_RISCV,
+        }
+    }
+
+    /// Returns the BFD name of the output format, as printed by `--print-output-format`.
+    pub(crate) fn output_format(self) -> &'static str {
+        match self {
+            Emulation::X86_64 => "elf64-x86-64",
+            Emulation::AArch64 => "elf64-littleaarch64",
+            Emulation::RiscV64 => "elf64-littleriscv",
+        }
+    }
+
+    /// Returns the dynamic relocation type that adds the load address to its addend.
+    pub(crate) fn relative_relocation(self) -> u32 {
+        match self {
+            Emulation::X86_64 => object::elf::R_X86_64_RELATIVE,
+            Emulation::AArch64 => object::elf::R_AARCH64_RELATIVE,
+            Emulation::RiscV64 => object::elf::R_RISCV_RELATIVE,
+        }
+    }
+
+    /// Returns the dynamic relocation type that stores the address of a symbol in a GOT entry.
+    pub(crate) fn glob_dat_relocation(self) -> u32 {
+        match self {
+            Emulation::X86_64 => object::elf::R_X86_64_GLOB_DAT,
+            Emulation::AArch64 => object::elf::R_AARCH64_GLOB_DAT,
+            // RISC-V doesn't have a separate relocation type for GOT entries.
+            Emulation::RiscV64 => object::elf::R_RISCV_64,
+        }
+    }
+
+    /// Returns the dynamic relocation type that tells the loader to copy the initial value of a data
+    /// object from the shared object that defines it into the executable.
+    pub(crate) fn copy_relocation(self) -> u32 {
+        match self {
+            Emulation::X86_64 => object::elf::R_X86_64_COPY,
+            Emulation::AArch64 => object::elf::R_AARCH64_COPY,
+            Emulation::RiscV64 => object::elf::R_RISCV_COPY,
+        }
+    }
+
+    /// Returns the 64 bit absolute relocation type, which can also be used to store the address of
+    /// a symbol in a GOT entry.
+    pub(crate) fn absolute_relocation(self) -> u32 {
+        match self {
+            Emulation::X86_64 => object::elf::R_X86_64_64,
+            Emulation::AArch64 => object::elf::R_AARCH64_ABS64,
+            Emulation::RiscV64 => object::elf::R_RISCV_64,
+        }
+    }
+
+    /// Returns the dynamic relocation type that calls an ifunc resolver to get its value.
+    pub(crate) fn irelative_relocation(self) -> u32 {
+        match self {
+            Emulation::X86_64 => object::elf::R_X86_64_IRELATIVE,
+            Emulation::AArch64 => object::elf::R_AARCH64_IRELATIVE,
+            Emulation::RiscV64 => crate::riscv64::R_RISCV_IRELATIVE,
+        }
+    }
+}
+
+impl std::fmt::Display for Emulation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Emulation::X86_64 => "x86-64",
+            Emulation::AArch64 => "AArch64",
+            Emulation::RiscV64 => "RISC-V",
+        })
+    }
+}
+
+/// Information that build systems can ask us to print instead of linking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Query {
+    /// `--print-output-format`
+    OutputFormat,
+    /// `--print-sysroot`
+    Sysroot,
+}
+
+/// The byte order of the output. Our ELF types are all little-endian, so that's all we support.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Endianness {
+    #[default]
+    Little,
+}
+
+impl Endianness {
+    /// Returns the value for `EI_DATA` in the file header.
+    pub(crate) fn elf_data(self) -> u8 {
+        match self {
+            Endianness::Little => object::elf::ELFDATA2LSB,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HashStyle {
+    /// Only the classic SysV `.hash` section.
+    Sysv,
+    /// Only `.gnu.hash`.
+    #[default]
+    Gnu,
+    /// Both `.hash` and `.gnu.hash`, for compatibility with loaders that only support `.hash`.
+    Both,
+}
+
+impl HashStyle {
+    fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "sysv" => HashStyle::Sysv,
+            "gnu" => HashStyle::Gnu,
+            "both" => HashStyle::Both,
+            other => bail!("Unsupported hash-style `{other}`"),
+        })
+    }
+
+    pub(crate) fn includes_sysv(self) -> bool {
+        matches!(self, HashStyle::Sysv | HashStyle::Both)
+    }
+
+    pub(crate) fn includes_gnu(self) -> bool {
+        matches!(self, HashStyle::Gnu | HashStyle::Both)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Icf {
+    #[default]
+    None,
+    /// Only fold sections where no symbol in the section has its address taken. Without an
+    /// `.llvm_addrsig` section, we have to assume that every symbol in an object is
+    /// address-significant.
+    Safe,
+    /// Fold all identical sections, even if that means that distinct functions end up with the
+    /// same address.
+    All,
+}
+
+impl Icf {
+    fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "none" => Icf::None,
+            "safe" => Icf::Safe,
+            "all" => Icf::All,
+            other => bail!("Unsupported --icf mode `{other}`"),
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SortSection {
+    /// Keep sections in the order that they appear in their object.
+    #[default]
+    None,
+    /// Sort sections by name.
+    Name,
+    /// Sort sections by descending alignment. Sections with different alignments already go in
+    /// separate parts of their output section, largest alignment first, so this only affects the
+    /// relative order of sections within each part, which is by section index as with `None`.
+    Alignment,
+}
+
+impl SortSection {
+    fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "name" => SortSection::Name,
+            "alignment" => SortSection::Alignment,
+            other => bail!("Unsupported --sort-section mode `{other}`"),
+        })
+    }
+}
+
+/// The parts of `.bss`, which include our common symbols, are laid out by alignment. Descending
+/// order minimises padding, so it's what we do without `--sort-common` too.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SortCommon {
+    #[default]
+    Descending,
+    Ascending,
+}
+
+impl SortCommon {
+    fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "descending" => SortCommon::Descending,
+            "ascending" => SortCommon::Ascending,
+            other => bail!("Unsupported --sort-common order `{other}`"),
+        })
+    }
+}
+
+/// What to do with flags that we don't recognise. Ignoring them lets wild be used with compiler
+/// drivers that pass flags we don't implement yet. Note that a flag that we don't know about might
+/// take a separate value, which we'd then treat as an input file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum UnknownFlags {
+    #[default]
+    Error,
+    Warn,
+    Ignore,
+}
+
+impl UnknownFlags {
+    fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "error" => UnknownFlags::Error,
+            "warn" => UnknownFlags::Warn,
+            "ignore" => UnknownFlags::Ignore,
+            other => bail!("Unsupported --unknown-flags policy `{other}`"),
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OrphanHandling {
+    /// Put each orphan in an output section with the same name.
+    #[default]
+    Place,
+    /// Like `Place`, but warn about each orphan.
+    Warn,
+    /// Fail the link if there are any orphans.
+    Error,
+    /// Drop orphans from the output.
+    Discard,
+}
+
+impl OrphanHandling {
+    fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "place" => OrphanHandling::Place,
+            "warn" => OrphanHandling::Warn,
+            "error" => OrphanHandling::Error,
+            "discard" => OrphanHandling::Discard,
+            other => bail!("Unsupported --orphan-handling mode `{other}`"),
+        })
+    }
+}
+
+/// The ELF visibility given to the `__start_X` / `__stop_X` symbols that bracket custom sections.
+/// As with GNU ld, the default is protected, so references from within the output can't be
+/// preempted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StartStopVisibility {
+    Default,
+    Internal,
+    Hidden,
+    #[default]
+    Protected,
+}
+
+impl StartStopVisibility {
+    fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "default" => StartStopVisibility::Default,
+            "internal" => StartStopVisibility::Internal,
+            "hidden" => StartStopVisibility::Hidden,
+            "protected" => StartStopVisibility::Protected,
+            other => bail!("Unsupported start-stop-visibility `{other}`"),
+        })
+    }
+
+    /// Returns the value for the `st_other` field of symbols with this visibility.
+    pub(crate) fn st_other(self) -> u8 {
+        match self {
+            StartStopVisibility::Default => object::elf::STV_DEFAULT,
+            StartStopVisibility::Internal => object::elf::STV_INTERNAL,
+            StartStopVisibility::Hidden => object::elf::STV_HIDDEN,
+            StartStopVisibility::Protected => object::elf::STV_PROTECTED,
+        }
+    }
+}
+
+/// Which undefined references to report, distinguishing references from the relocatable objects
+/// that we're linking from references in shared objects that we link against.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum UnresolvedSymbols {
+    ReportAll,
+    IgnoreAll,
+    IgnoreInObjectFiles,
+    /// Our default, since we don't otherwise check that the references in shared objects can be
+    /// resolved.
+    #[default]
+    IgnoreInSharedLibs,
+}
+
+impl UnresolvedSymbols {
+    fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "report-all" => UnresolvedSymbols::ReportAll,
+            "ignore-all" => UnresolvedSymbols::IgnoreAll,
+            "ignore-in-object-files" => UnresolvedSymbols::IgnoreInObjectFiles,
+            "ignore-in-shared-libs" => UnresolvedSymbols::IgnoreInSharedLibs,
+            other => bail!("Unsupported --unresolved-symbols method `{other}`"),
+        })
+    }
+
+    pub(crate) fn report_in_object_files(self) -> bool {
+        matches!(
+            self,
+            UnresolvedSymbols::ReportAll | UnresolvedSymbols::IgnoreInSharedLibs
+        )
+    }
+
+    pub(crate) fn report_in_shared_libs(self) -> bool {
+        matches!(
+            self,
+            UnresolvedSymbols::ReportAll | UnresolvedSymbols::IgnoreInObjectFiles
+        )
+    }
+
+    /// Returns a method that reports references in shared libs according to `report`, while
+    /// leaving how references in object files are handled unchanged. Used by
+    /// `--allow-shlib-undefined` and `--no-allow-shlib-undefined`.
+    fn with_report_in_shared_libs(self, report: bool) -> Self {
+        match (self.report_in_object_files(), report) {
+            (true, true) => UnresolvedSymbols::ReportAll,
+            (true, false) => UnresolvedSymbols::IgnoreInSharedLibs,
+            (false, true) => UnresolvedSymbols::IgnoreInObjectFiles,
+            (false, false) => UnresolvedSymbols::IgnoreAll,
+        }
+    }
+}
+
+/// Which references to symbols defined in a shared object should be bound at link time rather than
+/// being left interposable.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BSymbolic {
+    #[default]
+    None,
+    /// All references. Requested via `-Bsymbolic`.
+    All,
+    /// References to functions. Requested via `-Bsymbolic-functions`.
+    Functions,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum MapOutput {
+    /// Write the map to stdout. Requested via `-M` or `--print-map`.
+    Stdout,
+    /// Write the map to the supplied file. Requested via `-Map`.
+    File(PathBuf),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MapFormat {
+    /// A text map in roughly the same format as GNU ld.
+    #[default]
+    Text,
+    /// A JSON document, for consumption by tools.
+    Json,
+}
+
+impl MapFormat {
+    fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "text" => MapFormat::Text,
+            "json" => MapFormat::Json,
+            other => bail!("Unsupported --map-format `{other}`"),
+        })
+    }
+}
+
+/// A symbol definition supplied on the command line via `--defsym` or `--defsym-weak`.
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct Defsym {
+    pub(crate) name: String,
+    /// The value of the symbol, which may refer to other symbols, so is only evaluated once layout
+    /// is complete.
+    pub(crate) value: DefsymExpr,
+    /// Whether the definition is weak, allowing it to be overridden by a strong definition from an
+    /// input object.
+    pub(crate) weak: bool,
+}
+
+/// A rule supplied via `--merge=` or `--no-merge=` saying whether to merge the contents of
+/// `SHF_MERGE` sections that go into output sections whose names match a glob pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MergeRule {
+    pub(crate) pattern: String,
+    pub(crate) merge: bool,
+}
+
+/// A rule supplied via `--compress-sections` saying how to compress output sections whose names
+/// match a glob pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CompressSections {
+    pub(crate) pattern: String,
+    /// The algorithm to use, or `None` if matching sections should be left uncompressed.
+    pub(crate) format: Option<CompressionFormat>,
+    /// The compression level, or `None` to use the algorithm's default level.
+    pub(crate) level: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CompressionFormat {
+    Zlib,
+    Zstd,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -36,6 +666,9 @@ pub(crate) enum OutputKind {
     StaticExecutable,
     DynamicExecutable,
     SharedObject,
+    /// Partial linking (`-r`). The output is another object file that can be used as input to a
+    /// later link.
+    Relocatable,
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Copy)]
@@ -45,6 +678,9 @@ pub(crate) struct Modifiers {
 
     /// Whether we're currently allowed to link against shared libraries.
     pub(crate) allow_shared: bool,
+
+    /// Whether all members of archives should be loaded, even if nothing references them.
+    pub(crate) whole_archive: bool,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -60,10 +696,26 @@ pub(crate) struct Input {
 pub(crate) enum InputSpec {
     File(Box<Path>),
     Lib(Box<str>),
+    /// A file to be found on the library search path by its exact name. Specified via
+    /// `-l:filename`.
+    Search(Box<str>),
+}
+
+impl InputSpec {
+    /// Creates a spec from whatever followed `-l`.
+    pub(crate) fn from_lib_arg(arg: &str) -> Self {
+        if let Some(filename) = arg.strip_prefix(':') {
+            InputSpec::Search(Box::from(filename))
+        } else {
+            InputSpec::Lib(Box::from(arg))
+        }
+    }
 }
 
 pub const VALIDATE_ENV: &str = "WILD_VALIDATE_OUTPUT";
 
+const DEFAULT_SPARE_DYNAMIC_TAGS: usize = 5;
+
 // These flags don't currently affect our behaviour. TODO: Assess whether we should error or warn if
 // these are given. This is tricky though. On the one hand we want to be a drop-in replacement for
 // other linkers. On the other, we should perhaps somehow let the user know that we don't support a
@@ -75,10 +727,6 @@ const IGNORED_FLAGS: &[&str] = &[
     "--build-id",
     // TODO: We currently always GC sections. Support _not_ GCing them.
     "--gc-sections",
-    // TODO: Think about if anything is needed here. We don't need groups in order resolve cycles,
-    // so perhaps ignoring these is the right thing to do.
-    "--start-group",
-    "--end-group",
     // TODO: This is supposed to suppress built-in search paths, but I don't think we have any
     // built-in search paths. Perhaps we should?
     "-nostdlib",
@@ -86,15 +734,17 @@ const IGNORED_FLAGS: &[&str] = &[
     "--no-undefined-version",
 ];
 
-impl Args {
-    pub(crate) fn from_env() -> Result<Self> {
-        Self::parse(std::env::args())
-    }
+/// Workarounds for AArch64 errata. Compiler drivers pass these when targeting affected cores. We
+/// don't yet patch the affected instruction sequences.
+const CORTEX_A53_FIX_FLAGS: &[&str] = &["--fix-cortex-a53-835769", "--fix-cortex-a53-843419"];
 
-    // Parse the supplied input arguments, which should not include the program name.
+impl Args {
+    // Parse the supplied input arguments. The first argument is the program name and is ignored.
     #[allow(clippy::if_same_then_else)]
     pub(crate) fn parse<S: AsRef<str>, I: Iterator<Item = S>>(mut input: I) -> Result<Self> {
         let mut lib_search_path = Vec::new();
+        let mut sysroot = None;
+        let mut query = None;
         let mut inputs = Vec::new();
         let mut output = None;
         let mut dynamic_linker = None;
@@ -107,11 +757,81 @@ impl Args {
         let mut save_dir = SaveDir::new()?;
         let mut sym_info = None;
         let mut merge_strings = true;
+        let mut merge_constants = true;
+        let mut merge_rules = Vec::new();
         let mut debug_fuel = None;
         let mut validate_output = std::env::var(VALIDATE_ENV).is_ok_and(|v| v == "1");
+        let mut check_reproducible = false;
+        let mut apply_dynamic_relocs = false;
         let mut pie = false;
         let mut modifier_stack = vec![Modifiers::default()];
         let mut version_script_path = None;
+        let mut defsyms = Vec::new();
+        let mut section_starts = Vec::new();
+        let mut check_sections = true;
+        let mut export_all_dynamic_symbols = false;
+        let mut dynamic_list_path = None;
+        let mut map_file = None;
+        let mut map_format = MapFormat::default();
+        let mut cref = false;
+        let mut error_handling_script = None;
+        let mut icf = Icf::None;
+        let mut sort_section = SortSection::None;
+        let mut sort_common = SortCommon::default();
+        let mut orphan_handling = OrphanHandling::Place;
+        let mut unresolved_symbols = UnresolvedSymbols::default();
+        let mut warn_unresolved_symbols = false;
+        let mut emit_relocs = false;
+        let mut relocatable = false;
+        let mut retain_symbols = None;
+        let mut localize_symbols = Vec::new();
+        let mut localize_hidden = false;
+        let mut keep_sections = Vec::new();
+        let mut remove_sections = Vec::new();
+        let mut compress_sections = Vec::new();
+        let mut linker_script = None;
+        let mut hash_style = HashStyle::Gnu;
+        let mut emulation = None;
+        let mut endianness = Endianness::default();
+        let mut print_gc_sections = false;
+        let mut gc_keep_exported = false;
+        let mut print_icf_sections = false;
+        let mut print_memory_usage = false;
+        let mut undefined = Vec::new();
+        let mut trace_symbols = Vec::new();
+        let mut no_undefined = false;
+        let mut rpath: Option<String> = None;
+        let mut new_dtags = true;
+        let mut z_origin = false;
+        let mut z_flags_1 = 0;
+        let mut start_stop_visibility = StartStopVisibility::default();
+        let mut object_cache = None;
+        let mut warn_common = false;
+        let mut fatal_warnings = false;
+        let mut unknown_flags = UnknownFlags::default();
+        let mut unknown_flag_args = Vec::new();
+        let mut cortex_a53_fixes = Vec::new();
+        let mut noinhibit_exec = false;
+        let mut allow_multiple_definition = false;
+        let mut gdb_index = false;
+        let mut incremental = false;
+        let mut repro = None;
+        let mut reproduce_on_error = None;
+        let mut allow_text_relocations = false;
+        let mut warn_textrel = false;
+        let mut pack_relative_relocs = false;
+        let mut plugin = None;
+        let mut plugin_opts = Vec::new();
+        let mut in_group = false;
+        let mut relro = true;
+        let mut max_page_size = crate::alignment::PAGE;
+        let mut common_page_size = crate::alignment::PAGE;
+        let mut stack_size = 0;
+        let mut spare_dynamic_tags = DEFAULT_SPARE_DYNAMIC_TAGS;
+        let mut execstack = false;
+        let mut separate_code = false;
+        let mut rosegment = true;
+        let mut b_symbolic = BSymbolic::None;
         // Skip program name
         input.next();
         while let Some(arg) = input.next() {
@@ -126,7 +846,7 @@ impl Args {
                 }
             } else if let Some(rest) = arg.strip_prefix("-l") {
                 inputs.push(Input {
-                    spec: InputSpec::Lib(Box::from(rest)),
+                    spec: InputSpec::from_lib_arg(rest),
                     search_first: None,
                     modifiers: *modifier_stack.last().unwrap(),
                 });
@@ -134,6 +854,15 @@ impl Args {
                 modifier_stack.last_mut().unwrap().allow_shared = false;
             } else if arg == "-Bdynamic" {
                 modifier_stack.last_mut().unwrap().allow_shared = true;
+            } else if arg == "-Bsymbolic" {
+                // We don't currently support symbol interposition, so references to symbols that
+                // we define are always bound at link time. These flags therefore only affect the
+                // dynamic flags that we emit.
+                b_symbolic = BSymbolic::All;
+            } else if arg == "-Bsymbolic-functions" {
+                b_symbolic = BSymbolic::Functions;
+            } else if arg == "-Bno-symbolic" {
+                b_symbolic = BSymbolic::None;
             } else if arg == "-o" {
                 output = input.next().map(|a| Arc::from(Path::new(a.as_ref())));
             } else if arg == "--dynamic-linker" || arg == "-dynamic-linker" {
@@ -141,11 +870,34 @@ impl Args {
                 dynamic_linker = input.next().map(|a| Box::from(Path::new(a.as_ref())));
             } else if arg == "--no-dynamic-linker" {
                 dynamic_linker = None;
+            } else if let Some(path) = arg.strip_prefix("--sysroot=") {
+                sysroot = Some(PathBuf::from(path));
+            } else if arg == "--sysroot" {
+                let path = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --sysroot"))?;
+                sysroot = Some(PathBuf::from(path.as_ref()));
             } else if let Some(style) = arg.strip_prefix("--hash-style=") {
-                if style != "gnu" {
-                    bail!("Unsupported hash-style `{style}`");
-                }
+                hash_style = HashStyle::parse(style)?;
+            } else if let Some(format) = arg.strip_prefix("--pack-dyn-relocs=") {
+                pack_relative_relocs = match format {
+                    "relr" => true,
+                    "none" => false,
+                    other => bail!("Unsupported --pack-dyn-relocs format `{other}`"),
+                };
             } else if arg.starts_with("--build-id=") {
+            } else if let Some(count) = arg.strip_prefix("--spare-dynamic-tags=") {
+                spare_dynamic_tags = count
+                    .parse()
+                    .with_context(|| format!("Invalid --spare-dynamic-tags value `{count}`"))?;
+            } else if arg == "--spare-dynamic-tags" {
+                let count = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --spare-dynamic-tags"))?;
+                let count = count.as_ref();
+                spare_dynamic_tags = count
+                    .parse()
+                    .with_context(|| format!("Invalid --spare-dynamic-tags value `{count}`"))?;
             } else if arg == "--time" {
                 time_phases = true;
             } else if let Some(rest) = arg.strip_prefix("--threads=") {
@@ -155,9 +907,65 @@ impl Args {
                 strip_debug = true;
             } else if arg == "--strip-debug" {
                 strip_debug = true;
-            } else if arg == "-z" || arg == "-m" {
-                // Ignore this argument and the next thing that comes after it.
-                input.next();
+            } else if arg == "-z" {
+                // We always bind eagerly, so there's no lazily bound part of the GOT that would need
+                // to stay writable. That means partial RELRO is the same as full RELRO and `-z now`
+                // and `-z lazy` make no difference. Other keywords are ignored.
+                match input.next().as_ref().map(|a| a.as_ref()) {
+                    Some("relro") => relro = true,
+                    Some("norelro") => relro = false,
+                    Some("execstack") => execstack = true,
+                    Some("defs") => no_undefined = true,
+                    Some("muldefs") => allow_multiple_definition = true,
+                    Some("noexecstack") => execstack = false,
+                    Some("separate-code") => separate_code = true,
+                    Some("noseparate-code" | "noseparate-loadable-segments") => {
+                        separate_code = false;
+                    }
+                    Some("origin") => z_origin = true,
+                    Some("text") => allow_text_relocations = false,
+                    Some("notext") => allow_text_relocations = true,
+                    Some("pack-relative-relocs") => pack_relative_relocs = true,
+                    Some("nopack-relative-relocs") => pack_relative_relocs = false,
+                    Some("nodelete") => z_flags_1 |= elf::flags_1::NODELETE,
+                    Some("nodlopen") => z_flags_1 |= elf::flags_1::NOOPEN,
+                    Some("initfirst") => z_flags_1 |= elf::flags_1::INITFIRST,
+                    Some("global") => z_flags_1 |= elf::flags_1::GLOBAL,
+                    Some("interpose") => z_flags_1 |= elf::flags_1::INTERPOSE,
+                    Some("loadfltr") => z_flags_1 |= elf::flags_1::LOADFLTR,
+                    Some("nodefaultlib") => z_flags_1 |= elf::flags_1::NODEFLIB,
+                    Some("nodump") => z_flags_1 |= elf::flags_1::NODUMP,
+                    Some(keyword) => {
+                        if let Some(size) = keyword.strip_prefix("max-page-size=") {
+                            max_page_size = Alignment::page_size(
+                                parse_number(size)
+                                    .with_context(|| format!("Invalid page size `{size}`"))?,
+                            )?;
+                        } else if let Some(size) = keyword.strip_prefix("common-page-size=") {
+                            common_page_size = Alignment::page_size(
+                                parse_number(size)
+                                    .with_context(|| format!("Invalid page size `{size}`"))?,
+                            )?;
+                        } else if let Some(size) = keyword.strip_prefix("stack-size=") {
+                            stack_size = parse_number(size)
+                                .with_context(|| format!("Invalid stack size `{size}`"))?;
+                        } else if let Some(visibility) =
+                            keyword.strip_prefix("start-stop-visibility=")
+                        {
+                            start_stop_visibility = StartStopVisibility::parse(visibility)?;
+                        }
+                    }
+                    None => {}
+                }
+            } else if arg == "-m" {
+                let value = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to -m"))?;
+                emulation = Some(Emulation::parse(value.as_ref())?);
+            } else if arg == "-EL" {
+                endianness = Endianness::Little;
+            } else if arg == "-EB" {
+                bail!("-EB isn't supported: wild only supports little-endian output");
             } else if let Some(_rest) = arg.strip_prefix("-O") {
                 // We don't use opt-level for now.
             } else if arg == "--prepopulate-maps" {
@@ -168,6 +976,24 @@ impl Args {
                 modifier_stack.last_mut().unwrap().as_needed = true;
             } else if arg == "--no-as-needed" {
                 modifier_stack.last_mut().unwrap().as_needed = false;
+            } else if arg == "--whole-archive" {
+                modifier_stack.last_mut().unwrap().whole_archive = true;
+            } else if arg == "--no-whole-archive" {
+                modifier_stack.last_mut().unwrap().whole_archive = false;
+            } else if arg == "--start-group" || arg == "-(" {
+                // We decide which archive members to load by looking at all our inputs at once, so
+                // mutually dependent archives already resolve without needing to revisit a group.
+                // We still check that groups are well-formed, since a mistake here would make the
+                // link fail with GNU ld.
+                if in_group {
+                    bail!("Groups may not be nested");
+                }
+                in_group = true;
+            } else if arg == "--end-group" || arg == "-)" {
+                if !in_group {
+                    bail!("{arg} without a matching --start-group");
+                }
+                in_group = false;
             } else if arg == "--push-state" {
                 modifier_stack.push(*modifier_stack.last().unwrap());
             } else if arg == "--pop-state" {
@@ -180,28 +1006,326 @@ impl Args {
             } else if let Some(script) = arg.strip_prefix("--version-script=") {
                 save_dir.handle_file(script)?;
                 version_script_path = Some(PathBuf::from(script));
+            } else if let Some(list) = arg.strip_prefix("--dynamic-list=") {
+                save_dir.handle_file(list)?;
+                dynamic_list_path = Some(PathBuf::from(list));
+            } else if arg == "--dynamic-list" {
+                let list = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --dynamic-list"))?;
+                save_dir.handle_file(list.as_ref())?;
+                dynamic_list_path = Some(PathBuf::from(list.as_ref()));
+            } else if arg == "-E" || arg == "--export-dynamic" {
+                export_all_dynamic_symbols = true;
+            } else if arg == "--no-export-dynamic" {
+                export_all_dynamic_symbols = false;
+            } else if let Some(rest) = arg.strip_prefix("--section-start=") {
+                section_starts.push(parse_section_start(rest)?);
+            } else if arg == "--section-start" {
+                let rest = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --section-start"))?;
+                section_starts.push(parse_section_start(rest.as_ref())?);
+            } else if arg == "--check-sections" {
+                check_sections = true;
+            } else if arg == "--no-check-sections" {
+                check_sections = false;
+            } else if let Some(rest) = arg.strip_prefix("--defsym=") {
+                defsyms.push(Defsym::parse(rest, false)?);
+            } else if arg == "--defsym" {
+                let rest = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --defsym"))?;
+                defsyms.push(Defsym::parse(rest.as_ref(), false)?);
+            } else if let Some(rest) = arg.strip_prefix("--defsym-weak=") {
+                defsyms.push(Defsym::parse(rest, true)?);
+            } else if arg == "--defsym-weak" {
+                let rest = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --defsym-weak"))?;
+                defsyms.push(Defsym::parse(rest.as_ref(), true)?);
+            } else if arg == "-M" || arg == "--print-map" {
+                map_file = Some(MapOutput::Stdout);
+            } else if let Some(path) = arg
+                .strip_prefix("-Map=")
+                .or_else(|| arg.strip_prefix("--Map="))
+            {
+                map_file = Some(MapOutput::File(PathBuf::from(path)));
+            } else if arg == "-Map" || arg == "--Map" {
+                let path = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to {arg}"))?;
+                map_file = Some(MapOutput::File(PathBuf::from(path.as_ref())));
+            } else if let Some(format) = arg.strip_prefix("--map-format=") {
+                map_format = MapFormat::parse(format)?;
+            } else if arg == "--map-format" {
+                let format = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --map-format"))?;
+                map_format = MapFormat::parse(format.as_ref())?;
+            } else if let Some(script) = arg.strip_prefix("--error-handling-script=") {
+                error_handling_script = Some(PathBuf::from(script));
+            } else if arg == "--error-handling-script" {
+                let script = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --error-handling-script"))?;
+                error_handling_script = Some(PathBuf::from(script.as_ref()));
+            } else if let Some(mode) = arg.strip_prefix("--icf=") {
+                icf = Icf::parse(mode)?;
+            } else if arg == "--icf" {
+                let mode = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --icf"))?;
+                icf = Icf::parse(mode.as_ref())?;
+            } else if let Some(mode) = arg.strip_prefix("--sort-section=") {
+                sort_section = SortSection::parse(mode)?;
+            } else if arg == "--sort-section" {
+                let mode = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --sort-section"))?;
+                sort_section = SortSection::parse(mode.as_ref())?;
+            } else if arg == "--sort-common" {
+                sort_common = SortCommon::Descending;
+            } else if let Some(order) = arg.strip_prefix("--sort-common=") {
+                sort_common = SortCommon::parse(order)?;
+            } else if let Some(mode) = arg.strip_prefix("--orphan-handling=") {
+                orphan_handling = OrphanHandling::parse(mode)?;
+            } else if arg == "--orphan-handling" {
+                let mode = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --orphan-handling"))?;
+                orphan_handling = OrphanHandling::parse(mode.as_ref())?;
+            } else if let Some(method) = arg.strip_prefix("--unresolved-symbols=") {
+                unresolved_symbols = UnresolvedSymbols::parse(method)?;
+            } else if arg == "--unresolved-symbols" {
+                let method = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --unresolved-symbols"))?;
+                unresolved_symbols = UnresolvedSymbols::parse(method.as_ref())?;
+            } else if arg == "--allow-shlib-undefined" {
+                unresolved_symbols = unresolved_symbols.with_report_in_shared_libs(false);
+            } else if arg == "--no-allow-shlib-undefined" {
+                unresolved_symbols = unresolved_symbols.with_report_in_shared_libs(true);
+            } else if arg == "--warn-unresolved-symbols" {
+                warn_unresolved_symbols = true;
+            } else if arg == "--error-unresolved-symbols" {
+                warn_unresolved_symbols = false;
+            } else if arg == "--cref" {
+                cref = true;
+            } else if arg == "-u" || arg == "--undefined" {
+                let name = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to {arg}"))?;
+                undefined.push(name.as_ref().to_owned());
+            } else if let Some(name) = arg
+                .strip_prefix("--undefined=")
+                .or_else(|| arg.strip_prefix("-u"))
+            {
+                undefined.push(name.to_owned());
+            } else if arg == "-y" || arg == "--trace-symbol" {
+                let name = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to {arg}"))?;
+                trace_symbols.push(name.as_ref().to_owned());
+            } else if let Some(name) = arg
+                .strip_prefix("--trace-symbol=")
+                .or_else(|| arg.strip_prefix("-y"))
+            {
+                trace_symbols.push(name.to_owned());
+            } else if arg == "--no-undefined" {
+                no_undefined = true;
+            } else if arg == "-rpath" || arg == "--rpath" {
+                let path = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to {arg}"))?;
+                add_rpath(&mut rpath, path.as_ref())?;
+            } else if let Some(path) = arg
+                .strip_prefix("-rpath=")
+                .or_else(|| arg.strip_prefix("--rpath="))
+            {
+                add_rpath(&mut rpath, path)?;
+            } else if arg == "--enable-new-dtags" {
+                new_dtags = true;
+            } else if arg == "--disable-new-dtags" {
+                new_dtags = false;
+            } else if arg == "--print-gc-sections" {
+                print_gc_sections = true;
+            } else if arg == "--no-print-gc-sections" {
+                print_gc_sections = false;
+            } else if arg == "--gc-keep-exported" {
+                gc_keep_exported = true;
+            } else if arg == "--print-icf-sections" {
+                print_icf_sections = true;
+            } else if arg == "--no-print-icf-sections" {
+                print_icf_sections = false;
+            } else if arg == "--print-memory-usage" {
+                print_memory_usage = true;
+            } else if arg == "--warn-common" {
+                warn_common = true;
+            } else if arg == "--warn-textrel" || arg == "--warn-shared-textrel" {
+                warn_textrel = true;
+            } else if arg == "--fatal-warnings" {
+                fatal_warnings = true;
+            } else if arg == "--no-fatal-warnings" {
+                fatal_warnings = false;
+            } else if let Some(policy) = arg.strip_prefix("--unknown-flags=") {
+                unknown_flags = UnknownFlags::parse(policy)?;
+            } else if arg == "--noinhibit-exec" {
+                noinhibit_exec = true;
+            } else if arg == "--allow-multiple-definition" {
+                allow_multiple_definition = true;
+            } else if arg == "--gdb-index" {
+                gdb_index = true;
+            } else if arg == "--no-gdb-index" {
+                gdb_index = false;
+            } else if arg == "--incremental" {
+                incremental = true;
+            } else if arg == "--no-incremental" {
+                incremental = false;
+            } else if arg == "--rosegment" {
+                rosegment = true;
+            } else if arg == "--no-rosegment" {
+                rosegment = false;
+            } else if let Some(dir) = arg.strip_prefix("--reproduce-on-error=") {
+                reproduce_on_error = Some(PathBuf::from(dir));
+            } else if arg == "--reproduce-on-error" {
+                let dir = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --reproduce-on-error"))?;
+                reproduce_on_error = Some(PathBuf::from(dir.as_ref()));
+            } else if let Some(path) = arg.strip_prefix("--repro=") {
+                repro = Some(PathBuf::from(path));
+            } else if arg == "--repro" {
+                let path = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --repro"))?;
+                repro = Some(PathBuf::from(path.as_ref()));
+            } else if let Some(dir) = arg.strip_prefix("--object-cache=") {
+                object_cache = Some(PathBuf::from(dir));
+            } else if arg == "--object-cache" {
+                let dir = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --object-cache"))?;
+                object_cache = Some(PathBuf::from(dir.as_ref()));
+            } else if arg == "--emit-relocs" || arg == "-q" {
+                emit_relocs = true;
+            } else if let Some(path) = arg.strip_prefix("--script=") {
+                save_dir.handle_file(path)?;
+                linker_script = Some(read_linker_script(Path::new(path))?);
+            } else if arg == "-T" || arg == "--script" {
+                let path = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to {arg}"))?;
+                save_dir.handle_file(path.as_ref())?;
+                linker_script = Some(read_linker_script(Path::new(path.as_ref()))?);
+            } else if let Some(path) = arg.strip_prefix("--retain-symbols-file=") {
+                save_dir.handle_file(path)?;
+                retain_symbols = Some(read_retain_symbols_file(Path::new(path))?);
+            } else if arg == "--retain-symbols-file" {
+                let path = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --retain-symbols-file"))?;
+                save_dir.handle_file(path.as_ref())?;
+                retain_symbols = Some(read_retain_symbols_file(Path::new(path.as_ref()))?);
+            } else if let Some(pattern) = arg.strip_prefix("--localize-symbol=") {
+                localize_symbols.push(pattern.to_owned());
+            } else if arg == "--localize-symbol" {
+                let pattern = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --localize-symbol"))?;
+                localize_symbols.push(pattern.as_ref().to_owned());
+            } else if arg == "--localize-hidden" {
+                localize_hidden = true;
+            } else if let Some(pattern) = arg.strip_prefix("--keep-section=") {
+                keep_sections.push(pattern.to_owned());
+            } else if arg == "--keep-section" {
+                let pattern = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --keep-section"))?;
+                keep_sections.push(pattern.as_ref().to_owned());
+            } else if let Some(pattern) = arg.strip_prefix("--remove-section=") {
+                remove_sections.push(pattern.to_owned());
+            } else if arg == "--remove-section" {
+                let pattern = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --remove-section"))?;
+                remove_sections.push(pattern.as_ref().to_owned());
+            } else if let Some(rule) = arg.strip_prefix("--compress-sections=") {
+                compress_sections.push(CompressSections::parse(rule)?);
+            } else if arg == "--compress-sections" {
+                let rule = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to --compress-sections"))?;
+                compress_sections.push(CompressSections::parse(rule.as_ref())?);
             } else if arg == "--no-string-merge" {
                 merge_strings = false;
-            } else if arg == "-pie" {
+            } else if arg == "--no-merge" {
+                merge_strings = false;
+                merge_constants = false;
+            } else if let Some(pattern) = arg.strip_prefix("--merge=") {
+                merge_rules.push(MergeRule {
+                    pattern: pattern.to_owned(),
+                    merge: true,
+                });
+            } else if let Some(pattern) = arg.strip_prefix("--no-merge=") {
+                merge_rules.push(MergeRule {
+                    pattern: pattern.to_owned(),
+                    merge: false,
+                });
+            } else if arg == "-pie" || arg == "--pie" {
                 pie = true;
+            } else if arg == "-no-pie" || arg == "--no-pie" {
+                pie = false;
             } else if arg == "-shared" {
                 output_kind = OutputKind::SharedObject;
-            } else if arg.starts_with("-plugin-opt=") {
-                // TODO: Implement support for linker plugins.
-            } else if arg == "-plugin" {
-                input.next();
+            } else if arg == "-r" || arg == "--relocatable" {
+                relocatable = true;
+            } else if let Some(opt) = arg
+                .strip_prefix("-plugin-opt=")
+                .or_else(|| arg.strip_prefix("--plugin-opt="))
+            {
+                plugin_opts.push(opt.to_owned());
+            } else if arg == "-plugin-opt" || arg == "--plugin-opt" {
+                let opt = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to {arg}"))?;
+                plugin_opts.push(opt.as_ref().to_owned());
+            } else if let Some(path) = arg
+                .strip_prefix("-plugin=")
+                .or_else(|| arg.strip_prefix("--plugin="))
+            {
+                plugin = Some(PathBuf::from(path));
+            } else if arg == "-plugin" || arg == "--plugin" {
+                let path = input
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing argument to {arg}"))?;
+                plugin = Some(PathBuf::from(path.as_ref()));
             } else if arg == "--validate-output" {
                 validate_output = true;
+            } else if arg == "--check-reproducible" {
+                check_reproducible = true;
+            } else if arg == "--apply-dynamic-relocs" {
+                apply_dynamic_relocs = true;
+            } else if arg == "--no-apply-dynamic-relocs" {
+                apply_dynamic_relocs = false;
             } else if let Some(rest) = arg.strip_prefix("--debug-fuel=") {
                 debug_fuel = Some(AtomicI64::new(rest.parse()?));
                 // Using debug fuel with more than one thread would likely give non-deterministic
                 // results.
                 num_threads = Some(NonZeroUsize::new(1).unwrap());
+            } else if arg == "--print-output-format" {
+                query = Some(Query::OutputFormat);
+            } else if arg == "--print-sysroot" {
+                query = Some(Query::Sysroot);
             } else if arg == "--help" {
                 bail!("Sorry, help isn't implemented yet");
             } else if IGNORED_FLAGS.contains(&arg) {
+            } else if CORTEX_A53_FIX_FLAGS.contains(&arg) {
+                cortex_a53_fixes.push(arg.to_owned());
             } else if arg.starts_with('-') {
-                bail!("Unrecognised argument `{arg}`");
+                // We apply `--unknown-flags` once we've seen all our arguments, so that it doesn't
+                // matter where it appears.
+                unknown_flag_args.push(arg.to_owned());
             } else {
                 save_dir.handle_file(arg)?;
                 inputs.push(Input {
@@ -211,14 +1335,59 @@ impl Args {
                 });
             }
         }
+        let ignored_unknown_flags = match unknown_flags {
+            UnknownFlags::Error => {
+                if let Some(arg) = unknown_flag_args.first() {
+                    bail!("Unrecognised argument `{arg}`");
+                }
+                Vec::new()
+            }
+            UnknownFlags::Warn => unknown_flag_args,
+            UnknownFlags::Ignore => Vec::new(),
+        };
+        if relocatable {
+            if output_kind == OutputKind::SharedObject {
+                bail!("-r and -shared may not be used together");
+            }
+            output_kind = OutputKind::Relocatable;
+            // The output is an object file, so we keep every relocation and leave sections as they
+            // were, since a later link will need to process them again.
+            emit_relocs = true;
+            merge_strings = false;
+            merge_constants = false;
+            merge_rules.clear();
+        }
+        if check_reproducible {
+            // These have effects beyond writing the output, so the second link wouldn't be a
+            // re-run of the first, or something would get written twice.
+            let conflicting = [
+                (incremental, "--incremental"),
+                (object_cache.is_some(), "--object-cache"),
+                (repro.is_some(), "--repro"),
+                (map_file.is_some(), "-Map"),
+                (cref, "--cref"),
+                (print_memory_usage, "--print-memory-usage"),
+            ];
+            if let Some((_, flag)) = conflicting.iter().find(|(is_set, _)| *is_set) {
+                bail!("--check-reproducible and {flag} may not be used together");
+            }
+        }
         let num_threads = num_threads.unwrap_or_else(|| {
             std::thread::available_parallelism().unwrap_or(NonZeroUsize::new(1).unwrap())
         });
         save_dir.finish()?;
+        let reproduce_on_error = reproduce_on_error.map(|dir| save_dir.deferred(dir));
+        let lib_search_path = lib_search_path
+            .into_iter()
+            .map(|dir| apply_sysroot(dir, sysroot.as_deref()))
+            .collect();
         Ok(Args {
             lib_search_path,
             inputs,
-            output: output.ok_or_else(|| anyhow!("Missing required argument -o"))?,
+            // Queries don't write an output file, so don't need to be told where to write it.
+            output: output
+                .or_else(|| query.map(|_| Arc::from(Path::new("a.out"))))
+                .ok_or_else(|| anyhow!("Missing required argument -o"))?,
             dynamic_linker,
             output_kind,
             time_phases,
@@ -228,28 +1397,197 @@ impl Args {
             prepopulate_maps,
             sym_info,
             merge_strings,
+            merge_constants,
+            merge_rules,
             debug_fuel,
             pie,
             validate_output,
+            check_reproducible,
+            apply_dynamic_relocs,
             version_script_path,
+            defsyms,
+            section_starts,
+            check_sections,
+            relro,
+            max_page_size,
+            common_page_size: common_page_size.min(max_page_size),
+            stack_size,
+            spare_dynamic_tags,
+            execstack,
+            separate_code,
+            rosegment,
+            b_symbolic,
+            export_all_dynamic_symbols,
+            dynamic_list_path,
+            map_file,
+            map_format,
+            cref,
+            error_handling_script,
+            icf,
+            sort_section,
+            sort_common,
+            orphan_handling,
+            unresolved_symbols,
+            warn_unresolved_symbols,
+            emit_relocs,
+            retain_symbols,
+            localize_symbols,
+            localize_hidden,
+            keep_sections,
+            remove_sections,
+            compress_sections,
+            linker_script,
+            hash_style,
+            emulation,
+            endianness,
+            sysroot,
+            query,
+            print_gc_sections,
+            gc_keep_exported,
+            print_icf_sections,
+            print_memory_usage,
+            undefined,
+            trace_symbols,
+            no_undefined,
+            rpath,
+            new_dtags,
+            z_origin,
+            z_flags_1,
+            start_stop_visibility,
+            object_cache,
+            warn_common,
+            fatal_warnings,
+            ignored_unknown_flags,
+            cortex_a53_fixes,
+            noinhibit_exec,
+            allow_multiple_definition,
+            gdb_index,
+            repro,
+            reproduce_on_error,
+            allow_text_relocations,
+            warn_textrel,
+            pack_relative_relocs,
+            plugin,
+            plugin_opts,
+            incremental,
         })
     }
 
-    pub(crate) fn setup_thread_pool(&self) -> Result {
-        rayon::ThreadPoolBuilder::new()
+    /// Builds the thread pool that the link runs on. Each link gets its own pool rather than using
+    /// rayon's global pool, so that when we're embedded in a process that links more than once,
+    /// each link respects its own `--threads`.
+    pub(crate) fn build_thread_pool(&self) -> Result<rayon::ThreadPool> {
+        Ok(rayon::ThreadPoolBuilder::new()
             .num_threads(self.num_threads.get())
-            .build_global()?;
-        Ok(())
+            .build()?)
     }
 
     pub(crate) fn base_address(&self) -> u64 {
-        if self.pie {
+        if self.pie || self.output_kind == OutputKind::Relocatable {
             0
         } else {
             crate::elf::NON_PIE_START_MEM_ADDRESS
         }
     }
 
+    /// Returns whether a symbol with the supplied name is allowed in the output symbol table by
+    /// `--retain-symbols-file`. Always true if that flag wasn't given.
+    pub(crate) fn should_retain_symbol(&self, name: &[u8]) -> bool {
+        self.retain_symbols
+            .as_ref()
+            .is_none_or(|retain| std::str::from_utf8(name).is_ok_and(|name| retain.contains(name)))
+    }
+
+    /// Returns whether a global symbol with the specified name and visibility (`st_other & 3`)
+    /// should be made local in the output.
+    pub(crate) fn should_localize_symbol(&self, name: &[u8], visibility: u8) -> bool {
+        (self.localize_hidden
+            && matches!(
+                visibility,
+                object::elf::STV_HIDDEN | object::elf::STV_INTERNAL
+            ))
+            || self
+                .localize_symbols
+                .iter()
+                .any(|pattern| crate::linker_script::glob_matches(pattern.as_bytes(), name))
+    }
+
+    /// Returns the names of the symbols that `--defsym` expressions refer to. These need to be
+    /// loaded as if they'd been named via `-u`.
+    pub(crate) fn defsym_operands(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        for defsym in &self.defsyms {
+            defsym.value.for_each_symbol(&mut |name| names.push(name));
+        }
+        names
+    }
+
+    /// Returns whether input sections with the specified name should be kept, even if they're not
+    /// referenced.
+    pub(crate) fn should_keep_section(&self, name: &[u8]) -> bool {
+        self.keep_sections
+            .iter()
+            .any(|pattern| crate::linker_script::glob_matches(pattern.as_bytes(), name))
+    }
+
+    /// Returns whether input sections with the specified name should be left out of the output.
+    pub(crate) fn should_remove_section(&self, name: &[u8]) -> bool {
+        self.remove_sections
+            .iter()
+            .any(|pattern| crate::linker_script::glob_matches(pattern.as_bytes(), name))
+    }
+
+    /// Returns whether we should merge the contents of `SHF_MERGE` sections that go into the output
+    /// section with the specified name. `is_strings` says whether the sections contain strings
+    /// rather than fixed-size constants. If multiple `--merge=` / `--no-merge=` rules match, the
+    /// last one wins. If none match, we fall back to the global setting.
+    pub(crate) fn should_merge_section(&self, output_name: &[u8], is_strings: bool) -> bool {
+        self.merge_rules
+            .iter()
+            .rev()
+            .find(|rule| crate::linker_script::glob_matches(rule.pattern.as_bytes(), output_name))
+            .map_or(
+                if is_strings {
+                    self.merge_strings
+                } else {
+                    self.merge_constants
+                },
+                |rule| rule.merge,
+            )
+    }
+
+    /// Returns the `--compress-sections` rule for output sections with the specified name, if they
+    /// should be compressed. If multiple rules match, the last one wins, so a later rule with an
+    /// algorithm of `none` can exclude sections matched by an earlier one.
+    pub(crate) fn section_compression(&self, name: &[u8]) -> Option<&CompressSections> {
+        self.compress_sections
+            .iter()
+            .rev()
+            .find(|rule| crate::linker_script::glob_matches(rule.pattern.as_bytes(), name))
+            .filter(|rule| rule.format.is_some())
+    }
+
+    /// Prints the answer to `query` to stdout.
+    pub(crate) fn print_query(&self, query: Query) -> Result {
+        let mut stdout = std::io::stdout().lock();
+        match query {
+            Query::OutputFormat => writeln!(
+                stdout,
+                "{}",
+                self.emulation.unwrap_or_default().output_format()
+            )?,
+            Query::Sysroot => writeln!(
+                stdout,
+                "{}",
+                self.sysroot
+                    .as_deref()
+                    .map(|sysroot| sysroot.display().to_string())
+                    .unwrap_or_default()
+            )?,
+        }
+        Ok(())
+    }
+
     /// Uses 1 debug fuel, returning how much fuel remains. Debug fuel is intended to be used when
     /// debugging certain kinds of bugs, so this function isn't normally referenced. To use it, the
     /// caller should take a different branch depending on whether the value is still positive. You
@@ -284,13 +1622,158 @@ impl Args {
         }
     }
 
+    /// Returns whether the output might be loaded at an address other than the one we link it at,
+    /// in which case absolute addresses need dynamic relocations.
     pub(crate) fn is_relocatable(&self) -> bool {
-        self.pie || self.output_kind != OutputKind::StaticExecutable
+        self.pie || self.output_kind == OutputKind::SharedObject
+    }
+
+    /// Returns whether we'll emit relative relocations in `.relr.dyn`. Only outputs that can be
+    /// loaded at a different address have relative relocations to pack.
+    pub(crate) fn uses_relr(&self) -> bool {
+        self.pack_relative_relocs && self.is_relocatable()
     }
 
-    /// Returns whether we need a dynamic section.
+    /// Returns whether we need a dynamic section. A non-PIE executable still needs one if it has
+    /// a dynamic linker.
     pub(crate) fn needs_dynamic(&self) -> bool {
-        self.is_relocatable()
+        self.is_relocatable() || self.output_kind == OutputKind::DynamicExecutable
+    }
+
+    /// Returns whether we should set the ORIGIN dynamic flags, which tell the loader that it'll
+    /// need to know where we were loaded from. This is the case if `-z origin` was given or if our
+    /// rpath refers to `$ORIGIN`.
+    pub(crate) fn needs_origin(&self) -> bool {
+        self.z_origin
+            || self
+                .rpath
+                .as_ref()
+                .is_some_and(|rpath| rpath.contains("$ORIGIN") || rpath.contains("${ORIGIN}"))
+    }
+}
+
+/// Appends `path` to the colon-separated `rpath`. Multiple `-rpath` arguments are combined in this
+/// way, as GNU ld does.
+fn add_rpath(rpath: &mut Option<String>, path: &str) -> Result {
+    if path.is_empty() {
+        bail!("-rpath requires a non-empty path");
+    }
+    match rpath {
+        Some(existing) => {
+            existing.push(':');
+            existing.push_str(path);
+        }
+        None => *rpath = Some(path.to_owned()),
+    }
+    Ok(())
+}
+
+/// Replaces a leading `=` or `$SYSROOT` in a library search directory with the directory given by
+/// `--sysroot`. Without a sysroot, the prefix is just dropped.
+fn apply_sysroot(dir: Box<Path>, sysroot: Option<&Path>) -> Box<Path> {
+    let Some(dir_str) = dir.to_str() else {
+        return dir;
+    };
+    let Some(rest) = dir_str
+        .strip_prefix('=')
+        .or_else(|| dir_str.strip_prefix("$SYSROOT"))
+    else {
+        return dir;
+    };
+    let mut path = sysroot.map(Path::to_path_buf).unwrap_or_default();
+    path.as_mut_os_string().push(rest);
+    Box::from(path)
+}
+
+/// Parses an argument to `--section-start` of the form `section=address`.
+fn parse_section_start(arg: &str) -> Result<(String, u64)> {
+    let (name, address) = arg
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Invalid --section-start `{arg}`. Expected `section=address`"))?;
+    if name.is_empty() {
+        bail!("Invalid --section-start `{arg}`. Missing section name");
+    }
+    let address = parse_number(address)
+        .ok_or_else(|| anyhow!("Invalid address `{address}` for section `{name}`"))?;
+    Ok((name.to_owned(), address))
+}
+
+impl Defsym {
+    /// Parses a definition of the form `symbol=value`.
+    fn parse(arg: &str, weak: bool) -> Result<Self> {
+        let (name, value) = arg
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid defsym `{arg}`. Expected `symbol=value`"))?;
+        if name.is_empty() {
+            bail!("Invalid defsym `{arg}`. Missing symbol name");
+        }
+        Ok(Defsym {
+            name: name.to_owned(),
+            value: DefsymExpr::parse(value)
+                .with_context(|| format!("Invalid value `{value}` for defsym `{name}`"))?,
+            weak,
+        })
+    }
+}
+
+impl CompressSections {
+    /// Parses a rule of the form `glob=algorithm[:level]`.
+    fn parse(arg: &str) -> Result<Self> {
+        let (pattern, spec) = arg.rsplit_once('=').ok_or_else(|| {
+            anyhow!("Invalid --compress-sections `{arg}`. Expected `glob=algorithm[:level]`")
+        })?;
+        let (algorithm, level) = match spec.split_once(':') {
+            Some((algorithm, level)) => (algorithm, Some(level)),
+            None => (spec, None),
+        };
+        let (format, levels) = match algorithm {
+            "none" => (None, 0..=0),
+            "zlib" => (Some(CompressionFormat::Zlib), 0..=9),
+            "zstd" => (Some(CompressionFormat::Zstd), 1..=22),
+            other => bail!("Unsupported --compress-sections algorithm `{other}`"),
+        };
+        let level = level
+            .map(|level| {
+                level
+                    .parse::<i32>()
+                    .ok()
+                    .filter(|level| format.is_some() && levels.contains(level))
+                    .ok_or_else(|| anyhow!("Invalid compression level `{level}` for `{algorithm}`"))
+            })
+            .transpose()?;
+        Ok(CompressSections {
+            pattern: pattern.to_owned(),
+            format,
+            level,
+        })
+    }
+}
+
+/// Reads a file for `--retain-symbols-file`, which contains one symbol name per line.
+fn read_retain_symbols_file(path: &Path) -> Result<HashSet<String>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read retain-symbols file `{}`", path.display()))?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect())
+}
+
+fn read_linker_script(path: &Path) -> Result<LinkerScript> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read linker script `{}`", path.display()))?;
+    LinkerScript::parse(&contents)
+        .with_context(|| format!("Failed to parse linker script `{}`", path.display()))
+}
+
+/// Parses a decimal or 0x-prefixed hexadecimal number.
+pub(crate) fn parse_number(s: &str) -> Option<u64> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else {
+        s.parse().ok()
     }
 }
 
@@ -299,6 +1782,7 @@ impl Default for Modifiers {
         Self {
             as_needed: false,
             allow_shared: true,
+            whole_archive: false,
         }
     }
 }
@@ -309,14 +1793,34 @@ impl OutputKind {
             OutputKind::StaticExecutable => true,
             OutputKind::DynamicExecutable => true,
             OutputKind::SharedObject => false,
+            OutputKind::Relocatable => false,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::args::Args;
+    use crate::args::BSymbolic;
+    use crate::args::Defsym;
+    use crate::args::Emulation;
+    use crate::args::Endianness;
+    use crate::args::HashStyle;
+    use crate::args::Icf;
     use crate::args::InputSpec;
+    use crate::args::MapFormat;
+    use crate::args::MapOutput;
+    use crate::args::OrphanHandling;
+    use crate::args::OutputKind;
+    use crate::args::Query;
+    use crate::args::SortCommon;
+    use crate::args::SortSection;
+    use crate::args::StartStopVisibility;
+    use crate::args::UnresolvedSymbols;
+    use crate::defsym::DefsymExpr;
+    use crate::error::Result;
     use std::path::Path;
+    use std::path::PathBuf;
 
     const INPUT1: &[&str] = &[
         "wild",
@@ -396,6 +1900,14 @@ mod tests {
         "/lib/x86_64-linux-gnu/crtn.o",
     ];
 
+    /// Parses `flags`, adding the `-o` that's required for a link.
+    fn parse(flags: &[&str]) -> Result<Args> {
+        let mut args = vec!["wild"];
+        args.extend_from_slice(flags);
+        args.extend_from_slice(&["-o", "/tmp/out"]);
+        Args::parse(args.iter())
+    }
+
     #[track_caller]
     fn assert_contains(c: &[Box<Path>], v: &str) {
         assert!(c.iter().any(|p| p.as_ref() == Path::new(v)));
@@ -408,7 +1920,7 @@ mod tests {
             args.inputs
                 .iter()
                 .filter_map(|i| match &i.spec {
-                    InputSpec::File(_) => None,
+                    InputSpec::File(_) | InputSpec::Search(_) => None,
                     InputSpec::Lib(lib_name) => Some(lib_name.as_ref()),
                 })
                 .collect::<Vec<&str>>(),
@@ -421,4 +1933,878 @@ mod tests {
             _ => false,
         }));
     }
+
+    #[test]
+    fn test_b_symbolic() {
+        let parse_symbolic =
+            |flags: &[&str]| parse(&[&["-shared"], flags].concat()).unwrap().b_symbolic;
+        assert_eq!(parse_symbolic(&[]), BSymbolic::None);
+        assert_eq!(parse_symbolic(&["-Bsymbolic"]), BSymbolic::All);
+        assert_eq!(
+            parse_symbolic(&["-Bsymbolic-functions"]),
+            BSymbolic::Functions
+        );
+        assert_eq!(
+            parse_symbolic(&["-Bsymbolic", "-Bno-symbolic"]),
+            BSymbolic::None
+        );
+    }
+
+    #[test]
+    fn test_lib_exact_name() {
+        let args = parse(&["-lfoo", "-l:foo.so.1"]).unwrap();
+        assert_eq!(
+            args.inputs.iter().map(|i| &i.spec).collect::<Vec<_>>(),
+            [
+                &InputSpec::Lib(Box::from("foo")),
+                &InputSpec::Search(Box::from("foo.so.1"))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_defsym() {
+        let args = parse(&["--defsym=foo=0x20", "--defsym-weak", "bar=baz-7"]).unwrap();
+        assert_eq!(
+            args.defsyms,
+            &[
+                Defsym {
+                    name: "foo".to_owned(),
+                    value: DefsymExpr::Number(0x20),
+                    weak: false,
+                },
+                Defsym {
+                    name: "bar".to_owned(),
+                    value: DefsymExpr::Sub(
+                        Box::new(DefsymExpr::Symbol("baz".to_owned())),
+                        Box::new(DefsymExpr::Number(7)),
+                    ),
+                    weak: true,
+                },
+            ]
+        );
+        assert!(parse(&["--defsym=foo"]).is_err());
+        assert!(parse(&["--defsym=foo=bar+"]).is_err());
+    }
+
+    #[test]
+    fn test_section_start() {
+        let args = parse(&[]).unwrap();
+        assert!(args.section_starts.is_empty());
+        assert!(args.check_sections);
+        let args = parse(&[
+            "--section-start=.data=0x500000",
+            "--section-start",
+            ".text=4096",
+            "--no-check-sections",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.section_starts,
+            [(".data".to_owned(), 0x500000), (".text".to_owned(), 4096)]
+        );
+        assert!(!args.check_sections);
+        assert!(
+            parse(&["--no-check-sections", "--check-sections"])
+                .unwrap()
+                .check_sections
+        );
+        assert!(parse(&["--section-start=.data"]).is_err());
+        assert!(parse(&["--section-start==0x1000"]).is_err());
+        assert!(parse(&["--section-start=.data=nope"]).is_err());
+    }
+
+    #[test]
+    fn test_export_dynamic() {
+        let args = parse(&[]).unwrap();
+        assert!(!args.export_all_dynamic_symbols);
+        assert_eq!(args.dynamic_list_path, None);
+
+        let args = parse(&["-E", "--dynamic-list", "/tmp/list"]).unwrap();
+        assert!(args.export_all_dynamic_symbols);
+        assert_eq!(
+            args.dynamic_list_path.as_deref(),
+            Some(Path::new("/tmp/list"))
+        );
+
+        let args = parse(&[
+            "--export-dynamic",
+            "--no-export-dynamic",
+            "--dynamic-list=/tmp/list2",
+        ])
+        .unwrap();
+        assert!(!args.export_all_dynamic_symbols);
+        assert_eq!(
+            args.dynamic_list_path.as_deref(),
+            Some(Path::new("/tmp/list2"))
+        );
+    }
+
+    #[test]
+    fn test_groups() {
+        let args = parse(&[
+            "--start-group",
+            "-la",
+            "-lb",
+            "--end-group",
+            "-(",
+            "-lc",
+            "-)",
+        ])
+        .unwrap();
+        assert_eq!(args.inputs.len(), 3);
+        assert!(parse(&["--start-group", "--start-group"]).is_err());
+        assert!(parse(&["--end-group"]).is_err());
+        assert!(parse(&["--start-group", "--end-group", "-)"]).is_err());
+    }
+
+    #[test]
+    fn test_whole_archive() {
+        let args = parse(&[
+            "-la",
+            "--whole-archive",
+            "-lb",
+            "--push-state",
+            "--no-whole-archive",
+            "-lc",
+            "--pop-state",
+            "-ld",
+            "--no-whole-archive",
+            "-le",
+        ])
+        .unwrap();
+        let whole_archive: Vec<bool> = args
+            .inputs
+            .iter()
+            .map(|input| input.modifiers.whole_archive)
+            .collect();
+        assert_eq!(whole_archive, [false, true, false, true, false]);
+    }
+
+    #[test]
+    fn test_relro() {
+        let parse_relro = |flags: &[&str]| parse(flags).unwrap().relro;
+        assert!(parse_relro(&[]));
+        assert!(!parse_relro(&["-z", "norelro"]));
+        assert!(parse_relro(&["-z", "norelro", "-z", "now", "-z", "relro"]));
+    }
+
+    #[test]
+    fn test_pie() {
+        let parse_pie = |flags: &[&str]| parse(flags).unwrap();
+        let args = parse_pie(&[]);
+        assert!(!args.pie);
+        assert!(!args.is_relocatable());
+        for flag in ["-pie", "--pie"] {
+            let args = parse_pie(&[flag]);
+            assert!(args.pie);
+            assert!(args.is_relocatable());
+            assert!(args.needs_dynamic());
+            assert_eq!(args.base_address(), 0);
+        }
+        for flag in ["-no-pie", "--no-pie"] {
+            let args = parse_pie(&["-pie", flag]);
+            assert!(!args.pie);
+            assert!(!args.is_relocatable());
+            assert!(!args.needs_dynamic());
+            assert_eq!(args.base_address(), crate::elf::NON_PIE_START_MEM_ADDRESS);
+        }
+        assert!(parse_pie(&["--no-pie", "--pie"]).pie);
+
+        let args = parse_pie(&["-no-pie", "-dynamic-linker", "/lib64/ld-linux-x86-64.so.2"]);
+        assert_eq!(args.output_kind, OutputKind::DynamicExecutable);
+        assert!(!args.is_relocatable());
+        assert!(args.needs_dynamic());
+        assert_eq!(args.base_address(), crate::elf::NON_PIE_START_MEM_ADDRESS);
+    }
+
+    #[test]
+    fn test_merge() {
+        let parse_merge = |flags: &[&str]| {
+            let args = parse(flags).unwrap();
+            (args.merge_strings, args.merge_constants)
+        };
+        assert_eq!(parse_merge(&[]), (true, true));
+        assert_eq!(parse_merge(&["--no-string-merge"]), (false, true));
+        assert_eq!(parse_merge(&["--no-merge"]), (false, false));
+    }
+
+    #[test]
+    fn test_merge_rules() {
+        let args = parse(&["--no-merge=.rodata*", "--merge=.rodata.keep"]).unwrap();
+        assert!(!args.should_merge_section(b".rodata", true));
+        assert!(args.should_merge_section(b".rodata.keep", true));
+        assert!(args.should_merge_section(b"strings", true));
+
+        let args = parse(&["--no-string-merge", "--merge=strings"]).unwrap();
+        assert!(args.should_merge_section(b"strings", true));
+        assert!(!args.should_merge_section(b".rodata", true));
+        assert!(args.should_merge_section(b".rodata", false));
+
+        let args = parse(&["-r", "--merge=*"]).unwrap();
+        assert!(!args.should_merge_section(b".rodata", true));
+    }
+
+    #[test]
+    fn test_relocatable() {
+        for flag in ["-r", "--relocatable"] {
+            let args = parse(&[flag]).unwrap();
+            assert_eq!(args.output_kind, OutputKind::Relocatable);
+            assert!(!args.output_kind.is_executable());
+            assert!(!args.needs_dynamic());
+            assert!(args.emit_relocs);
+            assert!(!args.merge_strings);
+        }
+        assert!(parse(&["-r", "-shared"]).is_err());
+    }
+
+    #[test]
+    fn test_check_reproducible() {
+        assert!(parse(&["--check-reproducible"]).unwrap().check_reproducible);
+        for flags in [
+            &["--incremental"][..],
+            &["--object-cache", "cache"],
+            &["--repro=out.tar"],
+            &["-Map=out.map"],
+            &["--cref"],
+            &["--print-memory-usage"],
+        ] {
+            assert!(parse(flags).is_ok());
+            let flags = flags.iter().copied().chain(["--check-reproducible"]);
+            assert!(parse(&flags.collect::<Vec<_>>()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_plugin() {
+        let args = parse(&[]).unwrap();
+        assert_eq!(args.plugin, None);
+        assert!(args.plugin_opts.is_empty());
+        let args = parse(&[
+            "-plugin",
+            "/usr/lib/liblto_plugin.so",
+            "-plugin-opt=/usr/lib/lto-wrapper",
+            "--plugin-opt",
+            "-fresolution=/tmp/a.res",
+            "--plugin-opt=-pass-through=-lc",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.plugin.as_deref(),
+            Some(Path::new("/usr/lib/liblto_plugin.so"))
+        );
+        assert_eq!(
+            args.plugin_opts,
+            [
+                "/usr/lib/lto-wrapper",
+                "-fresolution=/tmp/a.res",
+                "-pass-through=-lc"
+            ]
+        );
+        let args = parse(&["--plugin=/usr/lib/LLVMgold.so"]).unwrap();
+        assert_eq!(
+            args.plugin.as_deref(),
+            Some(Path::new("/usr/lib/LLVMgold.so"))
+        );
+        assert!(parse(&["-plugin"]).is_err());
+    }
+
+    #[test]
+    fn test_max_page_size() {
+        let parse_page_size = |flags: &[&str]| parse(flags).map(|args| args.max_page_size.value());
+        assert_eq!(parse_page_size(&[]).unwrap(), 0x1000);
+        assert_eq!(
+            parse_page_size(&["-z", "max-page-size=0x10000"]).unwrap(),
+            0x10000
+        );
+        assert_eq!(
+            parse_page_size(&["-z", "max-page-size=16384"]).unwrap(),
+            0x4000
+        );
+        assert!(parse_page_size(&["-z", "max-page-size=0x3000"]).is_err());
+        assert!(parse_page_size(&["-z", "max-page-size=big"]).is_err());
+    }
+
+    #[test]
+    fn test_common_page_size() {
+        let parse_page_sizes = |flags: &[&str]| {
+            parse(flags).map(|args| (args.common_page_size.value(), args.max_page_size.value()))
+        };
+        assert_eq!(parse_page_sizes(&[]).unwrap(), (0x1000, 0x1000));
+        assert_eq!(
+            parse_page_sizes(&["-z", "max-page-size=0x10000"]).unwrap(),
+            (0x1000, 0x10000)
+        );
+        assert_eq!(
+            parse_page_sizes(&[
+                "-z",
+                "max-page-size=0x10000",
+                "-z",
+                "common-page-size=0x4000"
+            ])
+            .unwrap(),
+            (0x4000, 0x10000)
+        );
+        // The common page size can't be larger than the maximum.
+        assert_eq!(
+            parse_page_sizes(&["-z", "common-page-size=0x10000"]).unwrap(),
+            (0x1000, 0x1000)
+        );
+        assert!(parse_page_sizes(&["-z", "common-page-size=0x3000"]).is_err());
+    }
+
+    #[test]
+    fn test_stack() {
+        let parse_stack =
+            |flags: &[&str]| parse(flags).map(|args| (args.stack_size, args.execstack));
+        assert_eq!(parse_stack(&[]).unwrap(), (0, false));
+        assert_eq!(
+            parse_stack(&["-z", "stack-size=0x800000"]).unwrap(),
+            (0x800000, false)
+        );
+        assert_eq!(
+            parse_stack(&["-z", "stack-size=4096", "-z", "execstack"]).unwrap(),
+            (4096, true)
+        );
+        assert!(parse_stack(&["-z", "stack-size=lots"]).is_err());
+    }
+
+    #[test]
+    fn test_separate_code() {
+        let parse_separate_code = |flags: &[&str]| parse(flags).unwrap().separate_code;
+        assert!(!parse_separate_code(&[]));
+        assert!(parse_separate_code(&["-z", "separate-code"]));
+        assert!(!parse_separate_code(&[
+            "-z",
+            "separate-code",
+            "-z",
+            "noseparate-code"
+        ]));
+        assert!(!parse_separate_code(&[
+            "-z",
+            "separate-code",
+            "-z",
+            "noseparate-loadable-segments"
+        ]));
+    }
+
+    #[test]
+    fn test_rosegment() {
+        let parse_rosegment = |flags: &[&str]| parse(flags).unwrap().rosegment;
+        assert!(parse_rosegment(&[]));
+        assert!(!parse_rosegment(&["--no-rosegment"]));
+        assert!(parse_rosegment(&["--no-rosegment", "--rosegment"]));
+    }
+
+    #[test]
+    fn test_rpath() {
+        let parse_rpath = |flags: &[&str]| {
+            parse(flags).map(|args| (args.rpath.clone(), args.new_dtags, args.needs_origin()))
+        };
+        assert_eq!(parse_rpath(&[]).unwrap(), (None, true, false));
+        assert_eq!(
+            parse_rpath(&["-rpath", "/a", "-rpath=/b", "--disable-new-dtags"]).unwrap(),
+            (Some("/a:/b".to_owned()), false, false)
+        );
+        assert_eq!(
+            parse_rpath(&["-rpath", "$ORIGIN/../lib"]).unwrap(),
+            (Some("$ORIGIN/../lib".to_owned()), true, true)
+        );
+        assert_eq!(parse_rpath(&["-z", "origin"]).unwrap(), (None, true, true));
+        assert!(parse_rpath(&["-rpath="]).is_err());
+    }
+
+    #[test]
+    fn test_map_file() {
+        let parse_map = |flags: &[&str]| parse(flags).unwrap().map_file;
+        assert_eq!(parse_map(&[]), None);
+        assert_eq!(parse_map(&["-M"]), Some(MapOutput::Stdout));
+        assert_eq!(parse_map(&["--print-map"]), Some(MapOutput::Stdout));
+        assert_eq!(
+            parse_map(&["-Map=/tmp/a.map"]),
+            Some(MapOutput::File(PathBuf::from("/tmp/a.map")))
+        );
+        assert_eq!(
+            parse_map(&["-Map", "/tmp/b.map"]),
+            Some(MapOutput::File(PathBuf::from("/tmp/b.map")))
+        );
+        let parse_format = |flags: &[&str]| parse(flags).map(|args| args.map_format);
+        assert_eq!(parse_format(&[]).unwrap(), MapFormat::Text);
+        assert_eq!(
+            parse_format(&["--map-format=json"]).unwrap(),
+            MapFormat::Json
+        );
+        assert_eq!(
+            parse_format(&["--map-format=json", "--map-format", "text"]).unwrap(),
+            MapFormat::Text
+        );
+        assert!(parse_format(&["--map-format=yaml"]).is_err());
+    }
+
+    #[test]
+    fn test_error_handling_script() {
+        let parse_script = |flags: &[&str]| parse(flags).unwrap().error_handling_script;
+        assert_eq!(parse_script(&[]), None);
+        assert_eq!(
+            parse_script(&["--error-handling-script=/tmp/a.sh"]),
+            Some(PathBuf::from("/tmp/a.sh"))
+        );
+        assert_eq!(
+            parse_script(&["--error-handling-script", "/tmp/b.sh"]),
+            Some(PathBuf::from("/tmp/b.sh"))
+        );
+    }
+
+    #[test]
+    fn test_icf() {
+        let parse_icf = |flags: &[&str]| parse(flags).map(|args| args.icf);
+        assert_eq!(parse_icf(&[]).unwrap(), Icf::None);
+        assert_eq!(parse_icf(&["--icf=all"]).unwrap(), Icf::All);
+        assert_eq!(parse_icf(&["--icf", "safe"]).unwrap(), Icf::Safe);
+        assert_eq!(parse_icf(&["--icf=all", "--icf=none"]).unwrap(), Icf::None);
+        assert!(parse_icf(&["--icf=some"]).is_err());
+    }
+
+    #[test]
+    fn test_sort_section() {
+        let parse_sort = |flags: &[&str]| parse(flags).map(|args| args.sort_section);
+        assert_eq!(parse_sort(&[]).unwrap(), SortSection::None);
+        assert_eq!(
+            parse_sort(&["--sort-section=name"]).unwrap(),
+            SortSection::Name
+        );
+        assert_eq!(
+            parse_sort(&["--sort-section", "alignment"]).unwrap(),
+            SortSection::Alignment
+        );
+        assert!(parse_sort(&["--sort-section=size"]).is_err());
+    }
+
+    #[test]
+    fn test_sort_common() {
+        let parse_sort = |flags: &[&str]| parse(flags).map(|args| args.sort_common);
+        assert_eq!(parse_sort(&[]).unwrap(), SortCommon::Descending);
+        assert_eq!(
+            parse_sort(&["--sort-common=ascending"]).unwrap(),
+            SortCommon::Ascending
+        );
+        assert_eq!(
+            parse_sort(&["--sort-common=ascending", "--sort-common"]).unwrap(),
+            SortCommon::Descending
+        );
+        assert!(parse_sort(&["--sort-common=size"]).is_err());
+    }
+
+    #[test]
+    fn test_orphan_handling() {
+        let parse_orphan_handling = |flags: &[&str]| parse(flags).map(|args| args.orphan_handling);
+        assert_eq!(parse_orphan_handling(&[]).unwrap(), OrphanHandling::Place);
+        assert_eq!(
+            parse_orphan_handling(&["--orphan-handling=warn"]).unwrap(),
+            OrphanHandling::Warn
+        );
+        assert_eq!(
+            parse_orphan_handling(&["--orphan-handling", "discard"]).unwrap(),
+            OrphanHandling::Discard
+        );
+        assert!(parse_orphan_handling(&["--orphan-handling=ignore"]).is_err());
+    }
+
+    #[test]
+    fn test_unresolved_symbols() {
+        let args = parse(&[]).unwrap();
+        assert_eq!(
+            args.unresolved_symbols,
+            UnresolvedSymbols::IgnoreInSharedLibs
+        );
+        assert!(!args.warn_unresolved_symbols);
+        assert!(args.unresolved_symbols.report_in_object_files());
+        assert!(!args.unresolved_symbols.report_in_shared_libs());
+        let args = parse(&[
+            "--unresolved-symbols=report-all",
+            "--warn-unresolved-symbols",
+        ])
+        .unwrap();
+        assert_eq!(args.unresolved_symbols, UnresolvedSymbols::ReportAll);
+        assert!(args.warn_unresolved_symbols);
+        let args = parse(&[
+            "--unresolved-symbols",
+            "ignore-in-object-files",
+            "--warn-unresolved-symbols",
+            "--error-unresolved-symbols",
+        ])
+        .unwrap();
+        assert!(!args.unresolved_symbols.report_in_object_files());
+        assert!(args.unresolved_symbols.report_in_shared_libs());
+        assert!(!args.warn_unresolved_symbols);
+        assert!(parse(&["--unresolved-symbols=some"]).is_err());
+
+        assert_eq!(
+            parse(&["--no-allow-shlib-undefined"])
+                .unwrap()
+                .unresolved_symbols,
+            UnresolvedSymbols::ReportAll
+        );
+        assert_eq!(
+            parse(&[
+                "--unresolved-symbols=ignore-all",
+                "--no-allow-shlib-undefined"
+            ])
+            .unwrap()
+            .unresolved_symbols,
+            UnresolvedSymbols::IgnoreInObjectFiles
+        );
+        assert_eq!(
+            parse(&["--unresolved-symbols=report-all", "--allow-shlib-undefined"])
+                .unwrap()
+                .unresolved_symbols,
+            UnresolvedSymbols::IgnoreInSharedLibs
+        );
+        assert_eq!(
+            parse(&[
+                "--unresolved-symbols=ignore-in-object-files",
+                "--allow-shlib-undefined"
+            ])
+            .unwrap()
+            .unresolved_symbols,
+            UnresolvedSymbols::IgnoreAll
+        );
+    }
+
+    #[test]
+    fn test_gdb_index() {
+        let parse_gdb_index = |flags: &[&str]| parse(flags).unwrap().gdb_index;
+        assert!(!parse_gdb_index(&[]));
+        assert!(parse_gdb_index(&["--gdb-index"]));
+        assert!(!parse_gdb_index(&["--gdb-index", "--no-gdb-index"]));
+    }
+
+    #[test]
+    fn test_allow_multiple_definition() {
+        let parse_allow = |flags: &[&str]| parse(flags).unwrap().allow_multiple_definition;
+        assert!(!parse_allow(&[]));
+        assert!(parse_allow(&["--allow-multiple-definition"]));
+        assert!(parse_allow(&["-z", "muldefs"]));
+    }
+
+    #[test]
+    fn test_incremental() {
+        let parse_incremental = |flags: &[&str]| parse(flags).unwrap().incremental;
+        assert!(!parse_incremental(&[]));
+        assert!(parse_incremental(&["--incremental"]));
+        assert!(!parse_incremental(&["--incremental", "--no-incremental"]));
+    }
+
+    #[test]
+    fn test_spare_dynamic_tags() {
+        let parse_spare = |flags: &[&str]| parse(flags).map(|args| args.spare_dynamic_tags);
+        assert_eq!(parse_spare(&[]).unwrap(), super::DEFAULT_SPARE_DYNAMIC_TAGS);
+        assert_eq!(parse_spare(&["--spare-dynamic-tags=0"]).unwrap(), 0);
+        assert_eq!(parse_spare(&["--spare-dynamic-tags", "12"]).unwrap(), 12);
+        assert!(parse_spare(&["--spare-dynamic-tags=x"]).is_err());
+    }
+
+    #[test]
+    fn test_z_flags_1() {
+        let parse_flags_1 = |flags: &[&str]| parse(flags).unwrap().z_flags_1;
+        assert_eq!(parse_flags_1(&[]), 0);
+        assert_eq!(
+            parse_flags_1(&["-z", "nodelete", "-z", "initfirst"]),
+            crate::elf::flags_1::NODELETE | crate::elf::flags_1::INITFIRST
+        );
+        assert_eq!(
+            parse_flags_1(&["-z", "nodlopen"]),
+            crate::elf::flags_1::NOOPEN
+        );
+        assert_eq!(
+            parse_flags_1(&["-z", "global"]),
+            crate::elf::flags_1::GLOBAL
+        );
+    }
+
+    #[test]
+    fn test_text_relocations() {
+        let parse_allow_text_relocations =
+            |flags: &[&str]| parse(flags).unwrap().allow_text_relocations;
+        assert!(!parse_allow_text_relocations(&[]));
+        assert!(parse_allow_text_relocations(&["-z", "notext"]));
+        assert!(!parse_allow_text_relocations(&[
+            "-z", "notext", "-z", "text"
+        ]));
+    }
+
+    #[test]
+    fn test_pack_relative_relocs() {
+        let parse_pack_relative_relocs =
+            |flags: &[&str]| parse(flags).map(|args| args.pack_relative_relocs);
+        assert!(!parse_pack_relative_relocs(&[]).unwrap());
+        assert!(parse_pack_relative_relocs(&["-z", "pack-relative-relocs"]).unwrap());
+        assert!(!parse_pack_relative_relocs(&[
+            "-z",
+            "pack-relative-relocs",
+            "-z",
+            "nopack-relative-relocs"
+        ])
+        .unwrap());
+        assert!(parse_pack_relative_relocs(&["--pack-dyn-relocs=relr"]).unwrap());
+        assert!(!parse_pack_relative_relocs(&[
+            "-z",
+            "pack-relative-relocs",
+            "--pack-dyn-relocs=none"
+        ])
+        .unwrap());
+        assert!(parse_pack_relative_relocs(&["--pack-dyn-relocs=android"]).is_err());
+    }
+
+    #[test]
+    fn test_sysroot() {
+        let parse_search_path = |flags: &[&str]| parse(flags).unwrap().lib_search_path;
+        assert_eq!(
+            parse_search_path(&["-L=/usr/lib", "-L", "$SYSROOT/lib", "--sysroot=/sys"]),
+            [
+                Path::new("/sys/usr/lib").into(),
+                Path::new("/sys/lib").into()
+            ]
+        );
+        assert_eq!(
+            parse_search_path(&["--sysroot", "/sys", "-L/usr/lib"]),
+            [Path::new("/usr/lib").into()]
+        );
+        assert_eq!(
+            parse_search_path(&["-L=/usr/lib"]),
+            [Path::new("/usr/lib").into()]
+        );
+    }
+
+    #[test]
+    fn test_emulation() {
+        let parse_emulation = |flags: &[&str]| parse(flags).map(|args| args.emulation);
+        assert_eq!(parse_emulation(&[]).unwrap(), None);
+        assert_eq!(
+            parse_emulation(&["-m", "elf_x86_64"]).unwrap(),
+            Some(Emulation::X86_64)
+        );
+        assert_eq!(
+            parse_emulation(&["-m", "aarch64linux"]).unwrap(),
+            Some(Emulation::AArch64)
+        );
+        assert_eq!(
+            parse_emulation(&["-m", "elf64lriscv"]).unwrap(),
+            Some(Emulation::RiscV64)
+        );
+        let error = parse_emulation(&["-m", "elf_i386"]).err().unwrap();
+        assert_eq!(error.to_string(), "Unsupported emulation `elf_i386`");
+        assert!(parse_emulation(&["-m"]).is_err());
+    }
+
+    #[test]
+    fn test_unknown_flags() {
+        let parse_unknown = |flags: &[&str]| parse(flags).map(|args| args.ignored_unknown_flags);
+        let error = parse_unknown(&["--made-up-flag"]).err().unwrap();
+        assert_eq!(error.to_string(), "Unrecognised argument `--made-up-flag`");
+        assert!(parse_unknown(&["--made-up-flag", "--unknown-flags=error"]).is_err());
+        assert_eq!(
+            parse_unknown(&["--made-up-flag", "--unknown-flags=warn"]).unwrap(),
+            ["--made-up-flag"]
+        );
+        assert!(parse_unknown(&["--unknown-flags=ignore", "--made-up-flag"])
+            .unwrap()
+            .is_empty());
+        assert!(parse_unknown(&["--unknown-flags=sometimes"]).is_err());
+        // Known-harmless flags are accepted regardless of the policy.
+        assert!(parse_unknown(&["--fix-cortex-a53-843419"])
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            parse(&["--fix-cortex-a53-843419"])
+                .unwrap()
+                .cortex_a53_fixes,
+            ["--fix-cortex-a53-843419"]
+        );
+    }
+
+    #[test]
+    fn test_queries() {
+        let parse_query = |flags: &[&str]| Args::parse([&["wild"], flags].concat().iter());
+        // Queries don't need -o.
+        assert_eq!(
+            parse_query(&["--print-output-format"]).unwrap().query,
+            Some(Query::OutputFormat)
+        );
+        let args = parse_query(&["--print-sysroot", "--sysroot=/sysroot"]).unwrap();
+        assert_eq!(args.query, Some(Query::Sysroot));
+        assert_eq!(args.sysroot.as_deref(), Some(Path::new("/sysroot")));
+        assert_eq!(parse_query(&["-o", "/tmp/out"]).unwrap().query, None);
+        assert_eq!(
+            parse_query(&[]).err().unwrap().to_string(),
+            "Missing required argument -o"
+        );
+    }
+
+    #[test]
+    fn test_endianness() {
+        let parse_endianness = |flags: &[&str]| parse(flags).map(|args| args.endianness);
+        assert_eq!(parse_endianness(&[]).unwrap(), Endianness::Little);
+        assert_eq!(parse_endianness(&["-EL"]).unwrap(), Endianness::Little);
+        let error = parse_endianness(&["-EB"]).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "-EB isn't supported: wild only supports little-endian output"
+        );
+    }
+
+    #[test]
+    fn test_start_stop_visibility() {
+        let parse_visibility = |flags: &[&str]| parse(flags).map(|args| args.start_stop_visibility);
+        assert_eq!(
+            parse_visibility(&[]).unwrap(),
+            StartStopVisibility::Protected
+        );
+        assert_eq!(
+            parse_visibility(&["-z", "start-stop-visibility=hidden"]).unwrap(),
+            StartStopVisibility::Hidden
+        );
+        assert_eq!(
+            parse_visibility(&["-z", "start-stop-visibility=default"])
+                .unwrap()
+                .st_other(),
+            object::elf::STV_DEFAULT
+        );
+        assert!(parse_visibility(&["-z", "start-stop-visibility=bogus"]).is_err());
+    }
+
+    #[test]
+    fn test_hash_style() {
+        let parse_hash_style = |flags: &[&str]| parse(flags).map(|args| args.hash_style);
+        assert_eq!(parse_hash_style(&[]).unwrap(), HashStyle::Gnu);
+        assert_eq!(
+            parse_hash_style(&["--hash-style=sysv"]).unwrap(),
+            HashStyle::Sysv
+        );
+        assert_eq!(
+            parse_hash_style(&["--hash-style=both"]).unwrap(),
+            HashStyle::Both
+        );
+        assert!(parse_hash_style(&["--hash-style=mips"]).is_err());
+    }
+
+    #[test]
+    fn test_undefined() {
+        let parse_undefined = |flags: &[&str]| parse(flags).unwrap();
+        assert!(parse_undefined(&[]).undefined.is_empty());
+        let args = parse_undefined(&[
+            "-u",
+            "foo",
+            "-ubar",
+            "--undefined=baz",
+            "--undefined",
+            "qux",
+        ]);
+        assert_eq!(args.undefined, ["foo", "bar", "baz", "qux"]);
+        assert!(args.trace_symbols.is_empty());
+        let args = parse_undefined(&[
+            "-y",
+            "foo",
+            "-ybar",
+            "--trace-symbol=baz",
+            "--trace-symbol",
+            "qux",
+        ]);
+        assert_eq!(args.trace_symbols, ["foo", "bar", "baz", "qux"]);
+        assert!(args.undefined.is_empty());
+        assert!(!args.no_undefined);
+        assert!(parse_undefined(&["--no-undefined"]).no_undefined);
+        assert!(parse_undefined(&["-z", "defs"]).no_undefined);
+    }
+
+    #[test]
+    fn test_localize_symbols() {
+        let hidden = object::elf::STV_HIDDEN;
+        let default = object::elf::STV_DEFAULT;
+        let args = parse(&[]).unwrap();
+        assert!(!args.should_localize_symbol(b"foo", hidden));
+        let args = parse(&["--localize-hidden"]).unwrap();
+        assert!(args.should_localize_symbol(b"foo", hidden));
+        assert!(args.should_localize_symbol(b"foo", object::elf::STV_INTERNAL));
+        assert!(!args.should_localize_symbol(b"foo", default));
+        let args = parse(&["--localize-symbol=foo*", "--localize-symbol", "bar"]).unwrap();
+        assert!(args.should_localize_symbol(b"foobar", default));
+        assert!(args.should_localize_symbol(b"bar", default));
+        assert!(!args.should_localize_symbol(b"barfoo", default));
+        assert!(!args.should_localize_symbol(b"baz", hidden));
+    }
+
+    #[test]
+    fn test_keep_and_remove_sections() {
+        let args = parse(&[]).unwrap();
+        assert!(!args.should_keep_section(b".comment"));
+        assert!(!args.should_remove_section(b".comment"));
+        let args = parse(&[
+            "--keep-section=.my_meta*",
+            "--remove-section",
+            ".comment",
+            "--remove-section=.note.GNU-stack",
+        ])
+        .unwrap();
+        assert!(args.should_keep_section(b".my_meta"));
+        assert!(args.should_keep_section(b".my_meta.foo"));
+        assert!(!args.should_keep_section(b".comment"));
+        assert!(args.should_remove_section(b".comment"));
+        assert!(args.should_remove_section(b".note.GNU-stack"));
+        assert!(!args.should_remove_section(b".my_meta"));
+    }
+
+    #[test]
+    fn test_compress_sections() {
+        use super::CompressionFormat;
+
+        let args = parse(&[]).unwrap();
+        assert!(args.section_compression(b".asset").is_none());
+        let args = parse(&[
+            "--compress-sections=.debug_*=zlib",
+            "--compress-sections",
+            ".asset*=zstd:19",
+            "--compress-sections=.debug_str=none",
+        ])
+        .unwrap();
+        let rule = args.section_compression(b".debug_info").unwrap();
+        assert_eq!(rule.format, Some(CompressionFormat::Zlib));
+        assert_eq!(rule.level, None);
+        let rule = args.section_compression(b".asset.big").unwrap();
+        assert_eq!(rule.format, Some(CompressionFormat::Zstd));
+        assert_eq!(rule.level, Some(19));
+        assert!(args.section_compression(b".debug_str").is_none());
+        assert!(args.section_compression(b".comment").is_none());
+
+        assert!(parse(&["--compress-sections=.asset"]).is_err());
+        assert!(parse(&["--compress-sections=.asset=lz4"]).is_err());
+        assert!(parse(&["--compress-sections=.asset=zlib:10"]).is_err());
+        assert!(parse(&["--compress-sections=.asset=none:1"]).is_err());
+    }
+
+    #[test]
+    fn test_retain_symbols_file() {
+        let path = std::env::temp_dir().join("wild-test-retain-symbols");
+        std::fs::write(&path, "foo\n\n  bar  \n").unwrap();
+        let path = path.to_str().unwrap();
+        let parse_retain = |flags: &[&str]| parse(flags);
+        assert!(parse_retain(&[]).unwrap().should_retain_symbol(b"baz"));
+        let args = parse_retain(&["--retain-symbols-file", path]).unwrap();
+        assert!(args.should_retain_symbol(b"foo"));
+        assert!(args.should_retain_symbol(b"bar"));
+        assert!(!args.should_retain_symbol(b"baz"));
+        let flag = format!("--retain-symbols-file={path}");
+        assert_eq!(
+            parse_retain(&[&flag])
+                .unwrap()
+                .retain_symbols
+                .unwrap()
+                .len(),
+            2
+        );
+        let error = parse_retain(&["--retain-symbols-file=/does/not/exist"])
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("/does/not/exist"));
+    }
 }
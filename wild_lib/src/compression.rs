@@ -0,0 +1,294 @@
+//! Support for sections with `SHF_COMPRESSED` set. On the input side, these are things like the
+//! debug info produced by `gcc -gz`. We decompress such sections when we parse the object that
+//! contains them. Everything after that asks us for section contents, so it sees the sections as if
+//! they were stored uncompressed. On the output side, we compress the sections selected with
+//! `--compress-sections` once everything else has been written.
+
+use crate::alignment;
+use crate::alignment::Alignment;
+use crate::args::CompressSections;
+use crate::args::CompressionFormat;
+use crate::elf::File;
+use crate::elf::Section;
+use crate::elf::SectionHeader;
+use crate::error::Result;
+use crate::layout::Layout;
+use crate::output_section_id;
+use ahash::AHashMap;
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use object::LittleEndian;
+use object::Object as _;
+use object::ObjectSection as _;
+use object::SectionFlags;
+use rayon::prelude::*;
+use std::io::Write as _;
+
+/// The decompressed contents of the compressed sections of an object.
+pub(crate) struct DecompressedSections {
+    sections: AHashMap<object::SectionIndex, DecompressedSection>,
+}
+
+struct DecompressedSection {
+    data: Vec<u8>,
+    /// The alignment of the uncompressed data, which is stored in the compression header. The
+    /// section header's alignment is that of the compressed data.
+    alignment: u64,
+}
+
+impl Default for DecompressedSections {
+    fn default() -> Self {
+        Self {
+            sections: AHashMap::new(),
+        }
+    }
+}
+
+impl DecompressedSections {
+    pub(crate) fn new(object: &File) -> Result<Self> {
+        let mut sections = AHashMap::new();
+        for section in object.sections() {
+            let SectionFlags::Elf { sh_flags } = section.flags() else {
+                unreachable!();
+            };
+            if sh_flags & crate::elf::shf::COMPRESSED == 0 {
+                continue;
+            }
+            let decompressed = decompress(&section).with_context(|| {
+                format!(
+                    "Failed to decompress section `{}`",
+                    section.name().unwrap_or("??")
+                )
+            })?;
+            sections.insert(section.index(), decompressed);
+        }
+        Ok(Self { sections })
+    }
+
+    /// Returns the contents of `section`, decompressed if it was compressed.
+    pub(crate) fn data<'data>(&'data self, section: &Section<'data, '_>) -> Result<&'data [u8]> {
+        match self.sections.get(&section.index()) {
+            Some(decompressed) => Ok(&decompressed.data),
+            None => Ok(section.data()?),
+        }
+    }
+
+    /// Returns the size of `section` once decompressed.
+    pub(crate) fn size(&self, section: &Section) -> u64 {
+        self.sections
+            .get(&section.index())
+            .map_or(section.size(), |decompressed| {
+                decompressed.data.len() as u64
+            })
+    }
+
+    /// Returns the alignment of `section` once decompressed.
+    pub(crate) fn align(&self, section: &Section) -> u64 {
+        self.sections
+            .get(&section.index())
+            .map_or(section.align(), |decompressed| decompressed.alignment)
+    }
+}
+
+fn decompress(section: &Section) -> Result<DecompressedSection> {
+    let e = LittleEndian;
+    let data = section.data()?;
+    let (header, compressed) =
+        object::pod::from_bytes::<object::elf::CompressionHeader64<LittleEndian>>(data)
+            .map_err(|_| anyhow::anyhow!("Compressed section is too small for its header"))?;
+    let format = match header.ch_type.get(e) {
+        object::elf::ELFCOMPRESS_ZLIB => object::CompressionFormat::Zlib,
+        object::elf::ELFCOMPRESS_ZSTD => object::CompressionFormat::Zstandard,
+        other => bail!("Unsupported compression type {other}"),
+    };
+    let uncompressed_size = header.ch_size.get(e);
+    let data = object::CompressedData {
+        format,
+        data: compressed,
+        uncompressed_size,
+    }
+    .decompress()?
+    .into_owned();
+    if data.len() as u64 != uncompressed_size {
+        bail!(
+            "Decompressed to {} bytes, but header says {uncompressed_size}",
+            data.len()
+        );
+    }
+    Ok(DecompressedSection {
+        data,
+        alignment: header.ch_addralign.get(e).max(1),
+    })
+}
+
+/// An output section that is either compressed or needs to be moved because it comes after a
+/// compressed section.
+struct TailSection<'a> {
+    index: u32,
+    name: &'a [u8],
+    rule: Option<&'a CompressSections>,
+    file_offset: usize,
+    file_size: usize,
+    alignment: Alignment,
+}
+
+/// Compresses the output sections selected by `--compress-sections`. We can only do this once the
+/// output file has been written, since that's when we know the contents of the sections. Only
+/// sections that aren't loaded at runtime can be compressed, and these all come after the loaded
+/// sections in the file, so we can close up the space that we save by moving the sections that
+/// follow. Returns the new size of the file, if it changed.
+pub(crate) fn compress_output_sections(
+    layout: &Layout,
+    file_bytes: &mut [u8],
+) -> Result<Option<usize>> {
+    let args = layout.args();
+    if args.compress_sections.is_empty() {
+        return Ok(None);
+    }
+    let output_sections = &layout.output_sections;
+    let mut sections = Vec::new();
+    let mut error = None;
+    output_sections.sections_do(|id, details| {
+        let Some(index) = output_sections.output_index_of_section(id) else {
+            return;
+        };
+        let name = output_sections.name(id);
+        let rule = args.section_compression(name);
+        if rule.is_some() && details.section_flags & crate::elf::shf::ALLOC != 0 {
+            let kind = if details.section_flags & crate::elf::shf::EXECINSTR != 0 {
+                "executable code"
+            } else {
+                "data"
+            };
+            error.get_or_insert_with(|| {
+                anyhow!(
+                    "--compress-sections can't compress `{}`, since it contains {kind} that's \
+                     loaded at runtime",
+                    String::from_utf8_lossy(name)
+                )
+            });
+        }
+        let section_layout = layout.section_layouts.get(id);
+        sections.push(TailSection {
+            index,
+            name,
+            rule,
+            file_offset: section_layout.file_offset,
+            file_size: section_layout.file_size,
+            alignment: section_layout.alignment,
+        });
+    });
+    if let Some(error) = error {
+        return Err(error);
+    }
+    let Some(start) = sections
+        .iter()
+        .filter(|s| s.rule.is_some() && s.file_size > 0)
+        .map(|s| s.file_offset)
+        .min()
+    else {
+        return Ok(None);
+    };
+    sections.retain(|s| s.file_offset >= start && s.file_size > 0);
+    sections.sort_by_key(|s| s.file_offset);
+
+    let compressed = sections
+        .par_iter()
+        .map(|s| {
+            let Some(rule) = s.rule else {
+                return Ok(None);
+            };
+            let data = &file_bytes[s.file_offset..s.file_offset + s.file_size];
+            let compressed = compress(rule, data, s.alignment).with_context(|| {
+                format!(
+                    "Failed to compress section `{}`",
+                    String::from_utf8_lossy(s.name)
+                )
+            })?;
+            // If compression doesn't make the section smaller, even allowing for the padding that
+            // the compressed data might need, then we leave it as is. This also means that no
+            // section ever ends later than it did before, so we can move them in place.
+            let padding = alignment::COMPRESSION_HEADER.value() as usize - 1;
+            Ok((compressed.len() + padding < s.file_size).then_some(compressed))
+        })
+        .collect::<Result<Vec<Option<Vec<u8>>>>>()?;
+
+    let headers_offset = layout
+        .section_layouts
+        .get(output_section_id::SECTION_HEADERS)
+        .file_offset;
+    let mut offset = start;
+    for (section, compressed) in sections.iter().zip(compressed) {
+        let alignment = if compressed.is_some() {
+            alignment::COMPRESSION_HEADER
+        } else {
+            section.alignment
+        };
+        let aligned = alignment.align_up_usize(offset);
+        file_bytes[offset..aligned].fill(0);
+        offset = aligned;
+        let size = match &compressed {
+            Some(data) => {
+                file_bytes[offset..offset + data.len()].copy_from_slice(data);
+                data.len()
+            }
+            None => {
+                let old_range = section.file_offset..section.file_offset + section.file_size;
+                file_bytes.copy_within(old_range, offset);
+                section.file_size
+            }
+        };
+        let header_offset =
+            headers_offset + section.index as usize * core::mem::size_of::<SectionHeader>();
+        let header: &mut SectionHeader = object::from_bytes_mut(&mut file_bytes[header_offset..])
+            .map_err(|_| anyhow!("Section header for `{}` is out of range", section.index))?
+            .0;
+        let e = LittleEndian;
+        header.sh_offset.set(e, offset as u64);
+        if compressed.is_some() {
+            header.sh_size.set(e, size as u64);
+            header
+                .sh_flags
+                .set(e, header.sh_flags.get(e) | crate::elf::shf::COMPRESSED);
+            header.sh_addralign.set(e, alignment.value());
+        }
+        offset += size;
+    }
+    Ok(Some(offset))
+}
+
+/// Returns `data` compressed as specified by `rule`, preceded by a compression header.
+fn compress(rule: &CompressSections, data: &[u8], alignment: Alignment) -> Result<Vec<u8>> {
+    let e = LittleEndian;
+    let header = object::elf::CompressionHeader64::<LittleEndian> {
+        ch_type: object::U32::new(
+            e,
+            match rule.format {
+                Some(CompressionFormat::Zlib) => object::elf::ELFCOMPRESS_ZLIB,
+                Some(CompressionFormat::Zstd) => object::elf::ELFCOMPRESS_ZSTD,
+                None => bail!("Compression requested without an algorithm"),
+            },
+        ),
+        ch_reserved: object::U32::new(e, 0),
+        ch_size: object::U64::new(e, data.len() as u64),
+        ch_addralign: object::U64::new(e, alignment.value()),
+    };
+    let mut out = object::bytes_of(&header).to_vec();
+    match rule.format {
+        Some(CompressionFormat::Zlib) => {
+            let level = rule.level.map_or(flate2::Compression::default(), |level| {
+                flate2::Compression::new(level as u32)
+            });
+            let mut encoder = flate2::write::ZlibEncoder::new(out, level);
+            encoder.write_all(data)?;
+            out = encoder.finish()?;
+        }
+        Some(CompressionFormat::Zstd) => {
+            let level = rule.level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL);
+            zstd::stream::copy_encode(data, &mut out, level)?;
+        }
+        None => {}
+    }
+    Ok(out)
+}
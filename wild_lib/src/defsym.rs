@@ -0,0 +1,167 @@
+//! Expressions that give the values of symbols defined via `--defsym`. We support `+`, `-` and
+//! parentheses over integer literals and symbol names. Symbols can be defined anywhere in the link,
+//! so expressions that refer to them can only be evaluated once layout is complete.
+
+use crate::args::parse_number;
+use crate::error::Result;
+use anyhow::bail;
+use anyhow::Context as _;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum DefsymExpr {
+    Number(u64),
+    Symbol(String),
+    Add(Box<DefsymExpr>, Box<DefsymExpr>),
+    Sub(Box<DefsymExpr>, Box<DefsymExpr>),
+}
+
+impl DefsymExpr {
+    pub(crate) fn parse(s: &str) -> Result<Self> {
+        let mut parser = Parser { rest: s };
+        let expr = parser.expression()?;
+        parser.skip_whitespace();
+        if !parser.rest.is_empty() {
+            bail!("Unexpected `{}`", parser.rest);
+        }
+        Ok(expr)
+    }
+
+    /// Calls `cb` with the name of each symbol that the expression refers to.
+    pub(crate) fn for_each_symbol<'a>(&'a self, cb: &mut impl FnMut(&'a str)) {
+        match self {
+            DefsymExpr::Number(_) => {}
+            DefsymExpr::Symbol(name) => cb(name),
+            DefsymExpr::Add(a, b) | DefsymExpr::Sub(a, b) => {
+                a.for_each_symbol(cb);
+                b.for_each_symbol(cb);
+            }
+        }
+    }
+
+    /// Computes the value of the expression, calling `lookup` to get the value of each symbol.
+    pub(crate) fn evaluate(&self, lookup: &mut impl FnMut(&str) -> Result<u64>) -> Result<u64> {
+        Ok(match self {
+            DefsymExpr::Number(value) => *value,
+            DefsymExpr::Symbol(name) => lookup(name)?,
+            DefsymExpr::Add(a, b) => a
+                .evaluate(lookup)?
+                .checked_add(b.evaluate(lookup)?)
+                .with_context(|| format!("Overflow evaluating `{self}`"))?,
+            DefsymExpr::Sub(a, b) => a
+                .evaluate(lookup)?
+                .checked_sub(b.evaluate(lookup)?)
+                .with_context(|| format!("Overflow evaluating `{self}`"))?,
+        })
+    }
+}
+
+impl std::fmt::Display for DefsymExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DefsymExpr::Number(value) => write!(f, "0x{value:x}"),
+            DefsymExpr::Symbol(name) => write!(f, "{name}"),
+            DefsymExpr::Add(a, b) => write!(f, "({a} + {b})"),
+            DefsymExpr::Sub(a, b) => write!(f, "({a} - {b})"),
+        }
+    }
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn expression(&mut self) -> Result<DefsymExpr> {
+        let mut expr = self.operand()?;
+        loop {
+            self.skip_whitespace();
+            if let Some(rest) = self.rest.strip_prefix('+') {
+                self.rest = rest;
+                expr = DefsymExpr::Add(Box::new(expr), Box::new(self.operand()?));
+            } else if let Some(rest) = self.rest.strip_prefix('-') {
+                self.rest = rest;
+                expr = DefsymExpr::Sub(Box::new(expr), Box::new(self.operand()?));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn operand(&mut self) -> Result<DefsymExpr> {
+        self.skip_whitespace();
+        if let Some(rest) = self.rest.strip_prefix('(') {
+            self.rest = rest;
+            let expr = self.expression()?;
+            self.skip_whitespace();
+            self.rest = self.rest.strip_prefix(')').context("Missing `)`")?;
+            return Ok(expr);
+        }
+        let end = self
+            .rest
+            .find(|c: char| c.is_whitespace() || "+-()".contains(c))
+            .unwrap_or(self.rest.len());
+        let token = &self.rest[..end];
+        self.rest = &self.rest[end..];
+        if token.is_empty() {
+            bail!("Expected a number or symbol name");
+        }
+        if token.starts_with(|c: char| c.is_ascii_digit()) {
+            return Ok(DefsymExpr::Number(
+                parse_number(token).with_context(|| format!("Invalid number `{token}`"))?,
+            ));
+        }
+        Ok(DefsymExpr::Symbol(token.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DefsymExpr;
+
+    fn evaluate(s: &str) -> crate::error::Result<u64> {
+        DefsymExpr::parse(s)?.evaluate(&mut |name| match name {
+            "foo" => Ok(0x1000),
+            "bar" => Ok(0x20),
+            other => anyhow::bail!("Undefined symbol `{other}`"),
+        })
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(evaluate("0x30").unwrap(), 0x30);
+        assert_eq!(evaluate("foo").unwrap(), 0x1000);
+        assert_eq!(evaluate("foo+0x10").unwrap(), 0x1010);
+        assert_eq!(evaluate(" foo - bar + 1 ").unwrap(), 0xfe1);
+        assert_eq!(evaluate("foo-(bar+0x10)").unwrap(), 0xfd0);
+        assert_eq!(evaluate("((foo))").unwrap(), 0x1000);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(DefsymExpr::parse("").is_err());
+        assert!(DefsymExpr::parse("foo+").is_err());
+        assert!(DefsymExpr::parse("(foo").is_err());
+        assert!(DefsymExpr::parse("foo)").is_err());
+        assert!(DefsymExpr::parse("0xzz").is_err());
+        assert_eq!(
+            evaluate("baz+1").unwrap_err().to_string(),
+            "Undefined symbol `baz`"
+        );
+        assert_eq!(
+            evaluate("bar-foo").unwrap_err().to_string(),
+            "Overflow evaluating `(bar - foo)`"
+        );
+    }
+
+    #[test]
+    fn test_symbols() {
+        let expr = DefsymExpr::parse("foo-(bar+2)").unwrap();
+        let mut names = Vec::new();
+        expr.for_each_symbol(&mut |name| names.push(name));
+        assert_eq!(names, ["foo", "bar"]);
+    }
+}
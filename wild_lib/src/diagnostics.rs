@@ -0,0 +1,89 @@
+//! Collects warnings produced while linking so that they can be reported together once we're done
+//! and, with `--fatal-warnings`, cause the link to fail. Also collects errors that, with
+//! `--noinhibit-exec`, are reported after we've written the output file rather than stopping the
+//! link.
+
+use crate::args::Args;
+use crate::error::LinkError;
+use crate::error::Result;
+use anyhow::bail;
+use crossbeam_queue::SegQueue;
+use std::io::Write as _;
+
+#[derive(Default)]
+pub(crate) struct Diagnostics {
+    warnings: SegQueue<String>,
+    errors: SegQueue<anyhow::Error>,
+}
+
+impl Diagnostics {
+    pub(crate) fn warn(&self, message: String) {
+        self.warnings.push(message);
+    }
+
+    /// Returns `error` unless `--noinhibit-exec` was given, in which case it's recorded so that we
+    /// can report it once the output file has been written. Use this only for errors that we can
+    /// continue past, such as undefined symbols, not for things like malformed input.
+    pub(crate) fn error(&self, args: &Args, error: anyhow::Error) -> Result {
+        if !args.noinhibit_exec {
+            return Err(error);
+        }
+        self.errors.push(error);
+        Ok(())
+    }
+
+    /// Reports a reference to a symbol that isn't defined, as a warning if
+    /// `--warn-unresolved-symbols` was given, otherwise as an error.
+    pub(crate) fn unresolved_symbol(&self, args: &Args, error: LinkError) -> Result {
+        if args.warn_unresolved_symbols {
+            self.warn(error.to_string());
+            return Ok(());
+        }
+        self.error(args, error.into())
+    }
+
+    /// Prints any warnings to stderr. Returns an error if there were any and `--fatal-warnings` was
+    /// given.
+    pub(crate) fn report(&self, args: &Args) -> Result {
+        let mut messages = Vec::new();
+        while let Some(message) = self.warnings.pop() {
+            messages.push(message);
+        }
+        // As with errors, the same warning can come from many places, e.g. each reference to an
+        // unresolved symbol.
+        messages.sort();
+        messages.dedup();
+        let num_warnings = messages.len();
+        let mut stderr = std::io::stderr().lock();
+        for message in &messages {
+            writeln!(stderr, "wild: warning: {message}")?;
+        }
+        if args.fatal_warnings && num_warnings > 0 {
+            bail!("{num_warnings} warning(s) treated as errors because of --fatal-warnings");
+        }
+        Ok(())
+    }
+
+    /// Prints any errors recorded because of `--noinhibit-exec`, then fails if there were any.
+    pub(crate) fn report_errors(&self) -> Result {
+        let mut messages = Vec::new();
+        while let Some(error) = self.errors.pop() {
+            messages.push(format!("{error:#}"));
+        }
+        if messages.is_empty() {
+            return Ok(());
+        }
+        // The same error can be reported from many places, e.g. each reference to an undefined
+        // symbol, so only print each one once. Sorting also makes the output deterministic.
+        messages.sort();
+        messages.dedup();
+        let mut stderr = std::io::stderr().lock();
+        for message in &messages {
+            writeln!(stderr, "wild: error: {message}")?;
+        }
+        bail!(
+            "{} error(s), but wrote output anyway because of --noinhibit-exec",
+            messages.len()
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use crate::args::Emulation;
+use crate::error::LinkError;
 use crate::error::Result;
 use anyhow::bail;
 use bytemuck::Pod;
@@ -24,6 +26,11 @@ pub(crate) type SymtabEntry = object::elf::Sym64<LittleEndian>;
 pub(crate) type DynamicEntry = object::elf::Dyn64<LittleEndian>;
 pub(crate) type Rela = object::elf::Rela64<LittleEndian>;
 pub(crate) type GnuHashHeader = object::elf::GnuHashHeader<LittleEndian>;
+pub(crate) type Verdef = object::elf::Verdef<LittleEndian>;
+pub(crate) type Verdaux = object::elf::Verdaux<LittleEndian>;
+pub(crate) type Verneed = object::elf::Verneed<LittleEndian>;
+pub(crate) type Vernaux = object::elf::Vernaux<LittleEndian>;
+pub(crate) type VersionTable<'data> = object::read::elf::VersionTable<'data, FileHeader>;
 
 /// The module number for TLS variables in the current executable.
 pub(crate) const CURRENT_EXE_TLS_MOD: u64 = 1;
@@ -41,6 +48,7 @@ pub(crate) mod shf {
     pub(crate) const OS_NONCONFORMING: u64 = 0x100;
     pub(crate) const GROUP: u64 = 0x200;
     pub(crate) const TLS: u64 = 0x400;
+    pub(crate) const COMPRESSED: u64 = 0x800;
     pub(crate) const GNU_RETAIN: u64 = 0x200_000;
 }
 
@@ -77,7 +85,16 @@ pub(crate) enum Sht {
     PreinitArray = 0x10,
     Group = 0x11,
     SymtabShndx = 0x12,
-    Num = 0x13,
+    /// Relative relocations in the compact encoding used by `DT_RELR`.
+    Relr = 0x13,
+    /// A list of the symbols whose addresses are significant. Emitted by LLVM with `-faddrsig`.
+    LlvmAddrsig = 0x6fff4c03,
+    /// The versions that we define.
+    GnuVerdef = 0x6ffffffd,
+    /// The versions that we need from the shared objects that we depend on.
+    GnuVerneed = 0x6ffffffe,
+    /// The version index of each dynamic symbol.
+    GnuVersym = 0x6fffffff,
 }
 
 #[allow(unused)]
@@ -87,6 +104,15 @@ pub(crate) enum Binding {
     Local = 0,
     Global = 1,
     Weak = 2,
+    /// `STB_GNU_UNIQUE`. Used by GCC for things like static variables in inline functions, which
+    /// must have a single definition across the whole process, even if several shared objects
+    /// define them.
+    Unique = 10,
+}
+
+/// Returns whether `symbol` has `STB_GNU_UNIQUE` binding.
+pub(crate) fn is_unique_symbol(symbol: &Symbol) -> bool {
+    symbol.raw_symbol().st_bind() == Binding::Unique as u8
 }
 
 pub(crate) const RELA_ADDRESS_OFFSET: usize = 0;
@@ -106,6 +132,9 @@ pub(crate) enum SegmentType {
     Phdr = 6,
     Tls = 7,
     EhFrame = 0x6474e550,
+    GnuStack = 0x6474e551,
+    GnuRelro = 0x6474e552,
+    GnuProperty = 0x6474e553,
 }
 
 #[allow(unused)]
@@ -141,22 +170,41 @@ pub(crate) enum DynamicTag {
     FiniArray = 26,
     InitArraySize = 27,
     FiniArraySize = 28,
+    Runpath = 29,
     Flags = 30,
+    RelrSize = 35,
+    Relr = 36,
+    RelrEnt = 37,
     GnuHash = 0x6ffffef5,
     VerSym = 0x6ffffff0,
     Flags1 = 0x6ffffffb,
     RelaCount = 0x6ffffff9,
+    VerDef = 0x6ffffffc,
+    VerDefNum = 0x6ffffffd,
     VerNeed = 0x6ffffffe,
     VerNeedNum = 0x6fffffff,
 }
 
 pub(crate) mod flags_1 {
     pub(crate) const NOW: u64 = 0x1;
+    pub(crate) const GLOBAL: u64 = 0x2;
+    pub(crate) const NODELETE: u64 = 0x8;
+    pub(crate) const LOADFLTR: u64 = 0x10;
+    pub(crate) const INITFIRST: u64 = 0x20;
+    pub(crate) const NOOPEN: u64 = 0x40;
+    pub(crate) const ORIGIN: u64 = 0x80;
+    pub(crate) const INTERPOSE: u64 = 0x400;
+    pub(crate) const NODEFLIB: u64 = 0x800;
+    pub(crate) const NODUMP: u64 = 0x1000;
     pub(crate) const PIE: u64 = 0x08000000;
 }
 
 pub(crate) mod flags {
+    pub(crate) const ORIGIN: u64 = 0x1;
+    pub(crate) const SYMBOLIC: u64 = 0x2;
+    pub(crate) const TEXTREL: u64 = 0x4;
     pub(crate) const BIND_NOW: u64 = 0x8;
+    pub(crate) const STATIC_TLS: u64 = 0x10;
 }
 
 /// See https://refspecs.linuxfoundation.org/LSB_1.3.0/gLSB/gLSB/ehframehdr.html
@@ -167,8 +215,9 @@ pub(crate) struct EhFrameHdr {
     pub(crate) frame_pointer_encoding: u8,
     pub(crate) count_encoding: u8,
     pub(crate) table_encoding: u8,
-    // For now we just use 32 bit pointer and count because it means that they're aligned. If we
-    // need to upgrade these to u64, then we'd have to write these as unaligned fields.
+    // We use a 32 bit pointer and count, which means that they're aligned. Unwinders only binary
+    // search tables with 32 bit entries, so rather than switching to 64 bit fields, we report an
+    // error if anything is too far away. See `layout::check_eh_frame_hdr_range`.
     pub(crate) frame_pointer: i32,
     pub(crate) entry_count: u32,
 }
@@ -215,13 +264,6 @@ pub(crate) enum ExceptionHeaderApplication {
     EhFrameHdrRelative = 0x30,
 }
 
-#[allow(unused)]
-#[derive(Clone, Copy)]
-#[repr(u32)]
-pub(crate) enum RelocationType {
-    IRelative = 37,
-}
-
 /// The offset of the pc_begin field in an FDE.
 pub(crate) const FDE_PC_BEGIN_OFFSET: usize = 8;
 
@@ -235,8 +277,25 @@ pub(crate) const PROGRAM_HEADER_SIZE: u16 = 0x38;
 pub(crate) const SECTION_HEADER_SIZE: u16 = 0x40;
 
 pub(crate) const GOT_ENTRY_SIZE: u64 = 0x8;
+/// The size of a PLT entry. This happens to be the same for all the architectures we support.
 pub(crate) const PLT_ENTRY_SIZE: u64 = PLT_ENTRY_TEMPLATE.len() as u64;
 pub(crate) const RELA_ENTRY_SIZE: u64 = 0x18;
+pub(crate) const RELR_ENTRY_SIZE: u64 = 0x8;
+pub(crate) const VERSYM_ENTRY_SIZE: u64 = 0x2;
+
+/// The glibc version that a binary that uses DT_RELR needs to require.
+pub(crate) const GLIBC_ABI_DT_RELR: &[u8] = b"GLIBC_ABI_DT_RELR";
+
+/// The index of the first version that a version script defines. Indexes 0 and 1 are reserved for
+/// local and global symbols, with the base version that we define for a shared object also using 1.
+pub(crate) const FIRST_DEFINED_VERSION_INDEX: u16 = 2;
+
+/// The size of a `.gnu.version_d` entry that defines a single version with no parents.
+pub(crate) const VERDEF_WITH_AUX_SIZE: u64 =
+    (core::mem::size_of::<Verdef>() + core::mem::size_of::<Verdaux>()) as u64;
+
+pub(crate) const VERNEED_SIZE: u64 = core::mem::size_of::<Verneed>() as u64;
+pub(crate) const VERNAUX_SIZE: u64 = core::mem::size_of::<Vernaux>() as u64;
 
 pub(crate) const SYMTAB_ENTRY_SIZE: u64 = core::mem::size_of::<SymtabEntry>() as u64;
 
@@ -256,6 +315,8 @@ const _ASSERTS: () = {
     assert!(FILE_HEADER_SIZE as usize == std::mem::size_of::<FileHeader>());
     assert!(PROGRAM_HEADER_SIZE as usize == std::mem::size_of::<ProgramHeader>());
     assert!(SECTION_HEADER_SIZE as usize == std::mem::size_of::<SectionHeader>());
+    assert!(PLT_ENTRY_SIZE as usize == crate::aarch64::PLT_ENTRY_TEMPLATE.len());
+    assert!(PLT_ENTRY_SIZE as usize == crate::riscv64::PLT_ENTRY_TEMPLATE.len());
 };
 
 #[derive(Clone, Copy, Debug)]
@@ -276,14 +337,56 @@ pub(crate) enum RelocationKind {
     None,
 }
 
+impl RelocationKind {
+    /// Returns whether the relocation refers to TLS via an offset from the thread pointer. This
+    /// requires that the TLS block be allocated when the program starts.
+    pub(crate) fn uses_static_tls(self) -> bool {
+        matches!(self, RelocationKind::GotTpOff | RelocationKind::TpOff)
+    }
+
+    /// Returns whether the value that we compute for the relocation is relative to the address of
+    /// the place being relocated.
+    pub(crate) fn is_pc_relative(self) -> bool {
+        matches!(
+            self,
+            RelocationKind::Relative
+                | RelocationKind::PltRelative
+                | RelocationKind::GotRelative
+                | RelocationKind::TlsGd
+                | RelocationKind::TlsLd
+                | RelocationKind::GotTpOff
+        )
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub(crate) struct RelocationKindInfo {
     pub(crate) kind: RelocationKind,
-    pub(crate) byte_size: usize,
+    pub(crate) size: RelocationSize,
+}
+
+/// How the value of a relocation is stored at the place being relocated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RelocationSize {
+    /// The value is truncated to this many bytes and stored little-endian.
+    ByteSize(usize),
+    /// The value is stored in some of the bits of a 4-byte AArch64 instruction.
+    AArch64(crate::aarch64::Instruction),
+    /// The value is stored in some of the bits of one or two RISC-V instructions, or combined with
+    /// the data already at the place.
+    RiscV(crate::riscv64::Encoding),
 }
 
 impl RelocationKindInfo {
-    pub(crate) fn from_raw(r_type: u32) -> Result<Self> {
+    pub(crate) fn from_raw(emulation: Emulation, r_type: u32) -> Result<Self> {
+        match emulation {
+            Emulation::X86_64 => Self::from_raw_x86_64(r_type),
+            Emulation::AArch64 => crate::aarch64::relocation_kind_info(r_type),
+            Emulation::RiscV64 => crate::riscv64::relocation_kind_info(r_type),
+        }
+    }
+
+    fn from_raw_x86_64(r_type: u32) -> Result<Self> {
         let (kind, size) = match r_type {
             object::elf::R_X86_64_64 => (RelocationKind::Absolute, 8),
             object::elf::R_X86_64_PC32 => (RelocationKind::Relative, 4),
@@ -304,11 +407,11 @@ impl RelocationKindInfo {
             }
             object::elf::R_X86_64_TPOFF32 => (RelocationKind::TpOff, 4),
             object::elf::R_X86_64_NONE => (RelocationKind::None, 0),
-            _ => bail!("Unsupported relocation type {r_type}"),
+            _ => return Err(LinkError::UnsupportedRelocation { r_type }.into()),
         };
         Ok(Self {
             kind,
-            byte_size: size,
+            size: RelocationSize::ByteSize(size),
         })
     }
 }
@@ -318,3 +421,60 @@ pub(crate) fn slice_from_all_bytes_mut<T: object::Pod>(data: &mut [u8]) -> &mut
         .unwrap()
         .0
 }
+
+/// Returns whether the shared object `object` defines the symbol version `name`.
+pub(crate) fn defines_version(object: &File, name: &[u8]) -> Result<bool> {
+    use object::read::elf::FileHeader as _;
+    use object::read::elf::SectionHeader as _;
+    let e = LittleEndian;
+    let headers = object.raw_header().section_headers(e, object.data())?;
+    for header in headers {
+        let Some((mut verdefs, link)) = header.gnu_verdef(e, object.data())? else {
+            continue;
+        };
+        let Some(strings) = headers
+            .get(link.0)
+            .map(|h| h.strings(e, object.data()))
+            .transpose()?
+            .flatten()
+        else {
+            bail!(
+                "Invalid string table index {} for version definitions",
+                link.0
+            );
+        };
+        while let Some((_, mut verdauxs)) = verdefs.next()? {
+            // The first auxiliary entry names the version. Any others name its parents.
+            if let Some(verdaux) = verdauxs.next()? {
+                if verdaux.name(e, strings)? == name {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Returns the table that gives the version of each of the dynamic symbols of the shared object
+/// `object`, or None if it doesn't version its symbols.
+pub(crate) fn version_table<'data>(object: &File<'data>) -> Result<Option<VersionTable<'data>>> {
+    use object::read::elf::FileHeader as _;
+    let e = LittleEndian;
+    Ok(object
+        .raw_header()
+        .sections(e, object.data())?
+        .versions(e, object.data())?)
+}
+
+/// Returns the `sh_entsize` of the section with the specified index. The `object` crate doesn't
+/// expose this via its section API, so we look it up in the raw section headers.
+pub(crate) fn section_entsize(object: &File, index: object::SectionIndex) -> Result<u64> {
+    use object::read::elf::FileHeader as _;
+    let headers = object
+        .raw_header()
+        .section_headers(LittleEndian, object.data())?;
+    let Some(header) = headers.get(index.0) else {
+        bail!("Invalid section index {}", index.0);
+    };
+    Ok(header.sh_entsize.get(LittleEndian))
+}
@@ -1,4 +1,6 @@
 use crate::args::Args;
+use crate::args::BSymbolic;
+use crate::args::Emulation;
 use crate::args::OutputKind;
 use crate::elf;
 use crate::elf::slice_from_all_bytes_mut;
@@ -11,18 +13,26 @@ use crate::elf::GnuHashHeader;
 use crate::elf::ProgramHeader;
 use crate::elf::RelocationKind;
 use crate::elf::RelocationKindInfo;
+use crate::elf::RelocationSize;
 use crate::elf::SectionHeader;
 use crate::elf::SegmentType;
 use crate::elf::SymtabEntry;
 use crate::elf::PLT_ENTRY_TEMPLATE;
+use crate::error::LinkError;
 use crate::error::Result;
+use crate::error_handling_script;
+use crate::error_handling_script::ErrorKind;
+use crate::incremental::Incremental;
+use crate::layout::CieLayout;
 use crate::layout::DynamicLayout;
+use crate::layout::DynamicRelocationCounts;
 use crate::layout::EpilogueLayout;
 use crate::layout::FileLayout;
 use crate::layout::HeaderInfo;
 use crate::layout::InternalLayout;
 use crate::layout::InternalSymbols;
 use crate::layout::Layout;
+use crate::layout::NeededVersion;
 use crate::layout::ObjectLayout;
 use crate::layout::Resolution;
 use crate::layout::ResolutionValue;
@@ -33,6 +43,7 @@ use crate::output_section_id::OutputSectionId;
 use crate::output_section_id::OutputSections;
 use crate::output_section_map::OutputSectionMap;
 use crate::output_section_part_map::OutputSectionPartMap;
+use crate::parsing::InternalSymDefInfo;
 use crate::relaxation::Relaxation;
 use crate::relaxation::RelocationModifier;
 use crate::resolution::SectionSlot;
@@ -52,6 +63,7 @@ use object::LittleEndian;
 use object::Object;
 use object::ObjectSection;
 use object::ObjectSymbol;
+use object::ObjectSymbolTable as _;
 use rayon::prelude::*;
 use std::fmt::Display;
 use std::ops::Range;
@@ -63,6 +75,8 @@ use std::sync::Arc;
 pub struct Output {
     path: Arc<Path>,
     creator: FileCreator,
+    /// Set when linking with `--incremental`.
+    incremental: Option<Incremental>,
 }
 
 enum FileCreator {
@@ -89,8 +103,10 @@ struct SectionAllocation {
 }
 
 impl Output {
-    pub fn new(args: &Args) -> Output {
-        if args.num_threads.get() > 1 {
+    pub fn new(args: &Args, incremental: Option<Incremental>) -> Output {
+        // When linking incrementally, we don't know whether we'll update the existing output or
+        // replace it until we've finished layout, so we can't create the file early.
+        if args.num_threads.get() > 1 && incremental.is_none() {
             let (sized_output_sender, sized_output_recv) = std::sync::mpsc::channel();
             Output {
                 path: args.output.clone(),
@@ -98,15 +114,21 @@ impl Output {
                     sized_output_sender: Some(sized_output_sender),
                     sized_output_recv,
                 },
+                incremental,
             }
         } else {
             Output {
                 path: args.output.clone(),
                 creator: FileCreator::Regular { file_size: None },
+                incremental,
             }
         }
     }
 
+    pub(crate) fn incremental(&self) -> Option<&Incremental> {
+        self.incremental.as_ref()
+    }
+
     pub(crate) fn set_size(&mut self, size: u64) {
         match &mut self.creator {
             FileCreator::Background {
@@ -127,6 +149,9 @@ impl Output {
 
     #[tracing::instrument(skip_all, name = "Write output file")]
     pub fn write(&mut self, layout: &Layout) -> Result {
+        if let Some(incremental) = self.incremental.take() {
+            return incremental.write(layout, self);
+        }
         let mut sized_output = match &self.creator {
             FileCreator::Background {
                 sized_output_sender,
@@ -143,6 +168,36 @@ impl Output {
         sized_output.write(layout)
     }
 
+    /// Updates the output of a previous link that had exactly the same layout, writing only the
+    /// files for which `rewrite`, which is indexed by file ID, is set. We update a copy of the
+    /// existing output, then rename it over the original, so that anything that's running the
+    /// original or has it mapped, e.g. a process using it as a shared object, is unaffected.
+    /// Returns false without changing anything if the existing output can't be copied, isn't the
+    /// size that we expect or doesn't contain what we expect.
+    #[tracing::instrument(skip_all, name = "Update output file")]
+    pub(crate) fn patch(&mut self, layout: &Layout, rewrite: &[bool]) -> Result<bool> {
+        let FileCreator::Regular { file_size } = &self.creator else {
+            bail!("Internal error: incremental output was created lazily");
+        };
+        let file_size = file_size.context("set_size was never called")?;
+        let temp_path: Arc<Path> = self
+            .path
+            .with_extension(format!("tmp-{}", std::process::id()))
+            .into();
+        let Some(mut sized_output) = SizedOutput::copy_existing(&self.path, &temp_path, file_size)
+        else {
+            return Ok(false);
+        };
+        let patched = sized_output.patch(layout, rewrite);
+        if !matches!(patched, Ok(true)) {
+            let _ = std::fs::remove_file(&temp_path);
+            return patched;
+        }
+        std::fs::rename(&temp_path, &self.path)
+            .with_context(|| format!("Failed to write `{}`", self.path.display()))?;
+        Ok(true)
+    }
+
     #[tracing::instrument(skip_all, name = "Create output file")]
     fn create_file_non_lazily(&mut self, file_size: u64) -> Result<SizedOutput> {
         SizedOutput::new(self.path.clone(), file_size)
@@ -170,19 +225,160 @@ impl SizedOutput {
         Ok(SizedOutput { file, mmap, path })
     }
 
+    /// Copies the existing output file at `existing` to `path` and opens the copy, provided that
+    /// the existing file has the expected size. Returns None if we can't, e.g. because it isn't
+    /// readable, in which case the caller should write a new file instead.
+    fn copy_existing(existing: &Path, path: &Arc<Path>, file_size: u64) -> Option<SizedOutput> {
+        if std::fs::metadata(existing).ok()?.len() != file_size {
+            return None;
+        }
+        let _ = std::fs::remove_file(path);
+        let copy = || -> Result<SizedOutput> {
+            std::fs::copy(existing, path)?;
+            let file = std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(path)?;
+            let mmap = unsafe { MmapOptions::new().map_mut(&file) }?;
+            Ok(SizedOutput {
+                file,
+                mmap,
+                path: path.clone(),
+            })
+        };
+        let sized_output = copy();
+        if sized_output.is_err() {
+            let _ = std::fs::remove_file(path);
+        }
+        sized_output.ok()
+    }
+
     pub(crate) fn write(&mut self, layout: &Layout) -> Result {
         self.write_file_contents(layout)?;
+        // A relocatable output has no GOT, segments or .eh_frame_hdr and isn't something that can
+        // be run, so all that's left to do is to compress any sections that were requested.
+        if layout.args().output_kind == OutputKind::Relocatable {
+            return self.compress_sections(layout);
+        }
         if layout.args().validate_output {
             crate::validation::validate_bytes(layout, &self.mmap)?;
         }
 
         let mut section_buffers = split_output_into_sections(layout, &mut self.mmap);
         sort_eh_frame_hdr_entries(section_buffers.get_mut(output_section_id::EH_FRAME_HDR));
+        encode_relr(section_buffers.get_mut(output_section_id::RELR_DYN));
+        self.compress_sections(layout)?;
         crate::fs::make_executable(&self.file)
             .with_context(|| format!("Failed to make `{}` executable", self.path.display()))?;
         Ok(())
     }
 
+    /// Compresses sections as requested by `--compress-sections`, then truncates the file to drop
+    /// the space that was saved.
+    fn compress_sections(&mut self, layout: &Layout) -> Result {
+        if let Some(file_size) =
+            crate::compression::compress_output_sections(layout, &mut self.mmap)?
+        {
+            self.file
+                .set_len(file_size as u64)
+                .with_context(|| format!("Failed to truncate `{}`", self.path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the files selected by `rewrite` within an output that already contains everything
+    /// else. The only data that's shared between files is the sorted table in .eh_frame_hdr, so we
+    /// take the entries for files that we're not rewriting from the existing table, then combine
+    /// them with the entries from the files that we are. Returns false without writing anything if
+    /// the existing table doesn't have the entries that we expect.
+    fn patch(&mut self, layout: &Layout, rewrite: &[bool]) -> Result<bool> {
+        let rewritten_objects = layout
+            .file_layouts
+            .iter()
+            .zip(rewrite)
+            .filter_map(|(file, rewrite)| match file {
+                FileLayout::Object(object) if *rewrite => Some(object),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let rewritten_fde_ranges = rewritten_objects
+            .iter()
+            .map(|object| {
+                object.eh_frame_start_address
+                    ..object.eh_frame_start_address + object.mem_sizes.eh_frame
+            })
+            .collect::<Vec<_>>();
+        let mut entries = {
+            let mut section_buffers = split_output_into_sections(layout, &mut self.mmap);
+            let table =
+                eh_frame_hdr_entries(section_buffers.get_mut(output_section_id::EH_FRAME_HDR));
+            let hdr_address = layout
+                .section_layouts
+                .get(output_section_id::EH_FRAME_HDR)
+                .mem_offset;
+            let kept = table
+                .iter()
+                .filter(|entry| {
+                    let fde_address = hdr_address.wrapping_add(entry.frame_info_ptr as i64 as u64);
+                    !rewritten_fde_ranges
+                        .iter()
+                        .any(|range| range.contains(&fde_address))
+                })
+                .copied()
+                .collect::<Vec<_>>();
+            let num_new = rewritten_objects
+                .iter()
+                .map(|object| object.mem_sizes.eh_frame_hdr as usize)
+                .sum::<usize>()
+                / core::mem::size_of::<EhFrameHdrEntry>();
+            if kept.len() + num_new != table.len() {
+                return Ok(false);
+            }
+            kept
+        };
+
+        let mmap_start = self.mmap.as_ptr() as usize;
+        let mut section_buffers = split_output_into_sections(layout, &mut self.mmap);
+        let mut writable_buckets = split_buffers_by_alignment(&mut section_buffers, layout);
+        let files_and_buffers = split_output_by_file(layout, &mut writable_buckets);
+        let rewrite_loaded = layout
+            .file_layouts
+            .iter()
+            .zip(rewrite)
+            .filter(|(file, _)| file.file_sizes().is_some())
+            .map(|(_, rewrite)| *rewrite)
+            .collect::<Vec<bool>>();
+        // The offsets in the output of the parts of .eh_frame_hdr that we wrote.
+        let new_entry_ranges = files_and_buffers
+            .into_par_iter()
+            .zip(rewrite_loaded)
+            .filter(|(_, rewrite)| *rewrite)
+            .map(|((file, mut buffers), _)| {
+                buffers.fill_zero();
+                let entries = buffers.eh_frame_hdr.as_ptr() as usize - mmap_start
+                    ..buffers.eh_frame_hdr.as_ptr() as usize - mmap_start
+                        + buffers.eh_frame_hdr.len();
+                file.write(buffers, layout)
+                    .with_context(|| format!("Failed copying from {file} to output file"))?;
+                Ok(matches!(file, FileLayout::Object(_)).then_some(entries))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for range in new_entry_ranges.into_iter().flatten() {
+            entries.extend_from_slice(bytemuck::cast_slice::<_, EhFrameHdrEntry>(
+                &self.mmap[range],
+            ));
+        }
+
+        if layout.args().validate_output {
+            crate::validation::validate_bytes(layout, &self.mmap)?;
+        }
+        let mut section_buffers = split_output_into_sections(layout, &mut self.mmap);
+        let table = eh_frame_hdr_entries(section_buffers.get_mut(output_section_id::EH_FRAME_HDR));
+        table.copy_from_slice(&entries);
+        table.sort_by_key(|e| e.frame_ptr);
+        Ok(true)
+    }
+
     #[tracing::instrument(skip_all, name = "Write data to file")]
     pub(crate) fn write_file_contents(&mut self, layout: &Layout) -> Result {
         let mut section_buffers = split_output_into_sections(layout, &mut self.mmap);
@@ -253,9 +449,52 @@ fn split_output_into_sections<'out>(
 
 #[tracing::instrument(skip_all, name = "Sort .eh_frame_hdr")]
 fn sort_eh_frame_hdr_entries(eh_frame_hdr: &mut [u8]) {
-    let entry_bytes = &mut eh_frame_hdr[core::mem::size_of::<elf::EhFrameHdr>()..];
-    let entries: &mut [elf::EhFrameHdrEntry] = bytemuck::cast_slice_mut(entry_bytes);
-    entries.sort_by_key(|e| e.frame_ptr);
+    eh_frame_hdr_entries(eh_frame_hdr).sort_by_key(|e| e.frame_ptr);
+}
+
+/// Returns the table of entries that follows the header in .eh_frame_hdr. Empty if we don't have
+/// an .eh_frame_hdr.
+fn eh_frame_hdr_entries(eh_frame_hdr: &mut [u8]) -> &mut [EhFrameHdrEntry] {
+    let header_size = core::mem::size_of::<elf::EhFrameHdr>().min(eh_frame_hdr.len());
+    bytemuck::cast_slice_mut(&mut eh_frame_hdr[header_size..])
+}
+
+/// Each file writes the places that need relative relocations into .relr.dyn as plain addresses,
+/// which is a valid, but not very compact RELR encoding. Here we sort the addresses and rewrite them
+/// using bitmaps. An address entry is followed by bitmaps, each of which covers the next 63 words.
+/// Since the encoded form is never longer than the input, we pad any space that's left over with
+/// empty bitmaps, which the loader ignores.
+fn encode_relr(relr_dyn: &mut [u8]) {
+    const BITS_PER_BITMAP: u64 = u64::BITS as u64 - 1;
+    let entries: &mut [u64] = bytemuck::cast_slice_mut(relr_dyn);
+    let mut addresses = entries.to_vec();
+    addresses.sort_unstable();
+    addresses.dedup();
+    let mut out = 0;
+    let mut addresses = addresses.into_iter().peekable();
+    while let Some(address) = addresses.next() {
+        entries[out] = address;
+        out += 1;
+        let mut base = address + elf::RELR_ENTRY_SIZE;
+        loop {
+            let mut bitmap = 0;
+            while let Some(&next) = addresses.peek() {
+                let delta = (next - base) / elf::RELR_ENTRY_SIZE;
+                if delta >= BITS_PER_BITMAP {
+                    break;
+                }
+                bitmap |= 1 << delta;
+                addresses.next();
+            }
+            if bitmap == 0 {
+                break;
+            }
+            entries[out] = (bitmap << 1) | 1;
+            out += 1;
+            base += BITS_PER_BITMAP * elf::RELR_ENTRY_SIZE;
+        }
+    }
+    entries[out..].fill(1);
 }
 
 /// Splits the writable buffers for each segment further into separate buffers for each alignment.
@@ -277,13 +516,21 @@ fn write_program_headers(program_headers_out: &mut ProgramHeaderWriter, layout:
         let segment_header = program_headers_out.take_header()?;
         let mut alignment = segment_sizes.alignment;
         if segment_id.segment_type() == SegmentType::Load {
-            alignment = alignment.max(crate::alignment::PAGE);
+            alignment = alignment.max(layout.args().max_page_size);
         }
         let e = LittleEndian;
         segment_header
             .p_type
             .set(e, segment_id.segment_type() as u32);
-        segment_header.p_flags.set(e, segment_id.segment_flags());
+        let mut flags = segment_id.segment_flags();
+        let mut mem_size = segment_sizes.mem_size;
+        if segment_id == crate::program_segments::GNU_STACK {
+            if layout.args().execstack {
+                flags |= object::elf::PF_X;
+            }
+            mem_size = layout.args().stack_size;
+        }
+        segment_header.p_flags.set(e, flags);
         segment_header
             .p_offset
             .set(e, segment_sizes.file_offset as u64);
@@ -292,7 +539,7 @@ fn write_program_headers(program_headers_out: &mut ProgramHeaderWriter, layout:
         segment_header
             .p_filesz
             .set(e, segment_sizes.file_size as u64);
-        segment_header.p_memsz.set(e, segment_sizes.mem_size);
+        segment_header.p_memsz.set(e, mem_size);
         segment_header.p_align.set(e, alignment.value());
     }
     Ok(())
@@ -305,46 +552,70 @@ fn populate_file_header(
 ) -> Result {
     let args = layout.args();
     let ty = match args.output_kind {
-        OutputKind::StaticExecutable if !args.pie => elf::FileType::Executable,
+        OutputKind::StaticExecutable | OutputKind::DynamicExecutable if !args.pie => {
+            elf::FileType::Executable
+        }
+        OutputKind::Relocatable => elf::FileType::Relocatable,
         _ => elf::FileType::SharedObject,
     };
     let e = LittleEndian;
     header.e_ident.magic = object::elf::ELFMAG;
     header.e_ident.class = 2; // 64 bit
-    header.e_ident.data = 1; // Little endian
+    header.e_ident.data = args.endianness.elf_data();
     header.e_ident.version = 1;
     header.e_ident.os_abi = 0;
     header.e_ident.abi_version = 0;
     header.e_ident.padding = Default::default();
     header.e_type.set(e, ty as u16);
-    header.e_machine.set(e, 0x3e); // x86-64
+    header.e_machine.set(e, layout.emulation().elf_machine());
     header.e_version.set(e, 1);
     header.e_entry.set(e, layout.entry_symbol_address()?);
-    header.e_phoff.set(e, elf::PHEADER_OFFSET);
+    // A file without program headers, such as a relocatable object, has an e_phoff of 0.
+    let phoff = if header_info.active_segment_ids.is_empty() {
+        0
+    } else {
+        elf::PHEADER_OFFSET
+    };
+    header.e_phoff.set(e, phoff);
     header.e_shoff.set(
         e,
         u64::from(elf::FILE_HEADER_SIZE) + header_info.program_headers_size(),
     );
-    header.e_flags.set(e, 0);
+    header.e_flags.set(e, layout.symbol_db.e_flags);
     header.e_ehsize.set(e, elf::FILE_HEADER_SIZE);
     header.e_phentsize.set(e, elf::PROGRAM_HEADER_SIZE);
     header
         .e_phnum
         .set(e, header_info.active_segment_ids.len() as u16);
     header.e_shentsize.set(e, elf::SECTION_HEADER_SIZE);
-    header
-        .e_shnum
-        .set(e, header_info.num_output_sections_with_content);
+    // If either of these doesn't fit, we write 0 or SHN_XINDEX here and the real value goes in the
+    // first section header. See `write_section_headers`.
+    header.e_shnum.set(
+        e,
+        small_section_index(header_info.num_output_sections_with_content).unwrap_or(0),
+    );
     header.e_shstrndx.set(
         e,
-        layout
-            .output_sections
-            .output_index_of_section(crate::output_section_id::SHSTRTAB)
-            .expect("we always write .shstrtab"),
+        small_section_index(shstrtab_index(layout)).unwrap_or(object::elf::SHN_XINDEX),
     );
     Ok(())
 }
 
+/// Returns `index` if it's small enough to be stored directly in a 16 bit section index field,
+/// i.e. if it's below the range of reserved indexes.
+fn small_section_index(index: u32) -> Option<u16> {
+    u16::try_from(index)
+        .ok()
+        .filter(|index| *index < object::elf::SHN_LORESERVE)
+}
+
+fn shstrtab_index(layout: &Layout) -> u32 {
+    layout
+        .output_sections
+        .output_index_of_section(crate::output_section_id::SHSTRTAB)
+        .expect("we always write .shstrtab")
+}
+
 impl<'data> FileLayout<'data> {
     fn write(&self, buffers: OutputSectionPartMap<&mut [u8]>, layout: &Layout) -> Result {
         match self {
@@ -364,6 +635,8 @@ struct PltGotWriter<'data, 'out> {
     plt: &'out mut [u8],
     rela_plt: &'out mut [elf::Rela],
     tls: Range<u64>,
+    thread_pointer: u64,
+    dtp: u64,
 }
 
 impl<'data, 'out> PltGotWriter<'data, 'out> {
@@ -377,6 +650,8 @@ impl<'data, 'out> PltGotWriter<'data, 'out> {
             plt: core::mem::take(&mut buffers.plt),
             rela_plt: slice_from_all_bytes_mut(core::mem::take(&mut buffers.rela_plt)),
             tls: layout.tls_start_address()..layout.tls_end_address(),
+            thread_pointer: layout.thread_pointer_address(),
+            dtp: layout.dtp_address(),
         }
     }
 
@@ -402,19 +677,18 @@ impl<'data, 'out> PltGotWriter<'data, 'out> {
                     let mod_got_entry = slice_take_prefix_mut(&mut self.got, 1);
                     mod_got_entry.copy_from_slice(&[elf::CURRENT_EXE_TLS_MOD]);
                     let offset_entry = slice_take_prefix_mut(&mut self.got, 1);
-                    // Convert the address to an offset relative to the TCB which is the end of the TLS
-                    // segment.
+                    // Convert the address to an offset relative to the start of the module's TLS
+                    // block, which is what `__tls_get_addr` expects.
                     match res.value {
                         ResolutionValue::Address(address) => {
-                            offset_entry[0] = address.wrapping_sub(self.tls.end);
+                            offset_entry[0] = address.wrapping_sub(self.dtp);
                         }
                         other => bail!("Unexpected resolution value {other:?}"),
                     }
                     return Ok(());
                 }
                 TargetResolutionKind::GotTlsOffset => {
-                    // Convert the address to an offset relative to the TCB which is the end of the TLS
-                    // segment.
+                    // Convert the address to an offset relative to the thread pointer.
                     match res.value {
                         ResolutionValue::Address(address) => {
                             if !self.tls.contains(&address) {
@@ -423,7 +697,7 @@ impl<'data, 'out> PltGotWriter<'data, 'out> {
                                     address
                                 );
                             }
-                            ResolutionValue::Absolute(address.wrapping_sub(self.tls.end))
+                            ResolutionValue::Absolute(address.wrapping_sub(self.thread_pointer))
                         }
                         other => bail!("Unexpected resolution value {other:?}"),
                     }
@@ -451,6 +725,17 @@ impl<'data, 'out> PltGotWriter<'data, 'out> {
             bail!("Didn't allocate enough space in PLT");
         }
         let plt_entry = slice_take_prefix_mut(&mut self.plt, elf::PLT_ENTRY_SIZE as usize);
+        match self.layout.emulation() {
+            Emulation::X86_64 => {}
+            Emulation::AArch64 => {
+                plt_entry.copy_from_slice(crate::aarch64::PLT_ENTRY_TEMPLATE);
+                return crate::aarch64::write_plt_entry(plt_entry, got_address, plt_address);
+            }
+            Emulation::RiscV64 => {
+                plt_entry.copy_from_slice(crate::riscv64::PLT_ENTRY_TEMPLATE);
+                return crate::riscv64::write_plt_entry(plt_entry, got_address, plt_address);
+            }
+        }
         plt_entry.copy_from_slice(PLT_ENTRY_TEMPLATE);
         let offset: i32 = ((got_address.wrapping_sub(plt_address + 0xb)) as i64)
             .try_into()
@@ -483,7 +768,7 @@ impl<'data, 'out> PltGotWriter<'data, 'out> {
         let out = slice_take_prefix_mut(&mut self.rela_plt, 1);
         let out = &mut out[0];
         let e = LittleEndian;
-        if relocation_writer.is_active {
+        if relocation_writer.is_relocatable {
             relocation_writer.write_relocation(
                 rel.relocation_address + elf::RELA_ADDEND_OFFSET as u64,
                 ResolutionValue::Address(rel.resolver),
@@ -494,20 +779,37 @@ impl<'data, 'out> PltGotWriter<'data, 'out> {
                 ResolutionValue::Address(rel.got_address),
                 0,
             )?;
-        } else {
+        }
+        if !relocation_writer.is_relocatable
+            || relocation_writer.place_needs_value(ResolutionValue::Address(rel.resolver))
+        {
             out.r_addend.set(e, rel.resolver as i64);
             out.r_offset.set(e, rel.got_address);
         }
         out.r_info
-            .set(e, elf::RelocationType::IRelative as u32 as u64);
+            .set(e, self.layout.emulation().irelative_relocation().into());
         Ok(())
     }
 }
 
+/// The section of a symbol that we're writing.
+#[derive(Clone, Copy)]
+enum OutputShndx {
+    /// The index of an output section. Indexes that are too large for `st_shndx` get stored in
+    /// .symtab_shndx.
+    Index(u32),
+    /// A reserved value for `st_shndx`, e.g. `SHN_ABS`.
+    Special(u16),
+}
+
 struct SymbolTableWriter<'data, 'out> {
     string_offset: u32,
     local_entries: &'out mut [SymtabEntry],
     global_entries: &'out mut [SymtabEntry],
+    /// The .symtab_shndx entries corresponding to `local_entries` and `global_entries`. None if we
+    /// aren't writing .symtab_shndx.
+    local_shndx_entries: Option<&'out mut [object::U32<LittleEndian>]>,
+    global_shndx_entries: Option<&'out mut [object::U32<LittleEndian>]>,
     strings: &'out mut [u8],
     output_sections: &'data OutputSections<'data>,
 }
@@ -525,10 +827,24 @@ impl<'data, 'out> SymbolTableWriter<'data, 'out> {
             &mut buffers.symtab_strings,
             sizes.symtab_strings as usize,
         ));
+        let (local_shndx_entries, global_shndx_entries) = if output_sections.needs_symtab_shndx() {
+            (
+                Some(slice_from_all_bytes_mut(core::mem::take(
+                    &mut buffers.symtab_shndx_locals,
+                ))),
+                Some(slice_from_all_bytes_mut(core::mem::take(
+                    &mut buffers.symtab_shndx_globals,
+                ))),
+            )
+        } else {
+            (None, None)
+        };
         Self {
             string_offset: start_string_offset,
             local_entries,
             global_entries,
+            local_shndx_entries,
+            global_shndx_entries,
             strings,
             output_sections,
         }
@@ -545,23 +861,32 @@ impl<'data, 'out> SymbolTableWriter<'data, 'out> {
             string_offset,
             local_entries: Default::default(),
             global_entries,
+            local_shndx_entries: None,
+            global_shndx_entries: None,
             strings,
             output_sections,
         }
     }
 
+    /// Copies `sym` into the symbol table. If `localize` is set, then a global symbol is written as
+    /// a local symbol.
     fn copy_symbol(
         &mut self,
         sym: &crate::elf::Symbol,
         output_section_id: OutputSectionId,
         section_address: u64,
+        localize: bool,
     ) -> Result {
         let name = sym.name_bytes()?;
         if !crate::layout::should_copy_symbol(name) {
             return Ok(());
         }
-        let is_local = sym.is_local();
-        let object::SymbolFlags::Elf { st_info, st_other } = sym.flags() else {
+        let is_local = sym.is_local() || localize;
+        let object::SymbolFlags::Elf {
+            mut st_info,
+            st_other,
+        } = sym.flags()
+        else {
             unreachable!()
         };
         let shndx = self
@@ -575,9 +900,40 @@ impl<'data, 'out> SymbolTableWriter<'data, 'out> {
                     output_section_id,
                 )
             })?;
-        let value = section_address + sym.address();
+        // Common symbols store their alignment in the value field, so for those, the address we're
+        // given is already the address of the symbol.
+        let value = if sym.is_common() {
+            section_address
+        } else {
+            section_address + sym.address()
+        };
         let size = sym.size();
-        let entry = self.define_symbol(is_local, shndx, value, size, name)?;
+        if localize {
+            st_info = (object::elf::STB_LOCAL << 4) | (st_info & elf::SYMBOL_TYPE_MASK);
+        }
+        let entry = self.define_symbol(is_local, OutputShndx::Index(shndx), value, size, name)?;
+        entry.st_info = st_info;
+        entry.st_other = st_other;
+        Ok(())
+    }
+
+    /// Defines `sym`, a data object from a shared object, at `address`, where we've put our copy of
+    /// it in .bss.
+    fn define_copied_object(&mut self, sym: &crate::elf::Symbol, address: u64) -> Result {
+        let shndx = self
+            .output_sections
+            .output_index_of_section(output_section_id::BSS)
+            .context("Internal error: copied object, but .bss isn't being output")?;
+        let object::SymbolFlags::Elf { st_info, st_other } = sym.flags() else {
+            unreachable!()
+        };
+        let entry = self.define_symbol(
+            false,
+            OutputShndx::Index(shndx),
+            address,
+            sym.size(),
+            sym.name_bytes()?,
+        )?;
         entry.st_info = st_info;
         entry.st_other = st_other;
         Ok(())
@@ -586,11 +942,71 @@ impl<'data, 'out> SymbolTableWriter<'data, 'out> {
     fn define_symbol(
         &mut self,
         is_local: bool,
-        shndx: u16,
+        shndx: OutputShndx,
         value: u64,
         size: u64,
         name: &[u8],
     ) -> Result<&mut SymtabEntry> {
+        let string_offset = self.string_offset;
+        let len = name.len();
+        let str_out = slice_take_prefix_mut(&mut self.strings, len + 1);
+        str_out[..len].copy_from_slice(name);
+        str_out[len] = 0;
+        self.string_offset += len as u32 + 1;
+        let entry = self.take_entry(is_local, shndx, value, size, name)?;
+        entry.st_name.set(LittleEndian, string_offset);
+        Ok(entry)
+    }
+
+    /// Defines a local symbol for the start of the output section with the specified index. Section
+    /// symbols don't have names, so they don't use any space in the string table.
+    fn define_section_symbol(&mut self, shndx: u32) -> Result {
+        let entry = self.take_entry(true, OutputShndx::Index(shndx), 0, 0, &[])?;
+        entry.st_info = object::elf::STT_SECTION;
+        Ok(())
+    }
+
+    /// Takes the next local or global symbol table entry and fills in everything but its name.
+    /// `name` is only used for error messages.
+    fn take_entry(
+        &mut self,
+        is_local: bool,
+        shndx: OutputShndx,
+        value: u64,
+        size: u64,
+        name: &[u8],
+    ) -> Result<&mut SymtabEntry> {
+        let (st_shndx, extended_shndx) = match shndx {
+            OutputShndx::Index(index) => match small_section_index(index) {
+                Some(index) => (index, 0),
+                None => (object::elf::SHN_XINDEX, index),
+            },
+            OutputShndx::Special(value) => (value, 0),
+        };
+        let shndx_entries = if is_local {
+            &mut self.local_shndx_entries
+        } else {
+            &mut self.global_shndx_entries
+        };
+        match shndx_entries {
+            Some(shndx_entries) => {
+                take_first_mut(shndx_entries)
+                    .with_context(|| {
+                        format!(
+                            "Insufficient .symtab_shndx entries allocated for symbol `{}`",
+                            String::from_utf8_lossy(name),
+                        )
+                    })?
+                    .set(LittleEndian, extended_shndx);
+            }
+            None if st_shndx == object::elf::SHN_XINDEX => {
+                bail!(
+                    "Can't write symbol `{}` in section {extended_shndx} without .symtab_shndx",
+                    String::from_utf8_lossy(name),
+                );
+            }
+            None => {}
+        }
         let entry = if is_local {
             take_first_mut(&mut self.local_entries).with_context(|| {
                 format!(
@@ -607,17 +1023,12 @@ impl<'data, 'out> SymbolTableWriter<'data, 'out> {
             })?
         };
         let e = LittleEndian;
-        entry.st_name.set(e, self.string_offset);
+        entry.st_name.set(e, 0);
         entry.st_info = 0;
         entry.st_other = 0;
-        entry.st_shndx.set(e, shndx);
+        entry.st_shndx.set(e, st_shndx);
         entry.st_value.set(e, value);
         entry.st_size.set(e, size);
-        let len = name.len();
-        let str_out = slice_take_prefix_mut(&mut self.strings, len + 1);
-        str_out[..len].copy_from_slice(name);
-        str_out[len] = 0;
-        self.string_offset += len as u32 + 1;
         Ok(entry)
     }
 
@@ -627,6 +1038,14 @@ impl<'data, 'out> SymbolTableWriter<'data, 'out> {
         if !self.local_entries.is_empty()
             || !self.global_entries.is_empty()
             || !self.strings.is_empty()
+            || self
+                .local_shndx_entries
+                .as_ref()
+                .is_some_and(|e| !e.is_empty())
+            || self
+                .global_shndx_entries
+                .as_ref()
+                .is_some_and(|e| !e.is_empty())
         {
             bail!(
                 "Didn't use up all allocated symtab/strtab space. local={} global={} strings={}",
@@ -643,16 +1062,17 @@ impl<'data> ObjectLayout<'data> {
     fn write(&self, mut buffers: OutputSectionPartMap<&mut [u8]>, layout: &Layout) -> Result {
         let start_str_offset = self.strtab_offset_start;
         let mut plt_got_writer = PltGotWriter::new(layout, &mut buffers);
-        let mut relocation_writer =
-            DynamicRelocationWriter::new(layout.args().is_relocatable(), &mut buffers);
-        for sec in &self.sections {
-            match sec {
+        let mut relocation_writer = DynamicRelocationWriter::new(layout, &mut buffers);
+        let mut deferred_relocations = Vec::new();
+        for index in &self.section_order {
+            match &self.sections[index.0] {
                 SectionSlot::Loaded(sec) => self.write_section(
                     layout,
                     sec,
                     &mut buffers,
                     &mut plt_got_writer,
                     &mut relocation_writer,
+                    &mut deferred_relocations,
                 )?,
                 SectionSlot::EhFrameData(section_index) => {
                     self.write_eh_frame_data(
@@ -665,6 +1085,19 @@ impl<'data> ObjectLayout<'data> {
                 _ => (),
             }
         }
+        deferred_relocations.into_par_iter().try_for_each(
+            |(sec, out, mut section_relocation_writer)| {
+                self.apply_section_relocations(out, sec, layout, &mut section_relocation_writer)?;
+                section_relocation_writer
+                    .validate_empty(&self.mem_sizes)
+                    .with_context(|| {
+                        format!(
+                            "Incorrect dynamic relocation count for section {}",
+                            self.display_section_name(sec.index)
+                        )
+                    })
+            },
+        )?;
         for rel in &self.plt_relocations {
             plt_got_writer.write_ifunc_relocation(rel, &mut relocation_writer)?;
         }
@@ -687,13 +1120,22 @@ impl<'data> ObjectLayout<'data> {
         Ok(())
     }
 
-    fn write_section(
+    /// Copies the data for `sec` into the output and adds it to `deferred_relocations`, so that the
+    /// relocations of all our sections can be applied in parallel. Any dynamic relocations that the
+    /// section needs go in entries that we take from `relocation_writer` here, so that they're
+    /// written in a deterministic order.
+    fn write_section<'sec, 'out>(
         &self,
         layout: &Layout<'_>,
-        sec: &Section<'_>,
-        buffers: &mut OutputSectionPartMap<&mut [u8]>,
+        sec: &'sec Section<'_>,
+        buffers: &mut OutputSectionPartMap<&'out mut [u8]>,
         plt_got_writer: &mut PltGotWriter<'_, '_>,
-        relocation_writer: &mut DynamicRelocationWriter,
+        relocation_writer: &mut DynamicRelocationWriter<'out>,
+        deferred_relocations: &mut Vec<(
+            &'sec Section<'sec>,
+            &'out mut [u8],
+            DynamicRelocationWriter<'out>,
+        )>,
     ) -> Result {
         if layout
             .output_sections
@@ -712,10 +1154,39 @@ impl<'data> ObjectLayout<'data> {
             // Cut off any padding so that our output buffer is the size of our input buffer.
             let out = &mut out[..sec.data.len()];
             out.copy_from_slice(sec.data);
-            self.apply_relocations(out, sec, layout, relocation_writer)
+            // Sections that aren't loaded at runtime, e.g. debug info, never get dynamic relocations.
+            let is_alloc = layout
+                .output_sections
+                .details(sec.output_section_id.unwrap())
+                .section_flags
+                & elf::shf::ALLOC
+                != 0;
+            if layout.args().output_kind == OutputKind::Relocatable {
+                // Relocations are left for the final link, which will get them from the relocation
+                // sections that we write.
+            } else if relocation_writer.is_active && is_alloc {
+                let section_relocation_writer = relocation_writer
+                    .split_for_section(sec.dynamic_relocations)
+                    .with_context(|| {
+                        format!(
+                            "Failed to allocate dynamic relocations for section {}",
+                            self.display_section_name(sec.index)
+                        )
+                    })?;
+                deferred_relocations.push((sec, out, section_relocation_writer));
+            } else {
+                deferred_relocations.push((sec, out, DynamicRelocationWriter::inactive()));
+            }
+        }
+        if let Some(relocation_section_id) = layout
+            .output_sections
+            .relocation_section_id(sec.output_section_id.unwrap())
+        {
+            let out = buffers.regular_mut(relocation_section_id, crate::alignment::RELA_ENTRY);
+            self.write_emitted_relocations(layout, sec, out)
                 .with_context(|| {
                     format!(
-                        "Failed to apply relocations in section {} of {}",
+                        "Failed to copy relocations for section {} of {}",
                         self.display_section_name(sec.index),
                         self.input
                     )
@@ -740,17 +1211,26 @@ impl<'data> ObjectLayout<'data> {
         let mut symbol_writer =
             SymbolTableWriter::new(start_str_offset, &mut buffers, &self.mem_sizes, sections);
         for sym in self.object.symbols() {
+            let name = sym.name_bytes()?;
+            if !layout.args().should_retain_symbol(name) {
+                continue;
+            }
+            let localize = layout
+                .args()
+                .should_localize_symbol(name, sym.raw_symbol().st_visibility());
             match object::ObjectSymbol::section(&sym) {
                 object::SymbolSection::Section(section_index) => {
-                    if let SectionSlot::Loaded(section) = &self.sections[section_index.0] {
-                        let output_section_id = section.output_section_id.unwrap();
+                    if let Some(output_section_id) =
+                        self.sections[section_index.0].output_section_id()
+                    {
                         let section_address = self.section_resolutions[section_index.0]
                             .as_ref()
                             .unwrap()
                             .value
-                            .address_or_value()?;
+                            .address_or_value()?
+                            - layout.symbol_value_base(output_section_id);
                         symbol_writer
-                            .copy_symbol(&sym, output_section_id, section_address)
+                            .copy_symbol(&sym, output_section_id, section_address, localize)
                             .with_context(|| {
                                 format!(
                                     "Failed to copy {}",
@@ -766,7 +1246,13 @@ impl<'data> ObjectLayout<'data> {
                     if layout.symbol_db.is_definition(symbol_id) {
                         if let Some(res) = layout.symbol_resolution(symbol_id) {
                             symbol_writer
-                                .copy_symbol(&sym, output_section_id::BSS, res.value.address()?)
+                                .copy_symbol(
+                                    &sym,
+                                    output_section_id::BSS,
+                                    res.value.address()?
+                                        - layout.symbol_value_base(output_section_id::BSS),
+                                    localize,
+                                )
                                 .with_context(|| {
                                     format!(
                                         "Failed to copy common {}",
@@ -783,6 +1269,121 @@ impl<'data> ObjectLayout<'data> {
         Ok(())
     }
 
+    /// Copies the relocations for `sec` into the output for `--emit-relocs`, adjusted to refer to
+    /// output addresses and symbols. Relocation types are left unchanged, even where we relaxed
+    /// the instructions that they apply to.
+    fn write_emitted_relocations(
+        &self,
+        layout: &Layout,
+        sec: &Section,
+        out: &mut &mut [u8],
+    ) -> Result {
+        let section_address = self.section_resolutions[sec.index.0]
+            .as_ref()
+            .unwrap()
+            .value
+            .address_or_value()?
+            - layout.symbol_value_base(sec.output_section_id.unwrap());
+        let e = LittleEndian;
+        for (offset_in_section, rel) in self.object.section_by_index(sec.index)?.relocations() {
+            if out.len() < elf::RELA_ENTRY_SIZE as usize {
+                bail!("Insufficient space allocated for relocations");
+            }
+            let rela: &mut elf::Rela = &mut slice_from_all_bytes_mut(slice_take_prefix_mut(
+                out,
+                elf::RELA_ENTRY_SIZE as usize,
+            ))[0];
+            let object::RelocationFlags::Elf { r_type } = rel.flags() else {
+                unreachable!();
+            };
+            let (symbol_index, addend) = self.emitted_relocation_target(&rel, layout)?;
+            rela.r_offset.set(e, section_address + offset_in_section);
+            rela.r_info
+                .set(e, u64::from(symbol_index) << 32 | u64::from(r_type));
+            rela.r_addend.set(e, addend);
+        }
+        Ok(())
+    }
+
+    /// Returns the output symbol index and addend to use when copying `rel` into the output. If the
+    /// target symbol isn't in our output symbol table, e.g. because it's a section symbol, then we
+    /// fold its address into the addend and use the null symbol instead.
+    fn emitted_relocation_target(
+        &self,
+        rel: &object::Relocation,
+        layout: &Layout,
+    ) -> Result<(u32, i64)> {
+        if let object::RelocationTarget::Symbol(symbol_index) = rel.target() {
+            let symbol_id = layout
+                .symbol_db
+                .definition(self.start_symbol_id.add_usize(symbol_index.0));
+            if let Some(index) = layout.output_symtab_index(symbol_id) {
+                return Ok((index, rel.addend()));
+            }
+            if layout.args().output_kind == OutputKind::Relocatable {
+                if let Some(target) = self.relocatable_output_target(symbol_index, rel, layout)? {
+                    return Ok(target);
+                }
+            }
+        }
+        let value = match self.get_resolution(rel, layout)?.map(|res| res.value) {
+            Some(ResolutionValue::Address(value) | ResolutionValue::Absolute(value)) => value,
+            _ => 0,
+        };
+        Ok((0, (value as i64).wrapping_add(rel.addend())))
+    }
+
+    /// Returns the symbol index and addend to use for `rel` in a relocatable output when the symbol
+    /// that it refers to isn't in our output symbol table. References to undefined symbols use the
+    /// undefined symbol with the same name that we wrote, while references to places in our own
+    /// sections, e.g. via section symbols, become references to the section symbol of the output
+    /// section.
+    fn relocatable_output_target(
+        &self,
+        symbol_index: object::SymbolIndex,
+        rel: &object::Relocation,
+        layout: &Layout,
+    ) -> Result<Option<(u32, i64)>> {
+        let sym = self.object.symbol_by_index(symbol_index)?;
+        if sym.is_undefined() {
+            return Ok(layout
+                .undefined_symtab_index(sym.name_bytes()?)
+                .map(|index| (index, rel.addend())));
+        }
+        let object::SymbolSection::Section(section_index) = sym.section() else {
+            return Ok(None);
+        };
+        let Some(output_section_id) = self.sections[section_index.0].output_section_id() else {
+            return Ok(None);
+        };
+        let (Some(index), Some(res)) = (
+            layout.section_symbol_index(output_section_id),
+            self.section_resolutions[section_index.0].as_ref(),
+        ) else {
+            return Ok(None);
+        };
+        let offset = res.value.address_or_value()? + sym.address()
+            - layout.vma_of_section(output_section_id);
+        Ok(Some((index, (offset as i64).wrapping_add(rel.addend()))))
+    }
+
+    fn apply_section_relocations(
+        &self,
+        out: &mut [u8],
+        section: &Section,
+        layout: &Layout,
+        relocation_writer: &mut DynamicRelocationWriter,
+    ) -> Result {
+        self.apply_relocations(out, section, layout, relocation_writer)
+            .with_context(|| {
+                format!(
+                    "Failed to apply relocations in section {} of {}",
+                    self.display_section_name(section.index),
+                    self.input
+                )
+            })
+    }
+
     fn apply_relocations(
         &self,
         out: &mut [u8],
@@ -796,6 +1397,7 @@ impl<'data> ObjectLayout<'data> {
             .value
             .address_or_value()?;
         let elf_section = &self.object.section_by_index(section.index)?;
+        let mut target = RelocationTarget::new(section_address);
         let mut modifier = RelocationModifier::Normal;
         for (offset_in_section, rel) in elf_section.relocations() {
             if modifier == RelocationModifier::SkipNextRelocation {
@@ -806,7 +1408,7 @@ impl<'data> ObjectLayout<'data> {
                 self,
                 offset_in_section,
                 &rel,
-                section_address,
+                &mut target,
                 layout,
                 out,
                 relocation_writer,
@@ -836,29 +1438,45 @@ impl<'data> ObjectLayout<'data> {
         let data = eh_frame_section.data()?;
         const PREFIX_LEN: usize = core::mem::size_of::<elf::EhFrameEntryPrefix>();
         let mut relocations = eh_frame_section.relocations().peekable();
-        let mut input_pos = 0;
-        let mut output_pos = 0;
         let frame_info_ptr_base = self.eh_frame_start_address;
         let eh_frame_hdr_address = layout.mem_address_of_built_in(output_section_id::EH_FRAME_HDR);
 
-        // Map from input offset to output offset of each CIE.
-        let mut cies_offset_conversion: AHashMap<u32, u32> = AHashMap::new();
+        let entry_size = |pos: usize| {
+            let prefix: elf::EhFrameEntryPrefix =
+                bytemuck::pod_read_unaligned(&data[pos..pos + PREFIX_LEN]);
+            core::mem::size_of_val(&prefix.length) + prefix.length as usize
+        };
+        // The CIEs that we write go first, then our FDEs.
+        let mut input_pos = 0;
+        let mut output_pos: usize = self
+            .cies
+            .iter()
+            .filter(|cie| cie.duplicate_of.is_none())
+            .map(|cie| entry_size(cie.input_offset as usize))
+            .sum();
 
         while input_pos + PREFIX_LEN <= data.len() {
             let prefix: elf::EhFrameEntryPrefix =
                 bytemuck::pod_read_unaligned(&data[input_pos..input_pos + PREFIX_LEN]);
             let size = core::mem::size_of_val(&prefix.length) + prefix.length as usize;
             let next_input_pos = input_pos + size;
-            let next_output_pos = output_pos + size;
             if next_input_pos > data.len() {
-                bail!("Invalid .eh_frame data");
+                return Err(LinkError::MalformedInput {
+                    file: self.input.to_string(),
+                    reason: "Invalid .eh_frame data".to_owned(),
+                }
+                .into());
             }
             let mut should_keep = false;
+            let mut entry_output_pos = output_pos;
             let mut output_cie_offset = None;
             if prefix.cie_id == 0 {
                 // This is a CIE
-                cies_offset_conversion.insert(input_pos as u32, output_pos as u32);
-                should_keep = true;
+                let cie = self.cie_at(input_pos as u32)?;
+                if cie.duplicate_of.is_none() {
+                    should_keep = true;
+                    entry_output_pos = (cie.address - self.eh_frame_start_address) as usize;
+                }
             } else {
                 // This is an FDE
                 if let Some((rel_offset, rel)) = relocations.peek() {
@@ -876,7 +1494,11 @@ impl<'data> ObjectLayout<'data> {
                                         section_index = index;
                                         offset_in_section = elf_symbol.address();
                                     } else {
-                                        bail!(".eh_frame pc-begin refers to symbol that's not defined in file");
+                                        return Err(LinkError::MalformedInput {
+                                            file: self.input.to_string(),
+                                            reason: ".eh_frame pc-begin refers to symbol that's not defined in file".to_owned(),
+                                        }
+                                        .into());
                                     }
                                 }
                                 object::RelocationTarget::Section(index) => {
@@ -885,8 +1507,14 @@ impl<'data> ObjectLayout<'data> {
                                 }
                                 _ => bail!("Unexpected relocation type in .eh_frame pc-begin"),
                             };
-                            if let Some(section_resolution) =
-                                &self.section_resolutions[section_index.0]
+                            // FDEs for sections that were folded into other sections are dropped,
+                            // since the section that they were folded into has its own FDE.
+                            let is_folded =
+                                matches!(self.sections[section_index.0], SectionSlot::Folded(_));
+                            if let Some(section_resolution) = self.section_resolutions
+                                [section_index.0]
+                                .as_ref()
+                                .filter(|_| !is_folded)
                             {
                                 should_keep = true;
                                 let cie_pointer_pos = input_pos as u32 + 4;
@@ -898,34 +1526,40 @@ impl<'data> ObjectLayout<'data> {
                                             prefix.cie_id, cie_pointer_pos
                                         )
                                     })?;
-                                let frame_ptr = (section_resolution.value.address()?
-                                    + offset_in_section)
-                                    as i64
-                                    - eh_frame_hdr_address as i64;
-                                headers_out[header_offset] = EhFrameHdrEntry {
-                                    frame_ptr: i32::try_from(frame_ptr)
-                                        .context("32 bit overflow in frame_ptr")?,
-                                    frame_info_ptr: i32::try_from(
-                                        frame_info_ptr_base + output_pos as u64,
-                                    )
-                                    .context("32 bit overflow when computing frame_info_ptr")?,
-                                };
+                                // The addend is needed when pc-begin is relative to the section
+                                // rather than to a symbol, since then it's the function's offset
+                                // within the section.
+                                let function_address = section_resolution
+                                    .value
+                                    .address()?
+                                    .wrapping_add(offset_in_section)
+                                    .wrapping_add(rel.addend() as u64);
+                                headers_out[header_offset] = eh_frame_hdr_entry(
+                                    function_address,
+                                    frame_info_ptr_base + output_pos as u64,
+                                    eh_frame_hdr_address,
+                                )?;
                                 header_offset += 1;
-                                // TODO: Experiment with skipping this lookup if the `input_cie_pos`
-                                // is the same as the previous entry.
-                                let output_cie_pos = cies_offset_conversion.get(&input_cie_pos).with_context(|| format!("FDE referenced CIE at {input_cie_pos}, but no CIE at that position"))?;
-                                output_cie_offset = Some(output_pos as u32 + 4 - *output_cie_pos);
+                                // The CIE might be in an earlier object if ours was a duplicate.
+                                let cie_address = self.cie_at(input_cie_pos)?.address;
+                                output_cie_offset = Some(
+                                    u32::try_from(
+                                        frame_info_ptr_base + output_pos as u64 + 4 - cie_address,
+                                    )
+                                    .context("CIE is too far from FDE")?,
+                                );
                             }
                         }
                     }
                 }
             }
             if should_keep {
+                let next_output_pos = entry_output_pos + size;
                 if next_output_pos > output_data.len() {
                     bail!("Insufficient allocation to .eh_frame section. Allocated 0x{:x}, but tried to write up to 0x{:x}",
                         self.mem_sizes.eh_frame, next_output_pos);
                 }
-                let entry_out = &mut output_data[output_pos..next_output_pos];
+                let entry_out = &mut output_data[entry_output_pos..next_output_pos];
                 entry_out.copy_from_slice(&data[input_pos..next_input_pos]);
                 if let Some(output_cie_offset) = output_cie_offset {
                     entry_out[4..8].copy_from_slice(&output_cie_offset.to_le_bytes());
@@ -939,7 +1573,9 @@ impl<'data> ObjectLayout<'data> {
                         self,
                         rel_offset - input_pos as u64,
                         rel,
-                        output_pos as u64 + self.eh_frame_start_address,
+                        &mut RelocationTarget::new(
+                            entry_output_pos as u64 + self.eh_frame_start_address,
+                        ),
                         layout,
                         entry_out,
                         relocation_writer,
@@ -952,7 +1588,9 @@ impl<'data> ObjectLayout<'data> {
                     })?;
                     relocations.next();
                 }
-                output_pos = next_output_pos;
+                if prefix.cie_id != 0 {
+                    output_pos = next_output_pos;
+                }
             } else {
                 // We're ignoring this entry, skip any relocations for it.
                 while let Some((rel_offset, _rel)) = relocations.peek() {
@@ -977,6 +1615,16 @@ impl<'data> ObjectLayout<'data> {
         Ok(())
     }
 
+    /// Returns the layout of the CIE at `input_offset` within our .eh_frame section.
+    fn cie_at(&self, input_offset: u32) -> Result<&CieLayout> {
+        self.cies
+            .binary_search_by_key(&input_offset, |cie| cie.input_offset)
+            .map(|index| &self.cies[index])
+            .map_err(|_| {
+                anyhow!("FDE referenced CIE at {input_offset}, but no CIE at that position")
+            })
+    }
+
     fn display_relocation<'a>(
         &'a self,
         rel: &'a object::Relocation,
@@ -1002,11 +1650,22 @@ impl<'data> ObjectLayout<'data> {
                 let file_id = layout.symbol_db.file_id_for_symbol(symbol_id);
                 if symbol_id == SymbolId::undefined() || !layout.is_file_loaded(file_id) {
                     let local_symbol = &self.object.symbol_by_index(symbol_index)?;
-                    if !local_symbol.is_weak() {
-                        bail!(
-                            "Undefined strong reference to `{}`",
-                            String::from_utf8_lossy(local_symbol.name_bytes()?)
+                    if !local_symbol.is_weak()
+                        && layout.args().unresolved_symbols.report_in_object_files()
+                    {
+                        let name = String::from_utf8_lossy(local_symbol.name_bytes()?);
+                        let note = error_handling_script::note(
+                            layout.args(),
+                            ErrorKind::UndefinedSymbol,
+                            &name,
                         );
+                        layout.diagnostics.unresolved_symbol(
+                            layout.args(),
+                            LinkError::UndefinedSymbol {
+                                name: name.into_owned(),
+                                note,
+                            },
+                        )?;
                     }
                     // TODO: Check if reference is weak.
                     new_resolution = Some(layout.internal().undefined_symbol_resolution);
@@ -1018,6 +1677,11 @@ impl<'data> ObjectLayout<'data> {
                 bail!("Don't currently support relocations directly to sections");
                 // self.section_resolutions[local_index.0].unwrap()
             }
+            // A relocation without a symbol, such as RISC-V's R_RISCV_RELAX. These refer to symbol
+            // 0, which has a value of 0.
+            object::RelocationTarget::Absolute => {
+                new_resolution = Some(layout.internal().undefined_symbol_resolution);
+            }
             other => bail!("Unsupported relocation {other:?}"),
         };
         Ok(new_resolution)
@@ -1069,46 +1733,141 @@ impl<'a> Display for DisplayRelocation<'a> {
 }
 
 struct DynamicRelocationWriter<'out> {
-    /// Whether we're writing relocations. This will be false if we're writing a non-relocatable
-    /// output file.
+    /// Whether we're writing relocations. This will be false if we're writing an output file that
+    /// doesn't need dynamic relocations.
     is_active: bool,
+    /// Whether we write relative relocations for addresses. This will be false for non-PIE
+    /// executables, which are loaded at the address we linked them at.
+    is_relocatable: bool,
+    /// Whether places that get a dynamic relocation should also be given their link-time value.
+    apply_dynamic_relocs: bool,
+    /// Whether we write relative relocations as addresses in .relr.dyn rather than as RELA entries.
+    uses_relr: bool,
     rela_dyn_relative: &'out mut [crate::elf::Rela],
     rela_dyn_glob_dat: &'out mut [crate::elf::Rela],
+    relr_dyn: &'out mut [u64],
+    emulation: Emulation,
 }
 
 impl<'out> DynamicRelocationWriter<'out> {
-    fn new(is_active: bool, buffers: &mut OutputSectionPartMap<&'out mut [u8]>) -> Self {
+    fn new(layout: &Layout, buffers: &mut OutputSectionPartMap<&'out mut [u8]>) -> Self {
+        let args = layout.args();
         Self {
-            is_active,
+            is_active: args.needs_dynamic(),
+            is_relocatable: args.is_relocatable(),
+            apply_dynamic_relocs: args.apply_dynamic_relocs,
+            uses_relr: args.uses_relr(),
             rela_dyn_relative: slice_from_all_bytes_mut(core::mem::take(
                 &mut buffers.rela_dyn_relative,
             )),
             rela_dyn_glob_dat: slice_from_all_bytes_mut(core::mem::take(
                 &mut buffers.rela_dyn_glob_dat,
             )),
+            relr_dyn: slice_from_all_bytes_mut(core::mem::take(&mut buffers.relr_dyn)),
+            emulation: layout.emulation(),
         }
     }
 
+    /// Returns a writer for use when we're not producing dynamic relocations.
+    fn inactive() -> Self {
+        Self {
+            is_active: false,
+            is_relocatable: false,
+            apply_dynamic_relocs: false,
+            uses_relr: false,
+            rela_dyn_relative: &mut [],
+            rela_dyn_glob_dat: &mut [],
+            relr_dyn: &mut [],
+            emulation: Emulation::default(),
+        }
+    }
+
+    /// Takes enough of our remaining entries for the dynamic relocations described by `counts` and
+    /// returns a writer that will write to just those entries.
+    fn split_for_section(
+        &mut self,
+        counts: DynamicRelocationCounts,
+    ) -> Result<DynamicRelocationWriter<'out>> {
+        let (rela_dyn_relative, relr_dyn) = if self.uses_relr {
+            (
+                Default::default(),
+                take_entries(&mut self.relr_dyn, counts.relative, ".relr.dyn")?,
+            )
+        } else {
+            (
+                take_entries(
+                    &mut self.rela_dyn_relative,
+                    counts.relative,
+                    ".rela.dyn (relative)",
+                )?,
+                Default::default(),
+            )
+        };
+        Ok(DynamicRelocationWriter {
+            is_active: self.is_active,
+            is_relocatable: self.is_relocatable,
+            apply_dynamic_relocs: self.apply_dynamic_relocs,
+            uses_relr: self.uses_relr,
+            rela_dyn_relative,
+            rela_dyn_glob_dat: take_entries(
+                &mut self.rela_dyn_glob_dat,
+                counts.glob_dat,
+                ".rela.dyn (glob-dat)",
+            )?,
+            relr_dyn,
+            emulation: self.emulation,
+        })
+    }
+
+    /// Returns whether a reference to `res_value` needs a dynamic relocation.
+    fn needs_relocation(&self, res_value: ResolutionValue) -> bool {
+        match res_value {
+            ResolutionValue::Absolute(_) => false,
+            ResolutionValue::Address(_) => self.is_active && self.is_relocatable,
+            ResolutionValue::Dynamic(_) => self.is_active,
+        }
+    }
+
+    /// Returns whether a place that gets a dynamic relocation for `res_value` should also be given
+    /// its link-time value. RELR entries have no addend, so the loader adds our load address to
+    /// whatever the place already holds.
+    fn place_needs_value(&self, res_value: ResolutionValue) -> bool {
+        self.apply_dynamic_relocs
+            || (self.uses_relr && matches!(res_value, ResolutionValue::Address(_)))
+    }
+
     fn write_relocation(&mut self, place: u64, res_value: ResolutionValue, addend: u64) -> Result {
-        if !self.is_active {
+        if !self.needs_relocation(res_value) {
             return Ok(());
         }
         let e = LittleEndian;
         match res_value {
             ResolutionValue::Absolute(_) => {}
+            ResolutionValue::Address(_) if self.uses_relr => {
+                if !place.is_multiple_of(elf::RELR_ENTRY_SIZE) {
+                    bail!(
+                        "Can't pack relative relocation at unaligned address 0x{place:x} into \
+                         .relr.dyn"
+                    );
+                }
+                let relr = crate::slice::take_first_mut(&mut self.relr_dyn)
+                    .context("insufficient allocation to .relr.dyn")?;
+                *relr = place;
+            }
             ResolutionValue::Address(address) => {
                 let rela = crate::slice::take_first_mut(&mut self.rela_dyn_relative)
                     .context("insufficient allocation to .rela.dyn (relative)")?;
                 rela.r_offset.set(e, place);
                 rela.r_addend.set(e, address.wrapping_add(addend) as i64);
-                rela.r_info.set(e, object::elf::R_X86_64_RELATIVE.into());
+                rela.r_info
+                    .set(e, self.emulation.relative_relocation().into());
             }
             ResolutionValue::Dynamic(symbol_index) => {
                 let rela = crate::slice::take_first_mut(&mut self.rela_dyn_glob_dat)
                     .context("insufficient allocation to .rela.dyn (glob-dat)")?;
                 rela.r_offset.set(e, place);
                 rela.r_addend.set(e, addend as i64);
-                // We could plausibly use R_X86_64_JUMP_SLOT here in cases where we have only PLT
+                // We could plausibly use JUMP_SLOT relocations here in cases where we have only PLT
                 // references to a symbol and no GOT references. If we did that, we'd need to put
                 // the relocation in .rela.plt not .rela.dyn. Right now, we don't track whether a
                 // symbol has only PLT references and no GOT references. Also, we currently set the
@@ -1116,18 +1875,38 @@ impl<'out> DynamicRelocationWriter<'out> {
                 // use of JUMP_SLOT relocations pointless.
                 rela.r_info.set(
                     e,
-                    u64::from(symbol_index) << 32 | u64::from(object::elf::R_X86_64_GLOB_DAT),
+                    u64::from(symbol_index) << 32 | u64::from(self.emulation.glob_dat_relocation()),
                 );
             }
         }
         Ok(())
     }
 
+    /// Writes a relocation telling the loader to initialise `place` by copying the value of the
+    /// data object at `symbol_index` in .dynsym from the shared object that defines it.
+    fn write_copy_relocation(&mut self, place: u64, symbol_index: u32) -> Result {
+        let e = LittleEndian;
+        let rela = crate::slice::take_first_mut(&mut self.rela_dyn_glob_dat)
+            .context("insufficient allocation to .rela.dyn (glob-dat)")?;
+        rela.r_offset.set(e, place);
+        rela.r_addend.set(e, 0);
+        rela.r_info.set(
+            e,
+            u64::from(symbol_index) << 32 | u64::from(self.emulation.copy_relocation()),
+        );
+        Ok(())
+    }
+
     fn disabled() -> Self {
         Self {
             is_active: false,
+            is_relocatable: false,
+            apply_dynamic_relocs: false,
+            uses_relr: false,
             rela_dyn_relative: Default::default(),
             rela_dyn_glob_dat: Default::default(),
+            relr_dyn: Default::default(),
+            emulation: Emulation::default(),
         }
     }
 
@@ -1146,10 +1925,51 @@ impl<'out> DynamicRelocationWriter<'out> {
                 mem_sizes.rela_dyn_glob_dat / elf::RELA_ENTRY_SIZE,
             );
         }
+        if !self.relr_dyn.is_empty() {
+            bail!(
+                "Allocated too much space in .relr.dyn. {} of {} entries remain unused.",
+                self.relr_dyn.len(),
+                mem_sizes.relr_dyn / elf::RELR_ENTRY_SIZE,
+            );
+        }
         Ok(())
     }
 }
 
+fn take_entries<'out, T>(
+    entries: &mut &'out mut [T],
+    count: usize,
+    section_name: &str,
+) -> Result<&'out mut [T]> {
+    if entries.len() < count {
+        bail!(
+            "insufficient allocation to {section_name}. Needed {count} entries, but only {} remain",
+            entries.len()
+        );
+    }
+    Ok(slice_take_prefix_mut(entries, count))
+}
+
+/// The section, or part of a section, that we're applying relocations to.
+struct RelocationTarget {
+    /// The output address of the start of the section.
+    address: u64,
+
+    /// Values computed for RISC-V PC-relative `*_HI20` relocations in the section, keyed by the
+    /// address of the instruction that they were applied to. The `R_RISCV_PCREL_LO12_*` relocation
+    /// that goes with each of them refers to that instruction rather than to the actual target.
+    riscv_hi20_values: AHashMap<u64, u64>,
+}
+
+impl RelocationTarget {
+    fn new(address: u64) -> Self {
+        Self {
+            address,
+            riscv_hi20_values: AHashMap::new(),
+        }
+    }
+}
+
 /// Applies the relocation `rel` at `offset_in_section`, where the section bytes are `out`. See "ELF
 /// Handling For Thread-Local Storage" for details about some of the TLS-related relocations and
 /// transformations that are applied.
@@ -1157,7 +1977,7 @@ fn apply_relocation(
     object_layout: &ObjectLayout,
     mut offset_in_section: u64,
     rel: &object::Relocation,
-    section_address: u64,
+    target: &mut RelocationTarget,
     layout: &Layout,
     out: &mut [u8],
     relocation_writer: &mut DynamicRelocationWriter,
@@ -1170,31 +1990,43 @@ fn apply_relocation(
         ResolutionValue::Address(v) => (v, ValueKind::Address),
         ResolutionValue::Dynamic(_) => (0, ValueKind::Dynamic),
     };
-    let place = section_address + offset_in_section;
+    let place = target.address + offset_in_section;
     let mut addend = rel.addend() as u64;
     let mut next_modifier = RelocationModifier::Normal;
     let object::RelocationFlags::Elf { r_type } = rel.flags() else {
         unreachable!();
     };
+    let emulation = layout.emulation();
     let rel_info;
-    if let Some((relaxation, r_type)) = Relaxation::new(
+    if let Some((relaxation, relaxed_info)) = Relaxation::new(
+        emulation,
         r_type,
         out,
         offset_in_section,
         value_kind,
         layout.args().output_kind,
     ) {
-        rel_info = RelocationKindInfo::from_raw(r_type)?;
+        rel_info = relaxed_info;
         relaxation.apply(out, &mut offset_in_section, &mut addend, &mut next_modifier);
     } else {
-        rel_info = RelocationKindInfo::from_raw(r_type)?;
+        rel_info = RelocationKindInfo::from_raw(emulation, r_type)?;
     }
-    debug_assert!(rel.size() == 0 || rel.size() as usize / 8 == rel_info.byte_size);
     let value = match rel_info.kind {
         RelocationKind::Absolute => {
-            if relocation_writer.is_active && !resolution.value.is_absolute() {
+            if relocation_writer.needs_relocation(resolution.value) {
+                if !matches!(rel_info.size, RelocationSize::ByteSize(_)) {
+                    bail!(
+                        "Relocation type {r_type} ({:?}) can't be used when making a \
+                         position-independent output; recompile with -fPIC",
+                        rel_info.size
+                    );
+                }
                 relocation_writer.write_relocation(place, resolution.value, addend)?;
-                0
+                if relocation_writer.place_needs_value(resolution.value) {
+                    value.wrapping_add(addend)
+                } else {
+                    0
+                }
             } else {
                 value.wrapping_add(addend)
             }
@@ -1220,22 +2052,50 @@ fn apply_relocation(
             .wrapping_add(addend)
             .wrapping_sub(place),
         RelocationKind::DtpOff => value
-            .wrapping_sub(layout.tls_end_address())
+            .wrapping_sub(layout.dtp_address())
             .wrapping_add(addend),
         RelocationKind::GotTpOff => resolution
             .got_address()?
             .wrapping_add(addend)
             .wrapping_sub(place),
-        RelocationKind::TpOff => value.wrapping_sub(layout.tls_end_address()),
+        RelocationKind::TpOff => value
+            .wrapping_sub(layout.thread_pointer_address())
+            .wrapping_add(addend),
         RelocationKind::None => 0,
         other => bail!("Unsupported relocation kind {other:?}"),
     };
-    let value_bytes = value.to_le_bytes();
-    let end = offset_in_section as usize + rel_info.byte_size;
-    if out.len() < end {
-        bail!("Relocation outside of bounds of section");
+    match rel_info.size {
+        RelocationSize::ByteSize(byte_size) => {
+            debug_assert!(rel.size() == 0 || rel.size() as usize / 8 == byte_size);
+            let value_bytes = value.to_le_bytes();
+            let end = offset_in_section as usize + byte_size;
+            if out.len() < end {
+                bail!("Relocation outside of bounds of section");
+            }
+            out[offset_in_section as usize..end].copy_from_slice(&value_bytes[..byte_size]);
+        }
+        RelocationSize::AArch64(instruction) => {
+            crate::aarch64::write_instruction(
+                out.get_mut(offset_in_section as usize..)
+                    .unwrap_or_default(),
+                instruction,
+                value,
+                place,
+                rel_info.kind.is_pc_relative(),
+            )?;
+        }
+        RelocationSize::RiscV(encoding) => {
+            crate::riscv64::write_relocation(
+                out.get_mut(offset_in_section as usize..)
+                    .unwrap_or_default(),
+                encoding,
+                value,
+                place,
+                rel_info.kind.is_pc_relative(),
+                &mut target.riscv_hi20_values,
+            )?;
+        }
     }
-    out[offset_in_section as usize..end].copy_from_slice(&value_bytes[..rel_info.byte_size]);
     Ok(next_modifier)
 }
 
@@ -1249,12 +2109,11 @@ impl<'data> InternalLayout<'data> {
         let mut program_headers = ProgramHeaderWriter::new(buffers.program_headers);
         write_program_headers(&mut program_headers, layout)?;
 
-        write_section_headers(buffers.section_headers, layout);
+        write_section_headers(buffers.section_headers, &self.header_info, layout);
 
         write_section_header_strings(buffers.shstrtab, &layout.output_sections);
 
-        let mut relocation_writer =
-            DynamicRelocationWriter::new(layout.args().is_relocatable(), &mut buffers);
+        let mut relocation_writer = DynamicRelocationWriter::new(layout, &mut buffers);
 
         self.write_plt_got_entries(&mut buffers, layout, &mut relocation_writer)?;
 
@@ -1262,12 +2121,18 @@ impl<'data> InternalLayout<'data> {
             self.write_symbol_table_entries(&mut buffers, layout)?;
         }
 
-        write_eh_frame_hdr(&mut buffers, layout)?;
+        if layout.args().output_kind != OutputKind::Relocatable {
+            write_eh_frame_hdr(&mut buffers, layout)?;
+        }
 
         self.write_merged_strings(&mut buffers);
 
         self.write_interp(&mut buffers);
 
+        if layout.args().needs_dynamic() {
+            write_rpath(buffers.dynstr, layout.args());
+        }
+
         relocation_writer.validate_empty(&self.mem_sizes)?;
 
         Ok(())
@@ -1284,10 +2149,14 @@ impl<'data> InternalLayout<'data> {
     fn write_merged_strings(&self, buffers: &mut OutputSectionPartMap<&mut [u8]>) {
         self.merged_strings.for_each(|section_id, merged| {
             if merged.len > 0 {
-                let buffer = buffers.regular_mut(section_id, crate::alignment::MIN);
+                let buffer = buffers.regular_mut(section_id, merged.alignment);
+                let mut offset = 0;
                 for string in &merged.strings {
-                    let dest = crate::slice::slice_take_prefix_mut(buffer, string.len());
-                    dest.copy_from_slice(string)
+                    let padding = string.alignment.align_up(offset) - offset;
+                    crate::slice::slice_take_prefix_mut(buffer, padding as usize);
+                    let dest = crate::slice::slice_take_prefix_mut(buffer, string.bytes.len());
+                    dest.copy_from_slice(string.bytes);
+                    offset += padding + string.bytes.len() as u64;
                 }
             }
         });
@@ -1364,20 +2233,121 @@ impl<'data> InternalLayout<'data> {
         );
 
         // Define symbol 0. This needs to be a null placeholder.
-        symbol_writer.define_symbol(true, 0, 0, 0, &[])?;
+        symbol_writer.define_symbol(
+            true,
+            OutputShndx::Special(object::elf::SHN_UNDEF),
+            0,
+            0,
+            &[],
+        )?;
+
+        // Section symbols and undefined symbols are only present in relocatable outputs.
+        let mut section_symbols = Vec::new();
+        self.section_symbol_indexes.for_each(|section_id, index| {
+            if *index != 0 {
+                section_symbols.push((*index, section_id));
+            }
+        });
+        section_symbols.sort_unstable_by_key(|(index, _)| *index);
+        for (_, section_id) in section_symbols {
+            let shndx = layout
+                .output_sections
+                .output_index_of_section(section_id)
+                .context("Section symbol for a section that we're not going to output")?;
+            symbol_writer.define_section_symbol(shndx)?;
+        }
+        for symbol in &self.undefined_symbols {
+            let binding = if symbol.is_weak {
+                elf::Binding::Weak
+            } else {
+                elf::Binding::Global
+            };
+            let entry = symbol_writer.define_symbol(
+                false,
+                OutputShndx::Special(object::elf::SHN_UNDEF),
+                0,
+                0,
+                symbol.name,
+            )?;
+            entry.st_info = (binding as u8) << 4;
+        }
 
         let internal_symbols = &self.internal_symbols;
 
-        write_internal_symbols(internal_symbols, layout, &mut symbol_writer)?;
+        write_internal_symbols(
+            internal_symbols,
+            layout,
+            object::elf::STV_DEFAULT,
+            &mut symbol_writer,
+        )?;
         symbol_writer.check_exhausted()?;
         Ok(())
     }
 }
 
+/// The offset in .dynstr of our rpath, if we have one. The internal file writes it immediately after
+/// the empty string at the start of .dynstr.
+const RPATH_DYNSTR_OFFSET: u64 = 1;
+
+/// Writes our rpath, if any, to the internal file's part of .dynstr.
+fn write_rpath(dynstr: &mut [u8], args: &Args) {
+    if let Some(rpath) = args.rpath.as_ref() {
+        let out = &mut dynstr[RPATH_DYNSTR_OFFSET as usize..];
+        out[..rpath.len()].copy_from_slice(rpath.as_bytes());
+        out[rpath.len()] = 0;
+    }
+}
+
 fn write_epilogue_dynamic_entries(out: &mut [u8], layout: &Layout) -> Result {
     let mut out = DynamicEntriesWriter::new(out);
+    if layout.has_text_relocations {
+        out.write(DynamicTag::TextRel, 0)?;
+    }
+    if layout.has_init {
+        out.write(
+            DynamicTag::Init,
+            layout.vma_of_section(output_section_id::INIT),
+        )?;
+    }
+    if layout.has_fini {
+        out.write(
+            DynamicTag::Fini,
+            layout.vma_of_section(output_section_id::FINI),
+        )?;
+    }
+    if layout.section_part_layouts.gnu_version_d.mem_size > 0 {
+        out.write(
+            DynamicTag::VerDef,
+            layout.vma_of_section(output_section_id::GNU_VERSION_D),
+        )?;
+        out.write(
+            DynamicTag::VerDefNum,
+            layout.num_version_definitions() as u64,
+        )?;
+    }
+    if layout.section_part_layouts.gnu_version_r.mem_size > 0 {
+        out.write(
+            DynamicTag::VerNeed,
+            layout.vma_of_section(output_section_id::GNU_VERSION_R),
+        )?;
+        out.write(
+            DynamicTag::VerNeedNum,
+            layout.num_version_requirements() as u64,
+        )?;
+    }
+    if layout.section_part_layouts.gnu_version.mem_size > 0 {
+        out.write(
+            DynamicTag::VerSym,
+            layout.vma_of_section(output_section_id::GNU_VERSION),
+        )?;
+    }
     for writer in EPILOGUE_DYNAMIC_ENTRY_WRITERS {
-        writer.write(&mut out, layout)?;
+        if (writer.is_present)(layout.args()) {
+            writer.write(&mut out, layout)?;
+        }
+    }
+    for _ in 0..layout.args().spare_dynamic_tags {
+        out.write(DynamicTag::Null, 0)?;
     }
 
     Ok(())
@@ -1385,8 +2355,7 @@ fn write_epilogue_dynamic_entries(out: &mut [u8], layout: &Layout) -> Result {
 
 impl<'data> EpilogueLayout<'data> {
     fn write(&self, mut buffers: OutputSectionPartMap<&mut [u8]>, layout: &Layout) -> Result {
-        let mut relocation_writer =
-            DynamicRelocationWriter::new(layout.args().is_relocatable(), &mut buffers);
+        let mut relocation_writer = DynamicRelocationWriter::new(layout, &mut buffers);
 
         let mut plt_got_writer = PltGotWriter::new(layout, &mut buffers);
         write_internal_symbols_plt_got_entries(
@@ -1404,32 +2373,210 @@ impl<'data> EpilogueLayout<'data> {
                 &self.mem_sizes,
                 &layout.output_sections,
             );
-            write_internal_symbols(&self.internal_symbols, layout, &mut symbol_writer)?;
+            // All of our symbols are `__start_X` / `__stop_X` symbols for custom sections.
+            write_internal_symbols(
+                &self.internal_symbols,
+                layout,
+                layout.args().start_stop_visibility.st_other(),
+                &mut symbol_writer,
+            )?;
         }
         if layout.args().needs_dynamic() {
             write_epilogue_dynamic_entries(buffers.dynamic, layout)?;
         }
-        write_dynamic_symbol_definitions(self, &mut buffers, layout)?;
+        let dynstr_offset = write_version_definitions(self, &mut buffers, layout)?;
+        write_dynamic_symbol_definitions(self, &mut buffers, dynstr_offset, layout)?;
+        write_versym(self, buffers.gnu_version, layout);
+        self.gnu_properties.write(buffers.note_gnu_property)?;
+        self.abi_tag.write(buffers.note_abi_tag)?;
+        if let Some(gdb_index) = &self.gdb_index {
+            gdb_index.write(buffers.gdb_index, |file_id, section_index| {
+                layout.input_section_address(file_id, section_index)
+            })?;
+        }
 
         Ok(())
     }
 }
 
+/// Writes .gnu.version_d, putting the names of the versions at the start of our part of .dynstr.
+/// Returns the offset in .dynstr at which our remaining strings should be written.
+fn write_version_definitions(
+    epilogue: &EpilogueLayout,
+    buffers: &mut OutputSectionPartMap<&mut [u8]>,
+    layout: &Layout,
+) -> Result<u32> {
+    if epilogue.version_definitions.is_empty() {
+        return Ok(epilogue.dynstr_offset_start);
+    }
+    let e = LittleEndian;
+    let mut strtab = StrTabWriter {
+        next_offset: u64::from(epilogue.dynstr_offset_start),
+        out: core::mem::take(&mut buffers.dynstr),
+    };
+    let names = std::iter::once(crate::layout::base_version_name(layout.args()))
+        .chain(epilogue.version_definitions.iter().map(|def| def.name));
+    // Write all the names first, since parents are referenced by the offset of their name.
+    let mut name_offsets = Vec::with_capacity(epilogue.version_definitions.len() + 1);
+    for name in names.clone() {
+        let offset: u32 = strtab
+            .write_str(name)
+            .try_into()
+            .context(".dynstr is too big")?;
+        name_offsets.push((name, offset));
+    }
+    let name_offset = |name: &[u8]| {
+        name_offsets
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, offset)| *offset)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Undefined parent version `{}`",
+                    String::from_utf8_lossy(name)
+                )
+            })
+    };
+    let mut out = core::mem::take(&mut buffers.gnu_version_d);
+    let num_versions = name_offsets.len();
+    for (index, name) in names.enumerate() {
+        let parents = index.checked_sub(1).map_or(&[][..], |i| {
+            epilogue.version_definitions[i].parents.as_slice()
+        });
+        let (verdef, rest) = object::from_bytes_mut::<elf::Verdef>(out)
+            .map_err(|_| anyhow!("Insufficient .gnu.version_d allocation"))?;
+        out = rest;
+        let is_base = index == 0;
+        verdef.vd_version.set(e, object::elf::VER_DEF_CURRENT);
+        verdef.vd_flags.set(
+            e,
+            if is_base {
+                object::elf::VER_FLG_BASE
+            } else {
+                0
+            },
+        );
+        verdef.vd_ndx.set(
+            e,
+            if is_base {
+                object::elf::VER_NDX_GLOBAL
+            } else {
+                elf::FIRST_DEFINED_VERSION_INDEX + index as u16 - 1
+            },
+        );
+        let num_aux = 1 + parents.len();
+        verdef.vd_cnt.set(e, num_aux as u16);
+        verdef.vd_hash.set(e, object::elf::hash(name));
+        verdef
+            .vd_aux
+            .set(e, core::mem::size_of::<elf::Verdef>() as u32);
+        let is_last = index + 1 == num_versions;
+        verdef.vd_next.set(
+            e,
+            if is_last {
+                0
+            } else {
+                (core::mem::size_of::<elf::Verdef>()
+                    + num_aux * core::mem::size_of::<elf::Verdaux>()) as u32
+            },
+        );
+        // The first auxiliary entry names the version itself. Any others name its parents.
+        for (aux_index, aux_name) in std::iter::once(name)
+            .chain(parents.iter().copied())
+            .enumerate()
+        {
+            let (verdaux, rest) = object::from_bytes_mut::<elf::Verdaux>(out)
+                .map_err(|_| anyhow!("Insufficient .gnu.version_d allocation"))?;
+            out = rest;
+            verdaux.vda_name.set(e, name_offset(aux_name)?);
+            let is_last_aux = aux_index + 1 == num_aux;
+            verdaux.vda_next.set(
+                e,
+                if is_last_aux {
+                    0
+                } else {
+                    core::mem::size_of::<elf::Verdaux>() as u32
+                },
+            );
+        }
+    }
+    buffers.dynstr = strtab.out;
+    strtab.next_offset.try_into().context(".dynstr is too big")
+}
+
 fn write_dynamic_symbol_definitions(
     epilogue: &EpilogueLayout,
     buffers: &mut OutputSectionPartMap<&mut [u8]>,
+    dynstr_offset: u32,
     layout: &Layout,
 ) -> Result {
-    if layout.args().output_kind != OutputKind::SharedObject {
+    if !layout.args().needs_dynamic() {
         return Ok(());
     }
-    let mut dynamic_symbol_writer = SymbolTableWriter::new_dynamic(
-        epilogue.dynstr_offset_start,
-        buffers,
-        &layout.output_sections,
-    );
+    if layout.args().hash_style.includes_gnu() {
+        write_gnu_hash_table(epilogue, buffers.gnu_hash)?;
+    }
+    if layout.args().hash_style.includes_sysv() {
+        write_sysv_hash_table(epilogue, buffers.hash)?;
+    }
+
+    let mut dynamic_symbol_writer =
+        SymbolTableWriter::new_dynamic(dynstr_offset, buffers, &layout.output_sections);
+    for sym_def in &epilogue.dynamic_symbol_definitions {
+        let file_id = layout.symbol_db.file_id_for_symbol(sym_def.symbol_id);
+        let object = match &layout.file_layouts[file_id.as_usize()] {
+            FileLayout::Object(object) => object,
+            FileLayout::Dynamic(dynamic) => {
+                // A data object that we've copied from a shared object. We define it at our copy
+                // so that the shared object uses the copy too.
+                let sym = dynamic
+                    .object
+                    .dynamic_symbol_table()
+                    .context("Missing dynamic symbol table")?
+                    .symbol_by_index(object::SymbolIndex(
+                        sym_def.symbol_id.offset_from(dynamic.start_symbol_id),
+                    ))?;
+                let address = layout
+                    .symbol_resolution(sym_def.symbol_id)
+                    .context("Internal error: copied symbol has no resolution")?
+                    .value
+                    .address()?;
+                dynamic_symbol_writer.define_copied_object(&sym, address)?;
+                continue;
+            }
+            _ => bail!(
+                "Internal error: only objects and shared objects should define dynamic symbols"
+            ),
+        };
+        let sym = object.object.symbol_by_index(object::SymbolIndex(
+            sym_def.symbol_id.offset_from(object.start_symbol_id),
+        ))?;
+        let section_index = sym
+            .section_index()
+            .context("Internal error: Symbols should only be defined if they have a section")?;
+        let output_section_id = object.sections[section_index.0]
+            .output_section_id()
+            .context("Internal error: Defined symbols should always be for a loaded section")?;
+        let section_address = object.section_resolutions[section_index.0]
+            .as_ref()
+            .unwrap()
+            .value
+            .address_or_value()?;
+        dynamic_symbol_writer
+            .copy_symbol(&sym, output_section_id, section_address, false)
+            .with_context(|| {
+                format!(
+                    "Failed to copy dynamic {}",
+                    layout.symbol_debug(sym_def.symbol_id)
+                )
+            })?;
+    }
+
+    Ok(())
+}
 
-    let (header, rest) = object::from_bytes_mut::<GnuHashHeader>(buffers.gnu_hash)
+fn write_gnu_hash_table(epilogue: &EpilogueLayout, out: &mut [u8]) -> Result {
+    let (header, rest) = object::from_bytes_mut::<GnuHashHeader>(out)
         .map_err(|_| anyhow!("Insufficient .gnu.hash allocation"))?;
     let e = LittleEndian;
     let gnu_hash_layout = &epilogue.gnu_hash_layout;
@@ -1457,7 +2604,6 @@ fn write_dynamic_symbol_definitions(
     let mut start_of_chain = true;
     for (i, chain_out) in chains.iter_mut().enumerate() {
         let sym_def = sym_defs.next().unwrap();
-
         // For each symbol, we set two bits in the bloom filter. This speeds up dynamic loading,
         // since most symbols not defined by the shared object can be rejected just by the bloom
         // filter.
@@ -1482,43 +2628,71 @@ fn write_dynamic_symbol_definitions(
             *chain_out |= 1;
             start_of_chain = true;
         }
+    }
+    Ok(())
+}
 
-        let file_id = layout.symbol_db.file_id_for_symbol(sym_def.symbol_id);
-        let file_layout = &layout.file_layouts[file_id.as_usize()];
-        let FileLayout::Object(object) = file_layout else {
-            bail!("Internal error: only objects should define dynamic symbols");
-        };
-        let sym = object.object.symbol_by_index(object::SymbolIndex(
-            sym_def.symbol_id.offset_from(object.start_symbol_id),
-        ))?;
-        let section_index = sym
-            .section_index()
-            .context("Internal error: Symbols should only be defined if they have a section")?;
-        let SectionSlot::Loaded(section) = &object.sections[section_index.0] else {
-            bail!("Internal error: Defined symbols should always be for a loaded section");
-        };
-        let output_section_id = section.output_section_id.unwrap();
-        let section_address = object.section_resolutions[section_index.0]
-            .as_ref()
-            .unwrap()
-            .value
-            .address_or_value()?;
-        dynamic_symbol_writer
-            .copy_symbol(&sym, output_section_id, section_address)
-            .with_context(|| {
-                format!(
-                    "Failed to copy dynamic {}",
-                    layout.symbol_debug(sym_def.symbol_id)
-                )
-            })?;
+/// Writes the SysV .hash section. Each bucket holds the index of the first dynamic symbol in that
+/// bucket, with the chain entry for each symbol giving the index of the next symbol in the same
+/// bucket. Zero terminates a chain. Only the symbols that we define are included, since they're
+/// the only ones that the loader needs to be able to find.
+/// Writes .gnu.version. The null symbol is local. Our own definitions come last in .dynsym and have
+/// whatever version the version script gave them, or for copied definitions, the version of the
+/// original. The symbols that we reference from shared objects come before them, in the order of
+/// those shared objects, and have the versions that we need from them.
+fn write_versym(epilogue: &EpilogueLayout, out: &mut [u8], layout: &Layout) {
+    let entries: &mut [u16] = bytemuck::cast_slice_mut(out);
+    let Some((null_symbol, rest)) = entries.split_first_mut() else {
+        return;
+    };
+    *null_symbol = object::elf::VER_NDX_LOCAL;
+    let (references, definitions) =
+        rest.split_at_mut(rest.len() - epilogue.dynamic_symbol_definitions.len());
+    let reference_versions = layout.file_layouts.iter().flat_map(|file| match file {
+        FileLayout::Dynamic(dynamic) => dynamic.symbol_versions.as_slice(),
+        _ => &[],
+    });
+    for (entry, version) in references.iter_mut().zip(reference_versions) {
+        *entry = *version;
+    }
+    for (entry, definition) in definitions
+        .iter_mut()
+        .zip(&epilogue.dynamic_symbol_definitions)
+    {
+        *entry = definition.version;
     }
+}
 
+fn write_sysv_hash_table(epilogue: &EpilogueLayout, out: &mut [u8]) -> Result {
+    let sysv_hash_layout = &epilogue.sysv_hash_layout;
+    let words: &mut [u32] = slice_from_all_bytes_mut(out);
+    let (header, rest) = words.split_at_mut(2);
+    let (buckets, chains) = rest.split_at_mut(sysv_hash_layout.bucket_count as usize);
+    if chains.len() != sysv_hash_layout.chain_count as usize {
+        bail!("Incorrect .hash allocation");
+    }
+    header[0] = sysv_hash_layout.bucket_count;
+    header[1] = sysv_hash_layout.chain_count;
+    buckets.fill(0);
+    chains.fill(0);
+    let symbol_base = epilogue.gnu_hash_layout.symbol_base;
+    for (i, sym_def) in epilogue.dynamic_symbol_definitions.iter().enumerate() {
+        let symbol_index = symbol_base + i as u32;
+        let bucket = &mut buckets
+            [(object::elf::hash(sym_def.name) % sysv_hash_layout.bucket_count) as usize];
+        *chains
+            .get_mut(symbol_index as usize)
+            .context("Dynamic symbol index exceeds .hash chain count")? = *bucket;
+        *bucket = symbol_index;
+    }
     Ok(())
 }
 
+/// Writes symbol table entries for our internal symbols, giving each the visibility `st_other`.
 fn write_internal_symbols(
     internal_symbols: &InternalSymbols,
     layout: &Layout<'_>,
+    st_other: u8,
     symbol_writer: &mut SymbolTableWriter<'_, '_>,
 ) -> Result {
     for (local_index, def_info) in internal_symbols.symbol_definitions.iter().enumerate() {
@@ -1529,34 +2703,52 @@ fn write_internal_symbols(
         let Some(resolution) = layout.symbol_resolution(symbol_id) else {
             continue;
         };
-        let Some(section_id) = def_info.section_id() else {
-            // The null symbol is currently handled elsewhere. TODO: See if the code would be
-            // simpler if we just handled it here.
-            continue;
-        };
-
-        // We don't emit a section header for our headers section, so don't emit symbols that
-        // are in that section, otherwise they'll show up as undefined.
-        if section_id == output_section_id::FILE_HEADER {
+        let symbol_name = layout.symbol_db.symbol_name(symbol_id)?;
+        if !layout.args().should_retain_symbol(symbol_name.bytes()) {
             continue;
         }
+        let mut binding = elf::Binding::Global;
+        let (shndx, value) = if let InternalSymDefInfo::Defsym(index) = def_info {
+            if layout.args().defsyms[*index as usize].weak {
+                binding = elf::Binding::Weak;
+            }
+            (
+                OutputShndx::Special(object::elf::SHN_ABS),
+                resolution.value.address_or_value()?,
+            )
+        } else {
+            let Some(section_id) = def_info.section_id() else {
+                // The null symbol is currently handled elsewhere. TODO: See if the code would be
+                // simpler if we just handled it here.
+                continue;
+            };
 
-        let symbol_name = layout.symbol_db.symbol_name(symbol_id)?;
-        let shndx = layout
-            .output_sections
-            .output_index_of_section(section_id)
-            .with_context(|| {
-                format!(
-                    "symbol `{}` in section `{}` that we're not going to output {resolution:?}",
-                    symbol_name,
-                    layout.output_sections.display_name(section_id)
-                )
-            })?;
-        let address = resolution.value.address()?;
+            // We don't emit a section header for our headers section, so don't emit symbols that
+            // are in that section, otherwise they'll show up as undefined.
+            if section_id == output_section_id::FILE_HEADER {
+                continue;
+            }
+
+            let shndx = layout
+                .output_sections
+                .output_index_of_section(section_id)
+                .with_context(|| {
+                    format!(
+                        "symbol `{}` in section `{}` that we're not going to output {resolution:?}",
+                        symbol_name,
+                        layout.output_sections.display_name(section_id)
+                    )
+                })?;
+            (
+                OutputShndx::Index(shndx),
+                resolution.value.address()? - layout.symbol_value_base(section_id),
+            )
+        };
         let entry = symbol_writer
-            .define_symbol(false, shndx, address, 0, symbol_name.bytes())
+            .define_symbol(false, shndx, value, 0, symbol_name.bytes())
             .with_context(|| format!("Failed to write {}", layout.symbol_debug(symbol_id)))?;
-        entry.st_info = (elf::Binding::Global as u8) << 4;
+        entry.st_info = (binding as u8) << 4;
+        entry.st_other = st_other;
     }
     Ok(())
 }
@@ -1582,6 +2774,28 @@ fn write_eh_frame_hdr(
     Ok(())
 }
 
+/// Returns the entry for .eh_frame_hdr's lookup table for the FDE at `fde_address`, which covers the
+/// function starting at `function_address`. We always use a table of 32 bit offsets relative to the
+/// start of .eh_frame_hdr, since that's the only encoding that unwinders use for binary search.
+fn eh_frame_hdr_entry(
+    function_address: u64,
+    fde_address: u64,
+    eh_frame_hdr_address: u64,
+) -> Result<EhFrameHdrEntry> {
+    let relative = |address: u64, what: &str| {
+        i32::try_from(address.wrapping_sub(eh_frame_hdr_address) as i64).with_context(|| {
+            format!(
+                "{what} at 0x{address:x} is more than 2GiB away from .eh_frame_hdr at \
+                 0x{eh_frame_hdr_address:x}, so it can't be encoded in .eh_frame_hdr's lookup table"
+            )
+        })
+    };
+    Ok(EhFrameHdrEntry {
+        frame_ptr: relative(function_address, "Function")?,
+        frame_info_ptr: relative(fde_address, "FDE")?,
+    })
+}
+
 fn eh_frame_hdr_entry_count(layout: &Layout<'_>) -> Result<u32> {
     let hdr_sec = layout
         .section_layouts
@@ -1604,15 +2818,26 @@ fn eh_frame_ptr(layout: &Layout<'_>) -> Result<i32> {
     .context(".eh_frame more than 2GB away from .eh_frame_hdr")
 }
 
-pub(crate) const NUM_EPILOGUE_DYNAMIC_ENTRIES: usize = EPILOGUE_DYNAMIC_ENTRY_WRITERS.len();
+/// Returns the number of dynamic entries that the epilogue will write. `DT_TEXTREL`, `DT_INIT` and
+/// `DT_FINI` depend on what we found during layout rather than just on our arguments, so aren't in
+/// the table of writers.
+pub(crate) fn num_epilogue_dynamic_entries(
+    args: &Args,
+    has_text_relocations: bool,
+    has_init: bool,
+    has_fini: bool,
+) -> usize {
+    EPILOGUE_DYNAMIC_ENTRY_WRITERS
+        .iter()
+        .filter(|writer| (writer.is_present)(args))
+        .count()
+        + usize::from(has_text_relocations)
+        + usize::from(has_init)
+        + usize::from(has_fini)
+        + args.spare_dynamic_tags
+}
 
 const EPILOGUE_DYNAMIC_ENTRY_WRITERS: &[DynamicEntryWriter] = &[
-    DynamicEntryWriter::new(DynamicTag::Init, |layout| {
-        layout.vma_of_section(output_section_id::INIT)
-    }),
-    DynamicEntryWriter::new(DynamicTag::Fini, |layout| {
-        layout.vma_of_section(output_section_id::FINI)
-    }),
     DynamicEntryWriter::new(DynamicTag::InitArray, |layout| {
         layout.vma_of_section(output_section_id::INIT_ARRAY)
     }),
@@ -1651,15 +2876,68 @@ const EPILOGUE_DYNAMIC_ENTRY_WRITERS: &[DynamicEntryWriter] = &[
         layout.section_part_layouts.rela_dyn_relative.mem_size
             / core::mem::size_of::<elf::Rela>() as u64
     }),
-    DynamicEntryWriter::new(DynamicTag::GnuHash, |layout| {
-        layout.vma_of_section(output_section_id::GNU_HASH)
+    DynamicEntryWriter::optional(
+        DynamicTag::Relr,
+        |args| args.uses_relr(),
+        |layout| layout.vma_of_section(output_section_id::RELR_DYN),
+    ),
+    DynamicEntryWriter::optional(
+        DynamicTag::RelrSize,
+        |args| args.uses_relr(),
+        |layout| layout.size_of_section(output_section_id::RELR_DYN),
+    ),
+    DynamicEntryWriter::optional(
+        DynamicTag::RelrEnt,
+        |args| args.uses_relr(),
+        |_layout| elf::RELR_ENTRY_SIZE,
+    ),
+    DynamicEntryWriter::optional(
+        DynamicTag::Hash,
+        |args| args.hash_style.includes_sysv(),
+        |layout| layout.vma_of_section(output_section_id::HASH),
+    ),
+    DynamicEntryWriter::optional(
+        DynamicTag::GnuHash,
+        |args| args.hash_style.includes_gnu(),
+        |layout| layout.vma_of_section(output_section_id::GNU_HASH),
+    ),
+    DynamicEntryWriter::optional(
+        DynamicTag::Runpath,
+        |args| args.rpath.is_some() && args.new_dtags,
+        |_layout| RPATH_DYNSTR_OFFSET,
+    ),
+    DynamicEntryWriter::optional(
+        DynamicTag::Rpath,
+        |args| args.rpath.is_some() && !args.new_dtags,
+        |_layout| RPATH_DYNSTR_OFFSET,
+    ),
+    DynamicEntryWriter::new(DynamicTag::Flags, |layout| {
+        let mut flags = elf::flags::BIND_NOW;
+        if layout.args().b_symbolic == BSymbolic::All {
+            flags |= elf::flags::SYMBOLIC;
+        }
+        if layout.args().needs_origin() {
+            flags |= elf::flags::ORIGIN;
+        }
+        if layout.has_text_relocations {
+            flags |= elf::flags::TEXTREL;
+        }
+        if layout.uses_static_tls {
+            flags |= elf::flags::STATIC_TLS;
+        }
+        flags
     }),
-    DynamicEntryWriter::new(DynamicTag::Flags, |_layout| elf::flags::BIND_NOW),
     DynamicEntryWriter::new(DynamicTag::Flags1, |layout| {
-        let mut flags = elf::flags_1::NOW;
+        let mut flags = elf::flags_1::NOW | layout.args().z_flags_1;
         if layout.args().output_kind.is_executable() && layout.args().pie {
             flags |= elf::flags_1::PIE;
         }
+        if layout.args().needs_origin() {
+            flags |= elf::flags_1::ORIGIN;
+        }
+        if layout.exports_unique_symbols {
+            flags |= elf::flags_1::NODELETE;
+        }
         flags
     }),
     DynamicEntryWriter::new(DynamicTag::Null, |_layout| 0),
@@ -1667,12 +2945,30 @@ const EPILOGUE_DYNAMIC_ENTRY_WRITERS: &[DynamicEntryWriter] = &[
 
 struct DynamicEntryWriter {
     tag: DynamicTag,
+    /// Whether the entry should be written given the supplied arguments.
+    is_present: fn(&Args) -> bool,
     cb: fn(&Layout) -> u64,
 }
 
 impl DynamicEntryWriter {
     const fn new(tag: DynamicTag, cb: fn(&Layout) -> u64) -> DynamicEntryWriter {
-        DynamicEntryWriter { tag, cb }
+        DynamicEntryWriter {
+            tag,
+            is_present: |_| true,
+            cb,
+        }
+    }
+
+    const fn optional(
+        tag: DynamicTag,
+        is_present: fn(&Args) -> bool,
+        cb: fn(&Layout) -> u64,
+    ) -> DynamicEntryWriter {
+        DynamicEntryWriter {
+            tag,
+            is_present,
+            cb,
+        }
     }
 
     fn write(&self, out: &mut DynamicEntriesWriter, layout: &Layout) -> Result {
@@ -1686,7 +2982,7 @@ struct DynamicEntriesWriter<'out> {
 }
 
 impl<'out> DynamicEntriesWriter<'out> {
-    fn new(buffer: &mut [u8]) -> DynamicEntriesWriter {
+    fn new(buffer: &mut [u8]) -> DynamicEntriesWriter<'_> {
         DynamicEntriesWriter {
             out: slice_from_all_bytes_mut(buffer),
         }
@@ -1702,7 +2998,7 @@ impl<'out> DynamicEntriesWriter<'out> {
     }
 }
 
-fn write_section_headers(out: &mut [u8], layout: &Layout) {
+fn write_section_headers(out: &mut [u8], header_info: &HeaderInfo, layout: &Layout) {
     let entries: &mut [SectionHeader] = slice_from_all_bytes_mut(out);
     let output_sections = &layout.output_sections;
     let mut entries = entries.iter_mut();
@@ -1718,14 +3014,25 @@ fn write_section_headers(out: &mut [u8], layout: &Layout) {
         let entsize = section_details.element_size;
         let size;
         let alignment;
+        let mut link = 0;
         if section_details.ty == elf::Sht::Null {
-            size = 0;
+            // The null section holds the section count and the index of .shstrtab if they were too
+            // large for the file header.
+            let num_sections = header_info.num_output_sections_with_content;
+            size = if small_section_index(num_sections).is_some() {
+                0
+            } else {
+                u64::from(num_sections)
+            };
+            let shstrndx = shstrtab_index(layout);
+            if small_section_index(shstrndx).is_none() {
+                link = shstrndx;
+            }
             alignment = 0;
         } else {
             size = section_layout.mem_size;
             alignment = section_layout.alignment.value();
         };
-        let mut link = 0;
         if let Some(link_id) = layout.output_sections.link_id(section_id) {
             link = output_sections
                 .output_index_of_section(link_id)
@@ -1736,10 +3043,16 @@ fn write_section_headers(out: &mut [u8], layout: &Layout) {
         entry.sh_name.set(e, name_offset);
         entry.sh_type.set(e, section_details.ty as u32);
         entry.sh_flags.set(e, section_details.section_flags);
-        entry.sh_addr.set(e, section_layout.mem_offset);
+        // Sections in a relocatable output don't have addresses yet.
+        let address = if layout.args().output_kind == OutputKind::Relocatable {
+            0
+        } else {
+            section_layout.mem_offset
+        };
+        entry.sh_addr.set(e, address);
         entry.sh_offset.set(e, section_layout.file_offset as u64);
         entry.sh_size.set(e, size);
-        entry.sh_link.set(e, link.into());
+        entry.sh_link.set(e, link);
         entry.sh_info.set(e, section_id.info(layout));
         entry.sh_addralign.set(e, alignment);
         entry.sh_entsize.set(e, entsize);
@@ -1800,13 +3113,13 @@ fn write_internal_symbols_plt_got_entries(
 impl<'data> DynamicLayout<'data> {
     fn write(&self, mut buffers: OutputSectionPartMap<&mut [u8]>, layout: &Layout) -> Result {
         let mut plt_got_writer = PltGotWriter::new(layout, &mut buffers);
-        let mut relocation_writer = DynamicRelocationWriter::new(true, &mut buffers);
+        let mut relocation_writer = DynamicRelocationWriter::new(layout, &mut buffers);
         let mut strtab = StrTabWriter {
             next_offset: self.dynstr_start_offset,
             out: buffers.dynstr,
         };
 
-        self.write_so_name(buffers.dynamic, &mut strtab)?;
+        self.write_so_name(buffers.dynamic, buffers.gnu_version_r, &mut strtab)?;
 
         let mut dynsym: &mut [SymtabEntry] = slice_from_all_bytes_mut(buffers.dynsym);
         for ((symbol_id, resolution), symbol) in layout
@@ -1814,7 +3127,10 @@ impl<'data> DynamicLayout<'data> {
             .zip(self.object.dynamic_symbols())
         {
             if let Some(res) = resolution {
-                write_dynamic_symtab_entry(&symbol, &mut dynsym, &mut strtab)?;
+                // Objects that we copy are defined by the epilogue rather than imported.
+                if matches!(res.value, ResolutionValue::Dynamic(_)) {
+                    write_dynamic_symtab_entry(&symbol, &mut dynsym, &mut strtab)?;
+                }
 
                 plt_got_writer
                     .process_resolution(res, &mut relocation_writer)
@@ -1827,18 +3143,115 @@ impl<'data> DynamicLayout<'data> {
             }
         }
 
+        for (symbol_id, address) in &self.copy_relocations {
+            relocation_writer
+                .write_copy_relocation(*address, dynamic_symbol_index(layout, *symbol_id)?)?;
+        }
+
         Ok(())
     }
 
-    /// Write dynamic entry to indicate name of shared object to load.
-    fn write_so_name(&self, dynamic: &mut [u8], strtab: &mut StrTabWriter) -> Result {
+    /// Write dynamic entry to indicate name of shared object to load, together with our entry in
+    /// `.gnu.version_r` for the versions that we require from it, if any.
+    fn write_so_name(
+        &self,
+        dynamic: &mut [u8],
+        gnu_version_r: &mut [u8],
+        strtab: &mut StrTabWriter,
+    ) -> Result {
+        if !self.is_needed {
+            return Ok(());
+        }
         let mut dynamic_out = DynamicEntriesWriter::new(dynamic);
         let needed_offset = strtab.write_str(self.lib_name);
         dynamic_out.write(DynamicTag::Needed, needed_offset)?;
+        if !self.needed_versions.is_empty() {
+            write_version_requirement(
+                gnu_version_r,
+                needed_offset,
+                &self.needed_versions,
+                self.has_last_version_requirement,
+                strtab,
+            )?;
+        }
         Ok(())
     }
 }
 
+/// Returns the index in .dynsym of `symbol_id`, which must be one of the symbols that we define
+/// there.
+fn dynamic_symbol_index(layout: &Layout, symbol_id: SymbolId) -> Result<u32> {
+    let Some(FileLayout::Epilogue(epilogue)) = layout.file_layouts.last() else {
+        bail!("Internal error, epilogue must be last");
+    };
+    let index = epilogue
+        .dynamic_symbol_definitions
+        .iter()
+        .position(|sym_def| sym_def.symbol_id == symbol_id)
+        .with_context(|| {
+            format!(
+                "Internal error: {} isn't a dynamic symbol definition",
+                layout.symbol_debug(symbol_id)
+            )
+        })?;
+    Ok(epilogue.gnu_hash_layout.symbol_base + index as u32)
+}
+
+/// Writes a `.gnu.version_r` entry that requires `versions` from the shared object whose name is
+/// at `file_offset` in .dynstr. Each entry links to the one that follows it unless `is_last`.
+fn write_version_requirement(
+    out: &mut [u8],
+    file_offset: u64,
+    versions: &[NeededVersion],
+    is_last: bool,
+    strtab: &mut StrTabWriter,
+) -> Result {
+    let e = LittleEndian;
+    let (verneed, mut rest) = object::from_bytes_mut::<elf::Verneed>(out)
+        .map_err(|_| anyhow!("Insufficient .gnu.version_r allocation"))?;
+    verneed.vn_version.set(e, object::elf::VER_NEED_CURRENT);
+    verneed.vn_cnt.set(
+        e,
+        versions
+            .len()
+            .try_into()
+            .context("Too many versions needed from one shared object")?,
+    );
+    verneed
+        .vn_file
+        .set(e, file_offset.try_into().context(".dynstr is too big")?);
+    verneed.vn_aux.set(e, elf::VERNEED_SIZE as u32);
+    let entry_size = elf::VERNEED_SIZE + versions.len() as u64 * elf::VERNAUX_SIZE;
+    verneed
+        .vn_next
+        .set(e, if is_last { 0 } else { entry_size as u32 });
+    for (i, version) in versions.iter().enumerate() {
+        let (vernaux, after) = object::from_bytes_mut::<elf::Vernaux>(rest)
+            .map_err(|_| anyhow!("Insufficient .gnu.version_r allocation"))?;
+        rest = after;
+        vernaux.vna_hash.set(e, object::elf::hash(version.name));
+        vernaux.vna_flags.set(e, 0);
+        vernaux.vna_other.set(e, version.index);
+        vernaux.vna_name.set(
+            e,
+            strtab
+                .write_str(version.name)
+                .try_into()
+                .context(".dynstr is too big")?,
+        );
+        let is_last_version = i + 1 == versions.len();
+        vernaux.vna_next.set(
+            e,
+            if is_last_version {
+                0
+            } else {
+                elf::VERNAUX_SIZE as u32
+            },
+        );
+    }
+    Ok(())
+}
+
 fn write_dynamic_symtab_entry(
     symbol: &crate::elf::Symbol,
     dynsym: &mut &mut [SymtabEntry],
@@ -2,6 +2,60 @@ pub(crate) use anyhow::Error;
 
 pub type Result<T = (), E = Error> = core::result::Result<T, E>;
 
+/// Errors that callers of the library may want to handle programmatically. These are returned
+/// inside the `anyhow::Error` of our `Result`, possibly with context added, and can be recovered
+/// with `error.downcast_ref::<LinkError>()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LinkError {
+    /// A strong reference to a symbol that isn't defined by any loaded input.
+    UndefinedSymbol {
+        name: String,
+        /// Extra information, e.g. a suggestion from `--error-handling-script`.
+        note: Option<String>,
+    },
+    /// A symbol that is strongly defined by more than one input.
+    DuplicateDefinition {
+        name: String,
+        first_file: String,
+        second_file: String,
+    },
+    /// A relocation with a type that we don't support.
+    UnsupportedRelocation { r_type: u32 },
+    /// An input file that we couldn't parse.
+    MalformedInput { file: String, reason: String },
+}
+
+impl std::fmt::Display for LinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkError::UndefinedSymbol { name, note } => {
+                write!(f, "Undefined strong reference to `{name}`")?;
+                if let Some(note) = note {
+                    write!(f, "\n{note}")?;
+                }
+                Ok(())
+            }
+            LinkError::DuplicateDefinition {
+                name,
+                first_file,
+                second_file,
+            } => write!(
+                f,
+                "Duplicate definition of `{name}` in {first_file} and {second_file}"
+            ),
+            LinkError::UnsupportedRelocation { r_type } => {
+                write!(f, "Unsupported relocation type {r_type}")
+            }
+            LinkError::MalformedInput { file, reason } => {
+                write!(f, "Failed to parse `{file}`: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
 /// Like debug_assert, but bails instead of panicking. Returning an error often allows us to give
 /// more context as to what we were trying to do, e.g. which file / symbol we were processing,
 /// whereas a panic just gives us a function backtrace, which is less useful.
@@ -0,0 +1,56 @@
+//! Support for `--error-handling-script`. When we're about to report certain kinds of errors, we run
+//! the user-supplied script, passing it the kind of error and the symbol or library concerned. The
+//! script can then suggest a fix, e.g. a library that should be added to the link. Whatever it
+//! writes to stdout is included in our error message. This follows the interface provided by lld.
+
+use crate::args::Args;
+use std::process::Command;
+use std::process::Stdio;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ErrorKind {
+    UndefinedSymbol,
+    MissingLibrary,
+}
+
+impl ErrorKind {
+    fn tag(self) -> &'static str {
+        match self {
+            ErrorKind::UndefinedSymbol => "undefined-symbol",
+            ErrorKind::MissingLibrary => "missing-lib",
+        }
+    }
+}
+
+/// Builds an error with the supplied message. If an error handling script was supplied, it's run
+/// and its output is appended to the message.
+pub(crate) fn error(args: &Args, kind: ErrorKind, name: &str, message: String) -> anyhow::Error {
+    match note(args, kind, name) {
+        Some(note) => anyhow::anyhow!("{message}\n{note}"),
+        None => anyhow::anyhow!(message),
+    }
+}
+
+/// Runs the error handling script, if one was supplied, and returns what it wrote to stdout, if
+/// anything. Failure to run the script is returned as the note rather than replacing the original
+/// error, since the original error is what the user needs to see.
+pub(crate) fn note(args: &Args, kind: ErrorKind, name: &str) -> Option<String> {
+    let script = args.error_handling_script.as_ref()?;
+    let output = Command::new(script)
+        .arg(kind.tag())
+        .arg(name)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output();
+    match output {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stdout = stdout.trim_end();
+            (!stdout.is_empty()).then(|| stdout.to_owned())
+        }
+        Err(error) => Some(format!(
+            "Failed to run error handling script `{}`: {error}",
+            script.display()
+        )),
+    }
+}
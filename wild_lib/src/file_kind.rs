@@ -11,13 +11,23 @@ pub(crate) enum FileKind {
     ElfObject,
     ElfDynamic,
     Archive,
+    ThinArchive,
+    /// LLVM bitcode, which only a linker plugin can make sense of.
+    LlvmIr,
     Text,
 }
 
+/// Returns whether `bytes` is LLVM bitcode, either raw or in the wrapper that some tools add.
+pub(crate) fn is_llvm_ir(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"BC\xc0\xde") || bytes.starts_with(&[0xde, 0xc0, 0x17, 0x0b])
+}
+
 impl FileKind {
     pub(crate) fn identify_bytes(bytes: &[u8]) -> Result<FileKind> {
         if bytes.starts_with(b"!<arch>") {
             Ok(FileKind::Archive)
+        } else if bytes.starts_with(b"!<thin>\n") {
+            Ok(FileKind::ThinArchive)
         } else if bytes.starts_with(&[0x7f, b'E', b'L', b'F']) {
             const HEADER_LEN: usize = std::mem::size_of::<elf::FileHeader>();
             if bytes.len() < HEADER_LEN {
@@ -35,6 +45,8 @@ impl FileKind {
                 3 => Ok(FileKind::ElfDynamic),
                 t => bail!("Unsupported ELF kind {t}"),
             }
+        } else if is_llvm_ir(bytes) {
+            Ok(FileKind::LlvmIr)
         } else if bytes.is_ascii() {
             Ok(FileKind::Text)
         } else {
@@ -0,0 +1,538 @@
+//! Support for `--gdb-index`, which builds a `.gdb_index` section so that GDB doesn't need to read
+//! all of our debug info on startup in order to find out what's where. We use version 8 of the
+//! format. The index lists the compilation units (CUs) in `.debug_info`, the address ranges that
+//! each CU covers, which we get from `.debug_aranges`, and a hash table of names, which we get from
+//! `.debug_gnu_pubnames` and `.debug_pubnames` and the corresponding type sections. Objects need to
+//! be compiled with `-ggnu-pubnames` or similar in order for their names to be indexed.
+//!
+//! We only support 32 bit DWARF. Type units are omitted from the index.
+
+use crate::elf::File;
+use crate::elf::Section;
+use crate::error::Result;
+use crate::input_data::FileId;
+use ahash::AHashMap;
+use anyhow::bail;
+use anyhow::Context;
+use object::Object as _;
+use object::ObjectSection as _;
+use object::ObjectSymbol as _;
+use object::SectionIndex;
+
+const VERSION: u32 = 8;
+const HEADER_SIZE: usize = 6 * size_of::<u32>();
+const CU_ENTRY_SIZE: usize = 2 * size_of::<u64>();
+const ADDRESS_ENTRY_SIZE: usize = 2 * size_of::<u64>() + size_of::<u32>();
+const SYMBOL_SLOT_SIZE: usize = 2 * size_of::<u32>();
+
+/// The attributes in a CU vector entry are stored above the CU index.
+const ATTRIBUTES_SHIFT: u32 = 24;
+
+const DW_UT_TYPE: u8 = 0x02;
+const DW_UT_SPLIT_TYPE: u8 = 0x06;
+
+/// A contiguous range of some section within an input object.
+#[derive(Debug, Clone, Copy)]
+struct InputRange {
+    file_id: FileId,
+    section_index: SectionIndex,
+    offset: u64,
+    length: u64,
+}
+
+/// What we need from a single input object in order to build the index.
+pub(crate) struct ObjectDebugInfo<'data> {
+    units: Vec<InputRange>,
+    /// Address ranges covered by our CUs together with the index of the CU within `units`.
+    ranges: Vec<(InputRange, u32)>,
+    /// Names together with the index within `units` of the CU that defines them and their
+    /// attributes.
+    names: Vec<(&'data [u8], u32, u8)>,
+}
+
+impl<'data> ObjectDebugInfo<'data> {
+    /// Reads the debug info of `object`. `is_loaded` should return whether we're outputting the
+    /// section with the supplied index. Address ranges in sections that we're not outputting are
+    /// ignored.
+    pub(crate) fn from_object(
+        object: &File<'data>,
+        file_id: FileId,
+        is_loaded: impl Fn(SectionIndex) -> bool,
+    ) -> Result<Self> {
+        let mut info = ObjectDebugInfo {
+            units: Vec::new(),
+            ranges: Vec::new(),
+            names: Vec::new(),
+        };
+        let sections = || {
+            object
+                .sections()
+                .filter(|section| is_loaded(section.index()))
+        };
+        for section in sections().filter(|s| s.name_bytes().ok() == Some(b".debug_info")) {
+            info.read_units(&section, file_id)
+                .context("Failed to read .debug_info")?;
+        }
+        for section in sections() {
+            let name = section.name_bytes()?;
+            match name {
+                b".debug_gnu_pubnames" | b".debug_gnu_pubtypes" => {
+                    info.read_names(object, &section, true)
+                }
+                b".debug_pubnames" | b".debug_pubtypes" => info.read_names(object, &section, false),
+                b".debug_aranges" => info.read_ranges(object, &section, file_id, &is_loaded),
+                _ => continue,
+            }
+            .with_context(|| format!("Failed to read {}", String::from_utf8_lossy(name)))?;
+        }
+        Ok(info)
+    }
+
+    fn read_units(&mut self, section: &Section<'data, '_>, file_id: FileId) -> Result {
+        let data = section.data()?;
+        let mut offset = 0;
+        while offset < data.len() {
+            let mut reader = Reader::new(data, offset);
+            let length = reader.unit_length()?;
+            let version = reader.u16()?;
+            let is_type_unit =
+                version >= 5 && matches!(reader.u8()?, DW_UT_TYPE | DW_UT_SPLIT_TYPE);
+            let unit_size = size_of::<u32>() as u64 + length;
+            if !is_type_unit {
+                self.units.push(InputRange {
+                    file_id,
+                    section_index: section.index(),
+                    offset: offset as u64,
+                    length: unit_size,
+                });
+            }
+            offset += unit_size as usize;
+        }
+        Ok(())
+    }
+
+    /// Reads the sets of names in a pubnames or pubtypes section. The GNU variants of these
+    /// sections have a byte of flags for each name, which holds the attributes that GDB wants.
+    fn read_names(
+        &mut self,
+        object: &File<'data>,
+        section: &Section<'data, '_>,
+        has_flags: bool,
+    ) -> Result {
+        let data = section.data()?;
+        let relocations = Relocations::new(object, section)?;
+        let mut offset = 0;
+        while offset < data.len() {
+            let mut reader = Reader::new(data, offset);
+            let length = reader.unit_length()?;
+            let end = reader.offset + length as usize;
+            let _version = reader.u16()?;
+            let unit = self.unit_at(&relocations, &mut reader)?;
+            let _unit_size = reader.u32()?;
+            loop {
+                let die_offset = reader.u32()?;
+                if die_offset == 0 {
+                    break;
+                }
+                let flags = if has_flags { reader.u8()? } else { 0 };
+                let name = reader.cstr()?;
+                if let Some(unit) = unit {
+                    self.names.push((name, unit, flags));
+                }
+            }
+            offset = end;
+        }
+        Ok(())
+    }
+
+    fn read_ranges(
+        &mut self,
+        object: &File<'data>,
+        section: &Section<'data, '_>,
+        file_id: FileId,
+        is_loaded: &impl Fn(SectionIndex) -> bool,
+    ) -> Result {
+        let data = section.data()?;
+        let relocations = Relocations::new(object, section)?;
+        let mut offset = 0;
+        while offset < data.len() {
+            let set_start = offset;
+            let mut reader = Reader::new(data, offset);
+            let length = reader.unit_length()?;
+            let end = reader.offset + length as usize;
+            let _version = reader.u16()?;
+            let unit = self.unit_at(&relocations, &mut reader)?;
+            let address_size = reader.u8()?;
+            let segment_selector_size = reader.u8()?;
+            if address_size != 8 || segment_selector_size != 0 {
+                bail!("Unsupported address size {address_size}");
+            }
+            // Tuples are aligned to twice the address size relative to the start of the set.
+            reader.offset = set_start + (reader.offset - set_start).next_multiple_of(16);
+            while reader.offset + 16 <= end {
+                let address_offset = reader.offset as u64;
+                let _address = reader.u64()?;
+                let length = reader.u64()?;
+                let Some(unit) = unit else {
+                    continue;
+                };
+                // Addresses that aren't relocated, e.g. ranges for code that was discarded by the
+                // compiler, don't refer to anything that we're outputting.
+                let Some((section_index, offset)) = relocations.target(address_offset) else {
+                    continue;
+                };
+                if length == 0 || !is_loaded(section_index) {
+                    continue;
+                }
+                self.ranges.push((
+                    InputRange {
+                        file_id,
+                        section_index,
+                        offset,
+                        length,
+                    },
+                    unit,
+                ));
+            }
+            offset = end;
+        }
+        Ok(())
+    }
+
+    /// Reads a reference to a CU in `.debug_info` and returns the index of that CU within `units`,
+    /// if it's one that we know about.
+    fn unit_at(&self, relocations: &Relocations, reader: &mut Reader) -> Result<Option<u32>> {
+        let field_offset = reader.offset as u64;
+        let raw_offset = u64::from(reader.u32()?);
+        let (section_index, offset) = match relocations.target(field_offset) {
+            Some(target) => target,
+            // Without a relocation, the offset must be relative to our only `.debug_info`.
+            None => match self.units.first() {
+                Some(unit) => (unit.section_index, raw_offset),
+                None => return Ok(None),
+            },
+        };
+        Ok(self
+            .units
+            .iter()
+            .position(|unit| unit.section_index == section_index && unit.offset == offset)
+            .map(|index| index as u32))
+    }
+}
+
+/// The relocations of a section, keyed by offset.
+struct Relocations {
+    /// For each relocation, the section and offset within that section that it refers to.
+    targets: AHashMap<u64, (SectionIndex, u64)>,
+}
+
+impl Relocations {
+    fn new(object: &File, section: &Section) -> Result<Self> {
+        let mut targets = AHashMap::new();
+        for (offset, rel) in section.relocations() {
+            let object::RelocationTarget::Symbol(symbol_index) = rel.target() else {
+                continue;
+            };
+            let symbol = object.symbol_by_index(symbol_index)?;
+            if let Some(section_index) = symbol.section_index() {
+                targets.insert(
+                    offset,
+                    (
+                        section_index,
+                        symbol.address().wrapping_add(rel.addend() as u64),
+                    ),
+                );
+            }
+        }
+        Ok(Self { targets })
+    }
+
+    fn target(&self, offset: u64) -> Option<(SectionIndex, u64)> {
+        self.targets.get(&offset).copied()
+    }
+}
+
+struct Reader<'data> {
+    data: &'data [u8],
+    offset: usize,
+}
+
+impl<'data> Reader<'data> {
+    fn new(data: &'data [u8], offset: usize) -> Self {
+        Self { data, offset }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'data [u8]> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + len)
+            .context("Unexpected end of section")?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn cstr(&mut self) -> Result<&'data [u8]> {
+        let rest = self.data.get(self.offset..).unwrap_or_default();
+        let len = memchr::memchr(0, rest).context("Unterminated string")?;
+        let s = &rest[..len];
+        self.offset += len + 1;
+        Ok(s)
+    }
+
+    /// Reads the length field at the start of a unit or set, which is the size of the remainder.
+    fn unit_length(&mut self) -> Result<u64> {
+        let length = self.u32()?;
+        if length >= 0xffff_fff0 {
+            bail!("64 bit DWARF isn't supported");
+        }
+        Ok(u64::from(length))
+    }
+}
+
+/// A `.gdb_index` section. Everything other than the CU list and address area is independent of
+/// where things end up in the output, so is computed up-front.
+pub(crate) struct GdbIndex {
+    units: Vec<InputRange>,
+    ranges: Vec<(InputRange, u32)>,
+    symbol_table: Vec<u8>,
+    constant_pool: Vec<u8>,
+}
+
+impl GdbIndex {
+    pub(crate) fn build<'data>(
+        objects: impl Iterator<Item = ObjectDebugInfo<'data>>,
+    ) -> Result<GdbIndex> {
+        let mut units = Vec::new();
+        let mut ranges = Vec::new();
+        // Names in the order in which we first saw them, together with their CU vectors.
+        let mut names: Vec<(&[u8], Vec<u32>)> = Vec::new();
+        let mut name_indexes: AHashMap<&[u8], usize> = AHashMap::new();
+        for object in objects {
+            let first_unit = u32::try_from(units.len()).context("Too many CUs")?;
+            units.extend(object.units);
+            ranges.extend(
+                object
+                    .ranges
+                    .into_iter()
+                    .map(|(range, unit)| (range, first_unit + unit)),
+            );
+            for (name, unit, flags) in object.names {
+                let index = *name_indexes.entry(name).or_insert_with(|| {
+                    names.push((name, Vec::new()));
+                    names.len() - 1
+                });
+                let entry = (first_unit + unit) | (u32::from(flags) << ATTRIBUTES_SHIFT);
+                let cu_vector = &mut names[index].1;
+                if !cu_vector.contains(&entry) {
+                    cu_vector.push(entry);
+                }
+            }
+        }
+        if units.len() >= 1 << ATTRIBUTES_SHIFT {
+            bail!("Too many CUs for .gdb_index");
+        }
+
+        // The constant pool holds all the CU vectors followed by all the names. Since the names
+        // come second, a name offset is never zero, so an empty slot in the symbol table is
+        // unambiguous.
+        let mut constant_pool = Vec::new();
+        let mut cu_vector_offsets = Vec::with_capacity(names.len());
+        for (_, cu_vector) in &names {
+            cu_vector_offsets.push(constant_pool.len() as u32);
+            constant_pool.extend_from_slice(&(cu_vector.len() as u32).to_le_bytes());
+            for entry in cu_vector {
+                constant_pool.extend_from_slice(&entry.to_le_bytes());
+            }
+        }
+        let mut slots = vec![(0, 0); (names.len() * 4 / 3 + 1).next_power_of_two()];
+        let mask = slots.len() as u32 - 1;
+        for ((name, _), cu_vector_offset) in names.iter().zip(cu_vector_offsets) {
+            let name_offset = constant_pool.len() as u32;
+            constant_pool.extend_from_slice(name);
+            constant_pool.push(0);
+            let hash = name_hash(name);
+            let step = (hash.wrapping_mul(17) & mask) | 1;
+            let mut slot = hash & mask;
+            while slots[slot as usize] != (0, 0) {
+                slot = (slot + step) & mask;
+            }
+            slots[slot as usize] = (name_offset, cu_vector_offset);
+        }
+        let symbol_table = slots
+            .iter()
+            .flat_map(|(name_offset, cu_vector_offset)| {
+                name_offset
+                    .to_le_bytes()
+                    .into_iter()
+                    .chain(cu_vector_offset.to_le_bytes())
+            })
+            .collect();
+        u32::try_from(
+            HEADER_SIZE
+                + units.len() * CU_ENTRY_SIZE
+                + ranges.len() * ADDRESS_ENTRY_SIZE
+                + slots.len() * SYMBOL_SLOT_SIZE,
+        )
+        .context(".gdb_index is too large")?;
+        Ok(GdbIndex {
+            units,
+            ranges,
+            symbol_table,
+            constant_pool,
+        })
+    }
+
+    pub(crate) fn size(&self) -> u64 {
+        (HEADER_SIZE
+            + self.units.len() * CU_ENTRY_SIZE
+            + self.ranges.len() * ADDRESS_ENTRY_SIZE
+            + self.symbol_table.len()
+            + self.constant_pool.len()) as u64
+    }
+
+    /// Writes the index. `section_address` should return the address of the input section with the
+    /// supplied index in the supplied file. For sections that aren't loaded at runtime, like
+    /// `.debug_info`, this is the offset of the input section within its output section.
+    pub(crate) fn write(
+        &self,
+        out: &mut [u8],
+        section_address: impl Fn(FileId, SectionIndex) -> Result<u64>,
+    ) -> Result {
+        if (out.len() as u64) < self.size() {
+            bail!("Insufficient space allocated for .gdb_index");
+        }
+        let cu_list_offset = HEADER_SIZE;
+        let types_cu_list_offset = cu_list_offset + self.units.len() * CU_ENTRY_SIZE;
+        let address_area_offset = types_cu_list_offset;
+        let symbol_table_offset = address_area_offset + self.ranges.len() * ADDRESS_ENTRY_SIZE;
+        let constant_pool_offset = symbol_table_offset + self.symbol_table.len();
+        let mut writer = Writer { out };
+        for value in [
+            VERSION,
+            cu_list_offset as u32,
+            types_cu_list_offset as u32,
+            address_area_offset as u32,
+            symbol_table_offset as u32,
+            constant_pool_offset as u32,
+        ] {
+            writer.bytes(&value.to_le_bytes());
+        }
+        for unit in &self.units {
+            let address = section_address(unit.file_id, unit.section_index)? + unit.offset;
+            writer.bytes(&address.to_le_bytes());
+            writer.bytes(&unit.length.to_le_bytes());
+        }
+        for (range, unit) in &self.ranges {
+            let low = section_address(range.file_id, range.section_index)? + range.offset;
+            writer.bytes(&low.to_le_bytes());
+            writer.bytes(&(low + range.length).to_le_bytes());
+            writer.bytes(&unit.to_le_bytes());
+        }
+        writer.bytes(&self.symbol_table);
+        writer.bytes(&self.constant_pool);
+        Ok(())
+    }
+}
+
+struct Writer<'out> {
+    out: &'out mut [u8],
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, bytes: &[u8]) {
+        crate::slice::slice_take_prefix_mut(&mut self.out, bytes.len()).copy_from_slice(bytes);
+    }
+}
+
+/// The hash function that GDB uses for the symbol table from version 5 of the format onwards.
+fn name_hash(name: &[u8]) -> u32 {
+    name.iter().fold(0u32, |hash, c| {
+        hash.wrapping_mul(67)
+            .wrapping_add(u32::from(c.to_ascii_lowercase()))
+            .wrapping_sub(113)
+    })
+}
+
+#[test]
+fn test_symbol_table() {
+    let names: Vec<Vec<u8>> = (0..100).map(|i| format!("name{i}").into_bytes()).collect();
+    let file_id = FileId::new(1);
+    let unit = |offset| InputRange {
+        file_id,
+        section_index: SectionIndex(1),
+        offset,
+        length: 10,
+    };
+    let objects = [
+        ObjectDebugInfo {
+            units: vec![unit(0)],
+            ranges: vec![],
+            names: names
+                .iter()
+                .map(|name| (name.as_slice(), 0, 0x30))
+                .collect(),
+        },
+        ObjectDebugInfo {
+            units: vec![unit(10)],
+            ranges: vec![(unit(0), 0)],
+            names: vec![(b"name7", 0, 0x90)],
+        },
+    ];
+    let index = GdbIndex::build(objects.into_iter()).unwrap();
+    let mut out = vec![0; index.size() as usize];
+    index.write(&mut out, |_, _| Ok(0x100)).unwrap();
+
+    let word = |offset: usize| u32::from_le_bytes(out[offset..offset + 4].try_into().unwrap());
+    assert_eq!(word(0), VERSION);
+    assert_eq!(word(4), HEADER_SIZE as u32);
+    // The second CU starts 10 bytes into the section.
+    assert_eq!(&out[40..48], &0x10a_u64.to_le_bytes());
+    let symbol_table = word(16) as usize;
+    let constant_pool = word(20) as usize;
+    let num_slots = (constant_pool - symbol_table) / SYMBOL_SLOT_SIZE;
+    assert!(num_slots.is_power_of_two());
+
+    // Look up a name the way GDB does.
+    let lookup = |name: &[u8]| -> Option<Vec<u32>> {
+        let mask = num_slots as u32 - 1;
+        let hash = name_hash(name);
+        let step = (hash.wrapping_mul(17) & mask) | 1;
+        let mut slot = hash & mask;
+        loop {
+            let slot_offset = symbol_table + slot as usize * SYMBOL_SLOT_SIZE;
+            let (name_offset, vector_offset) = (word(slot_offset), word(slot_offset + 4));
+            if (name_offset, vector_offset) == (0, 0) {
+                return None;
+            }
+            let name_start = constant_pool + name_offset as usize;
+            if out[name_start..].starts_with(name) && out[name_start + name.len()] == 0 {
+                let vector = constant_pool + vector_offset as usize;
+                return Some(
+                    (0..word(vector))
+                        .map(|i| word(vector + 4 + i as usize * 4))
+                        .collect(),
+                );
+            }
+            slot = (slot + step) & mask;
+        }
+    };
+    assert_eq!(lookup(b"name42"), Some(vec![0x3000_0000]));
+    assert_eq!(lookup(b"name7"), Some(vec![0x3000_0000, 0x9000_0001]));
+    assert_eq!(lookup(b"missing"), None);
+}
@@ -0,0 +1,168 @@
+//! Handling of `.note.gnu.property` sections. Each input object may have a note describing
+//! properties of its code. For example, which x86 control-flow enforcement features the code is
+//! compatible with. We merge the notes from all our inputs into a single note in the output.
+
+use crate::elf::Section;
+use crate::error::Result;
+use anyhow::bail;
+use anyhow::Context;
+use object::elf::FileHeader64;
+use object::elf::GNU_PROPERTY_X86_FEATURE_1_AND;
+use object::elf::NT_GNU_PROPERTY_TYPE_0;
+use object::read::elf::NoteIterator;
+use object::LittleEndian;
+use object::ObjectSection as _;
+
+const NOTE_NAME: &[u8] = b"GNU\0";
+const NOTE_HEADER_SIZE: usize = 12;
+const PROPERTY_HEADER_SIZE: usize = 8;
+
+/// Properties are padded to this alignment in 64 bit objects.
+const PROPERTY_ALIGNMENT: usize = 8;
+
+/// The properties from `.note.gnu.property` that we know how to merge.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct GnuProperties {
+    /// The value of `GNU_PROPERTY_X86_FEATURE_1_AND`. A feature bit will only be set in the output
+    /// if it's set in every input.
+    pub(crate) x86_feature_1_and: Option<u32>,
+}
+
+impl GnuProperties {
+    /// Reads the properties from an input `.note.gnu.property` section.
+    pub(crate) fn from_section(section: &Section) -> Result<Self> {
+        let e = LittleEndian;
+        let mut properties = GnuProperties::default();
+        let mut notes =
+            NoteIterator::<FileHeader64<LittleEndian>>::new(e, section.align(), section.data()?)?;
+        while let Some(note) = notes.next()? {
+            let Some(mut props) = note.gnu_properties(e) else {
+                continue;
+            };
+            while let Some(prop) = props.next()? {
+                if prop.pr_type() == GNU_PROPERTY_X86_FEATURE_1_AND {
+                    properties.x86_feature_1_and = Some(
+                        prop.data_u32(e)
+                            .context("Invalid GNU_PROPERTY_X86_FEATURE_1_AND")?,
+                    );
+                }
+            }
+        }
+        Ok(properties)
+    }
+
+    /// Merges the properties of all our inputs. A property that's missing from any input is treated
+    /// as having no features, so won't be present in the output. If there are no inputs, then the
+    /// result is empty.
+    pub(crate) fn merge<'a>(inputs: impl Iterator<Item = &'a GnuProperties>) -> GnuProperties {
+        let mut x86_feature_1_and = None;
+        for input in inputs {
+            let features = input.x86_feature_1_and.unwrap_or(0);
+            x86_feature_1_and = Some(x86_feature_1_and.unwrap_or(features) & features);
+        }
+        GnuProperties {
+            x86_feature_1_and: x86_feature_1_and.filter(|features| *features != 0),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.x86_feature_1_and.is_none()
+    }
+
+    /// Returns the number of bytes needed to write our note. Returns 0 if we have no properties, in
+    /// which case we don't emit a note at all.
+    pub(crate) fn note_size(&self) -> u64 {
+        if self.is_empty() {
+            return 0;
+        }
+        (NOTE_HEADER_SIZE + NOTE_NAME.len() + self.desc_size()) as u64
+    }
+
+    fn desc_size(&self) -> usize {
+        self.x86_feature_1_and
+            .map_or(0, |_| property_size(core::mem::size_of::<u32>()))
+    }
+
+    pub(crate) fn write(&self, mut out: &mut [u8]) -> Result {
+        if self.is_empty() {
+            return Ok(());
+        }
+        write_u32(&mut out, NOTE_NAME.len() as u32)?;
+        write_u32(&mut out, self.desc_size() as u32)?;
+        write_u32(&mut out, NT_GNU_PROPERTY_TYPE_0)?;
+        write_bytes(&mut out, NOTE_NAME)?;
+        if let Some(features) = self.x86_feature_1_and {
+            write_u32(&mut out, GNU_PROPERTY_X86_FEATURE_1_AND)?;
+            write_u32(&mut out, core::mem::size_of::<u32>() as u32)?;
+            write_u32(&mut out, features)?;
+            write_bytes(&mut out, &[0; 4])?;
+        }
+        Ok(())
+    }
+}
+
+fn property_size(data_size: usize) -> usize {
+    (PROPERTY_HEADER_SIZE + data_size).next_multiple_of(PROPERTY_ALIGNMENT)
+}
+
+fn write_u32(out: &mut &mut [u8], value: u32) -> Result {
+    write_bytes(out, &value.to_le_bytes())
+}
+
+fn write_bytes(out: &mut &mut [u8], bytes: &[u8]) -> Result {
+    if out.len() < bytes.len() {
+        bail!("Insufficient space allocated for .note.gnu.property");
+    }
+    crate::slice::slice_take_prefix_mut(out, bytes.len()).copy_from_slice(bytes);
+    Ok(())
+}
+
+#[test]
+fn test_merge_no_inputs() {
+    let merged = GnuProperties::merge([].iter());
+    assert!(merged.is_empty());
+    assert_eq!(merged.note_size(), 0);
+}
+
+#[test]
+fn test_merge_no_properties() {
+    let inputs = [GnuProperties::default(), GnuProperties::default()];
+    let merged = GnuProperties::merge(inputs.iter());
+    assert!(merged.is_empty());
+    assert_eq!(merged.note_size(), 0);
+}
+
+#[test]
+fn test_merge_mixed() {
+    let inputs = [
+        GnuProperties {
+            x86_feature_1_and: Some(3),
+        },
+        GnuProperties::default(),
+    ];
+    assert!(GnuProperties::merge(inputs.iter()).is_empty());
+}
+
+#[test]
+fn test_merge_common_features() {
+    let inputs = [
+        GnuProperties {
+            x86_feature_1_and: Some(3),
+        },
+        GnuProperties {
+            x86_feature_1_and: Some(1),
+        },
+    ];
+    let merged = GnuProperties::merge(inputs.iter());
+    assert_eq!(merged.x86_feature_1_and, Some(1));
+
+    let mut out = vec![0; merged.note_size() as usize];
+    merged.write(&mut out).unwrap();
+    assert_eq!(
+        out,
+        [
+            4, 0, 0, 0, 16, 0, 0, 0, 5, 0, 0, 0, b'G', b'N', b'U', 0, 2, 0, 0, 0xc0, 4, 0, 0, 0, 1,
+            0, 0, 0, 0, 0, 0, 0,
+        ]
+    );
+}
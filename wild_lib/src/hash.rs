@@ -38,6 +38,36 @@ pub(crate) fn hash_bytes(bytes: &[u8]) -> u64 {
     hasher.finish()
 }
 
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x100_0000_01b3;
+
+/// A 64 bit FNV-1a hasher. Unlike `AHasher`, whose output may change between versions of ahash,
+/// this always gives the same hash for the same bytes, so it's suitable for hashes that we save to
+/// disk and compare against in a later run.
+pub(crate) struct FnvHasher {
+    hash: u64,
+}
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        Self {
+            hash: FNV_OFFSET_BASIS,
+        }
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash = (self.hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Copy)]
 pub(crate) struct PreHashed<T> {
     value: T,
@@ -48,6 +78,10 @@ impl<T> PreHashed<T> {
     pub(crate) fn new(value: T, hash: u64) -> Self {
         Self { value, hash }
     }
+
+    pub(crate) fn hash(&self) -> u64 {
+        self.hash
+    }
 }
 
 impl<T> std::hash::Hash for PreHashed<T> {
@@ -63,3 +97,15 @@ impl<T> Deref for PreHashed<T> {
         &self.value
     }
 }
+
+#[test]
+fn test_fnv_hasher() {
+    let hash = |bytes: &[u8]| {
+        let mut hasher = FnvHasher::default();
+        hasher.write(bytes);
+        hasher.finish()
+    };
+    assert_eq!(hash(b""), 0xcbf29ce484222325);
+    assert_eq!(hash(b"a"), 0xaf63dc4c8601ec8c);
+    assert_eq!(hash(b"foobar"), 0x85944171f73967e8);
+}
@@ -0,0 +1,264 @@
+//! Identical code folding (`--icf`). Finds executable input sections that have the same contents
+//! and whose relocations refer to the same things, so that all but one copy of each can be
+//! discarded. Relocations may refer to other sections that are themselves candidates for folding,
+//! so we start by assuming that all candidates are identical, then repeatedly split groups based on
+//! their contents and what their relocations refer to until no more splits happen.
+
+use crate::alignment::Alignment;
+use crate::error::Result;
+use crate::input_data::FileId;
+use crate::output_section_id::OutputSectionId;
+use crate::symbol_db::SymbolId;
+use ahash::AHashMap;
+use anyhow::Context;
+use object::ObjectSection as _;
+
+/// Identifies an input section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct InputSectionId {
+    pub(crate) file_id: FileId,
+    pub(crate) index: object::SectionIndex,
+}
+
+/// A section that has been folded into an identical section.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FoldedSection {
+    pub(crate) output_section_id: OutputSectionId,
+    /// The section that we were folded into.
+    pub(crate) survivor: InputSectionId,
+}
+
+/// A section that we might be able to fold.
+pub(crate) struct Candidate<'data> {
+    pub(crate) id: InputSectionId,
+    pub(crate) data: &'data [u8],
+    pub(crate) alignment: Alignment,
+    pub(crate) relocations: Vec<Relocation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Relocation {
+    pub(crate) offset: u64,
+    pub(crate) r_type: u32,
+    pub(crate) addend: i64,
+    pub(crate) target: Target,
+}
+
+/// What a relocation refers to, after following symbols to their definitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Target {
+    /// An offset within an input section.
+    Section(InputSectionId, u64),
+    /// A symbol that isn't defined in an input section, e.g. because it's undefined, absolute or
+    /// defined by a shared object.
+    Symbol(SymbolId),
+    Absolute,
+}
+
+/// Like `Target`, but with sections that are candidates for folding replaced by their current
+/// group. Two candidates with the same contents whose relocations refer to the same groups are put
+/// in the same group.
+#[derive(PartialEq, Eq, Hash)]
+enum GroupTarget {
+    Group(u32, u64),
+    Other(Target),
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct GroupKey<'data> {
+    previous_group: u32,
+    data: &'data [u8],
+    alignment: Alignment,
+    relocations: Vec<(u64, u32, i64, GroupTarget)>,
+}
+
+/// Returns, for each candidate, the index of the candidate that it should be folded into, or None
+/// if it should be kept. The first candidate in each group of identical candidates is the one that
+/// we keep.
+pub(crate) fn find_identical(candidates: &[Candidate]) -> Vec<Option<usize>> {
+    let index_by_id: AHashMap<InputSectionId, usize> = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, candidate)| (candidate.id, i))
+        .collect();
+    let mut groups = vec![0; candidates.len()];
+    let mut num_groups = 0;
+    loop {
+        let mut group_ids: AHashMap<GroupKey, u32> = AHashMap::new();
+        let new_groups: Vec<u32> = candidates
+            .iter()
+            .zip(&groups)
+            .map(|(candidate, previous_group)| {
+                let key = GroupKey {
+                    previous_group: *previous_group,
+                    data: candidate.data,
+                    alignment: candidate.alignment,
+                    relocations: candidate
+                        .relocations
+                        .iter()
+                        .map(|rel| {
+                            let target = match rel.target {
+                                Target::Section(id, offset) => match index_by_id.get(&id) {
+                                    Some(index) => GroupTarget::Group(groups[*index], offset),
+                                    None => GroupTarget::Other(rel.target),
+                                },
+                                other => GroupTarget::Other(other),
+                            };
+                            (rel.offset, rel.r_type, rel.addend, target)
+                        })
+                        .collect(),
+                };
+                let next_id = group_ids.len() as u32;
+                *group_ids.entry(key).or_insert(next_id)
+            })
+            .collect();
+        groups = new_groups;
+        // Groups only ever get split, so if we have the same number as last time, then nothing
+        // changed and we're done.
+        if group_ids.len() == num_groups {
+            break;
+        }
+        num_groups = group_ids.len();
+    }
+    let mut first_in_group: AHashMap<u32, usize> = AHashMap::new();
+    groups
+        .iter()
+        .enumerate()
+        .map(|(i, group)| {
+            let first = *first_in_group.entry(*group).or_insert(i);
+            (first != i).then_some(first)
+        })
+        .collect()
+}
+
+/// Returns whether `section` is an `.llvm_addrsig` section. These are only read by us, so are never
+/// copied to the output. They refer to symbols by index, so would be wrong after a relocatable link
+/// anyway.
+pub(crate) fn is_addrsig_section(section: &crate::elf::Section) -> bool {
+    section.kind() == object::SectionKind::Elf(crate::elf::Sht::LlvmAddrsig as u32)
+}
+
+/// Parses the contents of an `.llvm_addrsig` section, which is a list of ULEB128-encoded symbol
+/// indexes.
+pub(crate) fn parse_addrsig(data: &[u8]) -> Result<Vec<object::SymbolIndex>> {
+    let mut bytes = object::read::Bytes(data);
+    let mut indexes = Vec::new();
+    while !bytes.is_empty() {
+        let index = bytes
+            .read_uleb128()
+            .ok()
+            .context("Invalid .llvm_addrsig section")?;
+        indexes.push(object::SymbolIndex(
+            usize::try_from(index).context("Invalid symbol index in .llvm_addrsig")?,
+        ));
+    }
+    Ok(indexes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(
+        index: usize,
+        data: &'static [u8],
+        relocations: Vec<Relocation>,
+    ) -> Candidate<'static> {
+        Candidate {
+            id: section_id(index),
+            data,
+            alignment: Alignment::new(16).unwrap(),
+            relocations,
+        }
+    }
+
+    fn section_id(index: usize) -> InputSectionId {
+        InputSectionId {
+            file_id: FileId::new(1),
+            index: object::SectionIndex(index),
+        }
+    }
+
+    fn call(target: Target) -> Relocation {
+        Relocation {
+            offset: 1,
+            r_type: object::elf::R_X86_64_PLT32,
+            addend: -4,
+            target,
+        }
+    }
+
+    #[test]
+    fn test_identical_data() {
+        let candidates = [
+            candidate(1, b"\xc3", vec![]),
+            candidate(2, b"\x90\xc3", vec![]),
+            candidate(3, b"\xc3", vec![]),
+        ];
+        assert_eq!(find_identical(&candidates), [None, None, Some(0)]);
+    }
+
+    #[test]
+    fn test_relocations_to_foldable_sections() {
+        // Sections 1 and 2 are identical, so 3 and 4, which call them, are too. 5 calls something
+        // else, so is kept.
+        let candidates = [
+            candidate(1, b"\xc3", vec![]),
+            candidate(2, b"\xc3", vec![]),
+            candidate(
+                3,
+                b"\xe8\0\0\0\0",
+                vec![call(Target::Section(section_id(1), 0))],
+            ),
+            candidate(
+                4,
+                b"\xe8\0\0\0\0",
+                vec![call(Target::Section(section_id(2), 0))],
+            ),
+            candidate(
+                5,
+                b"\xe8\0\0\0\0",
+                vec![call(Target::Symbol(SymbolId::undefined()))],
+            ),
+        ];
+        assert_eq!(
+            find_identical(&candidates),
+            [None, Some(0), None, Some(2), None]
+        );
+    }
+
+    #[test]
+    fn test_recursive() {
+        let candidates = [
+            candidate(
+                1,
+                b"\xe8\0\0\0\0",
+                vec![call(Target::Section(section_id(1), 0))],
+            ),
+            candidate(
+                2,
+                b"\xe8\0\0\0\0",
+                vec![call(Target::Section(section_id(2), 0))],
+            ),
+            candidate(
+                3,
+                b"\xe8\0\0\0\0",
+                vec![call(Target::Section(section_id(1), 0))],
+            ),
+        ];
+        assert_eq!(find_identical(&candidates), [None, Some(0), Some(0)]);
+    }
+
+    #[test]
+    fn test_parse_addrsig() {
+        assert_eq!(
+            parse_addrsig(&[1, 0x80, 0x01, 5]).unwrap(),
+            [
+                object::SymbolIndex(1),
+                object::SymbolIndex(128),
+                object::SymbolIndex(5)
+            ]
+        );
+        assert!(parse_addrsig(&[0x80]).is_err());
+    }
+}
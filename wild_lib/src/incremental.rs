@@ -0,0 +1,578 @@
+//! Support for `--incremental`. After an incremental link, we save a summary of the output's layout
+//! alongside it. The next incremental link to the same output still reads and lays out all of its
+//! inputs, but if every file ends up occupying exactly the same parts of the output as last time,
+//! then rather than writing a whole new output, we update a copy of the existing one and rename it
+//! into place. Only the objects whose contents changed, or that refer to something that moved, are
+//! rewritten, together with the data that the linker generates, such as headers. To make this
+//! possible when an object grows, each object's contribution to .text, .rodata, .data and .bss is
+//! padded, which gives it room to grow. An object's padding is zeroed when it's rewritten, so the
+//! result is the same as a full link would produce. Anything that changes the layout, e.g. an
+//! object outgrowing its padding, a different set of inputs or different arguments, falls back to
+//! a full link.
+//!
+//! The hashes that we save are computed with `FnvHasher`, since they need to be the same when we
+//! compute them again in a later link.
+
+use crate::alignment::Alignment;
+use crate::alignment::NUM_ALIGNMENTS;
+use crate::args::Args;
+use crate::args::Icf;
+use crate::args::OutputKind;
+use crate::elf_writer::Output;
+use crate::error::Result;
+use crate::hash::FnvHasher;
+use crate::input_data::InputRef;
+use crate::layout::FileLayout;
+use crate::layout::Layout;
+use crate::layout::ObjectLayout;
+use crate::object_cache::Reader;
+use crate::output_section_id;
+use crate::output_section_id::OutputSectionId;
+use crate::output_section_part_map::OutputSectionPartMap;
+use ahash::AHashMap;
+use anyhow::bail;
+use anyhow::Context;
+use rayon::iter::IntoParallelRefIterator;
+use rayon::iter::ParallelIterator;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::os::unix::ffi::OsStrExt as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+/// Identifies the format of the state file. Change this whenever the format changes.
+const MAGIC: &[u8; 8] = b"WILDIN02";
+
+/// The sections in which we pad each object's contribution. Other sections are either generated
+/// by us, or might be treated as an array, e.g. `.init_array` or a custom section with
+/// `__start_`/`__stop_` symbols, where padding would add bogus entries.
+const PADDED_SECTIONS: [OutputSectionId; 4] = [
+    output_section_id::TEXT,
+    output_section_id::RODATA,
+    output_section_id::DATA,
+    output_section_id::BSS,
+];
+
+/// The least padding that we add to a non-empty part of a padded section.
+const MIN_PADDING: u64 = 64;
+
+pub(crate) struct Incremental {
+    state_path: PathBuf,
+
+    /// Identifies the linker and the arguments that it was run with. We only use state saved by a
+    /// link with the same value.
+    compatibility: u64,
+
+    previous: Option<LinkState>,
+
+    /// The index in `previous.files` of each object from the previous link.
+    previous_objects: AHashMap<ObjectKey, usize>,
+}
+
+/// What we save about a link.
+struct LinkState {
+    compatibility: u64,
+
+    /// The size and modification time of the output after we wrote it. If these no longer match,
+    /// then something else has written the output since.
+    output_size: u64,
+    output_modified: SystemTime,
+
+    /// A hash of everything about the layout that isn't specific to a single file.
+    global: u64,
+
+    /// Indexed by file ID.
+    files: Vec<FileState>,
+}
+
+struct FileState {
+    /// A hash of the kind of file and how much space it occupies in each part of the output. Since
+    /// files are laid out one after the other, if all files have the same placement and the
+    /// sections start at the same addresses, then each file is at the same addresses.
+    placement: u64,
+
+    object: Option<ObjectState>,
+}
+
+struct ObjectState {
+    key: ObjectKey,
+
+    /// The modification time and size of the file containing the object. If these haven't
+    /// changed, then we assume that the object's contents haven't either.
+    modified: Option<SystemTime>,
+    file_size: u64,
+
+    content_hash: u64,
+
+    /// A hash of the object's contents and of everything that we look up when writing it, such as
+    /// the addresses of the symbols that it refers to. If this hasn't changed, then neither have
+    /// the bytes that we'd write for it.
+    fingerprint: u64,
+
+    /// The space that we reserved in each padded section, as an index into `PADDED_SECTIONS`, the
+    /// alignment of the part of the section and its size. Only non-empty parts are included.
+    reserved: Vec<(u8, Alignment, u64)>,
+}
+
+#[derive(PartialEq, Eq, Hash, Clone)]
+struct ObjectKey {
+    path: PathBuf,
+    /// The name of the archive entry, if any.
+    entry: Vec<u8>,
+    /// The offset of the object within the file, or `u64::MAX` if it isn't stored in the file,
+    /// e.g. because it's the member of a thin archive.
+    offset: u64,
+}
+
+impl Incremental {
+    /// Loads the state saved by the previous link to our output. A missing, corrupt or
+    /// incompatible state just means that we'll do a full link.
+    pub(crate) fn load(args: &Args, arguments: &[String]) -> Incremental {
+        let state_path = state_path(&args.output);
+        let compatibility = compatibility(arguments);
+        let previous = std::fs::read(&state_path)
+            .ok()
+            .and_then(|bytes| read_state(&bytes).ok())
+            .filter(|state| state.compatibility == compatibility);
+        let previous_objects = previous
+            .iter()
+            .flat_map(|state| state.files.iter().enumerate())
+            .filter_map(|(index, file)| Some((file.object.as_ref()?.key.clone(), index)))
+            .collect();
+        Incremental {
+            state_path,
+            compatibility,
+            previous,
+            previous_objects,
+        }
+    }
+
+    fn previous_object(&self, key: &ObjectKey) -> Option<&ObjectState> {
+        let index = *self.previous_objects.get(key)?;
+        self.previous.as_ref()?.files[index].object.as_ref()
+    }
+
+    /// Pads the sizes of the object with contents `data` from `input`. If the object still fits in
+    /// the space that it had last time, then it gets exactly that space again.
+    pub(crate) fn reserve(
+        &self,
+        input: &InputRef,
+        data: &[u8],
+        sizes: &mut OutputSectionPartMap<u64>,
+    ) {
+        let previous = self.previous_object(&ObjectKey::new(input, data));
+        for (section_index, &section_id) in PADDED_SECTIONS.iter().enumerate() {
+            for exponent in 0..NUM_ALIGNMENTS {
+                let alignment = Alignment {
+                    exponent: exponent as u16,
+                };
+                let previous_size = previous.and_then(|previous| {
+                    previous
+                        .reserved
+                        .iter()
+                        .find(|(i, a, _)| usize::from(*i) == section_index && *a == alignment)
+                        .map(|(_, _, size)| *size)
+                });
+                let size = sizes.regular_mut(section_id, alignment);
+                *size = reserved_size(*size, previous_size, alignment);
+            }
+        }
+    }
+
+    /// Writes `layout` to our output, updating the output of the previous link if possible, then
+    /// saves the state needed to do the same next time.
+    #[tracing::instrument(skip_all, name = "Write incremental output")]
+    pub(crate) fn write(&self, layout: &Layout, output: &mut Output) -> Result {
+        let mut state = self.compute_state(layout);
+        let patched = match self.files_to_rewrite(layout, &state) {
+            Some(rewrite) => output.patch(layout, &rewrite)?,
+            None => false,
+        };
+        if !patched {
+            output.write(layout)?;
+        }
+        let output_path = &layout.args().output;
+        let metadata = std::fs::metadata(output_path)
+            .with_context(|| format!("Failed to read metadata for `{}`", output_path.display()))?;
+        state.output_size = metadata.len();
+        state.output_modified = metadata.modified()?;
+        // Write to a temporary file then rename it into place, so that if we're interrupted, we
+        // don't leave behind a partially written state.
+        let temp_path = self
+            .state_path
+            .with_extension(format!("tmp-{}", std::process::id()));
+        std::fs::write(&temp_path, write_state(&state)?)
+            .with_context(|| format!("Failed to write `{}`", temp_path.display()))?;
+        std::fs::rename(&temp_path, &self.state_path)
+            .with_context(|| format!("Failed to write `{}`", self.state_path.display()))?;
+        Ok(())
+    }
+
+    /// Returns which files need to be written if we update the existing output, indexed by file
+    /// ID, or None if we need to write a new output.
+    fn files_to_rewrite(&self, layout: &Layout, state: &LinkState) -> Option<Vec<bool>> {
+        let previous = self.previous.as_ref()?;
+        let args = layout.args();
+        // These options all involve processing that spans multiple files, which we don't yet know
+        // how to redo for just the files that changed.
+        if args.output_kind == OutputKind::Relocatable
+            || args.emit_relocs
+            || args.icf != Icf::None
+            || args.gdb_index
+            || args.uses_relr()
+            || !args.compress_sections.is_empty()
+        {
+            return None;
+        }
+        if state.global != previous.global || state.files.len() != previous.files.len() {
+            return None;
+        }
+        let metadata = std::fs::metadata(&args.output).ok()?;
+        if metadata.len() != previous.output_size
+            || metadata.modified().ok()? != previous.output_modified
+        {
+            return None;
+        }
+        state
+            .files
+            .iter()
+            .zip(&previous.files)
+            .map(|(file, previous_file)| {
+                if file.placement != previous_file.placement {
+                    return None;
+                }
+                // Files other than objects are all generated by us, so are cheap to write.
+                Some(match (&file.object, &previous_file.object) {
+                    (Some(object), Some(previous_object)) => {
+                        object.fingerprint != previous_object.fingerprint
+                    }
+                    _ => true,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(skip_all, name = "Compute incremental state")]
+    fn compute_state(&self, layout: &Layout) -> LinkState {
+        let files = layout
+            .file_layouts
+            .par_iter()
+            .map(|file| self.file_state(file, layout))
+            .collect();
+        LinkState {
+            compatibility: self.compatibility,
+            output_size: 0,
+            output_modified: SystemTime::UNIX_EPOCH,
+            global: global_fingerprint(layout),
+            files,
+        }
+    }
+
+    fn file_state(&self, file: &FileLayout, layout: &Layout) -> FileState {
+        let mut hasher = FnvHasher::default();
+        let object = match file {
+            FileLayout::Internal(internal) => {
+                0_u8.hash(&mut hasher);
+                internal.mem_sizes.hash(&mut hasher);
+                None
+            }
+            FileLayout::Object(object) => {
+                1_u8.hash(&mut hasher);
+                object.mem_sizes.hash(&mut hasher);
+                Some(self.object_state(object, layout))
+            }
+            FileLayout::Dynamic(_) => {
+                2_u8.hash(&mut hasher);
+                file.file_sizes().hash(&mut hasher);
+                None
+            }
+            FileLayout::Epilogue(epilogue) => {
+                3_u8.hash(&mut hasher);
+                epilogue.mem_sizes.hash(&mut hasher);
+                None
+            }
+            FileLayout::NotLoaded => {
+                4_u8.hash(&mut hasher);
+                None
+            }
+        };
+        FileState {
+            placement: hasher.finish(),
+            object,
+        }
+    }
+
+    fn object_state(&self, object: &ObjectLayout, layout: &Layout) -> ObjectState {
+        let data = object.object.data();
+        let key = ObjectKey::new(&object.input, data);
+        let file = object.input.file;
+        let file_size = file.data().len() as u64;
+        let content_hash = self
+            .previous_object(&key)
+            .filter(|previous| {
+                previous.modified.is_some()
+                    && previous.modified == file.modified
+                    && previous.file_size == file_size
+            })
+            .map_or_else(
+                || {
+                    let mut hasher = FnvHasher::default();
+                    hasher.write(data);
+                    hasher.finish()
+                },
+                |previous| previous.content_hash,
+            );
+
+        let mut hasher = FnvHasher::default();
+        key.hash(&mut hasher);
+        content_hash.hash(&mut hasher);
+        object.section_resolutions.hash(&mut hasher);
+        object.eh_frame_start_address.hash(&mut hasher);
+        for cie in &object.cies {
+            cie.address.hash(&mut hasher);
+            cie.duplicate_of.is_some().hash(&mut hasher);
+        }
+        // This covers both the addresses of our own symbols and of the symbols that we refer to.
+        for (symbol_id, _) in
+            layout.resolutions_in_range(object.start_symbol_id, object.num_symbols)
+        {
+            layout.symbol_resolution(symbol_id).hash(&mut hasher);
+        }
+
+        let mut reserved = Vec::new();
+        for (section_index, &section_id) in PADDED_SECTIONS.iter().enumerate() {
+            for exponent in 0..NUM_ALIGNMENTS {
+                let alignment = Alignment {
+                    exponent: exponent as u16,
+                };
+                let size = *object.mem_sizes.regular(section_id, alignment);
+                if size > 0 {
+                    reserved.push((section_index as u8, alignment, size));
+                }
+            }
+        }
+
+        ObjectState {
+            key,
+            modified: file.modified,
+            file_size,
+            content_hash,
+            fingerprint: hasher.finish(),
+            reserved,
+        }
+    }
+}
+
+impl ObjectKey {
+    fn new(input: &InputRef, data: &[u8]) -> ObjectKey {
+        let file_data = input.file.data();
+        let offset = (data.as_ptr() as usize)
+            .checked_sub(file_data.as_ptr() as usize)
+            .filter(|offset| offset + data.len() <= file_data.len())
+            .map_or(u64::MAX, |offset| offset as u64);
+        ObjectKey {
+            path: input.file.filename.clone(),
+            entry: input
+                .entry_filename
+                .as_ref()
+                .map(|entry| entry.as_slice().to_owned())
+                .unwrap_or_default(),
+            offset,
+        }
+    }
+}
+
+/// Returns how much space to reserve for an object that needs `size` bytes in a part of a section
+/// with the specified alignment, given that it had `previous_size` bytes last time.
+fn reserved_size(size: u64, previous_size: Option<u64>, alignment: Alignment) -> u64 {
+    match previous_size {
+        Some(previous_size) if size <= previous_size => previous_size,
+        _ if size == 0 => 0,
+        _ => alignment.align_up(size + (size / 4).max(MIN_PADDING)),
+    }
+}
+
+fn state_path(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(".incremental");
+    PathBuf::from(path)
+}
+
+/// Returns a value that changes if our arguments or the linker itself changes. Options for the
+/// linker plugin are ignored, since GCC passes a different temporary filename each time and they
+/// can only affect the objects that the plugin produces, which we compare like any other objects.
+fn compatibility(arguments: &[String]) -> u64 {
+    let mut hasher = FnvHasher::default();
+    MAGIC.hash(&mut hasher);
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    std::env::current_exe()
+        .and_then(std::fs::metadata)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .hash(&mut hasher);
+    let mut arguments = arguments.iter();
+    while let Some(arg) = arguments.next() {
+        let name = arg.trim_start_matches('-');
+        if name == "plugin-opt" {
+            arguments.next();
+        } else if !name.starts_with("plugin-opt=") {
+            arg.hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+fn global_fingerprint(layout: &Layout) -> u64 {
+    let mut hasher = FnvHasher::default();
+    layout.section_part_layouts.hash(&mut hasher);
+    layout.segment_layouts.segments.hash(&mut hasher);
+    // Merged strings are shared between all objects, so if they change, any object might refer
+    // to a string that has moved.
+    layout.internal().merged_strings.for_each(|_, section| {
+        section.len.hash(&mut hasher);
+        for string in &section.strings {
+            string.bytes.hash(&mut hasher);
+            string.alignment.hash(&mut hasher);
+        }
+    });
+    layout.has_text_relocations.hash(&mut hasher);
+    layout.has_init.hash(&mut hasher);
+    layout.has_fini.hash(&mut hasher);
+    layout.uses_static_tls.hash(&mut hasher);
+    layout.exports_unique_symbols.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn write_state(state: &LinkState) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&state.compatibility.to_le_bytes());
+    out.extend_from_slice(&state.output_size.to_le_bytes());
+    write_time(&mut out, Some(state.output_modified))?;
+    out.extend_from_slice(&state.global.to_le_bytes());
+    out.extend_from_slice(&(state.files.len() as u64).to_le_bytes());
+    for file in &state.files {
+        out.extend_from_slice(&file.placement.to_le_bytes());
+        let Some(object) = &file.object else {
+            out.push(0);
+            continue;
+        };
+        out.push(1);
+        let path = object.key.path.as_os_str().as_bytes();
+        out.extend_from_slice(&(path.len() as u64).to_le_bytes());
+        out.extend_from_slice(path);
+        out.extend_from_slice(&(object.key.entry.len() as u64).to_le_bytes());
+        out.extend_from_slice(&object.key.entry);
+        out.extend_from_slice(&object.key.offset.to_le_bytes());
+        write_time(&mut out, object.modified)?;
+        out.extend_from_slice(&object.file_size.to_le_bytes());
+        out.extend_from_slice(&object.content_hash.to_le_bytes());
+        out.extend_from_slice(&object.fingerprint.to_le_bytes());
+        out.extend_from_slice(&(object.reserved.len() as u64).to_le_bytes());
+        for (section_index, alignment, size) in &object.reserved {
+            out.push(*section_index);
+            out.push(alignment.exponent as u8);
+            out.extend_from_slice(&size.to_le_bytes());
+        }
+    }
+    Ok(out)
+}
+
+fn read_state(bytes: &[u8]) -> Result<LinkState> {
+    let mut reader = Reader::new(bytes);
+    if reader.take(MAGIC.len())? != MAGIC {
+        bail!("Incremental state is from a different version");
+    }
+    let compatibility = reader.u64()?;
+    let output_size = reader.u64()?;
+    let output_modified = read_time(&mut reader)?.context("Missing output modification time")?;
+    let global = reader.u64()?;
+    let num_files = reader.u64()?;
+    let mut files = Vec::new();
+    for _ in 0..num_files {
+        let placement = reader.u64()?;
+        let object = match reader.u8()? {
+            0 => None,
+            1 => {
+                let path_len = reader.u64()? as usize;
+                let path = PathBuf::from(std::ffi::OsStr::from_bytes(reader.take(path_len)?));
+                let entry_len = reader.u64()? as usize;
+                let key = ObjectKey {
+                    path,
+                    entry: reader.take(entry_len)?.to_owned(),
+                    offset: reader.u64()?,
+                };
+                let modified = read_time(&mut reader)?;
+                let file_size = reader.u64()?;
+                let content_hash = reader.u64()?;
+                let fingerprint = reader.u64()?;
+                let num_reserved = reader.u64()?;
+                let mut reserved = Vec::new();
+                for _ in 0..num_reserved {
+                    let section_index = reader.u8()?;
+                    let exponent = reader.u8()?;
+                    if usize::from(section_index) >= PADDED_SECTIONS.len()
+                        || usize::from(exponent) >= NUM_ALIGNMENTS
+                    {
+                        bail!("Invalid reservation in incremental state");
+                    }
+                    let alignment = Alignment {
+                        exponent: u16::from(exponent),
+                    };
+                    reserved.push((section_index, alignment, reader.u64()?));
+                }
+                Some(ObjectState {
+                    key,
+                    modified,
+                    file_size,
+                    content_hash,
+                    fingerprint,
+                    reserved,
+                })
+            }
+            other => bail!("Invalid file kind {other} in incremental state"),
+        };
+        files.push(FileState { placement, object });
+    }
+    Ok(LinkState {
+        compatibility,
+        output_size,
+        output_modified,
+        global,
+        files,
+    })
+}
+
+fn write_time(out: &mut Vec<u8>, time: Option<SystemTime>) -> Result {
+    let duration = time
+        .map(|time| time.duration_since(SystemTime::UNIX_EPOCH))
+        .transpose()?;
+    out.extend_from_slice(&duration.map_or(u64::MAX, |d| d.as_secs()).to_le_bytes());
+    out.extend_from_slice(&duration.map_or(0, |d| d.subsec_nanos()).to_le_bytes());
+    Ok(())
+}
+
+fn read_time(reader: &mut Reader) -> Result<Option<SystemTime>> {
+    let secs = reader.u64()?;
+    let nanos = reader.u32()?;
+    Ok((secs != u64::MAX).then(|| SystemTime::UNIX_EPOCH + Duration::new(secs, nanos)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserved_size() {
+        let align_16 = Alignment { exponent: 4 };
+        assert_eq!(reserved_size(0, None, align_16), 0);
+        assert_eq!(reserved_size(16, None, align_16), 80);
+        assert_eq!(reserved_size(1000, None, align_16), 1264);
+        // If we still fit, we get the same space as before, even if we shrank.
+        assert_eq!(reserved_size(100, Some(1264), align_16), 1264);
+        assert_eq!(reserved_size(0, Some(80), align_16), 80);
+        assert_eq!(reserved_size(2000, Some(1264), align_16), 2512);
+    }
+}
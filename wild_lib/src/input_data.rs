@@ -1,29 +1,43 @@
 //! Code for figuring out what input files we need to read then mapping them into memory.
 
 use crate::archive;
+use crate::archive::ArchiveEntry;
+use crate::archive::ArchiveIterator;
 use crate::args::Args;
 use crate::args::Input;
 use crate::args::InputSpec;
 use crate::args::Modifiers;
+use crate::error::LinkError;
 use crate::error::Result;
+use crate::error_handling_script;
+use crate::error_handling_script::ErrorKind;
 use crate::file_kind::FileKind;
+use crate::linker_script::DynamicList;
 use crate::linker_script::VersionScript;
+use crate::repro::Repro;
 use anyhow::bail;
 use anyhow::Context;
 use memmap2::Mmap;
 use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt as _;
 use std::path::Path;
 use std::path::PathBuf;
+use std::time::SystemTime;
 
 pub struct InputData<'config> {
     pub config: &'config Args,
     pub filenames: HashSet<PathBuf>,
     pub(crate) files: Vec<InputFile>,
     pub(crate) version_script: Option<VersionScript>,
+    pub(crate) dynamic_list: Option<DynamicList>,
+
+    /// Records the files that we read, if we were asked to write a reproduction tar via `--repro`.
+    pub(crate) repro: Option<Repro>,
 }
 
 /// Identifies an input file. IDs start from 0 which is reserved for our "internal" state file.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(crate) struct FileId(u32);
 
 pub(crate) const INTERNAL_FILE_ID: FileId = FileId::new(0);
@@ -37,6 +51,14 @@ pub(crate) struct InputFile {
     pub(crate) modifiers: Modifiers,
 
     bytes: Option<Mmap>,
+
+    /// When the file was last modified. Only set if we're using an object cache or linking
+    /// incrementally, since those are the only things that need it.
+    pub(crate) modified: Option<SystemTime>,
+
+    /// For thin archives, the contents of each of the archive's entries, which are stored in
+    /// separate files. Empty for all other kinds of file.
+    thin_archive_members: Vec<Mmap>,
 }
 
 /// Identifies an input object that may not be a regular file on disk, or may be an entry in an
@@ -51,6 +73,14 @@ impl InputFile {
     pub(crate) fn data(&self) -> &[u8] {
         self.bytes.as_deref().unwrap_or_default()
     }
+
+    /// Returns the data for the entry at `index` (counting only regular entries) of a thin archive.
+    pub(crate) fn thin_archive_member(&self, index: usize) -> Result<&[u8]> {
+        self.thin_archive_members
+            .get(index)
+            .map(|m| m.as_ref())
+            .with_context(|| format!("Missing thin archive member {index} of `{self}`"))
+    }
 }
 
 #[derive(Debug)]
@@ -75,6 +105,8 @@ impl<'config> InputData<'config> {
                 kind: FileKind::Internal,
                 modifiers: Default::default(),
                 bytes: None,
+                modified: None,
+                thin_archive_members: Vec::new(),
             },
         ];
         let version_script = config
@@ -82,11 +114,18 @@ impl<'config> InputData<'config> {
             .as_ref()
             .map(|path| read_version_script(path))
             .transpose()?;
+        let dynamic_list = config
+            .dynamic_list_path
+            .as_ref()
+            .map(|path| read_dynamic_list(path))
+            .transpose()?;
         let mut input_data = Self {
             config,
             filenames: Default::default(),
             files,
             version_script,
+            dynamic_list,
+            repro: config.repro.is_some().then(Repro::default),
         };
         for input in &config.inputs {
             input_data.register_input(input)?;
@@ -101,34 +140,15 @@ impl<'config> InputData<'config> {
             // File has already been added.
             return Ok(());
         }
-        let file = std::fs::File::open(absolute_path)
-            .with_context(|| format!("Failed to open input file `{}`", absolute_path.display()))?;
-
-        // Safety: Unfortunately, this is a bit of a compromise. Basically this is only safe if our
-        // users manage to avoid editing the input files while we've got them mapped. It'd be great
-        // if there were a way to protect against unsoundness when the input files were modified
-        // externally, but there isn't - at least on Linux. Not only could the bytes change without
-        // notice, but the mapped file could be truncated causing any access to result in a SIGBUS.
-        //
-        // For our use case, mmap just has too many advantages. There are likely large parts of our
-        // input files that we don't need to read, so reading all our input files up front isn't
-        // really an option. Reading just the parts we need might be an option, but would add
-        // substantial complexity. Also, using mmap means that if the system needs to reclaim
-        // memory, it can just release some of our pages.
-
-        let mut mmap_options = memmap2::MmapOptions::new();
-
-        // Prepopulating maps generally slows things down, so is off by default, however it's useful
-        // when profiling, since it means that you don't see false positive slowness in the parts of
-        // the code that first read a bit of memory.
-        if self.config.prepopulate_maps {
-            mmap_options.populate();
+        let bytes = map_file(absolute_path, self.config)?;
+        if let Some(repro) = &mut self.repro {
+            repro.add_file(absolute_path)?;
         }
 
-        let bytes = unsafe { mmap_options.map(&file) }
-            .with_context(|| format!("Failed to mmap input file `{}`", absolute_path.display()))?;
-
-        let kind = FileKind::identify_bytes(&bytes)?;
+        let kind = FileKind::identify_bytes(&bytes).map_err(|error| LinkError::MalformedInput {
+            file: absolute_path.display().to_string(),
+            reason: error.to_string(),
+        })?;
         if matches!(kind, FileKind::Text) {
             for input in crate::linker_script::linker_script_to_inputs(
                 &bytes,
@@ -140,16 +160,171 @@ impl<'config> InputData<'config> {
             return Ok(());
         }
 
+        let thin_archive_members = if kind == FileKind::ThinArchive {
+            map_thin_archive_members(&bytes, absolute_path, self.config, self.repro.as_mut())?
+        } else {
+            Vec::new()
+        };
+
+        let modified = if self.config.object_cache.is_some() || self.config.incremental {
+            Some(
+                std::fs::metadata(absolute_path)
+                    .and_then(|metadata| metadata.modified())
+                    .with_context(|| {
+                        format!("Failed to read metadata for `{}`", absolute_path.display())
+                    })?,
+            )
+        } else {
+            None
+        };
+
         let file_info = InputFile {
             filename: absolute_path.to_owned(),
             original_filename: paths.original,
             kind,
             modifiers: input.modifiers,
             bytes: Some(bytes),
+            modified,
+            thin_archive_members,
         };
         self.files.push(file_info);
         Ok(())
     }
+
+    /// Opens an object that a linker plugin added to the link, such as the output of LTO.
+    pub(crate) fn open_plugin_input(&self, path: &Path) -> Result<InputFile> {
+        self.open_extra_input(path, path.to_owned(), Modifiers::default())
+    }
+
+    /// Opens a library that a linker plugin added to the link, searching `search_first` before our
+    /// library search path. Returns `None` if the library is already part of the link.
+    pub(crate) fn open_plugin_library(
+        &self,
+        name: &str,
+        search_first: Option<&PathBuf>,
+    ) -> Result<Option<InputFile>> {
+        // Libraries get added after everything on the command line, so whatever options were in
+        // effect at the end of the command line apply.
+        let modifiers = self
+            .config
+            .inputs
+            .last()
+            .map_or_else(Modifiers::default, |input| input.modifiers);
+        let input = Input {
+            spec: InputSpec::from_lib_arg(name),
+            search_first: search_first.cloned(),
+            modifiers,
+        };
+        let paths = input.path(self.config)?;
+        if self.filenames.contains(&paths.absolute) {
+            return Ok(None);
+        }
+        self.open_extra_input(&paths.absolute, paths.original, modifiers)
+            .map(Some)
+    }
+
+    fn open_extra_input(
+        &self,
+        path: &Path,
+        original_filename: PathBuf,
+        modifiers: Modifiers,
+    ) -> Result<InputFile> {
+        let bytes = map_file(path, self.config)?;
+        let kind = FileKind::identify_bytes(&bytes).map_err(|error| LinkError::MalformedInput {
+            file: path.display().to_string(),
+            reason: error.to_string(),
+        })?;
+        if matches!(kind, FileKind::Text | FileKind::LlvmIr) {
+            bail!(
+                "Linker plugin added `{}`, which isn't an object file, archive or shared object",
+                path.display()
+            );
+        }
+        let thin_archive_members = if kind == FileKind::ThinArchive {
+            map_thin_archive_members(&bytes, path, self.config, None)?
+        } else {
+            Vec::new()
+        };
+        Ok(InputFile {
+            filename: path.to_owned(),
+            original_filename,
+            kind,
+            modifiers,
+            bytes: Some(bytes),
+            // Files added by a plugin are generally temporary, so there's no point caching them.
+            modified: None,
+            thin_archive_members,
+        })
+    }
+}
+
+fn map_file(path: &Path, config: &Args) -> Result<Mmap> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open input file `{}`", path.display()))?;
+
+    // Safety: Unfortunately, this is a bit of a compromise. Basically this is only safe if our
+    // users manage to avoid editing the input files while we've got them mapped. It'd be great
+    // if there were a way to protect against unsoundness when the input files were modified
+    // externally, but there isn't - at least on Linux. Not only could the bytes change without
+    // notice, but the mapped file could be truncated causing any access to result in a SIGBUS.
+    //
+    // For our use case, mmap just has too many advantages. There are likely large parts of our
+    // input files that we don't need to read, so reading all our input files up front isn't
+    // really an option. Reading just the parts we need might be an option, but would add
+    // substantial complexity. Also, using mmap means that if the system needs to reclaim
+    // memory, it can just release some of our pages.
+
+    let mut mmap_options = memmap2::MmapOptions::new();
+
+    // Prepopulating maps generally slows things down, so is off by default, however it's useful
+    // when profiling, since it means that you don't see false positive slowness in the parts of
+    // the code that first read a bit of memory.
+    if config.prepopulate_maps {
+        mmap_options.populate();
+    }
+
+    let bytes = unsafe { mmap_options.map(&file) }
+        .with_context(|| format!("Failed to mmap input file `{}`", path.display()))?;
+
+    // The mapping remains valid after the file is closed. Close it now rather than holding it
+    // open, so that the number of file descriptors we use doesn't grow with the number of
+    // inputs. Otherwise, links with thousands of inputs could hit the process's open-file limit.
+    drop(file);
+    Ok(bytes)
+}
+
+/// Maps the files referenced by the entries of a thin archive. Relative paths are relative to the
+/// directory containing the archive.
+fn map_thin_archive_members(
+    bytes: &[u8],
+    archive_path: &Path,
+    config: &Args,
+    mut repro: Option<&mut Repro>,
+) -> Result<Vec<Mmap>> {
+    let directory = archive_path.parent().unwrap_or_else(|| Path::new(""));
+    let mut extended_filenames = None;
+    let mut members = Vec::new();
+    for entry in ArchiveIterator::from_archive_bytes(bytes)? {
+        match entry? {
+            ArchiveEntry::Symbols(_) => {}
+            ArchiveEntry::Filenames(t) => extended_filenames = Some(t),
+            ArchiveEntry::Regular(content) => {
+                let name = content.identifier(extended_filenames).as_slice();
+                let path = directory.join(OsStr::from_bytes(name));
+                if let Some(repro) = repro.as_deref_mut() {
+                    repro.add_file(&path)?;
+                }
+                members.push(map_file(&path, config).with_context(|| {
+                    format!(
+                        "Failed to read member `{}` of thin archive `{}`",
+                        path.display(),
+                        archive_path.display()
+                    )
+                })?);
+            }
+        }
+    }
+    Ok(members)
 }
 
 fn read_version_script(path: &Path) -> Result<VersionScript> {
@@ -158,6 +333,13 @@ fn read_version_script(path: &Path) -> Result<VersionScript> {
     VersionScript::parse(&text)
 }
 
+fn read_dynamic_list(path: &Path) -> Result<DynamicList> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read dynamic list `{}`", path.display()))?;
+    DynamicList::parse(&text)
+        .with_context(|| format!("Failed to parse dynamic list `{}`", path.display()))
+}
+
 impl Input {
     fn path(&self, args: &Args) -> Result<InputPath> {
         match &self.spec {
@@ -179,6 +361,24 @@ impl Input {
                     original: p.as_ref().to_owned(),
                 })
             }
+            InputSpec::Search(filename) => {
+                if let Some(absolute) = search_for_file(
+                    &args.lib_search_path,
+                    self.search_first.as_ref(),
+                    filename.as_ref(),
+                ) {
+                    return Ok(InputPath {
+                        absolute,
+                        original: PathBuf::from(filename.as_ref()),
+                    });
+                }
+                Err(error_handling_script::error(
+                    args,
+                    ErrorKind::MissingLibrary,
+                    filename,
+                    format!("Couldn't find `{filename}` on library search path"),
+                ))
+            }
             InputSpec::Lib(lib_name) => {
                 if self.modifiers.allow_shared {
                     let filename = format!("lib{lib_name}.so");
@@ -202,7 +402,12 @@ impl Input {
                         original: PathBuf::from(filename),
                     });
                 }
-                bail!("Couldn't find library `{lib_name}` on library search path");
+                Err(error_handling_script::error(
+                    args,
+                    ErrorKind::MissingLibrary,
+                    lib_name,
+                    format!("Couldn't find library `{lib_name}` on library search path"),
+                ))
             }
         }
     }
@@ -245,6 +450,12 @@ impl FileId {
     }
 }
 
+impl std::fmt::Display for InputFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.filename.display(), f)
+    }
+}
+
 impl<'a> std::fmt::Display for InputRef<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         std::fmt::Display::fmt(&self.file.filename.display(), f)?;
@@ -273,3 +484,51 @@ impl<'data> InputRef<'data> {
         self.file.original_filename.as_os_str().as_encoded_bytes()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::args::Args;
+    use std::path::Path;
+
+    #[test]
+    fn test_lib_search_order() {
+        let dir = std::env::temp_dir().join("wild-test-lib-search-order");
+        let dir1 = dir.join("1");
+        let dir2 = dir.join("2");
+        std::fs::create_dir_all(&dir1).unwrap();
+        std::fs::create_dir_all(&dir2).unwrap();
+        for name in ["libfoo.a", "libfoo.so", "libbar.a", "libbar.so"] {
+            std::fs::write(dir1.join(name), "").unwrap();
+        }
+        std::fs::write(dir2.join("libbaz.so"), "").unwrap();
+        let dir1 = dir1.to_str().unwrap();
+        let dir2 = dir2.to_str().unwrap();
+        let args = Args::parse(
+            [
+                "wild",
+                "-L",
+                dir1,
+                "-Bstatic",
+                "-lfoo",
+                "-Bdynamic",
+                "-lbar",
+                // A library that's only found via a later `-L`.
+                "-lbaz",
+                &format!("-L{dir2}"),
+                "-o",
+                "/tmp/out",
+            ]
+            .iter(),
+        )
+        .unwrap();
+        let paths = args
+            .inputs
+            .iter()
+            .map(|input| input.path(&args).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(paths[0].absolute, Path::new(dir1).join("libfoo.a"));
+        assert_eq!(paths[1].absolute, Path::new(dir1).join("libbar.so"));
+        assert_eq!(paths[2].absolute, Path::new(dir2).join("libbaz.so"));
+        assert_eq!(paths[2].original, Path::new("libbaz.so"));
+    }
+}
@@ -2,11 +2,18 @@
 //! referenced. Determines which sections need to be linked, sums their sizes decides what goes
 //! where in the output file then allocates addresses for each symbol.
 
+use crate::abi_tag::AbiTag;
 use crate::alignment;
 use crate::alignment::Alignment;
 use crate::args::Args;
+use crate::args::Emulation;
+use crate::args::Icf;
 use crate::args::OutputKind;
+use crate::args::SortCommon;
+use crate::args::SortSection;
+use crate::compression::DecompressedSections;
 use crate::debug_assert_bail;
+use crate::diagnostics::Diagnostics;
 use crate::elf;
 use crate::elf::EhFrameHdrEntry;
 use crate::elf::File;
@@ -14,12 +21,22 @@ use crate::elf::RelocationKind;
 use crate::elf::RelocationKindInfo;
 use crate::elf_writer;
 use crate::error::Error;
+use crate::error::LinkError;
 use crate::error::Result;
+use crate::gdb_index::GdbIndex;
+use crate::gdb_index::ObjectDebugInfo;
+use crate::gnu_property::GnuProperties;
+use crate::icf;
+use crate::icf::FoldedSection;
+use crate::icf::InputSectionId;
+use crate::incremental::Incremental;
 use crate::input_data::FileId;
 use crate::input_data::InputRef;
 use crate::input_data::INTERNAL_FILE_ID;
+use crate::linker_script::DynamicList;
 use crate::linker_script::VersionScript;
 use crate::output_section_id;
+use crate::output_section_id::is_debug_section;
 use crate::output_section_id::OutputSectionId;
 use crate::output_section_id::OutputSections;
 use crate::output_section_id::UnloadedSection;
@@ -52,11 +69,14 @@ use object::ObjectSymbol;
 use object::ObjectSymbolTable as _;
 use rayon::prelude::IndexedParallelIterator;
 use rayon::prelude::IntoParallelIterator;
+use rayon::prelude::IntoParallelRefIterator;
 use rayon::prelude::IntoParallelRefMutIterator;
 use rayon::prelude::ParallelIterator;
 use smallvec::SmallVec;
+use std::collections::BTreeMap;
 use std::ffi::CString;
 use std::fmt::Display;
+use std::io::Write as _;
 use std::mem::size_of;
 use std::num::NonZeroU64;
 use std::sync::atomic;
@@ -66,35 +86,109 @@ use std::sync::Mutex;
 #[tracing::instrument(skip_all, name = "Layout")]
 pub fn compute<'data>(
     symbol_db: &'data SymbolDb<'data>,
+    diagnostics: &'data Diagnostics,
     file_states: Vec<resolution::ResolvedFile<'data>>,
     mut output_sections: OutputSections<'data>,
     version_script: Option<&'data VersionScript>,
+    dynamic_list: Option<&'data DynamicList>,
     output: &mut elf_writer::Output,
 ) -> Result<Layout<'data>> {
     if let Some(sym_info) = symbol_db.args.sym_info.as_deref() {
         print_symbol_info(symbol_db, sym_info);
     }
-    let mut layout_states =
-        find_required_sections(file_states, symbol_db, &output_sections, version_script)?;
+    if !symbol_db.args.relro {
+        output_sections.disable_relro();
+    }
+    if !symbol_db.args.rosegment {
+        output_sections.disable_rosegment();
+    }
+    output_sections.bss_ascending = symbol_db.args.sort_common == SortCommon::Ascending;
+    if symbol_db.args.z_flags_1 != 0 && !symbol_db.args.needs_dynamic() {
+        diagnostics.warn(
+            "-z keywords that set DT_FLAGS_1 are ignored when the output isn't dynamic".to_owned(),
+        );
+    }
+    let mut layout_states = find_required_sections(
+        file_states,
+        symbol_db,
+        &output_sections,
+        version_script,
+        dynamic_list,
+        diagnostics,
+    )?;
+    if symbol_db.args.output_kind == OutputKind::Relocatable {
+        collect_undefined_symbols(&mut layout_states, symbol_db)?;
+    }
+    if symbol_db.args.print_gc_sections {
+        print_gc_sections(&layout_states)?;
+    }
+    if symbol_db.args.icf != Icf::None {
+        fold_identical_sections(&mut layout_states, symbol_db, &output_sections)?;
+    }
+    resolve_debug_relocation_targets(&mut layout_states)?;
+    set_version_definitions(&mut layout_states, version_script, symbol_db.args);
+    assign_needed_versions(&mut layout_states, symbol_db.args)?;
     merge_dynamic_symbol_definitions(&mut layout_states)?;
+    merge_gnu_properties(&mut layout_states);
+    merge_abi_tags(&mut layout_states)?;
+    let has_text_relocations = merge_text_relocations(&mut layout_states);
+    let (has_init, has_fini) = merge_init_fini(&mut layout_states);
+    let uses_static_tls = layout_states
+        .iter()
+        .any(|state| matches!(state, FileLayoutState::Object(object) if object.uses_static_tls));
+    let exports_unique_symbols = symbol_db.args.output_kind == OutputKind::SharedObject
+        && layout_states.iter().any(
+            |state| matches!(state, FileLayoutState::Object(object) if object.exports_unique_symbols),
+        );
+    if symbol_db.args.gdb_index && !symbol_db.args.strip_debug {
+        build_gdb_index(&mut layout_states)?;
+    }
+    deduplicate_cies(&mut layout_states);
     finalise_all_sizes(symbol_db, &output_sections, &mut layout_states)?;
-    let section_part_sizes =
-        compute_total_section_part_sizes(&mut layout_states, &mut output_sections);
-    let section_part_layouts = layout_section_parts(&section_part_sizes, &output_sections);
+    let section_part_sizes = compute_total_section_part_sizes(
+        &mut layout_states,
+        &mut output_sections,
+        symbol_db.args,
+        output.incremental(),
+    )?;
+    let page_size = symbol_db.args.max_page_size;
+    let common_page_size = symbol_db.args.common_page_size;
+    let start_addresses =
+        section_start_addresses(symbol_db.args, &section_part_sizes, &output_sections);
+    let section_part_layouts = layout_section_parts(
+        &section_part_sizes,
+        &output_sections,
+        page_size,
+        common_page_size,
+        symbol_db.args.separate_code,
+        &start_addresses,
+    )?;
     let section_layouts = layout_sections(&section_part_layouts);
+    check_eh_frame_hdr_range(&section_layouts, &output_sections)?;
+    if symbol_db.args.check_sections {
+        check_section_overlaps(&section_layouts, &output_sections)?;
+    }
     output.set_size(compute_total_file_size(&section_layouts));
 
     let FileLayoutState::Internal(internal) = &layout_states[INTERNAL_FILE_ID.as_usize()] else {
         unreachable!();
     };
     let header_info = internal.header_info.as_ref().unwrap();
-    let segment_layouts = compute_segment_layout(&section_layouts, &output_sections, header_info);
+    let segment_layouts = compute_segment_layout(
+        &section_layouts,
+        &output_sections,
+        header_info,
+        common_page_size,
+    );
 
     let mem_offsets: OutputSectionPartMap<u64> =
         starting_memory_offsets(&section_part_layouts, &output_sections);
     let starting_mem_offsets_by_file = compute_start_offsets_by_file(&layout_states, mem_offsets);
-    let merged_string_start_addresses =
-        MergedStringStartAddresses::compute(&output_sections, &starting_mem_offsets_by_file);
+    let merged_string_start_addresses = MergedStringStartAddresses::compute(
+        &output_sections,
+        &starting_mem_offsets_by_file,
+        &internal.merged_strings,
+    );
     let mut symbol_resolutions = SymbolResolutions {
         resolutions: vec![None; symbol_db.num_symbols()],
     };
@@ -102,7 +196,7 @@ pub fn compute<'data>(
         &mut symbol_resolutions.resolutions,
         &symbol_db.num_symbols_per_file,
     );
-    let file_layouts = compute_symbols_and_layouts(
+    let mut file_layouts = compute_symbols_and_layouts(
         layout_states,
         starting_mem_offsets_by_file,
         &section_layouts,
@@ -111,18 +205,434 @@ pub fn compute<'data>(
         &output_sections,
         &mut resolutions_by_file,
     )?;
+    if symbol_db.args.icf != Icf::None {
+        resolve_folded_sections(&mut file_layouts, &mut symbol_resolutions)?;
+    }
+    if !symbol_db.args.defsyms.is_empty() {
+        resolve_defsyms(symbol_db, &mut symbol_resolutions)?;
+    }
+    resolve_duplicate_cies(&mut file_layouts)?;
 
     Ok(Layout {
         symbol_db,
+        diagnostics,
         symbol_resolutions,
         segment_layouts,
         section_part_layouts,
         section_layouts,
         file_layouts,
         output_sections,
+        has_text_relocations,
+        has_init,
+        has_fini,
+        uses_static_tls,
+        exports_unique_symbols,
     })
 }
 
+/// Finds the global symbols that our objects reference, but that nothing we're linking defines. When
+/// producing a relocatable output, these need to go in the symbol table as undefined symbols so that
+/// the final link can resolve them. Each symbol is weak only if all references to it are weak.
+fn collect_undefined_symbols<'data>(
+    layout_states: &mut [FileLayoutState<'data>],
+    symbol_db: &SymbolDb<'data>,
+) -> Result {
+    let mut undefined_symbols: Vec<UndefinedSymbol<'data>> = Vec::new();
+    let mut index_by_name = AHashMap::new();
+    for state in layout_states.iter() {
+        let FileLayoutState::Object(object) = state else {
+            continue;
+        };
+        for sym in object.object.symbols() {
+            if !sym.is_undefined() || sym.is_local() {
+                continue;
+            }
+            let name = sym.name_bytes()?;
+            if name.is_empty() {
+                continue;
+            }
+            let definition =
+                symbol_db.definition(object.start_symbol_id().add_usize(sym.index().0));
+            let is_defined = !definition.is_undefined()
+                && matches!(
+                    layout_states[symbol_db.file_id_for_symbol(definition).as_usize()],
+                    FileLayoutState::Object(_)
+                        | FileLayoutState::Internal(_)
+                        | FileLayoutState::Epilogue(_)
+                );
+            if is_defined {
+                continue;
+            }
+            let index = *index_by_name.entry(name).or_insert_with(|| {
+                undefined_symbols.push(UndefinedSymbol {
+                    name,
+                    is_weak: true,
+                });
+                undefined_symbols.len() - 1
+            });
+            undefined_symbols[index].is_weak &= sym.is_weak();
+        }
+    }
+    let FileLayoutState::Internal(internal) = &mut layout_states[INTERNAL_FILE_ID.as_usize()]
+    else {
+        unreachable!();
+    };
+    internal.undefined_symbols = undefined_symbols;
+    Ok(())
+}
+
+/// Checks that no two allocated output sections overlap in memory, reporting the first overlapping
+/// pair. Overlaps can only come from addresses supplied by the user, e.g. via `--section-start`,
+/// since otherwise we place each section after the previous one.
+fn check_section_overlaps(
+    section_layouts: &OutputSectionMap<OutputRecordLayout>,
+    output_sections: &OutputSections,
+) -> Result {
+    let mut ranges = Vec::new();
+    output_sections.sections_do(|section_id, details| {
+        let layout = section_layouts.get(section_id);
+        // .tbss doesn't occupy any memory outside of each thread's TLS block, so sections that
+        // follow it are free to use the same addresses.
+        let is_tbss = details.ty == elf::Sht::Nobits && details.section_flags & elf::shf::TLS != 0;
+        // Our headers aren't marked as allocated, but are still loaded.
+        let is_loaded = details.section_flags & elf::shf::ALLOC != 0
+            || output_sections
+                .loadable_segment_id_for(section_id)
+                .is_some();
+        if is_loaded && layout.mem_size > 0 && !is_tbss {
+            ranges.push((
+                layout.mem_offset,
+                layout.mem_offset + layout.mem_size,
+                section_id,
+            ));
+        }
+    });
+    ranges.sort_unstable_by_key(|(start, end, _)| (*start, *end));
+    let describe = |section_id: OutputSectionId| {
+        if section_id == output_section_id::FILE_HEADER {
+            "the file header".to_owned()
+        } else {
+            format!("`{}`", output_sections.display_name(section_id))
+        }
+    };
+    let mut furthest: Option<(u64, u64, OutputSectionId)> = None;
+    for range in ranges {
+        let (start, end, section_id) = range;
+        if let Some((other_start, other_end, other_id)) = furthest {
+            if start < other_end {
+                bail!(
+                    "Sections overlap in memory: {} [0x{other_start:x}, 0x{other_end:x}) and {} \
+                     [0x{start:x}, 0x{end:x}). Use --no-check-sections to allow this",
+                    describe(other_id),
+                    describe(section_id),
+                );
+            }
+        }
+        if furthest.is_none_or(|(_, other_end, _)| end > other_end) {
+            furthest = Some(range);
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the lookup table in .eh_frame_hdr will be able to reference all FDEs and the
+/// functions that they cover. The table holds 32 bit offsets from the start of .eh_frame_hdr, which
+/// is the only encoding that unwinders will binary search, so everything needs to be within 2GiB.
+/// We check this up-front so that we can report which section is too far away, rather than failing
+/// part way through writing the output.
+fn check_eh_frame_hdr_range(
+    section_layouts: &OutputSectionMap<OutputRecordLayout>,
+    output_sections: &OutputSections,
+) -> Result {
+    let eh_frame_hdr = section_layouts.get(output_section_id::EH_FRAME_HDR);
+    if eh_frame_hdr.mem_size <= size_of::<elf::EhFrameHdr>() as u64 {
+        // The lookup table is empty.
+        return Ok(());
+    }
+    let fits =
+        |address: u64| i32::try_from(address.wrapping_sub(eh_frame_hdr.mem_offset) as i64).is_ok();
+    let mut result = Ok(());
+    output_sections.sections_do(|section_id, details| {
+        let layout = section_layouts.get(section_id);
+        let may_be_referenced = section_id == output_section_id::EH_FRAME
+            || details.section_flags & elf::shf::EXECINSTR != 0;
+        if result.is_err()
+            || !may_be_referenced
+            || layout.mem_size == 0
+            || (fits(layout.mem_offset) && fits(layout.mem_offset + layout.mem_size))
+        {
+            return;
+        }
+        result = Err(anyhow!(
+            "`{}` at 0x{:x} is more than 2GiB away from .eh_frame_hdr at 0x{:x}, so it can't be \
+             referenced from .eh_frame_hdr's lookup table",
+            output_sections.display_name(section_id),
+            layout.mem_offset,
+            eh_frame_hdr.mem_offset
+        ));
+    });
+    result
+}
+
+/// Finds executable sections that are identical to other sections and folds them into those other
+/// sections. Folded sections don't take up any space in the output, so we release what we allocated
+/// when we loaded them. Their addresses are filled in by `resolve_folded_sections` once the
+/// sections that they were folded into have been laid out.
+#[tracing::instrument(skip_all, name = "Fold identical sections")]
+fn fold_identical_sections(
+    layout_states: &mut [FileLayoutState],
+    symbol_db: &SymbolDb,
+    output_sections: &OutputSections,
+) -> Result {
+    let candidates: Vec<icf::Candidate> = layout_states
+        .par_iter()
+        .map(|state| match state {
+            FileLayoutState::Object(object) => object.icf_candidates(layout_states, symbol_db),
+            _ => Ok(Vec::new()),
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+    let survivors = icf::find_identical(&candidates);
+    if symbol_db.args.print_icf_sections {
+        print_icf_sections(layout_states, &candidates, &survivors)?;
+    }
+    for (candidate, survivor) in candidates.iter().zip(survivors) {
+        let Some(survivor) = survivor else {
+            continue;
+        };
+        let FileLayoutState::Object(object) = &mut layout_states[candidate.id.file_id.as_usize()]
+        else {
+            bail!("Internal error: ICF candidate isn't from an object");
+        };
+        object.fold_section(
+            candidate.id.index,
+            candidates[survivor].id,
+            symbol_db,
+            output_sections,
+        )?;
+    }
+    Ok(())
+}
+
+/// Points sections that were folded by `--icf`, and the symbols that they define, at the sections
+/// that they were folded into. Called once all sections have addresses.
+/// Evaluates the expressions given to `--defsym` and stores the results as the values of the symbols
+/// that they define. This needs to happen after all other symbols have been resolved, since the
+/// expressions can refer to them. Expressions can also refer to symbols defined by other
+/// `--defsym`s, regardless of their order on the command line.
+fn resolve_defsyms(symbol_db: &SymbolDb, symbol_resolutions: &mut SymbolResolutions) -> Result {
+    let defsyms = &symbol_db.args.defsyms;
+    // The symbol ID of each `--defsym`, or None if it was overridden by a definition from an input.
+    let symbol_ids = defsyms
+        .iter()
+        .map(|defsym| {
+            let symbol_id = symbol_db.definition(
+                *symbol_db
+                    .global_names
+                    .get(&SymbolName::prehashed(defsym.name.as_bytes()))?,
+            );
+            (symbol_db.file_id_for_symbol(symbol_id) == INTERNAL_FILE_ID).then_some(symbol_id)
+        })
+        .collect::<Vec<_>>();
+
+    #[derive(Clone, Copy)]
+    enum State {
+        Pending,
+        InProgress,
+        Done(u64),
+    }
+
+    fn evaluate(
+        index: usize,
+        states: &mut [State],
+        symbol_ids: &[Option<SymbolId>],
+        symbol_db: &SymbolDb,
+        symbol_resolutions: &SymbolResolutions,
+    ) -> Result<u64> {
+        let defsym = &symbol_db.args.defsyms[index];
+        match states[index] {
+            State::Done(value) => return Ok(value),
+            State::InProgress => bail!("Symbol `{}` is defined in terms of itself", defsym.name),
+            State::Pending => {}
+        }
+        states[index] = State::InProgress;
+        let value = defsym
+            .value
+            .evaluate(&mut |name| {
+                let symbol_id = symbol_db
+                    .global_names
+                    .get(&SymbolName::prehashed(name.as_bytes()))
+                    .map(|symbol_id| symbol_db.definition(*symbol_id));
+                if let Some(other) = symbol_ids
+                    .iter()
+                    .position(|id| id.is_some() && *id == symbol_id)
+                {
+                    return evaluate(other, states, symbol_ids, symbol_db, symbol_resolutions);
+                }
+                let resolution = symbol_id
+                    .filter(|symbol_id| !symbol_id.is_undefined())
+                    .and_then(|symbol_id| symbol_resolutions.resolutions[symbol_id.as_usize()])
+                    .ok_or_else(|| {
+                        anyhow::Error::new(LinkError::UndefinedSymbol {
+                            name: name.to_owned(),
+                            note: None,
+                        })
+                    })?;
+                match resolution.value {
+                    ResolutionValue::Absolute(value) | ResolutionValue::Address(value) => Ok(value),
+                    ResolutionValue::Dynamic(_) => {
+                        bail!("Symbol `{name}` is defined in a shared object")
+                    }
+                }
+            })
+            .with_context(|| format!("Failed to evaluate defsym `{}`", defsym.name))?;
+        states[index] = State::Done(value);
+        Ok(value)
+    }
+
+    let mut states = vec![State::Pending; defsyms.len()];
+    for (index, symbol_id) in symbol_ids.iter().enumerate() {
+        let Some(symbol_id) = symbol_id else {
+            continue;
+        };
+        let value = evaluate(
+            index,
+            &mut states,
+            &symbol_ids,
+            symbol_db,
+            symbol_resolutions,
+        )?;
+        if let Some(resolution) = &mut symbol_resolutions.resolutions[symbol_id.as_usize()] {
+            resolution.value = ResolutionValue::Absolute(value);
+        }
+    }
+    Ok(())
+}
+
+fn resolve_folded_sections(
+    file_layouts: &mut [FileLayout],
+    symbol_resolutions: &mut SymbolResolutions,
+) -> Result {
+    let mut updates = Vec::new();
+    for (file_index, file_layout) in file_layouts.iter().enumerate() {
+        let FileLayout::Object(object) = file_layout else {
+            continue;
+        };
+        let mut survivor_addresses = vec![None; object.sections.len()];
+        for (slot, address) in object.sections.iter().zip(&mut survivor_addresses) {
+            let SectionSlot::Folded(folded) = slot else {
+                continue;
+            };
+            let FileLayout::Object(survivor_object) =
+                &file_layouts[folded.survivor.file_id.as_usize()]
+            else {
+                bail!(
+                    "Internal error: Section was folded into a section that isn't from an object"
+                );
+            };
+            *address = Some(
+                survivor_object.section_resolutions[folded.survivor.index.0]
+                    .as_ref()
+                    .context("Internal error: Section was folded into an unresolved section")?
+                    .value
+                    .address()?,
+            );
+        }
+        if survivor_addresses.iter().any(Option::is_some) {
+            updates.push((file_index, survivor_addresses));
+        }
+    }
+    for (file_index, survivor_addresses) in updates {
+        let FileLayout::Object(object) = &mut file_layouts[file_index] else {
+            unreachable!();
+        };
+        for (resolution, address) in object
+            .section_resolutions
+            .iter_mut()
+            .zip(&survivor_addresses)
+        {
+            if let (Some(resolution), Some(address)) = (resolution, address) {
+                resolution.value = ResolutionValue::Address(*address);
+            }
+        }
+        for symbol in object.object.symbols() {
+            let Some(address) = symbol
+                .section_index()
+                .and_then(|index| survivor_addresses[index.0])
+            else {
+                continue;
+            };
+            let symbol_id = object.start_symbol_id.add_usize(symbol.index().0);
+            if let Some(resolution) = &mut symbol_resolutions.resolutions[symbol_id.as_usize()] {
+                resolution.value = ResolutionValue::Address(address + symbol.address());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Finds CIEs that are identical to a CIE in the same or an earlier object. Only the first of each
+/// set of identical CIEs is written to the output. FDEs that refer to the others are pointed at it
+/// instead. Because objects are laid out in order and each object writes its CIEs before its FDEs,
+/// the surviving CIE always comes before any FDE that refers to it, as required by the format.
+#[tracing::instrument(skip_all, name = "Deduplicate CIEs")]
+fn deduplicate_cies(layout_states: &mut [FileLayoutState]) {
+    let mut survivors = AHashMap::new();
+    for state in layout_states {
+        let FileLayoutState::Object(object) = state else {
+            continue;
+        };
+        let file_id = object.state.common.file_id;
+        for (index, cie) in object.state.cies.iter_mut().enumerate() {
+            if !cie.cie.eligible_for_deduplication {
+                continue;
+            }
+            match survivors.entry(cie.cie.clone()) {
+                std::collections::hash_map::Entry::Occupied(entry) => {
+                    cie.duplicate_of = Some(*entry.get());
+                }
+                std::collections::hash_map::Entry::Vacant(entry) => {
+                    entry.insert(CieId { file_id, index });
+                }
+            }
+        }
+    }
+}
+
+/// Points CIEs that were found to be duplicates by `deduplicate_cies` at the addresses of the CIEs
+/// that are written in their place. Called once all objects have been laid out.
+fn resolve_duplicate_cies(file_layouts: &mut [FileLayout]) -> Result {
+    let mut updates = Vec::new();
+    for (file_index, file_layout) in file_layouts.iter().enumerate() {
+        let FileLayout::Object(object) = file_layout else {
+            continue;
+        };
+        for (index, cie) in object.cies.iter().enumerate() {
+            let Some(survivor) = cie.duplicate_of else {
+                continue;
+            };
+            let FileLayout::Object(survivor_object) = &file_layouts[survivor.file_id.as_usize()]
+            else {
+                bail!("Internal error: CIE is a duplicate of a CIE that isn't from an object");
+            };
+            updates.push((
+                file_index,
+                index,
+                survivor_object.cies[survivor.index].address,
+            ));
+        }
+    }
+    for (file_index, index, address) in updates {
+        let FileLayout::Object(object) = &mut file_layouts[file_index] else {
+            unreachable!();
+        };
+        object.cies[index].address = address;
+    }
+    Ok(())
+}
+
 #[tracing::instrument(skip_all, name = "Finalise per-object sizes")]
 fn finalise_all_sizes(
     symbol_db: &SymbolDb,
@@ -138,8 +648,14 @@ fn finalise_all_sizes(
 fn merge_dynamic_symbol_definitions(layout_states: &mut [FileLayoutState]) -> Result {
     let mut dynamic_symbol_definitions = Vec::new();
     for state in layout_states.iter() {
-        if let FileLayoutState::Object(s) = state {
-            dynamic_symbol_definitions.extend(s.dynamic_symbol_definitions.iter().copied())
+        match state {
+            FileLayoutState::Object(s) => {
+                dynamic_symbol_definitions.extend(s.dynamic_symbol_definitions.iter().copied())
+            }
+            FileLayoutState::Dynamic(s) => {
+                dynamic_symbol_definitions.extend(s.dynamic_symbol_definitions.iter().copied())
+            }
+            _ => {}
         }
     }
     let Some(FileLayoutState::Epilogue(epilogue)) = layout_states.last_mut() else {
@@ -149,7 +665,173 @@ fn merge_dynamic_symbol_definitions(layout_states: &mut [FileLayoutState]) -> Re
     Ok(())
 }
 
-fn compute_total_file_size(section_layouts: &OutputSectionMap<OutputRecordLayout>) -> u64 {
+/// Tells the epilogue which versions to define. We only define versions for shared objects.
+fn set_version_definitions<'data>(
+    layout_states: &mut [FileLayoutState<'data>],
+    version_script: Option<&'data VersionScript>,
+    args: &Args,
+) {
+    let Some(version_script) = version_script else {
+        return;
+    };
+    if args.output_kind != OutputKind::SharedObject {
+        return;
+    }
+    let Some(FileLayoutState::Epilogue(epilogue)) = layout_states.last_mut() else {
+        panic!("Internal error, epilogue must be last");
+    };
+    epilogue.version_definitions = version_script
+        .version_definitions()
+        .map(|(name, parents)| VersionDefinition {
+            name: name.as_bytes(),
+            parents: parents.iter().map(|parent| parent.as_bytes()).collect(),
+        })
+        .collect();
+}
+
+/// Returns the name of the base version, which is the first entry in `.gnu.version_d`. Like GNU
+/// ld, we name it after the output file, since we don't support setting DT_SONAME.
+pub(crate) fn base_version_name(args: &Args) -> &[u8] {
+    args.output
+        .file_name()
+        .map_or(&[], |name| name.as_encoded_bytes())
+}
+
+#[tracing::instrument(skip_all, name = "Resolve debug relocation targets")]
+fn resolve_debug_relocation_targets(layout_states: &mut [FileLayoutState]) -> Result {
+    layout_states
+        .par_iter_mut()
+        .try_for_each(|state| match state {
+            FileLayoutState::Object(object) => object.resolve_debug_relocation_targets(),
+            _ => Ok(()),
+        })
+}
+
+#[tracing::instrument(skip_all, name = "Merge GNU properties")]
+fn merge_gnu_properties(layout_states: &mut [FileLayoutState]) {
+    let gnu_properties = GnuProperties::merge(layout_states.iter().filter_map(|state| {
+        if let FileLayoutState::Object(s) = state {
+            Some(&s.gnu_properties)
+        } else {
+            None
+        }
+    }));
+    let Some(FileLayoutState::Epilogue(epilogue)) = layout_states.last_mut() else {
+        panic!("Internal error, epilogue must be last");
+    };
+    epilogue.gnu_properties = gnu_properties;
+}
+
+#[tracing::instrument(skip_all, name = "Merge ABI tags")]
+fn merge_abi_tags(layout_states: &mut [FileLayoutState]) -> Result {
+    let abi_tag = AbiTag::merge(layout_states.iter().filter_map(|state| {
+        if let FileLayoutState::Object(s) = state {
+            Some(&s.abi_tag)
+        } else {
+            None
+        }
+    }))?;
+    let Some(FileLayoutState::Epilogue(epilogue)) = layout_states.last_mut() else {
+        panic!("Internal error, epilogue must be last");
+    };
+    epilogue.abi_tag = abi_tag;
+    Ok(())
+}
+
+/// Returns whether any object needs text relocations, which also tells the epilogue to emit
+/// `DT_TEXTREL`.
+fn merge_text_relocations(layout_states: &mut [FileLayoutState]) -> bool {
+    let has_text_relocations = layout_states.iter().any(
+        |state| matches!(state, FileLayoutState::Object(object) if object.has_text_relocations),
+    );
+    let Some(FileLayoutState::Epilogue(epilogue)) = layout_states.last_mut() else {
+        panic!("Internal error, epilogue must be last");
+    };
+    epilogue.has_text_relocations = has_text_relocations;
+    has_text_relocations
+}
+
+/// Returns whether any object contributes to `.init` and `.fini` respectively. We only emit
+/// `DT_INIT` and `DT_FINI` if there's code for them to point at, so the epilogue needs to know.
+fn merge_init_fini(layout_states: &mut [FileLayoutState]) -> (bool, bool) {
+    let has_content = |section_id| {
+        layout_states.iter().any(|state| {
+            matches!(state, FileLayoutState::Object(_))
+                && state
+                    .common()
+                    .is_some_and(|common| *common.sections_with_content.get(section_id))
+        })
+    };
+    let has_init = has_content(output_section_id::INIT);
+    let has_fini = has_content(output_section_id::FINI);
+    let Some(FileLayoutState::Epilogue(epilogue)) = layout_states.last_mut() else {
+        panic!("Internal error, epilogue must be last");
+    };
+    epilogue.has_init = has_init;
+    epilogue.has_fini = has_fini;
+    (has_init, has_fini)
+}
+
+/// Works out which versions we need from each shared object and numbers them, following the
+/// versions that we define. Each symbol that we import needs the version of the definition that we
+/// resolved it to, as does each definition that we copy.
+///
+/// glibc only supports DT_RELR from version 2.36, which defines `GLIBC_ABI_DT_RELR` so that
+/// binaries that use it can require that version. An older glibc then refuses to load us rather
+/// than leaving our relative relocations unapplied. We add the requirement to the first shared
+/// object that we depend on that defines the version, which should be libc.
+fn assign_needed_versions(layout_states: &mut [FileLayoutState], args: &Args) -> Result {
+    let Some(FileLayoutState::Epilogue(epilogue)) = layout_states.last() else {
+        panic!("Internal error, epilogue must be last");
+    };
+    let mut next_index =
+        elf::FIRST_DEFINED_VERSION_INDEX + epilogue.version_definitions.len() as u16;
+    let mut needs_relr_version = args.uses_relr();
+    let mut last_requirement = None;
+    for (i, state) in layout_states.iter_mut().enumerate() {
+        let FileLayoutState::Dynamic(dynamic) = state else {
+            continue;
+        };
+        if !dynamic.is_needed {
+            continue;
+        }
+        if needs_relr_version && elf::defines_version(dynamic.object, elf::GLIBC_ABI_DT_RELR)? {
+            dynamic.require_version(elf::GLIBC_ABI_DT_RELR, &mut next_index)?;
+            needs_relr_version = false;
+        }
+        dynamic.record_symbol_versions(&mut next_index)?;
+        if !dynamic.needed_versions.is_empty() {
+            last_requirement = Some(i);
+        }
+    }
+    // Each entry in .gnu.version_r links to the next, except for the last.
+    if let Some(FileLayoutState::Dynamic(dynamic)) = last_requirement.map(|i| &mut layout_states[i])
+    {
+        dynamic.has_last_version_requirement = true;
+    }
+    Ok(())
+}
+
+#[tracing::instrument(skip_all, name = "Build GDB index")]
+fn build_gdb_index(layout_states: &mut [FileLayoutState]) -> Result {
+    let objects = layout_states
+        .par_iter()
+        .filter_map(|state| match state {
+            FileLayoutState::Object(object) => Some(object.debug_info()),
+            _ => None,
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let gdb_index = GdbIndex::build(objects.into_iter())?;
+    let Some(FileLayoutState::Epilogue(epilogue)) = layout_states.last_mut() else {
+        panic!("Internal error, epilogue must be last");
+    };
+    epilogue.gdb_index = Some(gdb_index);
+    Ok(())
+}
+
+pub(crate) fn compute_total_file_size(
+    section_layouts: &OutputSectionMap<OutputRecordLayout>,
+) -> u64 {
     let mut file_size = 0;
     section_layouts.for_each(|_, s| file_size = file_size.max(s.file_offset + s.file_size));
     file_size as u64
@@ -159,12 +841,26 @@ fn compute_total_file_size(section_layouts: &OutputSectionMap<OutputRecordLayout
 /// same time.
 pub struct Layout<'data> {
     pub(crate) symbol_db: &'data SymbolDb<'data>,
+    pub(crate) diagnostics: &'data Diagnostics,
     pub(crate) symbol_resolutions: SymbolResolutions,
     pub(crate) section_part_layouts: OutputSectionPartMap<OutputRecordLayout>,
     pub(crate) section_layouts: OutputSectionMap<OutputRecordLayout>,
     pub(crate) file_layouts: Vec<FileLayout<'data>>,
     pub(crate) segment_layouts: SegmentLayouts,
     pub(crate) output_sections: OutputSections<'data>,
+    /// Whether we emitted dynamic relocations in read-only sections. See `-z notext`.
+    pub(crate) has_text_relocations: bool,
+    /// Whether `.init` has any content, in which case we emit `DT_INIT`.
+    pub(crate) has_init: bool,
+    /// Whether `.fini` has any content, in which case we emit `DT_FINI`.
+    pub(crate) has_fini: bool,
+    /// Whether we're a shared object that uses static TLS, so can't be loaded via `dlopen`. Sets
+    /// `DF_STATIC_TLS`.
+    pub(crate) uses_static_tls: bool,
+    /// Whether we're a shared object that exports a `STB_GNU_UNIQUE` symbol. The runtime loader
+    /// never unloads such objects, since other objects may have bound to our copy of the symbol, so
+    /// we set `DF_1_NODELETE` to say so.
+    pub(crate) exports_unique_symbols: bool,
 }
 
 pub(crate) struct SegmentLayouts {
@@ -173,7 +869,7 @@ pub(crate) struct SegmentLayouts {
     pub(crate) segments: Vec<SegmentLayout>,
 }
 
-#[derive(Default, Clone)]
+#[derive(Default, Clone, Hash)]
 pub(crate) struct SegmentLayout {
     pub(crate) id: ProgramSegmentId,
     pub(crate) sizes: OutputRecordLayout,
@@ -197,7 +893,7 @@ pub(crate) struct MergedStringStartAddresses {
 }
 
 /// Address information for a symbol or section.
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub(crate) struct Resolution {
     // TODO: Experiment with putting these in separate vectors.
     pub(crate) value: ResolutionValue,
@@ -208,7 +904,7 @@ pub(crate) struct Resolution {
     pub(crate) kind: TargetResolutionKind,
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub(crate) enum ResolutionValue {
     /// An absolute value.
     Absolute(u64),
@@ -247,6 +943,16 @@ struct InternalLayoutState<'data> {
     identity: String,
     header_info: Option<HeaderInfo>,
     dynamic_linker: Option<CString>,
+    undefined_symbols: Vec<UndefinedSymbol<'data>>,
+    section_symbol_indexes: OutputSectionMap<u32>,
+}
+
+/// A global symbol that's referenced, but not defined, by the objects that make up a relocatable
+/// output. We write these to our symbol table so that the final link can resolve them.
+pub(crate) struct UndefinedSymbol<'data> {
+    pub(crate) name: &'data [u8],
+    /// Whether all references to the symbol were weak.
+    pub(crate) is_weak: bool,
 }
 
 pub(crate) struct EpilogueLayoutState<'data> {
@@ -254,7 +960,16 @@ pub(crate) struct EpilogueLayoutState<'data> {
     internal_symbols: InternalSymbols,
 
     dynamic_symbol_definitions: Vec<DynamicSymbolDefinition<'data>>,
+    /// The versions that we define in `.gnu.version_d`, other than the base version.
+    version_definitions: Vec<VersionDefinition<'data>>,
     gnu_hash_layout: GnuHashLayout,
+    sysv_hash_layout: SysvHashLayout,
+    gnu_properties: GnuProperties,
+    abi_tag: AbiTag,
+    gdb_index: Option<GdbIndex>,
+    has_text_relocations: bool,
+    has_init: bool,
+    has_fini: bool,
 }
 
 #[derive(Default)]
@@ -265,14 +980,27 @@ pub(crate) struct GnuHashLayout {
     pub(crate) symbol_base: u32,
 }
 
+#[derive(Default, Clone, Copy)]
+pub(crate) struct SysvHashLayout {
+    pub(crate) bucket_count: u32,
+    /// The number of entries in the chain array, which must be the same as the number of entries in
+    /// the dynamic symbol table.
+    pub(crate) chain_count: u32,
+}
+
 pub(crate) struct EpilogueLayout<'data> {
     pub(crate) mem_sizes: OutputSectionPartMap<u64>,
     pub(crate) file_sizes: OutputSectionPartMap<usize>,
     pub(crate) internal_symbols: InternalSymbols,
     pub(crate) strings_offset_start: u32,
     pub(crate) gnu_hash_layout: GnuHashLayout,
+    pub(crate) sysv_hash_layout: SysvHashLayout,
     pub(crate) dynamic_symbol_definitions: Vec<DynamicSymbolDefinition<'data>>,
+    pub(crate) version_definitions: Vec<VersionDefinition<'data>>,
     pub(crate) dynstr_offset_start: u32,
+    pub(crate) gnu_properties: GnuProperties,
+    pub(crate) abi_tag: AbiTag,
+    pub(crate) gdb_index: Option<GdbIndex>,
 }
 
 pub(crate) struct ObjectLayout<'data> {
@@ -282,13 +1010,39 @@ pub(crate) struct ObjectLayout<'data> {
     pub(crate) mem_sizes: OutputSectionPartMap<u64>,
     pub(crate) file_sizes: OutputSectionPartMap<usize>,
     pub(crate) sections: Vec<SectionSlot<'data>>,
+    /// The indexes of our sections in the order in which they're placed in the output.
+    pub(crate) section_order: Vec<object::SectionIndex>,
     pub(crate) section_resolutions: Vec<Option<Resolution>>,
     pub(crate) strtab_offset_start: u32,
     pub(crate) plt_relocations: Vec<IfuncRelocation>,
     /// The memory address of the start of this object's allocation within .eh_frame.
     pub(crate) eh_frame_start_address: u64,
+    /// Where each of the CIEs in our .eh_frame goes, ordered by input offset.
+    pub(crate) cies: Vec<CieLayout>,
     pub(crate) start_symbol_id: SymbolId,
     pub(crate) num_symbols: usize,
+    /// The index in the output symbol table of each of our symbols, or 0 if the symbol isn't
+    /// there. Only populated when `--emit-relocs` is active.
+    pub(crate) symtab_indexes: Vec<u32>,
+}
+
+/// Where a CIE from an input object's .eh_frame ends up in the output. CIEs that we write go at the
+/// start of the object's allocation within .eh_frame, in input order, followed by the FDEs.
+pub(crate) struct CieLayout {
+    /// The offset of the CIE within the input .eh_frame section.
+    pub(crate) input_offset: u32,
+    /// The address of the CIE in the output. For a duplicate, this is the address of the identical
+    /// CIE that was written instead.
+    pub(crate) address: u64,
+    /// Set if this CIE is identical to another CIE, in which case we don't write it.
+    pub(crate) duplicate_of: Option<CieId>,
+}
+
+/// Identifies a CIE by the object that it's from and its index within that object's CIEs.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CieId {
+    pub(crate) file_id: FileId,
+    pub(crate) index: usize,
 }
 
 pub(crate) struct InternalLayout<'data> {
@@ -311,6 +1065,13 @@ pub(crate) struct InternalLayout<'data> {
     pub(crate) header_info: HeaderInfo,
     pub(crate) internal_symbols: InternalSymbols,
     pub(crate) dynamic_linker: Option<CString>,
+    /// Symbols that we need to write as undefined in a relocatable output.
+    pub(crate) undefined_symbols: Vec<UndefinedSymbol<'data>>,
+    /// The index in the output symbol table of each of `undefined_symbols`.
+    pub(crate) undefined_symbol_indexes: AHashMap<&'data [u8], u32>,
+    /// The index in the output symbol table of the section symbol for each output section, or 0 if
+    /// the section doesn't have one. Section symbols are only written for relocatable outputs.
+    pub(crate) section_symbol_indexes: OutputSectionMap<u32>,
 }
 
 pub(crate) struct InternalSymbols {
@@ -326,6 +1087,23 @@ pub(crate) struct DynamicLayout<'data> {
     /// The name we'll put into the binary to tell the dynamic loader what to load.
     pub(crate) lib_name: &'data [u8],
 
+    /// Whether we should emit a DT_NEEDED entry. This is false for --as-needed libraries that
+    /// didn't end up being used.
+    pub(crate) is_needed: bool,
+
+    /// The versions that we need from this shared object, which we declare in `.gnu.version_r`.
+    pub(crate) needed_versions: Vec<NeededVersion<'data>>,
+
+    /// Whether our entry in `.gnu.version_r` is the last one.
+    pub(crate) has_last_version_requirement: bool,
+
+    /// The `.gnu.version` entry of each of our symbols that we import, in .dynsym order.
+    pub(crate) symbol_versions: Vec<u16>,
+
+    /// The data objects that we copy into .bss, each given by the symbol that its copy relocation
+    /// refers to and the address of the copy.
+    pub(crate) copy_relocations: Vec<(SymbolId, u64)>,
+
     /// The offset in .dynstr at which we'll start writing.
     pub(crate) dynstr_start_offset: u64,
 
@@ -372,7 +1150,10 @@ trait SymbolRequestHandler<'data>: std::fmt::Display {
         }
         if common.symbol_states[local_index] == TargetResolutionKind::None {
             let symbol_kind = self.load_symbol(symbol_id, local_index, resources, queue)?;
-            if symbol_kind == SymbolKind::IFunc {
+            // In a relocatable output, calls to an ifunc are left for the final link to resolve.
+            if symbol_kind == SymbolKind::IFunc
+                && resources.symbol_db.args.output_kind != OutputKind::Relocatable
+            {
                 common = self.common_mut();
                 common.mem_sizes.got += elf::GOT_ENTRY_SIZE;
                 common.mem_sizes.plt += elf::PLT_ENTRY_SIZE;
@@ -407,16 +1188,14 @@ trait SymbolRequestHandler<'data>: std::fmt::Display {
                 if common.symbol_states[local_index] < TargetResolutionKind::Got {
                     common.symbol_states[local_index] = TargetResolutionKind::Got;
                     common.mem_sizes.got += elf::GOT_ENTRY_SIZE;
-                    if resources.symbol_db.args.is_relocatable() {
-                        match resources.symbol_db.symbol_value_kind(symbol_id) {
-                            ValueKind::Address => {
-                                common.mem_sizes.rela_dyn_relative += elf::RELA_ENTRY_SIZE;
-                            }
-                            ValueKind::Dynamic => {
-                                common.mem_sizes.rela_dyn_glob_dat += elf::RELA_ENTRY_SIZE;
-                            }
-                            ValueKind::Absolute => {}
+                    match resources.symbol_db.symbol_value_kind(symbol_id) {
+                        ValueKind::Address if resources.symbol_db.args.is_relocatable() => {
+                            common.mem_sizes.rela_dyn_relative += elf::RELA_ENTRY_SIZE;
+                        }
+                        ValueKind::Dynamic => {
+                            common.mem_sizes.rela_dyn_glob_dat += elf::RELA_ENTRY_SIZE;
                         }
+                        ValueKind::Address | ValueKind::Absolute => {}
                     }
                 }
                 if matches!(
@@ -428,11 +1207,11 @@ trait SymbolRequestHandler<'data>: std::fmt::Display {
                     common.mem_sizes.plt += elf::PLT_ENTRY_SIZE;
                 }
             }
-            TargetResolutionKind::GotTlsOffset => {
-                if common.symbol_states[local_index] < TargetResolutionKind::Got {
-                    common.symbol_states[local_index] = TargetResolutionKind::GotTlsOffset;
-                    common.mem_sizes.got += elf::GOT_ENTRY_SIZE;
-                }
+            TargetResolutionKind::GotTlsOffset
+                if common.symbol_states[local_index] < TargetResolutionKind::Got =>
+            {
+                common.symbol_states[local_index] = TargetResolutionKind::GotTlsOffset;
+                common.mem_sizes.got += elf::GOT_ENTRY_SIZE;
             }
             TargetResolutionKind::GotTlsDouble => match &common.symbol_states[local_index] {
                 TargetResolutionKind::Value => {
@@ -462,9 +1241,6 @@ trait SymbolRequestHandler<'data>: std::fmt::Display {
         resources: &GraphResources<'data, 'scope>,
         queue: &mut LocalWorkQueue,
     ) -> Result<SymbolKind>;
-
-    /// Returns whether `local_index` is a weak definition.
-    fn is_weak(&self, local_index: usize) -> bool;
 }
 
 impl<'data> SymbolRequestHandler<'data> for ObjectLayoutState<'data> {
@@ -504,7 +1280,7 @@ impl<'data> SymbolRequestHandler<'data> for ObjectLayoutState<'data> {
                 self.load_sections(resources, queue)?;
             }
             object::SymbolSection::Common => {
-                let common = CommonSymbol::new(&local_symbol)?;
+                let common = CommonSymbol::new(&local_symbol, symbol_id, resources.symbol_db)?;
                 *self
                     .state
                     .common
@@ -516,14 +1292,6 @@ impl<'data> SymbolRequestHandler<'data> for ObjectLayoutState<'data> {
         Ok(symbol_kind)
     }
 
-    fn is_weak(&self, local_index: usize) -> bool {
-        let object_symbol_index = object::SymbolIndex(local_index);
-        self.object
-            .symbol_by_index(object_symbol_index)
-            .map(|local_symbol| local_symbol.is_weak())
-            .unwrap_or(false)
-    }
-
     fn file_id(&self) -> FileId {
         self.state.common.file_id
     }
@@ -564,15 +1332,9 @@ impl<'data> SymbolRequestHandler<'data> for DynamicLayoutState<'data> {
         let name = symbol.name_bytes()?;
         self.common.mem_sizes.dynstr += name.len() as u64 + 1;
         self.common.mem_sizes.dynsym += crate::elf::SYMTAB_ENTRY_SIZE;
+        self.mark_needed();
         Ok(SymbolKind::Regular)
     }
-
-    fn is_weak(&self, local_index: usize) -> bool {
-        self.object
-            .symbol_by_index(object::SymbolIndex(local_index))
-            .map(|sym| sym.is_local())
-            .unwrap_or(false)
-    }
 }
 
 impl<'data> SymbolRequestHandler<'data> for InternalLayoutState<'data> {
@@ -594,11 +1356,6 @@ impl<'data> SymbolRequestHandler<'data> for InternalLayoutState<'data> {
         Ok(SymbolKind::Regular)
     }
 
-    fn is_weak(&self, _local_index: usize) -> bool {
-        // None of our internal symbols are currently weak.
-        false
-    }
-
     fn start_symbol_id(&self) -> SymbolId {
         // Internal-layout always starts from the undefined symbol.
         SymbolId::undefined()
@@ -624,11 +1381,6 @@ impl<'data> SymbolRequestHandler<'data> for EpilogueLayoutState<'data> {
         Ok(SymbolKind::Regular)
     }
 
-    fn is_weak(&self, _local_index: usize) -> bool {
-        // Custom section start/stop symbols are currently never weak.
-        false
-    }
-
     fn start_symbol_id(&self) -> SymbolId {
         self.common.start_symbol_id
     }
@@ -686,6 +1438,8 @@ impl CommonLayoutState {
         // symtab
         memory_offsets.symtab_locals += self.mem_sizes.symtab_locals;
         memory_offsets.symtab_globals += self.mem_sizes.symtab_globals;
+        memory_offsets.symtab_shndx_locals += self.mem_sizes.symtab_shndx_locals;
+        memory_offsets.symtab_shndx_globals += self.mem_sizes.symtab_shndx_globals;
 
         strtab_offset_start
     }
@@ -694,7 +1448,7 @@ impl CommonLayoutState {
         &'state self,
         memory_offsets: &OutputSectionPartMap<u64>,
         symbol_db: &'state SymbolDb,
-    ) -> GlobalAddressEmitter {
+    ) -> GlobalAddressEmitter<'state> {
         GlobalAddressEmitter {
             next_got_address: memory_offsets.got,
             next_plt_address: memory_offsets.plt,
@@ -710,10 +1464,23 @@ impl CommonLayoutState {
 struct ObjectLayoutState<'data> {
     input: InputRef<'data>,
     object: &'data File<'data>,
+    decompressed_sections: &'data DecompressedSections,
     state: ObjectLayoutMutableState<'data>,
     section_frame_data: Vec<SectionFrameData>,
     /// Dynamic symbols defined by this object.
     dynamic_symbol_definitions: Vec<DynamicSymbolDefinition<'data>>,
+    /// Properties from this object's `.note.gnu.property` section, if any.
+    gnu_properties: GnuProperties,
+    /// The tag from this object's `.note.ABI-tag` section, if any.
+    abi_tag: AbiTag,
+    /// Whether any of our read-only sections need dynamic relocations. Only permitted with
+    /// `-z notext`.
+    has_text_relocations: bool,
+    /// Whether we're building a shared object and one of our sections accesses TLS using the
+    /// initial-exec or local-exec models, which need the TLS block to be allocated at startup.
+    uses_static_tls: bool,
+    /// Whether we export a `STB_GNU_UNIQUE` symbol.
+    exports_unique_symbols: bool,
 }
 
 /// The parts of `ObjectLayoutState` that we mutate during layout. Separate so that we can pass
@@ -760,7 +1527,7 @@ struct LocalWorkQueue {
 }
 
 /// What kind of resolution we want for a symbol or section.
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default, Hash)]
 pub(crate) enum TargetResolutionKind {
     /// No resolution.
     #[default]
@@ -792,6 +1559,45 @@ struct DynamicLayoutState<'data> {
     input: InputRef<'data>,
     common: CommonLayoutState,
     lib_name: &'data [u8],
+
+    /// Whether we were linked with --as-needed, in which case we only emit DT_NEEDED if one of our
+    /// symbols gets used.
+    as_needed: bool,
+
+    /// Whether we've allocated space for our DT_NEEDED entry.
+    is_needed: bool,
+
+    /// The versions that we need to declare that we require from this shared object.
+    needed_versions: Vec<NeededVersion<'data>>,
+
+    /// Whether our entry in `.gnu.version_r` is the last one.
+    has_last_version_requirement: bool,
+
+    /// The `.gnu.version` entry of each of our symbols that we import, in .dynsym order.
+    symbol_versions: Vec<u16>,
+
+    /// Our data objects that we copy into the executable's .bss, keyed by their address in this
+    /// shared object.
+    copy_relocations: BTreeMap<u64, CopyRelocation>,
+
+    /// The indexes of our defined data symbols, keyed by address. Built when we first need to copy
+    /// an object, so that we can find its aliases.
+    data_symbols_by_address: Option<AHashMap<u64, Vec<usize>>>,
+
+    /// The executable's definitions of the objects in `copy_relocations`. These take the place of
+    /// our definitions at runtime, so the dynamic loader needs to be able to find them.
+    dynamic_symbol_definitions: Vec<DynamicSymbolDefinition<'data>>,
+}
+
+/// Space in .bss for a copy of a data object from a shared object.
+struct CopyRelocation {
+    size: u64,
+    alignment: Alignment,
+    /// The symbol that the copy relocation refers to.
+    symbol_id: SymbolId,
+    /// The indexes of all of the shared object's symbols for the object, which the executable
+    /// defines at the copy.
+    symbol_indexes: Vec<usize>,
 }
 
 #[derive(Clone, Copy)]
@@ -799,6 +1605,24 @@ pub(crate) struct DynamicSymbolDefinition<'data> {
     pub(crate) symbol_id: SymbolId,
     pub(crate) name: &'data [u8],
     pub(crate) hash: u32,
+    /// The index of the symbol's version, which goes in `.gnu.version`.
+    pub(crate) version: u16,
+}
+
+/// A version that we need from a shared object.
+#[derive(Clone, Copy)]
+pub(crate) struct NeededVersion<'data> {
+    pub(crate) name: &'data [u8],
+    /// The index by which `.gnu.version` refers to the version.
+    pub(crate) index: u16,
+}
+
+/// A version from a version script that we define in `.gnu.version_d`.
+#[derive(Debug)]
+pub(crate) struct VersionDefinition<'data> {
+    pub(crate) name: &'data [u8],
+    /// The names of the versions that this version inherits from. Each is also defined by us.
+    pub(crate) parents: Vec<&'data [u8]>,
 }
 
 #[derive(Debug)]
@@ -812,6 +1636,16 @@ pub(crate) struct Section<'data> {
     pub(crate) alignment: Alignment,
     pub(crate) resolution_kind: TargetResolutionKind,
     packed: bool,
+    /// The dynamic relocations that applying our relocations will produce. Knowing these up front
+    /// lets the writer give each section its own part of `.rela.dyn`, so that sections can be
+    /// written in parallel.
+    pub(crate) dynamic_relocations: DynamicRelocationCounts,
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+pub(crate) struct DynamicRelocationCounts {
+    pub(crate) relative: usize,
+    pub(crate) glob_dat: usize,
 }
 
 struct FileWorker<'data> {
@@ -824,7 +1658,7 @@ struct FileWorker<'data> {
 /// slices. This means that if we were linking on a 32 bit system that we'd be limited to file
 /// offsets that were 32 bits. This isn't a loss though, since we couldn't mmap an output file where
 /// that would be a problem on a 32 bit system.
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub(crate) struct OutputRecordLayout {
     pub(crate) file_size: usize,
     pub(crate) mem_size: u64,
@@ -838,6 +1672,10 @@ struct GraphResources<'data, 'scope> {
 
     version_script: Option<&'data VersionScript>,
 
+    dynamic_list: Option<&'data DynamicList>,
+
+    diagnostics: &'data Diagnostics,
+
     worker_slots: Vec<Mutex<WorkerSlot<'data>>>,
 
     errors: Mutex<Vec<Error>>,
@@ -860,6 +1698,9 @@ struct GraphResources<'data, 'scope> {
 #[derive(Copy, Clone, Debug)]
 enum WorkItem {
     LoadGlobalSymbol(SymbolRequest),
+
+    /// Copy a data object from a shared object into our .bss. See `DynamicRelocationKind::Copy`.
+    CopyRelocateSymbol(SymbolId),
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -869,7 +1710,7 @@ struct SymbolRequest {
 }
 
 impl<'data> Layout<'data> {
-    pub(crate) fn internal(&self) -> &InternalLayout {
+    pub(crate) fn internal(&self) -> &InternalLayout<'_> {
         let Some(FileLayout::Internal(i)) = self.file_layouts.first() else {
             panic!("Internal layout not found at expected offset");
         };
@@ -880,7 +1721,35 @@ impl<'data> Layout<'data> {
         self.symbol_db.args
     }
 
-    pub(crate) fn symbol_debug(&self, symbol_id: SymbolId) -> SymbolDebug {
+    /// Returns the number of entries in `.gnu.version_r`, which has one for each shared object that
+    /// we need versions from.
+    pub(crate) fn num_version_requirements(&self) -> usize {
+        self.file_layouts
+            .iter()
+            .filter(|file| {
+                matches!(file, FileLayout::Dynamic(dynamic) if !dynamic.needed_versions.is_empty())
+            })
+            .count()
+    }
+
+    /// Returns the number of entries in `.gnu.version_d`, including the base version, or 0 if we
+    /// don't define any versions.
+    pub(crate) fn num_version_definitions(&self) -> usize {
+        let Some(FileLayout::Epilogue(epilogue)) = self.file_layouts.last() else {
+            return 0;
+        };
+        if epilogue.version_definitions.is_empty() {
+            0
+        } else {
+            epilogue.version_definitions.len() + 1
+        }
+    }
+
+    pub(crate) fn emulation(&self) -> Emulation {
+        self.symbol_db.emulation
+    }
+
+    pub(crate) fn symbol_debug(&self, symbol_id: SymbolId) -> SymbolDebug<'_, '_> {
         self.symbol_db.symbol_debug(symbol_id)
     }
 
@@ -943,13 +1812,42 @@ impl<'data> Layout<'data> {
     }
 
     /// Returns the memory address of the end of the TLS segment including any padding required to
-    /// make sure that the TCB will be usize-aligned.
+    /// make its size a multiple of its alignment. This is where the thread pointer points at
+    /// runtime, since the runtime places the TLS block at `TP - align_up(p_memsz, p_align)`.
     pub(crate) fn tls_end_address(&self) -> u64 {
+        let tdata = &self.section_layouts.built_in(output_section_id::TDATA);
         let tbss = &self.section_layouts.built_in(output_section_id::TBSS);
-        let tls_end = tbss.mem_offset + tbss.mem_size;
-        // If the end of the TLS segment isn't usize-aligned, then padding will be inserted so
-        // that the TCB is properly aligned.
-        alignment::USIZE.align_up(tls_end)
+        let tls_start = tdata.mem_offset;
+        let tls_size = tbss.mem_offset + tbss.mem_size - tls_start;
+        tls_start + tdata.alignment.max(tbss.alignment).align_up(tls_size)
+    }
+
+    /// Returns the address that the thread pointer would have if the TLS segment were loaded at its
+    /// link-time address. Offsets from the thread pointer to TLS variables are relative to this.
+    /// x86-64 puts the TLS block just before the thread pointer. AArch64 puts it after a 16 byte
+    /// thread control block that the thread pointer points to. On RISC-V, the thread pointer points
+    /// at the start of the TLS block.
+    pub(crate) fn thread_pointer_address(&self) -> u64 {
+        match self.emulation() {
+            Emulation::X86_64 => self.tls_end_address(),
+            Emulation::RiscV64 => self.tls_start_address(),
+            Emulation::AArch64 => {
+                let tdata = &self.section_layouts.built_in(output_section_id::TDATA);
+                let tbss = &self.section_layouts.built_in(output_section_id::TBSS);
+                let tcb_size = tdata.alignment.max(tbss.alignment).align_up(16);
+                self.tls_start_address().wrapping_sub(tcb_size)
+            }
+        }
+    }
+
+    /// Returns the address that DTP-relative offsets, such as those passed to `__tls_get_addr`, are
+    /// relative to. That's the start of the TLS segment, except on RISC-V, where the offsets are
+    /// biased.
+    pub(crate) fn dtp_address(&self) -> u64 {
+        match self.emulation() {
+            Emulation::X86_64 | Emulation::AArch64 => self.tls_start_address(),
+            Emulation::RiscV64 => self.tls_start_address() + crate::riscv64::DTP_OFFSET,
+        }
     }
 
     pub(crate) fn vma_of_section(&self, section_id: OutputSectionId) -> u64 {
@@ -960,9 +1858,64 @@ impl<'data> Layout<'data> {
         self.section_layouts.get(section_id).file_size as u64
     }
 
+    /// Returns the index in the output symbol table of the supplied symbol, if we copied it there.
+    /// Only available when `--emit-relocs` is active.
+    /// Returns the index in the output symbol table of the section symbol for `section_id`, if it
+    /// has one.
+    pub(crate) fn section_symbol_index(&self, section_id: OutputSectionId) -> Option<u32> {
+        let index = *self.internal().section_symbol_indexes.get(section_id);
+        (index != 0).then_some(index)
+    }
+
+    /// Returns the index in the output symbol table of the undefined symbol with the supplied name,
+    /// if we wrote one.
+    pub(crate) fn undefined_symtab_index(&self, name: &[u8]) -> Option<u32> {
+        self.internal().undefined_symbol_indexes.get(name).copied()
+    }
+
+    /// Returns what to subtract from an address in `section_id` to get the value of a symbol
+    /// defined there. In a relocatable output, symbol values are offsets within their section.
+    pub(crate) fn symbol_value_base(&self, section_id: OutputSectionId) -> u64 {
+        if self.args().output_kind == OutputKind::Relocatable {
+            self.vma_of_section(section_id)
+        } else {
+            0
+        }
+    }
+
+    pub(crate) fn output_symtab_index(&self, symbol_id: SymbolId) -> Option<u32> {
+        let file_id = self.symbol_db.file_id_for_symbol(symbol_id);
+        let FileLayout::Object(object) = &self.file_layouts[file_id.as_usize()] else {
+            return None;
+        };
+        let index = *object
+            .symtab_indexes
+            .get(symbol_id.offset_from(object.start_symbol_id))?;
+        (index != 0).then_some(index)
+    }
+
     pub(crate) fn is_file_loaded(&self, file_id: FileId) -> bool {
         !matches!(self.file_layouts[file_id.as_usize()], FileLayout::NotLoaded)
     }
+
+    /// Returns the address of the section with the supplied index in the supplied object. For
+    /// sections that aren't loaded at runtime, this is the offset within the output section.
+    pub(crate) fn input_section_address(
+        &self,
+        file_id: FileId,
+        section_index: object::SectionIndex,
+    ) -> Result<u64> {
+        let FileLayout::Object(object) = &self.file_layouts[file_id.as_usize()] else {
+            bail!("Expected {file_id} to be an object");
+        };
+        object
+            .section_resolutions
+            .get(section_index.0)
+            .and_then(Option::as_ref)
+            .with_context(|| format!("Section {} of {file_id} wasn't loaded", section_index.0))?
+            .value
+            .address_or_value()
+    }
 }
 
 fn layout_sections(
@@ -1044,6 +1997,7 @@ fn compute_segment_layout(
     section_layouts: &OutputSectionMap<OutputRecordLayout>,
     output_sections: &OutputSections,
     header_info: &HeaderInfo,
+    common_page_size: Alignment,
 ) -> SegmentLayouts {
     struct Record {
         segment_id: ProgramSegmentId,
@@ -1072,9 +2026,14 @@ fn compute_segment_layout(
             );
         }
         OrderEvent::SegmentEnd(segment_id) => {
-            let record = active_records
+            let mut record = active_records
                 .remove(&segment_id)
                 .expect("SegmentEnd without matching SegmentStart");
+            if segment_id == crate::program_segments::RELRO && record.mem_end > record.mem_start {
+                // The section following the RELRO segment is placed on a new page, so we can cover
+                // the remainder of the page. Our file size covers only the actual RELRO data.
+                record.mem_end = common_page_size.align_up(record.mem_end);
+            }
             complete.push(record);
         }
         OrderEvent::Section(section_id, _section_details) => {
@@ -1082,7 +2041,12 @@ fn compute_segment_layout(
             for rec in active_records.values_mut() {
                 rec.file_start = rec.file_start.min(part.file_offset);
                 rec.mem_start = rec.mem_start.min(part.mem_offset);
-                rec.file_end = rec.file_end.max(part.file_offset + part.file_size);
+                // Sections without file contents, e.g. .tbss, extend the segment in memory only.
+                // Their file offset is wherever the previous section ended, which may include
+                // alignment padding that we don't want to count towards the file size.
+                if part.file_size > 0 {
+                    rec.file_end = rec.file_end.max(part.file_offset + part.file_size);
+                }
                 rec.mem_end = rec.mem_end.max(part.mem_offset + part.mem_size);
                 rec.alignment = rec.alignment.max(part.alignment);
             }
@@ -1095,10 +2059,17 @@ fn compute_segment_layout(
         .iter()
         .map(|&id| {
             let r = &complete[id.as_usize()];
+            if r.mem_start > r.mem_end {
+                // The segment doesn't contain any sections, e.g. GNU_STACK.
+                return SegmentLayout {
+                    id,
+                    sizes: OutputRecordLayout::default(),
+                };
+            }
             SegmentLayout {
                 id,
                 sizes: OutputRecordLayout {
-                    file_size: r.file_end - r.file_start,
+                    file_size: r.file_end.saturating_sub(r.file_start),
                     mem_size: r.mem_end - r.mem_start,
                     alignment: r.alignment,
                     file_offset: r.file_start,
@@ -1114,19 +2085,51 @@ fn compute_segment_layout(
 fn compute_total_section_part_sizes(
     layout_states: &mut [FileLayoutState],
     output_sections: &mut OutputSections,
-) -> OutputSectionPartMap<u64> {
+    args: &Args,
+    incremental: Option<&Incremental>,
+) -> Result<OutputSectionPartMap<u64>> {
     let mut total_sizes: OutputSectionPartMap<u64> =
         OutputSectionPartMap::with_size(output_sections.len());
     let mut sections_with_content: OutputSectionMap<bool> =
         OutputSectionMap::with_size(output_sections.len());
-    for file_state in layout_states.iter() {
-        if let Some(sizes) = file_state.mem_sizes() {
+    for file_state in layout_states.iter_mut() {
+        if let (Some(incremental), FileLayoutState::Object(object)) =
+            (incremental, &mut *file_state)
+        {
+            // Leave room for the object to grow, so that the next link can update it in place.
+            incremental.reserve(
+                &object.input,
+                object.object.data(),
+                &mut object.state.common.mem_sizes,
+            );
+        }
+        if let Some(sizes) = file_state.mem_sizes_mut() {
+            if args.uses_relr() {
+                // Each relative relocation becomes an address in .relr.dyn. We allocate enough
+                // space for them all, since we don't know until we write them how well they'll
+                // encode.
+                sizes.relr_dyn +=
+                    sizes.rela_dyn_relative / elf::RELA_ENTRY_SIZE * elf::RELR_ENTRY_SIZE;
+                sizes.rela_dyn_relative = 0;
+            }
             total_sizes.merge(sizes);
         }
         if let Some(common) = file_state.common() {
             sections_with_content.merge(&common.sections_with_content, |a, b| a | b);
         }
     }
+    if args.needs_dynamic() && args.hash_style.includes_sysv() {
+        let Some(FileLayoutState::Epilogue(epilogue)) = layout_states.last_mut() else {
+            panic!("Internal error, epilogue must be last");
+        };
+        epilogue.allocate_sysv_hash(&mut total_sizes)?;
+    }
+    if total_sizes.gnu_version_r > 0 || total_sizes.gnu_version_d > 0 {
+        let Some(FileLayoutState::Epilogue(epilogue)) = layout_states.last_mut() else {
+            panic!("Internal error, epilogue must be last");
+        };
+        epilogue.allocate_versym(&mut total_sizes);
+    }
     let FileLayoutState::Internal(internal_layout) =
         &mut layout_states[INTERNAL_FILE_ID.as_usize()]
     else {
@@ -1136,8 +2139,9 @@ fn compute_total_section_part_sizes(
         &mut total_sizes,
         sections_with_content,
         output_sections,
+        args,
     );
-    total_sizes
+    Ok(total_sizes)
 }
 
 /// Returns the starting memory address for each alignment within each segment.
@@ -1161,6 +2165,8 @@ fn find_required_sections<'data>(
     symbol_db: &SymbolDb<'data>,
     output_sections: &OutputSections<'data>,
     version_script: Option<&'data VersionScript>,
+    dynamic_list: Option<&'data DynamicList>,
+    diagnostics: &'data Diagnostics,
 ) -> Result<Vec<FileLayoutState<'data>>> {
     let num_workers = file_states.len();
     let (worker_slots, workers) = create_worker_slots(file_states, output_sections);
@@ -1179,6 +2185,8 @@ fn find_required_sections<'data>(
         done: AtomicBool::new(false),
         output_sections,
         version_script,
+        dynamic_list,
+        diagnostics,
     };
 
     workers
@@ -1353,6 +2361,17 @@ impl LocalWorkQueue {
             WorkItem::LoadGlobalSymbol(symbol_request),
         )
     }
+
+    /// Asks the shared object that defines `symbol_id` to copy it into our .bss.
+    fn send_copy_relocation_request(&mut self, symbol_id: SymbolId, resources: &GraphResources) {
+        let symbol_definition = resources.symbol_db.definition(symbol_id);
+        let symbol_file_id = resources.symbol_db.file_id_for_symbol(symbol_definition);
+        self.send_work(
+            resources,
+            symbol_file_id,
+            WorkItem::CopyRelocateSymbol(symbol_definition),
+        );
+    }
 }
 
 impl<'data, 'scope> GraphResources<'data, 'scope> {
@@ -1384,6 +2403,44 @@ impl<'data, 'scope> GraphResources<'data, 'scope> {
         }
     }
 
+    /// Returns whether we might export any of the symbols defined by our input objects as dynamic
+    /// symbols.
+    fn exports_dynamic_symbols(&self) -> bool {
+        let args = self.symbol_db.args;
+        args.output_kind == OutputKind::SharedObject
+            || (args.needs_dynamic()
+                && (args.export_all_dynamic_symbols || self.dynamic_list.is_some()))
+    }
+
+    /// Returns whether a non-hidden symbol with the supplied name should be exported as a dynamic
+    /// symbol. Shared objects and executables linked with `--export-dynamic` export all such
+    /// symbols, except those that the version script makes local. Other executables only export
+    /// symbols named in the dynamic list.
+    fn should_export_symbol(&self, name: &[u8]) -> bool {
+        let args = self.symbol_db.args;
+        if args.output_kind == OutputKind::SharedObject || args.export_all_dynamic_symbols {
+            !self
+                .version_script
+                .is_some_and(|script| script.is_local(name))
+        } else {
+            self.dynamic_list.is_some_and(|list| list.contains(name))
+        }
+    }
+
+    /// Returns the index of the version that a dynamic symbol with the supplied name should have.
+    /// Only shared objects define versions. Symbols that the version script doesn't assign to a
+    /// version get the base version, which is the same index as for unversioned global symbols.
+    fn symbol_version(&self, name: &[u8]) -> u16 {
+        if self.symbol_db.args.output_kind != OutputKind::SharedObject {
+            return object::elf::VER_NDX_GLOBAL;
+        }
+        self.version_script
+            .and_then(|script| script.version_of(name))
+            .map_or(object::elf::VER_NDX_GLOBAL, |index| {
+                elf::FIRST_DEFINED_VERSION_INDEX + index as u16
+            })
+    }
+
     fn shut_down(&self) {
         self.done.store(true, atomic::Ordering::SeqCst);
         // Wake up all sleeping threads so that they can shut down.
@@ -1406,6 +2463,15 @@ impl<'data> FileLayoutState<'data> {
             FileLayoutState::Epilogue(s) => s.finalise_sizes(symbol_db)?,
             FileLayoutState::NotLoaded => {}
         }
+        if output_sections.needs_symtab_shndx() {
+            if let Some(mem_sizes) = self.mem_sizes_mut() {
+                // One extended index for each symbol that we're writing to .symtab.
+                let entry_size = size_of::<elf::SymtabEntry>() as u64;
+                let index_size = size_of::<u32>() as u64;
+                mem_sizes.symtab_shndx_locals = mem_sizes.symtab_locals / entry_size * index_size;
+                mem_sizes.symtab_shndx_globals = mem_sizes.symtab_globals / entry_size * index_size;
+            }
+        }
         Ok(())
     }
 
@@ -1424,6 +2490,16 @@ impl<'data> FileLayoutState<'data> {
                         resources.symbol_db.symbol_debug(symbol_request.symbol_id),
                     )
                 }),
+            WorkItem::CopyRelocateSymbol(symbol_id) => {
+                let FileLayoutState::Dynamic(state) = self else {
+                    bail!(
+                        "Internal error: Requested copy relocation for {} from {self}, which isn't \
+                         a shared object",
+                        resources.symbol_db.symbol_debug(symbol_id)
+                    );
+                };
+                state.copy_relocate_symbol(symbol_id, resources)
+            }
         }
     }
 
@@ -1461,6 +2537,16 @@ impl<'data> FileLayoutState<'data> {
         }
     }
 
+    fn mem_sizes_mut(&mut self) -> Option<&mut OutputSectionPartMap<u64>> {
+        match self {
+            Self::Object(s) => Some(&mut s.state.common.mem_sizes),
+            Self::Internal(s) => Some(&mut s.common.mem_sizes),
+            Self::Dynamic(s) => Some(&mut s.common.mem_sizes),
+            Self::NotLoaded => None,
+            Self::Epilogue(s) => Some(&mut s.common.mem_sizes),
+        }
+    }
+
     pub(crate) fn common(&self) -> Option<&CommonLayoutState> {
         match self {
             Self::Object(s) => Some(&s.state.common),
@@ -1638,10 +2724,30 @@ impl<'data> Section<'data> {
         resources: &GraphResources<'data, 'scope>,
     ) -> Result<Section<'data>> {
         let object_section = worker.object.section_by_index(section_id)?;
-        let alignment = Alignment::new(object_section.align())?;
-        let size = object_section.size();
-        let section_data = object_section.data()?;
-        for (rel_offset, rel) in object_section.relocations() {
+        // The fragments of a packed section like `.init` are pieces of a single function, so they
+        // must stay in input order. Output sections are split into parts by alignment, so we put
+        // all such fragments in the same part.
+        let alignment = if unloaded.details.packed {
+            alignment::MIN
+        } else {
+            Alignment::new(worker.decompressed_sections.align(&object_section))?
+        };
+        let size = worker.decompressed_sections.size(&object_section);
+        let section_data = worker.decompressed_sections.data(&object_section)?;
+        // Relocations in debug info mustn't keep the code that they refer to alive. We instead
+        // resolve whatever they refer to that we did load once GC is done. See
+        // `resolve_debug_relocation_targets`.
+        let relocations = if is_debug_section(&object_section) {
+            None
+        } else {
+            Some(object_section.relocations())
+        };
+        let object::SectionFlags::Elf { sh_flags } = object_section.flags() else {
+            unreachable!();
+        };
+        let is_read_only = sh_flags & elf::shf::WRITE == 0;
+        let mut dynamic_relocations = DynamicRelocationCounts::default();
+        for (rel_offset, rel) in relocations.into_iter().flatten() {
             if let Some(action) = RelocationLayoutAction::new(
                 &rel,
                 &object_section,
@@ -1649,6 +2755,15 @@ impl<'data> Section<'data> {
                 &worker.state,
                 resources.symbol_db,
             )? {
+                if is_read_only && action.needs_dynamic_relocation() {
+                    worker.add_text_relocation(&action, &object_section, resources)?;
+                }
+                if action.uses_static_tls
+                    && resources.symbol_db.args.output_kind == OutputKind::SharedObject
+                {
+                    worker.uses_static_tls = true;
+                }
+                action.count_dynamic_relocation(&mut dynamic_relocations);
                 action.apply(resources, &mut worker.state, queue);
             }
         }
@@ -1660,6 +2775,7 @@ impl<'data> Section<'data> {
             data: section_data,
             resolution_kind: TargetResolutionKind::None,
             packed: unloaded.details.packed,
+            dynamic_relocations,
         };
         Ok(section)
     }
@@ -1728,6 +2844,9 @@ impl<'data> Section<'data> {
 struct RelocationLayoutAction {
     kind: RelocationLayoutActionKind,
     dynamic_relocation_kind: DynamicRelocationKind,
+    /// Whether the relocation accesses TLS via an offset from the thread pointer, which is only
+    /// possible if the TLS block is allocated at startup.
+    uses_static_tls: bool,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -1735,6 +2854,11 @@ enum DynamicRelocationKind {
     None,
     Relative,
     Dynamic,
+
+    /// A reference from an executable that isn't position-independent to a data object in a shared
+    /// object. Rather than needing a dynamic relocation at the place, we copy the object into our
+    /// .bss with a copy relocation and point both the place and the shared object at our copy.
+    Copy,
 }
 
 /// An action that we need to perform if we decide to use a particular relocation.
@@ -1756,16 +2880,33 @@ impl RelocationLayoutAction {
         match rel.target() {
             object::RelocationTarget::Symbol(local_sym_index) => {
                 let symbol_id = state.common.start_symbol_id.add_usize(local_sym_index.0);
+                if args.output_kind == OutputKind::Relocatable {
+                    return Ok(Some(Self::retained(
+                        RelocationLayoutActionKind::LoadSymbol(
+                            symbol_id,
+                            TargetResolutionKind::Value,
+                        ),
+                    )));
+                }
                 return Ok(Some(Self::for_symbol(
                     rel, rel_offset, section, symbol_db, symbol_id, args,
                 )?));
             }
             object::RelocationTarget::Section(local_section_index) => {
+                if args.output_kind == OutputKind::Relocatable {
+                    return Ok(Some(Self::retained(
+                        RelocationLayoutActionKind::LoadSection(
+                            local_section_index,
+                            TargetResolutionKind::Value,
+                        ),
+                    )));
+                }
                 return Ok(Some(Self::for_section(
                     rel,
                     rel_offset,
                     section,
                     args,
+                    symbol_db.emulation,
                     local_section_index,
                 )?));
             }
@@ -1774,6 +2915,17 @@ impl RelocationLayoutAction {
         Ok(None)
     }
 
+    /// Returns an action for a relocation that we're copying into a relocatable output rather than
+    /// applying. We still need whatever it refers to, but we don't relax it, allocate GOT or PLT
+    /// entries for it or emit a dynamic relocation for it, since that's up to the final link.
+    fn retained(kind: RelocationLayoutActionKind) -> RelocationLayoutAction {
+        RelocationLayoutAction {
+            kind,
+            dynamic_relocation_kind: DynamicRelocationKind::None,
+            uses_static_tls: false,
+        }
+    }
+
     fn for_symbol(
         rel: &object::Relocation,
         rel_offset: u64,
@@ -1783,19 +2935,20 @@ impl RelocationLayoutAction {
         args: &Args,
     ) -> Result<RelocationLayoutAction> {
         let symbol_value_kind = symbol_db.symbol_value_kind(symbol_db.definition(symbol_id));
-        let object::RelocationFlags::Elf { mut r_type } = rel.flags() else {
+        let object::RelocationFlags::Elf { r_type } = rel.flags() else {
             unreachable!();
         };
-        if let Some((_relaxation, new_r_type)) = Relaxation::new(
+        let rel_info = match Relaxation::new(
+            symbol_db.emulation,
             r_type,
             section.data()?,
             rel_offset,
             symbol_value_kind,
             args.output_kind,
         ) {
-            r_type = new_r_type;
-        }
-        let rel_info = RelocationKindInfo::from_raw(r_type)?;
+            Some((_relaxation, rel_info)) => rel_info,
+            None => RelocationKindInfo::from_raw(symbol_db.emulation, r_type)?,
+        };
         let resolution_kind = TargetResolutionKind::new(rel_info.kind)?;
         let dynamic_relocation_kind =
             match (args.is_relocatable(), rel_info.kind, symbol_value_kind) {
@@ -1803,13 +2956,21 @@ impl RelocationLayoutAction {
                     DynamicRelocationKind::Relative
                 }
                 (_, RelocationKind::Absolute | RelocationKind::Relative, ValueKind::Dynamic) => {
-                    DynamicRelocationKind::Dynamic
+                    if args.output_kind == OutputKind::DynamicExecutable
+                        && !args.is_relocatable()
+                        && symbol_db.is_dynamic_data_object(symbol_db.definition(symbol_id))?
+                    {
+                        DynamicRelocationKind::Copy
+                    } else {
+                        DynamicRelocationKind::Dynamic
+                    }
                 }
                 _ => DynamicRelocationKind::None,
             };
         let relocation_layout_action = RelocationLayoutAction {
             kind: RelocationLayoutActionKind::LoadSymbol(symbol_id, resolution_kind),
             dynamic_relocation_kind,
+            uses_static_tls: rel_info.kind.uses_static_tls(),
         };
         Ok(relocation_layout_action)
     }
@@ -1819,21 +2980,23 @@ impl RelocationLayoutAction {
         rel_offset: u64,
         section: &elf::Section,
         args: &Args,
+        emulation: Emulation,
         local_section_index: object::SectionIndex,
     ) -> Result<RelocationLayoutAction, Error> {
-        let object::RelocationFlags::Elf { mut r_type } = rel.flags() else {
+        let object::RelocationFlags::Elf { r_type } = rel.flags() else {
             unreachable!();
         };
-        if let Some((_relaxation, new_r_type)) = Relaxation::new(
+        let rel_info = match Relaxation::new(
+            emulation,
             r_type,
             section.data()?,
             rel_offset,
             ValueKind::Address,
             args.output_kind,
         ) {
-            r_type = new_r_type;
-        }
-        let rel_info = RelocationKindInfo::from_raw(r_type)?;
+            Some((_relaxation, rel_info)) => rel_info,
+            None => RelocationKindInfo::from_raw(emulation, r_type)?,
+        };
         let resolution_kind = TargetResolutionKind::new(rel_info.kind)?;
         let dynamic_relocation_kind =
             if args.is_relocatable() && matches!(rel_info.kind, RelocationKind::Absolute) {
@@ -1844,6 +3007,7 @@ impl RelocationLayoutAction {
         Ok(RelocationLayoutAction {
             kind: RelocationLayoutActionKind::LoadSection(local_section_index, resolution_kind),
             dynamic_relocation_kind,
+            uses_static_tls: rel_info.kind.uses_static_tls(),
         })
     }
 
@@ -1882,6 +3046,40 @@ impl RelocationLayoutAction {
             DynamicRelocationKind::Dynamic => {
                 state.common.mem_sizes.rela_dyn_glob_dat += elf::RELA_ENTRY_SIZE;
             }
+            DynamicRelocationKind::Copy => {
+                if let RelocationLayoutActionKind::LoadSymbol(symbol_id, _) = self.kind {
+                    queue.send_copy_relocation_request(symbol_id, resources);
+                }
+            }
+        }
+    }
+
+    fn count_dynamic_relocation(&self, counts: &mut DynamicRelocationCounts) {
+        match self.dynamic_relocation_kind {
+            DynamicRelocationKind::None | DynamicRelocationKind::Copy => {}
+            DynamicRelocationKind::Relative => counts.relative += 1,
+            DynamicRelocationKind::Dynamic => counts.glob_dat += 1,
+        }
+    }
+
+    fn needs_dynamic_relocation(&self) -> bool {
+        matches!(
+            self.dynamic_relocation_kind,
+            DynamicRelocationKind::Relative | DynamicRelocationKind::Dynamic
+        )
+    }
+
+    /// Undoes the change that `apply` made to the number of dynamic relocations that we need.
+    fn remove_dynamic_relocation(&self, mem_sizes: &mut OutputSectionPartMap<u64>) {
+        match self.dynamic_relocation_kind {
+            // The copy of the object stays even if nothing else refers to it.
+            DynamicRelocationKind::None | DynamicRelocationKind::Copy => {}
+            DynamicRelocationKind::Relative => {
+                mem_sizes.rela_dyn_relative -= elf::RELA_ENTRY_SIZE;
+            }
+            DynamicRelocationKind::Dynamic => {
+                mem_sizes.rela_dyn_glob_dat -= elf::RELA_ENTRY_SIZE;
+            }
         }
     }
 }
@@ -1927,6 +3125,8 @@ impl<'data> InternalLayoutState<'data> {
             identity: crate::identity::linker_identity(),
             header_info: None,
             dynamic_linker: None,
+            undefined_symbols: Vec::new(),
+            section_symbol_indexes: OutputSectionMap::with_size(output_sections.len()),
         };
 
         layout.merged_strings.for_each(|section_id, merged| {
@@ -1934,44 +3134,60 @@ impl<'data> InternalLayoutState<'data> {
                 *layout
                     .common
                     .mem_sizes
-                    .regular_mut(section_id, alignment::MIN) += merged.len;
+                    .regular_mut(section_id, merged.alignment) += merged.len;
             }
         });
 
-        // Allocate space to store the identify of the linker in the .comment section.
-        *layout
-            .common
-            .mem_sizes
-            .regular_mut(output_section_id::COMMENT, alignment::MIN) +=
-            layout.identity.len() as u64;
-
         // The first entry in the symbol table must be null. Similarly, the first string in the
         // strings table must be empty.
         layout.common.mem_sizes.symtab_locals = size_of::<elf::SymtabEntry>() as u64;
         layout.common.mem_sizes.symtab_strings = 1;
 
-        // Allocate a GOT entry that we can use for any references to undefined weak symbols.
-        layout.common.mem_sizes.got += elf::GOT_ENTRY_SIZE;
-
         layout
     }
 
     fn activate(&mut self, resources: &GraphResources) -> Result {
+        // Allocate space to store the identify of the linker in the .comment section, unless we've
+        // been asked to remove .comment.
+        if resources.symbol_db.args.should_remove_section(b".comment") {
+            self.identity.clear();
+        } else {
+            *self
+                .common
+                .mem_sizes
+                .regular_mut(output_section_id::COMMENT, alignment::MIN) +=
+                self.identity.len() as u64;
+        }
+
+        // Allocate a GOT entry that we can use for any references to undefined weak symbols. A
+        // relocatable output doesn't have a GOT, since its relocations are left for the final link.
+        if resources.symbol_db.args.output_kind != OutputKind::Relocatable {
+            self.common.mem_sizes.got += elf::GOT_ENTRY_SIZE;
+        }
         if resources.symbol_db.args.output_kind.is_executable() {
             self.load_entry_point(resources)?;
         }
-        if resources.symbol_db.args.tls_mode() == TlsMode::Preserve {
+        self.load_undefined_symbols(resources);
+        if resources.symbol_db.args.tls_mode() == TlsMode::Preserve
+            && resources.symbol_db.args.output_kind != OutputKind::Relocatable
+        {
             // Allocate space for a TLS module number and offset for use with TLSLD relocations.
             self.common.mem_sizes.got += elf::GOT_ENTRY_SIZE * 2;
             self.needs_tlsld_got_entry = true;
         }
 
-        if resources.symbol_db.args.is_relocatable() {
+        if resources.symbol_db.args.needs_dynamic() {
             // Allocate space for the null symbol.
             self.common.mem_sizes.dynstr += 1;
             self.common.mem_sizes.dynsym += size_of::<elf::SymtabEntry>() as u64;
         }
 
+        if let Some(rpath) = resources.symbol_db.args.rpath.as_ref() {
+            if resources.symbol_db.args.needs_dynamic() {
+                self.common.mem_sizes.dynstr += rpath.len() as u64 + 1;
+            }
+        }
+
         self.dynamic_linker = resources
             .symbol_db
             .args
@@ -1987,11 +3203,18 @@ impl<'data> InternalLayoutState<'data> {
     }
 
     fn load_entry_point(&mut self, resources: &GraphResources) -> Result {
+        let entry = resources
+            .symbol_db
+            .args
+            .linker_script
+            .as_ref()
+            .and_then(|script| script.entry.as_deref())
+            .unwrap_or("_start");
         let symbol_id = *resources
             .symbol_db
             .global_names
-            .get(&SymbolName::prehashed(b"_start"))
-            .context("Missing _start symbol")?;
+            .get(&SymbolName::prehashed(entry.as_bytes()))
+            .with_context(|| format!("Missing {entry} symbol"))?;
         self.entry_symbol_id = Some(symbol_id);
         let file_id = resources.symbol_db.file_id_for_symbol(symbol_id);
         resources.send_work(
@@ -2004,13 +3227,49 @@ impl<'data> InternalLayoutState<'data> {
         Ok(())
     }
 
+    /// Loads the definitions of any symbols named via `-u`, so that they're kept even if nothing
+    /// references them. Symbols referred to by `--defsym` expressions are loaded too, so that they
+    /// have a value once layout is complete.
+    fn load_undefined_symbols(&self, resources: &GraphResources) {
+        let args = resources.symbol_db.args;
+        let defsym_operands = args.defsym_operands();
+        for name in args
+            .undefined
+            .iter()
+            .map(String::as_str)
+            .chain(defsym_operands)
+        {
+            let Some(&symbol_id) = resources
+                .symbol_db
+                .global_names
+                .get(&SymbolName::prehashed(name.as_bytes()))
+            else {
+                continue;
+            };
+            let file_id = resources.symbol_db.file_id_for_symbol(symbol_id);
+            resources.send_work(
+                file_id,
+                WorkItem::LoadGlobalSymbol(SymbolRequest {
+                    symbol_id,
+                    target_resolution_kind: Default::default(),
+                }),
+            );
+        }
+    }
+
     fn finalise_sizes(&mut self, symbol_db: &SymbolDb) -> Result {
         if !symbol_db.args.strip_all {
             self.internal_symbols
                 .allocate_symbol_table_sizes(symbol_db, &mut self.common)?;
+            for symbol in &self.undefined_symbols {
+                self.common.mem_sizes.symtab_globals += size_of::<elf::SymtabEntry>() as u64;
+                self.common.mem_sizes.symtab_strings += symbol.name.len() as u64 + 1;
+            }
         }
 
-        self.common.mem_sizes.eh_frame_hdr += core::mem::size_of::<elf::EhFrameHdr>() as u64;
+        if symbol_db.args.output_kind != OutputKind::Relocatable {
+            self.common.mem_sizes.eh_frame_hdr += core::mem::size_of::<elf::EhFrameHdr>() as u64;
+        }
 
         Ok(())
     }
@@ -2020,6 +3279,7 @@ impl<'data> InternalLayoutState<'data> {
         total_sizes: &mut OutputSectionPartMap<u64>,
         sections_with_content: OutputSectionMap<bool>,
         output_sections: &mut OutputSections,
+        args: &Args,
     ) {
         use output_section_id::OrderEvent;
 
@@ -2060,40 +3320,33 @@ impl<'data> InternalLayoutState<'data> {
         // Compute output indexes of each of section.
         let mut next_output_index = 0;
         let mut output_section_indexes = vec![None; output_sections.len()];
+        let mut kept_section_ids = Vec::with_capacity(num_sections);
         output_sections.sections_and_segments_do(|event| {
             if let OrderEvent::Section(id, _) = event {
                 if keep_sections[id.as_usize()] {
                     output_section_indexes[id.as_usize()] = Some(next_output_index);
                     next_output_index += 1;
+                    kept_section_ids.push(id);
                 }
             }
         });
         output_sections.output_section_indexes = output_section_indexes;
 
-        // Determine which program segments contain sections that we're keeping.
-        let mut keep_segments = [false; crate::program_segments::MAX_SEGMENTS];
-        let mut active_segments = Vec::with_capacity(4);
-        output_sections.sections_and_segments_do(|event| match event {
-            OrderEvent::SegmentStart(segment_id) => active_segments.push(segment_id),
-            OrderEvent::SegmentEnd(segment_id) => active_segments.retain(|a| *a != segment_id),
-            OrderEvent::Section(section_id, _) => {
-                if keep_sections[section_id.as_usize()] {
-                    for segment_id in &active_segments {
-                        keep_segments[segment_id.as_usize()] = true;
-                    }
-                    active_segments.clear();
-                }
-            }
-        });
+        if args.output_kind == OutputKind::Relocatable && !args.strip_all {
+            self.allocate_section_symbols(&kept_section_ids, total_sizes, output_sections);
+        }
+
+        // A relocatable output doesn't have any program segments.
+        let keep_segments = segments_to_keep(output_sections, &keep_sections);
         let active_segment_ids = (0..crate::program_segments::MAX_SEGMENTS)
-            .filter(|i| keep_segments[*i])
+            .filter(|i| keep_segments[*i] && args.output_kind != OutputKind::Relocatable)
             .map(ProgramSegmentId::new)
             .collect();
 
         let header_info = HeaderInfo {
             num_output_sections_with_content: num_sections
                 .try_into()
-                .expect("output section count must fit in a u16"),
+                .expect("output section count must fit in a u32"),
 
             active_segment_ids,
         };
@@ -2119,6 +3372,42 @@ impl<'data> InternalLayoutState<'data> {
         self.header_info = Some(header_info);
     }
 
+    /// Allocates a local section symbol for each of the supplied output sections that can hold
+    /// symbols, so that the relocations that we copy into a relocatable output can refer to places
+    /// that don't have a symbol of their own. Section symbols follow the null symbol, so they're the
+    /// first entries in the symbol table.
+    fn allocate_section_symbols(
+        &mut self,
+        kept_section_ids: &[OutputSectionId],
+        total_sizes: &mut OutputSectionPartMap<u64>,
+        output_sections: &OutputSections,
+    ) {
+        let mut next_index = 1;
+        for &section_id in kept_section_ids {
+            if matches!(
+                output_sections.details(section_id).ty,
+                elf::Sht::Progbits
+                    | elf::Sht::Nobits
+                    | elf::Sht::Note
+                    | elf::Sht::InitArray
+                    | elf::Sht::FiniArray
+                    | elf::Sht::PreinitArray
+            ) {
+                *self.section_symbol_indexes.get_mut(section_id) = next_index;
+                next_index += 1;
+            }
+        }
+        let num_symbols = u64::from(next_index - 1);
+        let size = num_symbols * size_of::<elf::SymtabEntry>() as u64;
+        self.common.mem_sizes.symtab_locals += size;
+        total_sizes.symtab_locals += size;
+        if output_sections.needs_symtab_shndx() {
+            let size = num_symbols * size_of::<u32>() as u64;
+            self.common.mem_sizes.symtab_shndx_locals += size;
+            total_sizes.symtab_shndx_locals += size;
+        }
+    }
+
     fn finalise_layout(
         self,
         memory_offsets: &mut OutputSectionPartMap<u64>,
@@ -2131,17 +3420,20 @@ impl<'data> InternalLayoutState<'data> {
         assert_eq!(header_layout.file_offset, 0);
 
         // We need a GOT address to use for any relocations that point to undefined weak symbols.
+        let got_address = (symbol_db.args.output_kind != OutputKind::Relocatable).then(|| {
+            let address =
+                NonZeroU64::new(memory_offsets.got).expect("GOT address must never be zero");
+            memory_offsets.got += elf::GOT_ENTRY_SIZE;
+            address
+        });
         let undefined_symbol_resolution = Resolution {
             value: ResolutionValue::Absolute(0),
-            got_address: Some(
-                NonZeroU64::new(memory_offsets.got).expect("GOT address must never be zero"),
-            ),
+            got_address,
             // If anything ever actually tries to call the PLT for an undefined symbol, it's
             // undefined behaviour, so we can put whatever pointer we like here.
             plt_address: NonZeroU64::new(0xdead),
             kind: TargetResolutionKind::Plt,
         };
-        memory_offsets.got += elf::GOT_ENTRY_SIZE;
 
         let tlsld_got_entry = self.needs_tlsld_got_entry.then(|| {
             let address =
@@ -2158,6 +3450,23 @@ impl<'data> InternalLayoutState<'data> {
             resolutions_out,
         )?;
 
+        // Our undefined symbols are the first of our global symbols.
+        let entry_size = size_of::<elf::SymtabEntry>() as u64;
+        let symtab_start = section_layouts
+            .built_in(output_section_id::SYMTAB)
+            .mem_offset;
+        let first_global = (memory_offsets.symtab_globals - symtab_start) / entry_size;
+        let undefined_symbol_indexes = self
+            .undefined_symbols
+            .iter()
+            .enumerate()
+            .map(|(i, symbol)| {
+                let index = u32::try_from(first_global + i as u64)
+                    .context("Symbol table index overflowed 32 bits")?;
+                Ok((symbol.name, index))
+            })
+            .collect::<Result<AHashMap<_, _>>>()?;
+
         let strings_offset_start = self.common.finalise_layout(memory_offsets, section_layouts);
         Ok(InternalLayout {
             file_sizes: compute_file_sizes(&self.common.mem_sizes, output_sections),
@@ -2173,6 +3482,9 @@ impl<'data> InternalLayoutState<'data> {
             header_info: self
                 .header_info
                 .expect("we should have computed header info by now"),
+            undefined_symbols: self.undefined_symbols,
+            undefined_symbol_indexes,
+            section_symbol_indexes: self.section_symbol_indexes,
         })
     }
 }
@@ -2201,8 +3513,11 @@ impl InternalSymbols {
                 continue;
             }
 
-            common.mem_sizes.symtab_globals += size_of::<elf::SymtabEntry>() as u64;
             let symbol_name = symbol_db.symbol_name(symbol_id)?;
+            if !symbol_db.args.should_retain_symbol(symbol_name.bytes()) {
+                continue;
+            }
+            common.mem_sizes.symtab_globals += size_of::<elf::SymtabEntry>() as u64;
             common.mem_sizes.symtab_strings += symbol_name.len() as u64 + 1;
         }
         Ok(())
@@ -2239,6 +3554,15 @@ impl InternalSymbols {
                     let sec = &section_layouts.built_in(*section_id);
                     ResolutionValue::Address(sec.mem_offset + sec.mem_size)
                 }
+                InternalSymDefInfo::GlobalPointer => {
+                    let data = &section_layouts.built_in(output_section_id::DATA);
+                    ResolutionValue::Address(
+                        data.mem_offset + crate::riscv64::GLOBAL_POINTER_OFFSET,
+                    )
+                }
+                // `--defsym` expressions can refer to any symbol, so we fill in the value once all
+                // other symbols have been resolved. See `resolve_defsyms`.
+                InternalSymDefInfo::Defsym(_) => ResolutionValue::Absolute(0),
             };
             emitter.emit_resolution(symbol_id, value, resolutions_out)?;
         }
@@ -2263,7 +3587,15 @@ impl<'data> EpilogueLayoutState<'data> {
                 start_symbol_id: input_state.start_symbol_id,
             },
             dynamic_symbol_definitions: Default::default(),
+            version_definitions: Default::default(),
             gnu_hash_layout: Default::default(),
+            sysv_hash_layout: Default::default(),
+            gnu_properties: Default::default(),
+            abi_tag: Default::default(),
+            gdb_index: None,
+            has_text_relocations: false,
+            has_init: false,
+            has_fini: false,
         }
     }
 
@@ -2274,17 +3606,17 @@ impl<'data> EpilogueLayoutState<'data> {
         }
 
         if symbol_db.args.needs_dynamic() {
-            self.common.mem_sizes.dynamic += (elf_writer::NUM_EPILOGUE_DYNAMIC_ENTRIES
-                * core::mem::size_of::<crate::elf::DynamicEntry>())
+            self.common.mem_sizes.dynamic += (elf_writer::num_epilogue_dynamic_entries(
+                symbol_db.args,
+                self.has_text_relocations,
+                self.has_init,
+                self.has_fini,
+            ) * core::mem::size_of::<crate::elf::DynamicEntry>())
                 as u64;
         }
 
         let num_defs = self.dynamic_symbol_definitions.len();
-        // Our number of buckets is computed somewhat arbitrarily so that we have on average 2
-        // symbols per bucket, but then we round up to a power of two.
-        self.gnu_hash_layout.bucket_count = (num_defs / 2).next_power_of_two() as u32;
-        self.gnu_hash_layout.bloom_shift = 6;
-        self.gnu_hash_layout.bloom_count = 1;
+        self.gnu_hash_layout.set_dimensions(num_defs)?;
         // Sort by bucket. Tie-break by name for determinism.
         self.dynamic_symbol_definitions
             .sort_by_key(|d| (self.gnu_hash_layout.bucket_for_hash(d.hash), d.name));
@@ -2296,17 +3628,82 @@ impl<'data> EpilogueLayoutState<'data> {
         self.common.mem_sizes.dynsym +=
             (self.dynamic_symbol_definitions.len() * size_of::<elf::SymtabEntry>()) as u64;
 
+        if !self.version_definitions.is_empty() {
+            self.allocate_version_definitions(symbol_db.args);
+        }
+
         // .gnu.hash
-        let num_blume = 1;
-        self.common.mem_sizes.gnu_hash += (core::mem::size_of::<elf::GnuHashHeader>()
-            + core::mem::size_of::<u64>() * num_blume
-            + core::mem::size_of::<u32>() * self.gnu_hash_layout.bucket_count as usize
-            + core::mem::size_of::<u32>() * num_defs)
-            as u64;
+        if symbol_db.args.needs_dynamic() && symbol_db.args.hash_style.includes_gnu() {
+            self.common.mem_sizes.gnu_hash += (core::mem::size_of::<elf::GnuHashHeader>()
+                + core::mem::size_of::<u64>() * self.gnu_hash_layout.bloom_count as usize
+                + core::mem::size_of::<u32>() * self.gnu_hash_layout.bucket_count as usize
+                + core::mem::size_of::<u32>() * num_defs)
+                as u64;
+        }
+
+        self.common.mem_sizes.note_gnu_property += self.gnu_properties.note_size();
+        self.common.mem_sizes.note_abi_tag += self.abi_tag.note_size();
+        self.common.mem_sizes.gdb_index += self.gdb_index.as_ref().map_or(0, GdbIndex::size);
+
+        Ok(())
+    }
 
+    /// Allocates space for the SysV .hash section. Its chain array needs an entry for each dynamic
+    /// symbol, so we can't do this until all files have allocated their dynamic symbols. We need
+    /// to update both our own size record and the file totals.
+    fn allocate_sysv_hash(&mut self, total_sizes: &mut OutputSectionPartMap<u64>) -> Result {
+        let num_defs = self.dynamic_symbol_definitions.len();
+        // As for .gnu.hash, we aim for an average of 2 symbols per bucket.
+        self.sysv_hash_layout.bucket_count = (num_defs / 2).max(1) as u32;
+        self.sysv_hash_layout.chain_count = (total_sizes.dynsym / elf::SYMTAB_ENTRY_SIZE)
+            .try_into()
+            .context("Too many dynamic symbols")?;
+        let size = (size_of::<u32>()
+            * (2 + self.sysv_hash_layout.bucket_count as usize
+                + self.sysv_hash_layout.chain_count as usize)) as u64;
+        self.common.mem_sizes.hash += size;
+        total_sizes.hash += size;
         Ok(())
     }
 
+    /// Allocates .gnu.version_d, which defines the base version, named after the output file,
+    /// followed by each of the versions from the version script. Each version needs an auxiliary
+    /// entry for its own name and one for each of its parents. Also allocates the version names
+    /// and the DT_VERDEF and DT_VERDEFNUM entries.
+    fn allocate_version_definitions(&mut self, args: &Args) {
+        let num_parents = self
+            .version_definitions
+            .iter()
+            .map(|def| def.parents.len() as u64)
+            .sum::<u64>();
+        self.common.mem_sizes.gnu_version_d += (self.version_definitions.len() as u64 + 1)
+            * elf::VERDEF_WITH_AUX_SIZE
+            + num_parents * size_of::<elf::Verdaux>() as u64;
+        self.common.mem_sizes.dynstr += base_version_name(args).len() as u64
+            + 1
+            + self
+                .version_definitions
+                .iter()
+                .map(|def| def.name.len() as u64 + 1)
+                .sum::<u64>();
+        self.common.mem_sizes.dynamic += 2 * size_of::<crate::elf::DynamicEntry>() as u64;
+    }
+
+    /// Allocates .gnu.version, which glibc requires whenever we have .gnu.version_r, together with
+    /// the DT_VERSYM entry that points to it. It needs an entry for each dynamic symbol. Also
+    /// allocates the DT_VERNEED and DT_VERNEEDNUM entries for .gnu.version_r, which each shared
+    /// object that we need versions from contributes to. We need to update both our own size
+    /// record and the file totals.
+    fn allocate_versym(&mut self, total_sizes: &mut OutputSectionPartMap<u64>) {
+        let size = total_sizes.dynsym / elf::SYMTAB_ENTRY_SIZE * elf::VERSYM_ENTRY_SIZE;
+        self.common.mem_sizes.gnu_version += size;
+        total_sizes.gnu_version += size;
+        let num_dynamic_entries = if total_sizes.gnu_version_r > 0 { 3 } else { 1 };
+        let dynamic_size = num_dynamic_entries * size_of::<crate::elf::DynamicEntry>() as u64;
+        self.common.mem_sizes.dynamic += dynamic_size;
+        total_sizes.dynamic += dynamic_size;
+    }
+
     fn finalise_layout(
         mut self,
         memory_offsets: &mut OutputSectionPartMap<u64>,
@@ -2344,14 +3741,19 @@ impl<'data> EpilogueLayoutState<'data> {
             internal_symbols: self.internal_symbols,
             strings_offset_start,
             gnu_hash_layout: self.gnu_hash_layout,
+            sysv_hash_layout: self.sysv_hash_layout,
             dynamic_symbol_definitions: self.dynamic_symbol_definitions,
+            version_definitions: self.version_definitions,
             dynstr_offset_start,
+            gnu_properties: self.gnu_properties,
+            abi_tag: self.abi_tag,
+            gdb_index: self.gdb_index,
         })
     }
 }
 
 pub(crate) struct HeaderInfo {
-    pub(crate) num_output_sections_with_content: u16,
+    pub(crate) num_output_sections_with_content: u32,
     pub(crate) active_segment_ids: Vec<ProgramSegmentId>,
 }
 
@@ -2381,8 +3783,14 @@ fn new_object_layout_state<'data>(
         FileLayoutState::Object(Box::new(ObjectLayoutState {
             input: input_state.input,
             object: input_state.object,
+            decompressed_sections: input_state.decompressed_sections,
             section_frame_data: Default::default(),
             dynamic_symbol_definitions: Default::default(),
+            gnu_properties: Default::default(),
+            abi_tag: Default::default(),
+            has_text_relocations: false,
+            uses_static_tls: false,
+            exports_unique_symbols: false,
             state: ObjectLayoutMutableState {
                 common,
                 sections: non_dynamic.sections,
@@ -2397,21 +3805,122 @@ fn new_object_layout_state<'data>(
             object: input_state.object,
             input: input_state.input,
             common,
+            as_needed: input_state.modifiers.as_needed,
+            is_needed: false,
+            needed_versions: Vec::new(),
+            has_last_version_requirement: false,
+            symbol_versions: Vec::new(),
+            copy_relocations: Default::default(),
+            data_symbols_by_address: None,
+            dynamic_symbol_definitions: Vec::new(),
         }))
     }
 }
 
 impl<'data> ObjectLayoutState<'data> {
+    /// Records that `action` needs a dynamic relocation in the read-only section `section`. Such
+    /// text relocations make the loader write to pages that are normally read-only, so we only
+    /// permit them with `-z notext`, in which case `--warn-textrel` reports each of them.
+    fn add_text_relocation(
+        &mut self,
+        action: &RelocationLayoutAction,
+        section: &elf::Section,
+        resources: &GraphResources,
+    ) -> Result {
+        let symbol_db = resources.symbol_db;
+        if !symbol_db.args.allow_text_relocations || symbol_db.args.warn_textrel {
+            let target = match action.kind {
+                RelocationLayoutActionKind::LoadSymbol(symbol_id, _) => {
+                    let name = symbol_db.symbol_name(symbol_id)?;
+                    if name.bytes().is_empty() {
+                        symbol_db.symbol_debug(symbol_id).to_string()
+                    } else {
+                        format!("symbol `{name}`")
+                    }
+                }
+                RelocationLayoutActionKind::LoadSection(section_index, _) => format!(
+                    "section `{}`",
+                    self.object.section_by_index(section_index)?.name()?
+                ),
+            };
+            if !symbol_db.args.allow_text_relocations {
+                bail!(
+                    "{}: Relocation against {target} in read-only section `{}` requires a text \
+                     relocation. Recompile with -fPIC or link with -z notext",
+                    self.input,
+                    section.name()?
+                );
+            }
+            resources.diagnostics.warn(format!(
+                "{}: Relocation against {target} in read-only section `{}` creates a text \
+                 relocation",
+                self.input,
+                section.name()?
+            ));
+        }
+        self.has_text_relocations = true;
+        Ok(())
+    }
+
+    /// Reads what we need for `.gdb_index` from our debug info.
+    fn debug_info(&self) -> Result<ObjectDebugInfo<'data>> {
+        ObjectDebugInfo::from_object(self.object, self.file_id(), |index| {
+            matches!(
+                self.state.sections.get(index.0),
+                Some(SectionSlot::Loaded(_))
+            )
+        })
+        .with_context(|| format!("Failed to index debug info of {}", self.input))
+    }
+
+    /// Marks the local symbols that relocations in our debug sections refer to as needing a value,
+    /// provided that they're in sections that we loaded. Debug info mustn't cause anything to be
+    /// loaded, so we do this once GC is done. Relocations that refer to anything else, e.g. code
+    /// that was GCed, are left as zero, unless the target got a value for some other reason.
+    fn resolve_debug_relocation_targets(&mut self) -> Result {
+        for section in &self.state.sections {
+            let SectionSlot::Loaded(section) = section else {
+                continue;
+            };
+            let object_section = self.object.section_by_index(section.index)?;
+            if !is_debug_section(&object_section) {
+                continue;
+            }
+            for (_, rel) in object_section.relocations() {
+                let object::RelocationTarget::Symbol(symbol_index) = rel.target() else {
+                    continue;
+                };
+                let symbol = self.object.symbol_by_index(symbol_index)?;
+                let Some(section_index) = symbol.section_index().filter(|_| symbol.is_local())
+                else {
+                    continue;
+                };
+                if matches!(
+                    self.state.sections.get(section_index.0),
+                    Some(SectionSlot::Loaded(_) | SectionSlot::MergeStrings(_))
+                ) {
+                    let state = &mut self.state.common.symbol_states[symbol_index.0];
+                    if *state == TargetResolutionKind::None {
+                        *state = TargetResolutionKind::Value;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn activate<'scope>(
         &mut self,
         resources: &GraphResources<'data, 'scope>,
         queue: &mut LocalWorkQueue,
     ) -> Result {
         let mut eh_frame_section = None;
+        // A relocatable output keeps everything, since we don't know what the final link will need.
+        let is_relocatable_output = resources.symbol_db.args.output_kind == OutputKind::Relocatable;
         for (i, section) in self.state.sections.iter().enumerate() {
             match section {
                 SectionSlot::Unloaded(unloaded_section) => {
-                    let retain = unloaded_section.details.retain;
+                    let retain = unloaded_section.details.retain || is_relocatable_output;
                     if retain {
                         self.state
                             .sections_required
@@ -2421,24 +3930,91 @@ impl<'data> ObjectLayoutState<'data> {
                 SectionSlot::EhFrameData(index) => {
                     eh_frame_section = Some(self.object.section_by_index(*index)?);
                 }
+                SectionSlot::GnuPropertyNote(index) => {
+                    self.gnu_properties =
+                        GnuProperties::from_section(&self.object.section_by_index(*index)?)
+                            .with_context(|| {
+                                format!("{self}: Failed to parse .note.gnu.property")
+                            })?;
+                }
+                SectionSlot::AbiTagNote(index) => {
+                    self.abi_tag = AbiTag::from_section(&self.object.section_by_index(*index)?)
+                        .with_context(|| format!("{self}: Failed to parse .note.ABI-tag"))?;
+                }
                 _ => (),
             }
         }
         if let Some(eh_frame_section) = eh_frame_section {
-            process_eh_frame_data(
-                self.object,
-                self.start_symbol_id(),
-                &mut self.section_frame_data,
-                &mut self.state,
-                eh_frame_section,
+            self.process_eh_frame_data(eh_frame_section, resources, queue)?;
+        }
+        if resources.exports_dynamic_symbols() {
+            self.load_non_hidden_symbols(resources, queue)?;
+        }
+        if resources.symbol_db.args.gc_keep_exported {
+            self.load_visible_symbols(resources, queue)?;
+        }
+        if is_relocatable_output {
+            self.load_common_symbols(resources, queue)?;
+        }
+        self.load_sections(resources, queue)
+    }
+
+    /// Loads the global symbols that we define with default or protected visibility, which could
+    /// be exported, so that `--gc-keep-exported` keeps them even if we don't export them.
+    fn load_visible_symbols<'scope>(
+        &mut self,
+        resources: &GraphResources<'data, 'scope>,
+        queue: &mut LocalWorkQueue,
+    ) -> Result {
+        for sym in self.object.symbols() {
+            if !sym.is_definition()
+                || !sym.is_global()
+                || !matches!(
+                    sym.raw_symbol().st_visibility(),
+                    object::elf::STV_DEFAULT | object::elf::STV_PROTECTED
+                )
+            {
+                continue;
+            }
+            let symbol_id = self.start_symbol_id().add_usize(sym.index().0);
+            // If another file's definition of the symbol was selected, then that's the one that
+            // matters.
+            if resources.symbol_db.definition(symbol_id) != symbol_id {
+                continue;
+            }
+            self.handle_symbol_request(
+                SymbolRequest {
+                    symbol_id,
+                    target_resolution_kind: TargetResolutionKind::Value,
+                },
                 resources,
                 queue,
             )?;
         }
-        if resources.symbol_db.args.output_kind == OutputKind::SharedObject {
-            self.load_non_hidden_symbols(resources, queue)?;
+        Ok(())
+    }
+
+    /// Allocates any common symbols that we define, even if nothing references them. Used for
+    /// relocatable outputs, where the commons are allocated in .bss.
+    fn load_common_symbols<'scope>(
+        &mut self,
+        resources: &GraphResources<'data, 'scope>,
+        queue: &mut LocalWorkQueue,
+    ) -> Result {
+        for sym in self.object.symbols() {
+            let symbol_id = self.start_symbol_id().add_usize(sym.index().0);
+            if sym.is_common() && resources.symbol_db.is_definition(symbol_id) {
+                self.handle_symbol_request(
+                    SymbolRequest {
+                        symbol_id,
+                        target_resolution_kind: TargetResolutionKind::Value,
+                    },
+                    resources,
+                    queue,
+                )?;
+            }
         }
-        self.load_sections(resources, queue)
+        Ok(())
     }
 
     /// Loads sections in `sections_required` (which may be empty).
@@ -2470,6 +4046,16 @@ impl<'data> ObjectLayoutState<'data> {
                     *allocation += section.capacity();
                     *self.state.common.sections_with_content.get_mut(sec_id) = true;
                     section.output_section_id = Some(sec_id);
+                    if let Some(relocation_section_id) =
+                        resources.output_sections.relocation_section_id(sec_id)
+                    {
+                        let size = self.emitted_relocations_size(section_id)?;
+                        *self
+                            .state
+                            .common
+                            .mem_sizes
+                            .regular_mut(relocation_section_id, alignment::RELA_ENTRY) += size;
+                    }
                     if let Some(frame_data) = self.section_frame_data.get(section_id.0) {
                         self.state.common.mem_sizes.eh_frame +=
                             u64::from(frame_data.total_fde_size);
@@ -2486,12 +4072,23 @@ impl<'data> ObjectLayoutState<'data> {
                 }
                 SectionSlot::Discard => {
                     let object_section = self.object.section_by_index(section_id)?;
+                    let name = object_section.name_bytes()?;
+                    if resources.symbol_db.args.should_remove_section(name) {
+                        bail!(
+                            "{self}: `{}` was removed by --remove-section, but it's referenced",
+                            String::from_utf8_lossy(name),
+                        );
+                    }
                     bail!(
                         "{self}: Don't know what segment to put `{}` in, but it's referenced",
                         String::from_utf8_lossy(object_section.name_bytes()?),
                     );
                 }
-                SectionSlot::Loaded(_) | SectionSlot::EhFrameData(..) => {}
+                SectionSlot::Loaded(_)
+                | SectionSlot::EhFrameData(..)
+                | SectionSlot::GnuPropertyNote(..)
+                | SectionSlot::AbiTagNote(..)
+                | SectionSlot::Folded(_) => {}
                 SectionSlot::MergeStrings(_) => {
                     // We currently always load everything in merge-string sections. i.e. we don't
                     // GC unreferenced data. So there's nothing to do here.
@@ -2513,10 +4110,12 @@ impl<'data> ObjectLayoutState<'data> {
         if !symbol_db.args.strip_all {
             self.allocate_symtab_space(symbol_db)?;
         }
-        // TODO: Deduplicate CIEs from different objects, then only allocate space for those CIEs
-        // that we "won".
+        // We only allocate space for CIEs that aren't duplicates of CIEs that we or some earlier
+        // object will write.
         for cie in &self.state.cies {
-            self.state.common.mem_sizes.eh_frame += cie.cie.bytes.len() as u64;
+            if cie.duplicate_of.is_none() {
+                self.state.common.mem_sizes.eh_frame += cie.cie.bytes.len() as u64;
+            }
         }
         Ok(())
     }
@@ -2526,33 +4125,15 @@ impl<'data> ObjectLayoutState<'data> {
         let mut num_globals = 0;
         let mut strings_size = 0;
         for sym in self.object.symbols() {
-            match sym.section() {
-                object::SymbolSection::Section(section_index) => {
-                    if self.state.sections[section_index.0].is_loaded() {
-                        let name = &sym.name_bytes()?;
-                        if should_copy_symbol(name) {
-                            if sym.is_global() {
-                                num_globals += 1;
-                            } else {
-                                num_locals += 1;
-                            }
-                            strings_size += name.len() + 1;
-                        }
-                    }
-                }
-                object::SymbolSection::Common => {
-                    let symbol_id = self.start_symbol_id().add_usize(sym.index().0);
-                    let symbol_file_id = symbol_db.file_id_for_symbol(symbol_id);
-                    if symbol_file_id == self.state.common.file_id
-                        && self.state.common.symbol_states[sym.index().0]
-                            != TargetResolutionKind::None
-                    {
-                        num_globals += 1;
-                        strings_size += sym.name_bytes()?.len() + 1;
-                    }
-                }
-                _ => {}
+            let Some(is_global) = self.symtab_placement(&sym, symbol_db)? else {
+                continue;
+            };
+            if is_global {
+                num_globals += 1;
+            } else {
+                num_locals += 1;
             }
+            strings_size += sym.name_bytes()?.len() + 1;
         }
         let entry_size = size_of::<elf::SymtabEntry>() as u64;
         self.state.common.mem_sizes.symtab_locals += num_locals * entry_size;
@@ -2561,6 +4142,94 @@ impl<'data> ObjectLayoutState<'data> {
         Ok(())
     }
 
+    /// Returns whether `sym` gets copied into the output symbol table and if it does, whether it
+    /// goes with the global symbols rather than the locals. Global symbols that are localized via
+    /// `--localize-symbol` or `--localize-hidden` go with the locals.
+    fn symtab_placement(
+        &self,
+        sym: &crate::elf::Symbol,
+        symbol_db: &SymbolDb<'_>,
+    ) -> Result<Option<bool>> {
+        match sym.section() {
+            object::SymbolSection::Section(section_index)
+                if self.state.sections[section_index.0]
+                    .output_section_id()
+                    .is_some() =>
+            {
+                let name = sym.name_bytes()?;
+                Ok(
+                    (should_copy_symbol(name) && symbol_db.args.should_retain_symbol(name)).then(
+                        || {
+                            sym.is_global()
+                                && !symbol_db
+                                    .args
+                                    .should_localize_symbol(name, sym.raw_symbol().st_visibility())
+                        },
+                    ),
+                )
+            }
+            object::SymbolSection::Common => {
+                let symbol_id = self.start_symbol_id().add_usize(sym.index().0);
+                let symbol_file_id = symbol_db.file_id_for_symbol(symbol_id);
+                // When commons collide, only the one that was selected is written.
+                Ok((symbol_file_id == self.state.common.file_id
+                    && symbol_db.is_definition(symbol_id)
+                    && self.state.common.symbol_states[sym.index().0]
+                        != TargetResolutionKind::None
+                    && symbol_db.args.should_retain_symbol(sym.name_bytes()?))
+                .then_some(
+                    !symbol_db.args.should_localize_symbol(
+                        sym.name_bytes()?,
+                        sym.raw_symbol().st_visibility(),
+                    ),
+                ))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the index in the output symbol table of each of our symbols, or 0 for symbols that
+    /// we don't copy there. Must be called before our symbol table offsets have been advanced.
+    fn symtab_indexes(
+        &self,
+        memory_offsets: &OutputSectionPartMap<u64>,
+        section_layouts: &OutputSectionMap<OutputRecordLayout>,
+        symbol_db: &SymbolDb,
+    ) -> Result<Vec<u32>> {
+        let entry_size = size_of::<elf::SymtabEntry>() as u64;
+        let symtab_start = section_layouts
+            .built_in(output_section_id::SYMTAB)
+            .mem_offset;
+        let mut next_local = (memory_offsets.symtab_locals - symtab_start) / entry_size;
+        let mut next_global = (memory_offsets.symtab_globals - symtab_start) / entry_size;
+        let mut indexes = vec![0; self.state.common.symbol_states.len()];
+        for sym in self.object.symbols() {
+            let Some(is_global) = self.symtab_placement(&sym, symbol_db)? else {
+                continue;
+            };
+            let next = if is_global {
+                &mut next_global
+            } else {
+                &mut next_local
+            };
+            indexes[sym.index().0] =
+                u32::try_from(*next).context("Symbol table index overflowed 32 bits")?;
+            *next += 1;
+        }
+        Ok(indexes)
+    }
+
+    /// Returns the space needed to copy the relocations for the supplied section into the output
+    /// for `--emit-relocs`.
+    fn emitted_relocations_size(&self, section_index: object::SectionIndex) -> Result<u64> {
+        let num_relocations = self
+            .object
+            .section_by_index(section_index)?
+            .relocations()
+            .count();
+        Ok(num_relocations as u64 * elf::RELA_ENTRY_SIZE)
+    }
+
     fn finalise_layout(
         self,
         memory_offsets: &mut OutputSectionPartMap<u64>,
@@ -2571,6 +4240,12 @@ impl<'data> ObjectLayoutState<'data> {
         merged_string_start_addresses: &MergedStringStartAddresses,
     ) -> Result<ObjectLayout<'data>> {
         let start_symbol_id = self.start_symbol_id();
+        let args = symbol_db.args;
+        let symtab_indexes = if args.emit_relocs && !args.strip_all {
+            self.symtab_indexes(memory_offsets, section_layouts, symbol_db)?
+        } else {
+            Vec::new()
+        };
         let mut sections = self.state.sections;
 
         let mut emitter = self
@@ -2578,27 +4253,45 @@ impl<'data> ObjectLayoutState<'data> {
             .common
             .create_global_address_emitter(memory_offsets, symbol_db);
 
+        // Assign addresses to our loaded sections in the order that they'll be written.
+        let section_order = section_order(self.object, &sections, args.sort_section)?;
+        let mut section_addresses = vec![0; sections.len()];
+        for index in &section_order {
+            let SectionSlot::Loaded(sec) = &sections[index.0] else {
+                continue;
+            };
+            let output_section_id = sec.output_section_id.with_context(|| {
+                format!(
+                    "Tried to load section `{}` which isn't mapped to an output section",
+                    self.object
+                        .section_by_index(sec.index)
+                        .and_then(|s| s.name())
+                        .unwrap_or("??")
+                )
+            })?;
+            let address = memory_offsets.regular_mut(output_section_id, sec.alignment);
+            section_addresses[index.0] = *address;
+            *address += sec.capacity();
+        }
+
         let mut section_resolutions = Vec::with_capacity(sections.len());
         for slot in sections.iter_mut() {
             match slot {
                 SectionSlot::Loaded(sec) => {
-                    let output_section_id = sec.output_section_id.with_context(|| {
-                        format!(
-                            "Tried to load section `{}` which isn't mapped to an output section",
-                            self.object
-                                .section_by_index(sec.index)
-                                .and_then(|s| s.name())
-                                .unwrap_or("??")
-                        )
-                    })?;
-                    let address = memory_offsets.regular_mut(output_section_id, sec.alignment);
                     // TODO: We probably need to be able to handle sections that are ifuncs and sections
                     // that need a TLS GOT struct.
                     section_resolutions.push(Some(emitter.create_resolution(
                         sec.resolution_kind,
-                        ResolutionValue::Address(*address),
+                        ResolutionValue::Address(section_addresses[sec.index.0]),
+                    )?));
+                }
+                SectionSlot::Folded(_) => {
+                    // We don't know the address of the section that we were folded into yet, so
+                    // for now we resolve relative to 0. `resolve_folded_sections` fixes this up.
+                    section_resolutions.push(Some(emitter.create_resolution(
+                        TargetResolutionKind::Value,
+                        ResolutionValue::Address(0),
                     )?));
-                    *address += sec.capacity();
                 }
                 SectionSlot::EhFrameData(..) => {
                     // References to symbols defined in .eh_frame are a bit weird, since it's a
@@ -2649,7 +4342,7 @@ impl<'data> ObjectLayoutState<'data> {
                     }
                 }
                 object::SymbolSection::Common => {
-                    let common = CommonSymbol::new(&local_symbol)?;
+                    let common = CommonSymbol::new(&local_symbol, symbol_id, symbol_db)?;
                     let offset =
                         memory_offsets.regular_mut(output_section_id::BSS, common.alignment);
                     let address = *offset;
@@ -2665,6 +4358,24 @@ impl<'data> ObjectLayoutState<'data> {
         }
 
         let plt_relocations = emitter.plt_relocations;
+        let mut cie_offset = 0;
+        let cies = self
+            .state
+            .cies
+            .iter()
+            .map(|cie| {
+                let address = memory_offsets.eh_frame + cie_offset;
+                if cie.duplicate_of.is_none() {
+                    cie_offset += cie.cie.bytes.len() as u64;
+                }
+                CieLayout {
+                    input_offset: cie.offset,
+                    // Duplicates get their addresses from `resolve_duplicate_cies`.
+                    address,
+                    duplicate_of: cie.duplicate_of,
+                }
+            })
+            .collect();
         let strtab_offset_start = self
             .state
             .common
@@ -2677,12 +4388,15 @@ impl<'data> ObjectLayoutState<'data> {
             file_sizes: compute_file_sizes(&self.state.common.mem_sizes, output_sections),
             mem_sizes: self.state.common.mem_sizes,
             sections,
+            section_order,
             section_resolutions,
             strtab_offset_start,
             plt_relocations,
             eh_frame_start_address: memory_offsets.eh_frame,
+            cies,
             start_symbol_id,
             num_symbols: self.state.common.symbol_states.len(),
+            symtab_indexes,
         })
     }
 
@@ -2694,13 +4408,20 @@ impl<'data> ObjectLayoutState<'data> {
         for sym in self.object.symbols() {
             if can_export_symbol(sym) {
                 let name = sym.name_bytes()?;
-                if resources
-                    .version_script
-                    .is_some_and(|script| script.is_local(name))
+                if !resources.should_export_symbol(name)
+                    || resources
+                        .symbol_db
+                        .args
+                        .should_localize_symbol(name, sym.raw_symbol().st_visibility())
                 {
                     continue;
                 }
                 let symbol_id = self.start_symbol_id().add_usize(sym.index().0);
+                // If another file's definition of the symbol was selected, e.g. because ours is
+                // weak or unique, then that file exports it.
+                if resources.symbol_db.definition(symbol_id) != symbol_id {
+                    continue;
+                }
                 self.handle_symbol_request(
                     SymbolRequest {
                         symbol_id,
@@ -2714,14 +4435,230 @@ impl<'data> ObjectLayoutState<'data> {
                         symbol_id,
                         name,
                         hash: gnu_hash(name),
+                        version: resources.symbol_version(name),
                     });
+                self.exports_unique_symbols |= elf::is_unique_symbol(&sym);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns those of our sections that `--icf` might be able to fold.
+    fn icf_candidates(
+        &self,
+        layout_states: &[FileLayoutState<'data>],
+        symbol_db: &SymbolDb,
+    ) -> Result<Vec<icf::Candidate<'data>>> {
+        let unfoldable = self.unfoldable_sections(symbol_db)?;
+        let file_id = self.file_id();
+        let mut candidates = Vec::new();
+        for slot in &self.state.sections {
+            let SectionSlot::Loaded(section) = slot else {
+                continue;
+            };
+            // Only consider regular code. In particular, sections like .init are made up of
+            // fragments from multiple objects, so must never be folded.
+            if section.output_section_id != Some(output_section_id::TEXT)
+                || section.data.is_empty()
+                || section.resolution_kind > TargetResolutionKind::Value
+                || unfoldable[section.index.0]
+            {
+                continue;
+            }
+            let object_section = self.object.section_by_index(section.index)?;
+            let mut relocations = Vec::new();
+            for (offset, rel) in object_section.relocations() {
+                let object::RelocationFlags::Elf { r_type } = rel.flags() else {
+                    unreachable!();
+                };
+                let target = match rel.target() {
+                    object::RelocationTarget::Symbol(local_sym_index) => icf_target(
+                        self.start_symbol_id().add_usize(local_sym_index.0),
+                        layout_states,
+                        symbol_db,
+                    )?,
+                    object::RelocationTarget::Section(index) => {
+                        icf::Target::Section(InputSectionId { file_id, index }, 0)
+                    }
+                    _ => icf::Target::Absolute,
+                };
+                relocations.push(icf::Relocation {
+                    offset,
+                    r_type,
+                    addend: rel.addend(),
+                    target,
+                });
+            }
+            candidates.push(icf::Candidate {
+                id: InputSectionId {
+                    file_id,
+                    index: section.index,
+                },
+                data: section.data,
+                alignment: section.alignment,
+                relocations,
+            });
+        }
+        Ok(candidates)
+    }
+
+    /// Returns, for each of our sections, whether `--icf` must leave it alone. We never fold
+    /// sections that define ifuncs, since we record their resolvers during layout. In safe mode, we
+    /// also don't fold sections that define address-significant symbols, since code might compare
+    /// their addresses.
+    fn unfoldable_sections(&self, symbol_db: &SymbolDb) -> Result<Vec<bool>> {
+        let mut unfoldable = vec![false; self.state.sections.len()];
+        let mut mark_symbol = |index: object::SymbolIndex| -> Result {
+            if let Some(section_index) = self.object.symbol_by_index(index)?.section_index() {
+                unfoldable[section_index.0] = true;
+            }
+            Ok(())
+        };
+        for sym in self.object.symbols() {
+            if let object::SymbolFlags::Elf { st_info, .. } = sym.flags() {
+                if st_info & elf::SYMBOL_TYPE_MASK == elf::SYMBOL_TYPE_IFUNC {
+                    mark_symbol(sym.index())?;
+                }
+            }
+        }
+        if symbol_db.args.icf == Icf::Safe {
+            let addrsig = self.object.sections().find(icf::is_addrsig_section);
+            let Some(addrsig) = addrsig else {
+                // Without an address-significance table, any symbol might have its address taken.
+                return Ok(vec![true; self.state.sections.len()]);
+            };
+            for index in icf::parse_addrsig(addrsig.data()?)
+                .with_context(|| format!("{self}: Failed to parse .llvm_addrsig"))?
+            {
+                mark_symbol(index)?;
+            }
+            // Something outside of the output might compare addresses of symbols that we export.
+            for definition in &self.dynamic_symbol_definitions {
+                mark_symbol(object::SymbolIndex(
+                    definition.symbol_id.offset_from(self.start_symbol_id()),
+                ))?;
+            }
+        }
+        Ok(unfoldable)
+    }
+
+    /// Folds one of our sections into an identical section. Releases the space that we allocated
+    /// when we loaded it, including for any dynamic relocations and FDEs, since we won't be writing
+    /// them.
+    fn fold_section(
+        &mut self,
+        section_index: object::SectionIndex,
+        survivor: InputSectionId,
+        symbol_db: &SymbolDb,
+        output_sections: &OutputSections,
+    ) -> Result {
+        let SectionSlot::Loaded(section) = &self.state.sections[section_index.0] else {
+            bail!("Internal error: Tried to fold a section that wasn't loaded");
+        };
+        let output_section_id = section
+            .output_section_id
+            .context("Internal error: Tried to fold a section without an output section")?;
+        *self
+            .state
+            .common
+            .mem_sizes
+            .regular_mut(output_section_id, section.alignment) -= section.capacity();
+        if let Some(relocation_section_id) =
+            output_sections.relocation_section_id(output_section_id)
+        {
+            *self
+                .state
+                .common
+                .mem_sizes
+                .regular_mut(relocation_section_id, alignment::RELA_ENTRY) -=
+                self.emitted_relocations_size(section_index)?;
+        }
+        let object_section = self.object.section_by_index(section_index)?;
+        for (rel_offset, rel) in object_section.relocations() {
+            if let Some(action) = RelocationLayoutAction::new(
+                &rel,
+                &object_section,
+                rel_offset,
+                &self.state,
+                symbol_db,
+            )? {
+                action.remove_dynamic_relocation(&mut self.state.common.mem_sizes);
+            }
+        }
+        if let Some(frame_data) = self.section_frame_data.get(section_index.0) {
+            let mem_sizes = &mut self.state.common.mem_sizes;
+            mem_sizes.eh_frame -= u64::from(frame_data.total_fde_size);
+            mem_sizes.eh_frame_hdr -=
+                core::mem::size_of::<EhFrameHdrEntry>() as u64 * u64::from(frame_data.num_fdes);
+            for action in &frame_data.relocation_actions {
+                action.remove_dynamic_relocation(mem_sizes);
             }
         }
+        self.state.sections[section_index.0] = SectionSlot::Folded(FoldedSection {
+            output_section_id,
+            survivor,
+        });
         Ok(())
     }
 }
 
-/// Returns whether the supplied symbol can be exported when we're outputting a shared object.
+/// Returns what a reference to `symbol_id` refers to for the purposes of `--icf`. References to
+/// symbols defined in sections are converted to references to the section, so that references to
+/// sections that get folded compare equal.
+fn icf_target(
+    symbol_id: SymbolId,
+    layout_states: &[FileLayoutState],
+    symbol_db: &SymbolDb,
+) -> Result<icf::Target> {
+    let definition = symbol_db.definition(symbol_id);
+    let file_id = symbol_db.file_id_for_symbol(definition);
+    if let FileLayoutState::Object(object) = &layout_states[file_id.as_usize()] {
+        let symbol = object.object.symbol_by_index(object::SymbolIndex(
+            definition.offset_from(object.start_symbol_id()),
+        ))?;
+        if let Some(index) = symbol.section_index() {
+            return Ok(icf::Target::Section(
+                InputSectionId { file_id, index },
+                symbol.address(),
+            ));
+        }
+    }
+    Ok(icf::Target::Symbol(definition))
+}
+
+/// Returns the indexes of `sections` in the order in which they should be placed in the output.
+/// Sorting is stable, so sections that compare equal stay in the order that they appear in the
+/// object. Together with files being laid out in order, this keeps our output deterministic.
+fn section_order(
+    object: &File,
+    sections: &[SectionSlot],
+    sort_section: SortSection,
+) -> Result<Vec<object::SectionIndex>> {
+    let mut order: Vec<object::SectionIndex> =
+        (0..sections.len()).map(object::SectionIndex).collect();
+    match sort_section {
+        SortSection::None => {}
+        SortSection::Name => {
+            let names = sections
+                .iter()
+                .map(|slot| match slot {
+                    SectionSlot::Loaded(sec) => object.section_by_index(sec.index)?.name_bytes(),
+                    _ => Ok(&[][..]),
+                })
+                .collect::<object::Result<Vec<&[u8]>>>()?;
+            order.sort_by_key(|index| names[index.0]);
+        }
+        SortSection::Alignment => {
+            order.sort_by_key(|index| match &sections[index.0] {
+                SectionSlot::Loaded(sec) => std::cmp::Reverse(sec.alignment),
+                _ => std::cmp::Reverse(alignment::MIN),
+            });
+        }
+    }
+    Ok(order)
+}
+
+/// Returns whether the supplied symbol can be exported as a dynamic symbol.
 pub(crate) fn can_export_symbol(sym: crate::elf::Symbol) -> bool {
     sym.is_definition() && sym.is_global() && sym.raw_symbol().st_visibility() == 0
 }
@@ -2731,6 +4668,7 @@ impl MergedStringStartAddresses {
     fn compute(
         output_sections: &OutputSections<'_>,
         starting_mem_offsets_by_file: &[Option<OutputSectionPartMap<u64>>],
+        merged_strings: &OutputSectionMap<resolution::MergedStringsSection>,
     ) -> Self {
         let mut addresses = OutputSectionMap::with_size(output_sections.len());
         if let Some(internal_start_offsets) =
@@ -2738,8 +4676,8 @@ impl MergedStringStartAddresses {
         {
             for i in 0..output_sections.num_regular_sections() {
                 let section_id = OutputSectionId::regular(i as u16);
-                *addresses.get_mut(section_id) =
-                    *internal_start_offsets.regular(section_id, alignment::MIN);
+                *addresses.get_mut(section_id) = *internal_start_offsets
+                    .regular(section_id, merged_strings.get(section_id).alignment);
             }
         }
         Self { addresses }
@@ -2765,124 +4703,133 @@ pub(crate) fn should_copy_symbol(name: &[u8]) -> bool {
     !name.is_empty() && !name.starts_with(b".")
 }
 
-fn process_eh_frame_data<'data>(
-    object: &crate::elf::File<'data>,
-    file_start_symbol: SymbolId,
-    section_frame_data: &mut Vec<SectionFrameData>,
-    state: &mut ObjectLayoutMutableState<'data>,
-    eh_frame_section: elf::Section<'data, '_>,
-    resources: &GraphResources,
-    queue: &mut LocalWorkQueue,
-) -> Result {
-    section_frame_data.resize_with(state.sections.len(), Default::default);
-    let data = eh_frame_section.data()?;
-    const PREFIX_LEN: usize = core::mem::size_of::<elf::EhFrameEntryPrefix>();
-    let mut relocations = eh_frame_section.relocations().peekable();
-    let mut offset = 0;
-    while offset + PREFIX_LEN <= data.len() {
-        // Although the section data will be aligned within the object file, there's
-        // no guarantee that the object is aligned within the archive to any more
-        // than 2 bytes, so we can't rely on alignment here. Archives are annoying!
-        // See https://www.airs.com/blog/archives/170
-        let prefix: elf::EhFrameEntryPrefix =
-            bytemuck::pod_read_unaligned(&data[offset..offset + PREFIX_LEN]);
-        let size = core::mem::size_of_val(&prefix.length) + prefix.length as usize;
-        let next_offset = offset + size;
-        if next_offset > data.len() {
-            bail!("Invalid .eh_frame data");
-        }
-        if prefix.cie_id == 0 {
-            // This is a CIE
-            let mut referenced_symbols: SmallVec<[SymbolId; 1]> = Default::default();
-            // When deduplicating CIEs, we take into consideration the bytes of the CIE and all the
-            // symbols it references. If however, it references something other than a symbol, then,
-            // because we're not taking that into consideration, we disallow deduplication.
-            let mut eligible_for_deduplication = true;
-            while let Some((rel_offset, rel)) = relocations.peek() {
-                if *rel_offset >= next_offset as u64 {
-                    // This relocation belongs to the next entry.
-                    break;
-                }
-                // We currently always load all CIEs, so any relocations found in CIEs always need
-                // to be processed.
-                if let Some(action) = RelocationLayoutAction::new(
-                    rel,
-                    &eh_frame_section,
-                    *rel_offset,
-                    state,
-                    resources.symbol_db,
-                )? {
-                    action.apply(resources, state, queue);
-                }
-                if let object::RelocationTarget::Symbol(local_sym_index) = rel.target() {
-                    let local_symbol_id = file_start_symbol.add_usize(local_sym_index.0);
-                    let definition = resources.symbol_db.definition(local_symbol_id);
-                    referenced_symbols.push(definition);
-                } else {
-                    eligible_for_deduplication = false;
+impl<'data> ObjectLayoutState<'data> {
+    fn process_eh_frame_data(
+        &mut self,
+        eh_frame_section: elf::Section<'data, '_>,
+        resources: &GraphResources,
+        queue: &mut LocalWorkQueue,
+    ) -> Result {
+        self.section_frame_data
+            .resize_with(self.state.sections.len(), Default::default);
+        let data = eh_frame_section.data()?;
+        const PREFIX_LEN: usize = core::mem::size_of::<elf::EhFrameEntryPrefix>();
+        let mut relocations = eh_frame_section.relocations().peekable();
+        let mut offset = 0;
+        while offset + PREFIX_LEN <= data.len() {
+            // Although the section data will be aligned within the object file, there's
+            // no guarantee that the object is aligned within the archive to any more
+            // than 2 bytes, so we can't rely on alignment here. Archives are annoying!
+            // See https://www.airs.com/blog/archives/170
+            let prefix: elf::EhFrameEntryPrefix =
+                bytemuck::pod_read_unaligned(&data[offset..offset + PREFIX_LEN]);
+            let size = core::mem::size_of_val(&prefix.length) + prefix.length as usize;
+            let next_offset = offset + size;
+            if next_offset > data.len() {
+                return Err(LinkError::MalformedInput {
+                    file: self.input.to_string(),
+                    reason: "Invalid .eh_frame data".to_owned(),
                 }
-                relocations.next();
-            }
-            state.cies.push(CieAtOffset {
-                offset: offset as u32,
-                cie: Cie {
-                    bytes: &data[offset..next_offset],
-                    eligible_for_deduplication,
-                    referenced_symbols,
-                },
-            });
-        } else {
-            // This is an FDE
-            let mut section_index = None;
-            let mut actions: SmallVec<[RelocationLayoutAction; 2]> = Default::default();
-
-            while let Some((rel_offset, rel)) = relocations.peek() {
-                if *rel_offset < next_offset as u64 {
-                    let is_pc_begin = (*rel_offset as usize - offset) == elf::FDE_PC_BEGIN_OFFSET;
-
-                    if is_pc_begin {
-                        match rel.target() {
-                            object::RelocationTarget::Symbol(index) => {
-                                let elf_symbol = &object.symbol_by_index(index)?;
-                                section_index = elf_symbol.section_index();
-                            }
-                            object::RelocationTarget::Section(index) => {
-                                section_index = Some(index);
-                            }
-                            _ => {}
-                        };
+                .into());
+            }
+            if prefix.cie_id == 0 {
+                // This is a CIE
+                let mut referenced_symbols: SmallVec<[SymbolId; 1]> = Default::default();
+                // When deduplicating CIEs, we take into consideration the bytes of the CIE and all the
+                // symbols it references. If however, it references something other than a symbol, then,
+                // because we're not taking that into consideration, we disallow deduplication.
+                let mut eligible_for_deduplication = true;
+                while let Some((rel_offset, rel)) = relocations.peek() {
+                    if *rel_offset >= next_offset as u64 {
+                        // This relocation belongs to the next entry.
+                        break;
                     }
+                    // We currently always load all CIEs, so any relocations found in CIEs always need
+                    // to be processed.
                     if let Some(action) = RelocationLayoutAction::new(
                         rel,
                         &eh_frame_section,
                         *rel_offset,
-                        state,
+                        &self.state,
                         resources.symbol_db,
                     )? {
-                        actions.push(action);
+                        action.apply(resources, &mut self.state, queue);
+                        // The relocation needs something like a GOT entry or a dynamic relocation that
+                        // we've now allocated, so we need to write this CIE.
+                        eligible_for_deduplication = false;
+                    }
+                    if let object::RelocationTarget::Symbol(local_sym_index) = rel.target() {
+                        let local_symbol_id = self.start_symbol_id().add_usize(local_sym_index.0);
+                        let definition = resources.symbol_db.definition(local_symbol_id);
+                        referenced_symbols.push(definition);
+                    } else {
+                        eligible_for_deduplication = false;
                     }
                     relocations.next();
-                } else {
-                    break;
+                }
+                self.state.cies.push(CieAtOffset {
+                    offset: offset as u32,
+                    duplicate_of: None,
+                    cie: Cie {
+                        bytes: &data[offset..next_offset],
+                        eligible_for_deduplication,
+                        referenced_symbols,
+                    },
+                });
+            } else {
+                // This is an FDE
+                let mut section_index = None;
+                let mut actions: SmallVec<[RelocationLayoutAction; 2]> = Default::default();
+
+                while let Some((rel_offset, rel)) = relocations.peek() {
+                    if *rel_offset < next_offset as u64 {
+                        let is_pc_begin =
+                            (*rel_offset as usize - offset) == elf::FDE_PC_BEGIN_OFFSET;
+
+                        if is_pc_begin {
+                            match rel.target() {
+                                object::RelocationTarget::Symbol(index) => {
+                                    let elf_symbol = &self.object.symbol_by_index(index)?;
+                                    section_index = elf_symbol.section_index();
+                                }
+                                object::RelocationTarget::Section(index) => {
+                                    section_index = Some(index);
+                                }
+                                _ => {}
+                            };
+                        }
+                        if let Some(action) = RelocationLayoutAction::new(
+                            rel,
+                            &eh_frame_section,
+                            *rel_offset,
+                            &self.state,
+                            resources.symbol_db,
+                        )? {
+                            actions.push(action);
+                        }
+                        relocations.next();
+                    } else {
+                        break;
+                    }
+                }
+                if let Some(section_index) = section_index {
+                    let section_frame_data = &mut self.section_frame_data[section_index.0];
+                    section_frame_data.relocation_actions.append(&mut actions);
+                    section_frame_data.num_fdes += 1;
+                    section_frame_data.total_fde_size += size as u32;
                 }
             }
-            if let Some(section_index) = section_index {
-                let section_frame_data = &mut section_frame_data[section_index.0];
-                section_frame_data.relocation_actions.append(&mut actions);
-                section_frame_data.num_fdes += 1;
-                section_frame_data.total_fde_size += size as u32;
-            }
+            offset = next_offset;
         }
-        offset = next_offset;
+        // Allocate space for any remaining bytes in .eh_frame that aren't large enough to constitute an
+        // actual entry. crtend.o has a single u32 equal to 0 as an end marker.
+        self.state.common.mem_sizes.eh_frame += (data.len() - offset) as u64;
+        Ok(())
     }
-    // Allocate space for any remaining bytes in .eh_frame that aren't large enough to constitute an
-    // actual entry. crtend.o has a single u32 equal to 0 as an end marker.
-    state.common.mem_sizes.eh_frame += (data.len() - offset) as u64;
-    Ok(())
 }
 
 /// A "common information entry". This is part of the .eh_frame data in ELF.
-#[derive(PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 struct Cie<'data> {
     bytes: &'data [u8],
     eligible_for_deduplication: bool,
@@ -2890,11 +4837,11 @@ struct Cie<'data> {
 }
 
 struct CieAtOffset<'data> {
-    // TODO: Use or remove. I think we need this when we implement deduplication of CIEs.
     /// Offset within .eh_frame
-    #[allow(dead_code)]
     offset: u32,
     cie: Cie<'data>,
+    /// Set by `deduplicate_cies` if an identical CIE will be written instead of this one.
+    duplicate_of: Option<CieId>,
 }
 
 #[derive(Clone, Copy)]
@@ -2904,11 +4851,22 @@ struct CommonSymbol {
 }
 
 impl CommonSymbol {
-    fn new(local_symbol: &crate::elf::Symbol) -> Result<CommonSymbol> {
+    fn new(
+        local_symbol: &crate::elf::Symbol,
+        symbol_id: SymbolId,
+        symbol_db: &SymbolDb,
+    ) -> Result<CommonSymbol> {
         debug_assert!(local_symbol.is_common());
         // Common symbols misuse the value field (which we access via `address()`) to store the
-        // alignment.
-        let alignment = Alignment::new(local_symbol.address())?;
+        // alignment. Another common definition of the same symbol may have requested a larger
+        // alignment, in which case we use that instead.
+        let alignment = Alignment::new(
+            symbol_db
+                .common_alignments
+                .get(&symbol_id)
+                .copied()
+                .unwrap_or_else(|| local_symbol.address()),
+        )?;
         let size = alignment.align_up(local_symbol.size());
         Ok(CommonSymbol { size, alignment })
     }
@@ -3030,26 +4988,184 @@ impl Resolution {
     }
 }
 
+/// Returns the addresses that the linker script, if any, says that sections should start at. Each
+/// address is keyed by the section at which we need to move to that address. That's normally the
+/// section itself, but if it's preceded in its segment by empty sections, then we move those too,
+/// otherwise they'd stretch the segment back to our previous address.
+fn section_start_addresses(
+    args: &Args,
+    sizes: &OutputSectionPartMap<u64>,
+    output_sections: &OutputSections,
+) -> AHashMap<OutputSectionId, u64> {
+    let mut addresses = AHashMap::new();
+    let script = args.linker_script.as_ref();
+    if script.is_none() && args.section_starts.is_empty() {
+        return addresses;
+    }
+    let mut previous_section_id = None;
+    let mut current_seg_id = None;
+    // The first section in the current run of empty sections within the current segment.
+    let mut empty_run_start = None;
+    sizes.output_order_map(output_sections, |section_id, _, part_size| {
+        if previous_section_id != Some(section_id) {
+            previous_section_id = Some(section_id);
+            let seg_id = output_sections.loadable_segment_id_for(section_id);
+            if current_seg_id != seg_id {
+                current_seg_id = seg_id;
+                empty_run_start = None;
+            }
+            let run_start = *empty_run_start.get_or_insert(section_id);
+            let name = output_sections.name(section_id);
+            let from_args = args
+                .section_starts
+                .iter()
+                .rev()
+                .find(|(section_name, _)| section_name.as_bytes() == name)
+                .map(|(_, address)| *address);
+            if let Some(address) = from_args.or_else(|| {
+                script?
+                    .sections
+                    .iter()
+                    .find(|section| section.name.as_bytes() == name)
+                    .and_then(|section| section.address)
+            }) {
+                addresses.insert(run_start, address);
+            }
+        }
+        if *part_size > 0 {
+            empty_run_start = None;
+        }
+    });
+    addresses
+}
+
+/// Determines which program segments contain sections that we're keeping.
+fn segments_to_keep(
+    output_sections: &OutputSections,
+    keep_sections: &[bool],
+) -> [bool; crate::program_segments::MAX_SEGMENTS] {
+    use output_section_id::OrderEvent;
+    let mut keep_segments = [false; crate::program_segments::MAX_SEGMENTS];
+    keep_segments[crate::program_segments::GNU_STACK.as_usize()] = true;
+    let mut active_segments = Vec::with_capacity(4);
+    output_sections.sections_and_segments_do(|event| match event {
+        OrderEvent::SegmentStart(segment_id) => active_segments.push(segment_id),
+        OrderEvent::SegmentEnd(segment_id) => active_segments.retain(|a| *a != segment_id),
+        OrderEvent::Section(section_id, _) => {
+            if keep_sections[section_id.as_usize()] {
+                for segment_id in &active_segments {
+                    // With `-z norelro`, sections no longer count towards the RELRO segment.
+                    if *segment_id != crate::program_segments::RELRO
+                        || output_sections.is_relro(section_id)
+                    {
+                        keep_segments[segment_id.as_usize()] = true;
+                    }
+                }
+                active_segments.clear();
+            }
+        }
+    });
+    keep_segments
+}
+
 fn layout_section_parts(
     sizes: &OutputSectionPartMap<u64>,
     output_sections: &OutputSections,
-) -> OutputSectionPartMap<OutputRecordLayout> {
+    page_size: Alignment,
+    common_page_size: Alignment,
+    separate_code: bool,
+    start_addresses: &AHashMap<OutputSectionId, u64>,
+) -> Result<OutputSectionPartMap<OutputRecordLayout>> {
     let mut file_offset = 0;
     let mut mem_offset = output_sections.base_address;
     let mut current_seg_id = None;
-    sizes.output_order_map(
+    let mut in_relro = false;
+    let mut relro_size = 0;
+    let mut previous_section_id = None;
+    let mut error = None;
+    // The TLS segment needs to start at a multiple of its alignment, which may come from .tbss
+    // rather than .tdata.
+    let tls_alignment = sizes
+        .max_alignment(output_section_id::TDATA)
+        .max(sizes.max_alignment(output_section_id::TBSS));
+    let layouts = sizes.output_order_map(
         output_sections,
         |section_id, section_alignment, part_size| {
             let defs = output_sections.details(section_id);
             let mem_size = *part_size;
+            let seg_id = output_sections.loadable_segment_id_for(section_id);
+            if previous_section_id != Some(section_id) {
+                previous_section_id = Some(section_id);
+                if seg_id.is_none() && defs.section_flags & elf::shf::ALLOC == 0 {
+                    // Sections that aren't loaded at runtime have an address of 0. Their contents,
+                    // e.g. debug info, refer to each other by offset, which is then just the
+                    // address.
+                    mem_offset = 0;
+                }
+                if let Some(&address) = start_addresses.get(&section_id) {
+                    if seg_id.is_some() && current_seg_id != seg_id {
+                        // We're starting a new segment, so we just need our file offset to be
+                        // congruent to our address modulo the page size. The address may even be
+                        // before previous segments, in which case `check_section_overlaps` reports
+                        // any overlap.
+                        current_seg_id = seg_id;
+                        let mask = page_size.value() - 1;
+                        file_offset += (address.wrapping_sub(file_offset as u64) & mask) as usize;
+                        mem_offset = address;
+                    } else if address < mem_offset {
+                        error.get_or_insert_with(|| {
+                            anyhow!(
+                                "`{}` is placed at 0x{address:x}, which is before the end of the \
+                                 previous section in its segment at 0x{mem_offset:x}",
+                                output_sections.display_name(section_id)
+                            )
+                        });
+                    } else {
+                        // Sections before us in our segment have already fixed the relationship
+                        // between file offsets and addresses, so we need to pad the file.
+                        file_offset += (address - mem_offset) as usize;
+                        mem_offset = address;
+                    }
+                }
+                if section_id == output_section_id::TDATA {
+                    file_offset = tls_alignment.align_up_usize(file_offset);
+                    mem_offset = tls_alignment.align_up(mem_offset);
+                }
+            }
+            let is_relro = output_sections.is_relro(section_id);
+            if in_relro && !is_relro && relro_size > 0 {
+                // The runtime loader can only make whole pages read-only, so pad to the end of the
+                // page, otherwise the last page of RELRO data would be left writable. We advance
+                // the file offset by the same amount in order to preserve the relationship between
+                // file and memory offsets within the segment. We only pad to the common page size,
+                // since that's what the system is expected to actually use.
+                let padded = common_page_size.align_up(mem_offset);
+                file_offset += (padded - mem_offset) as usize;
+                mem_offset = padded;
+            }
+            in_relro = is_relro;
+            if is_relro {
+                relro_size += mem_size;
+            }
             // Note, we align up even if our size is zero, otherwise our section will start at an
             // unaligned address.
             file_offset = section_alignment.align_up_usize(file_offset);
             mem_offset = section_alignment.align_up(mem_offset);
-            let seg_id = output_sections.loadable_segment_id_for(section_id);
             if current_seg_id != seg_id {
+                let is_exec = |seg_id: Option<ProgramSegmentId>| {
+                    seg_id.is_some_and(|s| s.segment_flags() & object::elf::PF_X != 0)
+                };
+                if separate_code && (is_exec(current_seg_id) || is_exec(seg_id)) {
+                    // Start on a new page, so that executable pages never contain anything from
+                    // other segments. The file only needs padding to the common page size. Memory
+                    // is still aligned to the maximum page size below.
+                    file_offset = common_page_size.align_up_usize(file_offset);
+                    mem_offset = page_size.align_up(mem_offset);
+                }
                 current_seg_id = seg_id;
-                let segment_alignment = seg_id.map(|s| s.alignment()).unwrap_or(alignment::MIN);
+                let segment_alignment = seg_id
+                    .map(|s| s.alignment(page_size))
+                    .unwrap_or(alignment::MIN);
                 mem_offset = segment_alignment.align_modulo(file_offset as u64, mem_offset);
             }
             let file_size = if defs.has_data_in_file() {
@@ -3069,17 +5185,201 @@ fn layout_section_parts(
             mem_offset += mem_size;
             section_layout
         },
-    )
+    );
+    if let Some(error) = error {
+        return Err(error);
+    }
+    Ok(layouts)
 }
 
 impl<'data> DynamicLayoutState<'data> {
     fn activate(&mut self) -> Result {
+        if !self.as_needed {
+            self.mark_needed();
+        }
+        Ok(())
+    }
+
+    /// Allocates space for our DT_NEEDED entry if we haven't already.
+    fn mark_needed(&mut self) {
+        if self.is_needed {
+            return;
+        }
+        self.is_needed = true;
         self.common.mem_sizes.dynamic += core::mem::size_of::<crate::elf::DynamicEntry>() as u64;
         self.common.mem_sizes.dynstr += self.lib_name.len() as u64 + 1;
+    }
+
+    /// Adds `version` to the versions that we require from this shared object, unless it's already
+    /// there, allocating space for it in our `.gnu.version_r` entry. Returns the index by which
+    /// `.gnu.version` refers to it. The epilogue allocates the dynamic entries that go with it.
+    fn require_version(&mut self, version: &'data [u8], next_index: &mut u16) -> Result<u16> {
+        debug_assert!(self.is_needed);
+        if let Some(needed) = self.needed_versions.iter().find(|n| n.name == version) {
+            return Ok(needed.index);
+        }
+        let index = *next_index;
+        *next_index = index
+            .checked_add(1)
+            .filter(|next| *next & object::elf::VERSYM_HIDDEN == 0)
+            .context("Too many symbol versions")?;
+        if self.needed_versions.is_empty() {
+            self.common.mem_sizes.gnu_version_r += elf::VERNEED_SIZE;
+        }
+        self.common.mem_sizes.gnu_version_r += elf::VERNAUX_SIZE;
+        self.common.mem_sizes.dynstr += version.len() as u64 + 1;
+        self.needed_versions.push(NeededVersion {
+            name: version,
+            index,
+        });
+        Ok(index)
+    }
+
+    /// Records the version of each of our symbols that we import and of each of our definitions
+    /// that gets copied, requiring those versions from this shared object. Symbols from the base
+    /// version, or from a shared object that doesn't version its symbols, are global.
+    fn record_symbol_versions(&mut self, next_index: &mut u16) -> Result {
+        let imported = self.imported_symbol_indexes();
+        let Some(versions) = elf::version_table(self.object)? else {
+            self.symbol_versions = vec![object::elf::VER_NDX_GLOBAL; imported.len()];
+            return Ok(());
+        };
+        let mut version_index = |state: &mut Self, local_index: usize| -> Result<u16> {
+            match versions.version(versions.version_index(object::LittleEndian, local_index))? {
+                Some(version) => state.require_version(version.name(), next_index),
+                None => Ok(object::elf::VER_NDX_GLOBAL),
+            }
+        };
+        self.symbol_versions = imported
+            .into_iter()
+            .map(|local_index| version_index(self, local_index))
+            .collect::<Result<_>>()?;
+        for i in 0..self.dynamic_symbol_definitions.len() {
+            let local_index = self.dynamic_symbol_definitions[i]
+                .symbol_id
+                .offset_from(self.common.start_symbol_id);
+            self.dynamic_symbol_definitions[i].version = version_index(self, local_index)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the indexes of our symbols that we import, which are those that we reference other
+    /// than the ones that we copy.
+    fn imported_symbol_indexes(&self) -> Vec<usize> {
+        let mut copied = self
+            .copy_relocations
+            .values()
+            .flat_map(|copy| copy.symbol_indexes.iter().copied())
+            .collect::<Vec<_>>();
+        copied.sort_unstable();
+        self.common
+            .symbol_states
+            .iter()
+            .enumerate()
+            .filter(|(index, state)| {
+                **state != TargetResolutionKind::None && copied.binary_search(index).is_err()
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Copies our data object `symbol_id` into the executable's .bss, unless we already have. Our
+    /// other symbols at the same address, e.g. `environ` and `__environ` in glibc, are aliases for
+    /// the same object, so the executable defines those at the copy too. Otherwise we'd keep using
+    /// our own definition of the object via the aliases.
+    fn copy_relocate_symbol(&mut self, symbol_id: SymbolId, resources: &GraphResources) -> Result {
+        let symbol_table = self
+            .object
+            .dynamic_symbol_table()
+            .context("Missing dynamic symbol table")?;
+        let symbol = symbol_table.symbol_by_index(object::SymbolIndex(
+            symbol_id.offset_from(self.common.start_symbol_id),
+        ))?;
+        if self.copy_relocations.contains_key(&symbol.address()) {
+            return Ok(());
+        }
+        let alignment = copy_relocation_alignment(self.object, &symbol)?;
+        let size = alignment.align_up(symbol.size());
+        *self
+            .common
+            .mem_sizes
+            .regular_mut(output_section_id::BSS, alignment) += size;
+        self.common.mem_sizes.rela_dyn_glob_dat += elf::RELA_ENTRY_SIZE;
+
+        let mut copy = CopyRelocation {
+            size,
+            alignment,
+            symbol_id,
+            symbol_indexes: Vec::new(),
+        };
+        let data_symbols_by_address = self.data_symbols_by_address.get_or_insert_with(|| {
+            let mut by_address = AHashMap::<u64, Vec<usize>>::new();
+            for sym in symbol_table.symbols() {
+                if sym.section_index().is_some() && sym.kind() == object::SymbolKind::Data {
+                    by_address
+                        .entry(sym.address())
+                        .or_default()
+                        .push(sym.index().0);
+                }
+            }
+            by_address
+        });
+        let alias_indexes = data_symbols_by_address
+            .get(&symbol.address())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let mut found_non_weak = false;
+        for &alias_index in alias_indexes {
+            let alias = symbol_table.symbol_by_index(object::SymbolIndex(alias_index))?;
+            if alias.section_index() != symbol.section_index() {
+                continue;
+            }
+            let alias_id = self.common.start_symbol_id.add_usize(alias.index().0);
+            // If some other file's definition of the name was selected, then it's not an alias.
+            if resources.symbol_db.definition(alias_id) != alias_id {
+                continue;
+            }
+            // Prefer to refer to a non-weak symbol, regardless of which alias was requested, so
+            // that our output doesn't depend on the order in which we process requests.
+            if alias.is_global() && !alias.is_weak() && !found_non_weak {
+                copy.symbol_id = alias_id;
+                found_non_weak = true;
+            }
+            let name = alias.name_bytes()?;
+            self.dynamic_symbol_definitions
+                .push(DynamicSymbolDefinition {
+                    symbol_id: alias_id,
+                    name,
+                    hash: gnu_hash(name),
+                    version: resources.symbol_version(name),
+                });
+            copy.symbol_indexes.push(alias.index().0);
+        }
+        self.copy_relocations.insert(symbol.address(), copy);
         Ok(())
     }
 
     fn finalise_sizes(&mut self) -> Result {
+        // Objects that we copy are defined by the executable, so don't need the undefined dynamic
+        // symbols that `load_symbol` allocated. Any GOT entries for them can point straight at the
+        // copy, so don't need dynamic relocations either.
+        for definition in &self.dynamic_symbol_definitions {
+            let local_index = definition
+                .symbol_id
+                .offset_from(self.common.start_symbol_id);
+            let symbol_state = self.common.symbol_states[local_index];
+            if symbol_state == TargetResolutionKind::None {
+                continue;
+            }
+            self.common.mem_sizes.dynsym -= crate::elf::SYMTAB_ENTRY_SIZE;
+            self.common.mem_sizes.dynstr -= definition.name.len() as u64 + 1;
+            if matches!(
+                symbol_state,
+                TargetResolutionKind::Got | TargetResolutionKind::Plt
+            ) {
+                self.common.mem_sizes.rela_dyn_glob_dat -= elf::RELA_ENTRY_SIZE;
+            }
+        }
         Ok(())
     }
 
@@ -3094,6 +5394,18 @@ impl<'data> DynamicLayoutState<'data> {
         let dynstr_start_offset =
             memory_offsets.dynstr - section_layouts.get(output_section_id::DYNSTR).mem_offset;
 
+        let mut copy_relocations = Vec::with_capacity(self.copy_relocations.len());
+        let mut copy_addresses = Vec::new();
+        for copy in self.copy_relocations.values() {
+            let offset = memory_offsets.regular_mut(output_section_id::BSS, copy.alignment);
+            let address = *offset;
+            *offset += copy.size;
+            copy_relocations.push((copy.symbol_id, address));
+            copy_addresses.extend(copy.symbol_indexes.iter().map(|index| (*index, address)));
+        }
+        copy_addresses.sort_unstable();
+        let mut copy_addresses = copy_addresses.into_iter().peekable();
+
         let mut emitter = self
             .common
             .create_global_address_emitter(memory_offsets, symbol_db);
@@ -3106,12 +5418,22 @@ impl<'data> DynamicLayoutState<'data> {
 
         debug_assert_eq!(resolutions_out.len(), self.common.symbol_states.len());
 
-        for ((_local_symbol, symbol_state), resolution) in self
-            .object
-            .dynamic_symbols()
-            .zip(&self.common.symbol_states)
+        for (local_index, (symbol_state, resolution)) in self
+            .common
+            .symbol_states
+            .iter()
             .zip(resolutions_out)
+            .enumerate()
         {
+            // A symbol that we copy resolves to the copy, which the epilogue defines. That's the
+            // case even for aliases that nothing referenced, since the epilogue needs their
+            // addresses.
+            if let Some((_, address)) = copy_addresses.next_if(|(index, _)| *index == local_index) {
+                *resolution = Some(
+                    emitter.create_resolution(*symbol_state, ResolutionValue::Address(address))?,
+                );
+                continue;
+            }
             if *symbol_state == TargetResolutionKind::None {
                 continue;
             }
@@ -3127,11 +5449,13 @@ impl<'data> DynamicLayoutState<'data> {
         Ok(DynamicLayout {
             file_id: self.file_id(),
             input: self.input,
-            file_sizes: self
-                .common
-                .mem_sizes
-                .map(output_sections, |_section_id, mem_size| *mem_size as usize),
+            file_sizes: compute_file_sizes(&self.common.mem_sizes, output_sections),
             lib_name: self.lib_name,
+            is_needed: self.is_needed,
+            needed_versions: self.needed_versions,
+            has_last_version_requirement: self.has_last_version_requirement,
+            symbol_versions: self.symbol_versions,
+            copy_relocations,
             dynstr_start_offset,
             object: self.object,
             start_symbol_id: self.common.start_symbol_id,
@@ -3158,6 +5482,92 @@ impl<'data> std::fmt::Debug for FileLayoutState<'data> {
     }
 }
 
+/// Returns the alignment for our copy of the data object `symbol` from the shared object `object`.
+/// Like GNU ld, we use the alignment of the section that contains it, reduced to what its address
+/// implies if that's less.
+fn copy_relocation_alignment(object: &File, symbol: &crate::elf::Symbol) -> Result<Alignment> {
+    let section_alignment = match symbol.section_index() {
+        Some(index) => object.section_by_index(index)?.align(),
+        None => 0,
+    };
+    let exponent = symbol
+        .address()
+        .trailing_zeros()
+        .min(section_alignment.trailing_zeros())
+        .min(u32::from(alignment::MAX.exponent));
+    Alignment::new(1 << exponent)
+}
+
+/// Reports each input section that we didn't load because nothing referenced it. Sections that
+/// were retained or reachable from the entry point will have been loaded by now, so aren't
+/// reported. The wording matches that of GNU ld.
+fn print_gc_sections(layout_states: &[FileLayoutState]) -> Result {
+    let mut stderr = std::io::stderr().lock();
+    for state in layout_states {
+        let FileLayoutState::Object(object) = state else {
+            continue;
+        };
+        for (index, slot) in object.state.sections.iter().enumerate() {
+            if !matches!(slot, SectionSlot::Unloaded(_)) {
+                continue;
+            }
+            let section = object
+                .object
+                .section_by_index(object::SectionIndex(index))?;
+            writeln!(
+                stderr,
+                "removing unused section '{}' in file '{}'",
+                String::from_utf8_lossy(section.name_bytes()?),
+                object.input
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Reports each group of sections folded by `--icf`: the section that we kept, followed by the
+/// sections that were folded into it. Groups are sorted by the name and then the file of the kept
+/// section, as are the folded sections within each group, so that the report doesn't depend on the
+/// order of our inputs.
+fn print_icf_sections(
+    layout_states: &[FileLayoutState],
+    candidates: &[icf::Candidate],
+    survivors: &[Option<usize>],
+) -> Result {
+    let describe = |id: InputSectionId| -> Result<(String, String)> {
+        let FileLayoutState::Object(object) = &layout_states[id.file_id.as_usize()] else {
+            bail!("Internal error: ICF candidate isn't from an object");
+        };
+        let section = object.object.section_by_index(id.index)?;
+        Ok((
+            String::from_utf8_lossy(section.name_bytes()?).into_owned(),
+            object.input.to_string(),
+        ))
+    };
+    let mut groups: BTreeMap<(String, String), Vec<(String, String)>> = BTreeMap::new();
+    for (candidate, survivor) in candidates.iter().zip(survivors) {
+        let Some(survivor) = survivor else {
+            continue;
+        };
+        groups
+            .entry(describe(candidates[*survivor].id)?)
+            .or_default()
+            .push(describe(candidate.id)?);
+    }
+    let mut stderr = std::io::stderr().lock();
+    for ((name, file), mut folded) in groups {
+        writeln!(stderr, "selected section '{name}' in file '{file}'")?;
+        folded.sort();
+        for (name, file) in folded {
+            writeln!(
+                stderr,
+                "  removing identical section '{name}' in file '{file}'"
+            )?;
+        }
+    }
+    Ok(())
+}
+
 fn print_symbol_info(symbol_db: &SymbolDb, name: &str) {
     if let Some(symbol_id) = symbol_db
         .global_names
@@ -3215,16 +5625,55 @@ impl ResolutionValue {
             ResolutionValue::Dynamic(..) => bail!("Unexpected dynamic resolution"),
         }
     }
-
-    pub(crate) fn is_absolute(&self) -> bool {
-        matches!(self, ResolutionValue::Absolute(..))
-    }
 }
 
 impl GnuHashLayout {
+    /// Chooses the table dimensions for `num_defs` dynamic symbol definitions. The loader requires
+    /// at least one bucket and a non-zero, power-of-two number of bloom filter words, even when
+    /// there are no symbols.
+    fn set_dimensions(&mut self, num_defs: usize) -> Result {
+        // Our number of buckets is computed somewhat arbitrarily so that we have on average 2
+        // symbols per bucket, but then we round up to a power of two.
+        self.bucket_count = (num_defs / 2)
+            .next_power_of_two()
+            .try_into()
+            .context("Too many dynamic symbols")?;
+        // Each symbol sets 2 bits in the bloom filter. Allowing 8 bits per symbol keeps the false
+        // positive rate low.
+        self.bloom_count = num_defs
+            .div_ceil(8)
+            .next_power_of_two()
+            .try_into()
+            .context("Too many dynamic symbols")?;
+        self.bloom_shift = 6;
+        Ok(())
+    }
+
+    /// Returns the bucket that the loader will look in for a symbol with the supplied hash.
     pub(crate) fn bucket_for_hash(&self, hash: u32) -> u32 {
-        (hash & ((1 << self.bloom_shift) - 1)) % self.bucket_count
+        hash % self.bucket_count
+    }
+}
+
+#[test]
+fn test_gnu_hash_dimensions() {
+    let mut layout = GnuHashLayout::default();
+    for (num_defs, bucket_count, bloom_count) in [
+        (0, 1, 1),
+        (1, 1, 1),
+        (9, 4, 2),
+        (300, 256, 64),
+        (1000, 512, 128),
+    ] {
+        layout.set_dimensions(num_defs).unwrap();
+        assert_eq!(
+            (layout.bucket_count, layout.bloom_count),
+            (bucket_count, bloom_count),
+            "num_defs={num_defs}"
+        );
     }
+    // Bucket selection must use the whole hash, as the loader does, not just the low bits.
+    assert_eq!(layout.bucket_for_hash(0x1234_5678), 0x1234_5678 % 512);
 }
 
 /// Performs layout of sections and segments then makes sure that the loadable segments don't
@@ -3237,7 +5686,15 @@ fn test_no_disallowed_overlaps() {
             .unwrap();
     let section_part_sizes = OutputSectionPartMap::<u64>::with_size(output_sections.len())
         .output_order_map(&output_sections, |_, _, _| 7);
-    let section_part_layouts = layout_section_parts(&section_part_sizes, &output_sections);
+    let section_part_layouts = layout_section_parts(
+        &section_part_sizes,
+        &output_sections,
+        alignment::PAGE,
+        alignment::PAGE,
+        false,
+        &AHashMap::new(),
+    )
+    .unwrap();
     let section_layouts = layout_sections(&section_part_layouts);
 
     // Make sure no sections overlap
@@ -3246,12 +5703,14 @@ fn test_no_disallowed_overlaps() {
     let mut last_file_end = 0;
     let mut last_mem_end = 0;
     let mut last_section_id = output_section_id::FILE_HEADER;
-    output_sections.sections_do(|section_id, _section_details| {
+    output_sections.sections_do(|section_id, section_details| {
         let section = section_layouts.get(section_id);
         let mem_offset = section.mem_offset;
         let mem_end = mem_offset + section.mem_size;
+        // Sections that aren't allocated all have address 0, so can't overlap in memory.
+        let is_alloc = section_details.section_flags & elf::shf::ALLOC != 0;
         assert!(
-            mem_offset >= last_mem_end,
+            !is_alloc || mem_offset >= last_mem_end,
             "Memory sections: {last_section_id} @{last_mem_start:x}..{last_mem_end:x} overlaps {section_id} @{mem_offset:x}..{mem_end:x}",
         );
         let file_offset = section.file_offset;
@@ -3260,9 +5719,11 @@ fn test_no_disallowed_overlaps() {
             file_offset >= last_file_end,
             "File sections {last_section_id} @{last_file_start:x}..{last_file_end} {section_id} @{file_offset:x}..{file_end:x}",
         );
-        last_mem_start = mem_offset;
+        if is_alloc {
+            last_mem_start = mem_offset;
+            last_mem_end = mem_end;
+        }
         last_file_start = file_offset;
-        last_mem_end = mem_end;
         last_file_end = file_end;
         last_section_id = section_id;
     });
@@ -3271,7 +5732,12 @@ fn test_no_disallowed_overlaps() {
         active_segment_ids: (0..MAX_SEGMENTS).map(ProgramSegmentId::new).collect(),
     };
 
-    let segment_layouts = compute_segment_layout(&section_layouts, &output_sections, &header_info);
+    let segment_layouts = compute_segment_layout(
+        &section_layouts,
+        &output_sections,
+        &header_info,
+        alignment::PAGE,
+    );
 
     // Make sure loadable segments don't overlap in memory or in the file.
     let mut last_file = 0;
@@ -3297,3 +5763,336 @@ fn test_no_disallowed_overlaps() {
         last_file = seg_layout.sizes.file_offset + seg_layout.sizes.file_size;
     }
 }
+
+/// Checks that with a non-default page size, each loadable segment's address is congruent to its
+/// file offset modulo the page size, as required in order for the segment to be mmapped.
+#[test]
+fn test_segment_offsets_congruent_with_page_size() {
+    let page_size = Alignment::page_size(0x10000).unwrap();
+    let output_sections =
+        crate::output_section_id::OutputSectionsBuilder::with_base_address(0x400000)
+            .build()
+            .unwrap();
+    let section_part_sizes = OutputSectionPartMap::<u64>::with_size(output_sections.len())
+        .output_order_map(&output_sections, |_, _, _| 7);
+    let section_part_layouts = layout_section_parts(
+        &section_part_sizes,
+        &output_sections,
+        page_size,
+        page_size,
+        false,
+        &AHashMap::new(),
+    )
+    .unwrap();
+    let section_layouts = layout_sections(&section_part_layouts);
+    let header_info = HeaderInfo {
+        num_output_sections_with_content: 0,
+        active_segment_ids: (0..MAX_SEGMENTS).map(ProgramSegmentId::new).collect(),
+    };
+    let segment_layouts =
+        compute_segment_layout(&section_layouts, &output_sections, &header_info, page_size);
+
+    let mask = page_size.value() - 1;
+    let mut num_load_segments = 0;
+    for seg_layout in &segment_layouts.segments {
+        if seg_layout.id.segment_type() != elf::SegmentType::Load {
+            continue;
+        }
+        num_load_segments += 1;
+        let sizes = &seg_layout.sizes;
+        assert_eq!(
+            sizes.mem_offset & mask,
+            sizes.file_offset as u64 & mask,
+            "Segment {:?} at address 0x{:x} has file offset 0x{:x}",
+            seg_layout.id,
+            sizes.mem_offset,
+            sizes.file_offset,
+        );
+    }
+    assert!(num_load_segments > 1);
+}
+
+/// Checks that with `-z separate-code`, the file pages mapped by the executable segment don't
+/// overlap those mapped by the segments on either side of it. Without it, we pack the segments
+/// together as before.
+#[test]
+fn test_separate_code() {
+    use crate::program_segments::LOAD_EXEC;
+    use crate::program_segments::LOAD_RO;
+    use crate::program_segments::LOAD_RW;
+
+    let page_size = alignment::PAGE;
+    let segment_pages = |separate_code: bool| {
+        let output_sections =
+            crate::output_section_id::OutputSectionsBuilder::with_base_address(0x400000)
+                .build()
+                .unwrap();
+        // Keep everything small and with low alignment, so that without separate-code, segments
+        // share pages.
+        let mut section_part_sizes = OutputSectionPartMap::<u64>::with_size(output_sections.len())
+            .output_order_map(&output_sections, |id, _, _| {
+                if id.as_usize() < output_section_id::NUM_GENERATED_SECTIONS {
+                    7
+                } else {
+                    0
+                }
+            });
+        for id in [
+            output_section_id::RODATA,
+            output_section_id::TEXT,
+            output_section_id::DATA,
+        ] {
+            *section_part_sizes.regular_mut(id, alignment::MIN) = 7;
+        }
+        let section_part_layouts = layout_section_parts(
+            &section_part_sizes,
+            &output_sections,
+            page_size,
+            page_size,
+            separate_code,
+            &AHashMap::new(),
+        )
+        .unwrap();
+        let section_layouts = layout_sections(&section_part_layouts);
+        let header_info = HeaderInfo {
+            num_output_sections_with_content: 0,
+            active_segment_ids: (0..MAX_SEGMENTS).map(ProgramSegmentId::new).collect(),
+        };
+        let segment_layouts =
+            compute_segment_layout(&section_layouts, &output_sections, &header_info, page_size);
+        let pages = |id: ProgramSegmentId| {
+            let sizes = &segment_layouts
+                .segments
+                .iter()
+                .find(|s| s.id == id)
+                .unwrap()
+                .sizes;
+            // Segments are always on separate pages in memory, however without separate-code,
+            // adjacent segments can map the same page of the file.
+            let start = sizes.file_offset & !(page_size.value() as usize - 1);
+            start..page_size.align_up_usize(sizes.file_offset + sizes.file_size)
+        };
+        [pages(LOAD_RO), pages(LOAD_EXEC), pages(LOAD_RW)]
+    };
+    let overlaps =
+        |a: &std::ops::Range<usize>, b: &std::ops::Range<usize>| a.start < b.end && b.start < a.end;
+
+    let [ro, text, data] = segment_pages(true);
+    assert!(!overlaps(&text, &data), "{text:x?} overlaps {data:x?}");
+    assert!(!overlaps(&ro, &text), "{ro:x?} overlaps {text:x?}");
+
+    let [ro, text, data] = segment_pages(false);
+    assert!(overlaps(&ro, &text), "{ro:x?} doesn't overlap {text:x?}");
+    assert!(
+        overlaps(&text, &data),
+        "{text:x?} doesn't overlap {data:x?}"
+    );
+}
+
+/// Checks that when the common page size is smaller than the maximum page size, we only pad the
+/// file to the common page size, while keeping segments congruent modulo the maximum page size.
+#[test]
+fn test_common_page_size() {
+    let max_page_size = Alignment::page_size(0x10000).unwrap();
+    let layout = |common_page_size: Alignment| {
+        let output_sections =
+            crate::output_section_id::OutputSectionsBuilder::with_base_address(0x400000)
+                .build()
+                .unwrap();
+        let mut section_part_sizes = OutputSectionPartMap::<u64>::with_size(output_sections.len())
+            .output_order_map(&output_sections, |id, _, _| {
+                if id.as_usize() < output_section_id::NUM_GENERATED_SECTIONS {
+                    7
+                } else {
+                    0
+                }
+            });
+        for id in [
+            output_section_id::RODATA,
+            output_section_id::TEXT,
+            output_section_id::DATA_REL_RO,
+            output_section_id::DATA,
+        ] {
+            *section_part_sizes.regular_mut(id, alignment::MIN) = 7;
+        }
+        let section_part_layouts = layout_section_parts(
+            &section_part_sizes,
+            &output_sections,
+            max_page_size,
+            common_page_size,
+            true,
+            &AHashMap::new(),
+        )
+        .unwrap();
+        let section_layouts = layout_sections(&section_part_layouts);
+        let header_info = HeaderInfo {
+            num_output_sections_with_content: 0,
+            active_segment_ids: (0..MAX_SEGMENTS).map(ProgramSegmentId::new).collect(),
+        };
+        let segment_layouts = compute_segment_layout(
+            &section_layouts,
+            &output_sections,
+            &header_info,
+            common_page_size,
+        );
+        (compute_total_file_size(&section_layouts), segment_layouts)
+    };
+
+    let (max_file_size, _) = layout(max_page_size);
+    let (common_file_size, segment_layouts) = layout(alignment::PAGE);
+    assert!(
+        common_file_size < max_file_size,
+        "File size 0x{common_file_size:x} isn't smaller than 0x{max_file_size:x}"
+    );
+
+    let mask = max_page_size.value() - 1;
+    for seg_layout in &segment_layouts.segments {
+        if seg_layout.id.segment_type() != elf::SegmentType::Load {
+            continue;
+        }
+        let sizes = &seg_layout.sizes;
+        assert_eq!(
+            sizes.mem_offset & mask,
+            sizes.file_offset as u64 & mask,
+            "Segment {:?} at address 0x{:x} has file offset 0x{:x}",
+            seg_layout.id,
+            sizes.mem_offset,
+            sizes.file_offset,
+        );
+    }
+}
+
+/// Checks that `--no-rosegment` places read-only sections in the executable segment, saving a
+/// `PT_LOAD`.
+#[test]
+fn test_no_rosegment() {
+    use crate::program_segments::LOAD_EXEC;
+
+    let load_segments = |rosegment: bool| {
+        let mut output_sections =
+            crate::output_section_id::OutputSectionsBuilder::with_base_address(0x400000)
+                .build()
+                .unwrap();
+        if !rosegment {
+            output_sections.disable_rosegment();
+        }
+        let mut keep_sections = vec![false; output_sections.len()];
+        for id in [
+            output_section_id::FILE_HEADER,
+            output_section_id::RODATA,
+            output_section_id::TEXT,
+            output_section_id::DATA,
+        ] {
+            keep_sections[id.as_usize()] = true;
+        }
+        let keep_segments = segments_to_keep(&output_sections, &keep_sections);
+        let num_loads = (0..MAX_SEGMENTS)
+            .map(ProgramSegmentId::new)
+            .filter(|id| {
+                keep_segments[id.as_usize()] && id.segment_type() == crate::elf::SegmentType::Load
+            })
+            .count();
+        (
+            num_loads,
+            output_sections.loadable_segment_id_for(output_section_id::RODATA),
+        )
+    };
+
+    let (num_loads, rodata_segment) = load_segments(true);
+    assert_eq!(num_loads, 3);
+    assert_ne!(rodata_segment, Some(LOAD_EXEC));
+
+    let (num_loads, rodata_segment) = load_segments(false);
+    assert_eq!(num_loads, 2);
+    assert_eq!(rodata_segment, Some(LOAD_EXEC));
+}
+
+/// Checks that `.data.rel.ro` is placed with the other RELRO sections ahead of `.data` and that the
+/// RELRO segment covers exactly those sections, padded to the end of the page.
+#[test]
+fn test_relro_placement() {
+    use crate::program_segments::RELRO;
+
+    let page_size = alignment::PAGE;
+    let output_sections =
+        crate::output_section_id::OutputSectionsBuilder::with_base_address(0x400000)
+            .build()
+            .unwrap();
+    let mut section_part_sizes = OutputSectionPartMap::<u64>::with_size(output_sections.len())
+        .output_order_map(&output_sections, |id, _, _| {
+            if id.as_usize() < output_section_id::NUM_GENERATED_SECTIONS {
+                7
+            } else {
+                0
+            }
+        });
+    for id in [
+        output_section_id::INIT_ARRAY,
+        output_section_id::DATA_REL_RO,
+        output_section_id::DATA,
+        output_section_id::BSS,
+    ] {
+        *section_part_sizes.regular_mut(id, alignment::MIN) = 7;
+    }
+    let section_part_layouts = layout_section_parts(
+        &section_part_sizes,
+        &output_sections,
+        page_size,
+        page_size,
+        false,
+        &AHashMap::new(),
+    )
+    .unwrap();
+    let section_layouts = layout_sections(&section_part_layouts);
+    let header_info = HeaderInfo {
+        num_output_sections_with_content: 0,
+        active_segment_ids: (0..MAX_SEGMENTS).map(ProgramSegmentId::new).collect(),
+    };
+    let segment_layouts =
+        compute_segment_layout(&section_layouts, &output_sections, &header_info, page_size);
+    let relro = &segment_layouts
+        .segments
+        .iter()
+        .find(|s| s.id == RELRO)
+        .unwrap()
+        .sizes;
+    let relro_end = relro.mem_offset + relro.mem_size;
+
+    assert!(output_sections.is_relro(output_section_id::DATA_REL_RO));
+    assert!(!output_sections.is_relro(output_section_id::DATA));
+    let relro_sections = [
+        output_section_id::INIT_ARRAY,
+        output_section_id::DATA_REL_RO,
+        output_section_id::DYNAMIC,
+        output_section_id::GOT,
+    ];
+    let relro_start = relro_sections
+        .iter()
+        .map(|id| section_layouts.get(*id).mem_offset)
+        .min()
+        .unwrap();
+    let relro_data_end = relro_sections
+        .iter()
+        .map(|id| {
+            let layout = section_layouts.get(*id);
+            layout.mem_offset + layout.mem_size
+        })
+        .max()
+        .unwrap();
+    assert_eq!(relro.mem_offset, relro_start);
+    assert_eq!(relro_end, page_size.align_up(relro_data_end));
+    assert_eq!(relro.file_size as u64, relro_data_end - relro_start);
+
+    let data = section_layouts.get(output_section_id::DATA);
+    assert!(
+        data.mem_offset >= relro_end,
+        ".data at 0x{:x} is before the end of RELRO at 0x{relro_end:x}",
+        data.mem_offset
+    );
+    assert!(
+        section_layouts
+            .get(output_section_id::DATA_REL_RO)
+            .mem_offset
+            < data.mem_offset
+    );
+}
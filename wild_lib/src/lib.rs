@@ -1,26 +1,43 @@
 #![allow(clippy::needless_update)]
 
+pub(crate) mod aarch64;
+pub(crate) mod abi_tag;
 pub(crate) mod alignment;
 pub(crate) mod archive;
 pub(crate) mod archive_splitter;
 pub mod args;
+pub(crate) mod compression;
+pub(crate) mod defsym;
+pub(crate) mod diagnostics;
 pub(crate) mod elf;
 pub(crate) mod elf_writer;
 pub mod error;
+pub(crate) mod error_handling_script;
 pub(crate) mod file_kind;
 pub(crate) mod fs;
+pub(crate) mod gdb_index;
+pub(crate) mod gnu_property;
 pub(crate) mod hash;
+pub(crate) mod icf;
 pub(crate) mod identity;
+pub(crate) mod incremental;
 pub(crate) mod input_data;
 pub(crate) mod layout;
+pub(crate) mod link_map;
+pub(crate) mod link_options;
+pub(crate) mod linker_plugin;
 pub(crate) mod linker_script;
+pub(crate) mod memory_usage;
+pub(crate) mod object_cache;
 pub(crate) mod output_section_id;
 pub(crate) mod output_section_map;
 pub(crate) mod output_section_part_map;
 pub(crate) mod parsing;
 pub(crate) mod program_segments;
 pub(crate) mod relaxation;
+pub(crate) mod repro;
 pub(crate) mod resolution;
+pub(crate) mod riscv64;
 pub(crate) mod save_dir;
 pub(crate) mod sharding;
 pub(crate) mod shutdown;
@@ -30,40 +47,152 @@ pub(crate) mod symbol_db;
 pub(crate) mod timing;
 pub(crate) mod validation;
 
+use anyhow::Context as _;
+pub use link_options::LinkOptions;
+use std::ffi::OsString;
+
+/// Links using `args`, which are the arguments accepted by the `wild` binary, not including the
+/// program name. The whole link happens in-process on a thread pool that's created for it, so this
+/// can be called repeatedly from a long-lived process.
+pub fn link(args: impl IntoIterator<Item = OsString>) -> crate::error::Result {
+    Linker::from_args(args)?.run()
+}
+
 pub struct Linker {
     args: crate::args::Args,
+    /// Our arguments, not including the program name.
+    arguments: Vec<String>,
 }
 
 impl Linker {
     pub fn from_env() -> crate::error::Result<Self> {
-        Ok(Linker {
-            args: crate::args::Args::from_env()?,
-        })
+        Self::from_args(std::env::args_os().skip(1))
+    }
+
+    /// Creates a linker from `args`, which shouldn't include the program name.
+    pub fn from_args(args: impl IntoIterator<Item = OsString>) -> crate::error::Result<Self> {
+        let arguments = args
+            .into_iter()
+            .map(|arg| {
+                arg.into_string().map_err(|arg| {
+                    anyhow::anyhow!("Argument `{}` isn't valid UTF-8", arg.to_string_lossy())
+                })
+            })
+            .collect::<crate::error::Result<Vec<String>>>()?;
+        let args = crate::args::Args::parse(
+            std::iter::once("wild").chain(arguments.iter().map(String::as_str)),
+        )?;
+        Ok(Linker { args, arguments })
     }
 
     pub fn run(&self) -> crate::error::Result {
+        if let Some(query) = self.args.query {
+            return self.args.print_query(query);
+        }
         timing::init_tracing(&self.args);
-        self.link()
+        let thread_pool = self.args.build_thread_pool()?;
+        let result = thread_pool.install(|| {
+            if self.args.check_reproducible {
+                self.link_twice_and_compare()
+            } else {
+                self.link()
+            }
+        });
+        let Some(save_dir) = &self.args.reproduce_on_error else {
+            return result;
+        };
+        result.map_err(|error| match save_dir.save(&self.arguments) {
+            Ok(dir) => error.context(format!(
+                "Link failed. Inputs and arguments were saved to `{}`",
+                dir.display()
+            )),
+            Err(save_error) => error.context(format!(
+                "Link failed, then saving inputs for --reproduce-on-error also failed: \
+                 {save_error:#}"
+            )),
+        })
+    }
+
+    /// Runs the whole link twice with the same inputs, then checks that both links wrote exactly
+    /// the same bytes. Used for `--check-reproducible` to track down non-determinism.
+    fn link_twice_and_compare(&self) -> crate::error::Result {
+        let read_output = || {
+            std::fs::read(&self.args.output)
+                .with_context(|| format!("Failed to read output `{}`", self.args.output.display()))
+        };
+        self.link()?;
+        let first = read_output()?;
+        self.link()?;
+        let second = read_output()?;
+        validation::check_reproducible(&first, &second)
     }
 
     #[tracing::instrument(skip_all, name = "Link")]
     fn link(&self) -> crate::error::Result {
-        self.args.setup_thread_pool()?;
-        let mut output = elf_writer::Output::new(&self.args);
-        let input_data = input_data::InputData::from_args(&self.args)?;
+        let incremental = self
+            .args
+            .incremental
+            .then(|| incremental::Incremental::load(&self.args, &self.arguments));
+        let mut output = elf_writer::Output::new(&self.args, incremental);
+        let mut input_data = input_data::InputData::from_args(&self.args)?;
+        if let (Some(tar_path), Some(repro)) = (&self.args.repro, &mut input_data.repro) {
+            repro.write(tar_path, self.arguments.iter().cloned())?;
+        }
         let inputs = archive_splitter::split_archives(&input_data)?;
-        let files = parsing::parse_input_files(&inputs, &self.args)?;
+        let diagnostics = diagnostics::Diagnostics::default();
+        let plugin_output = self
+            .args
+            .plugin
+            .as_deref()
+            .map(|plugin| {
+                linker_plugin::run(plugin, &self.args, &input_data, &inputs, &diagnostics)
+            })
+            .transpose()?;
+        let inputs = match &plugin_output {
+            Some(plugin_output) => plugin_output.replace_claimed_inputs(inputs)?,
+            None => inputs,
+        };
+        let object_cache = self
+            .args
+            .object_cache
+            .as_deref()
+            .map(object_cache::ObjectCache::load)
+            .transpose()?;
+        let files = parsing::parse_input_files(&inputs, &self.args, object_cache.as_ref())?;
         let mut symbol_db = symbol_db::SymbolDb::build(&files, &self.args)?;
+        if let Some(object_cache) = &object_cache {
+            object_cache.update(&files)?;
+        }
+        for flag in &self.args.ignored_unknown_flags {
+            diagnostics.warn(format!("Ignoring unrecognised argument `{flag}`"));
+        }
+        if symbol_db.emulation == crate::args::Emulation::AArch64 {
+            for flag in &self.args.cortex_a53_fixes {
+                diagnostics.warn(format!(
+                    "Ignoring `{flag}`: we don't yet work around Cortex-A53 errata"
+                ));
+            }
+        }
         let (resolved_files, output_sections) =
-            resolution::resolve_symbols_and_sections(&files, &mut symbol_db)?;
+            resolution::resolve_symbols_and_sections(&files, &mut symbol_db, &diagnostics)?;
         let layout = layout::compute(
             &symbol_db,
+            &diagnostics,
             resolved_files,
             output_sections,
             input_data.version_script.as_ref(),
+            input_data.dynamic_list.as_ref(),
             &mut output,
         )?;
+        if self.args.map_file.is_some() || self.args.cref {
+            link_map::write(&layout)?;
+        }
+        if self.args.print_memory_usage {
+            memory_usage::print(&layout)?;
+        }
+        diagnostics.report(&self.args)?;
         output.write(&layout)?;
+        diagnostics.report_errors()?;
 
         let scope = tracing::span!(tracing::Level::INFO, "Shutdown");
         let _scope = scope.enter();
@@ -0,0 +1,389 @@
+//! Writes a link map describing where each input section and symbol ended up in the output and/or
+//! a cross reference table showing which files define and reference each symbol. The format
+//! roughly follows that of GNU ld so that tools and habits built around grepping its maps keep
+//! working. With `--map-format=json`, the same information is written as JSON instead.
+
+use crate::args::MapFormat;
+use crate::args::MapOutput;
+use crate::error::Result;
+use crate::layout::FileLayout;
+use crate::layout::Layout;
+use crate::layout::ResolutionValue;
+use crate::output_section_id::OutputSectionId;
+use crate::resolution::SectionSlot;
+use crate::sharding::ShardKey as _;
+use anyhow::Context;
+use object::Object as _;
+use object::ObjectSection as _;
+use object::ObjectSymbol as _;
+use object::SymbolKind;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Column at which addresses start. Names that don't fit before this column are put on their own
+/// line, as GNU ld does.
+const NAME_WIDTH: usize = 16;
+
+/// Column at which filenames start in the cross reference table.
+const CREF_NAME_WIDTH: usize = 50;
+
+struct InputSectionInfo {
+    address: u64,
+    size: u64,
+    name: String,
+    file: String,
+}
+
+struct SymbolInfo {
+    value: u64,
+    name: String,
+    /// Whether `name` had to be lossily converted because the symbol name wasn't valid UTF-8.
+    name_is_lossy: bool,
+}
+
+#[derive(Default)]
+struct CrefEntry {
+    /// The file containing the definition that was selected, if any.
+    definer: Option<String>,
+    /// The files that reference the symbol.
+    references: Vec<String>,
+}
+
+/// Writes the link map and/or cross reference table, whichever were requested. As with GNU ld, the
+/// cross reference table goes to the map file if there is one, otherwise to stdout. A JSON map
+/// file contains only the map, so in that case, the cross reference table always goes to stdout.
+#[tracing::instrument(skip_all, name = "Write link map")]
+pub(crate) fn write(layout: &Layout) -> Result {
+    let args = layout.args();
+    if args.cref && args.map_format == MapFormat::Json {
+        write_cref(layout, &mut std::io::stdout().lock())?;
+    }
+    match &args.map_file {
+        None | Some(MapOutput::Stdout) => write_to(layout, &mut std::io::stdout().lock()),
+        Some(MapOutput::File(path)) => {
+            let file = std::fs::File::create(path)
+                .with_context(|| format!("Failed to create map file `{}`", path.display()))?;
+            let mut out = std::io::BufWriter::new(file);
+            write_to(layout, &mut out)?;
+            out.flush()
+                .with_context(|| format!("Failed to write map file `{}`", path.display()))?;
+            Ok(())
+        }
+    }
+}
+
+fn write_to(layout: &Layout, out: &mut impl Write) -> Result {
+    let args = layout.args();
+    if args.map_file.is_some() {
+        match args.map_format {
+            MapFormat::Text => write_map(layout, out)?,
+            MapFormat::Json => write_json_map(layout, out)?,
+        }
+    }
+    if args.cref && args.map_format == MapFormat::Text {
+        write_cref(layout, out)?;
+    }
+    Ok(())
+}
+
+/// Returns the output sections to include in the map, in output order. We skip sections that
+/// aren't output as well as the file header, which has no name.
+fn mapped_output_sections(layout: &Layout) -> Vec<OutputSectionId> {
+    let mut section_ids = Vec::new();
+    layout.output_sections.sections_do(|section_id, details| {
+        if !details.name.is_empty()
+            && layout
+                .output_sections
+                .output_index_of_section(section_id)
+                .is_some()
+        {
+            section_ids.push(section_id);
+        }
+    });
+    section_ids
+}
+
+fn write_map(layout: &Layout, out: &mut impl Write) -> Result {
+    let (input_sections, symbols) = collect_inputs(layout)?;
+
+    writeln!(out, "Memory map")?;
+    writeln!(out)?;
+    for section_id in mapped_output_sections(layout) {
+        write_output_section(layout, section_id, &input_sections, out)?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "Symbols")?;
+    writeln!(out)?;
+    for symbol in symbols {
+        writeln!(
+            out,
+            "{:NAME_WIDTH$}0x{:016x}                {}",
+            "", symbol.value, symbol.name
+        )?;
+    }
+    Ok(())
+}
+
+fn write_output_section(
+    layout: &Layout,
+    section_id: OutputSectionId,
+    input_sections: &[Vec<InputSectionInfo>],
+    out: &mut impl Write,
+) -> Result {
+    let section_layout = layout.section_layouts.get(section_id);
+    write_name(&layout.output_sections.display_name(section_id), out)?;
+    writeln!(
+        out,
+        "0x{:016x} {:>#10x} align {:#x}",
+        section_layout.mem_offset,
+        section_layout.mem_size,
+        section_layout.alignment.value()
+    )?;
+    if let Some(inputs) = input_sections.get(section_id.as_usize()) {
+        for input in inputs {
+            write_name(&format!(" {}", input.name), out)?;
+            writeln!(
+                out,
+                "0x{:016x} {:>#10x} {}",
+                input.address, input.size, input.file
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the map as a JSON object with an `output_sections` array, in output order, each with the
+/// input sections that contributed to it, and a `symbols` array, sorted by value. Fields are always
+/// written in the same order, so that maps from different links can be diffed.
+fn write_json_map(layout: &Layout, out: &mut impl Write) -> Result {
+    let (input_sections, symbols) = collect_inputs(layout)?;
+
+    writeln!(out, "{{")?;
+    write!(out, "  \"output_sections\": [")?;
+    for (i, section_id) in mapped_output_sections(layout).into_iter().enumerate() {
+        let section_layout = layout.section_layouts.get(section_id);
+        write!(out, "{}\n    {{\"name\": ", json_separator(i))?;
+        write_json_string(&layout.output_sections.display_name(section_id), out)?;
+        write!(
+            out,
+            ", \"address\": {}, \"size\": {}, \"alignment\": {}, \"inputs\": [",
+            section_layout.mem_offset,
+            section_layout.mem_size,
+            section_layout.alignment.value()
+        )?;
+        let inputs = input_sections
+            .get(section_id.as_usize())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for (j, input) in inputs.iter().enumerate() {
+            write!(out, "{}\n      {{\"file\": ", json_separator(j))?;
+            write_json_string(&input.file, out)?;
+            write!(out, ", \"section\": ")?;
+            write_json_string(&input.name, out)?;
+            write!(
+                out,
+                ", \"offset\": {}, \"size\": {}}}",
+                input.address - section_layout.mem_offset,
+                input.size
+            )?;
+        }
+        if !inputs.is_empty() {
+            write!(out, "\n    ")?;
+        }
+        write!(out, "]}}")?;
+    }
+    writeln!(out, "\n  ],")?;
+
+    write!(out, "  \"symbols\": [")?;
+    for (i, symbol) in symbols.iter().enumerate() {
+        write!(out, "{}\n    {{\"name\": ", json_separator(i))?;
+        write_json_string(&symbol.name, out)?;
+        write!(
+            out,
+            ", \"name_is_lossy\": {}, \"value\": {}}}",
+            symbol.name_is_lossy, symbol.value
+        )?;
+    }
+    writeln!(out, "\n  ]")?;
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
+fn json_separator(index: usize) -> &'static str {
+    if index == 0 {
+        ""
+    } else {
+        ","
+    }
+}
+
+fn write_json_string(value: &str, out: &mut impl Write) -> Result {
+    write!(out, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(out, "\\\"")?,
+            '\\' => write!(out, "\\\\")?,
+            '\n' => write!(out, "\\n")?,
+            '\r' => write!(out, "\\r")?,
+            '\t' => write!(out, "\\t")?,
+            c if c < ' ' => write!(out, "\\u{:04x}", c as u32)?,
+            c => write!(out, "{c}")?,
+        }
+    }
+    write!(out, "\"")?;
+    Ok(())
+}
+
+fn write_name(name: &str, out: &mut impl Write) -> Result {
+    if name.len() >= NAME_WIDTH {
+        writeln!(out, "{name}")?;
+        write!(out, "{:NAME_WIDTH$}", "")?;
+    } else {
+        write!(out, "{name:NAME_WIDTH$}")?;
+    }
+    Ok(())
+}
+
+/// Writes a table with an entry for each global symbol. The first file listed for each symbol is
+/// the one that provided the definition, or `<undefined>` if there was none. Subsequent files are
+/// those that reference the symbol.
+fn write_cref(layout: &Layout, out: &mut impl Write) -> Result {
+    let entries = collect_cref(layout)?;
+    writeln!(out)?;
+    writeln!(out, "Cross Reference Table")?;
+    writeln!(out)?;
+    writeln!(out, "{:CREF_NAME_WIDTH$}File", "Symbol")?;
+    for (name, entry) in &entries {
+        if name.len() >= CREF_NAME_WIDTH {
+            writeln!(out, "{name}")?;
+            write!(out, "{:CREF_NAME_WIDTH$}", "")?;
+        } else {
+            write!(out, "{name:CREF_NAME_WIDTH$}")?;
+        }
+        writeln!(out, "{}", entry.definer.as_deref().unwrap_or("<undefined>"))?;
+        for reference in &entry.references {
+            writeln!(out, "{:CREF_NAME_WIDTH$}{reference}", "")?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the definer and referencing files of each global symbol in our loaded objects, keyed by
+/// symbol name.
+fn collect_cref(layout: &Layout) -> Result<BTreeMap<String, CrefEntry>> {
+    let mut entries: BTreeMap<String, CrefEntry> = BTreeMap::new();
+    for file_layout in &layout.file_layouts {
+        let FileLayout::Object(object) = file_layout else {
+            continue;
+        };
+        let file = object.input.to_string();
+        for symbol in object.object.symbols() {
+            if symbol.is_local() || matches!(symbol.kind(), SymbolKind::Section | SymbolKind::File)
+            {
+                continue;
+            }
+            let name = symbol.name_bytes()?;
+            if name.is_empty() {
+                continue;
+            }
+            let entry = entries
+                .entry(String::from_utf8_lossy(name).into_owned())
+                .or_default();
+            let symbol_id = object.start_symbol_id.add_usize(symbol.index().0);
+            let definition = layout.symbol_db.definition(symbol_id);
+            if symbol.is_undefined() {
+                entry.references.push(file.clone());
+                let file_id = layout.symbol_db.file_id_for_symbol(definition);
+                if entry.definer.is_none()
+                    && !definition.is_undefined()
+                    && layout.is_file_loaded(file_id)
+                {
+                    entry.definer = Some(layout.file_layouts[file_id.as_usize()].to_string());
+                }
+            } else if definition == symbol_id {
+                entry.definer = Some(file.clone());
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Returns the input sections contributing to each output section, indexed by output section ID
+/// and sorted by address, together with all the symbols defined by our input objects, sorted by
+/// value.
+fn collect_inputs(layout: &Layout) -> Result<(Vec<Vec<InputSectionInfo>>, Vec<SymbolInfo>)> {
+    let mut input_sections: Vec<Vec<InputSectionInfo>> = Vec::new();
+    input_sections.resize_with(layout.output_sections.len(), Vec::new);
+    let mut symbols = Vec::new();
+    for file_layout in &layout.file_layouts {
+        let FileLayout::Object(object) = file_layout else {
+            continue;
+        };
+        let file = object.input.to_string();
+        for (slot, resolution) in object.sections.iter().zip(&object.section_resolutions) {
+            let (SectionSlot::Loaded(section), Some(resolution)) = (slot, resolution) else {
+                continue;
+            };
+            let Some(output_section_id) = section.output_section_id else {
+                continue;
+            };
+            let name = object
+                .object
+                .section_by_index(section.index)?
+                .name_bytes()?;
+            input_sections[output_section_id.as_usize()].push(InputSectionInfo {
+                address: resolution.value.address()?,
+                size: section.size,
+                name: String::from_utf8_lossy(name).into_owned(),
+                file: file.clone(),
+            });
+        }
+        for symbol in object.object.symbols() {
+            if !symbol.is_definition()
+                || matches!(symbol.kind(), SymbolKind::Section | SymbolKind::File)
+            {
+                continue;
+            }
+            let symbol_id = object.start_symbol_id.add_usize(symbol.index().0);
+            if layout.symbol_db.definition(symbol_id) != symbol_id {
+                // Another definition of this symbol took precedence.
+                continue;
+            }
+            let value = match layout.symbol_resolution(symbol_id).map(|r| r.value) {
+                Some(ResolutionValue::Address(value) | ResolutionValue::Absolute(value)) => value,
+                _ => continue,
+            };
+            let name = symbol.name_bytes()?;
+            if name.is_empty() {
+                continue;
+            }
+            symbols.push(SymbolInfo {
+                value,
+                name: String::from_utf8_lossy(name).into_owned(),
+                name_is_lossy: std::str::from_utf8(name).is_err(),
+            });
+        }
+    }
+    for inputs in &mut input_sections {
+        inputs.sort_by_key(|input| input.address);
+    }
+    symbols.sort_by(|a, b| (a.value, &a.name).cmp(&(b.value, &b.name)));
+    Ok((input_sections, symbols))
+}
+
+#[test]
+fn test_write_json_string() {
+    let json = |value: &str| {
+        let mut out = Vec::new();
+        write_json_string(value, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(json("main"), r#""main""#);
+    assert_eq!(json("a\"b\\c"), r#""a\"b\\c""#);
+    assert_eq!(json("x\n\t\u{1}"), r#""x\n\t\u0001""#);
+    assert_eq!(
+        json(&String::from_utf8_lossy(b"bad\xff")),
+        "\"bad\u{fffd}\""
+    );
+}
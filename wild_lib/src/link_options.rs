@@ -0,0 +1,100 @@
+//! A builder for the arguments to [`crate::link`], for use when embedding the linker in another
+//! program. Each method appends the equivalent command-line arguments, so options behave exactly
+//! as they would on the command line and anything without a dedicated method can be passed via
+//! [`LinkOptions::arg`].
+
+use crate::error::Result;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default)]
+pub struct LinkOptions {
+    args: Vec<OsString>,
+}
+
+impl LinkOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the path of the output file. Equivalent to `-o`.
+    pub fn output(self, path: impl AsRef<Path>) -> Self {
+        self.arg("-o").arg(path.as_ref())
+    }
+
+    /// Adds an input file, e.g. an object, archive or shared object.
+    pub fn input(self, path: impl AsRef<Path>) -> Self {
+        self.arg(path.as_ref())
+    }
+
+    /// Adds a library to be found via the library search path. Equivalent to `-l`.
+    pub fn library(self, name: &str) -> Self {
+        self.arg(format!("-l{name}"))
+    }
+
+    /// Adds a directory to the library search path. Equivalent to `-L`.
+    pub fn library_search_path(self, dir: impl AsRef<Path>) -> Self {
+        self.arg("-L").arg(dir.as_ref())
+    }
+
+    /// Produces a shared object. Equivalent to `-shared`.
+    pub fn shared(self) -> Self {
+        self.arg("-shared")
+    }
+
+    /// Produces a position-independent executable. Equivalent to `-pie`.
+    pub fn pie(self) -> Self {
+        self.arg("-pie")
+    }
+
+    /// Don't link against shared objects. Equivalent to `-static`.
+    pub fn static_linking(self) -> Self {
+        self.arg("-static")
+    }
+
+    /// Sets the number of threads to use. Equivalent to `--threads`.
+    pub fn threads(self, num_threads: usize) -> Self {
+        self.arg(format!("--threads={num_threads}"))
+    }
+
+    /// Omits all symbol information from the output. Equivalent to `--strip-all`.
+    pub fn strip_all(self) -> Self {
+        self.arg("--strip-all")
+    }
+
+    /// Omits debug information from the output. Equivalent to `--strip-debug`.
+    pub fn strip_debug(self) -> Self {
+        self.arg("--strip-debug")
+    }
+
+    /// Appends an arbitrary command-line argument.
+    pub fn arg(mut self, arg: impl AsRef<OsStr>) -> Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// Returns the arguments that we'll pass to the linker.
+    pub fn args(&self) -> &[OsString] {
+        &self.args
+    }
+
+    /// Runs the link.
+    pub fn link(&self) -> Result {
+        crate::link(self.args.iter().cloned())
+    }
+}
+
+#[test]
+fn test_link_options_args() {
+    let options = LinkOptions::new()
+        .static_linking()
+        .library_search_path("/usr/lib")
+        .library("c")
+        .input("a.o")
+        .output("out");
+    assert_eq!(
+        options.args(),
+        ["-static", "-L", "/usr/lib", "-lc", "a.o", "-o", "out"]
+    );
+}
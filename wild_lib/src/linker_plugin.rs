@@ -0,0 +1,1263 @@
+//! Support for linker plugins, which is how GCC and LLVM do link-time optimisation (LTO). A plugin
+//! is a shared library that we load, then offer each input that might contain the compiler's
+//! intermediate representation (IR). The plugin claims the inputs that it understands and tells us
+//! which symbols they define and reference. Once we've seen all our inputs, we tell the plugin how
+//! each of those symbols got resolved. It then compiles the IR and gives us objects to link in
+//! place of the inputs that it claimed. See https://gcc.gnu.org/wiki/whopr/driver for details of
+//! the API.
+
+use crate::archive_splitter::split_file;
+use crate::archive_splitter::InputBytes;
+use crate::args::Args;
+use crate::args::OutputKind;
+use crate::diagnostics::Diagnostics;
+use crate::elf::FileHeader;
+use crate::elf::SectionHeader;
+use crate::elf::SymtabEntry;
+use crate::error::Result;
+use crate::file_kind::is_llvm_ir;
+use crate::file_kind::FileKind;
+use crate::input_data::FileId;
+use crate::input_data::InputData;
+use crate::input_data::InputFile;
+use crate::parsing::InputObject;
+use crate::resolution::ResolvedFile;
+use crate::sharding::ShardKey as _;
+use crate::symbol::SymbolName;
+use crate::symbol_db::SymbolDb;
+use crate::symbol_db::SymbolId;
+use ahash::AHashMap;
+use ahash::AHashSet;
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context as _;
+use object::elf;
+use object::LittleEndian;
+use object::Object as _;
+use object::ObjectSection as _;
+use object::ObjectSymbol as _;
+use object::U16;
+use object::U32;
+use object::U64;
+use rayon::iter::IntoParallelRefIterator as _;
+use rayon::iter::ParallelIterator as _;
+use std::ffi::c_char;
+use std::ffi::c_int;
+use std::ffi::c_void;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::ffi::OsStr;
+use std::os::fd::AsRawFd as _;
+use std::os::unix::ffi::OsStrExt as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicPtr;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+
+type Status = c_int;
+
+const LDPS_OK: Status = 0;
+const LDPS_NO_SYMS: Status = 1;
+const LDPS_BAD_HANDLE: Status = 2;
+const LDPS_ERR: Status = 3;
+
+// Tags for the entries of the transfer vector that we pass to the plugin's `onload` function.
+const LDPT_NULL: c_int = 0;
+const LDPT_API_VERSION: c_int = 1;
+const LDPT_LINKER_OUTPUT: c_int = 3;
+const LDPT_OPTION: c_int = 4;
+const LDPT_REGISTER_CLAIM_FILE_HOOK: c_int = 5;
+const LDPT_REGISTER_ALL_SYMBOLS_READ_HOOK: c_int = 6;
+const LDPT_REGISTER_CLEANUP_HOOK: c_int = 7;
+const LDPT_ADD_SYMBOLS: c_int = 8;
+const LDPT_GET_SYMBOLS: c_int = 9;
+const LDPT_ADD_INPUT_FILE: c_int = 10;
+const LDPT_MESSAGE: c_int = 11;
+const LDPT_GET_INPUT_FILE: c_int = 12;
+const LDPT_RELEASE_INPUT_FILE: c_int = 13;
+const LDPT_ADD_INPUT_LIBRARY: c_int = 14;
+const LDPT_OUTPUT_NAME: c_int = 15;
+const LDPT_SET_EXTRA_LIBRARY_PATH: c_int = 16;
+const LDPT_GET_VIEW: c_int = 18;
+const LDPT_GET_SYMBOLS_V2: c_int = 25;
+const LDPT_GET_SYMBOLS_V3: c_int = 28;
+const LDPT_ADD_SYMBOLS_V2: c_int = 33;
+
+// The kinds of output that we can tell the plugin we're producing.
+const LDPO_REL: c_int = 0;
+const LDPO_EXEC: c_int = 1;
+const LDPO_DYN: c_int = 2;
+const LDPO_PIE: c_int = 3;
+
+// Values of `PluginSymbol::def`.
+const LDPK_DEF: u8 = 0;
+const LDPK_WEAKDEF: u8 = 1;
+const LDPK_UNDEF: u8 = 2;
+const LDPK_WEAKUNDEF: u8 = 3;
+const LDPK_COMMON: u8 = 4;
+
+// Values of `PluginSymbol::visibility`.
+const LDPV_DEFAULT: c_int = 0;
+const LDPV_PROTECTED: c_int = 1;
+const LDPV_INTERNAL: c_int = 2;
+const LDPV_HIDDEN: c_int = 3;
+
+// Values of `PluginSymbol::resolution`.
+const LDPR_UNDEF: c_int = 1;
+const LDPR_PREVAILING_DEF: c_int = 2;
+const LDPR_PREVAILING_DEF_IRONLY: c_int = 3;
+const LDPR_PREEMPTED_REG: c_int = 4;
+const LDPR_PREEMPTED_IR: c_int = 5;
+const LDPR_RESOLVED_IR: c_int = 6;
+const LDPR_RESOLVED_EXEC: c_int = 7;
+const LDPR_RESOLVED_DYN: c_int = 8;
+const LDPR_PREVAILING_DEF_IRONLY_EXP: c_int = 9;
+
+// Levels of the messages that the plugin can send us.
+const LDPL_WARNING: c_int = 1;
+const LDPL_ERROR: c_int = 2;
+
+#[repr(C)]
+struct TransferVector {
+    tag: c_int,
+    value: TransferValue,
+}
+
+#[repr(C)]
+union TransferValue {
+    int: c_int,
+    string: *const c_char,
+    pointer: *const c_void,
+}
+
+#[repr(C)]
+struct PluginInputFile {
+    name: *const c_char,
+    fd: c_int,
+    offset: libc::off_t,
+    filesize: libc::off_t,
+    handle: *mut c_void,
+}
+
+#[repr(C)]
+struct PluginSymbol {
+    name: *mut c_char,
+    version: *mut c_char,
+    /// Older versions of the API had `def` as an int. These fields are laid out such that on a
+    /// little-endian system, `def` is still in the same place.
+    def: u8,
+    symbol_type: u8,
+    section_kind: u8,
+    unused: u8,
+    visibility: c_int,
+    size: u64,
+    comdat_key: *mut c_char,
+    resolution: c_int,
+}
+
+type OnloadFn = unsafe extern "C" fn(*const TransferVector) -> Status;
+type ClaimFileHook = unsafe extern "C" fn(*const PluginInputFile, *mut c_int) -> Status;
+type AllSymbolsReadHook = unsafe extern "C" fn() -> Status;
+type CleanupHook = unsafe extern "C" fn() -> Status;
+
+/// Serialises links that use a plugin. Plugins keep global state of their own, so they can't be
+/// used by more than one link at a time.
+static PLUGIN_LOCK: Mutex<()> = Mutex::new(());
+
+/// The state of the link that's currently calling into its plugin. The plugin API doesn't pass any
+/// context to callbacks, so we point this at the link's state for the duration of each call.
+static ACTIVE_STATE: AtomicPtr<Mutex<PluginState>> = AtomicPtr::new(std::ptr::null_mut());
+
+/// A plugin loaded for one link, together with the state that its callbacks operate on.
+struct LinkerPlugin {
+    /// Boxed, so that it stays put while callbacks might be pointing at it.
+    state: Box<Mutex<PluginState>>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+#[derive(Default)]
+struct PluginState {
+    claim_file_hook: Option<ClaimFileHook>,
+    all_symbols_read_hook: Option<AllSymbolsReadHook>,
+    cleanup_hook: Option<CleanupHook>,
+
+    /// The inputs that we've offered to the plugin. A file's handle is its index plus one.
+    files: Vec<OfferedFile>,
+
+    /// Objects that the plugin asked us to link, generally the output of LTO.
+    added_files: Vec<PathBuf>,
+
+    /// Libraries that the plugin asked us to link, as they'd be given to `-l`.
+    added_libraries: Vec<String>,
+
+    /// A directory to search for `added_libraries` before the regular library search path.
+    extra_library_path: Option<PathBuf>,
+
+    /// Messages from the plugin that we haven't yet reported, with their levels.
+    messages: Vec<(c_int, String)>,
+}
+
+struct OfferedFile {
+    /// The name of the file on disk. For a member of a regular archive, this is the archive.
+    name: CString,
+    fd: c_int,
+    offset: u64,
+    /// The address and size of the file's contents, which we hand out via `get_view`.
+    data_address: usize,
+    data_len: usize,
+    /// The symbols that the plugin told us about, if it claimed the file.
+    symbols: Vec<IrSymbol>,
+    /// How each of `symbols` got resolved, or `None` if the file turned out to be an archive
+    /// member that nothing needed.
+    resolutions: Option<Vec<c_int>>,
+}
+
+struct IrSymbol {
+    name: Vec<u8>,
+    def: u8,
+    visibility: c_int,
+    size: u64,
+    /// Whether the symbol is in a COMDAT group, in which case other files may define it too.
+    is_comdat: bool,
+}
+
+/// What a linker plugin did to our inputs.
+pub(crate) struct PluginOutput {
+    plugin: LinkerPlugin,
+
+    /// Which of our inputs the plugin claimed, indexed like our inputs.
+    claimed: Vec<bool>,
+
+    /// Objects and libraries to link in place of the inputs that the plugin claimed.
+    files: Vec<InputFile>,
+
+    /// Strings that we passed to the plugin's `onload`. Plugins hold on to these, so they need to
+    /// stay alive until we're done with the plugin.
+    _onload_strings: Vec<CString>,
+}
+
+/// Loads the plugin at `path`, lets it claim whichever of `inputs` it wants, then runs it to
+/// produce objects to replace them.
+#[tracing::instrument(skip_all, name = "Run linker plugin")]
+pub(crate) fn run(
+    path: &Path,
+    args: &Args,
+    input_data: &InputData,
+    inputs: &[InputBytes],
+    diagnostics: &Diagnostics,
+) -> Result<PluginOutput> {
+    let mut output = PluginOutput {
+        plugin: LinkerPlugin {
+            state: Box::default(),
+            _lock: PLUGIN_LOCK.lock().unwrap_or_else(PoisonError::into_inner),
+        },
+        claimed: vec![false; inputs.len()],
+        files: Vec::new(),
+        _onload_strings: Vec::new(),
+    };
+    let plugin = &output.plugin;
+    let onload = load_plugin(path)?;
+    output._onload_strings = call_onload(plugin, onload, args, diagnostics)
+        .with_context(|| format!("Failed to load linker plugin `{}`", path.display()))?;
+    let claim_file_hook = plugin.state().claim_file_hook.with_context(|| {
+        format!(
+            "Linker plugin `{}` didn't register to claim files",
+            path.display()
+        )
+    })?;
+
+    // Working out which inputs might contain IR requires parsing them, so do that in parallel, but
+    // the plugin isn't thread-safe, so offer them to it one at a time.
+    let candidates = inputs
+        .par_iter()
+        .map(|input| might_be_ir(input))
+        .collect::<Vec<bool>>();
+    let mut open_files = AHashMap::new();
+    let mut claimed_file_indexes = Vec::new();
+    for (input_index, input) in inputs.iter().enumerate() {
+        if !candidates[input_index] {
+            continue;
+        }
+        if claim_file(plugin, claim_file_hook, input, &mut open_files, diagnostics)? {
+            output.claimed[input_index] = true;
+            claimed_file_indexes.push(plugin.state().files.len() - 1);
+        }
+    }
+    if claimed_file_indexes.is_empty() {
+        return Ok(output);
+    }
+
+    resolve_ir_symbols(plugin, args, inputs, &output.claimed, &claimed_file_indexes)?;
+
+    let all_symbols_read_hook = plugin.state().all_symbols_read_hook;
+    if let Some(hook) = all_symbols_read_hook {
+        // Safety: The plugin registered this hook for us to call once we've read all symbols.
+        let status = plugin.call(|| unsafe { hook() });
+        check_status(plugin, status, diagnostics).context("Linker plugin failed")?;
+    }
+    drop(open_files);
+
+    let (added_files, added_libraries, extra_library_path) = {
+        let mut state = plugin.state();
+        (
+            std::mem::take(&mut state.added_files),
+            std::mem::take(&mut state.added_libraries),
+            state.extra_library_path.take(),
+        )
+    };
+    for path in added_files {
+        output.files.push(input_data.open_plugin_input(&path)?);
+    }
+    for name in added_libraries {
+        if let Some(file) = input_data.open_plugin_library(&name, extra_library_path.as_ref())? {
+            output.files.push(file);
+        }
+    }
+    Ok(output)
+}
+
+impl PluginOutput {
+    /// Returns `inputs` with the inputs that the plugin claimed replaced by what it produced.
+    pub(crate) fn replace_claimed_inputs<'data>(
+        &'data self,
+        inputs: Vec<InputBytes<'data>>,
+    ) -> Result<Vec<InputBytes<'data>>> {
+        let mut replacements = Some(
+            self.files
+                .iter()
+                .map(split_file)
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .flatten(),
+        );
+        let mut result = Vec::with_capacity(inputs.len());
+        for (input, claimed) in inputs.into_iter().zip(&self.claimed) {
+            if !*claimed {
+                result.push(input);
+            } else if let Some(replacements) = replacements.take() {
+                // What the plugin produced goes where the first claimed input was.
+                result.extend(replacements);
+            }
+        }
+        result.extend(replacements.into_iter().flatten());
+        Ok(result)
+    }
+}
+
+impl Drop for PluginOutput {
+    fn drop(&mut self) {
+        let cleanup_hook = self.plugin.state().cleanup_hook.take();
+        if let Some(hook) = cleanup_hook {
+            // Safety: The plugin registered this hook for us to call once we're done with it.
+            // Errors here generally mean that temporary files couldn't be deleted, which isn't
+            // worth failing the link for.
+            self.plugin.call(|| unsafe { hook() });
+        }
+    }
+}
+
+impl LinkerPlugin {
+    fn state(&self) -> MutexGuard<'_, PluginState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Runs `f`, which calls into the plugin, with our state available to the plugin's callbacks.
+    /// We mustn't hold the lock on our state while doing so, since the callbacks need it.
+    fn call<T>(&self, f: impl FnOnce() -> T) -> T {
+        struct Deactivate;
+        impl Drop for Deactivate {
+            fn drop(&mut self) {
+                ACTIVE_STATE.store(std::ptr::null_mut(), Ordering::Release);
+            }
+        }
+        ACTIVE_STATE.store(
+            std::ptr::from_ref(&*self.state).cast_mut(),
+            Ordering::Release,
+        );
+        let _deactivate = Deactivate;
+        f()
+    }
+}
+
+fn load_plugin(path: &Path) -> Result<OnloadFn> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    // Safety: Loading a library runs its initialisers, so we're trusting the plugin that we were
+    // asked to load.
+    let handle = unsafe { libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_GLOBAL) };
+    if handle.is_null() {
+        // Safety: dlerror returns either null or a pointer to a null-terminated string.
+        let reason = unsafe {
+            let error = libc::dlerror();
+            if error.is_null() {
+                "unknown error".to_owned()
+            } else {
+                CStr::from_ptr(error).to_string_lossy().into_owned()
+            }
+        };
+        bail!(
+            "Failed to load linker plugin `{}`: {reason}",
+            path.display()
+        );
+    }
+    // Safety: `handle` came from a successful dlopen and the name is null-terminated.
+    let onload = unsafe { libc::dlsym(handle, c"onload".as_ptr()) };
+    if onload.is_null() {
+        bail!(
+            "`{}` isn't a linker plugin, since it doesn't define `onload`",
+            path.display()
+        );
+    }
+    // Safety: Plugins define `onload` with this signature.
+    Ok(unsafe { std::mem::transmute::<*mut c_void, OnloadFn>(onload) })
+}
+
+/// Calls the plugin's `onload` function, passing it our options and callbacks. Returns the strings
+/// that we passed, since they need to outlive the plugin.
+fn call_onload(
+    plugin: &LinkerPlugin,
+    onload: OnloadFn,
+    args: &Args,
+    diagnostics: &Diagnostics,
+) -> Result<Vec<CString>> {
+    let linker_output = match args.output_kind {
+        OutputKind::Relocatable => LDPO_REL,
+        OutputKind::SharedObject => LDPO_DYN,
+        _ if args.pie => LDPO_PIE,
+        _ => LDPO_EXEC,
+    };
+    let mut strings = vec![CString::new(args.output.as_os_str().as_bytes())?];
+    for opt in &args.plugin_opts {
+        strings.push(CString::new(opt.as_str())?);
+    }
+    let int = |tag, int| TransferVector {
+        tag,
+        value: TransferValue { int },
+    };
+    let string = |tag, string: &CString| TransferVector {
+        tag,
+        value: TransferValue {
+            string: string.as_ptr(),
+        },
+    };
+    let pointer = |tag, pointer: *const c_void| TransferVector {
+        tag,
+        value: TransferValue { pointer },
+    };
+    let mut transfer_vector = vec![
+        int(LDPT_API_VERSION, 1),
+        int(LDPT_LINKER_OUTPUT, linker_output),
+        string(LDPT_OUTPUT_NAME, &strings[0]),
+    ];
+    for opt in &strings[1..] {
+        transfer_vector.push(string(LDPT_OPTION, opt));
+    }
+    transfer_vector.extend([
+        pointer(
+            LDPT_REGISTER_CLAIM_FILE_HOOK,
+            register_claim_file_hook as *const c_void,
+        ),
+        pointer(
+            LDPT_REGISTER_ALL_SYMBOLS_READ_HOOK,
+            register_all_symbols_read_hook as *const c_void,
+        ),
+        pointer(
+            LDPT_REGISTER_CLEANUP_HOOK,
+            register_cleanup_hook as *const c_void,
+        ),
+        pointer(LDPT_ADD_SYMBOLS, add_symbols as *const c_void),
+        pointer(LDPT_ADD_SYMBOLS_V2, add_symbols as *const c_void),
+        pointer(LDPT_GET_SYMBOLS, get_symbols_v1 as *const c_void),
+        pointer(LDPT_GET_SYMBOLS_V2, get_symbols_v2 as *const c_void),
+        pointer(LDPT_GET_SYMBOLS_V3, get_symbols_v3 as *const c_void),
+        pointer(LDPT_ADD_INPUT_FILE, add_input_file as *const c_void),
+        pointer(LDPT_ADD_INPUT_LIBRARY, add_input_library as *const c_void),
+        pointer(
+            LDPT_SET_EXTRA_LIBRARY_PATH,
+            set_extra_library_path as *const c_void,
+        ),
+        pointer(LDPT_GET_INPUT_FILE, get_input_file as *const c_void),
+        pointer(LDPT_RELEASE_INPUT_FILE, release_input_file as *const c_void),
+        pointer(LDPT_GET_VIEW, get_view as *const c_void),
+        pointer(LDPT_MESSAGE, message as *const c_void),
+        int(LDPT_NULL, 0),
+    ]);
+    // Safety: The transfer vector is terminated and everything that it points to outlives the call.
+    let status = plugin.call(|| unsafe { onload(transfer_vector.as_ptr()) });
+    check_status(plugin, status, diagnostics)?;
+    Ok(strings)
+}
+
+/// Returns whether `input` might contain IR and so should be offered to the plugin. That's either
+/// LLVM bitcode, or an ELF object containing GCC's LTO sections.
+fn might_be_ir(input: &InputBytes) -> bool {
+    match input.kind {
+        FileKind::LlvmIr => true,
+        FileKind::ElfObject | FileKind::Archive | FileKind::ThinArchive => {
+            is_llvm_ir(input.data)
+                || crate::elf::File::parse(input.data).is_ok_and(|object| {
+                    object.sections().any(|section| {
+                        section
+                            .name_bytes()
+                            .is_ok_and(|name| name.starts_with(b".gnu.lto_"))
+                    })
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Offers `input` to the plugin. Returns whether the plugin claimed it.
+fn claim_file(
+    plugin: &LinkerPlugin,
+    hook: ClaimFileHook,
+    input: &InputBytes,
+    open_files: &mut AHashMap<PathBuf, std::fs::File>,
+    diagnostics: &Diagnostics,
+) -> Result<bool> {
+    let file = input.input.file;
+    let file_data = file.data();
+    let data_address = input.data.as_ptr() as usize;
+    // Members of regular archives are identified by the archive and their offset within it. The
+    // members of thin archives are files in their own right.
+    let (path, offset) = if file_data.as_ptr_range().contains(&input.data.as_ptr()) {
+        (
+            file.filename.clone(),
+            (data_address - file_data.as_ptr() as usize) as u64,
+        )
+    } else {
+        let member = input
+            .input
+            .entry_filename
+            .context("Input isn't part of the file that contains it")?;
+        let directory = file.filename.parent().unwrap_or_else(|| Path::new(""));
+        (directory.join(OsStr::from_bytes(member.as_slice())), 0)
+    };
+    let fd = match open_files.entry(path.clone()) {
+        std::collections::hash_map::Entry::Occupied(entry) => entry.get().as_raw_fd(),
+        std::collections::hash_map::Entry::Vacant(entry) => entry
+            .insert(
+                std::fs::File::open(&path)
+                    .with_context(|| format!("Failed to open `{}`", path.display()))?,
+            )
+            .as_raw_fd(),
+    };
+    let name = CString::new(path.as_os_str().as_bytes())?;
+    let plugin_file = PluginInputFile {
+        name: name.as_ptr(),
+        fd,
+        offset: offset as libc::off_t,
+        filesize: input.data.len() as libc::off_t,
+        handle: std::ptr::null_mut(),
+    };
+    let handle = {
+        let mut state = plugin.state();
+        state.files.push(OfferedFile {
+            name,
+            fd,
+            offset,
+            data_address,
+            data_len: input.data.len(),
+            symbols: Vec::new(),
+            resolutions: None,
+        });
+        state.files.len()
+    };
+    let plugin_file = PluginInputFile {
+        handle: handle as *mut c_void,
+        ..plugin_file
+    };
+    let mut claimed = 0;
+    // Safety: `plugin_file` describes an open file and its name outlives the plugin, since we keep
+    // it in our state.
+    let status = plugin.call(|| unsafe { hook(&plugin_file, &mut claimed) });
+    check_status(plugin, status, diagnostics)
+        .with_context(|| format!("Linker plugin failed to read `{input}`"))?;
+    if claimed == 0 {
+        // Forget about the file, so that the last offered file is always the last claimed one.
+        plugin.state().files.pop();
+    }
+    Ok(claimed != 0)
+}
+
+/// What defines a symbol, as far as telling the plugin how the symbol got resolved goes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Definer {
+    Undefined,
+    Ir,
+    SharedObject,
+    Regular,
+}
+
+/// Works out how each symbol in the files that the plugin claimed gets resolved, then records that
+/// in our state for when the plugin asks. To do that, we run our regular symbol resolution over our
+/// inputs, with each claimed file replaced by a placeholder object that has the symbols that the
+/// plugin told us about.
+fn resolve_ir_symbols(
+    plugin: &LinkerPlugin,
+    args: &Args,
+    inputs: &[InputBytes],
+    claimed: &[bool],
+    claimed_file_indexes: &[usize],
+) -> Result {
+    let mut state = plugin.state();
+    let default_header = inputs
+        .iter()
+        .zip(claimed)
+        .filter(|(input, claimed)| !**claimed && input.kind == FileKind::ElfObject)
+        .find_map(|(input, _)| elf_header_info(input.data))
+        .unwrap_or((args.emulation.unwrap_or_default().elf_machine(), 0));
+    let mut claimed_files = claimed_file_indexes.iter();
+    let placeholders = inputs
+        .iter()
+        .zip(claimed)
+        .filter(|(_, claimed)| **claimed)
+        .map(|(input, _)| {
+            let (e_machine, e_flags) = elf_header_info(input.data).unwrap_or(default_header);
+            let file = &state.files[*claimed_files.next().unwrap()];
+            placeholder_object(&file.symbols, e_machine, e_flags)
+        })
+        .collect::<Vec<Vec<u8>>>();
+    let mut placeholders_iter = placeholders.iter();
+    let inputs = inputs
+        .iter()
+        .zip(claimed)
+        .map(|(input, claimed)| InputBytes {
+            input: input.input,
+            kind: if *claimed {
+                FileKind::ElfObject
+            } else {
+                input.kind
+            },
+            data: if *claimed {
+                placeholders_iter.next().unwrap()
+            } else {
+                input.data
+            },
+            modifiers: input.modifiers,
+        })
+        .collect::<Vec<InputBytes>>();
+    let files = crate::parsing::parse_input_files(&inputs, args, None)?;
+    let mut symbol_db = SymbolDb::build(&files, args)?;
+    // Anything that resolution would warn about will come up again when we link what the plugin
+    // produces, so we don't report it now.
+    let resolved = crate::resolution::resolve_symbol_definitions(
+        &files,
+        &mut symbol_db,
+        &Diagnostics::default(),
+    )?;
+
+    let is_loaded =
+        |file_id: FileId| !matches!(resolved[file_id.as_usize()], ResolvedFile::NotLoaded);
+    let definer = |symbol_id: SymbolId| -> Result<(SymbolId, Definer)> {
+        let definition = symbol_db.definition(symbol_id);
+        let file_id = symbol_db.file_id_for_symbol(definition);
+        let definer = match &files[file_id.as_usize()] {
+            InputObject::Object(obj) => {
+                if !is_loaded(file_id) || obj.symbol(definition)?.is_undefined() {
+                    Definer::Undefined
+                } else if claimed[file_id.as_usize()] {
+                    Definer::Ir
+                } else if obj.is_dynamic {
+                    Definer::SharedObject
+                } else {
+                    Definer::Regular
+                }
+            }
+            _ if definition.is_undefined() => Definer::Undefined,
+            _ => Definer::Regular,
+        };
+        Ok((definition, definer))
+    };
+
+    // Definitions that are referenced by something other than IR need to be kept even if the
+    // plugin can see that none of its IR uses them.
+    let entry = args
+        .linker_script
+        .as_ref()
+        .and_then(|script| script.entry.as_deref())
+        .unwrap_or("_start");
+    let mut roots = args
+        .undefined
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if args.output_kind.is_executable() {
+        roots.push(entry);
+    }
+    let mut referenced_outside_ir = roots
+        .iter()
+        .filter_map(|name| {
+            symbol_db
+                .global_names
+                .get(&SymbolName::prehashed(name.as_bytes()))
+        })
+        .map(|&symbol_id| symbol_db.definition(symbol_id))
+        .collect::<AHashSet<SymbolId>>();
+    for file in &files {
+        let InputObject::Object(obj) = file else {
+            continue;
+        };
+        if claimed[obj.file_id.as_usize()] || !is_loaded(obj.file_id) {
+            continue;
+        }
+        for offset in 0..obj.num_symbols {
+            let symbol_id = obj.start_symbol_id.add_usize(offset);
+            let definition = symbol_db.definition(symbol_id);
+            if definition != symbol_id {
+                referenced_outside_ir.insert(definition);
+            }
+        }
+    }
+
+    let exports_symbols =
+        args.output_kind == OutputKind::SharedObject || args.export_all_dynamic_symbols;
+    let claimed_inputs = claimed
+        .iter()
+        .enumerate()
+        .filter(|(_, claimed)| **claimed)
+        .map(|(input_index, _)| input_index);
+    for (input_index, &file_index) in claimed_inputs.zip(claimed_file_indexes) {
+        let InputObject::Object(obj) = &files[input_index] else {
+            continue;
+        };
+        if !is_loaded(obj.file_id) {
+            continue;
+        }
+        let file = &state.files[file_index];
+        let resolutions = file
+            .symbols
+            .iter()
+            .enumerate()
+            .map(|(index, symbol)| {
+                // Placeholders start with the null symbol, like any other symbol table.
+                let symbol_id = obj.start_symbol_id.add_usize(index + 1);
+                let (definition, definer) = definer(symbol_id)?;
+                Ok(if matches!(symbol.def, LDPK_UNDEF | LDPK_WEAKUNDEF) {
+                    match definer {
+                        Definer::Undefined => LDPR_UNDEF,
+                        Definer::Ir => LDPR_RESOLVED_IR,
+                        Definer::SharedObject => LDPR_RESOLVED_DYN,
+                        Definer::Regular => LDPR_RESOLVED_EXEC,
+                    }
+                } else if definition != symbol_id {
+                    if definer == Definer::Ir {
+                        LDPR_PREEMPTED_IR
+                    } else {
+                        LDPR_PREEMPTED_REG
+                    }
+                } else if args.output_kind == OutputKind::Relocatable
+                    || referenced_outside_ir.contains(&symbol_id)
+                {
+                    LDPR_PREVAILING_DEF
+                } else if exports_symbols
+                    && matches!(symbol.visibility, LDPV_DEFAULT | LDPV_PROTECTED)
+                {
+                    LDPR_PREVAILING_DEF_IRONLY_EXP
+                } else {
+                    LDPR_PREVAILING_DEF_IRONLY
+                })
+            })
+            .collect::<Result<Vec<c_int>>>()?;
+        state.files[file_index].resolutions = Some(resolutions);
+    }
+    Ok(())
+}
+
+/// Returns the `e_machine` and `e_flags` of `data` if it's an ELF object.
+fn elf_header_info(data: &[u8]) -> Option<(u16, u32)> {
+    let header = crate::elf::File::parse(data).ok()?.raw_header();
+    Some((
+        header.e_machine.get(LittleEndian),
+        header.e_flags.get(LittleEndian),
+    ))
+}
+
+/// Returns an ELF object that defines and references `symbols`, to stand in for a file that the
+/// plugin claimed while we resolve symbols. Definitions go in an empty section, since nothing looks
+/// at their values.
+fn placeholder_object(symbols: &[IrSymbol], e_machine: u16, e_flags: u32) -> Vec<u8> {
+    const TEXT_INDEX: u16 = 1;
+    const STRTAB_INDEX: u32 = 3;
+    const SHSTRTAB_INDEX: u16 = 4;
+    let e = LittleEndian;
+
+    let mut strtab = vec![0];
+    let mut symtab = vec![SymtabEntry::default()];
+    for symbol in symbols {
+        let name = strtab.len() as u32;
+        strtab.extend_from_slice(&symbol.name);
+        strtab.push(0);
+        let binding = match symbol.def {
+            LDPK_WEAKDEF | LDPK_WEAKUNDEF => elf::STB_WEAK,
+            // Each file that uses a COMDAT group defines its symbols, but only one copy is kept.
+            LDPK_DEF if symbol.is_comdat => elf::STB_WEAK,
+            _ => elf::STB_GLOBAL,
+        };
+        let (kind, section_index, value, size) = match symbol.def {
+            LDPK_DEF | LDPK_WEAKDEF => (elf::STT_NOTYPE, TEXT_INDEX, 0, 0),
+            LDPK_COMMON => (elf::STT_OBJECT, elf::SHN_COMMON, 1, symbol.size),
+            _ => (elf::STT_NOTYPE, elf::SHN_UNDEF, 0, 0),
+        };
+        let visibility = match symbol.visibility {
+            LDPV_PROTECTED => elf::STV_PROTECTED,
+            LDPV_INTERNAL => elf::STV_INTERNAL,
+            LDPV_HIDDEN => elf::STV_HIDDEN,
+            _ => elf::STV_DEFAULT,
+        };
+        symtab.push(SymtabEntry {
+            st_name: U32::new(e, name),
+            st_info: (binding << 4) | kind,
+            st_other: visibility,
+            st_shndx: U16::new(e, section_index),
+            st_value: U64::new(e, value),
+            st_size: U64::new(e, size),
+        });
+    }
+    let shstrtab = b"\0.text\0.symtab\0.strtab\0.shstrtab\0";
+    let name_offset = |name: &[u8]| {
+        shstrtab
+            .windows(name.len())
+            .position(|window| window == name)
+            .unwrap() as u32
+    };
+
+    let header_size = size_of::<FileHeader>();
+    let symtab_offset = header_size;
+    let symtab_size = symtab.len() * size_of::<SymtabEntry>();
+    let strtab_offset = symtab_offset + symtab_size;
+    let shstrtab_offset = strtab_offset + strtab.len();
+    let section_headers_offset = (shstrtab_offset + shstrtab.len()).next_multiple_of(8);
+    let section_header =
+        |name: &[u8], sh_type, flags, offset: usize, size: usize, link, info| SectionHeader {
+            sh_name: U32::new(e, name_offset(name)),
+            sh_type: U32::new(e, sh_type),
+            sh_flags: U64::new(e, flags),
+            sh_addr: U64::new(e, 0),
+            sh_offset: U64::new(e, offset as u64),
+            sh_size: U64::new(e, size as u64),
+            sh_link: U32::new(e, link),
+            sh_info: U32::new(e, info),
+            sh_addralign: U64::new(e, if sh_type == elf::SHT_SYMTAB { 8 } else { 1 }),
+            sh_entsize: U64::new(
+                e,
+                if sh_type == elf::SHT_SYMTAB {
+                    size_of::<SymtabEntry>() as u64
+                } else {
+                    0
+                },
+            ),
+        };
+    let section_headers = [
+        section_header(b"\0", elf::SHT_NULL, 0, 0, 0, 0, 0),
+        section_header(
+            b".text\0",
+            elf::SHT_PROGBITS,
+            u64::from(elf::SHF_ALLOC | elf::SHF_EXECINSTR),
+            header_size,
+            0,
+            0,
+            0,
+        ),
+        // All our symbols are global, so the first non-local symbol is the one after the null
+        // symbol.
+        section_header(
+            b".symtab\0",
+            elf::SHT_SYMTAB,
+            0,
+            symtab_offset,
+            symtab_size,
+            STRTAB_INDEX,
+            1,
+        ),
+        section_header(
+            b".strtab\0",
+            elf::SHT_STRTAB,
+            0,
+            strtab_offset,
+            strtab.len(),
+            0,
+            0,
+        ),
+        section_header(
+            b".shstrtab\0",
+            elf::SHT_STRTAB,
+            0,
+            shstrtab_offset,
+            shstrtab.len(),
+            0,
+            0,
+        ),
+    ];
+    let header = FileHeader {
+        e_ident: elf::Ident {
+            magic: elf::ELFMAG,
+            class: elf::ELFCLASS64,
+            data: elf::ELFDATA2LSB,
+            version: elf::EV_CURRENT,
+            os_abi: elf::ELFOSABI_NONE,
+            abi_version: 0,
+            padding: [0; 7],
+        },
+        e_type: U16::new(e, elf::ET_REL),
+        e_machine: U16::new(e, e_machine),
+        e_version: U32::new(e, elf::EV_CURRENT.into()),
+        e_entry: U64::new(e, 0),
+        e_phoff: U64::new(e, 0),
+        e_shoff: U64::new(e, section_headers_offset as u64),
+        e_flags: U32::new(e, e_flags),
+        e_ehsize: U16::new(e, header_size as u16),
+        e_phentsize: U16::new(e, 0),
+        e_phnum: U16::new(e, 0),
+        e_shentsize: U16::new(e, size_of::<SectionHeader>() as u16),
+        e_shnum: U16::new(e, section_headers.len() as u16),
+        e_shstrndx: U16::new(e, SHSTRTAB_INDEX),
+    };
+
+    let mut out = object::bytes_of(&header).to_vec();
+    out.extend_from_slice(object::bytes_of_slice(&symtab));
+    out.extend_from_slice(&strtab);
+    out.extend_from_slice(shstrtab);
+    out.resize(section_headers_offset, 0);
+    out.extend_from_slice(object::bytes_of_slice(&section_headers));
+    out
+}
+
+/// Runs `f` on the state of the link that's calling into its plugin. Returns `LDPS_ERR` if no link
+/// is, which can only happen if a plugin calls us when we haven't called it.
+fn with_state(f: impl FnOnce(&mut PluginState) -> Status) -> Status {
+    let state = ACTIVE_STATE.load(Ordering::Acquire);
+    if state.is_null() {
+        return LDPS_ERR;
+    }
+    // Safety: `ACTIVE_STATE` is only non-null while `LinkerPlugin::call` is running, during which
+    // the state that it points to is alive.
+    let state = unsafe { &*state };
+    f(&mut state.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// Reports any warnings that the plugin sent us, then returns an error if `status` indicates
+/// failure or the plugin sent us any errors.
+fn check_status(plugin: &LinkerPlugin, status: Status, diagnostics: &Diagnostics) -> Result {
+    let messages = std::mem::take(&mut plugin.state().messages);
+    let mut errors = Vec::new();
+    for (level, message) in messages {
+        match level {
+            LDPL_WARNING => diagnostics.warn(message),
+            level if level >= LDPL_ERROR => errors.push(message),
+            _ => {}
+        }
+    }
+    if !errors.is_empty() {
+        return Err(anyhow!(errors.join("\n")));
+    }
+    if status != LDPS_OK {
+        bail!("Linker plugin returned status {status}");
+    }
+    Ok(())
+}
+
+impl PluginState {
+    fn file_mut(&mut self, handle: *const c_void) -> Option<&mut OfferedFile> {
+        (handle as usize)
+            .checked_sub(1)
+            .and_then(|index| self.files.get_mut(index))
+    }
+}
+
+unsafe extern "C" fn register_claim_file_hook(hook: ClaimFileHook) -> Status {
+    with_state(|state| {
+        state.claim_file_hook = Some(hook);
+        LDPS_OK
+    })
+}
+
+unsafe extern "C" fn register_all_symbols_read_hook(hook: AllSymbolsReadHook) -> Status {
+    with_state(|state| {
+        state.all_symbols_read_hook = Some(hook);
+        LDPS_OK
+    })
+}
+
+unsafe extern "C" fn register_cleanup_hook(hook: CleanupHook) -> Status {
+    with_state(|state| {
+        state.cleanup_hook = Some(hook);
+        LDPS_OK
+    })
+}
+
+unsafe extern "C" fn add_symbols(
+    handle: *mut c_void,
+    num_symbols: c_int,
+    symbols: *const PluginSymbol,
+) -> Status {
+    let symbols = std::slice::from_raw_parts(symbols, num_symbols.max(0) as usize)
+        .iter()
+        .map(|symbol| IrSymbol {
+            name: CStr::from_ptr(symbol.name).to_bytes().to_owned(),
+            def: symbol.def,
+            visibility: symbol.visibility,
+            size: symbol.size,
+            is_comdat: !symbol.comdat_key.is_null(),
+        })
+        .collect();
+    with_state(|state| match state.file_mut(handle) {
+        Some(file) => {
+            file.symbols = symbols;
+            LDPS_OK
+        }
+        None => LDPS_BAD_HANDLE,
+    })
+}
+
+unsafe extern "C" fn get_symbols_v1(
+    handle: *const c_void,
+    num_symbols: c_int,
+    symbols: *mut PluginSymbol,
+) -> Status {
+    get_symbols(handle, num_symbols, symbols, 1)
+}
+
+unsafe extern "C" fn get_symbols_v2(
+    handle: *const c_void,
+    num_symbols: c_int,
+    symbols: *mut PluginSymbol,
+) -> Status {
+    get_symbols(handle, num_symbols, symbols, 2)
+}
+
+unsafe extern "C" fn get_symbols_v3(
+    handle: *const c_void,
+    num_symbols: c_int,
+    symbols: *mut PluginSymbol,
+) -> Status {
+    get_symbols(handle, num_symbols, symbols, 3)
+}
+
+/// Fills in how each of the symbols of the file identified by `handle` got resolved. Later
+/// versions of this callback can report more kinds of resolution.
+unsafe fn get_symbols(
+    handle: *const c_void,
+    num_symbols: c_int,
+    symbols: *mut PluginSymbol,
+    version: u32,
+) -> Status {
+    let symbols = std::slice::from_raw_parts_mut(symbols, num_symbols.max(0) as usize);
+    with_state(|state| {
+        let Some(file) = state.file_mut(handle) else {
+            return LDPS_BAD_HANDLE;
+        };
+        let Some(resolutions) = &file.resolutions else {
+            // The file is an archive member that isn't being loaded. Version 3 lets us say so.
+            // Otherwise, say that its definitions were overridden, so that they get discarded.
+            if version >= 3 {
+                return LDPS_NO_SYMS;
+            }
+            for symbol in symbols {
+                symbol.resolution = if matches!(symbol.def, LDPK_UNDEF | LDPK_WEAKUNDEF) {
+                    LDPR_UNDEF
+                } else {
+                    LDPR_PREEMPTED_REG
+                };
+            }
+            return LDPS_OK;
+        };
+        if resolutions.len() != symbols.len() {
+            return LDPS_ERR;
+        }
+        for (symbol, &resolution) in symbols.iter_mut().zip(resolutions) {
+            symbol.resolution = if version == 1 && resolution == LDPR_PREVAILING_DEF_IRONLY_EXP {
+                LDPR_PREVAILING_DEF
+            } else {
+                resolution
+            };
+        }
+        LDPS_OK
+    })
+}
+
+unsafe extern "C" fn add_input_file(path: *const c_char) -> Status {
+    let path = PathBuf::from(OsStr::from_bytes(CStr::from_ptr(path).to_bytes()));
+    with_state(|state| {
+        state.added_files.push(path);
+        LDPS_OK
+    })
+}
+
+unsafe extern "C" fn add_input_library(name: *const c_char) -> Status {
+    let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+    with_state(|state| {
+        state.added_libraries.push(name);
+        LDPS_OK
+    })
+}
+
+unsafe extern "C" fn set_extra_library_path(path: *const c_char) -> Status {
+    let path = PathBuf::from(OsStr::from_bytes(CStr::from_ptr(path).to_bytes()));
+    with_state(|state| {
+        state.extra_library_path = Some(path);
+        LDPS_OK
+    })
+}
+
+unsafe extern "C" fn get_input_file(handle: *const c_void, out: *mut PluginInputFile) -> Status {
+    with_state(|state| {
+        let Some(file) = state.file_mut(handle) else {
+            return LDPS_BAD_HANDLE;
+        };
+        *out = PluginInputFile {
+            name: file.name.as_ptr(),
+            fd: file.fd,
+            offset: file.offset as libc::off_t,
+            filesize: file.data_len as libc::off_t,
+            handle: handle.cast_mut(),
+        };
+        LDPS_OK
+    })
+}
+
+unsafe extern "C" fn release_input_file(_handle: *const c_void) -> Status {
+    LDPS_OK
+}
+
+unsafe extern "C" fn get_view(handle: *const c_void, view: *mut *const c_void) -> Status {
+    with_state(|state| {
+        let Some(file) = state.file_mut(handle) else {
+            return LDPS_BAD_HANDLE;
+        };
+        *view = file.data_address as *const c_void;
+        LDPS_OK
+    })
+}
+
+/// Records a message from the plugin. The real signature is variadic, like printf, but we can't
+/// define variadic functions in stable Rust. Instead we declare the first few variadic arguments
+/// as regular ones. This works on the 64 bit ABIs that we support, since they pass integer and
+/// pointer arguments in the same registers whether or not a function is variadic.
+unsafe extern "C" fn message(
+    level: c_int,
+    format: *const c_char,
+    arg0: usize,
+    arg1: usize,
+    arg2: usize,
+    arg3: usize,
+) -> Status {
+    let message = format_message(CStr::from_ptr(format).to_bytes(), &[arg0, arg1, arg2, arg3]);
+    with_state(|state| {
+        state.messages.push((level, message));
+        LDPS_OK
+    })
+}
+
+/// Expands a printf-style `format` using `args`. Only conversions of integers and strings are
+/// supported, since those are all that we can receive.
+unsafe fn format_message(format: &[u8], args: &[usize]) -> String {
+    let mut out = Vec::new();
+    let mut args = args.iter().copied();
+    let mut bytes = format.iter().copied();
+    while let Some(byte) = bytes.next() {
+        if byte != b'%' {
+            out.push(byte);
+            continue;
+        }
+        let mut is_long = false;
+        let conversion = loop {
+            match bytes.next() {
+                Some(b'l' | b'z' | b'j' | b't') => is_long = true,
+                Some(b'-' | b'+' | b' ' | b'#' | b'.' | b'h' | b'0'..=b'9') => {}
+                other => break other,
+            }
+        };
+        let Some(conversion) = conversion else {
+            break;
+        };
+        if conversion == b'%' {
+            out.push(b'%');
+            continue;
+        }
+        let arg = args.next().unwrap_or(0);
+        match conversion {
+            b's' if arg == 0 => out.extend_from_slice(b"(null)"),
+            b's' => out.extend_from_slice(CStr::from_ptr(arg as *const c_char).to_bytes()),
+            b'd' | b'i' if is_long => out.extend_from_slice((arg as i64).to_string().as_bytes()),
+            b'd' | b'i' => out.extend_from_slice((arg as i32).to_string().as_bytes()),
+            b'u' if is_long => out.extend_from_slice(arg.to_string().as_bytes()),
+            b'u' => out.extend_from_slice((arg as u32).to_string().as_bytes()),
+            b'x' if is_long => out.extend_from_slice(format!("{arg:x}").as_bytes()),
+            b'x' => out.extend_from_slice(format!("{:x}", arg as u32).as_bytes()),
+            b'p' => out.extend_from_slice(format!("{arg:#x}").as_bytes()),
+            b'c' => out.push(arg as u8),
+            other => {
+                out.push(b'%');
+                out.push(other);
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_message() {
+        let name = c"foo.o";
+        let format =
+            |format: &CStr, args: &[usize]| unsafe { format_message(format.to_bytes(), args) };
+        assert_eq!(
+            format(c"%s: bad symbol %d", &[name.as_ptr() as usize, 7]),
+            "foo.o: bad symbol 7"
+        );
+        assert_eq!(format(c"%d%%", &[(-1i32) as u32 as usize]), "-1%");
+        assert_eq!(format(c"%ld %lx", &[usize::MAX, 255]), "-1 ff");
+        assert_eq!(format(c"%5u %s", &[3, 0]), "3 (null)");
+    }
+
+    #[test]
+    fn test_placeholder_object() {
+        let symbol = |name: &str, def, is_comdat| IrSymbol {
+            name: name.as_bytes().to_owned(),
+            def,
+            visibility: LDPV_DEFAULT,
+            size: 8,
+            is_comdat,
+        };
+        let data = placeholder_object(
+            &[
+                symbol("strong", LDPK_DEF, false),
+                symbol("weak", LDPK_WEAKDEF, false),
+                symbol("inline", LDPK_DEF, true),
+                symbol("common", LDPK_COMMON, false),
+                symbol("undefined", LDPK_UNDEF, false),
+            ],
+            elf::EM_X86_64,
+            0,
+        );
+        let object = crate::elf::File::parse(data.as_slice()).unwrap();
+        let symbols = object
+            .symbols()
+            .skip(1)
+            .map(|symbol| {
+                (
+                    symbol.name().unwrap(),
+                    symbol.is_undefined(),
+                    symbol.is_weak(),
+                    symbol.is_common(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            symbols,
+            [
+                ("strong", false, false, false),
+                ("weak", false, true, false),
+                ("inline", false, true, false),
+                ("common", false, false, true),
+                ("undefined", true, false, false),
+            ]
+        );
+    }
+}
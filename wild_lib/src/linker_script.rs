@@ -1,7 +1,8 @@
-//! This module is responsible for parsing very basic linker scripts. These are not the kind of
-//! linker script you might write to specify the layout of your program on an embedded platform, we
-//! don't currently support those. It's just for supporting small linker scripts that are put in
-//! place of .so files to tell the linker to load some other input file(s).
+//! This module is responsible for parsing very basic linker scripts. Mostly, that's small linker
+//! scripts that are put in place of .so files to tell the linker to load some other input file(s).
+//! We also support a small subset of the scripts that can be passed via `-T` to control layout -
+//! just enough to say which input sections go in which output sections, where some of those output
+//! sections start and what the entry point is.
 
 use crate::args::Input;
 use crate::args::InputSpec;
@@ -12,6 +13,12 @@ use anyhow::bail;
 use anyhow::Context;
 use std::path::Path;
 
+/// The BFD name of the only output format that we can produce.
+const OUTPUT_FORMAT: &str = "elf64-x86-64";
+
+/// The BFD name of the only architecture that we can produce output for.
+const OUTPUT_ARCH: &str = "i386:x86-64";
+
 /// Parse the kind of linker script that's put in place of a shared object to specify that the
 /// linker should load several files.
 pub(crate) fn linker_script_to_inputs(
@@ -33,37 +40,419 @@ pub(crate) fn linker_script_to_inputs(
         .collect())
 }
 
+/// A linker script passed via `-T`. We support `ENTRY` and a `SECTIONS` command containing
+/// assignments of absolute addresses to the location counter and output section descriptions. Input
+/// section descriptions can only match on section names, not on filenames.
+#[derive(Debug)]
+pub(crate) struct LinkerScript {
+    /// The name of the symbol that should be our entry point, if set via `ENTRY`.
+    pub(crate) entry: Option<String>,
+    pub(crate) sections: Vec<OutputSectionDescription>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct OutputSectionDescription {
+    pub(crate) name: String,
+    /// The address at which the section should start, if set either by assigning to `.` before
+    /// the section or by an address in the section's description.
+    pub(crate) address: Option<u64>,
+    /// Patterns for the names of the input sections that go in this output section.
+    patterns: Vec<String>,
+}
+
+impl LinkerScript {
+    pub(crate) fn parse(script: &str) -> Result<LinkerScript> {
+        let mut tokens = Tokeniser::new(script);
+        let mut entry = None;
+        let mut sections = Vec::new();
+        while let Some(token) = tokens.next() {
+            match token {
+                "ENTRY" => {
+                    tokens.expect("(")?;
+                    let name = tokens
+                        .next()
+                        .ok_or_else(|| anyhow!("Missing symbol name in ENTRY"))?;
+                    tokens.expect(")")?;
+                    entry = Some(name.to_owned());
+                }
+                "SECTIONS" => {
+                    tokens.expect("{")?;
+                    parse_sections(&mut tokens, &mut sections)?;
+                }
+                "OUTPUT_FORMAT" => check_output_format(&parse_directive_args(&mut tokens)?)?,
+                "OUTPUT_ARCH" => check_output_arch(&parse_directive_args(&mut tokens)?)?,
+                ";" => {}
+                other => bail!("Unsupported linker script command `{other}`"),
+            }
+        }
+        Ok(LinkerScript { entry, sections })
+    }
+
+    /// Returns the description of the output section into which we should put input sections
+    /// with the supplied name, or None if the script doesn't say.
+    pub(crate) fn output_section_for(
+        &self,
+        section_name: &[u8],
+    ) -> Option<&OutputSectionDescription> {
+        self.sections.iter().find(|section| {
+            section
+                .patterns
+                .iter()
+                .any(|pattern| glob_matches(pattern.as_bytes(), section_name))
+        })
+    }
+}
+
+/// Parses the contents of a `SECTIONS` command up to and including the closing brace.
+fn parse_sections(tokens: &mut Tokeniser, sections: &mut Vec<OutputSectionDescription>) -> Result {
+    let mut location = None;
+    loop {
+        match tokens.next() {
+            None => bail!("Missing close '}}' in SECTIONS"),
+            Some("}") => return Ok(()),
+            Some(";") => {}
+            Some(".") => {
+                tokens.expect("=")?;
+                location = Some(parse_address(tokens)?);
+                tokens.expect(";")?;
+            }
+            Some(name) => {
+                // NAME [ADDRESS] : { INPUT-SECTION-DESCRIPTIONS }
+                let mut address = location.take();
+                let name = match name.strip_suffix(':') {
+                    Some(name) => name,
+                    None => {
+                        if tokens.peek() != Some(":") {
+                            address = Some(parse_address(tokens)?);
+                        }
+                        tokens.expect(":")?;
+                        name
+                    }
+                };
+                tokens.expect("{")?;
+                let patterns = parse_input_sections(tokens, name)?;
+                sections.push(OutputSectionDescription {
+                    name: name.to_owned(),
+                    address,
+                    patterns,
+                });
+            }
+        }
+    }
+}
+
+/// Parses the input section descriptions of an output section up to and including the closing
+/// brace, returning the section name patterns. e.g. `*(.text .text.*) }`.
+fn parse_input_sections(tokens: &mut Tokeniser, output_name: &str) -> Result<Vec<String>> {
+    let mut patterns = Vec::new();
+    loop {
+        match tokens.next() {
+            None => bail!("Missing close '}}' for output section `{output_name}`"),
+            Some("}") => return Ok(patterns),
+            Some("*") => {
+                tokens.expect("(")?;
+                loop {
+                    match tokens.next() {
+                        None => bail!("Missing close ')' for output section `{output_name}`"),
+                        Some(")") => break,
+                        Some(",") => {}
+                        Some(pattern) => patterns.push(pattern.to_owned()),
+                    }
+                }
+            }
+            Some(other) => bail!("Unsupported input section description `{other}`"),
+        }
+    }
+}
+
+fn parse_address(tokens: &mut Tokeniser) -> Result<u64> {
+    let token = tokens
+        .next()
+        .ok_or_else(|| anyhow!("Expected an address, got end of input"))?;
+    crate::args::parse_number(token)
+        .ok_or_else(|| anyhow!("Unsupported address expression `{token}`"))
+}
+
+/// Returns whether `name` matches `pattern`, which may contain `*` to match any sequence of bytes,
+/// `?` to match any single byte and `[...]` to match any single byte in a set such as `[a-cx]`. A
+/// set starting with `!` or `^` matches any byte not in the set.
+pub(crate) fn glob_matches(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|i| glob_matches(rest, &name[i..])),
+        Some((b'?', rest)) => !name.is_empty() && glob_matches(rest, &name[1..]),
+        Some((b'[', rest)) => {
+            let Some((set, rest)) = split_byte_set(rest) else {
+                // An unterminated set is just a literal `[`.
+                return name.first() == Some(&b'[') && glob_matches(rest, &name[1..]);
+            };
+            name.first().is_some_and(|ch| byte_set_contains(set, *ch))
+                && glob_matches(rest, &name[1..])
+        }
+        Some((ch, rest)) => name.first() == Some(ch) && glob_matches(rest, &name[1..]),
+    }
+}
+
+/// Splits the contents of a `[...]` set, which starts after the open bracket, from the rest of the
+/// pattern. A `]` straight after the open bracket (or after negation) is part of the set.
+fn split_byte_set(pattern: &[u8]) -> Option<(&[u8], &[u8])> {
+    let start = usize::from(matches!(pattern.first(), Some(b'!' | b'^')));
+    let end = start
+        + 1
+        + pattern
+            .get(start + 1..)?
+            .iter()
+            .position(|ch| *ch == b']')?;
+    Some((&pattern[..end], &pattern[end + 1..]))
+}
+
+fn byte_set_contains(set: &[u8], ch: u8) -> bool {
+    let (negated, mut set) = match set.split_first() {
+        Some((b'!' | b'^', rest)) => (true, rest),
+        _ => (false, set),
+    };
+    let mut found = false;
+    while let Some((&first, rest)) = set.split_first() {
+        if let [b'-', last, rest @ ..] = rest {
+            found |= (first..=*last).contains(&ch);
+            set = rest;
+        } else {
+            found |= first == ch;
+            set = rest;
+        }
+    }
+    found != negated
+}
+
 /// A version script. See https://sourceware.org/binutils/docs/ld/VERSION.html
 pub(crate) struct VersionScript {
-    // For now, we only support a single version.
-    version: Version,
+    /// Either a single anonymous version, which just says what should be global and what should be
+    /// local, or one or more named versions, which also define symbol versions.
+    versions: Vec<Version>,
+
+    /// Whether any version has patterns from an `extern "C++"` block, in which case we need to
+    /// demangle symbol names in order to match them.
+    has_cxx_patterns: bool,
 }
 
 pub(crate) struct Version {
-    globals: Vec<SymbolMatcher>,
-    locals: Vec<SymbolMatcher>,
+    /// None for an anonymous version.
+    name: Option<String>,
+    /// The names of the versions that this version inherits from.
+    parents: Vec<String>,
+    globals: SymbolMatchers,
+    locals: SymbolMatchers,
+}
+
+/// A list of symbol name patterns. Patterns from `extern "C++"` blocks are matched against the
+/// demangled name, other patterns against the raw name.
+#[derive(Default, Debug, PartialEq, Eq)]
+struct SymbolMatchers {
+    c: Vec<SymbolMatcher>,
+    cxx: Vec<SymbolMatcher>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub(crate) enum SymbolMatcher {
     All,
-    Prefix(String),
+    Glob(String),
     Exact(String),
 }
 
+/// What a version script says about a symbol.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum VersionMatch {
+    /// The symbol is global and belongs to the version at this index.
+    Global(usize),
+    Local,
+}
+
 impl VersionScript {
     pub(crate) fn parse(script: &str) -> Result<VersionScript> {
         let mut tokens = Tokeniser::new(script);
-        // For now, we only support anonymous versions - i.e. a single version that just says what
-        // should be global and what should be local.
-        tokens.expect("{")?;
-        let version = Version::parse(&mut tokens)?;
-        Ok(VersionScript { version })
+        let mut versions = Vec::new();
+        while let Some(token) = tokens.next() {
+            let name = match token {
+                "{" => None,
+                ";" => continue,
+                name => {
+                    tokens.expect("{")?;
+                    Some(name.to_owned())
+                }
+            };
+            let mut version = Version::parse(&mut tokens)?;
+            version.name = name;
+            // Any names between the closing brace and the semicolon are versions that this one
+            // inherits from.
+            loop {
+                match tokens.next() {
+                    None | Some(";") => break,
+                    Some(parent) => version.parents.push(parent.to_owned()),
+                }
+            }
+            versions.push(version);
+        }
+        if versions.is_empty() {
+            bail!("Version script doesn't define any versions");
+        }
+        if versions.len() > 1 && versions.iter().any(|v| v.name.is_none()) {
+            bail!("An anonymous version can't be combined with other versions");
+        }
+        for (index, version) in versions.iter().enumerate() {
+            if versions[..index].iter().any(|v| v.name == version.name) {
+                bail!(
+                    "Version `{}` is defined more than once",
+                    version.name.as_deref().unwrap_or_default()
+                );
+            }
+            for parent in &version.parents {
+                if !versions
+                    .iter()
+                    .any(|v| v.name.as_deref() == Some(parent.as_str()))
+                {
+                    bail!("Version dependency `{parent}` isn't defined");
+                }
+            }
+        }
+        let has_cxx_patterns = versions
+            .iter()
+            .any(|v| !v.globals.cxx.is_empty() || !v.locals.cxx.is_empty());
+        Ok(VersionScript {
+            versions,
+            has_cxx_patterns,
+        })
     }
 
+    /// Returns whether the script makes the symbol `name` local.
     pub(crate) fn is_local(&self, name: &[u8]) -> bool {
-        self.version.is_local(name)
+        self.find(name) == Some(VersionMatch::Local)
+    }
+
+    /// Returns the name of each version that the script defines together with the names of the
+    /// versions that it inherits from, in the order in which they'll be numbered in the output.
+    /// Empty if the script only has an anonymous version.
+    pub(crate) fn version_definitions(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.versions
+            .iter()
+            .filter_map(|v| Some((v.name.as_deref()?, v.parents.as_slice())))
+    }
+
+    /// Returns the position in `version_definitions` of the version to which the symbol `name`
+    /// belongs, if any.
+    pub(crate) fn version_of(&self, name: &[u8]) -> Option<usize> {
+        match self.find(name)? {
+            VersionMatch::Global(index) if self.versions[index].name.is_some() => Some(index),
+            _ => None,
+        }
+    }
+
+    /// Finds the pattern that best matches `name`. As with GNU ld, an exact name takes precedence
+    /// over a wildcard pattern, which in turn takes precedence over `*`, regardless of which
+    /// version they're in. Between equally good matches, global beats local, then earlier versions
+    /// beat later ones.
+    fn find(&self, name: &[u8]) -> Option<VersionMatch> {
+        let demangled = if self.has_cxx_patterns {
+            demangle(name)
+        } else {
+            None
+        };
+        let mut best = None;
+        for (index, version) in self.versions.iter().enumerate() {
+            let candidates = [
+                (&version.globals, VersionMatch::Global(index)),
+                (&version.locals, VersionMatch::Local),
+            ];
+            for (matchers, outcome) in candidates {
+                let Some(precedence) = matchers.best_match(name, demangled.as_deref()) else {
+                    continue;
+                };
+                let is_local = outcome == VersionMatch::Local;
+                if best.is_none_or(|(best_key, _)| (precedence, is_local) < best_key) {
+                    best = Some(((precedence, is_local), outcome));
+                }
+            }
+        }
+        best.map(|(_, outcome)| outcome)
+    }
+}
+
+/// Returns the demangled form of `name` if it's a mangled C++ name.
+fn demangle(name: &[u8]) -> Option<String> {
+    cpp_demangle::Symbol::new(name)
+        .ok()?
+        .demangle(&cpp_demangle::DemangleOptions::default())
+        .ok()
+}
+
+/// A list of symbols that should be exported as dynamic symbols, as supplied via `--dynamic-list`.
+/// See https://sourceware.org/binutils/docs/ld/Options.html
+pub(crate) struct DynamicList {
+    symbols: SymbolMatchers,
+}
+
+impl DynamicList {
+    pub(crate) fn parse(script: &str) -> Result<DynamicList> {
+        let mut tokens = Tokeniser::new(script);
+        let mut symbols = SymbolMatchers::default();
+        while let Some(token) = tokens.next() {
+            if token != "{" {
+                bail!("Expected token '{{', got '{token}'");
+            }
+            loop {
+                match tokens.next() {
+                    Some("}") => break,
+                    Some("extern") => parse_extern_block(&mut tokens, &mut symbols)?,
+                    Some(pattern) => {
+                        tokens.expect(";")?;
+                        symbols.c.push(SymbolMatcher::from_pattern(pattern)?);
+                    }
+                    None => bail!("Missing close '}}' in dynamic list"),
+                }
+            }
+            // The semicolon after the closing brace is optional.
+            if tokens.peek() == Some(";") {
+                tokens.next();
+            }
+        }
+        Ok(DynamicList { symbols })
+    }
+
+    pub(crate) fn contains(&self, name: &[u8]) -> bool {
+        let demangled = if self.symbols.cxx.is_empty() {
+            None
+        } else {
+            demangle(name)
+        };
+        self.symbols
+            .best_match(name, demangled.as_deref())
+            .is_some()
+    }
+}
+
+/// Parses a language-specific block such as `extern "C++" { ns::*; "f(int)"; }`, following the
+/// `extern` keyword, adding its patterns to `matchers`.
+fn parse_extern_block(tokens: &mut Tokeniser, matchers: &mut SymbolMatchers) -> Result {
+    let language = tokens
+        .next()
+        .ok_or_else(|| anyhow!("Expected a language after `extern`"))?;
+    let patterns = match language {
+        "\"C++\"" => &mut matchers.cxx,
+        "\"C\"" => &mut matchers.c,
+        other => bail!("Unsupported language {other} in `extern` block"),
+    };
+    tokens.expect("{")?;
+    loop {
+        match tokens.next() {
+            None => bail!("Missing close '}}' in `extern` block"),
+            Some("}") => break,
+            Some(";") => {}
+            Some(pattern) => patterns.push(SymbolMatcher::from_pattern(pattern)?),
+        }
     }
+    if tokens.peek() == Some(";") {
+        tokens.next();
+    }
+    Ok(())
 }
 
 enum VersionRuleSection {
@@ -74,60 +463,77 @@ enum VersionRuleSection {
 impl Version {
     fn parse(tokens: &mut Tokeniser) -> Result<Version> {
         let mut version = Version {
+            name: None,
+            parents: Vec::new(),
             globals: Default::default(),
             locals: Default::default(),
         };
-        let mut section = None;
+        // Patterns before any `global:` or `local:` are global.
+        let mut section = VersionRuleSection::Global;
         while let Some(token) = tokens.next() {
+            let matchers = match section {
+                VersionRuleSection::Global => &mut version.globals,
+                VersionRuleSection::Local => &mut version.locals,
+            };
+            // Allow `global :` as well as `global:`.
+            let token = match token {
+                "global" | "local" if tokens.peek() == Some(":") => {
+                    tokens.next();
+                    if token == "global" {
+                        "global:"
+                    } else {
+                        "local:"
+                    }
+                }
+                _ => token,
+            };
             match token {
                 "}" => return Ok(version),
-                "global:" => section = Some(VersionRuleSection::Global),
-                "local:" => section = Some(VersionRuleSection::Local),
+                "global:" => section = VersionRuleSection::Global,
+                "local:" => section = VersionRuleSection::Local,
+                "extern" => parse_extern_block(tokens, matchers)?,
                 pattern => {
-                    tokens.expect(";")?;
-                    match section {
-                        Some(VersionRuleSection::Global) => {
-                            version.globals.push(SymbolMatcher::from_pattern(pattern)?)
-                        }
-                        Some(VersionRuleSection::Local) => {
-                            version.locals.push(SymbolMatcher::from_pattern(pattern)?)
-                        }
-                        None => bail!("Expected global/local, found '{token}'"),
+                    // The semicolon after the last pattern is optional.
+                    if tokens.peek() != Some("}") {
+                        tokens.expect(";")?;
                     }
+                    matchers.c.push(SymbolMatcher::from_pattern(pattern)?);
                 }
             }
         }
         bail!("Missing close '}}' in version script");
     }
+}
 
-    fn is_local(&self, name: &[u8]) -> bool {
-        for matcher in &self.globals {
-            if matcher.matches(name) {
-                return false;
-            }
-        }
-        for matcher in &self.locals {
-            if matcher.matches(name) {
-                return true;
-            }
-        }
-        false
+impl SymbolMatchers {
+    /// Returns the precedence of the best of our patterns that matches, if any. Lower values are
+    /// better. `demangled` is the demangled form of `name`, if it's a mangled C++ name.
+    fn best_match(&self, name: &[u8], demangled: Option<&str>) -> Option<u8> {
+        let c = self.c.iter().filter(|matcher| matcher.matches(name));
+        let cxx = demangled.into_iter().flat_map(|demangled| {
+            self.cxx
+                .iter()
+                .filter(move |matcher| matcher.matches(demangled.as_bytes()))
+        });
+        c.chain(cxx).map(SymbolMatcher::precedence).min()
     }
 }
 
 impl SymbolMatcher {
+    /// Creates a matcher from a token in a version script or dynamic list. A quoted token is always
+    /// matched exactly, even if it contains wildcard characters.
     fn from_pattern(token: &str) -> Result<SymbolMatcher> {
+        if let Some(quoted) = token.strip_prefix('"') {
+            let exact = quoted
+                .strip_suffix('"')
+                .ok_or_else(|| anyhow!("Unterminated string {token}"))?;
+            return Ok(SymbolMatcher::Exact(exact.to_owned()));
+        }
         if token == "*" {
             return Ok(SymbolMatcher::All);
         }
-        if let Some(prefix) = token.strip_suffix('*') {
-            if prefix.contains('*') {
-                bail!("Unsupported symbol pattern '{token}'");
-            }
-            return Ok(SymbolMatcher::Prefix(prefix.to_owned()));
-        }
-        if token.contains('*') {
-            bail!("Unsupported symbol pattern '{token}'");
+        if token.contains(['*', '?', '[']) {
+            return Ok(SymbolMatcher::Glob(token.to_owned()));
         }
         Ok(SymbolMatcher::Exact(token.to_owned()))
     }
@@ -135,10 +541,18 @@ impl SymbolMatcher {
     fn matches(&self, name: &[u8]) -> bool {
         match self {
             SymbolMatcher::All => true,
-            SymbolMatcher::Prefix(prefix) => name.starts_with(prefix.as_bytes()),
+            SymbolMatcher::Glob(pattern) => glob_matches(pattern.as_bytes(), name),
             SymbolMatcher::Exact(exact) => name == exact.as_bytes(),
         }
     }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            SymbolMatcher::Exact(_) => 0,
+            SymbolMatcher::Glob(_) => 1,
+            SymbolMatcher::All => 2,
+        }
+    }
 }
 
 struct Tokeniser<'a> {
@@ -155,13 +569,24 @@ impl<'a> Tokeniser<'a> {
                 }
                 continue;
             }
+            if self.text.starts_with('#') {
+                self.text = self.text.split_once('\n').map_or("", |(_, rest)| rest);
+                continue;
+            }
             if self.text.is_empty() {
                 return None;
             }
+            // A quoted string is a single token, including its quotes.
+            if let Some(quoted) = self.text.strip_prefix('"') {
+                let len = quoted.find('"').map_or(self.text.len(), |end| end + 2);
+                let token = &self.text[..len];
+                self.text = &self.text[len..];
+                return Some(token);
+            }
             let len = self
                 .text
                 .char_indices()
-                .find(|(_, ch)| " \n\t(){};".contains(*ch))
+                .find(|(_, ch)| " \n\t(){};=,".contains(*ch))
                 .map(|(offset, _)| offset)
                 .unwrap_or(self.text.len())
                 .max(1);
@@ -175,6 +600,10 @@ impl<'a> Tokeniser<'a> {
         Tokeniser { text }
     }
 
+    fn peek(&self) -> Option<&'a str> {
+        Tokeniser { text: self.text }.next()
+    }
+
     fn expect(&mut self, expected: &str) -> Result {
         let token = self
             .next()
@@ -202,6 +631,10 @@ fn parse_commands_up_to<'a>(
         if end == Some(token) {
             return Ok(out);
         }
+        if token == "," {
+            // Filenames may optionally be separated by commas.
+            continue;
+        }
         if token.chars().all(|ch| ch.is_ascii_uppercase() || ch == '_') {
             out.push(parse_command(tokens, token)?);
         } else {
@@ -217,13 +650,18 @@ fn parse_commands_up_to<'a>(
 
 fn parse_command<'a>(tokens: &mut Tokeniser<'a>, token: &str) -> Result<Command<'a>> {
     match token {
-        "GROUP" => {
+        // We don't care about the order in which archives are given, so a group is just a list of
+        // inputs.
+        "GROUP" | "INPUT" => {
             tokens.expect("(")?;
             Ok(Command::Group(parse_commands_up_to(tokens, Some(")"))?))
         }
         "OUTPUT_FORMAT" => {
-            tokens.expect("(")?;
-            parse_commands_up_to(tokens, Some(")"))?;
+            check_output_format(&parse_directive_args(tokens)?)?;
+            Ok(Command::Ignored)
+        }
+        "OUTPUT_ARCH" => {
+            check_output_arch(&parse_directive_args(tokens)?)?;
             Ok(Command::Ignored)
         }
         "AS_NEEDED" => {
@@ -234,6 +672,50 @@ fn parse_command<'a>(tokens: &mut Tokeniser<'a>, token: &str) -> Result<Command<
     }
 }
 
+/// Parses the parenthesised, optionally comma-separated and quoted arguments of a directive such as
+/// `OUTPUT_FORMAT("elf64-x86-64", "elf64-x86-64", "elf64-x86-64")`.
+fn parse_directive_args<'a>(tokens: &mut Tokeniser<'a>) -> Result<Vec<&'a str>> {
+    tokens.expect("(")?;
+    let mut args = Vec::new();
+    loop {
+        match tokens.next() {
+            None => bail!("Got end of script, expected ')'"),
+            Some(")") => return Ok(args),
+            Some(",") => {}
+            Some(arg) => args.push(
+                arg.strip_prefix('"')
+                    .and_then(|arg| arg.strip_suffix('"'))
+                    .unwrap_or(arg),
+            ),
+        }
+    }
+}
+
+/// Checks that the format named by `OUTPUT_FORMAT` is one that we can produce. When three formats
+/// are given, they're the default, big-endian and little-endian formats. We don't support selecting
+/// endianness, so only the default matters.
+fn check_output_format(args: &[&str]) -> Result {
+    match args {
+        [] => bail!("Missing format name in OUTPUT_FORMAT"),
+        [OUTPUT_FORMAT] | [OUTPUT_FORMAT, _, _] => Ok(()),
+        [format] | [format, _, _] => bail!(
+            "Unsupported output format `{format}` in OUTPUT_FORMAT. \
+             Only `{OUTPUT_FORMAT}` is supported"
+        ),
+        _ => bail!("OUTPUT_FORMAT takes either one or three format names"),
+    }
+}
+
+fn check_output_arch(args: &[&str]) -> Result {
+    match args {
+        [OUTPUT_ARCH] => Ok(()),
+        [arch] => bail!(
+            "Unsupported architecture `{arch}` in OUTPUT_ARCH. Only `{OUTPUT_ARCH}` is supported"
+        ),
+        _ => bail!("OUTPUT_ARCH takes a single architecture name"),
+    }
+}
+
 fn inputs_from_script(text: &str, starting_modifiers: Modifiers) -> Result<Vec<Input>> {
     let mut tokens = Tokeniser::new(text);
     let commands = parse_commands_up_to(&mut tokens, None)?;
@@ -247,7 +729,7 @@ fn collect_inputs(commands: &[Command], inputs: &mut Vec<Input>, modifiers: Modi
         match command {
             Command::Arg(arg) => {
                 let spec = if let Some(lib_name) = arg.strip_prefix("-l") {
-                    InputSpec::Lib(Box::from(lib_name))
+                    InputSpec::from_lib_arg(lib_name)
                 } else {
                     InputSpec::File(Box::from(Path::new(arg)))
                 };
@@ -310,6 +792,10 @@ mod tests {
             tokenise("GROUP ( AS_NEEDED ( /a/b/c ))"),
             vec!["GROUP", "(", "AS_NEEDED", "(", "/a/b/c", ")", ")"]
         );
+        assert_eq!(
+            tokenise("INPUT(a.o,b.o)"),
+            vec!["INPUT", "(", "a.o", ",", "b.o", ")"]
+        );
         assert_eq!(tokenise(""), Vec::<&str>::new());
     }
 
@@ -352,17 +838,223 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_input_command() {
+        let inputs = inputs_from_script(
+            "INPUT(a.o, -lfoo) GROUP(libbar.a AS_NEEDED(libbaz.so))",
+            Modifiers::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            inputs
+                .iter()
+                .map(|i| (&i.spec, i.modifiers.as_needed))
+                .collect::<Vec<_>>(),
+            vec![
+                (&InputSpec::File(Box::from(Path::new("a.o"))), false),
+                (&InputSpec::Lib(Box::from("foo")), false),
+                (&InputSpec::File(Box::from(Path::new("libbar.a"))), false),
+                (&InputSpec::File(Box::from(Path::new("libbaz.so"))), true),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_output_format() {
+        assert!(inputs_from_script(
+            r#"OUTPUT_FORMAT("elf64-x86-64") OUTPUT_ARCH(i386:x86-64) GROUP(a.o)"#,
+            Modifiers::default()
+        )
+        .is_ok());
+        assert!(LinkerScript::parse(
+            r#"OUTPUT_FORMAT("elf64-x86-64", "elf64-x86-64", "elf64-x86-64")
+            OUTPUT_ARCH(i386:x86-64)
+            ENTRY(_start)"#
+        )
+        .is_ok());
+        let error =
+            inputs_from_script(r#"OUTPUT_FORMAT("elf32-i386")"#, Modifiers::default()).unwrap_err();
+        assert!(error.to_string().contains("`elf32-i386`"), "{error}");
+        assert!(LinkerScript::parse("OUTPUT_FORMAT(elf32-i386)").is_err());
+        assert!(LinkerScript::parse("OUTPUT_ARCH(i386)").is_err());
+    }
+
     #[test]
     fn test_parse_version_script() {
         let script = VersionScript::parse("{global:\n foo; bar*; local: *; }").unwrap();
-        let version = script.version;
+        assert_eq!(script.version_definitions().count(), 0);
+        let version = &script.versions[0];
+        assert_eq!(
+            version.globals.c,
+            vec![
+                SymbolMatcher::Exact("foo".to_owned()),
+                SymbolMatcher::Glob("bar*".to_owned())
+            ]
+        );
+        assert_eq!(version.locals.c, vec![SymbolMatcher::All]);
+
+        let script =
+            VersionScript::parse("VERS_1.0 { global: foo; local: *; };\n VERS_2.0 { bar; baz*; };")
+                .unwrap();
+        assert_eq!(
+            script
+                .version_definitions()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>(),
+            vec!["VERS_1.0", "VERS_2.0"]
+        );
+        assert_eq!(script.version_of(b"foo"), Some(0));
+        assert_eq!(script.version_of(b"baz2"), Some(1));
+        assert_eq!(script.version_of(b"other"), None);
+        assert!(!script.is_local(b"bar"));
+        assert!(script.is_local(b"other"));
+
+        assert!(VersionScript::parse("{ foo; }; VERS_1.0 { bar; };").is_err());
+        assert!(VersionScript::parse("VERS_1.0 { foo; }; VERS_2.0 { bar; } VERS_3.0;").is_err());
+        assert!(VersionScript::parse("VERS_1.0 { foo; }; VERS_1.0 { bar; };").is_err());
+    }
+
+    #[test]
+    fn test_version_script_inheritance() {
+        let script = VersionScript::parse(
+            "VERS_1.0 { foo; local: *; };\n VERS_1.1 { bar; } VERS_1.0;\n VERS_2.0 { baz; } VERS_1.1 VERS_1.0;",
+        )
+        .unwrap();
+        assert_eq!(
+            script.version_definitions().collect::<Vec<_>>(),
+            vec![
+                ("VERS_1.0", &[][..]),
+                ("VERS_1.1", &["VERS_1.0".to_owned()][..]),
+                (
+                    "VERS_2.0",
+                    &["VERS_1.1".to_owned(), "VERS_1.0".to_owned()][..]
+                ),
+            ]
+        );
+        assert_eq!(script.version_of(b"bar"), Some(1));
+        assert_eq!(script.version_of(b"baz"), Some(2));
+    }
+
+    #[test]
+    fn test_version_script_precedence() {
+        // An exact name beats a glob, which beats `*`, regardless of the order of versions.
+        let script = VersionScript::parse(
+            r#"VERS_1.0 { global: foo_*; local: *; };
+            VERS_2.0 { global: foo_bar; local: foo_b*; };"#,
+        )
+        .unwrap();
+        assert_eq!(script.version_of(b"foo_bar"), Some(1));
+        assert_eq!(script.version_of(b"foo_other"), Some(0));
+        assert!(!script.is_local(b"foo_baz"));
+        assert_eq!(script.version_of(b"foo_baz"), Some(0));
+        assert!(script.is_local(b"other"));
+
+        // Between equally specific patterns, global beats local, but specificity comes first.
+        let script = VersionScript::parse("{ local: *; global: *; };").unwrap();
+        assert!(!script.is_local(b"foo"));
+        let script = VersionScript::parse("{ local: foo; global: f*; };").unwrap();
+        assert!(script.is_local(b"foo"));
+    }
+
+    #[test]
+    fn test_version_script_patterns() {
+        let script = VersionScript::parse(
+            r#"# A comment
+            VERS_1.0 {
+                global :
+                    [abc]?_x;
+                    "quoted*";
+                    extern "C++" {
+                        ns::*;
+                        "foo::bar()"
+                    };
+                local :
+                    *;
+            };"#,
+        )
+        .unwrap();
+        assert!(!script.is_local(b"a1_x"));
+        assert!(!script.is_local(b"c1_x"));
+        assert!(script.is_local(b"d1_x"));
+        assert!(!script.is_local(b"quoted*"));
+        assert!(script.is_local(b"quoted_name"));
+        assert_eq!(script.version_of(b"_ZN3foo3barEv"), Some(0));
+        assert_eq!(script.version_of(b"_ZN2ns4funcEi"), Some(0));
+        assert!(script.is_local(b"_ZN3foo3bazEv"));
+        // C++ patterns only match mangled names.
+        assert!(script.is_local(b"ns::func"));
+
+        assert!(VersionScript::parse(r#"{ extern "Java" { foo; }; };"#).is_err());
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches(b"foo_*", b"foo_"));
+        assert!(glob_matches(b"foo_*", b"foo_bar"));
+        assert!(!glob_matches(b"foo_*", b"foo"));
+        assert!(glob_matches(b"[abc]?", b"bz"));
+        assert!(!glob_matches(b"[abc]?", b"dz"));
+        assert!(glob_matches(b"x[a-c0-9]", b"x5"));
+        assert!(!glob_matches(b"x[a-c0-9]", b"xd"));
+        assert!(glob_matches(b"x[!a-c]", b"xd"));
+        assert!(!glob_matches(b"x[^a-c]", b"xb"));
+        assert!(glob_matches(b"x[]]", b"x]"));
+        assert!(glob_matches(b"x[", b"x["));
+    }
+
+    #[test]
+    fn test_parse_linker_script() {
+        let script = LinkerScript::parse(
+            r#"ENTRY(my_start)
+            SECTIONS {
+                . = 0x600000;
+                .text : { *(.text .text.*) }
+                .data 0x800000 : { *(.data*) }
+                .custom: { *(.foo?) }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(script.entry.as_deref(), Some("my_start"));
+        assert_eq!(
+            script
+                .sections
+                .iter()
+                .map(|s| (s.name.as_str(), s.address))
+                .collect::<Vec<_>>(),
+            vec![
+                (".text", Some(0x600000)),
+                (".data", Some(0x800000)),
+                (".custom", None)
+            ]
+        );
+        let output_name = |name: &[u8]| script.output_section_for(name).map(|s| s.name.as_str());
+        assert_eq!(output_name(b".text"), Some(".text"));
+        assert_eq!(output_name(b".text.main"), Some(".text"));
+        assert_eq!(output_name(b".textual"), None);
+        assert_eq!(output_name(b".data.rel.ro"), Some(".data"));
+        assert_eq!(output_name(b".foo1"), Some(".custom"));
+        assert_eq!(output_name(b".foo12"), None);
+        assert!(LinkerScript::parse("SECTIONS { . = ALIGN(16); }").is_err());
+        assert!(LinkerScript::parse("SECTIONS { .text : { foo.o(.text) } }").is_err());
+    }
+
+    #[test]
+    fn test_parse_dynamic_list() {
+        let list = DynamicList::parse("{\n foo;\n bar*;\n};\n/* comment */ { baz; }").unwrap();
         assert_eq!(
-            version.globals,
+            list.symbols.c,
             vec![
                 SymbolMatcher::Exact("foo".to_owned()),
-                SymbolMatcher::Prefix("bar".to_owned())
+                SymbolMatcher::Glob("bar*".to_owned()),
+                SymbolMatcher::Exact("baz".to_owned()),
             ]
         );
-        assert_eq!(version.locals, vec![SymbolMatcher::All]);
+        assert!(list.contains(b"foo"));
+        assert!(list.contains(b"bar2"));
+        assert!(!list.contains(b"foo2"));
+        let list = DynamicList::parse(r#"{ extern "C++" { "ns::f(int)"; }; };"#).unwrap();
+        assert!(list.contains(b"_ZN2ns1fEi"));
+        assert!(!list.contains(b"_ZN2ns1gEi"));
+        assert!(DynamicList::parse("{ foo; ").is_err());
     }
 }
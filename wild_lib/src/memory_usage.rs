@@ -0,0 +1,72 @@
+//! Prints a breakdown of the size of the output file for `--print-memory-usage`. The intent is that
+//! the numbers can be tracked, e.g. in CI, in order to spot binary-size regressions, so they're
+//! computed from the same layout that we use when writing the output.
+
+use crate::elf;
+use crate::error::Result;
+use crate::layout::Layout;
+use crate::output_section_id;
+use crate::output_section_id::OutputSectionId;
+use std::io::Write;
+
+/// The sections that we report on, in the order that we report them.
+const SECTIONS: &[OutputSectionId] = &[
+    output_section_id::TEXT,
+    output_section_id::RODATA,
+    output_section_id::DATA,
+    output_section_id::BSS,
+    output_section_id::RELA_DYN,
+    output_section_id::GOT,
+    output_section_id::PLT,
+    output_section_id::SYMTAB,
+    output_section_id::STRTAB,
+    output_section_id::DYNSYM,
+    output_section_id::DYNSTR,
+];
+
+/// Column at which sizes start.
+const NAME_WIDTH: usize = 24;
+
+pub(crate) fn print(layout: &Layout) -> Result {
+    write_to(layout, &mut std::io::stdout().lock())
+}
+
+fn write_to(layout: &Layout, out: &mut impl Write) -> Result {
+    writeln!(out, "Memory usage:")?;
+    writeln!(
+        out,
+        "  {:NAME_WIDTH$}{}",
+        "File size",
+        crate::layout::compute_total_file_size(&layout.section_layouts)
+    )?;
+    for section_id in SECTIONS {
+        writeln!(
+            out,
+            "  {:NAME_WIDTH$}{}",
+            layout.output_sections.display_name(*section_id),
+            section_size(layout, *section_id)
+        )?;
+    }
+    let num_dynamic_relocations = (section_size(layout, output_section_id::RELA_DYN)
+        + section_size(layout, output_section_id::RELA_PLT))
+        / elf::RELA_ENTRY_SIZE;
+    writeln!(
+        out,
+        "  {:NAME_WIDTH$}{num_dynamic_relocations}",
+        "Dynamic relocations"
+    )?;
+    Ok(())
+}
+
+/// Returns the size that we'll write into the section header for `section_id`, or 0 if the section
+/// won't be in the output.
+fn section_size(layout: &Layout, section_id: OutputSectionId) -> u64 {
+    if layout
+        .output_sections
+        .output_index_of_section(section_id)
+        .is_none()
+    {
+        return 0;
+    }
+    layout.section_layouts.get(section_id).mem_size
+}
@@ -0,0 +1,326 @@
+//! An optional on-disk cache (`--object-cache=<dir>`) of the symbol tables of our input objects.
+//! When the same inputs, e.g. the same set of rlibs, are linked repeatedly, this lets us skip
+//! walking and hashing the symbols of each object. Entries are keyed by the path, modification time
+//! and size of the file containing the object, together with the object's offset within that file.
+//! Cached symbol names are stored as offsets into the object's data, so we still read the names
+//! from the input file, we just don't need to find or hash them.
+
+use crate::error::Result;
+use crate::input_data::InputFile;
+use crate::parsing::InputObject;
+use crate::resolution::ValueKind;
+use ahash::AHashMap;
+use anyhow::bail;
+use anyhow::Context;
+use object::Object as _;
+use object::ObjectSymbol as _;
+use std::os::unix::ffi::OsStrExt as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+const INDEX_FILENAME: &str = "index";
+
+/// Identifies the format of the index file. Change this whenever the format changes.
+const MAGIC: &[u8; 8] = b"WILDOC01";
+
+pub(crate) struct ObjectCache {
+    index_path: PathBuf,
+    entries: AHashMap<CacheKey, CachedSymbols>,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct CacheKey {
+    path: PathBuf,
+    modified: SystemTime,
+    file_size: u64,
+    /// The offset of the object within the file. Non-zero for archive entries.
+    offset: u64,
+}
+
+/// The symbols of an object as loaded by the symbol DB.
+pub(crate) struct CachedSymbols {
+    pub(crate) num_symbols: usize,
+    /// The symbols that aren't undefined.
+    pub(crate) definitions: Vec<CachedSymbol>,
+}
+
+pub(crate) struct CachedSymbol {
+    pub(crate) index: u32,
+    pub(crate) value_kind: ValueKind,
+    pub(crate) is_local: bool,
+    /// The offset of the symbol's name within the object's data. Unused for local symbols.
+    pub(crate) name_offset: u32,
+    pub(crate) name_len: u32,
+    pub(crate) name_hash: u64,
+}
+
+impl ObjectCache {
+    /// Loads the cache in `dir`, creating the directory if it doesn't exist. A missing, corrupt or
+    /// out-of-date index is treated as empty, since it'll be replaced once we've linked.
+    pub(crate) fn load(dir: &Path) -> Result<ObjectCache> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create object cache `{}`", dir.display()))?;
+        let index_path = dir.join(INDEX_FILENAME);
+        let entries = std::fs::read(&index_path)
+            .ok()
+            .and_then(|bytes| read_index(&bytes).ok())
+            .unwrap_or_else(AHashMap::new);
+        Ok(ObjectCache {
+            index_path,
+            entries,
+        })
+    }
+
+    /// Returns the cached symbols for the object with contents `data` from `file`, if we have a
+    /// valid entry for it.
+    pub(crate) fn get(&self, file: &InputFile, data: &[u8]) -> Option<&CachedSymbols> {
+        let cached = self.entries.get(&cache_key(file, data)?)?;
+        let is_valid = cached.definitions.iter().all(|symbol| {
+            (symbol.index as usize) < cached.num_symbols
+                && symbol.name_offset as usize + symbol.name_len as usize <= data.len()
+        });
+        is_valid.then_some(cached)
+    }
+
+    /// Adds entries for any objects that we didn't find in the cache, then writes the updated index.
+    /// Does nothing if all our objects were already cached.
+    #[tracing::instrument(skip_all, name = "Update object cache")]
+    pub(crate) fn update(&self, objects: &[InputObject]) -> Result {
+        let mut new_entries = Vec::new();
+        for object in objects {
+            let InputObject::Object(object) = object else {
+                continue;
+            };
+            if object.cached_symbols.is_some() {
+                continue;
+            }
+            let data = object.object.data();
+            let Some(key) = cache_key(object.input.file, data) else {
+                continue;
+            };
+            new_entries.push((
+                key,
+                symbols_for_object(object.object.as_ref(), object.is_dynamic, data)?,
+            ));
+        }
+        if new_entries.is_empty() {
+            return Ok(());
+        }
+        // Entries for files that have since changed are dropped, otherwise the index would grow
+        // every time an input was rebuilt.
+        let changed_files = new_entries
+            .iter()
+            .map(|(key, _)| (&key.path, (key.modified, key.file_size)))
+            .collect::<AHashMap<_, _>>();
+        let entries = self
+            .entries
+            .iter()
+            .filter(|(key, _)| {
+                changed_files
+                    .get(&key.path)
+                    .is_none_or(|version| *version == (key.modified, key.file_size))
+            })
+            .chain(new_entries.iter().map(|(key, symbols)| (key, symbols)))
+            .collect::<Vec<_>>();
+        // Write to a temporary file then rename it into place so that concurrent links never see a
+        // partially written index.
+        let temp_path = self
+            .index_path
+            .with_extension(format!("tmp-{}", std::process::id()));
+        std::fs::write(&temp_path, write_index(&entries)?)
+            .with_context(|| format!("Failed to write `{}`", temp_path.display()))?;
+        std::fs::rename(&temp_path, &self.index_path)
+            .with_context(|| format!("Failed to write `{}`", self.index_path.display()))?;
+        Ok(())
+    }
+}
+
+/// Returns the key for an object with contents `data` from `file`. Returns None for objects that
+/// we can't cache, such as the members of thin archives, which are stored in separate files.
+fn cache_key(file: &InputFile, data: &[u8]) -> Option<CacheKey> {
+    let modified = file.modified?;
+    let file_data = file.data();
+    let offset = (data.as_ptr() as usize).checked_sub(file_data.as_ptr() as usize)?;
+    if offset + data.len() > file_data.len() {
+        return None;
+    }
+    Some(CacheKey {
+        path: file.filename.clone(),
+        modified,
+        file_size: file_data.len() as u64,
+        offset: offset as u64,
+    })
+}
+
+fn symbols_for_object(
+    object: &crate::elf::File,
+    is_dynamic: bool,
+    data: &[u8],
+) -> Result<CachedSymbols> {
+    let symbols = if is_dynamic {
+        object.dynamic_symbols()
+    } else {
+        object.symbols()
+    };
+    let mut num_symbols = 0;
+    let mut definitions = Vec::new();
+    for symbol in symbols {
+        num_symbols += 1;
+        if symbol.is_undefined() {
+            continue;
+        }
+        let value_kind = if is_dynamic {
+            ValueKind::Dynamic
+        } else if symbol.section() == object::SymbolSection::Absolute {
+            ValueKind::Absolute
+        } else {
+            ValueKind::Address
+        };
+        let mut cached = CachedSymbol {
+            index: symbol.index().0 as u32,
+            value_kind,
+            is_local: symbol.is_local(),
+            name_offset: 0,
+            name_len: 0,
+            name_hash: 0,
+        };
+        if !cached.is_local {
+            let name = symbol.name_bytes()?;
+            cached.name_offset = (name.as_ptr() as usize)
+                .checked_sub(data.as_ptr() as usize)
+                .filter(|offset| offset + name.len() <= data.len())
+                .context("Symbol name isn't within object")?
+                as u32;
+            cached.name_len = name.len() as u32;
+            cached.name_hash = crate::hash::hash_bytes(name);
+        }
+        definitions.push(cached);
+    }
+    Ok(CachedSymbols {
+        num_symbols,
+        definitions,
+    })
+}
+
+/// Returns a value that changes if our hash function does, since cached hashes are only valid if
+/// they were computed by the same hash function that we're using now.
+fn hash_check() -> u64 {
+    crate::hash::hash_bytes(MAGIC)
+}
+
+fn write_index(entries: &[(&CacheKey, &CachedSymbols)]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&hash_check().to_le_bytes());
+    out.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+    for (key, symbols) in entries {
+        let path = key.path.as_os_str().as_bytes();
+        let modified = key.modified.duration_since(SystemTime::UNIX_EPOCH)?;
+        out.extend_from_slice(&(path.len() as u64).to_le_bytes());
+        out.extend_from_slice(path);
+        out.extend_from_slice(&modified.as_secs().to_le_bytes());
+        out.extend_from_slice(&modified.subsec_nanos().to_le_bytes());
+        out.extend_from_slice(&key.file_size.to_le_bytes());
+        out.extend_from_slice(&key.offset.to_le_bytes());
+        out.extend_from_slice(&(symbols.num_symbols as u64).to_le_bytes());
+        out.extend_from_slice(&(symbols.definitions.len() as u64).to_le_bytes());
+        for symbol in &symbols.definitions {
+            let value_kind: u8 = match symbol.value_kind {
+                ValueKind::Address => 0,
+                ValueKind::Absolute => 1,
+                ValueKind::Dynamic => 2,
+            };
+            out.extend_from_slice(&symbol.index.to_le_bytes());
+            out.push(value_kind | (u8::from(symbol.is_local) << 7));
+            out.extend_from_slice(&symbol.name_offset.to_le_bytes());
+            out.extend_from_slice(&symbol.name_len.to_le_bytes());
+            out.extend_from_slice(&symbol.name_hash.to_le_bytes());
+        }
+    }
+    Ok(out)
+}
+
+fn read_index(bytes: &[u8]) -> Result<AHashMap<CacheKey, CachedSymbols>> {
+    let mut reader = Reader::new(bytes);
+    if reader.take(MAGIC.len())? != MAGIC || reader.u64()? != hash_check() {
+        bail!("Object cache index is from a different version");
+    }
+    let num_entries = reader.u64()?;
+    let mut entries = AHashMap::new();
+    for _ in 0..num_entries {
+        let path_len = reader.u64()? as usize;
+        let path = PathBuf::from(std::ffi::OsStr::from_bytes(reader.take(path_len)?));
+        let secs = reader.u64()?;
+        let nanos = reader.u32()?;
+        let key = CacheKey {
+            path,
+            modified: SystemTime::UNIX_EPOCH + Duration::new(secs, nanos),
+            file_size: reader.u64()?,
+            offset: reader.u64()?,
+        };
+        let num_symbols = reader.u64()? as usize;
+        let num_definitions = reader.u64()? as usize;
+        let mut definitions = Vec::new();
+        for _ in 0..num_definitions {
+            let index = reader.u32()?;
+            let flags = reader.u8()?;
+            let value_kind = match flags & 0x7f {
+                0 => ValueKind::Address,
+                1 => ValueKind::Absolute,
+                2 => ValueKind::Dynamic,
+                other => bail!("Invalid value kind {other} in object cache"),
+            };
+            definitions.push(CachedSymbol {
+                index,
+                value_kind,
+                is_local: flags & 0x80 != 0,
+                name_offset: reader.u32()?,
+                name_len: reader.u32()?,
+                name_hash: reader.u64()?,
+            });
+        }
+        entries.insert(
+            key,
+            CachedSymbols {
+                num_symbols,
+                definitions,
+            },
+        );
+    }
+    Ok(entries)
+}
+
+/// Reads the little-endian values that make up our index. Also used for the state that we save
+/// for incremental linking.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes }
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.bytes.len() {
+            bail!("Unexpected end of file");
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
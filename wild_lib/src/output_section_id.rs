@@ -1,6 +1,8 @@
 use crate::alignment;
 use crate::alignment::Alignment;
 use crate::args::Args;
+use crate::args::OutputKind;
+use crate::compression::DecompressedSections;
 use crate::elf;
 use crate::elf::Section;
 use crate::error::Result;
@@ -10,6 +12,7 @@ use ahash::AHashMap;
 use anyhow::anyhow;
 use anyhow::Context as _;
 use core::mem::size_of;
+use object::Object as _;
 use object::ObjectSection;
 use object::SectionFlags;
 use std::collections::BTreeMap;
@@ -20,6 +23,8 @@ pub(crate) enum TemporaryOutputSectionId<'data> {
     BuiltIn(OutputSectionId),
     Custom(CustomSectionId<'data>),
     EhFrameData,
+    GnuPropertyNote,
+    AbiTagNote,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -35,6 +40,9 @@ pub(crate) struct UnloadedSection<'data> {
     pub(crate) output_section_id: TemporaryOutputSectionId<'data>,
     pub(crate) details: SectionDetails<'data>,
     pub(crate) is_string_merge: bool,
+    /// Whether this is an allocated section that neither the linker script nor our built-in rules
+    /// place, so it got an output section of its own. See `--orphan-handling`.
+    pub(crate) is_orphan: bool,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -69,9 +77,18 @@ pub(crate) const DYNSYM: OutputSectionId = OutputSectionId(13);
 pub(crate) const DYNSTR: OutputSectionId = OutputSectionId(14);
 pub(crate) const RELA_DYN: OutputSectionId = OutputSectionId(15);
 pub(crate) const INTERP: OutputSectionId = OutputSectionId(16);
+pub(crate) const NOTE_GNU_PROPERTY: OutputSectionId = OutputSectionId(17);
+pub(crate) const HASH: OutputSectionId = OutputSectionId(18);
+pub(crate) const SYMTAB_SHNDX: OutputSectionId = OutputSectionId(19);
+pub(crate) const NOTE_ABI_TAG: OutputSectionId = OutputSectionId(20);
+pub(crate) const GDB_INDEX: OutputSectionId = OutputSectionId(21);
+pub(crate) const GNU_VERSION_R: OutputSectionId = OutputSectionId(22);
+pub(crate) const RELR_DYN: OutputSectionId = OutputSectionId(23);
+pub(crate) const GNU_VERSION: OutputSectionId = OutputSectionId(24);
+pub(crate) const GNU_VERSION_D: OutputSectionId = OutputSectionId(25);
 
 /// Regular sections are sections that come from input files and can contain a mix of alignments.
-pub(crate) const NUM_GENERATED_SECTIONS: usize = 17;
+pub(crate) const NUM_GENERATED_SECTIONS: usize = 26;
 
 // Sections that need to be referenced from code. When adding new sections here, be sure to update
 // `test_constant_ids`.
@@ -88,8 +105,9 @@ pub(crate) const TBSS: OutputSectionId = OutputSectionId::regular(9);
 pub(crate) const BSS: OutputSectionId = OutputSectionId::regular(10);
 pub(crate) const COMMENT: OutputSectionId = OutputSectionId::regular(11);
 pub(crate) const GCC_EXCEPT_TABLE: OutputSectionId = OutputSectionId::regular(12);
+pub(crate) const DATA_REL_RO: OutputSectionId = OutputSectionId::regular(13);
 
-pub(crate) const NUM_REGULAR_SECTIONS: usize = 13;
+pub(crate) const NUM_REGULAR_SECTIONS: usize = 14;
 
 // pub(crate) const DYNSTR: BuiltInId = BuiltInId(14);
 
@@ -106,20 +124,39 @@ pub struct OutputSections<'data> {
     // sections have content, which we don't know until half way through the layout phase.
     /// Mapping from internal section IDs to output section indexes. None, if the section isn't
     /// being output.
-    pub(crate) output_section_indexes: Vec<Option<u16>>,
+    pub(crate) output_section_indexes: Vec<Option<u32>>,
 
     custom_by_name: AHashMap<&'data [u8], OutputSectionId>,
     pub(crate) ro_custom: Vec<OutputSectionId>,
     pub(crate) exec_custom: Vec<OutputSectionId>,
     pub(crate) data_custom: Vec<OutputSectionId>,
     pub(crate) bss_custom: Vec<OutputSectionId>,
+    /// Custom sections that don't occupy memory at runtime, such as debug info. These go after all
+    /// our segments.
+    pub(crate) non_alloc_custom: Vec<OutputSectionId>,
+
+    /// Sections into which we copy relocations from our input files when `--emit-relocs` is
+    /// active, in output order.
+    pub(crate) relocation_sections: Vec<OutputSectionId>,
+
+    /// Mapping from output section IDs to the ID of the section holding relocations for that
+    /// section. Empty unless `--emit-relocs` is active.
+    relocation_section_ids: Vec<Option<OutputSectionId>>,
+
+    /// Whether read-only sections go in their own segment. When false (`--no-rosegment`), the
+    /// executable segment starts at the file header and covers the read-only sections too.
+    rosegment: bool,
+
+    /// Whether the parts of `.bss` are laid out in ascending order of alignment rather than the
+    /// usual descending order. Set for `--sort-common=ascending`.
+    pub(crate) bss_ascending: bool,
 }
 
 impl<'data> OutputSections<'data> {
     /// Returns an iterator that emits all section IDs and their info.
     pub(crate) fn ids_with_info(
         &self,
-    ) -> impl Iterator<Item = (OutputSectionId, &SectionOutputInfo)> {
+    ) -> impl Iterator<Item = (OutputSectionId, &SectionOutputInfo<'_>)> {
         self.section_infos
             .iter()
             .enumerate()
@@ -141,20 +178,30 @@ impl<'data> OutputSections<'data> {
                     )
                 })?,
             TemporaryOutputSectionId::EhFrameData => EH_FRAME,
+            TemporaryOutputSectionId::GnuPropertyNote => NOTE_GNU_PROPERTY,
+            TemporaryOutputSectionId::AbiTagNote => NOTE_ABI_TAG,
         })
     }
 }
 
 pub(crate) struct SectionOutputInfo<'data> {
     pub(crate) loadable_segment_id: Option<ProgramSegmentId>,
+    /// Whether the section is part of the RELRO segment, which the runtime loader makes read-only
+    /// once relocations have been applied.
+    pub(crate) is_relro: bool,
     pub(crate) details: SectionDetails<'data>,
+    /// For a section into which we copy input relocations, the section that those relocations
+    /// apply to.
+    pub(crate) relocations_for: Option<OutputSectionId>,
 }
 
 pub(crate) struct BuiltInSectionDetails {
     details: SectionDetails<'static>,
     pub(crate) link: Option<OutputSectionId>,
-    pub(crate) start_symbol_name: Option<&'static str>,
-    pub(crate) end_symbol_name: Option<&'static str>,
+    /// Names of symbols that, if referenced, will be defined to point to the start of the section.
+    pub(crate) start_symbol_names: &'static [&'static str],
+    /// Names of symbols that, if referenced, will be defined to point to the end of the section.
+    pub(crate) end_symbol_names: &'static [&'static str],
     pub(crate) min_alignment: Alignment,
     info_fn: Option<fn(&Layout) -> u32>,
     pub(crate) keep_if_empty: bool,
@@ -187,8 +234,8 @@ const DEFAULT_DEFS: BuiltInSectionDetails = BuiltInSectionDetails {
         ..SectionDetails::default()
     },
     link: None,
-    start_symbol_name: None,
-    end_symbol_name: None,
+    start_symbol_names: &[],
+    end_symbol_names: &[],
     min_alignment: alignment::MIN,
     info_fn: None,
     keep_if_empty: false,
@@ -202,7 +249,7 @@ const SECTION_DEFINITIONS: [BuiltInSectionDetails; NUM_BUILT_IN_SECTIONS] = [
             section_flags: 0,
             ..SectionDetails::default()
         },
-        start_symbol_name: Some("__ehdr_start"),
+        start_symbol_names: &["__ehdr_start"],
         keep_if_empty: true,
         ..DEFAULT_DEFS
     },
@@ -261,7 +308,7 @@ const SECTION_DEFINITIONS: [BuiltInSectionDetails; NUM_BUILT_IN_SECTIONS] = [
             ty: elf::Sht::Progbits,
             ..SectionDetails::default()
         },
-        start_symbol_name: Some("_GLOBAL_OFFSET_TABLE_"),
+        start_symbol_names: &["_GLOBAL_OFFSET_TABLE_"],
         min_alignment: alignment::GOT_ENTRY,
         ..DEFAULT_DEFS
     },
@@ -272,6 +319,7 @@ const SECTION_DEFINITIONS: [BuiltInSectionDetails; NUM_BUILT_IN_SECTIONS] = [
             section_flags: elf::shf::ALLOC | elf::shf::EXECINSTR,
             ..SectionDetails::default()
         },
+        min_alignment: alignment::PLT_ENTRY,
         ..DEFAULT_DEFS
     },
     BuiltInSectionDetails {
@@ -283,8 +331,8 @@ const SECTION_DEFINITIONS: [BuiltInSectionDetails; NUM_BUILT_IN_SECTIONS] = [
             ..SectionDetails::default()
         },
         min_alignment: alignment::RELA_ENTRY,
-        start_symbol_name: Some("__rela_iplt_start"),
-        end_symbol_name: Some("__rela_iplt_end"),
+        start_symbol_names: &["__rela_iplt_start"],
+        end_symbol_names: &["__rela_iplt_end"],
         ..DEFAULT_DEFS
     },
     BuiltInSectionDetails {
@@ -314,7 +362,7 @@ const SECTION_DEFINITIONS: [BuiltInSectionDetails; NUM_BUILT_IN_SECTIONS] = [
             ..SectionDetails::default()
         },
         min_alignment: alignment::USIZE,
-        start_symbol_name: Some("_DYNAMIC"),
+        start_symbol_names: &["_DYNAMIC"],
         ..DEFAULT_DEFS
     },
     BuiltInSectionDetails {
@@ -371,6 +419,106 @@ const SECTION_DEFINITIONS: [BuiltInSectionDetails; NUM_BUILT_IN_SECTIONS] = [
         },
         ..DEFAULT_DEFS
     },
+    BuiltInSectionDetails {
+        details: SectionDetails {
+            name: ".note.gnu.property".as_bytes(),
+            ty: elf::Sht::Note,
+            section_flags: elf::shf::ALLOC,
+            ..SectionDetails::default()
+        },
+        min_alignment: alignment::USIZE,
+        ..DEFAULT_DEFS
+    },
+    BuiltInSectionDetails {
+        details: SectionDetails {
+            name: ".hash".as_bytes(),
+            ty: elf::Sht::Hash,
+            section_flags: elf::shf::ALLOC,
+            element_size: size_of::<u32>() as u64,
+            ..SectionDetails::default()
+        },
+        link: Some(DYNSYM),
+        min_alignment: alignment::HASH,
+        ..DEFAULT_DEFS
+    },
+    BuiltInSectionDetails {
+        details: SectionDetails {
+            name: ".symtab_shndx".as_bytes(),
+            ty: elf::Sht::SymtabShndx,
+            element_size: size_of::<u32>() as u64,
+            ..SectionDetails::default()
+        },
+        link: Some(SYMTAB),
+        min_alignment: alignment::SYMTAB_SHNDX_ENTRY,
+        ..DEFAULT_DEFS
+    },
+    BuiltInSectionDetails {
+        details: SectionDetails {
+            name: ".note.ABI-tag".as_bytes(),
+            ty: elf::Sht::Note,
+            section_flags: elf::shf::ALLOC,
+            ..SectionDetails::default()
+        },
+        min_alignment: alignment::NOTE_ABI_TAG,
+        ..DEFAULT_DEFS
+    },
+    BuiltInSectionDetails {
+        details: SectionDetails {
+            name: ".gdb_index".as_bytes(),
+            ty: elf::Sht::Progbits,
+            section_flags: 0,
+            ..SectionDetails::default()
+        },
+        min_alignment: alignment::GDB_INDEX,
+        ..DEFAULT_DEFS
+    },
+    BuiltInSectionDetails {
+        details: SectionDetails {
+            name: ".gnu.version_r".as_bytes(),
+            ty: elf::Sht::GnuVerneed,
+            section_flags: elf::shf::ALLOC,
+            ..SectionDetails::default()
+        },
+        link: Some(DYNSTR),
+        min_alignment: alignment::VERNEED,
+        info_fn: Some(gnu_version_r_info),
+        ..DEFAULT_DEFS
+    },
+    BuiltInSectionDetails {
+        details: SectionDetails {
+            name: ".relr.dyn".as_bytes(),
+            ty: elf::Sht::Relr,
+            section_flags: elf::shf::ALLOC,
+            element_size: elf::RELR_ENTRY_SIZE,
+            ..SectionDetails::default()
+        },
+        min_alignment: alignment::RELR_ENTRY,
+        ..DEFAULT_DEFS
+    },
+    BuiltInSectionDetails {
+        details: SectionDetails {
+            name: ".gnu.version".as_bytes(),
+            ty: elf::Sht::GnuVersym,
+            section_flags: elf::shf::ALLOC,
+            element_size: elf::VERSYM_ENTRY_SIZE,
+            ..SectionDetails::default()
+        },
+        link: Some(DYNSYM),
+        min_alignment: alignment::VERSYM_ENTRY,
+        ..DEFAULT_DEFS
+    },
+    BuiltInSectionDetails {
+        details: SectionDetails {
+            name: ".gnu.version_d".as_bytes(),
+            ty: elf::Sht::GnuVerdef,
+            section_flags: elf::shf::ALLOC,
+            ..SectionDetails::default()
+        },
+        link: Some(DYNSTR),
+        min_alignment: alignment::VERDEF,
+        info_fn: Some(gnu_version_d_info),
+        ..DEFAULT_DEFS
+    },
     // Start of regular sections
     BuiltInSectionDetails {
         details: SectionDetails {
@@ -388,8 +536,8 @@ const SECTION_DEFINITIONS: [BuiltInSectionDetails; NUM_BUILT_IN_SECTIONS] = [
             retain: true,
             ..SectionDetails::default()
         },
-        start_symbol_name: Some("__init_array_start"),
-        end_symbol_name: Some("__init_array_end"),
+        start_symbol_names: &["__init_array_start"],
+        end_symbol_names: &["__init_array_end"],
         ..DEFAULT_DEFS
     },
     BuiltInSectionDetails {
@@ -400,8 +548,8 @@ const SECTION_DEFINITIONS: [BuiltInSectionDetails; NUM_BUILT_IN_SECTIONS] = [
             retain: true,
             ..SectionDetails::default()
         },
-        start_symbol_name: Some("__fini_array_start"),
-        end_symbol_name: Some("__fini_array_end"),
+        start_symbol_names: &["__fini_array_start"],
+        end_symbol_names: &["__fini_array_end"],
         ..DEFAULT_DEFS
     },
     BuiltInSectionDetails {
@@ -411,8 +559,8 @@ const SECTION_DEFINITIONS: [BuiltInSectionDetails; NUM_BUILT_IN_SECTIONS] = [
             retain: true,
             ..SectionDetails::default()
         },
-        start_symbol_name: Some("__preinit_array_start"),
-        end_symbol_name: Some("__preinit_array_end"),
+        start_symbol_names: &["__preinit_array_start"],
+        end_symbol_names: &["__preinit_array_end"],
         ..DEFAULT_DEFS
     },
     BuiltInSectionDetails {
@@ -481,7 +629,8 @@ const SECTION_DEFINITIONS: [BuiltInSectionDetails; NUM_BUILT_IN_SECTIONS] = [
             ..SectionDetails::default()
         },
         keep_if_empty: true,
-        end_symbol_name: Some("_end"),
+        start_symbol_names: &["__bss_start", "_edata", "edata"],
+        end_symbol_names: &["_end", "end"],
         ..DEFAULT_DEFS
     },
     BuiltInSectionDetails {
@@ -503,6 +652,17 @@ const SECTION_DEFINITIONS: [BuiltInSectionDetails; NUM_BUILT_IN_SECTIONS] = [
         },
         ..DEFAULT_DEFS
     },
+    // Data that's only written by relocations applied at load time. This goes in the RELRO segment
+    // so that it can be made read-only once those relocations have been applied.
+    BuiltInSectionDetails {
+        details: SectionDetails {
+            name: ".data.rel.ro".as_bytes(),
+            ty: elf::Sht::Progbits,
+            section_flags: elf::shf::ALLOC | elf::shf::WRITE,
+            ..SectionDetails::default()
+        },
+        ..DEFAULT_DEFS
+    },
     // OutputSectionDef {
     //     name: ".dynamic",
     //     ty: elf::Sht::Dynamic,
@@ -521,17 +681,58 @@ const SECTION_DEFINITIONS: [BuiltInSectionDetails; NUM_BUILT_IN_SECTIONS] = [
 
 impl<'data> UnloadedSection<'data> {
     #[allow(clippy::if_same_then_else)]
-    pub(crate) fn from_section(section: &Section<'data, '_>, args: &Args) -> Result<Option<Self>> {
-        // Ideally we support reading an actual linker script to make these decisions, but for now
-        // we just hard code stuff.
+    pub(crate) fn from_section(
+        section: &Section<'data, '_>,
+        object: &crate::elf::File<'data>,
+        decompressed_sections: &DecompressedSections,
+        args: &'data Args,
+    ) -> Result<Option<Self>> {
         let section_name = section.name_bytes().unwrap_or_default();
         let SectionFlags::Elf { sh_flags } = section.flags() else {
             unreachable!();
         };
+        if crate::icf::is_addrsig_section(section) {
+            return Ok(None);
+        }
+        if let Some(output) = args
+            .linker_script
+            .as_ref()
+            .and_then(|script| script.output_section_for(section_name))
+        {
+            let name = output.name.as_bytes();
+            let is_string_merge =
+                should_merge_strings(section, name, object, decompressed_sections, args)?;
+            // Sections that the script puts in one of our built-in output sections go there as
+            // usual. Anything else gets a custom output section with the name from the script.
+            let output_section_id = built_in_section_ids()
+                .skip(NUM_GENERATED_SECTIONS)
+                .find(|id| id.built_in_details().details.name == name);
+            return Ok(Some(match output_section_id {
+                Some(built_in_id) => {
+                    let mut details = built_in_id.built_in_details().details;
+                    details.retain |= sh_flags & crate::elf::shf::GNU_RETAIN != 0;
+                    UnloadedSection {
+                        output_section_id: TemporaryOutputSectionId::BuiltIn(built_in_id),
+                        details,
+                        is_string_merge,
+                        is_orphan: false,
+                    }
+                }
+                None => UnloadedSection {
+                    output_section_id: TemporaryOutputSectionId::Custom(CustomSectionId { name }),
+                    details: custom_section_details(section, name, sh_flags),
+                    is_string_merge,
+                    is_orphan: false,
+                },
+            }));
+        }
+        // Without a linker script, we use hard coded rules.
         let built_in_id = if section_name.starts_with(b".rodata") {
             Some(RODATA)
         } else if section_name.starts_with(b".text") {
             Some(TEXT)
+        } else if section_name.starts_with(b".data.rel.ro") {
+            Some(DATA_REL_RO)
         } else if section_name.starts_with(b".data") {
             Some(DATA)
         } else if section_name.starts_with(b".bss") {
@@ -552,11 +753,36 @@ impl<'data> UnloadedSection<'data> {
             Some(TBSS)
         } else if section_name == b".comment" {
             Some(COMMENT)
+        } else if section_name == b".eh_frame" && args.output_kind == OutputKind::Relocatable {
+            // A relocatable output keeps .eh_frame as it is, along with its relocations, for the
+            // final link to process.
+            let custom_section_id = CustomSectionId { name: section_name };
+            return Ok(Some(UnloadedSection {
+                output_section_id: TemporaryOutputSectionId::Custom(custom_section_id),
+                details: custom_section_details(section, section_name, sh_flags),
+                is_string_merge: false,
+                is_orphan: false,
+            }));
         } else if section_name == b".eh_frame" {
             return Ok(Some(UnloadedSection {
                 output_section_id: TemporaryOutputSectionId::EhFrameData,
                 details: EH_FRAME.built_in_details().details,
                 is_string_merge: false,
+                is_orphan: false,
+            }));
+        } else if section_name == b".note.gnu.property" {
+            return Ok(Some(UnloadedSection {
+                output_section_id: TemporaryOutputSectionId::GnuPropertyNote,
+                details: NOTE_GNU_PROPERTY.built_in_details().details,
+                is_string_merge: false,
+                is_orphan: false,
+            }));
+        } else if section_name == b".note.ABI-tag" {
+            return Ok(Some(UnloadedSection {
+                output_section_id: TemporaryOutputSectionId::AbiTagNote,
+                details: NOTE_ABI_TAG.built_in_details().details,
+                is_string_merge: false,
+                is_orphan: false,
             }));
         } else if section_name.starts_with(b".gcc_except_table") {
             Some(GCC_EXCEPT_TABLE)
@@ -569,31 +795,22 @@ impl<'data> UnloadedSection<'data> {
             // We don't currently allow references to these sections, discard them so that we avoid
             // allocating output section IDs.
             None
-        } else if args.strip_debug && section_name == b".debug_str" {
+        } else if args.strip_debug && section_name.starts_with(b".debug_") {
             None
         } else {
-            let ty = match section.kind() {
-                object::SectionKind::UninitializedData | object::SectionKind::UninitializedTls => {
-                    crate::elf::Sht::Nobits
-                }
-                _ => crate::elf::Sht::Progbits,
-            };
-            let retain = sh_flags & crate::elf::shf::GNU_RETAIN != 0;
-            let section_flags = sh_flags;
             if !section_name.is_empty() {
                 let custom_section_id = CustomSectionId { name: section_name };
-                let details = SectionDetails {
-                    name: section_name,
-                    ty,
-                    section_flags,
-                    element_size: 0,
-                    retain,
-                    packed: false,
-                };
                 return Ok(Some(UnloadedSection {
                     output_section_id: TemporaryOutputSectionId::Custom(custom_section_id),
-                    details,
-                    is_string_merge: should_merge_strings(section, args),
+                    details: custom_section_details(section, section_name, sh_flags),
+                    is_string_merge: should_merge_strings(
+                        section,
+                        section_name,
+                        object,
+                        decompressed_sections,
+                        args,
+                    )?,
+                    is_orphan: sh_flags & crate::elf::shf::ALLOC != 0,
                 }));
             }
             match section.kind() {
@@ -613,27 +830,129 @@ impl<'data> UnloadedSection<'data> {
         let Some(built_in_id) = built_in_id else {
             return Ok(None);
         };
+        let mut details = built_in_id.built_in_details().details;
+        // A section like `.text.foo` can be marked as retained even though it goes into a built-in
+        // output section that isn't.
+        details.retain |= sh_flags & crate::elf::shf::GNU_RETAIN != 0;
         Ok(Some(UnloadedSection {
             output_section_id: TemporaryOutputSectionId::BuiltIn(built_in_id),
-            details: built_in_id.built_in_details().details,
-            is_string_merge: should_merge_strings(section, args),
+            details,
+            is_string_merge: should_merge_strings(
+                section,
+                details.name,
+                object,
+                decompressed_sections,
+                args,
+            )?,
+            is_orphan: false,
         }))
     }
+
+    /// Returns the name of the output section into which we should copy the relocations for
+    /// `section` when `--emit-relocs` is active, or None if there aren't any relocations that we
+    /// copy. We only copy relocations for allocated sections that we copy from our inputs, unless
+    /// we're producing a relocatable output, in which case we need the relocations for debug info
+    /// too.
+    pub(crate) fn relocation_section_name(
+        &self,
+        section: &Section<'data, '_>,
+        object: &crate::elf::File<'data>,
+        args: &Args,
+    ) -> Result<Option<&'data [u8]>> {
+        if self.is_string_merge
+            || (self.details.section_flags & elf::shf::ALLOC == 0
+                && args.output_kind != OutputKind::Relocatable)
+            || section.relocations().next().is_none()
+        {
+            return Ok(None);
+        }
+        match self.output_section_id {
+            TemporaryOutputSectionId::BuiltIn(id) => Ok(id.relocation_section_name()),
+            TemporaryOutputSectionId::Custom(custom) => {
+                // Use the name of the section holding the input relocations, since it's the name
+                // we'd have constructed and it lives as long as our input data.
+                let name = [b".rela", custom.name].concat();
+                let rela_section = object.section_by_name_bytes(&name).with_context(|| {
+                    format!(
+                        "Failed to find relocation section for `{}`",
+                        String::from_utf8_lossy(custom.name)
+                    )
+                })?;
+                Ok(Some(rela_section.name_bytes()?))
+            }
+            TemporaryOutputSectionId::EhFrameData
+            | TemporaryOutputSectionId::GnuPropertyNote
+            | TemporaryOutputSectionId::AbiTagNote => Ok(None),
+        }
+    }
 }
 
-/// Returns whether the supplied section meets our criteria for string merging. String merging is
-/// optional, so there are cases where we might be able to merge, but don't currently. For example
-/// if alignment is > 1.
-fn should_merge_strings(section: &Section, args: &Args) -> bool {
-    if !args.merge_strings {
-        return false;
+/// Returns the details of a custom output section with the supplied name that holds `section`.
+fn custom_section_details<'data>(
+    section: &Section,
+    name: &'data [u8],
+    sh_flags: u64,
+) -> SectionDetails<'data> {
+    let ty = match section.kind() {
+        object::SectionKind::UninitializedData | object::SectionKind::UninitializedTls => {
+            crate::elf::Sht::Nobits
+        }
+        _ => crate::elf::Sht::Progbits,
+    };
+    SectionDetails {
+        name,
+        ty,
+        // We always write sections uncompressed.
+        section_flags: sh_flags & !crate::elf::shf::COMPRESSED,
+        element_size: 0,
+        // Nothing refers to debug info, so we'd otherwise garbage collect it.
+        retain: sh_flags & crate::elf::shf::GNU_RETAIN != 0 || is_debug_section(section),
+        packed: false,
     }
+}
+
+/// Returns whether `section` holds debug info. Relocations in such sections don't cause the sections
+/// that they refer to to be loaded.
+pub(crate) fn is_debug_section(section: &Section) -> bool {
     let SectionFlags::Elf { sh_flags } = section.flags() else {
         unreachable!();
     };
-    (sh_flags & crate::elf::shf::MERGE) != 0
-        && (sh_flags & crate::elf::shf::STRINGS) != 0
-        && section.align() <= 1
+    sh_flags & crate::elf::shf::ALLOC == 0
+        && section
+            .name_bytes()
+            .is_ok_and(|name| name.starts_with(b".debug_"))
+}
+
+/// Returns whether the supplied section meets our criteria for merging. Sections with both
+/// SHF_MERGE and SHF_STRINGS contain null-terminated strings. Sections with just SHF_MERGE contain
+/// fixed-size entries of `sh_entsize` bytes, e.g. floating point constants. Merging is optional, so
+/// there are cases where we might be able to merge, but don't currently. For example strings with
+/// alignment > 1. `output_name` is the name of the output section that `section` goes into, which
+/// `--merge=` and `--no-merge=` rules are matched against.
+fn should_merge_strings(
+    section: &Section,
+    output_name: &[u8],
+    object: &crate::elf::File,
+    decompressed_sections: &DecompressedSections,
+    args: &Args,
+) -> Result<bool> {
+    let SectionFlags::Elf { sh_flags } = section.flags() else {
+        unreachable!();
+    };
+    // Debug info refers to strings in sections like `.debug_str` via the section symbol plus an
+    // addend, which we can't resolve to a merged string, so we just concatenate such sections.
+    if (sh_flags & crate::elf::shf::MERGE) == 0 || is_debug_section(section) {
+        return Ok(false);
+    }
+    if (sh_flags & crate::elf::shf::STRINGS) != 0 {
+        return Ok(args.should_merge_section(output_name, true)
+            && decompressed_sections.align(section) <= 1);
+    }
+    if !args.should_merge_section(output_name, false) {
+        return Ok(false);
+    }
+    let entsize = crate::elf::section_entsize(object, section.index())?;
+    Ok(entsize > 0 && decompressed_sections.size(section).is_multiple_of(entsize))
 }
 
 pub(crate) fn built_in_section_ids(
@@ -662,6 +981,26 @@ impl OutputSectionId {
         OrderEvent::Section(self, &SECTION_DEFINITIONS[self.as_usize()].details)
     }
 
+    /// Returns the name of the section that holds relocations for this built-in section when
+    /// `--emit-relocs` is active.
+    fn relocation_section_name(self) -> Option<&'static [u8]> {
+        let name: &'static [u8] = match self {
+            RODATA => b".rela.rodata",
+            INIT_ARRAY => b".rela.init_array",
+            FINI_ARRAY => b".rela.fini_array",
+            PREINIT_ARRAY => b".rela.preinit_array",
+            TEXT => b".rela.text",
+            INIT => b".rela.init",
+            FINI => b".rela.fini",
+            DATA => b".rela.data",
+            TDATA => b".rela.tdata",
+            GCC_EXCEPT_TABLE => b".rela.gcc_except_table",
+            DATA_REL_RO => b".rela.data.rel.ro",
+            _ => return None,
+        };
+        Some(name)
+    }
+
     pub(crate) fn min_alignment(&self) -> Alignment {
         SECTION_DEFINITIONS
             .get(self.as_usize())
@@ -672,6 +1011,11 @@ impl OutputSectionId {
     /// Computes the value for the info field for this section. For most sections this is just 0,
     /// but a few sections put some special value in there.
     pub(crate) fn info(&self, layout: &Layout) -> u32 {
+        let output_sections = &layout.output_sections;
+        if let Some(target) = output_sections.output_info(*self).relocations_for {
+            // Relocation sections hold the index of the section to which they apply.
+            return output_sections.output_index_of_section(target).unwrap_or(0);
+        }
         SECTION_DEFINITIONS
             .get(self.as_usize())
             .and_then(|d| d.info_fn)
@@ -689,6 +1033,8 @@ pub(crate) enum OrderEvent<'data> {
 pub(crate) struct OutputSectionsBuilder<'data> {
     base_address: u64,
     custom: BTreeMap<&'data [u8], SectionDetails<'data>>,
+    /// Sections for `--emit-relocs`, keyed by name, with the section to which they apply.
+    relocations: BTreeMap<&'data [u8], TemporaryOutputSectionId<'data>>,
 }
 
 impl<'data> OutputSectionsBuilder<'data> {
@@ -698,12 +1044,15 @@ impl<'data> OutputSectionsBuilder<'data> {
             .map(|d| SectionOutputInfo {
                 details: d.details,
                 loadable_segment_id: Some(crate::program_segments::LOAD_RO),
+                is_relro: false,
+                relocations_for: None,
             })
             .collect();
         let mut ro_custom = Vec::new();
         let mut exec_custom = Vec::new();
         let mut data_custom = Vec::new();
         let mut bss_custom = Vec::new();
+        let mut non_alloc_custom = Vec::new();
         let custom_by_name = self
             .custom
             .iter()
@@ -713,9 +1062,13 @@ impl<'data> OutputSectionsBuilder<'data> {
                     details: *details,
                     // We'll fill this in properly below.
                     loadable_segment_id: None,
+                    is_relro: false,
+                    relocations_for: None,
                 });
                 let id = OutputSectionId::from_usize(offset + NUM_BUILT_IN_SECTIONS);
-                if (details.section_flags & crate::elf::shf::EXECINSTR) != 0 {
+                if (details.section_flags & crate::elf::shf::ALLOC) == 0 {
+                    non_alloc_custom.push(id);
+                } else if (details.section_flags & crate::elf::shf::EXECINSTR) != 0 {
                     exec_custom.push(id);
                 } else if (details.section_flags & crate::elf::shf::WRITE) == 0 {
                     ro_custom.push(id)
@@ -736,36 +1089,50 @@ impl<'data> OutputSectionsBuilder<'data> {
             exec_custom,
             data_custom,
             bss_custom,
+            non_alloc_custom,
+            relocation_sections: Vec::new(),
+            relocation_section_ids: Vec::new(),
             output_section_indexes: Default::default(),
+            rosegment: true,
+            bss_ascending: false,
         };
+        for (name, target) in &self.relocations {
+            output_sections.add_relocation_section(name, *target)?;
+        }
         let mut extra = vec![None; output_sections.section_infos.len()];
         let mut load_seg_id = None;
+        let mut in_relro = false;
         output_sections.sections_and_segments_do(|event| match event {
             OrderEvent::SegmentStart(seg_id) => {
                 if seg_id.segment_type() == crate::elf::SegmentType::Load {
                     load_seg_id = Some(seg_id);
+                } else if seg_id == crate::program_segments::RELRO {
+                    in_relro = true;
                 }
             }
             OrderEvent::SegmentEnd(seg_id) => {
                 if load_seg_id == Some(seg_id) {
                     load_seg_id = None;
+                } else if seg_id == crate::program_segments::RELRO {
+                    in_relro = false;
                 }
             }
             OrderEvent::Section(section_id, _section_details) => {
-                extra[section_id.as_usize()] = Some(load_seg_id);
+                extra[section_id.as_usize()] = Some((load_seg_id, in_relro));
             }
         });
         extra
             .iter()
             .zip(output_sections.section_infos.iter_mut())
             .try_for_each(|(ext, info)| -> Result {
-                let load_seg_id = ext.ok_or_else(|| {
+                let (load_seg_id, is_relro) = ext.ok_or_else(|| {
                     anyhow!(
                         "Section `{}` is missing from output order (update sections_and_segments_do)",
                         String::from_utf8_lossy(info.details.name),
                     )
                 })?;
                 info.loadable_segment_id = load_seg_id;
+                info.is_relro = is_relro;
                 Ok(())
             })?;
         Ok(output_sections)
@@ -792,19 +1159,76 @@ impl<'data> OutputSectionsBuilder<'data> {
         Ok(())
     }
 
+    /// Records sections for `--emit-relocs`. Each entry is the section to which the relocations
+    /// apply together with the name of the section to hold them.
+    pub(crate) fn add_relocation_sections(
+        &mut self,
+        relocation_sections: &[(TemporaryOutputSectionId<'data>, &'data [u8])],
+    ) {
+        for (target, name) in relocation_sections {
+            self.relocations.entry(name).or_insert(*target);
+        }
+    }
+
     pub(crate) fn with_base_address(base_address: u64) -> Self {
         Self {
             base_address,
             custom: Default::default(),
+            relocations: Default::default(),
         }
     }
 }
 
 impl<'data> OutputSections<'data> {
+    fn add_relocation_section(
+        &mut self,
+        name: &'data [u8],
+        target: TemporaryOutputSectionId<'data>,
+    ) -> Result {
+        let target = self.output_section_id(target)?;
+        if self.relocation_section_id(target).is_some() {
+            return Ok(());
+        }
+        let id = OutputSectionId::from_usize(self.section_infos.len());
+        self.section_infos.push(SectionOutputInfo {
+            loadable_segment_id: None,
+            is_relro: false,
+            details: SectionDetails {
+                name,
+                ty: elf::Sht::Rela,
+                section_flags: elf::shf::INFO_LINK,
+                element_size: elf::RELA_ENTRY_SIZE,
+                retain: false,
+                packed: false,
+            },
+            relocations_for: Some(target),
+        });
+        self.relocation_sections.push(id);
+        self.relocation_section_ids
+            .resize(self.section_infos.len(), None);
+        self.relocation_section_ids[target.as_usize()] = Some(id);
+        Ok(())
+    }
+
+    /// Returns the ID of the section into which we copy relocations that apply to `id`, if any.
+    pub(crate) fn relocation_section_id(&self, id: OutputSectionId) -> Option<OutputSectionId> {
+        self.relocation_section_ids
+            .get(id.as_usize())
+            .copied()
+            .flatten()
+    }
+
     /// Calls `cb` for each section and segment in output order. Segments span multiple sections and
     /// can overlap, so are represented as start and end events.
     pub(crate) fn sections_and_segments_do(&self, mut cb: impl FnMut(OrderEvent)) {
-        cb(OrderEvent::SegmentStart(crate::program_segments::LOAD_RO));
+        if self.rosegment {
+            cb(OrderEvent::SegmentStart(crate::program_segments::LOAD_RO));
+        } else {
+            // The read-only segment is left empty, so won't be output.
+            cb(OrderEvent::SegmentStart(crate::program_segments::LOAD_RO));
+            cb(OrderEvent::SegmentEnd(crate::program_segments::LOAD_RO));
+            cb(OrderEvent::SegmentStart(crate::program_segments::LOAD_EXEC));
+        }
         cb(FILE_HEADER.event());
         cb(OrderEvent::SegmentStart(crate::program_segments::PHDR));
         cb(PROGRAM_HEADERS.event());
@@ -813,10 +1237,25 @@ impl<'data> OutputSections<'data> {
         cb(OrderEvent::SegmentStart(crate::program_segments::INTERP));
         cb(INTERP.event());
         cb(OrderEvent::SegmentEnd(crate::program_segments::INTERP));
+        cb(OrderEvent::SegmentStart(crate::program_segments::NOTE));
+        cb(OrderEvent::SegmentStart(
+            crate::program_segments::GNU_PROPERTY,
+        ));
+        cb(NOTE_GNU_PROPERTY.event());
+        cb(OrderEvent::SegmentEnd(
+            crate::program_segments::GNU_PROPERTY,
+        ));
+        cb(NOTE_ABI_TAG.event());
+        cb(OrderEvent::SegmentEnd(crate::program_segments::NOTE));
+        cb(HASH.event());
         cb(GNU_HASH.event());
         cb(DYNSYM.event());
         cb(DYNSTR.event());
+        cb(GNU_VERSION.event());
+        cb(GNU_VERSION_D.event());
+        cb(GNU_VERSION_R.event());
         cb(RELA_DYN.event());
+        cb(RELR_DYN.event());
         cb(RODATA.event());
         cb(OrderEvent::SegmentStart(crate::program_segments::EH_FRAME));
         cb(EH_FRAME_HDR.event());
@@ -824,12 +1263,15 @@ impl<'data> OutputSections<'data> {
         cb(PREINIT_ARRAY.event());
         cb(SHSTRTAB.event());
         cb(SYMTAB.event());
+        cb(SYMTAB_SHNDX.event());
         cb(STRTAB.event());
         cb(GCC_EXCEPT_TABLE.event());
         self.ids_do(&self.ro_custom, &mut cb);
-        cb(OrderEvent::SegmentEnd(crate::program_segments::LOAD_RO));
+        if self.rosegment {
+            cb(OrderEvent::SegmentEnd(crate::program_segments::LOAD_RO));
+            cb(OrderEvent::SegmentStart(crate::program_segments::LOAD_EXEC));
+        }
 
-        cb(OrderEvent::SegmentStart(crate::program_segments::LOAD_EXEC));
         cb(PLT.event());
         cb(TEXT.event());
         cb(INIT.event());
@@ -838,15 +1280,18 @@ impl<'data> OutputSections<'data> {
         cb(OrderEvent::SegmentEnd(crate::program_segments::LOAD_EXEC));
 
         cb(OrderEvent::SegmentStart(crate::program_segments::LOAD_RW));
-        cb(GOT.event());
-        cb(RELA_PLT.event());
+        cb(OrderEvent::SegmentStart(crate::program_segments::RELRO));
         cb(INIT_ARRAY.event());
         cb(FINI_ARRAY.event());
-        cb(DATA.event());
-        cb(EH_FRAME.event());
+        cb(DATA_REL_RO.event());
         cb(OrderEvent::SegmentStart(crate::program_segments::DYNAMIC));
         cb(DYNAMIC.event());
         cb(OrderEvent::SegmentEnd(crate::program_segments::DYNAMIC));
+        cb(GOT.event());
+        cb(OrderEvent::SegmentEnd(crate::program_segments::RELRO));
+        cb(RELA_PLT.event());
+        cb(DATA.event());
+        cb(EH_FRAME.event());
         self.ids_do(&self.data_custom, &mut cb);
         cb(OrderEvent::SegmentStart(crate::program_segments::TLS));
         cb(TDATA.event());
@@ -857,6 +1302,14 @@ impl<'data> OutputSections<'data> {
         cb(OrderEvent::SegmentEnd(crate::program_segments::LOAD_RW));
 
         cb(COMMENT.event());
+        self.ids_do(&self.non_alloc_custom, &mut cb);
+        cb(GDB_INDEX.event());
+        self.ids_do(&self.relocation_sections, &mut cb);
+
+        // GNU_STACK doesn't contain any sections. It just tells the loader what permissions and
+        // size to use for the stack.
+        cb(OrderEvent::SegmentStart(crate::program_segments::GNU_STACK));
+        cb(OrderEvent::SegmentEnd(crate::program_segments::GNU_STACK));
     }
 
     fn ids_do(&self, ids: &Vec<OutputSectionId>, cb: &mut impl FnMut(OrderEvent<'_>)) {
@@ -891,28 +1344,60 @@ impl<'data> OutputSections<'data> {
         self.output_info(id).details.has_data_in_file()
     }
 
-    pub(crate) fn output_info(&self, id: OutputSectionId) -> &SectionOutputInfo {
+    pub(crate) fn output_info(&self, id: OutputSectionId) -> &SectionOutputInfo<'_> {
         &self.section_infos[id.as_usize()]
     }
 
     /// Returns the output index of the built-in-section `id` or None if the section isn't being
     /// output.
-    pub(crate) fn output_index_of_section(&self, id: OutputSectionId) -> Option<u16> {
+    pub(crate) fn output_index_of_section(&self, id: OutputSectionId) -> Option<u32> {
         self.output_section_indexes
             .get(id.as_usize())
             .copied()
             .flatten()
     }
 
+    /// Returns whether we might output so many sections that some symbols will need their section
+    /// index stored in `.symtab_shndx`. We decide this before we know which sections will be output,
+    /// so we assume that they all will be.
+    pub(crate) fn needs_symtab_shndx(&self) -> bool {
+        self.len() >= usize::from(object::elf::SHN_LORESERVE)
+    }
+
     pub(crate) fn loadable_segment_id_for(&self, id: OutputSectionId) -> Option<ProgramSegmentId> {
         self.output_info(id).loadable_segment_id
     }
 
-    pub(crate) fn details(&self, id: OutputSectionId) -> &SectionDetails {
+    pub(crate) fn is_relro(&self, id: OutputSectionId) -> bool {
+        self.output_info(id).is_relro
+    }
+
+    /// Stops treating any sections as RELRO. Used for `-z norelro`.
+    pub(crate) fn disable_relro(&mut self) {
+        for info in &mut self.section_infos {
+            info.is_relro = false;
+        }
+    }
+
+    /// Places read-only sections in the executable segment rather than in a segment of their own.
+    /// Used for `--no-rosegment`.
+    pub(crate) fn disable_rosegment(&mut self) {
+        self.rosegment = false;
+        for info in &mut self.section_infos {
+            if info.loadable_segment_id == Some(crate::program_segments::LOAD_RO) {
+                info.loadable_segment_id = Some(crate::program_segments::LOAD_EXEC);
+            }
+        }
+    }
+
+    pub(crate) fn details(&self, id: OutputSectionId) -> &SectionDetails<'_> {
         &self.output_info(id).details
     }
 
     pub(crate) fn link_id(&self, section_id: OutputSectionId) -> Option<OutputSectionId> {
+        if self.output_info(section_id).relocations_for.is_some() {
+            return Some(SYMTAB);
+        }
         SECTION_DEFINITIONS
             .get(section_id.as_usize())
             .and_then(|def| def.link)
@@ -922,7 +1407,7 @@ impl<'data> OutputSections<'data> {
         self.section_infos[section_id.as_usize()].details.name
     }
 
-    pub(crate) fn display_name(&self, section_id: OutputSectionId) -> std::borrow::Cow<str> {
+    pub(crate) fn display_name(&self, section_id: OutputSectionId) -> std::borrow::Cow<'_, str> {
         String::from_utf8_lossy(self.name(section_id))
     }
 
@@ -990,6 +1475,16 @@ fn dynsym_info(_layout: &Layout) -> u32 {
     1
 }
 
+fn gnu_version_r_info(layout: &Layout) -> u32 {
+    // For GNU_VERSION_R, the info field holds the number of entries.
+    layout.num_version_requirements() as u32
+}
+
+fn gnu_version_d_info(layout: &Layout) -> u32 {
+    // For GNU_VERSION_D, the info field holds the number of entries.
+    layout.num_version_definitions() as u32
+}
+
 #[test]
 fn test_constant_ids() {
     let check = &[
@@ -1020,9 +1515,19 @@ fn test_constant_ids() {
         (RELA_DYN, ".rela.dyn"),
         (GCC_EXCEPT_TABLE, ".gcc_except_table"),
         (INTERP, ".interp"),
+        (NOTE_GNU_PROPERTY, ".note.gnu.property"),
         (PROGRAM_HEADERS, ".phdr"),
         (SECTION_HEADERS, ".shdr"),
         (GNU_HASH, ".gnu.hash"),
+        (HASH, ".hash"),
+        (SYMTAB_SHNDX, ".symtab_shndx"),
+        (NOTE_ABI_TAG, ".note.ABI-tag"),
+        (GDB_INDEX, ".gdb_index"),
+        (GNU_VERSION_R, ".gnu.version_r"),
+        (RELR_DYN, ".relr.dyn"),
+        (GNU_VERSION, ".gnu.version"),
+        (GNU_VERSION_D, ".gnu.version_d"),
+        (DATA_REL_RO, ".data.rel.ro"),
     ];
     for (id, name) in check {
         assert_eq!(
@@ -1058,6 +1563,8 @@ impl<'data> std::fmt::Display for TemporaryOutputSectionId<'data> {
                 )
             }
             TemporaryOutputSectionId::EhFrameData => write!(f, "eh_frame data"),
+            TemporaryOutputSectionId::GnuPropertyNote => write!(f, "GNU property note"),
+            TemporaryOutputSectionId::AbiTagNote => write!(f, "ABI tag note"),
         }
     }
 }
@@ -11,7 +11,7 @@ use std::ops::AddAssign;
 /// parts in different ways. Sections that come from input files are split by alignment. Some
 /// sections have no splitting and some have splitting that is specific to that particular section.
 /// For example the symbol table is split into local then global symbols.
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
 pub(crate) struct OutputSectionPartMap<T> {
     pub(crate) regular: Vec<AlignmentMap<T>>,
     pub(crate) file_header: T,
@@ -22,6 +22,10 @@ pub(crate) struct OutputSectionPartMap<T> {
     pub(crate) symtab_locals: T,
     pub(crate) symtab_globals: T,
     pub(crate) symtab_strings: T,
+    /// The extended section indexes of the symbols in `symtab_locals` and `symtab_globals`. Only
+    /// allocated when we have too many sections to fit in a symbol's `st_shndx`.
+    pub(crate) symtab_shndx_locals: T,
+    pub(crate) symtab_shndx_globals: T,
     pub(crate) shstrtab: T,
     pub(crate) rela_plt: T,
     pub(crate) eh_frame: T,
@@ -32,7 +36,15 @@ pub(crate) struct OutputSectionPartMap<T> {
     pub(crate) dynstr: T,
     pub(crate) rela_dyn_relative: T,
     pub(crate) rela_dyn_glob_dat: T,
+    pub(crate) relr_dyn: T,
+    pub(crate) gnu_version: T,
+    pub(crate) gnu_version_d: T,
+    pub(crate) gnu_version_r: T,
     pub(crate) interp: T,
+    pub(crate) note_gnu_property: T,
+    pub(crate) note_abi_tag: T,
+    pub(crate) gdb_index: T,
+    pub(crate) hash: T,
 }
 
 impl<T: Default> OutputSectionPartMap<T> {
@@ -49,6 +61,8 @@ impl<T: Default> OutputSectionPartMap<T> {
             symtab_locals: Default::default(),
             symtab_globals: Default::default(),
             symtab_strings: Default::default(),
+            symtab_shndx_locals: Default::default(),
+            symtab_shndx_globals: Default::default(),
             shstrtab: Default::default(),
             rela_plt: Default::default(),
             eh_frame: Default::default(),
@@ -59,7 +73,15 @@ impl<T: Default> OutputSectionPartMap<T> {
             dynstr: Default::default(),
             rela_dyn_relative: Default::default(),
             rela_dyn_glob_dat: Default::default(),
+            relr_dyn: Default::default(),
+            gnu_version: Default::default(),
+            gnu_version_d: Default::default(),
+            gnu_version_r: Default::default(),
             interp: Default::default(),
+            note_gnu_property: Default::default(),
+            note_abi_tag: Default::default(),
+            gdb_index: Default::default(),
+            hash: Default::default(),
         }
     }
 }
@@ -113,6 +135,21 @@ impl<T: Default + PartialEq> OutputSectionPartMap<T> {
             output_section_id::INTERP.min_alignment(),
             &self.interp,
         );
+        let note_gnu_property = cb(
+            output_section_id::NOTE_GNU_PROPERTY,
+            output_section_id::NOTE_GNU_PROPERTY.min_alignment(),
+            &self.note_gnu_property,
+        );
+        let note_abi_tag = cb(
+            output_section_id::NOTE_ABI_TAG,
+            output_section_id::NOTE_ABI_TAG.min_alignment(),
+            &self.note_abi_tag,
+        );
+        let hash = cb(
+            output_section_id::HASH,
+            output_section_id::HASH.min_alignment(),
+            &self.hash,
+        );
         let gnu_hash = cb(
             output_section_id::GNU_HASH,
             output_section_id::GNU_HASH.min_alignment(),
@@ -128,6 +165,21 @@ impl<T: Default + PartialEq> OutputSectionPartMap<T> {
             output_section_id::DYNSTR.min_alignment(),
             &self.dynstr,
         );
+        let gnu_version = cb(
+            output_section_id::GNU_VERSION,
+            output_section_id::GNU_VERSION.min_alignment(),
+            &self.gnu_version,
+        );
+        let gnu_version_d = cb(
+            output_section_id::GNU_VERSION_D,
+            output_section_id::GNU_VERSION_D.min_alignment(),
+            &self.gnu_version_d,
+        );
+        let gnu_version_r = cb(
+            output_section_id::GNU_VERSION_R,
+            output_section_id::GNU_VERSION_R.min_alignment(),
+            &self.gnu_version_r,
+        );
         let rela_dyn_relative = cb(
             output_section_id::RELA_DYN,
             output_section_id::RELA_DYN.min_alignment(),
@@ -138,6 +190,11 @@ impl<T: Default + PartialEq> OutputSectionPartMap<T> {
             output_section_id::RELA_DYN.min_alignment(),
             &self.rela_dyn_glob_dat,
         );
+        let relr_dyn = cb(
+            output_section_id::RELR_DYN,
+            output_section_id::RELR_DYN.min_alignment(),
+            &self.relr_dyn,
+        );
         self.map_regular(output_section_id::RODATA, &mut cb, &mut regular);
         let eh_frame_hdr = cb(
             output_section_id::EH_FRAME_HDR,
@@ -160,6 +217,16 @@ impl<T: Default + PartialEq> OutputSectionPartMap<T> {
             output_section_id::SYMTAB.min_alignment(),
             &self.symtab_globals,
         );
+        let symtab_shndx_locals = cb(
+            output_section_id::SYMTAB_SHNDX,
+            output_section_id::SYMTAB_SHNDX.min_alignment(),
+            &self.symtab_shndx_locals,
+        );
+        let symtab_shndx_globals = cb(
+            output_section_id::SYMTAB_SHNDX,
+            output_section_id::SYMTAB_SHNDX.min_alignment(),
+            &self.symtab_shndx_globals,
+        );
         let symtab_strings = cb(
             output_section_id::STRTAB,
             output_section_id::STRTAB.min_alignment(),
@@ -180,6 +247,14 @@ impl<T: Default + PartialEq> OutputSectionPartMap<T> {
         output_sections.exec_custom.iter().for_each(|id| {
             self.map_regular(*id, &mut cb, &mut regular);
         });
+        self.map_regular(output_section_id::INIT_ARRAY, &mut cb, &mut regular);
+        self.map_regular(output_section_id::FINI_ARRAY, &mut cb, &mut regular);
+        self.map_regular(output_section_id::DATA_REL_RO, &mut cb, &mut regular);
+        let dynamic = cb(
+            output_section_id::DYNAMIC,
+            output_section_id::DYNAMIC.min_alignment(),
+            &self.dynamic,
+        );
         let got = cb(
             output_section_id::GOT,
             output_section_id::GOT.min_alignment(),
@@ -190,29 +265,38 @@ impl<T: Default + PartialEq> OutputSectionPartMap<T> {
             output_section_id::RELA_PLT.min_alignment(),
             &self.rela_plt,
         );
-        self.map_regular(output_section_id::INIT_ARRAY, &mut cb, &mut regular);
-        self.map_regular(output_section_id::FINI_ARRAY, &mut cb, &mut regular);
         self.map_regular(output_section_id::DATA, &mut cb, &mut regular);
         let eh_frame = cb(
             output_section_id::EH_FRAME,
             output_section_id::EH_FRAME.min_alignment(),
             &self.eh_frame,
         );
-        let dynamic = cb(
-            output_section_id::DYNAMIC,
-            output_section_id::DYNAMIC.min_alignment(),
-            &self.dynamic,
-        );
         output_sections.data_custom.iter().for_each(|id| {
             self.map_regular(*id, &mut cb, &mut regular);
         });
         self.map_regular(output_section_id::TDATA, &mut cb, &mut regular);
         self.map_regular(output_section_id::TBSS, &mut cb, &mut regular);
-        self.map_regular(output_section_id::BSS, &mut cb, &mut regular);
+        self.map_regular_in_order(
+            output_section_id::BSS,
+            output_sections.bss_ascending,
+            &mut cb,
+            &mut regular,
+        );
         output_sections.bss_custom.iter().for_each(|id| {
             self.map_regular(*id, &mut cb, &mut regular);
         });
         self.map_regular(output_section_id::COMMENT, &mut cb, &mut regular);
+        output_sections.non_alloc_custom.iter().for_each(|id| {
+            self.map_regular(*id, &mut cb, &mut regular);
+        });
+        let gdb_index = cb(
+            output_section_id::GDB_INDEX,
+            output_section_id::GDB_INDEX.min_alignment(),
+            &self.gdb_index,
+        );
+        output_sections.relocation_sections.iter().for_each(|id| {
+            self.map_regular(*id, &mut cb, &mut regular);
+        });
 
         OutputSectionPartMap {
             regular,
@@ -224,6 +308,8 @@ impl<T: Default + PartialEq> OutputSectionPartMap<T> {
             symtab_locals,
             symtab_globals,
             symtab_strings,
+            symtab_shndx_locals,
+            symtab_shndx_globals,
             shstrtab,
             rela_plt,
             eh_frame,
@@ -234,7 +320,15 @@ impl<T: Default + PartialEq> OutputSectionPartMap<T> {
             dynstr,
             rela_dyn_relative,
             rela_dyn_glob_dat,
+            relr_dyn,
+            gnu_version,
+            gnu_version_d,
+            gnu_version_r,
             interp,
+            note_gnu_property,
+            note_abi_tag,
+            gdb_index,
+            hash,
         }
     }
 
@@ -243,10 +337,22 @@ impl<T: Default + PartialEq> OutputSectionPartMap<T> {
         id: OutputSectionId,
         cb: &mut impl FnMut(OutputSectionId, Alignment, &T) -> U,
         out: &mut [AlignmentMap<U>],
+    ) {
+        self.map_regular_in_order(id, false, cb, out);
+    }
+
+    /// Like `map_regular`, but if `ascending` is set, visits the parts of the section in ascending
+    /// order of alignment.
+    fn map_regular_in_order<U: Default>(
+        &self,
+        id: OutputSectionId,
+        ascending: bool,
+        cb: &mut impl FnMut(OutputSectionId, Alignment, &T) -> U,
+        out: &mut [AlignmentMap<U>],
     ) {
         let offset = id.as_usize() - NUM_GENERATED_SECTIONS;
         let alignment_map = &self.regular[offset];
-        out[offset] = map_alignment_map(alignment_map, cb, id);
+        out[offset] = map_alignment_map(alignment_map, cb, id, ascending);
     }
 
     pub(crate) fn regular_mut(
@@ -267,6 +373,19 @@ impl<T: Default + PartialEq> OutputSectionPartMap<T> {
         &self.regular[output_section_id.as_usize() - NUM_GENERATED_SECTIONS][alignment]
     }
 
+    /// Returns the alignment of the most-aligned non-empty part of the specified regular section.
+    pub(crate) fn max_alignment(&self, output_section_id: OutputSectionId) -> Alignment
+    where
+        T: Default + PartialEq,
+    {
+        self.regular[output_section_id.as_usize() - NUM_GENERATED_SECTIONS]
+            .iter()
+            .rev()
+            .find(|(_, value)| *value != &T::default())
+            .map(|(alignment, _)| alignment)
+            .unwrap_or_default()
+    }
+
     /// Zip mutable references to values in `self` with shared references from `other` producing a
     /// new map with the returned values. For custom sections, `other` must be a subset of `self`.
     /// Values not in `other` will not be in the returned map.
@@ -296,6 +415,8 @@ impl<T: Default + PartialEq> OutputSectionPartMap<T> {
             symtab_locals: cb(&mut self.symtab_locals, &other.symtab_locals),
             symtab_globals: cb(&mut self.symtab_globals, &other.symtab_globals),
             symtab_strings: cb(&mut self.symtab_strings, &other.symtab_strings),
+            symtab_shndx_locals: cb(&mut self.symtab_shndx_locals, &other.symtab_shndx_locals),
+            symtab_shndx_globals: cb(&mut self.symtab_shndx_globals, &other.symtab_shndx_globals),
             shstrtab: cb(&mut self.shstrtab, &other.shstrtab),
             rela_plt: cb(&mut self.rela_plt, &other.rela_plt),
             eh_frame: cb(&mut self.eh_frame, &other.eh_frame),
@@ -306,7 +427,15 @@ impl<T: Default + PartialEq> OutputSectionPartMap<T> {
             dynstr: cb(&mut self.dynstr, &other.dynstr),
             rela_dyn_relative: cb(&mut self.rela_dyn_relative, &other.rela_dyn_relative),
             rela_dyn_glob_dat: cb(&mut self.rela_dyn_glob_dat, &other.rela_dyn_glob_dat),
+            relr_dyn: cb(&mut self.relr_dyn, &other.relr_dyn),
+            gnu_version: cb(&mut self.gnu_version, &other.gnu_version),
+            gnu_version_d: cb(&mut self.gnu_version_d, &other.gnu_version_d),
+            gnu_version_r: cb(&mut self.gnu_version_r, &other.gnu_version_r),
             interp: cb(&mut self.interp, &other.interp),
+            note_gnu_property: cb(&mut self.note_gnu_property, &other.note_gnu_property),
+            note_abi_tag: cb(&mut self.note_abi_tag, &other.note_abi_tag),
+            gdb_index: cb(&mut self.gdb_index, &other.gdb_index),
+            hash: cb(&mut self.hash, &other.hash),
         }
     }
 }
@@ -318,10 +447,14 @@ impl<T: Default> OutputSectionPartMap<T> {
     }
 }
 
+/// Calls `cb` for each part of a section in output order, which is normally descending alignment.
+/// If `ascending` is set, the smallest alignment comes first, but still starts at an address with
+/// the section's maximum alignment.
 fn map_alignment_map<T: Default + PartialEq, U: Default>(
     alignment_map: &AlignmentMap<T>,
     cb: &mut impl FnMut(OutputSectionId, Alignment, &T) -> U,
     output_section_id: OutputSectionId,
+    ascending: bool,
 ) -> AlignmentMap<U> {
     // The maximum alignment is the alignment of the first non-default bucket when iterating the
     // alignment buckets in reverse order. We cap alignment to at most this value.
@@ -331,20 +464,21 @@ fn map_alignment_map<T: Default + PartialEq, U: Default>(
         .find(|(_, value)| *value != &Default::default())
         .map(|(alignment, _)| alignment)
         .unwrap_or_default();
-    alignment_map
-        .iter()
-        .rev()
-        .map(|(alignment, value)| {
-            (
-                alignment,
-                cb(
-                    output_section_id,
-                    max_alignment.min(alignment.max(output_section_id.min_alignment())),
-                    value,
-                ),
-            )
-        })
-        .collect()
+    let mut is_first = true;
+    let mut map_part = |(alignment, value): (Alignment, &T)| {
+        let part_alignment = if is_first && ascending {
+            max_alignment
+        } else {
+            max_alignment.min(alignment.max(output_section_id.min_alignment()))
+        };
+        is_first = false;
+        (alignment, cb(output_section_id, part_alignment, value))
+    };
+    if ascending {
+        alignment_map.iter().map(&mut map_part).collect()
+    } else {
+        alignment_map.iter().rev().map(&mut map_part).collect()
+    }
 }
 
 impl<T: Copy> OutputSectionPartMap<T> {
@@ -381,6 +515,21 @@ impl<T: Copy> OutputSectionPartMap<T> {
             &[self.rela_dyn_relative, self.rela_dyn_glob_dat],
         );
         update(output_section_id::INTERP, &[self.interp]);
+        update(
+            output_section_id::NOTE_GNU_PROPERTY,
+            &[self.note_gnu_property],
+        );
+        update(output_section_id::HASH, &[self.hash]);
+        update(
+            output_section_id::SYMTAB_SHNDX,
+            &[self.symtab_shndx_locals, self.symtab_shndx_globals],
+        );
+        update(output_section_id::NOTE_ABI_TAG, &[self.note_abi_tag]);
+        update(output_section_id::GDB_INDEX, &[self.gdb_index]);
+        update(output_section_id::GNU_VERSION_R, &[self.gnu_version_r]);
+        update(output_section_id::RELR_DYN, &[self.relr_dyn]);
+        update(output_section_id::GNU_VERSION, &[self.gnu_version]);
+        update(output_section_id::GNU_VERSION_D, &[self.gnu_version_d]);
         values_out.extend(self.regular.iter().map(|parts| cb(parts.raw_values())));
         debug_assert!(
             values_out.len() == values_out.capacity(),
@@ -407,6 +556,8 @@ impl<T: AddAssign + Copy + Default> OutputSectionPartMap<T> {
         self.symtab_locals += rhs.symtab_locals;
         self.symtab_globals += rhs.symtab_globals;
         self.symtab_strings += rhs.symtab_strings;
+        self.symtab_shndx_locals += rhs.symtab_shndx_locals;
+        self.symtab_shndx_globals += rhs.symtab_shndx_globals;
         self.shstrtab += rhs.shstrtab;
         self.rela_plt += rhs.rela_plt;
         self.eh_frame += rhs.eh_frame;
@@ -417,7 +568,15 @@ impl<T: AddAssign + Copy + Default> OutputSectionPartMap<T> {
         self.dynstr += rhs.dynstr;
         self.rela_dyn_relative += rhs.rela_dyn_relative;
         self.rela_dyn_glob_dat += rhs.rela_dyn_glob_dat;
+        self.relr_dyn += rhs.relr_dyn;
+        self.gnu_version += rhs.gnu_version;
+        self.gnu_version_d += rhs.gnu_version_d;
+        self.gnu_version_r += rhs.gnu_version_r;
         self.interp += rhs.interp;
+        self.note_gnu_property += rhs.note_gnu_property;
+        self.note_abi_tag += rhs.note_abi_tag;
+        self.gdb_index += rhs.gdb_index;
+        self.hash += rhs.hash;
     }
 }
 
@@ -430,6 +589,12 @@ impl<'out> OutputSectionPartMap<&'out mut [u8]> {
             crate::slice::slice_take_prefix_mut(buffer, *size)
         })
     }
+
+    /// Fills all of our buffers with zeros.
+    pub(crate) fn fill_zero(&mut self) {
+        let parts = OutputSectionPartMap::<()>::with_size(self.len());
+        self.mut_with_map(&parts, |buffer, _| buffer.fill(0));
+    }
 }
 
 #[test]
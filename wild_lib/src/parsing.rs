@@ -1,17 +1,24 @@
 use crate::archive_splitter::InputBytes;
 use crate::args::Args;
+use crate::args::Defsym;
 use crate::args::Modifiers;
+use crate::args::OutputKind;
+use crate::compression::DecompressedSections;
 use crate::elf::File;
+use crate::error::LinkError;
 use crate::error::Result;
 use crate::file_kind::FileKind;
 use crate::input_data::FileId;
 use crate::input_data::InputRef;
 use crate::input_data::INTERNAL_FILE_ID;
+use crate::object_cache::CachedSymbols;
+use crate::object_cache::ObjectCache;
 use crate::output_section_id;
 use crate::output_section_id::OutputSectionId;
 use crate::sharding::ShardKey;
 use crate::symbol::SymbolName;
 use crate::symbol_db::SymbolId;
+use anyhow::bail;
 use anyhow::Context;
 use object::Object as _;
 use object::ObjectSymbol;
@@ -25,11 +32,12 @@ use std::path::Path;
 pub(crate) fn parse_input_files<'data>(
     inputs: &'data [InputBytes],
     args: &'data Args,
+    object_cache: Option<&'data ObjectCache>,
 ) -> Result<Vec<InputObject<'data>>> {
     let mut objects = inputs
         .par_iter()
         .enumerate()
-        .map(|(index, f)| InputObject::new(f, FileId::from_usize(index)?, args))
+        .map(|(index, f)| InputObject::new(f, FileId::from_usize(index)?, args, object_cache))
         .collect::<Result<Vec<InputObject>>>()?;
     objects.push(InputObject::Epilogue(Epilogue {
         file_id: FileId::from_usize(objects.len())?,
@@ -56,13 +64,16 @@ pub(crate) fn parse_input_files<'data>(
 }
 
 pub(crate) enum InputObject<'data> {
-    Internal(InternalInputObject),
+    Internal(InternalInputObject<'data>),
     Object(RegularInputObject<'data>),
     Epilogue(Epilogue),
 }
 
-pub(crate) struct InternalInputObject {
+pub(crate) struct InternalInputObject<'data> {
     pub(crate) symbol_definitions: Vec<InternalSymDefInfo>,
+    /// The name of each symbol in `symbol_definitions`.
+    symbol_names: Vec<SymbolName<'data>>,
+    defsyms: &'data [Defsym],
 }
 
 pub(crate) struct RegularInputObject<'data> {
@@ -72,7 +83,10 @@ pub(crate) struct RegularInputObject<'data> {
     pub(crate) start_symbol_id: SymbolId,
     pub(crate) file_id: FileId,
     pub(crate) is_dynamic: bool,
-    modifiers: Modifiers,
+    pub(crate) modifiers: Modifiers,
+    pub(crate) decompressed_sections: DecompressedSections,
+    /// Our symbols, if we found them in the object cache.
+    pub(crate) cached_symbols: Option<&'data CachedSymbols>,
 }
 
 pub(crate) struct Epilogue {
@@ -91,23 +105,48 @@ pub(crate) enum InternalSymDefInfo {
     /// Defines a symbol that points at the non-inclusive end of the section. i.e. 1 byte past the
     /// last byte of the section.
     SectionEnd(OutputSectionId),
+
+    /// A symbol defined on the command line with `--defsym` or `--defsym-weak`. The value is an
+    /// index into `Args::defsyms`.
+    Defsym(u32),
+
+    /// RISC-V's global pointer, which we define if something references it. It points a little way
+    /// into .data.
+    GlobalPointer,
 }
 
 impl<'data> RegularInputObject<'data> {
-    fn new(input: &'data InputBytes, file_id: FileId, is_dynamic: bool) -> Result<Self> {
-        let object = Box::new(
-            File::parse(input.data)
-                .with_context(|| format!("Failed to parse object file `{input}`"))?,
-        );
+    fn new(
+        input: &'data InputBytes,
+        file_id: FileId,
+        is_dynamic: bool,
+        object_cache: Option<&'data ObjectCache>,
+    ) -> Result<Self> {
+        let object =
+            Box::new(
+                File::parse(input.data).map_err(|error| LinkError::MalformedInput {
+                    file: input.to_string(),
+                    reason: error.to_string(),
+                })?,
+            );
+        let cached_symbols = object_cache.and_then(|cache| cache.get(input.input.file, input.data));
         // Note, this looks bad performance-wise, but it seems like it's actually OK. Initially, I
         // tried using object.section_by_name(".symtab") then getting the size and computing the
         // number of symbols from that. However it turns out that, perhaps not surprisingly that
         // `section_by_name` is really slow.
-        let num_symbols = if is_dynamic {
+        let num_symbols = if let Some(cached) = cached_symbols {
+            cached.num_symbols
+        } else if is_dynamic {
             object.dynamic_symbols().count()
         } else {
             object.symbols().count()
         };
+        let decompressed_sections = if is_dynamic {
+            DecompressedSections::default()
+        } else {
+            DecompressedSections::new(&object)
+                .with_context(|| format!("Failed to read object file `{input}`"))?
+        };
         Ok(Self {
             input: input.input,
             object,
@@ -117,14 +156,17 @@ impl<'data> RegularInputObject<'data> {
             file_id,
             is_dynamic,
             modifiers: input.modifiers,
+            decompressed_sections,
+            cached_symbols,
         })
     }
 
     /// Returns whether this input should be skipped if there are no non-weak reference to symbols
-    /// it defines. This is true for archive entries and shared objects for which --as-needed is
-    /// true.
+    /// it defines. This is true for archive entries, unless --whole-archive is active, and shared
+    /// objects for which --as-needed is true.
     pub(crate) fn is_optional(&self) -> bool {
-        self.input.entry_filename.is_some() || (self.is_dynamic && self.modifiers.as_needed)
+        (self.input.entry_filename.is_some() && !self.modifiers.whole_archive)
+            || (self.is_dynamic && self.modifiers.as_needed)
     }
 
     fn filename(&self) -> &'data Path {
@@ -135,30 +177,47 @@ impl<'data> RegularInputObject<'data> {
         &self,
         symbol_id: crate::symbol_db::SymbolId,
     ) -> Result<SymbolName<'data>> {
-        let symbol = if self.is_dynamic {
-            self.object
+        Ok(SymbolName::new(self.symbol(symbol_id)?.name_bytes()?))
+    }
+
+    /// Returns the symbol with the supplied ID, which must belong to us. For a shared object, this
+    /// is from the dynamic symbol table.
+    pub(crate) fn symbol(
+        &self,
+        symbol_id: crate::symbol_db::SymbolId,
+    ) -> Result<crate::elf::Symbol<'data, '_>> {
+        let index = object::SymbolIndex(symbol_id.offset_from(self.start_symbol_id));
+        if self.is_dynamic {
+            Ok(self
+                .object
                 .dynamic_symbol_table()
                 .context("Missing dynamic symbol table")?
-                .symbol_by_index(object::SymbolIndex(
-                    symbol_id.offset_from(self.start_symbol_id),
-                ))?
+                .symbol_by_index(index)?)
         } else {
-            self.object.symbol_by_index(object::SymbolIndex(
-                symbol_id.offset_from(self.start_symbol_id),
-            ))?
-        };
-        Ok(SymbolName::new(symbol.name_bytes()?))
+            Ok(self.object.symbol_by_index(index)?)
+        }
     }
 }
 
 impl<'data> InputObject<'data> {
-    fn new(input: &'data InputBytes, file_id: FileId, args: &'data Args) -> Result<Self> {
+    fn new(
+        input: &'data InputBytes,
+        file_id: FileId,
+        args: &'data Args,
+        object_cache: Option<&'data ObjectCache>,
+    ) -> Result<Self> {
         Ok(match input.kind {
-            FileKind::ElfObject | FileKind::Archive => {
-                Self::Object(RegularInputObject::new(input, file_id, false)?)
-            }
+            FileKind::ElfObject | FileKind::Archive | FileKind::ThinArchive => Self::Object(
+                RegularInputObject::new(input, file_id, false, object_cache)?,
+            ),
             FileKind::Internal => Self::Internal(InternalInputObject::new(file_id, args)?),
-            FileKind::ElfDynamic => Self::Object(RegularInputObject::new(input, file_id, true)?),
+            FileKind::ElfDynamic => {
+                Self::Object(RegularInputObject::new(input, file_id, true, object_cache)?)
+            }
+            FileKind::LlvmIr => bail!(
+                "`{input}` is LLVM bitcode, which can only be linked using a linker plugin \
+                 (-plugin)"
+            ),
             FileKind::Text => unreachable!("Should have been handled earlier"),
         })
     }
@@ -193,41 +252,51 @@ impl<'data> InputObject<'data> {
     }
 }
 
-impl InternalInputObject {
-    fn new(file_id: FileId, args: &Args) -> Result<Self> {
+impl<'data> InternalInputObject<'data> {
+    fn new(file_id: FileId, args: &'data Args) -> Result<Self> {
         assert_eq!(file_id, INTERNAL_FILE_ID);
         // The undefined symbol must always be symbol 0.
         let mut symbol_definitions = vec![InternalSymDefInfo::Undefined];
-        for section_id in output_section_id::built_in_section_ids() {
-            // If we're not producing a relocatable output, then don't define any symbols for the
+        let mut symbol_names = vec![SymbolName::new(&[])];
+        // When producing a relocatable output, symbols like `_GLOBAL_OFFSET_TABLE_` and
+        // `__bss_start` need to stay undefined so that the final link can define them.
+        let section_ids = output_section_id::built_in_section_ids()
+            .filter(|_| args.output_kind != OutputKind::Relocatable);
+        for section_id in section_ids {
+            // If we're not producing a dynamic output, then don't define any symbols for the
             // .dynamic section.
-            if section_id == output_section_id::DYNAMIC && !args.is_relocatable() {
+            if section_id == output_section_id::DYNAMIC && !args.needs_dynamic() {
                 continue;
             }
             let def = section_id.built_in_details();
-            if def.start_symbol_name.is_some() {
+            for name in def.start_symbol_names {
                 symbol_definitions.push(InternalSymDefInfo::SectionStart(section_id));
+                symbol_names.push(SymbolName::new(name.as_bytes()));
             }
-            if def.end_symbol_name.is_some() {
+            for name in def.end_symbol_names {
                 symbol_definitions.push(InternalSymDefInfo::SectionEnd(section_id));
+                symbol_names.push(SymbolName::new(name.as_bytes()));
             }
         }
-        Ok(Self { symbol_definitions })
+        for (index, defsym) in args.defsyms.iter().enumerate() {
+            symbol_definitions.push(InternalSymDefInfo::Defsym(
+                u32::try_from(index).context("Too many defsyms")?,
+            ));
+            symbol_names.push(SymbolName::new(defsym.name.as_bytes()));
+        }
+        Ok(Self {
+            symbol_definitions,
+            symbol_names,
+            defsyms: &args.defsyms,
+        })
     }
 
-    pub(crate) fn symbol_name(&self, symbol_id: SymbolId) -> SymbolName<'static> {
-        let def = &self.symbol_definitions[symbol_id.offset_from(SymbolId::undefined())];
-        let name = match def {
-            InternalSymDefInfo::Undefined => Some(""),
-            InternalSymDefInfo::SectionStart(section_id) => {
-                section_id.built_in_details().start_symbol_name
-            }
-            InternalSymDefInfo::SectionEnd(section_id) => {
-                section_id.built_in_details().end_symbol_name
-            }
-        }
-        .unwrap();
-        SymbolName::new(name.as_bytes())
+    pub(crate) fn symbol_name(&self, symbol_id: SymbolId) -> SymbolName<'data> {
+        self.symbol_names[symbol_id.offset_from(SymbolId::undefined())]
+    }
+
+    pub(crate) fn defsym(&self, index: u32) -> &'data Defsym {
+        &self.defsyms[index as usize]
     }
 }
 
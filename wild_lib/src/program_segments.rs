@@ -13,6 +13,10 @@ pub(crate) const LOAD_RW: ProgramSegmentId = ProgramSegmentId(4);
 pub(crate) const TLS: ProgramSegmentId = ProgramSegmentId(5);
 pub(crate) const EH_FRAME: ProgramSegmentId = ProgramSegmentId(6);
 pub(crate) const DYNAMIC: ProgramSegmentId = ProgramSegmentId(7);
+pub(crate) const NOTE: ProgramSegmentId = ProgramSegmentId(8);
+pub(crate) const GNU_PROPERTY: ProgramSegmentId = ProgramSegmentId(9);
+pub(crate) const RELRO: ProgramSegmentId = ProgramSegmentId(10);
+pub(crate) const GNU_STACK: ProgramSegmentId = ProgramSegmentId(11);
 
 pub(crate) struct ProgramSegmentDef {
     pub(crate) segment_type: SegmentType,
@@ -56,6 +60,22 @@ const PROGRAM_SEGMENT_DEFS: &[ProgramSegmentDef] = &[
         segment_type: SegmentType::Dynamic,
         segment_flags: PF_R | PF_W,
     },
+    ProgramSegmentDef {
+        segment_type: SegmentType::Note,
+        segment_flags: PF_R,
+    },
+    ProgramSegmentDef {
+        segment_type: SegmentType::GnuProperty,
+        segment_flags: PF_R,
+    },
+    ProgramSegmentDef {
+        segment_type: SegmentType::GnuRelro,
+        segment_flags: PF_R,
+    },
+    ProgramSegmentDef {
+        segment_type: SegmentType::GnuStack,
+        segment_flags: PF_R | PF_W,
+    },
 ];
 
 impl ProgramSegmentId {
@@ -79,9 +99,12 @@ impl ProgramSegmentId {
         )
     }
 
-    pub(crate) fn alignment(&self) -> crate::alignment::Alignment {
+    pub(crate) fn alignment(
+        &self,
+        page_size: crate::alignment::Alignment,
+    ) -> crate::alignment::Alignment {
         if self.segment_type() == SegmentType::Load {
-            crate::alignment::PAGE
+            page_size
         } else {
             crate::alignment::MIN
         }
@@ -145,4 +168,21 @@ fn test_constant_segment_ids() {
         PROGRAM_SEGMENT_DEFS[INTERP.as_usize()].segment_type,
         SegmentType::Interp
     );
+    assert_eq!(
+        PROGRAM_SEGMENT_DEFS[NOTE.as_usize()].segment_type,
+        SegmentType::Note
+    );
+    assert_eq!(
+        PROGRAM_SEGMENT_DEFS[GNU_PROPERTY.as_usize()].segment_type,
+        SegmentType::GnuProperty
+    );
+    assert_eq!(
+        PROGRAM_SEGMENT_DEFS[RELRO.as_usize()].segment_type,
+        SegmentType::GnuRelro
+    );
+    assert_eq!(PROGRAM_SEGMENT_DEFS[RELRO.as_usize()].segment_flags, PF_R);
+    assert_eq!(
+        PROGRAM_SEGMENT_DEFS[GNU_STACK.as_usize()].segment_type,
+        SegmentType::GnuStack
+    );
 }
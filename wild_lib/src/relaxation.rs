@@ -5,7 +5,9 @@
 //!
 //! For now, we only apply those relaxations that we find we need.
 
+use crate::args::Emulation;
 use crate::args::OutputKind;
+use crate::elf::RelocationKindInfo;
 use crate::resolution::ValueKind;
 
 #[derive(Debug)]
@@ -29,6 +31,10 @@ pub(crate) enum Relaxation {
 
     /// Transform LD (local dynamic) into LE (local exec).
     TlsLdToLocalExec,
+
+    /// Replace the 4-byte instruction being relocated. Used on AArch64, where all instructions are
+    /// the same size, so relaxations only ever need to swap one instruction for another.
+    ReplaceInstruction(u32),
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -39,8 +45,39 @@ pub(crate) enum RelocationModifier {
 
 impl Relaxation {
     /// Tries to create a relaxation for the relocation of the specified kind, to be applied at the
-    /// specified offset in the supplied section.
+    /// specified offset in the supplied section. Returns the relaxation together with how the
+    /// relocation should then be applied.
     pub(crate) fn new(
+        emulation: Emulation,
+        relocation_kind: u32,
+        section_bytes: &[u8],
+        offset_in_section: u64,
+        value_kind: ValueKind,
+        output_kind: OutputKind,
+    ) -> Option<(Self, RelocationKindInfo)> {
+        match emulation {
+            Emulation::X86_64 => {
+                let (relaxation, r_type) = Self::new_x86_64(
+                    relocation_kind,
+                    section_bytes,
+                    offset_in_section,
+                    value_kind,
+                    output_kind,
+                )?;
+                let rel_info = RelocationKindInfo::from_raw(emulation, r_type)
+                    .expect("x86-64 relaxations only produce supported relocation types");
+                Some((relaxation, rel_info))
+            }
+            Emulation::AArch64 => {
+                crate::aarch64::relaxation(relocation_kind, value_kind, output_kind)
+            }
+            Emulation::RiscV64 => {
+                crate::riscv64::relaxation(relocation_kind, value_kind, output_kind)
+            }
+        }
+    }
+
+    fn new_x86_64(
         relocation_kind: u32,
         section_bytes: &[u8],
         offset_in_section: u64,
@@ -90,7 +127,7 @@ impl Relaxation {
                 match section_bytes[offset - 3..offset - 1] {
                     [0x48, 0x8b] => (
                         Relaxation::MovIndirectToAbsolute,
-                        object::elf::R_X86_64_DTPOFF32,
+                        object::elf::R_X86_64_TPOFF32,
                     ),
                     _ => return None,
                 }
@@ -110,6 +147,11 @@ impl Relaxation {
                 }
                 (Relaxation::TlsLdToLocalExec, object::elf::R_X86_64_NONE)
             }
+            object::elf::R_X86_64_DTPOFF32 if output_kind == OutputKind::StaticExecutable => {
+                // Once TLSLD has been relaxed, the base that these offsets get added to is the
+                // thread pointer rather than the start of our TLS block.
+                (Relaxation::NoOp, object::elf::R_X86_64_TPOFF32)
+            }
 
             _ => return None,
         };
@@ -145,6 +187,9 @@ impl Relaxation {
                 section_bytes[offset - 4..offset + 8]
                     .copy_from_slice(&[0x64, 0x48, 0x8b, 0x04, 0x25, 0, 0, 0, 0, 0x48, 0x8d, 0x80]);
                 *offset_in_section += 8;
+                // The addend of the original relocation accounts for it being PC-relative, which
+                // doesn't apply to the TPOFF32 relocation that replaces it.
+                *addend = 0;
                 *next_modifier = RelocationModifier::SkipNextRelocation;
             }
             Relaxation::TlsLdToLocalExec => {
@@ -154,6 +199,9 @@ impl Relaxation {
                 *offset_in_section += 5;
                 *next_modifier = RelocationModifier::SkipNextRelocation;
             }
+            Relaxation::ReplaceInstruction(instruction) => {
+                section_bytes[offset..offset + 4].copy_from_slice(&instruction.to_le_bytes());
+            }
             Relaxation::NoOp => {}
         }
     }
@@ -167,6 +215,7 @@ fn test_relaxation() {
         let mut offset = bytes_in.len() as u64;
         let mut modifier = RelocationModifier::Normal;
         if let Some((r, _)) = Relaxation::new(
+            Emulation::X86_64,
             relocation_kind,
             bytes_in,
             offset,
@@ -181,6 +230,7 @@ fn test_relaxation() {
             );
         }
         if let Some((r, _)) = Relaxation::new(
+            Emulation::X86_64,
             relocation_kind,
             bytes_in,
             offset,
@@ -0,0 +1,319 @@
+//! Support for `--repro=out.tar`, which bundles everything needed to reproduce a link into a single
+//! tar file that can be attached to bug reports. Each input is stored under a directory named after
+//! the tar file at a path that mirrors its absolute path on this machine. This means that relative
+//! references between inputs, e.g. from a thin archive to its members or from a linker script to
+//! the libraries that it names, continue to work. `response.txt` contains our arguments with paths
+//! rewritten to be relative to that directory, one per line and quoted where necessary, so the link
+//! can be reproduced by extracting the tar, changing into the directory and running
+//! `xargs wild < response.txt`.
+
+use crate::error::Result;
+use anyhow::bail;
+use anyhow::Context;
+use std::collections::HashSet;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+const RESPONSE_FILE: &str = "response.txt";
+
+const BLOCK_SIZE: usize = 512;
+
+/// Options whose value is the path of a file that we read.
+const FILE_OPTIONS: &[&str] = &[
+    "-T",
+    "--script",
+    "--version-script",
+    "--dynamic-list",
+    "--retain-symbols-file",
+];
+
+#[derive(Default)]
+pub(crate) struct Repro {
+    /// The absolute paths of the files that we need to store, in the order in which we found them.
+    files: Vec<PathBuf>,
+    seen: HashSet<PathBuf>,
+}
+
+impl Repro {
+    /// Records that `path` was used as an input.
+    pub(crate) fn add_file(&mut self, path: &Path) -> Result {
+        let path = mirrored_path(path)?;
+        if self.seen.insert(path.clone()) {
+            self.files.push(path);
+        }
+        Ok(())
+    }
+
+    /// Writes the tar file to `tar_path`. `args` are the arguments that we were invoked with, not
+    /// including the program name.
+    pub(crate) fn write(&mut self, tar_path: &Path, args: impl Iterator<Item = String>) -> Result {
+        let response = self.response_file(args)?;
+        let root = tar_path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .context("--repro path must have a UTF-8 file name")?;
+        let file = std::fs::File::create(tar_path)
+            .with_context(|| format!("Failed to create `{}`", tar_path.display()))?;
+        let mut tar = TarWriter {
+            out: BufWriter::new(file),
+        };
+        tar.add_entry(&format!("{root}/{RESPONSE_FILE}"), response.as_bytes())?;
+        for path in &self.files {
+            let name = format!("{root}/{}", relative_path(path)?);
+            let data = std::fs::read(path)
+                .with_context(|| format!("Failed to read `{}`", path.display()))?;
+            tar.add_entry(&name, &data)?;
+        }
+        tar.finish()
+            .with_context(|| format!("Failed to write `{}`", tar_path.display()))
+    }
+
+    /// Returns the contents of our response file, recording any files referenced by `args` that we
+    /// haven't already seen.
+    fn response_file(&mut self, mut args: impl Iterator<Item = String>) -> Result<String> {
+        let mut out = String::new();
+        while let Some(arg) = args.next() {
+            let rewritten = if arg == "--repro" {
+                args.next();
+                continue;
+            } else if arg.starts_with("--repro=") {
+                continue;
+            } else if arg == "-o" {
+                // Write the output into the extracted directory rather than where it originally
+                // went.
+                push_arg(&mut out, &arg);
+                let output = args.next().context("Missing argument to -o")?;
+                file_name(&output)?
+            } else if arg == "-L" {
+                push_arg(&mut out, &arg);
+                let dir = args.next().context("Missing argument to -L")?;
+                relative_path(&mirrored_path(Path::new(&dir))?)?
+            } else if let Some(dir) = arg.strip_prefix("-L") {
+                format!("-L{}", relative_path(&mirrored_path(Path::new(dir))?)?)
+            } else if FILE_OPTIONS.contains(&arg.as_str()) {
+                push_arg(&mut out, &arg);
+                let path = args
+                    .next()
+                    .with_context(|| format!("Missing argument to {arg}"))?;
+                self.rewrite_file(&path)?
+            } else if let Some((option, path)) = arg
+                .split_once('=')
+                .filter(|(option, _)| FILE_OPTIONS.contains(option))
+            {
+                format!("{option}={}", self.rewrite_file(path)?)
+            } else if let Some(path) = self.recorded_input(&arg)? {
+                relative_path(&path)?
+            } else {
+                arg
+            };
+            push_arg(&mut out, &rewritten);
+        }
+        Ok(out)
+    }
+
+    /// Returns the stored path if `arg` is a file that was recorded as an input. Arguments that
+    /// aren't options are usually inputs, but could also be the value of an option like `-z`.
+    fn recorded_input(&self, arg: &str) -> Result<Option<PathBuf>> {
+        if arg.starts_with('-') {
+            return Ok(None);
+        }
+        let path = mirrored_path(Path::new(arg))?;
+        Ok(self.seen.contains(&path).then_some(path))
+    }
+
+    fn rewrite_file(&mut self, path: &str) -> Result<String> {
+        self.add_file(Path::new(path))?;
+        relative_path(&mirrored_path(Path::new(path))?)
+    }
+}
+
+/// Appends `arg` as a line of a response file, quoting it if necessary.
+fn push_arg(out: &mut String, arg: &str) {
+    if arg.is_empty()
+        || arg
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '\'' | '"' | '\\'))
+    {
+        out.push('\'');
+        out.push_str(&arg.replace('\'', "'\\''"));
+        out.push('\'');
+    } else {
+        out.push_str(arg);
+    }
+    out.push('\n');
+}
+
+/// Returns the absolute path under which we store `path`. We resolve symlinks in the directory, but
+/// not in the file name, since libraries are commonly found via a symlink with a different name to
+/// the file that it points to.
+fn mirrored_path(path: &Path) -> Result<PathBuf> {
+    let absolute = std::path::absolute(path)
+        .with_context(|| format!("Failed to get absolute path of `{}`", path.display()))?;
+    let (Some(parent), Some(file_name)) = (absolute.parent(), absolute.file_name()) else {
+        return Ok(std::fs::canonicalize(&absolute).unwrap_or(absolute));
+    };
+    Ok(std::fs::canonicalize(parent)
+        .unwrap_or_else(|_| parent.to_owned())
+        .join(file_name))
+}
+
+/// Returns `path`, which must be absolute, as a path relative to the root directory of the tar.
+fn relative_path(path: &Path) -> Result<String> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::RootDir => {}
+            Component::Normal(part) => parts.push(
+                part.to_str()
+                    .with_context(|| format!("Path `{}` isn't valid UTF-8", path.display()))?,
+            ),
+            _ => bail!("Unsupported path `{}` for --repro", path.display()),
+        }
+    }
+    Ok(parts.join("/"))
+}
+
+fn file_name(path: &str) -> Result<String> {
+    Ok(Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("Invalid output path `{path}`"))?
+        .to_owned())
+}
+
+/// Writes a tar file in the POSIX ustar format. Names that don't fit in a ustar header are stored
+/// in a preceding PAX extended header.
+struct TarWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> TarWriter<W> {
+    fn add_entry(&mut self, name: &str, data: &[u8]) -> Result {
+        let (prefix, short_name) = match split_name(name) {
+            Some(split) => split,
+            None => {
+                let record = pax_record("path", name);
+                self.write_header("", "././@PaxHeader", record.len() as u64, b'x')?;
+                self.write_data(record.as_bytes())?;
+                ("", truncate(name, 100))
+            }
+        };
+        self.write_header(prefix, short_name, data.len() as u64, b'0')?;
+        self.write_data(data)
+    }
+
+    fn write_header(&mut self, prefix: &str, name: &str, size: u64, type_flag: u8) -> Result {
+        if size >= 1 << 33 {
+            bail!("`{name}` is too large for --repro");
+        }
+        let mut header = [0u8; BLOCK_SIZE];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        write_octal(&mut header[100..108], 0o644);
+        write_octal(&mut header[108..116], 0);
+        write_octal(&mut header[116..124], 0);
+        write_octal(&mut header[124..136], size);
+        write_octal(&mut header[136..148], 0);
+        header[156] = type_flag;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+        // The checksum is computed with the checksum field itself filled with spaces.
+        header[148..156].fill(b' ');
+        let checksum = header.iter().map(|b| u32::from(*b)).sum::<u32>();
+        write_octal(&mut header[148..155], u64::from(checksum));
+        self.out.write_all(&header)?;
+        Ok(())
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result {
+        self.out.write_all(data)?;
+        let padding = data.len().next_multiple_of(BLOCK_SIZE) - data.len();
+        self.out.write_all(&[0; BLOCK_SIZE][..padding])?;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result {
+        // An archive ends with two zero-filled blocks.
+        self.out.write_all(&[0; BLOCK_SIZE * 2])?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes `value` as a zero-padded, nul-terminated octal number filling `out`.
+fn write_octal(out: &mut [u8], value: u64) {
+    let digits = out.len() - 1;
+    out[..digits].copy_from_slice(format!("{value:0digits$o}").as_bytes());
+    out[digits] = 0;
+}
+
+/// Splits `name` into the prefix and name fields of a ustar header, if it fits.
+fn split_name(name: &str) -> Option<(&str, &str)> {
+    if name.len() <= 100 {
+        return Some(("", name));
+    }
+    name.match_indices('/')
+        .map(|(index, _)| (&name[..index], &name[index + 1..]))
+        .find(|(prefix, rest)| prefix.len() <= 155 && rest.len() <= 100 && !rest.is_empty())
+}
+
+/// Returns a PAX extended header record. The record starts with its own length in decimal,
+/// including the digits of the length itself.
+fn pax_record(key: &str, value: &str) -> String {
+    let content_len = key.len() + value.len() + 3;
+    let mut len = content_len + content_len.to_string().len();
+    if len.to_string().len() != content_len.to_string().len() {
+        len += 1;
+    }
+    format!("{len} {key}={value}\n")
+}
+
+fn truncate(name: &str, max_len: usize) -> &str {
+    let mut end = max_len.min(name.len());
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    &name[..end]
+}
+
+#[test]
+fn test_tar_header() {
+    let mut out = Vec::new();
+    let mut tar = TarWriter { out: &mut out };
+    tar.add_entry("repro/response.txt", b"hello\n").unwrap();
+    let long_name = format!("repro/{}", "x".repeat(300));
+    tar.add_entry(&long_name, b"").unwrap();
+    tar.finish().unwrap();
+
+    assert_eq!(&out[..18], b"repro/response.txt");
+    assert_eq!(&out[124..136], b"00000000006\0");
+    assert_eq!(&out[257..263], b"ustar\0");
+    let checksum = out[..BLOCK_SIZE]
+        .iter()
+        .enumerate()
+        .map(|(i, b)| u32::from(if (148..156).contains(&i) { b' ' } else { *b }))
+        .sum::<u32>();
+    assert_eq!(&out[148..156], format!("{checksum:06o}\0 ").as_bytes());
+    assert_eq!(&out[BLOCK_SIZE..BLOCK_SIZE + 6], b"hello\n");
+
+    // The long name doesn't fit in the header, so is in a PAX header.
+    let pax = &out[BLOCK_SIZE * 2..];
+    assert_eq!(pax[156], b'x');
+    let record = pax_record("path", &long_name);
+    assert_eq!(
+        &pax[BLOCK_SIZE..BLOCK_SIZE + record.len()],
+        record.as_bytes()
+    );
+    assert_eq!(out.len(), BLOCK_SIZE * 7);
+}
+
+#[test]
+fn test_pax_record() {
+    assert_eq!(pax_record("path", "a"), "9 path=a\n");
+    let record = pax_record("path", &"x".repeat(92));
+    assert_eq!(record.len(), 102);
+    assert!(record.starts_with("102 "));
+}
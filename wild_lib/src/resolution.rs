@@ -2,13 +2,23 @@
 //! entries are needed. We also resolve which output section, if any, each input section should be
 //! assigned to.
 
+use crate::alignment;
+use crate::alignment::Alignment;
 use crate::args::Args;
+use crate::args::Emulation;
+use crate::args::Modifiers;
+use crate::args::OrphanHandling;
+use crate::args::OutputKind;
+use crate::compression::DecompressedSections;
 use crate::debug_assert_bail;
+use crate::diagnostics::Diagnostics;
 use crate::elf::File;
 use crate::error::Error;
+use crate::error::LinkError;
 use crate::error::Result;
 use crate::hash::PassThroughHashMap;
 use crate::hash::PreHashed;
+use crate::icf::FoldedSection;
 use crate::input_data::FileId;
 use crate::input_data::InputRef;
 use crate::input_data::INTERNAL_FILE_ID;
@@ -37,15 +47,22 @@ use crossbeam_utils::atomic::AtomicCell;
 use object::Object;
 use object::ObjectSection;
 use object::ObjectSymbol;
+use object::SectionFlags;
+use rayon::iter::IndexedParallelIterator as _;
+use rayon::iter::IntoParallelRefIterator as _;
+use rayon::iter::IntoParallelRefMutIterator as _;
+use rayon::iter::ParallelIterator as _;
 use std::collections::BTreeMap;
+use std::io::Write as _;
 
 #[tracing::instrument(skip_all, name = "Symbol resolution")]
-pub fn resolve_symbols_and_sections<'data>(
+pub(crate) fn resolve_symbols_and_sections<'data>(
     file_states: &'data [InputObject<'data>],
     symbol_db: &mut SymbolDb<'data>,
+    diagnostics: &Diagnostics,
 ) -> Result<(Vec<ResolvedFile<'data>>, OutputSections<'data>)> {
     let (mut resolved, start_stop_sets, internal) =
-        resolve_symbols_in_files(file_states, symbol_db)?;
+        resolve_symbols_in_files(file_states, symbol_db, diagnostics)?;
 
     let output_sections = assign_section_ids(&resolved, symbol_db.args)?;
 
@@ -55,37 +72,63 @@ pub fn resolve_symbols_and_sections<'data>(
         panic!("Epilogue must be the last input");
     };
 
-    allocate_start_stop_symbol_ids(
-        start_stop_sets,
-        &mut custom,
-        &mut resolved,
-        &output_sections,
-        symbol_db,
-    )?;
+    // With -r, references to `__start_X` and `__stop_X` are left undefined for the final link.
+    if symbol_db.args.output_kind != OutputKind::Relocatable {
+        allocate_start_stop_symbol_ids(
+            start_stop_sets,
+            &mut custom,
+            &mut resolved,
+            &output_sections,
+            symbol_db,
+        )?;
+    }
 
     resolved.push(ResolvedFile::Epilogue(custom));
 
-    resolve_alternative_symbol_definitions(symbol_db, &resolved)?;
+    resolve_alternative_symbol_definitions(symbol_db, &resolved, diagnostics)?;
 
     resolved[INTERNAL_FILE_ID.as_usize()] = ResolvedFile::Internal(ResolvedInternal {
         symbol_definitions: &internal.symbol_definitions,
         merged_strings,
     });
+
+    if symbol_db.args.unresolved_symbols.report_in_shared_libs() {
+        check_shared_object_references(symbol_db, &resolved, diagnostics)?;
+    }
+
+    if !symbol_db.args.trace_symbols.is_empty() {
+        trace_symbols(symbol_db.args, &resolved)?;
+    }
+
     Ok((resolved, output_sections))
 }
 
+/// Resolves symbols, including choosing between alternative definitions, but doesn't go on to
+/// resolve sections. Used when we need to know how symbols resolve before we have all our inputs,
+/// e.g. to tell a linker plugin.
+pub(crate) fn resolve_symbol_definitions<'data>(
+    file_states: &'data [InputObject<'data>],
+    symbol_db: &mut SymbolDb<'data>,
+    diagnostics: &Diagnostics,
+) -> Result<Vec<ResolvedFile<'data>>> {
+    let (resolved, _, _) = resolve_symbols_in_files(file_states, symbol_db, diagnostics)?;
+    resolve_alternative_symbol_definitions(symbol_db, &resolved, diagnostics)?;
+    Ok(resolved)
+}
+
 /// A cell that holds mutable reference to the symbol definitions for one of our input objects. We
 /// unfortunately need to box these mutable slices, otherwise the cell isn't lock-free.
 type DefinitionsCell<'definitions> = AtomicCell<Option<Box<&'definitions mut [SymbolId]>>>;
 
 #[tracing::instrument(skip_all, name = "Resolve symbols")]
-pub(crate) fn resolve_symbols_in_files<'data>(
+fn resolve_symbols_in_files<'data>(
     file_states: &'data [InputObject<'data>],
     symbol_db: &mut SymbolDb<'data>,
+    diagnostics: &Diagnostics,
 ) -> Result<(
     Vec<ResolvedFile<'data>>,
     SegQueue<StartStopSet<'data>>,
-    &'data InternalInputObject,
+    &'data InternalInputObject<'data>,
 )> {
     let mut num_objects = 0;
     let mut objects = Vec::new();
@@ -109,7 +152,7 @@ pub(crate) fn resolve_symbols_in_files<'data>(
             InputObject::Object(s) => {
                 if !s.is_optional() {
                     let definitions = definitions_per_file[s.file_id.as_usize()].take().unwrap();
-                    objects.push((s, definitions));
+                    objects.push((s, *definitions));
                 }
                 num_objects += 1;
                 ResolvedFile::NotLoaded
@@ -121,17 +164,19 @@ pub(crate) fn resolve_symbols_in_files<'data>(
             }),
         })
         .collect();
+    request_undefined_symbols(file_states, symbol_db, &definitions_per_file, &mut objects)?;
     let outputs = Outputs::new(num_objects);
     let resources = ResolutionResources {
         file_states,
         definitions_per_file: &definitions_per_file,
         symbol_db,
         outputs: &outputs,
+        diagnostics,
     };
     rayon::scope(|s| {
         for (obj, definitions) in objects {
             s.spawn(|s| {
-                let r = process_object(obj, *definitions, s, &resources);
+                let r = process_object(obj, definitions, s, &resources);
                 if let Err(e) = r {
                     // We currently only store the first error.
                     let _ = resources.outputs.errors.push(e);
@@ -152,22 +197,73 @@ pub(crate) fn resolve_symbols_in_files<'data>(
     Ok((resolved, outputs.start_stop_sets, internal))
 }
 
+/// Treats each symbol named via `-u` or referred to by a `--defsym` expression as an undefined
+/// reference, adding the files that define them to `objects` if they aren't already there. This is
+/// how `-u` causes archive members to be loaded.
+fn request_undefined_symbols<'data, 'definitions>(
+    file_states: &'data [InputObject<'data>],
+    symbol_db: &SymbolDb<'data>,
+    definitions_per_file: &[DefinitionsCell<'definitions>],
+    objects: &mut Vec<(
+        &'data RegularInputObject<'data>,
+        &'definitions mut [SymbolId],
+    )>,
+) -> Result {
+    let mut request = |symbol_id| {
+        let file_id = symbol_db.file_id_for_symbol(symbol_id);
+        if let InputObject::Object(obj) = &file_states[file_id.as_usize()] {
+            if let Some(definitions) = definitions_per_file[file_id.as_usize()].take() {
+                objects.push((obj, *definitions));
+            }
+        }
+    };
+    for name in &symbol_db.args.undefined {
+        let Some(&symbol_id) = symbol_db
+            .global_names
+            .get(&SymbolName::prehashed(name.as_bytes()))
+        else {
+            if symbol_db.args.no_undefined {
+                return Err(anyhow::Error::new(LinkError::UndefinedSymbol {
+                    name: name.clone(),
+                    note: None,
+                })
+                .context(format!("Symbol `{name}` was specified via -u")));
+            }
+            continue;
+        };
+        request(symbol_id);
+    }
+    // Undefined symbols in `--defsym` expressions are reported when we evaluate them.
+    for name in symbol_db.args.defsym_operands() {
+        if let Some(&symbol_id) = symbol_db
+            .global_names
+            .get(&SymbolName::prehashed(name.as_bytes()))
+        {
+            request(symbol_id);
+        }
+    }
+    Ok(())
+}
+
 struct ResolutionResources<'data, 'definitions, 'outer_scope> {
     file_states: &'data [InputObject<'data>],
     definitions_per_file: &'outer_scope Vec<DefinitionsCell<'definitions>>,
     symbol_db: &'outer_scope SymbolDb<'data>,
     outputs: &'outer_scope Outputs<'data>,
+    diagnostics: &'outer_scope Diagnostics,
 }
 
 /// For each symbol that has multiple definitions, some of which may be weak, some strong, some
 /// "common" symbols and some in archive entries that weren't loaded, resolve which version of the
 /// symbol we're using. The symbol we select will be the first strongly defined symbol in a loaded
 /// object, or if there are no strong definitions, then the first definition in a loaded object. If
-/// a symbol definition is a common symbol, then the largest definition will be used.
+/// a symbol definition is a common symbol, then the largest definition will be used, aligned to the
+/// largest alignment of any of the common definitions.
 #[tracing::instrument(skip_all, name = "Resolve alternative symbol definitions")]
 fn resolve_alternative_symbol_definitions<'data>(
     symbol_db: &mut SymbolDb<'data>,
     resolved: &[ResolvedFile],
+    diagnostics: &Diagnostics,
 ) -> Result {
     // For now, we do this from a single thread since we don't expect a lot of symbols will have
     // multiple definitions. If it turns out that there are cases where it's actually taking
@@ -179,6 +275,36 @@ fn resolve_alternative_symbol_definitions<'data>(
             continue;
         }
         let selected = select_symbol(symbol_db, symbol_id, &alternatives, resolved);
+        if !symbol_db.args.allow_multiple_definition {
+            check_duplicate_definitions(
+                symbol_db,
+                symbol_id,
+                &alternatives,
+                resolved,
+                diagnostics,
+            )?;
+        }
+        if symbol_db.args.warn_common {
+            warn_common(
+                symbol_db,
+                symbol_id,
+                &alternatives,
+                selected,
+                resolved,
+                diagnostics,
+            )?;
+        }
+        if let Some(selected_alignment) = symbol_db.common_alignment(selected, resolved) {
+            let max_alignment = alternatives
+                .iter()
+                .filter_map(|&alt| symbol_db.common_alignment(alt, resolved))
+                .chain(symbol_db.common_alignment(symbol_id, resolved))
+                .max()
+                .unwrap_or(selected_alignment);
+            if max_alignment > selected_alignment {
+                symbol_db.common_alignments.insert(selected, max_alignment);
+            }
+        }
         symbol_db.replace_definition(symbol_id, selected);
         for alt in alternatives {
             symbol_db.replace_definition(alt, selected);
@@ -187,6 +313,180 @@ fn resolve_alternative_symbol_definitions<'data>(
     Ok(())
 }
 
+/// Warns about each common definition of a symbol that lost out to `selected`, either because
+/// `selected` is a regular definition or a larger common definition. If several common definitions
+/// of the same size are merged, we warn about those too.
+fn warn_common(
+    symbol_db: &SymbolDb,
+    symbol_id: SymbolId,
+    alternatives: &[SymbolId],
+    selected: SymbolId,
+    resolved: &[ResolvedFile],
+    diagnostics: &Diagnostics,
+) -> Result {
+    let selected_strength = symbol_db.symbol_strength(selected, resolved);
+    let file_for = |id| &resolved[symbol_db.file_id_for_symbol(id).as_usize()];
+    for &other in std::iter::once(&symbol_id).chain(alternatives) {
+        if other == selected {
+            continue;
+        }
+        let SymbolStrength::Common(size) = symbol_db.symbol_strength(other, resolved) else {
+            continue;
+        };
+        let name = symbol_db.symbol_name(other)?;
+        let (other_file, selected_file) = (file_for(other), file_for(selected));
+        let message = match selected_strength {
+            SymbolStrength::Common(selected_size) if selected_size == size => {
+                format!("multiple common of `{name}` in {other_file} and {selected_file}")
+            }
+            SymbolStrength::Common(_) => format!(
+                "common of `{name}` in {other_file} overridden by larger common in {selected_file}"
+            ),
+            _ => format!(
+                "common of `{name}` in {other_file} overridden by definition in {selected_file}"
+            ),
+        };
+        diagnostics.warn(message);
+    }
+    Ok(())
+}
+
+/// Prints a line for each loaded file that references or defines one of the symbols named via
+/// `-y`. Files are reported in command-line order, so a reference from an object is followed by the
+/// definition in the archive member that it caused us to load.
+fn trace_symbols(args: &Args, resolved: &[ResolvedFile]) -> Result {
+    let mut stdout = std::io::stdout().lock();
+    for file in resolved {
+        let ResolvedFile::Object(obj) = file else {
+            continue;
+        };
+        let symbols = if obj.non_dynamic.is_some() {
+            obj.object.symbols()
+        } else {
+            obj.object.dynamic_symbols()
+        };
+        for symbol in symbols {
+            if !symbol.is_global() {
+                continue;
+            }
+            let name = symbol.name_bytes()?;
+            if !args.trace_symbols.iter().any(|t| t.as_bytes() == name) {
+                continue;
+            }
+            let kind = if symbol.is_undefined() {
+                "reference to"
+            } else if symbol.is_common() {
+                "common definition of"
+            } else {
+                "definition of"
+            };
+            writeln!(
+                stdout,
+                "{}: {kind} {}",
+                obj.input,
+                String::from_utf8_lossy(name)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Reports strong references from the shared objects that we're linking against to symbols that
+/// aren't defined by any loaded input.
+fn check_shared_object_references(
+    symbol_db: &SymbolDb,
+    resolved: &[ResolvedFile],
+    diagnostics: &Diagnostics,
+) -> Result {
+    for file in resolved {
+        let ResolvedFile::Object(obj) = file else {
+            continue;
+        };
+        if obj.non_dynamic.is_some() {
+            continue;
+        }
+        for symbol in obj.object.dynamic_symbols() {
+            if symbol.index().0 == 0 || !symbol.is_undefined() || symbol.is_weak() {
+                continue;
+            }
+            let definition = symbol_db.definition(obj.start_symbol_id.add_usize(symbol.index().0));
+            let is_defined = !definition.is_undefined()
+                && !matches!(
+                    resolved[symbol_db.file_id_for_symbol(definition).as_usize()],
+                    ResolvedFile::NotLoaded
+                );
+            if !is_defined {
+                let name = String::from_utf8_lossy(symbol.name_bytes()?);
+                diagnostics.unresolved_symbol(
+                    symbol_db.args,
+                    LinkError::UndefinedSymbol {
+                        name: name.into_owned(),
+                        note: Some(format!("Referenced by {obj}")),
+                    },
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reports an error if more than one loaded, regular object strongly defines the symbol. Definitions
+/// in sections that belong to a group are skipped, since they're expected to be duplicated and
+/// only one copy of each group ends up being used. Definitions that aren't in a section, such as
+/// absolute symbols, are skipped too, as are `STB_GNU_UNIQUE` definitions, since all copies of a
+/// unique symbol are merged into one.
+fn check_duplicate_definitions(
+    symbol_db: &SymbolDb,
+    symbol_id: SymbolId,
+    alternatives: &[SymbolId],
+    resolved: &[ResolvedFile],
+    diagnostics: &Diagnostics,
+) -> Result {
+    let is_duplicable_strong_definition = |id: SymbolId| -> Result<bool> {
+        let ResolvedFile::Object(obj) = &resolved[symbol_db.file_id_for_symbol(id).as_usize()]
+        else {
+            return Ok(false);
+        };
+        if obj.non_dynamic.is_none()
+            || symbol_db.symbol_strength(id, resolved) != SymbolStrength::Strong
+        {
+            return Ok(false);
+        }
+        let local_index = object::SymbolIndex(id.offset_from(obj.start_symbol_id));
+        let symbol = obj.object.symbol_by_index(local_index)?;
+        if crate::elf::is_unique_symbol(&symbol) {
+            return Ok(false);
+        }
+        let Some(section_index) = symbol.section_index() else {
+            return Ok(false);
+        };
+        let section = obj.object.section_by_index(section_index)?;
+        let object::SectionFlags::Elf { sh_flags } = section.flags() else {
+            unreachable!()
+        };
+        Ok(sh_flags & crate::elf::shf::GROUP == 0)
+    };
+    let mut strong = Vec::new();
+    for &id in std::iter::once(&symbol_id).chain(alternatives) {
+        if is_duplicable_strong_definition(id)? {
+            strong.push(id);
+        }
+    }
+    if let [first, second, ..] = strong[..] {
+        let file_for = |id| resolved[symbol_db.file_id_for_symbol(id).as_usize()].to_string();
+        diagnostics.error(
+            symbol_db.args,
+            LinkError::DuplicateDefinition {
+                name: symbol_db.symbol_name(first)?.to_string(),
+                first_file: file_for(first),
+                second_file: file_for(second),
+            }
+            .into(),
+        )?;
+    }
+    Ok(())
+}
+
 /// Selects which version of the symbol to use.
 fn select_symbol(
     symbol_db: &SymbolDb,
@@ -258,7 +558,11 @@ pub(crate) enum SectionSlot<'data> {
     Unloaded(UnloadedSection<'data>),
     Loaded(crate::layout::Section<'data>),
     EhFrameData(object::SectionIndex),
+    GnuPropertyNote(object::SectionIndex),
+    AbiTagNote(object::SectionIndex),
     MergeStrings(MergeStringsFileSection<'data>),
+    /// A section that was identical to another section, so was folded into it by `--icf`.
+    Folded(FoldedSection),
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -275,9 +579,11 @@ pub(crate) struct ResolvedInternal<'data> {
 pub(crate) struct ResolvedObject<'data> {
     pub(crate) input: InputRef<'data>,
     pub(crate) object: &'data File<'data>,
+    pub(crate) decompressed_sections: &'data DecompressedSections,
     pub(crate) file_id: FileId,
     pub(crate) num_symbols: usize,
     pub(crate) start_symbol_id: SymbolId,
+    pub(crate) modifiers: Modifiers,
 
     pub(crate) non_dynamic: Option<NonDynamicResolved<'data>>,
 }
@@ -291,6 +597,10 @@ pub(crate) struct NonDynamicResolved<'data> {
     /// Details about each custom section that is defined in this object. The index is an index into
     /// self.sections.
     custom_sections: Vec<(object::SectionIndex, SectionDetails<'data>)>,
+
+    /// Sections for which we'll copy relocations into the output when `--emit-relocs` is active,
+    /// together with the name of the output section that'll hold those relocations.
+    relocation_sections: Vec<(TemporaryOutputSectionId<'data>, &'data [u8])>,
 }
 
 pub struct ResolvedEpilogue {
@@ -318,21 +628,33 @@ struct RefToMergeString {
     symbol_index: object::SymbolIndex,
 }
 
+/// A null-terminated string or, for sections without SHF_STRINGS, a fixed-size entry.
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
-struct StringToMerge<'data> {
-    bytes: &'data [u8],
+pub(crate) struct StringToMerge<'data> {
+    pub(crate) bytes: &'data [u8],
+
+    /// The alignment required by the entry. Entries with the same bytes, but different alignments
+    /// aren't merged.
+    pub(crate) alignment: Alignment,
 }
 
 #[derive(Default)]
 struct MergeStringsSection<'data> {
-    strings: Vec<&'data [u8]>,
+    strings: Vec<StringToMerge<'data>>,
     next_offset: u64,
+    alignment: Alignment,
     string_offsets: PassThroughHashMap<StringToMerge<'data>, u64>,
 }
 
 pub(crate) struct MergedStringsSection<'data> {
     pub(crate) len: u64,
-    pub(crate) strings: Vec<&'data [u8]>,
+
+    /// The maximum alignment of any of our strings.
+    pub(crate) alignment: Alignment,
+
+    /// The strings in the order that they should be written. Each string should be padded to its
+    /// alignment.
+    pub(crate) strings: Vec<StringToMerge<'data>>,
 }
 
 impl<'data> MergeStringsSection<'data> {
@@ -340,65 +662,155 @@ impl<'data> MergeStringsSection<'data> {
     /// present. Returns the offset into the section.
     fn add_string(&mut self, string: PreHashed<StringToMerge<'data>>) -> u64 {
         *self.string_offsets.entry(string).or_insert_with(|| {
-            let offset = self.next_offset;
-            self.next_offset += string.bytes.len() as u64;
-            self.strings.push(string.bytes);
+            let offset = string.alignment.align_up(self.next_offset);
+            self.next_offset = offset + string.bytes.len() as u64;
+            self.alignment = self.alignment.max(string.alignment);
+            self.strings.push(*string);
             offset
         })
     }
 }
 
+/// The distinct strings from the string-merge sections of a single file. These are found without
+/// reference to any other file, so files can be processed in parallel.
+#[derive(Default)]
+struct FileMergeStrings<'data> {
+    /// Each distinct string together with the output section that it goes in, in the order in which
+    /// the strings first occur in the file.
+    strings: Vec<(OutputSectionId, PreHashed<StringToMerge<'data>>)>,
+
+    /// For each of the file's string-merge sections, the output section that it goes in and the
+    /// index in `strings` of each of its strings.
+    sections: Vec<(OutputSectionId, Vec<u32>)>,
+}
+
 /// Merges identical strings from all loaded objects where those strings are from input sections
-/// that are marked with both the SHF_MERGE and SHF_STRINGS flags.
+/// that are marked with both the SHF_MERGE and SHF_STRINGS flags. Also merges identical fixed-size
+/// entries from sections that are marked with just SHF_MERGE.
+///
+/// Each file first deduplicates its own strings in parallel. The distinct strings from each file are
+/// then added to the output sections in file order, so that the result is the same as if we'd added
+/// every string from every file in order. Finally, references to merged strings are resolved, again
+/// in parallel.
 #[tracing::instrument(skip_all, name = "Merge strings")]
 fn merge_strings<'data>(
     resolved: &mut [ResolvedFile<'data>],
     output_sections: &OutputSections,
 ) -> Result<OutputSectionMap<MergedStringsSection<'data>>> {
+    let per_file = resolved
+        .par_iter()
+        .map(|file| file_merge_strings(file, output_sections))
+        .collect::<Result<Vec<FileMergeStrings>>>()?;
+
     let mut strings_by_section: OutputSectionMap<MergeStringsSection> =
         OutputSectionMap::with_size(output_sections.len());
-    for file in resolved {
-        let ResolvedFile::Object(obj) = file else {
-            continue;
-        };
-        let Some(non_dynamic) = obj.non_dynamic.as_mut() else {
-            continue;
-        };
-        for sec in &mut non_dynamic.merge_strings_sections {
-            let output_section_id = output_sections.output_section_id(sec.temporary_section_id)?;
-            let string_to_offset = strings_by_section.get_mut(output_section_id);
-            let mut symbols = sec.references.iter().peekable();
-            // The offset within the input section of the current string.
-            let mut input_offset = 0;
-            for string in &sec.strings {
-                let output_offset = string_to_offset.add_string(*string);
-                while let Some(merge_ref) = symbols.peek() {
-                    debug_assert!(
-                        merge_ref.offset >= input_offset,
-                        "String-merge symbol offsets went backwards"
-                    );
-                    let offset_into_string = merge_ref.offset - input_offset;
-                    if offset_into_string >= string.bytes.len() as u64 {
-                        // This reference belongs to a subsequent string.
-                        break;
-                    }
-                    non_dynamic.merged_string_resolutions[merge_ref.symbol_index.0] =
-                        Some(MergedStringResolution {
-                            output_section_id,
-                            offset: output_offset + offset_into_string,
-                        });
-                    symbols.next();
-                }
-                input_offset += string.bytes.len() as u64;
-            }
-        }
-    }
+    let output_offsets_per_file = per_file
+        .iter()
+        .map(|file_strings| {
+            file_strings
+                .strings
+                .iter()
+                .map(|(output_section_id, string)| {
+                    strings_by_section
+                        .get_mut(*output_section_id)
+                        .add_string(*string)
+                })
+                .collect::<Vec<u64>>()
+        })
+        .collect::<Vec<_>>();
+
+    resolved
+        .par_iter_mut()
+        .zip(&per_file)
+        .zip(&output_offsets_per_file)
+        .for_each(|((file, file_strings), output_offsets)| {
+            resolve_merged_string_references(file, file_strings, output_offsets);
+        });
+
     Ok(strings_by_section.into_map(|s| MergedStringsSection {
         len: s.next_offset,
+        alignment: s.alignment,
         strings: s.strings,
     }))
 }
 
+fn file_merge_strings<'data>(
+    file: &ResolvedFile<'data>,
+    output_sections: &OutputSections,
+) -> Result<FileMergeStrings<'data>> {
+    let mut file_strings = FileMergeStrings::default();
+    let ResolvedFile::Object(obj) = file else {
+        return Ok(file_strings);
+    };
+    let Some(non_dynamic) = obj.non_dynamic.as_ref() else {
+        return Ok(file_strings);
+    };
+    let mut indexes: PassThroughHashMap<(OutputSectionId, StringToMerge), u32> = Default::default();
+    for sec in &non_dynamic.merge_strings_sections {
+        let output_section_id = output_sections.output_section_id(sec.temporary_section_id)?;
+        let section_indexes = sec
+            .strings
+            .iter()
+            .map(|string| {
+                let key = PreHashed::new((output_section_id, **string), string.hash());
+                *indexes.entry(key).or_insert_with(|| {
+                    file_strings.strings.push((output_section_id, *string));
+                    file_strings.strings.len() as u32 - 1
+                })
+            })
+            .collect();
+        file_strings
+            .sections
+            .push((output_section_id, section_indexes));
+    }
+    Ok(file_strings)
+}
+
+/// Resolves the symbols in `file` that refer to merged strings. `output_offsets` gives the offset
+/// within its output section of each string in `file_strings`.
+fn resolve_merged_string_references(
+    file: &mut ResolvedFile,
+    file_strings: &FileMergeStrings,
+    output_offsets: &[u64],
+) {
+    let ResolvedFile::Object(obj) = file else {
+        return;
+    };
+    let Some(non_dynamic) = obj.non_dynamic.as_mut() else {
+        return;
+    };
+    for (sec, (output_section_id, section_indexes)) in non_dynamic
+        .merge_strings_sections
+        .iter()
+        .zip(&file_strings.sections)
+    {
+        let mut symbols = sec.references.iter().peekable();
+        // The offset within the input section of the current string.
+        let mut input_offset = 0;
+        for (string, index) in sec.strings.iter().zip(section_indexes) {
+            let output_offset = output_offsets[*index as usize];
+            while let Some(merge_ref) = symbols.peek() {
+                debug_assert!(
+                    merge_ref.offset >= input_offset,
+                    "String-merge symbol offsets went backwards"
+                );
+                let offset_into_string = merge_ref.offset - input_offset;
+                if offset_into_string >= string.bytes.len() as u64 {
+                    // This reference belongs to a subsequent string.
+                    break;
+                }
+                non_dynamic.merged_string_resolutions[merge_ref.symbol_index.0] =
+                    Some(MergedStringResolution {
+                        output_section_id: *output_section_id,
+                        offset: output_offset + offset_into_string,
+                    });
+                symbols.next();
+            }
+            input_offset += string.bytes.len() as u64;
+        }
+    }
+}
+
 #[tracing::instrument(skip_all, name = "Assign section IDs")]
 fn assign_section_ids<'data>(
     resolved: &[ResolvedFile<'data>],
@@ -411,6 +823,7 @@ fn assign_section_ids<'data>(
                 output_sections_builder
                     .add_sections(&non_dynamic.custom_sections)
                     .with_context(|| format!("Failed to process custom sections for {s}"))?;
+                output_sections_builder.add_relocation_sections(&non_dynamic.relocation_sections);
             }
         }
     }
@@ -463,6 +876,7 @@ fn process_object<'scope, 'data: 'scope, 'definitions>(
         request_file_id,
         definitions_out,
         &resources.outputs.start_stop_sets,
+        resources.diagnostics,
     )
     .with_context(|| format!("Failed to process {input}"))?;
     let _ = resources.outputs.loaded.push(res);
@@ -494,6 +908,17 @@ fn allocate_start_stop_symbol_ids<'data>(
         }
     }
     for (symbol_name, refs) in names.into_iter() {
+        if symbol_name == crate::riscv64::GLOBAL_POINTER_SYMBOL_NAME {
+            define_epilogue_symbol(
+                symbol_name,
+                InternalSymDefInfo::GlobalPointer,
+                &refs,
+                epilogue,
+                objects,
+                symbol_db,
+            );
+            continue;
+        }
         let (section_name, is_start) = if let Some(s) = symbol_name.strip_prefix(b"__start_") {
             (s, true)
         } else if let Some(s) = symbol_name.strip_prefix(b"__stop_") {
@@ -504,38 +929,65 @@ fn allocate_start_stop_symbol_ids<'data>(
                 String::from_utf8_lossy(symbol_name)
             );
         };
-        let section_id = if let Some(s) = output_sections.custom_name_to_id(section_name) {
+        // Like GNU ld, we only define start/stop symbols for sections whose names could be
+        // written as C identifiers.
+        let section_id = if let Some(s) = is_c_identifier(section_name)
+            .then(|| output_sections.custom_name_to_id(section_name))
+            .flatten()
+        {
             s
         } else {
-            if all_unresolved_weak(&refs, objects) {
+            if all_unresolved_weak(&refs, objects) || symbol_db.args.noinhibit_exec {
                 // There's no output section with the appropriate name, but the references are all weak,
-                // so we ignore it.
+                // so we ignore it. With --noinhibit-exec, strong references are left undefined and get
+                // reported when we write their relocations.
                 continue;
             }
-            bail!(
-                "Reference to undefined symbol `{}` and there's no custom section named `{}`",
-                String::from_utf8_lossy(symbol_name),
-                String::from_utf8_lossy(section_name),
-            )
+            return Err(LinkError::UndefinedSymbol {
+                name: String::from_utf8_lossy(symbol_name).into_owned(),
+                note: Some(format!(
+                    "There's no custom section named `{}`",
+                    String::from_utf8_lossy(section_name)
+                )),
+            }
+            .into());
         };
 
-        let symbol_id = symbol_db.add_start_stop_symbol(symbol_name);
         let def_info = if is_start {
             InternalSymDefInfo::SectionStart(section_id)
         } else {
             InternalSymDefInfo::SectionEnd(section_id)
         };
-        epilogue.symbol_definitions.push(def_info);
-        for (file_id, sym_index) in refs {
-            if let ResolvedFile::Object(obj) = &mut objects[file_id.as_usize()] {
-                let local_symbol_id = obj.start_symbol_id.add_usize(sym_index.0);
-                symbol_db.replace_definition(local_symbol_id, symbol_id);
-            }
-        }
+        define_epilogue_symbol(symbol_name, def_info, &refs, epilogue, objects, symbol_db);
     }
     Ok(())
 }
 
+/// Defines a linker-provided symbol in the epilogue and points `refs` at it.
+fn define_epilogue_symbol<'data>(
+    symbol_name: &'data [u8],
+    def_info: InternalSymDefInfo,
+    refs: &[(FileId, object::SymbolIndex)],
+    epilogue: &mut ResolvedEpilogue,
+    objects: &mut [ResolvedFile],
+    symbol_db: &mut SymbolDb<'data>,
+) {
+    let symbol_id = symbol_db.add_start_stop_symbol(symbol_name);
+    epilogue.symbol_definitions.push(def_info);
+    for (file_id, sym_index) in refs {
+        if let ResolvedFile::Object(obj) = &mut objects[file_id.as_usize()] {
+            let local_symbol_id = obj.start_symbol_id.add_usize(sym_index.0);
+            symbol_db.replace_definition(local_symbol_id, symbol_id);
+        }
+    }
+}
+
+fn is_c_identifier(name: &[u8]) -> bool {
+    name.first()
+        .is_some_and(|b| b.is_ascii_alphabetic() || *b == b'_')
+        && name.iter().all(|b| b.is_ascii_alphanumeric() || *b == b'_')
+}
+
 /// Returns whether all the specified symbols in the specified files are unresolved weak references.
 fn all_unresolved_weak(
     refs: &[(FileId, object::SymbolIndex)],
@@ -560,6 +1012,7 @@ impl<'data> ResolvedObject<'data> {
         request_file_id: impl FnMut(FileId),
         definitions_out: &mut [SymbolId],
         start_stop_sets: &SegQueue<StartStopSet<'data>>,
+        diagnostics: &Diagnostics,
     ) -> Result<Self> {
         let mut non_dynamic = None;
 
@@ -574,7 +1027,14 @@ impl<'data> ResolvedObject<'data> {
             .with_context(|| format!("Failed to resolve symbols in {obj}"))?;
         } else {
             let mut custom_sections = Vec::new();
-            let mut sections = resolve_sections(obj, &mut custom_sections, symbol_db.args)?;
+            let mut relocation_sections = Vec::new();
+            let mut sections = resolve_sections(
+                obj,
+                &mut custom_sections,
+                &mut relocation_sections,
+                symbol_db.args,
+                diagnostics,
+            )?;
             resolve_symbols(
                 obj,
                 symbol_db,
@@ -599,38 +1059,76 @@ impl<'data> ResolvedObject<'data> {
                 sections,
                 merge_strings_sections,
                 custom_sections,
+                relocation_sections,
             });
         }
 
         Ok(Self {
             input: obj.input,
             object: &obj.object,
+            decompressed_sections: &obj.decompressed_sections,
             file_id: obj.file_id,
             num_symbols: obj.num_symbols,
             start_symbol_id: obj.start_symbol_id,
+            modifiers: obj.modifiers,
             non_dynamic,
         })
     }
 }
 
 fn resolve_sections<'data>(
-    obj: &RegularInputObject<'data>,
+    obj: &'data RegularInputObject<'data>,
     custom_sections: &mut Vec<(object::SectionIndex, SectionDetails<'data>)>,
-    args: &Args,
+    relocation_sections: &mut Vec<(TemporaryOutputSectionId<'data>, &'data [u8])>,
+    args: &'data Args,
+    diagnostics: &Diagnostics,
 ) -> Result<Vec<SectionSlot<'data>>> {
     let sections = obj
         .object
         .sections()
         .map(|input_section| {
-            if let Some(unloaded) = UnloadedSection::from_section(&input_section, args)? {
+            let section_name = input_section.name_bytes()?;
+            if args.should_remove_section(section_name) {
+                return Ok(SectionSlot::Discard);
+            }
+            if let Some(mut unloaded) = UnloadedSection::from_section(
+                &input_section,
+                &obj.object,
+                &obj.decompressed_sections,
+                args,
+            )? {
+                unloaded.details.retain |= args.should_keep_section(section_name);
+                if unloaded.is_orphan {
+                    let name = String::from_utf8_lossy(section_name);
+                    match args.orphan_handling {
+                        OrphanHandling::Place => {}
+                        OrphanHandling::Warn => diagnostics.warn(format!(
+                            "{obj}: orphan section `{name}` placed in output section `{name}`"
+                        )),
+                        OrphanHandling::Error => {
+                            bail!("Unplaced orphan section `{name}` in {obj}")
+                        }
+                        OrphanHandling::Discard => return Ok(SectionSlot::Discard),
+                    }
+                }
+                if args.emit_relocs {
+                    if let Some(name) =
+                        unloaded.relocation_section_name(&input_section, &obj.object, args)?
+                    {
+                        relocation_sections.push((unloaded.output_section_id, name));
+                    }
+                }
                 if unloaded.is_string_merge {
                     if let TemporaryOutputSectionId::Custom(_custom_section_id) =
                         unloaded.output_section_id
                     {
                         custom_sections.push((input_section.index(), unloaded.details));
                     }
+                    let data = obj.decompressed_sections.data(&input_section)?;
                     Ok(SectionSlot::MergeStrings(MergeStringsFileSection::new(
                         input_section,
+                        &obj.object,
+                        data,
                         unloaded.output_section_id,
                     )?))
                 } else {
@@ -643,6 +1141,12 @@ fn resolve_sections<'data>(
                         TemporaryOutputSectionId::EhFrameData => {
                             Ok(SectionSlot::EhFrameData(input_section.index()))
                         }
+                        TemporaryOutputSectionId::GnuPropertyNote => {
+                            Ok(SectionSlot::GnuPropertyNote(input_section.index()))
+                        }
+                        TemporaryOutputSectionId::AbiTagNote => {
+                            Ok(SectionSlot::AbiTagNote(input_section.index()))
+                        }
                     }
                 }
             } else {
@@ -755,7 +1259,12 @@ fn resolve_symbol<'data>(
             }
         }
         None => {
-            if name_bytes.starts_with(b"__start_") || name_bytes.starts_with(b"__stop_") {
+            // RISC-V's global pointer gets defined on demand in the same way as start/stop symbols.
+            if name_bytes.starts_with(b"__start_")
+                || name_bytes.starts_with(b"__stop_")
+                || (symbol_db.emulation == Emulation::RiscV64
+                    && name_bytes == crate::riscv64::GLOBAL_POINTER_SYMBOL_NAME)
+            {
                 start_stop_refs
                     .entry(name_bytes)
                     .or_default()
@@ -784,17 +1293,51 @@ impl<'data> std::fmt::Display for ResolvedFile<'data> {
 }
 
 impl<'data> SectionSlot<'data> {
-    pub(crate) fn is_loaded(&self) -> bool {
-        matches!(self, SectionSlot::Loaded(_))
+    /// Returns the output section containing this section, or, for a folded section, containing
+    /// the section that it was folded into. Returns None if the section isn't in the output.
+    pub(crate) fn output_section_id(&self) -> Option<OutputSectionId> {
+        match self {
+            SectionSlot::Loaded(section) => section.output_section_id,
+            SectionSlot::Folded(folded) => Some(folded.output_section_id),
+            _ => None,
+        }
     }
 }
 
 impl<'data> MergeStringsFileSection<'data> {
     fn new(
         input_section: crate::elf::Section<'data, '_>,
+        object: &crate::elf::File<'data>,
+        data: &'data [u8],
         section_id: TemporaryOutputSectionId<'data>,
     ) -> Result<MergeStringsFileSection<'data>> {
-        let mut remaining = input_section.data()?;
+        let SectionFlags::Elf { sh_flags } = input_section.flags() else {
+            unreachable!();
+        };
+        if sh_flags & crate::elf::shf::STRINGS == 0 {
+            // A section without SHF_STRINGS is only treated as string-merge if its size is a
+            // multiple of its entry size. Each entry is only guaranteed the alignment that's common
+            // to all offsets that are a multiple of the entry size.
+            let entsize = crate::elf::section_entsize(object, input_section.index())?;
+            let alignment = Alignment::new(
+                input_section
+                    .align()
+                    .clamp(1, 1 << entsize.trailing_zeros()),
+            )?;
+            let strings = data
+                .chunks_exact(entsize as usize)
+                .map(|bytes| {
+                    let hash = crate::hash::hash_bytes(bytes);
+                    PreHashed::new(StringToMerge { bytes, alignment }, hash)
+                })
+                .collect();
+            return Ok(MergeStringsFileSection {
+                temporary_section_id: section_id,
+                strings,
+                references: Default::default(),
+            });
+        }
+        let mut remaining = data;
         let mut strings = Vec::new();
         while !remaining.is_empty() {
             let len = memchr::memchr(0, remaining)
@@ -807,7 +1350,13 @@ impl<'data> MergeStringsFileSection<'data> {
                 })?;
             let (bytes, rest) = remaining.split_at(len);
             let hash = crate::hash::hash_bytes(bytes);
-            strings.push(PreHashed::new(StringToMerge { bytes }, hash));
+            strings.push(PreHashed::new(
+                StringToMerge {
+                    bytes,
+                    alignment: alignment::MIN,
+                },
+                hash,
+            ));
             remaining = rest;
         }
         Ok(MergeStringsFileSection {
@@ -859,8 +1408,31 @@ impl<'data> SymbolDb<'data> {
             } else {
                 SymbolStrength::Strong
             }
+        } else if let InputObject::Internal(internal) = &self.inputs[file_id.as_usize()] {
+            let local_index = symbol_id.offset_from(SymbolId::undefined());
+            match internal.symbol_definitions[local_index] {
+                InternalSymDefInfo::Defsym(index) if internal.defsym(index).weak => {
+                    SymbolStrength::Weak
+                }
+                InternalSymDefInfo::Defsym(_) => SymbolStrength::Strong,
+                // Other linker-defined symbols are only used if no input object defines the
+                // symbol.
+                _ => SymbolStrength::Undefined,
+            }
         } else {
             SymbolStrength::Undefined
         }
     }
+
+    /// Returns the alignment requested by `symbol_id` if it's a common symbol in a loaded object.
+    fn common_alignment(&self, symbol_id: SymbolId, resolved: &[ResolvedFile]) -> Option<u64> {
+        let file_id = self.file_id_for_symbol(symbol_id);
+        let ResolvedFile::Object(obj) = &resolved[file_id.as_usize()] else {
+            return None;
+        };
+        let local_index = object::SymbolIndex(symbol_id.offset_from(obj.start_symbol_id));
+        let obj_symbol = obj.object.symbol_by_index(local_index).ok()?;
+        // Common symbols store their alignment in the value field.
+        obj_symbol.is_common().then(|| obj_symbol.address())
+    }
 }
@@ -0,0 +1,552 @@
+//! RISC-V-specific parts of linking. Instructions are 4 bytes, or 2 bytes for the compressed
+//! instructions, with immediates scattered across various bit fields. PC-relative addresses are
+//! usually formed by an AUIPC, which adds the high 20 bits of an offset to the PC, followed by an
+//! instruction with a 12 bit immediate that supplies the low bits. See "RISC-V ABIs Specification"
+//! for details.
+
+use crate::args::OutputKind;
+use crate::elf::RelocationKind;
+use crate::elf::RelocationKindInfo;
+use crate::elf::RelocationSize;
+use crate::error::LinkError;
+use crate::error::Result;
+use crate::relaxation::Relaxation;
+use crate::resolution::ValueKind;
+use ahash::AHashMap;
+use anyhow::bail;
+use anyhow::Context as _;
+use object::elf::*;
+
+// Relocation types that are newer than our version of the `object` crate.
+pub(crate) const R_RISCV_IRELATIVE: u32 = 58;
+const R_RISCV_PLT32: u32 = 59;
+const R_RISCV_SET_ULEB128: u32 = 60;
+const R_RISCV_SUB_ULEB128: u32 = 61;
+
+/// Code that uses the global pointer expects this symbol to be defined by the linker.
+pub(crate) const GLOBAL_POINTER_SYMBOL_NAME: &[u8] = b"__global_pointer$";
+
+/// How far the global pointer is from the start of .data. Since GP-relative accesses use a signed
+/// 12 bit offset, this lets them reach the first 4 KiB of data.
+pub(crate) const GLOBAL_POINTER_OFFSET: u64 = 0x800;
+
+/// Offsets within a module's TLS block, as used by `__tls_get_addr`, are biased by this amount so
+/// that a 12 bit signed immediate can reach more of the block.
+pub(crate) const DTP_OFFSET: u64 = 0x800;
+
+/// A PLT entry loads the address of the function from its GOT entry into t3 and jumps there. The
+/// GOT offset gets filled in by `write_plt_entry`.
+pub(crate) const PLT_ENTRY_TEMPLATE: &[u8] = &[
+    0x17, 0x0e, 0x00, 0x00, // auipc t3, {GOT offset high bits}
+    0x03, 0x3e, 0x0e, 0x00, // ld t3, {GOT offset low bits}(t3)
+    0x67, 0x03, 0x0e, 0x00, // jalr t1, t3
+    0x13, 0x00, 0x00, 0x00, // nop
+];
+
+/// The different ways in which a relocation can store its value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Encoding {
+    /// LUI or AUIPC. Stores bits 12..32 of the value, rounded so that adding the sign-extended low
+    /// 12 bits gives back the value.
+    UType,
+    /// An instruction with a 12 bit immediate, such as ADDI, JALR or a load. Stores the low 12
+    /// bits of the value.
+    IType,
+    /// A store. Stores the low 12 bits of the value, split across two fields.
+    SType,
+    /// Like `IType`, but the symbol points at an AUIPC and the value is whatever was computed for
+    /// the `*_HI20` relocation of that AUIPC.
+    PcrelLo12I,
+    /// Like `SType`, but with the value found in the same way as `PcrelLo12I`.
+    PcrelLo12S,
+    /// A conditional branch. Stores a 13 bit offset from the instruction to the target.
+    BType,
+    /// JAL. Stores a 21 bit offset.
+    JType,
+    /// An AUIPC followed by a JALR, which together store a 32 bit offset.
+    Call,
+    /// C.BEQZ or C.BNEZ. Stores a 9 bit offset.
+    CbType,
+    /// C.J. Stores a 12 bit offset.
+    CjType,
+    /// Adds the value to the little-endian integer of this many bytes at the place.
+    Add(usize),
+    /// Subtracts the value from the little-endian integer of this many bytes at the place.
+    Sub(usize),
+    /// Stores the value in this many bytes, like a regular data relocation.
+    Set(usize),
+    /// Subtracts the value from the low 6 bits of the byte at the place.
+    Sub6,
+    /// Stores the value in the low 6 bits of the byte at the place.
+    Set6,
+    /// Stores the value in the ULEB128 at the place, keeping its existing length.
+    SetUleb128,
+    /// Subtracts the value from the ULEB128 at the place, keeping its existing length.
+    SubUleb128,
+}
+
+pub(crate) fn relocation_kind_info(r_type: u32) -> Result<RelocationKindInfo> {
+    use Encoding as E;
+    use RelocationKind as K;
+    // Relocations that add or subtract symbol values in place are used in pairs to compute the
+    // distance between two symbols, which doesn't change if the output is loaded at a different
+    // address. We use `RelocationKind::Relative` for them, so that they never need a dynamic
+    // relocation, and add the place back on when writing them.
+    let (kind, size) = match r_type {
+        R_RISCV_NONE => (K::None, RelocationSize::ByteSize(0)),
+        R_RISCV_32 => (K::Absolute, RelocationSize::ByteSize(4)),
+        R_RISCV_64 => (K::Absolute, RelocationSize::ByteSize(8)),
+        R_RISCV_TLS_DTPREL32 => (K::DtpOff, RelocationSize::ByteSize(4)),
+        R_RISCV_TLS_DTPREL64 => (K::DtpOff, RelocationSize::ByteSize(8)),
+        R_RISCV_32_PCREL => (K::Relative, RelocationSize::ByteSize(4)),
+        R_RISCV_PLT32 => (K::PltRelative, RelocationSize::ByteSize(4)),
+        // RELAX marks instructions that a linker may optimise and ALIGN marks padding that a
+        // relaxing linker would trim so as to keep alignment. We don't relax, so the code is
+        // already correct as written. TPREL_ADD only marks the instruction that adds the thread
+        // pointer.
+        R_RISCV_RELAX | R_RISCV_ALIGN | R_RISCV_TPREL_ADD => (K::None, RelocationSize::ByteSize(0)),
+        _ => {
+            let (kind, encoding) = match r_type {
+                R_RISCV_BRANCH => (K::Relative, E::BType),
+                R_RISCV_JAL => (K::Relative, E::JType),
+                R_RISCV_CALL | R_RISCV_CALL_PLT => (K::PltRelative, E::Call),
+                R_RISCV_GOT_HI20 => (K::GotRelative, E::UType),
+                R_RISCV_TLS_GOT_HI20 => (K::GotTpOff, E::UType),
+                R_RISCV_TLS_GD_HI20 => (K::TlsGd, E::UType),
+                R_RISCV_PCREL_HI20 => (K::Relative, E::UType),
+                R_RISCV_PCREL_LO12_I => (K::Relative, E::PcrelLo12I),
+                R_RISCV_PCREL_LO12_S => (K::Relative, E::PcrelLo12S),
+                R_RISCV_HI20 => (K::Absolute, E::UType),
+                R_RISCV_LO12_I => (K::Absolute, E::IType),
+                R_RISCV_LO12_S => (K::Absolute, E::SType),
+                R_RISCV_TPREL_HI20 => (K::TpOff, E::UType),
+                R_RISCV_TPREL_LO12_I => (K::TpOff, E::IType),
+                R_RISCV_TPREL_LO12_S => (K::TpOff, E::SType),
+                R_RISCV_RVC_BRANCH => (K::Relative, E::CbType),
+                R_RISCV_RVC_JUMP => (K::Relative, E::CjType),
+                R_RISCV_ADD8 => (K::Relative, E::Add(1)),
+                R_RISCV_ADD16 => (K::Relative, E::Add(2)),
+                R_RISCV_ADD32 => (K::Relative, E::Add(4)),
+                R_RISCV_ADD64 => (K::Relative, E::Add(8)),
+                R_RISCV_SUB8 => (K::Relative, E::Sub(1)),
+                R_RISCV_SUB16 => (K::Relative, E::Sub(2)),
+                R_RISCV_SUB32 => (K::Relative, E::Sub(4)),
+                R_RISCV_SUB64 => (K::Relative, E::Sub(8)),
+                R_RISCV_SUB6 => (K::Relative, E::Sub6),
+                R_RISCV_SET6 => (K::Relative, E::Set6),
+                R_RISCV_SET8 => (K::Relative, E::Set(1)),
+                R_RISCV_SET16 => (K::Relative, E::Set(2)),
+                R_RISCV_SET32 => (K::Relative, E::Set(4)),
+                R_RISCV_SET_ULEB128 => (K::Relative, E::SetUleb128),
+                R_RISCV_SUB_ULEB128 => (K::Relative, E::SubUleb128),
+                _ => return Err(LinkError::UnsupportedRelocation { r_type }.into()),
+            };
+            (kind, RelocationSize::RiscV(encoding))
+        }
+    };
+    Ok(RelocationKindInfo { kind, size })
+}
+
+/// Returns a relaxation for the relocation `r_type`, if there's one that we can apply.
+pub(crate) fn relaxation(
+    r_type: u32,
+    value_kind: ValueKind,
+    output_kind: OutputKind,
+) -> Option<(Relaxation, RelocationKindInfo)> {
+    let is_executable = output_kind != OutputKind::SharedObject;
+    let is_local = value_kind != ValueKind::Dynamic;
+    match r_type {
+        // Calls to functions defined in an executable can go directly to the function rather than
+        // via the PLT.
+        R_RISCV_CALL | R_RISCV_CALL_PLT if is_executable && is_local => Some((
+            Relaxation::NoOp,
+            RelocationKindInfo {
+                kind: RelocationKind::Relative,
+                size: RelocationSize::RiscV(Encoding::Call),
+            },
+        )),
+        _ => None,
+    }
+}
+
+/// Combines the `e_flags` of an input object with those of the objects before it. Objects that
+/// use compressed instructions or total store ordering taint the output, but all objects need to
+/// agree on the floating-point ABI and whether they use the embedded base ISA.
+pub(crate) fn merge_e_flags(existing: u32, input: u32) -> Result<u32> {
+    let must_match = EF_RISCV_FLOAT_ABI | EF_RISCV_RVE;
+    if existing & must_match != input & must_match {
+        bail!(
+            "Incompatible floating-point ABI or base ISA (e_flags 0x{input:x}, expected 0x{:x})",
+            existing & must_match
+        );
+    }
+    Ok(existing | input & (EF_RISCV_RVC | EF_RISCV_TSO))
+}
+
+/// Stores `value` at the start of `out` as specified by `encoding`. `value` is the value computed
+/// for the relocation, which for a PC-relative relocation is an offset from `place`. Values of
+/// PC-relative `*_HI20` relocations are recorded in `hi20_values`, keyed by `place`, so that the
+/// `R_RISCV_PCREL_LO12_*` relocations that refer to them can find them.
+pub(crate) fn write_relocation(
+    out: &mut [u8],
+    encoding: Encoding,
+    value: u64,
+    place: u64,
+    is_pc_relative: bool,
+    hi20_values: &mut AHashMap<u64, u64>,
+) -> Result {
+    let target = if is_pc_relative {
+        value.wrapping_add(place)
+    } else {
+        value
+    };
+    match encoding {
+        Encoding::UType => {
+            if is_pc_relative {
+                hi20_values.insert(place, value);
+            }
+            write_instruction(out, encoding, encode_u(value)?)
+        }
+        Encoding::IType => write_instruction(out, encoding, encode_i(value)),
+        Encoding::SType => write_instruction(out, encoding, encode_s(value)),
+        Encoding::PcrelLo12I | Encoding::PcrelLo12S => {
+            let hi20_value = *hi20_values
+                .get(&target)
+                .with_context(|| format!("No PC-relative HI20 relocation found at 0x{target:x}"))?;
+            let bits = if encoding == Encoding::PcrelLo12I {
+                encode_i(hi20_value)
+            } else {
+                encode_s(hi20_value)
+            };
+            write_instruction(out, encoding, bits)
+        }
+        Encoding::BType => write_instruction(out, encoding, encode_b(value)?),
+        Encoding::JType => write_instruction(out, encoding, encode_j(value)?),
+        Encoding::Call => {
+            write_instruction(out, encoding, encode_u(value)?)?;
+            write_instruction(
+                out.get_mut(4..).unwrap_or_default(),
+                encoding,
+                encode_i(value),
+            )
+        }
+        Encoding::CbType => write_compressed(out, encoding, encode_cb(value)?),
+        Encoding::CjType => write_compressed(out, encoding, encode_cj(value)?),
+        Encoding::Add(size) => update_data(out, size, |existing| existing.wrapping_add(target)),
+        Encoding::Sub(size) => update_data(out, size, |existing| existing.wrapping_sub(target)),
+        Encoding::Set(size) => update_data(out, size, |_| target),
+        Encoding::Sub6 => update_data(out, 1, |existing| {
+            existing & !0x3f | existing.wrapping_sub(target) & 0x3f
+        }),
+        Encoding::Set6 => update_data(out, 1, |existing| existing & !0x3f | target & 0x3f),
+        Encoding::SetUleb128 => update_uleb128(out, |_| target),
+        Encoding::SubUleb128 => update_uleb128(out, |existing| existing.wrapping_sub(target)),
+    }
+}
+
+/// Sets the bits of the 4-byte instruction at the start of `out` that are selected by `mask`.
+fn write_instruction(out: &mut [u8], encoding: Encoding, (mask, bits): (u32, u32)) -> Result {
+    let Some(bytes) = out.first_chunk_mut::<4>() else {
+        bail!("Relocation ({encoding:?}) outside of bounds of section");
+    };
+    let existing = u32::from_le_bytes(*bytes);
+    *bytes = (existing & !mask | bits).to_le_bytes();
+    Ok(())
+}
+
+/// Like `write_instruction`, but for a 2-byte compressed instruction.
+fn write_compressed(out: &mut [u8], encoding: Encoding, (mask, bits): (u16, u16)) -> Result {
+    let Some(bytes) = out.first_chunk_mut::<2>() else {
+        bail!("Relocation ({encoding:?}) outside of bounds of section");
+    };
+    let existing = u16::from_le_bytes(*bytes);
+    *bytes = (existing & !mask | bits).to_le_bytes();
+    Ok(())
+}
+
+fn update_data(out: &mut [u8], size: usize, update: impl FnOnce(u64) -> u64) -> Result {
+    let Some(bytes) = out.get_mut(..size) else {
+        bail!("Relocation outside of bounds of section");
+    };
+    let mut existing = [0; 8];
+    existing[..size].copy_from_slice(bytes);
+    let value = update(u64::from_le_bytes(existing));
+    bytes.copy_from_slice(&value.to_le_bytes()[..size]);
+    Ok(())
+}
+
+fn update_uleb128(out: &mut [u8], update: impl FnOnce(u64) -> u64) -> Result {
+    let Some(len) = out.iter().position(|byte| byte & 0x80 == 0).map(|i| i + 1) else {
+        bail!("Unterminated ULEB128 at relocation");
+    };
+    let bytes = &mut out[..len];
+    let existing = bytes
+        .iter()
+        .rev()
+        .fold(0u64, |value, byte| value << 7 | u64::from(byte & 0x7f));
+    let mut value = update(existing);
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (value & 0x7f) as u8 | if i + 1 < len { 0x80 } else { 0 };
+        value >>= 7;
+    }
+    if value != 0 {
+        bail!("Relocation value doesn't fit in the {len} byte ULEB128 at the place");
+    }
+    Ok(())
+}
+
+fn encode_u(value: u64) -> Result<(u32, u32)> {
+    // The low 12 bits get sign-extended when they're added, so round the high bits to compensate.
+    let hi = (value as i64).wrapping_add(0x800) >> 12;
+    check_range(Encoding::UType, hi, 20)?;
+    Ok((0xffff_f000, (hi as u32) << 12))
+}
+
+fn encode_i(value: u64) -> (u32, u32) {
+    (0xfff << 20, (value as u32 & 0xfff) << 20)
+}
+
+fn encode_s(value: u64) -> (u32, u32) {
+    let imm = value as u32;
+    (0xfe00_0f80, (imm >> 5 & 0x7f) << 25 | (imm & 0x1f) << 7)
+}
+
+fn encode_b(value: u64) -> Result<(u32, u32)> {
+    let imm = check_branch(Encoding::BType, value, 13)?;
+    Ok((
+        0xfe00_0f80,
+        (imm >> 12 & 0x1) << 31
+            | (imm >> 5 & 0x3f) << 25
+            | (imm >> 1 & 0xf) << 8
+            | (imm >> 11 & 0x1) << 7,
+    ))
+}
+
+fn encode_j(value: u64) -> Result<(u32, u32)> {
+    let imm = check_branch(Encoding::JType, value, 21)?;
+    Ok((
+        0xffff_f000,
+        (imm >> 20 & 0x1) << 31
+            | (imm >> 1 & 0x3ff) << 21
+            | (imm >> 11 & 0x1) << 20
+            | (imm >> 12 & 0xff) << 12,
+    ))
+}
+
+fn encode_cb(value: u64) -> Result<(u16, u16)> {
+    let imm = check_branch(Encoding::CbType, value, 9)?;
+    let bits = (imm >> 8 & 0x1) << 12
+        | (imm >> 3 & 0x3) << 10
+        | (imm >> 6 & 0x3) << 5
+        | (imm >> 1 & 0x3) << 3
+        | (imm >> 5 & 0x1) << 2;
+    Ok((0x1c7c, bits as u16))
+}
+
+fn encode_cj(value: u64) -> Result<(u16, u16)> {
+    let imm = check_branch(Encoding::CjType, value, 12)?;
+    let bits = (imm >> 11 & 0x1) << 12
+        | (imm >> 4 & 0x1) << 11
+        | (imm >> 8 & 0x3) << 9
+        | (imm >> 10 & 0x1) << 8
+        | (imm >> 6 & 0x1) << 7
+        | (imm >> 7 & 0x1) << 6
+        | (imm >> 1 & 0x7) << 3
+        | (imm >> 5 & 0x1) << 2;
+    Ok((0x1ffc, bits as u16))
+}
+
+/// Checks that `offset` is a valid branch offset that fits in `bits` bits and returns it.
+fn check_branch(encoding: Encoding, offset: u64, bits: u32) -> Result<u32> {
+    if offset & 0x1 != 0 {
+        bail!("Branch offset 0x{offset:x} for {encoding:?} isn't a multiple of 2");
+    }
+    check_range(encoding, offset as i64, bits)?;
+    Ok(offset as u32)
+}
+
+/// Checks that `value` fits in a signed immediate of `bits` bits.
+fn check_range(encoding: Encoding, value: i64, bits: u32) -> Result {
+    let limit = 1i64 << (bits - 1);
+    if !(-limit..limit).contains(&value) {
+        bail!("Relocation value 0x{value:x} is out of range for {encoding:?}");
+    }
+    Ok(())
+}
+
+/// Fills in the GOT offset in a PLT entry that was copied from `PLT_ENTRY_TEMPLATE`.
+pub(crate) fn write_plt_entry(plt_entry: &mut [u8], got_address: u64, plt_address: u64) -> Result {
+    write_relocation(
+        plt_entry,
+        Encoding::Call,
+        got_address.wrapping_sub(plt_address),
+        plt_address,
+        true,
+        &mut AHashMap::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn encode(instruction: u32, encoding: Encoding, value: u64) -> u32 {
+        let mut bytes = instruction.to_le_bytes();
+        write_relocation(&mut bytes, encoding, value, 0, false, &mut AHashMap::new()).unwrap();
+        u32::from_le_bytes(bytes)
+    }
+
+    #[test]
+    fn test_encode_instructions() {
+        // auipc a0, 0x12345 - the low bits are negative, so the high bits get rounded up.
+        assert_eq!(
+            encode(0x0000_0517, Encoding::UType, 0x1234_4800),
+            0x1234_5517
+        );
+        // addi a0, a0, -0x800
+        assert_eq!(
+            encode(0x0005_0513, Encoding::IType, 0x1234_4800),
+            0x8005_0513
+        );
+        // sd a1, 0x7f8(a0)
+        assert_eq!(encode(0x00b5_3023, Encoding::SType, 0x7f8), 0x7eb5_3c23);
+        // beq a0, a1, .-4
+        assert_eq!(
+            encode(0x00b5_0063, Encoding::BType, (-4i64) as u64),
+            0xfeb5_0ee3
+        );
+        // jal ra, .+0x800
+        assert_eq!(encode(0x0000_00ef, Encoding::JType, 0x800), 0x0010_00ef);
+        let mut bytes = 0x0000_00efu32.to_le_bytes();
+        assert!(write_relocation(
+            &mut bytes,
+            Encoding::JType,
+            1 << 20,
+            0,
+            true,
+            &mut AHashMap::new()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_compressed_branches() {
+        let mut bytes = 0xa001u16.to_le_bytes();
+        // c.j .+0x7fe
+        write_relocation(
+            &mut bytes,
+            Encoding::CjType,
+            0x7fe,
+            0,
+            true,
+            &mut AHashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(u16::from_le_bytes(bytes), 0xaffd);
+        let mut bytes = 0xc101u16.to_le_bytes();
+        // c.beqz a0, .-2
+        write_relocation(
+            &mut bytes,
+            Encoding::CbType,
+            (-2i64) as u64,
+            0,
+            true,
+            &mut AHashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(u16::from_le_bytes(bytes), 0xdd7d);
+    }
+
+    #[test]
+    fn test_data() {
+        let mut hi20_values = AHashMap::new();
+        let mut write = |bytes: &mut [u8], encoding, target: u64| {
+            // Data relocations are PC-relative so that they don't need dynamic relocations.
+            let place = 0x1000;
+            write_relocation(
+                bytes,
+                encoding,
+                target.wrapping_sub(place),
+                place,
+                true,
+                &mut hi20_values,
+            )
+        };
+        let mut bytes = 0x10u32.to_le_bytes();
+        write(&mut bytes, Encoding::Add(4), 0x2000).unwrap();
+        write(&mut bytes, Encoding::Sub(4), 0x1ff8).unwrap();
+        assert_eq!(u32::from_le_bytes(bytes), 0x18);
+        let mut bytes = [0x41];
+        write(&mut bytes, Encoding::Sub6, 0x2).unwrap();
+        assert_eq!(bytes, [0x7f]);
+        // A padded ULEB128 keeps its length.
+        let mut bytes = [0x80, 0x80, 0x00];
+        write(&mut bytes, Encoding::SetUleb128, 0x2000).unwrap();
+        write(&mut bytes, Encoding::SubUleb128, 0x1f80).unwrap();
+        assert_eq!(bytes, [0x80, 0x81, 0x00]);
+        assert!(write(&mut [0x80, 0x00], Encoding::SetUleb128, 1 << 14).is_err());
+    }
+
+    #[test]
+    fn test_pcrel_lo12() {
+        let mut hi20_values = AHashMap::new();
+        // auipc a0, {hi}; addi a0, a0, {lo}, with the target 0x1801 bytes after the auipc.
+        let mut code = [0x0000_0517u32, 0x0005_0513].map(u32::to_le_bytes).concat();
+        write_relocation(
+            &mut code,
+            Encoding::UType,
+            0x1801,
+            0x1000,
+            true,
+            &mut hi20_values,
+        )
+        .unwrap();
+        // The low relocation refers to the auipc rather than to the target.
+        let label_offset = 0x1000u64.wrapping_sub(0x1004);
+        write_relocation(
+            &mut code[4..],
+            Encoding::PcrelLo12I,
+            label_offset,
+            0x1004,
+            true,
+            &mut hi20_values,
+        )
+        .unwrap();
+        assert_eq!(
+            code,
+            [0x0000_2517u32, 0x8015_0513].map(u32::to_le_bytes).concat()
+        );
+        assert!(write_relocation(
+            &mut code[4..],
+            Encoding::PcrelLo12I,
+            0,
+            0x1004,
+            true,
+            &mut hi20_values,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_plt_entry() {
+        let mut entry = PLT_ENTRY_TEMPLATE.to_owned();
+        write_plt_entry(&mut entry, 0x12_018, 0x11_010).unwrap();
+        let words: Vec<u32> = entry
+            .chunks(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        // auipc t3, 0x1; ld t3, 8(t3); jalr t1, t3; nop
+        assert_eq!(words, [0x0000_1e17, 0x008e_3e03, 0x000e_0367, 0x0000_0013]);
+    }
+
+    #[test]
+    fn test_merge_e_flags() {
+        let double = EF_RISCV_FLOAT_ABI_DOUBLE;
+        assert_eq!(
+            merge_e_flags(double, double | EF_RISCV_RVC).unwrap(),
+            double | EF_RISCV_RVC
+        );
+        assert!(merge_e_flags(double, EF_RISCV_FLOAT_ABI_SOFT).is_err());
+    }
+}
@@ -1,4 +1,5 @@
-//! Support for saving inputs for later use.
+//! Support for saving inputs for later use, either on every link, when requested via environment
+//! variables, or only when a link fails, via `--reproduce-on-error`.
 
 use crate::error::Result;
 use ahash::AHashMap;
@@ -11,6 +12,15 @@ use std::path::PathBuf;
 pub(crate) struct SaveDir {
     dir: Option<PathBuf>,
     copied_paths: AHashMap<String, String>,
+    /// Every path passed to `handle_file`, whether or not we copied it, so that we can copy them
+    /// later if the link fails.
+    handled_paths: Vec<String>,
+}
+
+/// A save directory that we only populate if the link fails. Created by `--reproduce-on-error`.
+pub(crate) struct DeferredSaveDir {
+    dir: PathBuf,
+    paths: Vec<String>,
 }
 
 const SAVE_DIR_ENV: &str = "WILD_SAVE_DIR";
@@ -65,6 +75,16 @@ impl SaveDir {
         SaveDir {
             dir,
             copied_paths: AHashMap::new(),
+            handled_paths: Vec::new(),
+        }
+    }
+
+    /// Returns a save directory that, if the link fails, will be populated in `dir` with the files
+    /// that were passed to `handle_file`.
+    pub(crate) fn deferred(&mut self, dir: PathBuf) -> DeferredSaveDir {
+        DeferredSaveDir {
+            dir,
+            paths: std::mem::take(&mut self.handled_paths),
         }
     }
 
@@ -73,7 +93,7 @@ impl SaveDir {
             return Ok(());
         };
         let run_with_file = dir.join("run-with");
-        self.write_args_file(&run_with_file)
+        self.write_args_file(&run_with_file, std::env::args().skip(1))
             .with_context(|| format!("Failed to write `{}`", run_with_file.display()))?;
         if std::env::var(SKIP_LINKING_ENV).is_ok() {
             std::process::exit(0);
@@ -81,19 +101,21 @@ impl SaveDir {
         Ok(())
     }
 
-    fn write_args_file(&self, run_file: &Path) -> Result {
+    fn write_args_file(&self, run_file: &Path, args: impl Iterator<Item = String>) -> Result {
         let mut file = std::fs::File::create(run_file)?;
         let mut out = BufWriter::new(&mut file);
-        let mut args = std::env::args();
         out.write_all(PRELUDE.as_bytes())?;
-        args.next();
         self.write_args(args, &mut out)?;
         drop(out);
         crate::fs::make_executable(&file)?;
         Ok(())
     }
 
-    fn write_args(&self, args: std::env::Args, out: &mut BufWriter<&mut std::fs::File>) -> Result {
+    fn write_args(
+        &self,
+        args: impl Iterator<Item = String>,
+        out: &mut BufWriter<&mut std::fs::File>,
+    ) -> Result {
         let mut is_output_file = false;
         let mut original_output_file = None;
         for arg in args {
@@ -126,6 +148,7 @@ impl SaveDir {
     }
 
     pub(crate) fn handle_file(&mut self, arg: &str) -> Result {
+        self.handled_paths.push(arg.to_owned());
         let Some(dir) = self.dir.as_ref() else {
             return Ok(());
         };
@@ -157,6 +180,31 @@ impl SaveDir {
     }
 }
 
+impl DeferredSaveDir {
+    /// Copies our inputs into our directory, replacing anything already there, and writes a
+    /// `run-with` script that repeats the link with `args`, which shouldn't include the program
+    /// name. Returns the directory.
+    pub(crate) fn save(&self, args: &[String]) -> Result<&Path> {
+        if self.dir.exists() {
+            std::fs::remove_dir_all(&self.dir)
+                .with_context(|| format!("Failed to delete `{}`", self.dir.display()))?;
+        }
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create directory `{}`", self.dir.display()))?;
+        let mut save_dir = SaveDir::with_dir(Some(self.dir.clone()));
+        // An input that doesn't exist may well be why the link failed, so there's nothing to copy,
+        // but the script still refers to it.
+        for path in self.paths.iter().filter(|path| Path::new(path).exists()) {
+            save_dir.handle_file(path)?;
+        }
+        let run_with_file = self.dir.join("run-with");
+        save_dir
+            .write_args_file(&run_with_file, args.iter().cloned())
+            .with_context(|| format!("Failed to write `{}`", run_with_file.display()))?;
+        Ok(&self.dir)
+    }
+}
+
 /// Return the full path to a new filename in `dir` that if possible has the same filename as that
 /// of `path`.
 fn unique_dest_path(dir: &Path, path: &Path) -> Option<PathBuf> {
@@ -2,10 +2,13 @@
 //! information about where each symbol can be obtained.
 
 use crate::args::Args;
+use crate::args::Emulation;
 use crate::error::Result;
 use crate::hash::PassThroughHashMap;
 use crate::hash::PreHashed;
 use crate::input_data::FileId;
+use crate::object_cache::CachedSymbols;
+use crate::output_section_id;
 use crate::output_section_id::OutputSectionId;
 use crate::parsing::InputObject;
 use crate::parsing::InternalInputObject;
@@ -15,7 +18,10 @@ use crate::sharding::Shard;
 use crate::sharding::ShardKey;
 use crate::symbol::SymbolName;
 use ahash::AHashMap;
+use anyhow::bail;
 use anyhow::Context;
+use object::read::elf::FileHeader as _;
+use object::LittleEndian;
 use object::Object;
 use object::ObjectSection;
 use object::ObjectSymbol;
@@ -27,6 +33,12 @@ use std::collections::hash_map;
 pub struct SymbolDb<'data> {
     pub(crate) args: &'data Args,
 
+    /// The architecture that we're linking for. Either from `-m` or from our first input object.
+    pub(crate) emulation: Emulation,
+
+    /// The `e_flags` for the output file header, merged from those of our input objects.
+    pub(crate) e_flags: u32,
+
     pub(crate) inputs: &'data [InputObject<'data>],
 
     /// Mapping from global symbol names to a symbol ID with that name. If there are multiple
@@ -50,6 +62,10 @@ pub struct SymbolDb<'data> {
     /// name.
     pub(crate) alternate_definitions: AHashMap<SymbolId, Vec<SymbolId>>,
 
+    /// Alignments for common symbols where some other common definition with the same name
+    /// requested a larger alignment than the selected definition. Keyed by the selected definition.
+    pub(crate) common_alignments: AHashMap<SymbolId, u64>,
+
     pub(crate) num_symbols_per_file: Vec<usize>,
 
     custom_sections_file_id: FileId,
@@ -57,6 +73,51 @@ pub struct SymbolDb<'data> {
     start_stop_symbol_names: Vec<SymbolName<'data>>,
 }
 
+/// Returns the emulation requested by `-m`, or if there was none, the one that matches our first
+/// input object. Checks that all input objects are for the selected architecture.
+fn select_emulation(inputs: &[InputObject], args: &Args) -> Result<Emulation> {
+    let mut emulation = args.emulation;
+    for input in inputs {
+        let InputObject::Object(obj) = input else {
+            continue;
+        };
+        let e_machine = obj.object.raw_header().e_machine(LittleEndian);
+        let Some(input_emulation) = Emulation::from_elf_machine(e_machine) else {
+            bail!("`{obj}` is for unsupported machine type {e_machine}");
+        };
+        let expected = *emulation.get_or_insert(input_emulation);
+        if input_emulation != expected {
+            bail!("`{obj}` is for {input_emulation}, but we're linking for {expected}");
+        }
+    }
+    Ok(emulation.unwrap_or_default())
+}
+
+/// Returns the `e_flags` for the output file. Only RISC-V uses these, to record things like which
+/// floating-point ABI the code uses. Shared objects are checked by the dynamic loader, so we only
+/// merge the flags of our regular input objects.
+fn merge_e_flags(inputs: &[InputObject], emulation: Emulation) -> Result<u32> {
+    if emulation != Emulation::RiscV64 {
+        return Ok(0);
+    }
+    let mut e_flags = None;
+    for input in inputs {
+        let InputObject::Object(obj) = input else {
+            continue;
+        };
+        if obj.is_dynamic {
+            continue;
+        }
+        let input_flags = obj.object.raw_header().e_flags(LittleEndian);
+        e_flags = Some(match e_flags {
+            None => input_flags,
+            Some(existing) => crate::riscv64::merge_e_flags(existing, input_flags)
+                .with_context(|| format!("Failed to merge e_flags of `{obj}`"))?,
+        });
+    }
+    Ok(e_flags.unwrap_or(0))
+}
+
 /// A global symbol that hasn't been put into our database yet.
 #[derive(Clone, Copy)]
 pub(crate) struct PendingSymbol<'data> {
@@ -69,12 +130,6 @@ pub(crate) struct PendingSymbol<'data> {
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(crate) struct SymbolId(u32);
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub(crate) struct ObjectSymDefInfo {
-    /// The index of the symbol within the symbol table of the object that defined it.
-    pub(crate) local_symbol_id: object::SymbolIndex,
-}
-
 struct SymbolLoadOutputs<'data> {
     pending_symbols: Vec<PendingSymbol<'data>>,
 }
@@ -82,6 +137,8 @@ struct SymbolLoadOutputs<'data> {
 impl<'data> SymbolDb<'data> {
     #[tracing::instrument(skip_all, name = "Build symbol DB")]
     pub fn build(inputs: &'data [InputObject], args: &'data Args) -> Result<Self> {
+        let emulation = select_emulation(inputs, args)?;
+        let e_flags = merge_e_flags(inputs, emulation)?;
         let num_symbols_per_file = inputs
             .iter()
             .map(|f| f.num_symbols())
@@ -97,7 +154,7 @@ impl<'data> SymbolDb<'data> {
             .iter()
             .enumerate()
             .flat_map(|(file_id, num_symbols)| {
-                std::iter::repeat(FileId::new(file_id as u32)).take(*num_symbols)
+                std::iter::repeat_n(FileId::new(file_id as u32), *num_symbols)
             })
             .collect();
 
@@ -109,8 +166,11 @@ impl<'data> SymbolDb<'data> {
         ));
         let mut index = SymbolDb {
             args,
+            emulation,
+            e_flags,
             global_names: Default::default(),
             alternate_definitions: AHashMap::new(),
+            common_alignments: AHashMap::new(),
             custom_sections_file_id,
             symbol_files,
             symbol_definitions,
@@ -175,14 +235,14 @@ impl<'data> SymbolDb<'data> {
     }
 
     /// Returns a struct that can be used to print debug information about the specified symbol.
-    pub(crate) fn symbol_debug(&self, symbol_id: SymbolId) -> SymbolDebug {
+    pub(crate) fn symbol_debug(&self, symbol_id: SymbolId) -> SymbolDebug<'_, '_> {
         SymbolDebug {
             db: self,
             symbol_id,
         }
     }
 
-    pub(crate) fn symbol_name(&self, symbol_id: SymbolId) -> Result<SymbolName> {
+    pub(crate) fn symbol_name(&self, symbol_id: SymbolId) -> Result<SymbolName<'_>> {
         let file_id = self.file_id_for_symbol(symbol_id);
         let input_object = &self.inputs[file_id.as_usize()];
         match input_object {
@@ -194,6 +254,16 @@ impl<'data> SymbolDb<'data> {
         }
     }
 
+    /// Returns whether `symbol_id` is a data object defined by a shared object. An executable that
+    /// isn't position-independent can only refer to such an object directly by copying it.
+    pub(crate) fn is_dynamic_data_object(&self, symbol_id: SymbolId) -> Result<bool> {
+        let file_id = self.file_id_for_symbol(symbol_id);
+        let Some(InputObject::Object(object)) = self.inputs.get(file_id.as_usize()) else {
+            return Ok(false);
+        };
+        Ok(object.is_dynamic && object.symbol(symbol_id)?.kind() == object::SymbolKind::Data)
+    }
+
     pub(crate) fn symbol_value_kind(&self, symbol_id: SymbolId) -> ValueKind {
         self.symbol_value_kinds[symbol_id.as_usize()]
     }
@@ -268,9 +338,12 @@ fn load_symbols_from_file<'data>(
 ) -> Result<SymbolLoadOutputs<'data>> {
     Ok(match reader {
         InputObject::Object(s) => {
-            if s.is_dynamic {
+            if let Some(cached) = s.cached_symbols {
+                load_cached_symbols(cached, s.object.data(), resolutions, value_kinds)
+            } else if s.is_dynamic {
                 load_symbols(
                     s.object.dynamic_symbols(),
+                    crate::elf::version_table(&s.object)?.as_ref(),
                     resolutions,
                     value_kinds,
                     |_sym| ValueKind::Dynamic,
@@ -278,6 +351,7 @@ fn load_symbols_from_file<'data>(
             } else {
                 load_symbols(
                     s.object.symbols(),
+                    None,
                     resolutions,
                     value_kinds,
                     |sym| match sym.section() {
@@ -297,6 +371,7 @@ fn load_symbols_from_file<'data>(
 
 fn load_symbols<'data>(
     symbols: crate::elf::SymbolIterator<'data, '_>,
+    versions: Option<&crate::elf::VersionTable>,
     resolutions: &mut Shard<'_, SymbolId, SymbolId>,
     value_kinds: &mut Shard<'_, SymbolId, ValueKind>,
     compute_value_kind: impl Fn(&crate::elf::Symbol) -> ValueKind,
@@ -315,6 +390,15 @@ fn load_symbols<'data>(
         if symbol.is_local() {
             continue;
         }
+        // A shared object's older versions of a symbol, e.g. `foo@VERS_1` alongside `foo@@VERS_2`,
+        // only satisfy references that ask for them by version, which we don't support.
+        if versions.is_some_and(|versions| {
+            versions
+                .version_index(LittleEndian, symbol.index().0)
+                .is_hidden()
+        }) {
+            continue;
+        }
         let name = symbol.name_bytes()?;
         let pending = PendingSymbol::new(symbol_id, name);
         pending_symbols.push(pending);
@@ -322,6 +406,32 @@ fn load_symbols<'data>(
     Ok(SymbolLoadOutputs { pending_symbols })
 }
 
+/// Like `load_symbols`, but using symbols from the object cache, so we don't need to iterate
+/// through the object's symbol table or hash names.
+fn load_cached_symbols<'data>(
+    cached: &CachedSymbols,
+    data: &'data [u8],
+    resolutions: &mut Shard<'_, SymbolId, SymbolId>,
+    value_kinds: &mut Shard<'_, SymbolId, ValueKind>,
+) -> SymbolLoadOutputs<'data> {
+    let mut pending_symbols = Vec::new();
+    for symbol in &cached.definitions {
+        let symbol_id = resolutions.start_key.add_usize(symbol.index as usize);
+        resolutions[symbol_id] = symbol_id;
+        value_kinds[symbol_id] = symbol.value_kind;
+        if symbol.is_local {
+            continue;
+        }
+        let start = symbol.name_offset as usize;
+        let name = &data[start..start + symbol.name_len as usize];
+        pending_symbols.push(PendingSymbol {
+            symbol_id,
+            name: PreHashed::new(SymbolName::new(name), symbol.name_hash),
+        });
+    }
+    SymbolLoadOutputs { pending_symbols }
+}
+
 #[derive(Clone, Copy)]
 pub(crate) struct SymbolDebug<'db, 'data> {
     db: &'db SymbolDb<'data>,
@@ -413,12 +523,12 @@ impl TryFrom<usize> for SymbolId {
     }
 }
 
-impl InternalInputObject {
+impl<'data> InternalInputObject<'data> {
     fn load_symbols(
         &self,
         resolutions: &mut Shard<SymbolId, SymbolId>,
         value_kinds: &mut Shard<SymbolId, ValueKind>,
-    ) -> Result<SymbolLoadOutputs<'static>> {
+    ) -> Result<SymbolLoadOutputs<'data>> {
         let mut pending_symbols = Vec::with_capacity(self.symbol_definitions.len());
         for ((definition, (symbol_id, resolution)), value_kind) in self
             .symbol_definitions
@@ -431,17 +541,17 @@ impl InternalInputObject {
                 InternalSymDefInfo::Undefined => {
                     *value_kind = ValueKind::Absolute;
                 }
-                InternalSymDefInfo::SectionStart(section_id) => {
-                    let def = section_id.built_in_details();
-                    let name = def.start_symbol_name.unwrap().as_bytes();
+                InternalSymDefInfo::SectionStart(_)
+                | InternalSymDefInfo::SectionEnd(_)
+                | InternalSymDefInfo::GlobalPointer => {
+                    let name = self.symbol_name(symbol_id).bytes();
                     pending_symbols.push(PendingSymbol::new(symbol_id, name));
                     *value_kind = ValueKind::Address;
                 }
-                InternalSymDefInfo::SectionEnd(section_id) => {
-                    let def = section_id.built_in_details();
-                    let name = def.end_symbol_name.unwrap().as_bytes();
+                InternalSymDefInfo::Defsym(_) => {
+                    let name = self.symbol_name(symbol_id).bytes();
                     pending_symbols.push(PendingSymbol::new(symbol_id, name));
-                    *value_kind = ValueKind::Address;
+                    *value_kind = ValueKind::Absolute;
                 }
             }
         }
@@ -461,6 +571,8 @@ impl InternalSymDefInfo {
             InternalSymDefInfo::Undefined => None,
             InternalSymDefInfo::SectionStart(i) => Some(i),
             InternalSymDefInfo::SectionEnd(i) => Some(i),
+            InternalSymDefInfo::Defsym(_) => None,
+            InternalSymDefInfo::GlobalPointer => Some(output_section_id::DATA),
         }
     }
 }
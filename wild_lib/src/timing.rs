@@ -105,7 +105,9 @@ pub fn init_tracing(args: &crate::args::Args) {
     if args.time_phases {
         let layer = TimingLayer::default();
         let subscriber = tracing_subscriber::Registry::default().with(layer);
-        tracing::subscriber::set_global_default(subscriber).unwrap();
+        // If we're embedded in a process that has already set a subscriber, then we leave that in
+        // place and don't report timings.
+        let _ = tracing::subscriber::set_global_default(subscriber);
     }
 }
 
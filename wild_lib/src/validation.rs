@@ -1,9 +1,15 @@
 //! Code to double-check that we did certain things correctly. Generally only used in debug builds.
 
+use crate::alignment::Alignment;
+use crate::args::Emulation;
+use crate::elf::SegmentType;
 use crate::error::Result;
+use crate::layout::IfuncRelocation;
 use crate::layout::Layout;
 use crate::layout::ResolutionValue;
 use crate::layout::TargetResolutionKind;
+use crate::symbol::SymbolName;
+use ahash::AHashMap;
 use anyhow::bail;
 use anyhow::Context;
 use object::Object;
@@ -11,26 +17,76 @@ use object::ObjectSection;
 
 pub(crate) fn validate_bytes(layout: &Layout, file_bytes: &[u8]) -> Result {
     let object = crate::elf::File::parse(file_bytes).context("Failed to parse our output file")?;
-    validate_object(&object, layout).context("Output validation failed")
+    validate_object(&object, layout).context("Output validation failed")?;
+    validate_ifuncs(&object, layout).context("Output ifunc validation failed")?;
+    validate_segments(&object, layout).context("Output segment validation failed")
+}
+
+/// Checks that two links of the same inputs produced byte-identical outputs. If they didn't, reports
+/// the offset of the first difference and the section of the first output that contains it.
+pub(crate) fn check_reproducible(first: &[u8], second: &[u8]) -> Result {
+    let Some(offset) = first
+        .iter()
+        .zip(second)
+        .position(|(a, b)| a != b)
+        .or_else(|| (first.len() != second.len()).then_some(first.len().min(second.len())))
+    else {
+        return Ok(());
+    };
+    let location = match section_containing_offset(first, offset as u64) {
+        Some(name) => format!("in section `{name}`"),
+        None => "outside of any section".to_owned(),
+    };
+    bail!(
+        "Output isn't reproducible. Outputs of {} and {} bytes first differ at offset 0x{offset:x}, \
+         {location}",
+        first.len(),
+        second.len()
+    );
+}
+
+fn section_containing_offset(file_bytes: &[u8], offset: u64) -> Option<String> {
+    let object = crate::elf::File::parse(file_bytes).ok()?;
+    let section = object.sections().find(|section| {
+        section
+            .file_range()
+            .is_some_and(|(start, size)| (start..start + size).contains(&offset))
+    })?;
+    section.name().ok().map(str::to_owned)
 }
 
 /// Checks that what we actually wrote to our output file matches what we intended to write in
 /// `layout`.
 fn validate_object(object: &crate::elf::File, layout: &Layout) -> Result {
-    if layout.args().is_relocatable() {
-        // For now, we don't do any validation of relocatable outputs. The only thing we're
-        // currently validating is GOT entries and they'll all have dynamic relocations.
-        return Ok(());
-    }
     let got = object
         .section_by_name(".got")
         .context("Missing .got from output file")?;
-    let got_data = got.data()?;
+    let got = Got {
+        address: got.address(),
+        data: got.data()?,
+    };
+    // For dynamic outputs, GOT entries that refer to symbols defined elsewhere, and for relocatable
+    // outputs also those that hold addresses, are filled in by the dynamic loader, so we check the
+    // dynamic relocations instead.
+    let is_relocatable = layout.args().is_relocatable();
+    let emulation = layout.emulation();
+    let relocations = if layout.args().needs_dynamic() {
+        Some(dynamic_relocations(object)?)
+    } else {
+        None
+    };
     for (symbol_name, symbol_id) in &layout.symbol_db.global_names {
         match layout.symbol_resolution(*symbol_id) {
             None => {}
             Some(resolution) => {
-                validate_resolution(symbol_name.bytes(), resolution, &got, got_data)?;
+                validate_resolution(
+                    symbol_name.bytes(),
+                    resolution,
+                    &got,
+                    relocations.as_ref(),
+                    is_relocatable,
+                    emulation,
+                )?;
             }
         }
     }
@@ -40,7 +96,14 @@ fn validate_object(object: &crate::elf::File, layout: &Layout) -> Result {
             crate::layout::FileLayout::Object(obj) => {
                 for sec in obj.object.sections() {
                     if let Some(resolution) = obj.section_resolutions[sec.index().0] {
-                        validate_resolution(sec.name_bytes()?, &resolution, &got, got_data)?;
+                        validate_resolution(
+                            sec.name_bytes()?,
+                            &resolution,
+                            &got,
+                            relocations.as_ref(),
+                            is_relocatable,
+                            emulation,
+                        )?;
                     }
                 }
             }
@@ -52,30 +115,233 @@ fn validate_object(object: &crate::elf::File, layout: &Layout) -> Result {
     Ok(())
 }
 
+struct Got<'data> {
+    address: u64,
+    data: &'data [u8],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DynamicRelocation {
+    r_type: u32,
+    symbol: u32,
+    addend: i64,
+}
+
+/// Returns the relocations from `.rela.dyn` and `.rela.plt`, keyed by the address that they apply
+/// to.
+fn dynamic_relocations(object: &crate::elf::File) -> Result<AHashMap<u64, DynamicRelocation>> {
+    let mut relocations = AHashMap::new();
+    for name in [".rela.dyn", ".rela.plt"] {
+        relocations.extend(section_relocations(object, name)?);
+    }
+    Ok(relocations)
+}
+
+/// Returns the relocations in the section `name` together with the address that each applies to,
+/// in the order in which they appear. Returns an empty list if the section isn't present.
+fn section_relocations(
+    object: &crate::elf::File,
+    name: &str,
+) -> Result<Vec<(u64, DynamicRelocation)>> {
+    let e = object::LittleEndian;
+    let Some(section) = object.section_by_name(name) else {
+        return Ok(Vec::new());
+    };
+    let data = section.data()?;
+    let (entries, rest) = object::pod::slice_from_bytes::<crate::elf::Rela>(
+        data,
+        data.len() / core::mem::size_of::<crate::elf::Rela>(),
+    )
+    .map_err(|_| anyhow::anyhow!("Failed to read {name}"))?;
+    if !rest.is_empty() {
+        bail!("{name} has invalid size");
+    }
+    Ok(entries
+        .iter()
+        .map(|rela| {
+            (
+                rela.r_offset.get(e),
+                DynamicRelocation {
+                    r_type: rela.r_type(e, false),
+                    symbol: rela.r_sym(e, false),
+                    addend: rela.r_addend.get(e),
+                },
+            )
+        })
+        .collect())
+}
+
+/// Checks that each ifunc has an IRELATIVE relocation in `.rela.plt` that applies to its
+/// GOT entry and has the address of the resolver as its addend. The loader applies `.rela.plt`
+/// after `.rela.dyn`, so keeping IRELATIVE relocations out of `.rela.dyn` ensures that resolvers
+/// only run once everything that they might depend on has been relocated. For static executables,
+/// the startup code finds these relocations via `__rela_iplt_start` and `__rela_iplt_end`, so we
+/// check that those symbols bracket `.rela.plt`.
+fn validate_ifuncs(object: &crate::elf::File, layout: &Layout) -> Result {
+    let ifuncs: Vec<&IfuncRelocation> = layout
+        .file_layouts
+        .iter()
+        .filter_map(|file| match file {
+            crate::layout::FileLayout::Object(obj) => Some(obj.plt_relocations.iter()),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    let irelative = layout.emulation().irelative_relocation();
+    let relative = layout.emulation().relative_relocation();
+    let rela_dyn = section_relocations(object, ".rela.dyn")?;
+    if let Some((address, _)) = rela_dyn.iter().find(|(_, rel)| rel.r_type == irelative) {
+        bail!("IRELATIVE relocation for 0x{address:x} is in .rela.dyn rather than .rela.plt");
+    }
+    let rela_plt = section_relocations(object, ".rela.plt")?;
+    if rela_plt.len() != ifuncs.len() {
+        bail!(
+            ".rela.plt has {} entries, but we have {} ifuncs",
+            rela_plt.len(),
+            ifuncs.len()
+        );
+    }
+    if let Some((address, rel)) = rela_plt.iter().find(|(_, rel)| rel.r_type != irelative) {
+        bail!("Non-IRELATIVE relocation {rel:?} for 0x{address:x} in .rela.plt");
+    }
+    if layout.args().is_relocatable() {
+        // The addresses in our .rela.plt entries aren't known until load time, so the entries are
+        // themselves filled in by relative relocations.
+        let relative_by_address = rela_dyn.into_iter().collect::<AHashMap<_, _>>();
+        for ifunc in &ifuncs {
+            for (field_offset, expected) in [
+                (crate::elf::RELA_ADDRESS_OFFSET, ifunc.got_address),
+                (crate::elf::RELA_ADDEND_OFFSET, ifunc.resolver),
+            ] {
+                let place = ifunc.relocation_address + field_offset as u64;
+                let actual = relative_by_address.get(&place);
+                if !actual
+                    .is_some_and(|rel| rel.r_type == relative && rel.addend == expected as i64)
+                {
+                    bail!(
+                        "Expected relative relocation to 0x{expected:x} at 0x{place:x} for \
+                         ifunc with GOT entry at 0x{:x}, but found {actual:?}",
+                        ifunc.got_address
+                    );
+                }
+            }
+        }
+    } else {
+        let by_address = rela_plt.iter().copied().collect::<AHashMap<_, _>>();
+        for ifunc in &ifuncs {
+            let actual = by_address.get(&ifunc.got_address);
+            if actual.is_none_or(|rel| rel.addend != ifunc.resolver as i64) {
+                bail!(
+                    "Ifunc with GOT entry at 0x{:x} needs IRELATIVE relocation with resolver \
+                     0x{:x}, but found {actual:?}",
+                    ifunc.got_address,
+                    ifunc.resolver
+                );
+            }
+        }
+    }
+    if !layout.args().needs_dynamic() {
+        validate_iplt_symbols(object, layout)?;
+    }
+    Ok(())
+}
+
+/// Checks that `__rela_iplt_start` and `__rela_iplt_end`, if referenced, are the start and end of
+/// `.rela.plt`, or are equal if there's no `.rela.plt`.
+fn validate_iplt_symbols(object: &crate::elf::File, layout: &Layout) -> Result {
+    let symbol_address = |name: &str| -> Result<Option<u64>> {
+        layout
+            .symbol_db
+            .global_names
+            .get(&SymbolName::prehashed(name.as_bytes()))
+            .and_then(|symbol_id| layout.symbol_resolution(*symbol_id))
+            .map(|resolution| resolution.value.address_or_value())
+            .transpose()
+    };
+    let start = symbol_address("__rela_iplt_start")?;
+    let end = symbol_address("__rela_iplt_end")?;
+    let Some(rela_plt) = object.section_by_name(".rela.plt") else {
+        if let (Some(start), Some(end)) = (start, end) {
+            if start != end {
+                bail!(
+                    "There's no .rela.plt, but `__rela_iplt_start` (0x{start:x}) and \
+                     `__rela_iplt_end` (0x{end:x}) differ"
+                );
+            }
+        }
+        return Ok(());
+    };
+    for (name, actual, expected) in [
+        ("__rela_iplt_start", start, rela_plt.address()),
+        ("__rela_iplt_end", end, rela_plt.address() + rela_plt.size()),
+    ] {
+        if let Some(actual) = actual {
+            if actual != expected {
+                bail!("`{name}` is 0x{actual:x}, but should be 0x{expected:x}");
+            }
+        }
+    }
+    Ok(())
+}
+
 fn validate_resolution(
     name: &[u8],
     resolution: &crate::layout::Resolution,
-    got: &crate::elf::Section,
-    got_data: &[u8],
+    got: &Got,
+    relocations: Option<&AHashMap<u64, DynamicRelocation>>,
+    is_relocatable: bool,
+    emulation: Emulation,
 ) -> Result {
     let res_kind = resolution.kind;
     if matches!(
         res_kind,
-        TargetResolutionKind::IFunc | TargetResolutionKind::GotTlsOffset
+        TargetResolutionKind::IFunc
+            | TargetResolutionKind::GotTlsOffset
+            | TargetResolutionKind::GotTlsDouble
     ) {
         return Ok(());
     };
     if let Some(got_address) = resolution.got_address {
-        let start_offset = (got_address.get() - got.address()) as usize;
+        let start_offset = (got_address.get() - got.address) as usize;
         let end_offset = start_offset + core::mem::size_of::<u64>();
-        if end_offset > got_data.len() {
+        if end_offset > got.data.len() {
             bail!("GOT offset beyond end of GOT 0x{end_offset}");
         }
+        if let Some(relocations) = relocations {
+            let expected = match resolution.value {
+                ResolutionValue::Absolute(_) => None,
+                ResolutionValue::Address(v) if is_relocatable => {
+                    Some((emulation.relative_relocation(), 0, v as i64))
+                }
+                ResolutionValue::Address(_) => None,
+                ResolutionValue::Dynamic(symbol) => {
+                    Some((emulation.glob_dat_relocation(), symbol, 0))
+                }
+            };
+            if let Some((r_type, symbol, addend)) = expected {
+                let actual = relocations.get(&got_address.get());
+                let matches = actual.is_some_and(|rel| {
+                    let type_matches = rel.r_type == r_type
+                        || (r_type == emulation.glob_dat_relocation()
+                            && rel.r_type == emulation.absolute_relocation());
+                    type_matches && rel.symbol == symbol && rel.addend == addend
+                });
+                if !matches {
+                    let name = String::from_utf8_lossy(name);
+                    bail!(
+                        "res={res_kind:?} `{name}` needs dynamic relocation type {r_type} \
+                         symbol={symbol} addend=0x{addend:x} for GOT entry at 0x{got_address:x}, \
+                         but found {actual:?}"
+                    );
+                }
+                return Ok(());
+            }
+        }
         let expected = match resolution.value {
             ResolutionValue::Absolute(v) | ResolutionValue::Address(v) => v,
             ResolutionValue::Dynamic(_) => return Ok(()),
         };
-        let address = bytemuck::pod_read_unaligned(&got_data[start_offset..end_offset]);
+        let address = bytemuck::pod_read_unaligned(&got.data[start_offset..end_offset]);
         if expected != address {
             let name = String::from_utf8_lossy(name);
             bail!(
@@ -86,3 +352,326 @@ fn validate_resolution(
     }
     Ok(())
 }
+
+#[derive(Debug)]
+struct SectionInfo {
+    name: String,
+    address: u64,
+    size: u64,
+    /// The file offset of the section, or None if the section occupies no space in the file.
+    file_offset: Option<u64>,
+    is_tls: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SegmentInfo {
+    segment_type: u32,
+    offset: u64,
+    vaddr: u64,
+    file_size: u64,
+    mem_size: u64,
+    alignment: u64,
+}
+
+impl SegmentInfo {
+    fn contains_address_range(&self, address: u64, size: u64) -> bool {
+        address >= self.vaddr && address + size <= self.vaddr + self.mem_size
+    }
+}
+
+/// Checks that every allocated section is covered by exactly one `PT_LOAD` segment whose file and
+/// memory mappings agree with those of the section and that TLS sections are covered by `PT_TLS`.
+fn validate_segments(object: &crate::elf::File, layout: &Layout) -> Result {
+    // With `--no-check-sections`, we were asked to permit overlapping sections, which can leave a
+    // section in more than one segment.
+    if !layout.args().check_sections {
+        return Ok(());
+    }
+    let e = object::LittleEndian;
+    let segments: Vec<SegmentInfo> = object
+        .raw_segments()
+        .iter()
+        .map(|header| SegmentInfo {
+            segment_type: header.p_type.get(e),
+            offset: header.p_offset.get(e),
+            vaddr: header.p_vaddr.get(e),
+            file_size: header.p_filesz.get(e),
+            mem_size: header.p_memsz.get(e),
+            alignment: header.p_align.get(e),
+        })
+        .collect();
+    let mut sections = Vec::new();
+    for section in object.sections() {
+        let object::SectionFlags::Elf { sh_flags } = section.flags() else {
+            continue;
+        };
+        if sh_flags & crate::elf::shf::ALLOC == 0 {
+            continue;
+        }
+        sections.push(SectionInfo {
+            name: String::from_utf8_lossy(section.name_bytes()?).into_owned(),
+            address: section.address(),
+            size: section.size(),
+            file_offset: section.file_range().map(|(offset, _)| offset),
+            is_tls: sh_flags & crate::elf::shf::TLS != 0,
+        });
+    }
+    check_segments(&sections, &segments, layout.args().max_page_size)
+}
+
+fn check_segments(
+    sections: &[SectionInfo],
+    segments: &[SegmentInfo],
+    page_size: Alignment,
+) -> Result {
+    for (index, segment) in segments.iter().enumerate() {
+        if segment.file_size > segment.mem_size {
+            bail!(
+                "Segment {index} has file size 0x{:x} larger than memory size 0x{:x}",
+                segment.file_size,
+                segment.mem_size
+            );
+        }
+        if segment.segment_type == SegmentType::Load as u32 {
+            if segment.alignment < page_size.value() {
+                bail!(
+                    "Segment {index} has alignment 0x{:x}, which is less than the page size 0x{:x}",
+                    segment.alignment,
+                    page_size.value()
+                );
+            }
+            if segment.vaddr.wrapping_sub(segment.offset) % segment.alignment != 0 {
+                bail!(
+                    "Segment {index} has address 0x{:x} and offset 0x{:x}, which aren't \
+                     congruent modulo its alignment 0x{:x}",
+                    segment.vaddr,
+                    segment.offset,
+                    segment.alignment
+                );
+            }
+        }
+    }
+    for section in sections {
+        // Empty sections can legitimately sit at the boundary between segments.
+        if section.size == 0 {
+            continue;
+        }
+        let name = &section.name;
+        if section.is_tls {
+            let tls = segments
+                .iter()
+                .position(|s| s.segment_type == SegmentType::Tls as u32)
+                .with_context(|| format!("TLS section `{name}` present, but no PT_TLS segment"))?;
+            if !segments[tls].contains_address_range(section.address, section.size) {
+                bail!("TLS section `{name}` isn't contained within PT_TLS segment {tls}");
+            }
+            if section.file_offset.is_none() {
+                // .tbss doesn't take up space in the load segments. Each thread gets its own copy.
+                continue;
+            }
+        }
+        let mut containing = segments.iter().enumerate().filter(|(_, s)| {
+            s.segment_type == SegmentType::Load as u32
+                && s.contains_address_range(section.address, section.size)
+        });
+        let Some((index, segment)) = containing.next() else {
+            bail!(
+                "Section `{name}` at 0x{:x} isn't contained within any PT_LOAD segment",
+                section.address
+            );
+        };
+        if let Some((other, _)) = containing.next() {
+            bail!("Section `{name}` is contained within both segment {index} and segment {other}");
+        }
+        if let Some(file_offset) = section.file_offset {
+            if file_offset < segment.offset
+                || file_offset + section.size > segment.offset + segment.file_size
+            {
+                bail!(
+                    "Section `{name}` at file offset 0x{file_offset:x} isn't within the file \
+                     range of segment {index}"
+                );
+            }
+            if file_offset - segment.offset != section.address - segment.vaddr {
+                bail!(
+                    "Section `{name}` has address 0x{:x} and file offset 0x{file_offset:x}, \
+                     which don't match the mapping of segment {index}",
+                    section.address
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::num::NonZeroU64;
+
+    const GOT_ADDRESS: u64 = 0x2000;
+
+    fn check(value: ResolutionValue, relocation: Option<DynamicRelocation>) -> Result {
+        let resolution = crate::layout::Resolution {
+            value,
+            got_address: NonZeroU64::new(GOT_ADDRESS),
+            plt_address: None,
+            kind: TargetResolutionKind::Got,
+        };
+        let got = Got {
+            address: GOT_ADDRESS,
+            data: &[0; 8],
+        };
+        let relocations = relocation
+            .into_iter()
+            .map(|rel| (GOT_ADDRESS, rel))
+            .collect();
+        validate_resolution(
+            b"foo",
+            &resolution,
+            &got,
+            Some(&relocations),
+            true,
+            Emulation::X86_64,
+        )
+    }
+
+    #[test]
+    fn test_relative_relocation() {
+        let relative = DynamicRelocation {
+            r_type: object::elf::R_X86_64_RELATIVE,
+            symbol: 0,
+            addend: 0x1234,
+        };
+        let value = ResolutionValue::Address(0x1234);
+        assert!(check(value, Some(relative)).is_ok());
+        assert!(check(value, None).is_err());
+        let corrupted = DynamicRelocation {
+            addend: 0x1238,
+            ..relative
+        };
+        assert!(check(value, Some(corrupted)).is_err());
+    }
+
+    #[test]
+    fn test_symbolic_relocation() {
+        let glob_dat = DynamicRelocation {
+            r_type: object::elf::R_X86_64_GLOB_DAT,
+            symbol: 3,
+            addend: 0,
+        };
+        let value = ResolutionValue::Dynamic(3);
+        assert!(check(value, Some(glob_dat)).is_ok());
+        let wrong_symbol = DynamicRelocation {
+            symbol: 4,
+            ..glob_dat
+        };
+        assert!(check(value, Some(wrong_symbol)).is_err());
+        let relative = DynamicRelocation {
+            r_type: object::elf::R_X86_64_RELATIVE,
+            ..glob_dat
+        };
+        assert!(check(value, Some(relative)).is_err());
+    }
+
+    #[test]
+    fn test_check_reproducible() {
+        // Our test binary is an ELF file, so it makes a convenient output to corrupt.
+        let bytes = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        assert!(check_reproducible(&bytes, &bytes.clone()).is_ok());
+
+        let object = crate::elf::File::parse(bytes.as_slice()).unwrap();
+        let (text_offset, _) = object
+            .section_by_name(".text")
+            .and_then(|section| section.file_range())
+            .unwrap();
+        let mut nondeterministic = bytes.clone();
+        nondeterministic[text_offset as usize + 1] ^= 0xff;
+        let message = check_reproducible(&bytes, &nondeterministic)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            message.contains(&format!("0x{:x}", text_offset + 1)) && message.contains("`.text`"),
+            "{message}"
+        );
+
+        let message = check_reproducible(&bytes, &bytes[..bytes.len() - 1])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            message.contains(&format!("0x{:x}", bytes.len() - 1)),
+            "{message}"
+        );
+    }
+
+    fn load_segment(offset: u64, vaddr: u64, file_size: u64, mem_size: u64) -> SegmentInfo {
+        SegmentInfo {
+            segment_type: SegmentType::Load as u32,
+            offset,
+            vaddr,
+            file_size,
+            mem_size,
+            alignment: 0x1000,
+        }
+    }
+
+    fn section(name: &str, address: u64, size: u64, file_offset: Option<u64>) -> SectionInfo {
+        SectionInfo {
+            name: name.to_owned(),
+            address,
+            size,
+            file_offset,
+            is_tls: false,
+        }
+    }
+
+    #[test]
+    fn test_check_segments() {
+        let sections = [
+            section(".text", 0x401000, 0x100, Some(0x1000)),
+            section(".data", 0x402000, 0x20, Some(0x2000)),
+            section(".bss", 0x402020, 0x40, None),
+        ];
+        let segments = [
+            load_segment(0x1000, 0x401000, 0x100, 0x100),
+            load_segment(0x2000, 0x402000, 0x20, 0x60),
+        ];
+        check_segments(&sections, &segments, crate::alignment::PAGE).unwrap();
+
+        // Segment is too small in memory to hold .bss.
+        let mut bad_segments = segments;
+        bad_segments[1].mem_size = 0x40;
+        let error = check_segments(&sections, &bad_segments, crate::alignment::PAGE).unwrap_err();
+        assert!(error.to_string().contains("`.bss`"), "{error}");
+
+        // Segment doesn't have enough file bytes for .data.
+        let mut bad_segments = segments;
+        bad_segments[1].file_size = 0x10;
+        let error = check_segments(&sections, &bad_segments, crate::alignment::PAGE).unwrap_err();
+        assert!(error.to_string().contains("`.data`"), "{error}");
+        assert!(error.to_string().contains("segment 1"), "{error}");
+
+        // Segment address and offset are inconsistent.
+        let mut bad_segments = segments;
+        bad_segments[0].offset = 0x800;
+        bad_segments[0].file_size = 0x900;
+        assert!(check_segments(&sections, &bad_segments, crate::alignment::PAGE).is_err());
+    }
+
+    #[test]
+    fn test_check_tls_segment() {
+        let mut tbss = section(".tbss", 0x402000, 0x8, None);
+        tbss.is_tls = true;
+        let segments = [load_segment(0x2000, 0x402000, 0x0, 0x0)];
+        assert!(check_segments(&[tbss], &segments, crate::alignment::PAGE).is_err());
+
+        let mut tbss = section(".tbss", 0x402000, 0x8, None);
+        tbss.is_tls = true;
+        let tls = SegmentInfo {
+            segment_type: SegmentType::Tls as u32,
+            alignment: 8,
+            ..load_segment(0x2000, 0x402000, 0, 8)
+        };
+        check_segments(&[tbss], &[segments[0], tls], crate::alignment::PAGE).unwrap();
+    }
+}